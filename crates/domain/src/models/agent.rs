@@ -1,21 +1,41 @@
 use std::{
     collections::HashMap,
     fmt::{Debug, Display},
-    io::Write,
+    io::{Read, Write},
+    path::PathBuf,
+    pin::Pin,
     sync::Arc,
+    time::Duration,
 };
 
 use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::Mutex;
 
-use super::tools::Tool;
+use super::{
+    attachment::Attachment,
+    conversation::{ConversationLock, ConversationStore},
+    events::{AgentEvent, AgentObserver, Observers},
+    health::{ProviderHealth, RateLimitStatus},
+    mode::{Mode, ModeConfig, ToolAllowlists, render_prompt},
+    project_context::ProjectContext,
+    tool_registry::{ToolMetadata, ToolRegistry},
+    tools::{Tool, ToolContext, ToolDecorators, ToolFilter, ToolOutput},
+    turn::{MAX_STEPS, ToolLimits},
+    usage::TokenUsage,
+};
 
-#[derive(Debug)]
+// Who a message comes from. Each client maps these to the roles of its own
+// wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AgentRole {
     User,
     Model,
+    System,
+    Tool,
 }
 
 impl Display for AgentRole {
@@ -23,26 +43,190 @@ impl Display for AgentRole {
         match self {
             AgentRole::User => write!(f, "user"),
             AgentRole::Model => write!(f, "model"),
+            AgentRole::System => write!(f, "system"),
+            AgentRole::Tool => write!(f, "tool"),
         }
     }
 }
 
+// Also takes the names other providers use, so stored conversations map back.
+impl From<&str> for AgentRole {
+    fn from(role: &str) -> Self {
+        match role {
+            "model" | "assistant" => AgentRole::Model,
+            "system" => AgentRole::System,
+            "tool" | "function" => AgentRole::Tool,
+            _ => AgentRole::User,
+        }
+    }
+}
+
+pub type PartStream = Pin<Box<dyn Stream<Item = Result<Part, AgentError>> + Send>>;
+
 #[async_trait]
 pub trait AgentClient: Debug + Send + Sync + 'static {
     async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError>;
+
+    // Yields the response parts as they arrive. Text arrives in several
+    // parts that together make up the answer. Clients without streaming
+    // support yield the parts of the full response.
+    async fn ask_stream(&self, prompt: &str) -> Result<PartStream, AgentError> {
+        let parts = self
+            .ask(prompt)
+            .await?
+            .into_iter()
+            .flat_map(|content| content.parts)
+            .map(Ok)
+            .collect::<Vec<Result<Part, AgentError>>>();
+
+        Ok(Box::pin(futures::stream::iter(parts)))
+    }
     async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError>;
     async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError>;
+
+    // Hands the output of a tool the model called back to it. Clients without
+    // a dedicated message for tool results send it with the tool role.
+    async fn add_tool_result(&self, _name: &str, output: &ToolOutput) -> Result<(), AgentError> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        self.add_system_prompt(&output.to_json().to_string(), AgentRole::Tool)
+            .await
+    }
+
+    async fn health(&self) -> Option<ProviderHealth> {
+        None
+    }
+
+    // The model requests are sent to, None when the endpoint picks it.
+    fn model(&self) -> Option<String> {
+        None
+    }
+
+    // The URL requests are sent to, None for models running in process.
+    fn endpoint(&self) -> Option<String> {
+        None
+    }
+
+    async fn rate_limit(&self) -> Option<RateLimitStatus> {
+        None
+    }
+
+    // The tokens the provider reported so far, None when it doesn't report
+    // them.
+    fn usage(&self) -> Option<TokenUsage> {
+        None
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        None
+    }
+
+    async fn set_system_prompt(&self, _prompt: SystemPrompt) -> Result<(), AgentError> {
+        Err(AgentError::AgentError(Some(
+            "This client does not support replacing the system prompt".to_string(),
+        )))
+    }
+
+    // Limits the tools advertised to the model from the next request on. The
+    // tools stay registered so a wider filter brings them back.
+    async fn set_tool_filter(&self, _filter: ToolFilter) -> Result<(), AgentError> {
+        Err(AgentError::AgentError(Some(
+            "This client does not support filtering tools".to_string(),
+        )))
+    }
+
+    // Overrides the sampling temperature from the next request on, None goes
+    // back to the one the client was built with.
+    async fn set_temperature(&self, _temperature: Option<f32>) -> Result<(), AgentError> {
+        Err(AgentError::AgentError(Some(
+            "This client does not support changing the temperature".to_string(),
+        )))
+    }
+
+    // Replaces the decorators that add hints to the tool descriptions sent with
+    // each request.
+    async fn set_tool_decorators(&self, _decorators: ToolDecorators) -> Result<(), AgentError> {
+        Err(AgentError::AgentError(Some(
+            "This client does not support decorating tool descriptions".to_string(),
+        )))
+    }
+
+    // Rough number of input tokens the next request would send for `prompt`,
+    // including the history. None when the client can't tell.
+    async fn estimate_tokens(&self, _prompt: &str) -> Option<u64> {
+        None
+    }
+
+    // Binds a file to the conversation, it is sent with every request until
+    // it is detached.
+    async fn attach(
+        &self,
+        _name: &str,
+        _mime: &str,
+        _data: &[u8],
+    ) -> Result<Attachment, AgentError> {
+        Err(AgentError::AgentError(Some(
+            "This client does not support attachments".to_string(),
+        )))
+    }
+
+    async fn attachments(&self) -> Vec<Attachment> {
+        vec![]
+    }
+
+    async fn detach(&self, _id: &str) -> Result<Attachment, AgentError> {
+        Err(AgentError::AgentError(Some(
+            "This client does not support attachments".to_string(),
+        )))
+    }
+
+    // Releases what the client keeps with the provider for the session, e.g.
+    // uploaded files. Called once the session ends.
+    async fn close(&self) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemPrompt {
+    pub version: u32,
+    pub text: String,
 }
 
 pub trait InputReader: Debug + Send + Sync + 'static {
     fn read(&self) -> Result<String, AgentError>;
 }
 
+// About four characters per token for English text and code.
+pub fn approximate_tokens(text: &str) -> u64 {
+    text.len().div_ceil(4) as u64
+}
+
+type Attached = (Attachment, Vec<u8>);
+
 #[derive(Debug)]
 pub struct Agent {
     reader: Arc<dyn InputReader>,
     client: Arc<dyn AgentClient>,
-    tools: Arc<Mutex<HashMap<String, Arc<dyn Tool>>>>,
+    tools: Arc<Mutex<ToolRegistry>>,
+    env: Arc<Mutex<HashMap<String, String>>>,
+    store: Option<Arc<dyn ConversationStore>>,
+    mode: Arc<Mutex<Mode>>,
+    allowlists: ToolAllowlists,
+    modes: HashMap<Mode, ModeConfig>,
+    // The system prompt before any mode filled it in, taken from the client
+    // the first time the mode is set.
+    prompt_template: Arc<Mutex<Option<SystemPrompt>>>,
+    // Named instructions appended to the prompt of every mode, e.g. the
+    // response language.
+    instructions: Arc<Mutex<Vec<(String, String)>>>,
+    // The files attached through the agent with their data, attached again
+    // when the client is replaced.
+    attached: Arc<Mutex<Vec<Attached>>>,
+    decorators: ToolDecorators,
+    max_steps: usize,
+    tool_limits: ToolLimits,
+    observers: Observers,
+    project_context: Option<Arc<ProjectContext>>,
 }
 
 impl Agent {
@@ -50,48 +234,482 @@ impl Agent {
         Self {
             client: Arc::new(client),
             reader: Arc::new(TerminalInputReader),
-            tools: Arc::new(Mutex::new(HashMap::new())),
+            tools: Arc::new(Mutex::new(ToolRegistry::default())),
+            env: Arc::new(Mutex::new(HashMap::new())),
+            store: None,
+            mode: Arc::new(Mutex::new(Mode::default())),
+            allowlists: ToolAllowlists::default(),
+            modes: HashMap::new(),
+            prompt_template: Arc::new(Mutex::new(None)),
+            instructions: Arc::new(Mutex::new(vec![])),
+            attached: Arc::new(Mutex::new(vec![])),
+            decorators: ToolDecorators::default(),
+            max_steps: MAX_STEPS,
+            tool_limits: ToolLimits::default(),
+            observers: Observers::default(),
+            project_context: None,
+        }
+    }
+
+    pub fn with_reader(mut self, reader: impl InputReader) -> Self {
+        self.reader = Arc::new(reader);
+        self
+    }
+
+    pub fn with_allowlists(mut self, allowlists: ToolAllowlists) -> Self {
+        self.allowlists = allowlists;
+        self
+    }
+
+    // The tools of the config replace the allowlist of the mode.
+    pub fn with_mode_config(mut self, mode: Mode, config: ModeConfig) -> Self {
+        if let Some(tools) = &config.tools {
+            let tools = tools.iter().map(String::as_str).collect::<Vec<&str>>();
+            self.allowlists = self.allowlists.with_tools(mode, &tools);
+        }
+        self.modes.insert(mode, config);
+        self
+    }
+
+    // Requests a turn may send before `run_turn` gives up on it.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    pub fn with_tool_limits(mut self, tool_limits: ToolLimits) -> Self {
+        self.tool_limits = tool_limits;
+        self
+    }
+
+    pub fn tool_limits(&self) -> ToolLimits {
+        self.tool_limits
+    }
+
+    // Adds the instruction files at `root`, e.g. AGENTS.md, to the system
+    // prompt from the next turn on, see ProjectContext.
+    pub fn with_project_context(mut self, root: impl Into<PathBuf>) -> Self {
+        self.project_context = Some(Arc::new(ProjectContext::new(root)));
+        self
+    }
+
+    pub fn project_context(&self) -> Option<Arc<ProjectContext>> {
+        self.project_context.clone()
+    }
+
+    // Brings the project instructions in the system prompt up to date with
+    // the files. Every turn does this, clients without a system prompt are
+    // left alone.
+    pub async fn refresh_project_context(&self) -> Result<(), AgentError> {
+        let Some(context) = &self.project_context else {
+            return Ok(());
+        };
+        let Some(prompt) = self.client.system_prompt().await else {
+            return Ok(());
+        };
+
+        match context.apply(&prompt) {
+            Some(prompt) => self.client.set_system_prompt(prompt).await,
+            None => Ok(()),
+        }
+    }
+
+    // Calls `observer` with every event of the turns from now on.
+    pub fn observe(&self, observer: impl AgentObserver + 'static) {
+        self.observers.add(Arc::new(observer));
+    }
+
+    pub fn emit(&self, event: AgentEvent) {
+        self.observers.emit(&event);
+    }
+
+    pub async fn mode(&self) -> Mode {
+        *self.mode.lock().await
+    }
+
+    // Switches the mode, so the client only advertises the tools it allows,
+    // and fills the mode into the system prompt.
+    pub async fn set_mode(&self, mode: Mode) -> Result<(), AgentError> {
+        let filter = self
+            .tools
+            .lock()
+            .await
+            .filter(&self.allowlists.filter(mode));
+        self.client.set_tool_filter(filter).await?;
+
+        // Clients that can't replace their prompt or temperature keep their
+        // own.
+        _ = self.apply_prompt(mode).await;
+        let config = self.modes.get(&mode).cloned().unwrap_or_default();
+        _ = self.client.set_temperature(config.temperature).await;
+
+        *self.mode.lock().await = mode;
+        Ok(())
+    }
+
+    // Adds the instruction under `name` to the system prompt of every mode,
+    // replacing the one added under that name before. None removes it.
+    pub async fn set_instruction(&self, name: &str, text: Option<&str>) -> Result<(), AgentError> {
+        let previous = {
+            let mut instructions = self.instructions.lock().await;
+            let previous = instructions.clone();
+            instructions.retain(|(known, _)| known != name);
+            if let Some(text) = text {
+                instructions.push((name.to_string(), text.to_string()));
+            }
+            previous
+        };
+
+        let result = self.apply_prompt(self.mode().await).await;
+        if result.is_err() {
+            *self.instructions.lock().await = previous;
+        }
+        result
+    }
+
+    // Sets the system prompt of the mode: the template, or the prompt the
+    // mode config replaces it with, followed by the instructions.
+    async fn apply_prompt(&self, mode: Mode) -> Result<(), AgentError> {
+        let template = {
+            let mut template = self.prompt_template.lock().await;
+            if template.is_none() {
+                *template = self.client.system_prompt().await;
+            }
+            template.clone()
+        }
+        .ok_or_else(|| {
+            AgentError::AgentError(Some(
+                "The provider has no system prompt to extend".to_string(),
+            ))
+        })?;
+
+        let config = self.modes.get(&mode);
+        let text = config
+            .and_then(|config| config.system_prompt.as_deref())
+            .unwrap_or(&template.text);
+        let mut text = render_prompt(text, mode);
+        for (_, instruction) in self.instructions.lock().await.iter() {
+            text = format!("{}\n\n{}", text.trim_end(), instruction);
+        }
+        self.client
+            .set_system_prompt(SystemPrompt {
+                version: template.version,
+                text,
+            })
+            .await
+    }
+
+    // Whether the tool is enabled and the current mode allows it, checked
+    // again before running a call in case the model names a tool it was not
+    // offered.
+    pub async fn allows_tool(&self, name: &str) -> bool {
+        self.tools.lock().await.is_enabled(name)
+            && self.allowlists.filter(self.mode().await).allows(name)
+    }
+
+    // Enables or disables a registered tool from the next request on.
+    pub async fn set_tool_enabled(&self, name: &str, enabled: bool) -> Result<(), AgentError> {
+        let mode = self.allowlists.filter(self.mode().await);
+        let mut tools = self.tools.lock().await;
+        let was_enabled = tools.is_enabled(name);
+        tools.set_enabled(name, enabled)?;
+
+        if let Err(e) = self.client.set_tool_filter(tools.filter(&mode)).await {
+            tools.set_enabled(name, was_enabled)?;
+            return Err(e);
         }
+        Ok(())
     }
 
-    pub async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+    pub async fn set_tool_decorators(
+        &mut self,
+        decorators: ToolDecorators,
+    ) -> Result<(), AgentError> {
+        self.client.set_tool_decorators(decorators.clone()).await?;
+        self.decorators = decorators;
+        Ok(())
+    }
+
+    pub fn with_store(mut self, store: impl ConversationStore + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    // The version of the system prompt the stored conversation was continued
+    // with last, None without a store or for a conversation stored before
+    // versions were recorded. Check it before `resume`, which records the
+    // current one.
+    pub fn stored_prompt_version(&self) -> Result<Option<u32>, AgentError> {
+        match &self.store {
+            Some(store) => store.prompt_version(),
+            None => Ok(None),
+        }
+    }
+
+    // Replays the stored conversation into the client. Function calls are
+    // replayed as text since their ids don't survive a restart. Returns the
+    // number of restored contents.
+    pub async fn resume(&self) -> Result<usize, AgentError> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        self.record_prompt_version().await?;
+
+        let contents = store.load()?;
+        for content in &contents {
+            let text = content
+                .parts
+                .iter()
+                .filter_map(|part| match &part.function_call {
+                    Some(call) => Some(format!("Called {}({})", call.name, call.args)),
+                    None => part.answer().cloned(),
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+            let role = AgentRole::from(content.role.as_str());
+            self.client.add_system_prompt(&text, role).await?;
+        }
+
+        Ok(contents.len())
+    }
+
+    pub fn remember(&self, content: &Content) -> Result<(), AgentError> {
+        match &self.store {
+            Some(store) => store.append(content),
+            None => Ok(()),
+        }
+    }
+
+    // The stored conversation, empty without a store.
+    pub fn history(&self) -> Result<Vec<Content>, AgentError> {
+        match &self.store {
+            Some(store) => store.load(),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn rewrite_history(&self, contents: &[Content]) -> Result<(), AgentError> {
+        match &self.store {
+            Some(store) => store.replace(contents),
+            None => Ok(()),
+        }
+    }
+
+    // Holds the conversation for `owner` until the lock is dropped.
+    pub fn lock_conversation(&self, owner: &str) -> Result<ConversationLock, AgentError> {
+        match &self.store {
+            Some(store) => store.lock(owner),
+            None => Ok(ConversationLock::default()),
+        }
+    }
+
+    pub async fn forget(&self) -> Result<(), AgentError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        store.clear()?;
+        self.record_prompt_version().await
+    }
+
+    // The conversation goes on with the current system prompt.
+    async fn record_prompt_version(&self) -> Result<(), AgentError> {
+        match (&self.store, self.client.system_prompt().await) {
+            (Some(store), Some(prompt)) => store.set_prompt_version(prompt.version),
+            _ => Ok(()),
+        }
+    }
+
+    // Registers a tool. A disabled one is known to the client but not offered
+    // to the model until it is enabled, which takes effect with the next
+    // `set_mode`.
+    pub async fn add_tool(
+        &self,
+        tool: Arc<dyn Tool>,
+        metadata: ToolMetadata,
+        enabled: bool,
+    ) -> Result<(), AgentError> {
         self.tools
             .lock()
             .await
-            .insert(tool.name().to_string(), tool.clone());
+            .register(tool.clone(), metadata, enabled);
         self.client.add_tool(tool).await?;
 
         Ok(())
     }
 
-    pub fn tools(&self) -> Arc<Mutex<HashMap<String, Arc<dyn Tool>>>> {
+    // Swaps the model client, registering the agent's tools with the new one.
+    pub async fn replace_client(
+        &mut self,
+        client: impl AgentClient + 'static,
+    ) -> Result<(), AgentError> {
+        let client: Arc<dyn AgentClient> = Arc::new(client);
+        let mode = self.allowlists.filter(self.mode().await);
+        let tools = self.tools.lock().await;
+        for registered in tools.iter() {
+            client.add_tool(registered.tool.clone()).await?;
+        }
+        let filter = tools.filter(&mode);
+        drop(tools);
+        if filter != ToolFilter::all() {
+            client.set_tool_filter(filter).await?;
+        }
+        if !self.decorators.is_empty() {
+            client.set_tool_decorators(self.decorators.clone()).await?;
+        }
+        // A file the new client can't take is reported and left out.
+        let mut attached = self.attached.lock().await;
+        let mut carried = vec![];
+        for (attachment, data) in attached.drain(..) {
+            match client
+                .attach(&attachment.name, &attachment.mime, &data)
+                .await
+            {
+                Ok(attachment) => carried.push((attachment, data)),
+                Err(e) => self.emit(AgentEvent::Error {
+                    message: format!("{} is no longer attached: {}", attachment.name, e),
+                }),
+            }
+        }
+        *attached = carried;
+        drop(attached);
+
+        let previous = std::mem::replace(&mut self.client, client);
+        previous.close().await;
+        Ok(())
+    }
+
+    // Attaches a file through the client, see AgentClient::attach.
+    pub async fn attach(
+        &self,
+        name: &str,
+        mime: &str,
+        data: Vec<u8>,
+    ) -> Result<Attachment, AgentError> {
+        let attachment = self.client.attach(name, mime, &data).await?;
+        self.attached.lock().await.push((attachment.clone(), data));
+        Ok(attachment)
+    }
+
+    pub async fn detach(&self, id: &str) -> Result<Attachment, AgentError> {
+        let attachment = self.client.detach(id).await?;
+        self.attached
+            .lock()
+            .await
+            .retain(|(attached, _)| attached.id != attachment.id);
+        Ok(attachment)
+    }
+
+    pub fn tools(&self) -> Arc<Mutex<ToolRegistry>> {
         self.tools.clone()
     }
+
+    pub async fn set_env(&self, key: &str, value: &str) {
+        self.env
+            .lock()
+            .await
+            .insert(key.to_string(), value.to_string());
+    }
+
+    pub async fn unset_env(&self, key: &str) -> bool {
+        self.env.lock().await.remove(key).is_some()
+    }
+
+    pub async fn env_keys(&self) -> Vec<String> {
+        let mut keys = self
+            .env
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        keys.sort();
+        keys
+    }
+
+    pub async fn tool_context(&self) -> ToolContext {
+        ToolContext {
+            env: self.env.lock().await.clone(),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum AgentError {
+    #[error("UserInputError: {}", message(.0))]
     UserInputError(Option<String>),
+    #[error("AgentError: {}", message(.0))]
     AgentError(Option<String>),
-    ExpiredApiKey,
+    // The key was rejected, e.g. because it expired.
+    #[error("InvalidApiKey: {}", message(.0))]
+    InvalidApiKey(Option<String>),
+    // A quota that only resets much later, such as a daily one.
+    #[error("QuotaExhausted: {}", message(.0))]
+    QuotaExhausted(Option<String>),
+    // A short-term limit, the request may be sent again after `retry_after`.
+    #[error("RateLimited: {message}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    // The provider could not be reached or failed on its side, the same
+    // request may succeed when sent again.
+    #[error("Network: {0}")]
+    Network(String),
+    // The provider rejected the request, sending it again fails the same way.
+    #[error("InvalidRequest: {}", message(.0))]
+    InvalidRequest(Option<String>),
+    // The response is not in the format voo expects.
+    #[error("Deserialization: {message}")]
+    Deserialization { body: String, message: String },
+    // The provider withheld the answer, with its reason when it gave one.
+    #[error("SafetyBlocked: {}", message(.0))]
+    SafetyBlocked(Option<String>),
+    // The conversation no longer fits the model's context window.
+    #[error("ContextTooLong: {}", message(.0))]
+    ContextTooLong(Option<String>),
+    // The provider failed too often and is not asked again until the circuit
+    // breaker lets a request probe it after `retry_after`.
+    #[error("CircuitOpen: {message}")]
+    CircuitOpen {
+        retry_after: Option<Duration>,
+        message: String,
+    },
 }
 
-impl Display for AgentError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl AgentError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            AgentError::Network(_) | AgentError::RateLimited { .. }
+        )
+    }
+
+    // A stable name of the kind of failure, e.g. for API responses and logs.
+    pub fn code(&self) -> &'static str {
         match self {
-            AgentError::UserInputError(msg) => match msg {
-                Some(msg) => write!(f, "UserInputError: {}", msg),
-                None => write!(f, "UserInputError: "),
-            },
-            AgentError::AgentError(msg) => match msg {
-                Some(msg) => write!(f, "AgentError: {}", msg),
-                None => write!(f, "AgentError: "),
-            },
-            AgentError::ExpiredApiKey => write!(f, "ExpiredApiKey"),
+            AgentError::UserInputError(_) => "user_input",
+            AgentError::AgentError(_) => "agent",
+            AgentError::InvalidApiKey(_) => "invalid_api_key",
+            AgentError::QuotaExhausted(_) => "quota_exhausted",
+            AgentError::RateLimited { .. } => "rate_limited",
+            AgentError::Network(_) => "network",
+            AgentError::InvalidRequest(_) => "invalid_request",
+            AgentError::Deserialization { .. } => "deserialization",
+            AgentError::SafetyBlocked(_) => "safety_blocked",
+            AgentError::ContextTooLong(_) => "context_too_long",
+            AgentError::CircuitOpen { .. } => "circuit_open",
         }
     }
 }
 
+fn message(message: &Option<String>) -> &str {
+    message.as_deref().unwrap_or_default()
+}
+
 impl Agent {
     pub fn client(&self) -> &Arc<dyn AgentClient> {
         &self.client
@@ -114,7 +732,7 @@ impl InputReader for TerminalInputReader {
             .map_err(|e| AgentError::UserInputError(Some(e.to_string())))?;
 
         std::io::stdout().flush().map_err(|e| {
-            AgentError::UserInputError(Some(format!("Error flushing stdout: {}", e.to_string())))
+            AgentError::UserInputError(Some(format!("Error flushing stdout: {}", e)))
         })?;
 
         std::io::stdin()
@@ -126,9 +744,50 @@ impl InputReader for TerminalInputReader {
     }
 }
 
+// Reads the whole input as a single prompt, e.g. `echo "..." | voo`. Later
+// reads return nothing, which ends the chat.
+pub struct PipeInputReader {
+    input: std::sync::Mutex<Option<Box<dyn Read + Send>>>,
+}
+
+impl PipeInputReader {
+    pub fn new(input: impl Read + Send + 'static) -> Self {
+        Self {
+            input: std::sync::Mutex::new(Some(Box::new(input))),
+        }
+    }
+
+    pub fn stdin() -> Self {
+        Self::new(std::io::stdin())
+    }
+}
+
+impl Debug for PipeInputReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipeInputReader").finish_non_exhaustive()
+    }
+}
+
+impl InputReader for PipeInputReader {
+    fn read(&self) -> Result<String, AgentError> {
+        let mut input = self.input.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(mut reader) = input.take() else {
+            return Ok(String::new());
+        };
+
+        let mut content = String::new();
+        reader
+            .read_to_string(&mut content)
+            .map_err(|e| AgentError::UserInputError(Some(e.to_string())))?;
+
+        Ok(content)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::tool_registry::tests::tool;
 
     #[derive(Debug)]
     struct MockAgentClient {}
@@ -167,19 +826,208 @@ mod tests {
         let agent = Agent {
             client: Arc::new(MockAgentClient {}),
             reader: Arc::new(reader),
-            tools: Arc::new(Mutex::new(HashMap::new())),
+            tools: Arc::new(Mutex::new(ToolRegistry::default())),
+            env: Arc::new(Mutex::new(HashMap::new())),
+            store: None,
+            mode: Arc::new(Mutex::new(Mode::default())),
+            allowlists: ToolAllowlists::default(),
+            modes: HashMap::new(),
+            prompt_template: Arc::new(Mutex::new(None)),
+            instructions: Arc::new(Mutex::new(vec![])),
+            attached: Arc::new(Mutex::new(vec![])),
+            decorators: ToolDecorators::default(),
+            max_steps: MAX_STEPS,
+            tool_limits: ToolLimits::default(),
+            observers: Observers::default(),
+            project_context: None,
         };
 
         let input = "test input";
         let result = agent.reader().read();
         assert_eq!(result.unwrap(), input);
     }
+
+    #[test]
+    fn test_pipe_input_reader() {
+        let reader =
+            PipeInputReader::new(std::io::Cursor::new("summarize src/main.rs\nin one line\n"));
+
+        assert_eq!(
+            reader.read().unwrap(),
+            "summarize src/main.rs\nin one line\n"
+        );
+        assert_eq!(reader.read().unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_tool_context_env() {
+        let agent = Agent::new(MockAgentClient {});
+        agent
+            .set_env("DATABASE_URL", "postgres://localhost/app")
+            .await;
+        agent.set_env("API_TOKEN", "secret").await;
+
+        assert_eq!(agent.env_keys().await, vec!["API_TOKEN", "DATABASE_URL"]);
+        assert!(agent.unset_env("API_TOKEN").await);
+        assert!(!agent.unset_env("API_TOKEN").await);
+
+        let context = agent.tool_context().await;
+        assert_eq!(
+            context.env.get("DATABASE_URL").map(String::as_str),
+            Some("postgres://localhost/app")
+        );
+        assert!(!context.env.contains_key("API_TOKEN"));
+    }
+
+    // Keeps the prompt and tool filter it is given.
+    #[derive(Debug, Default)]
+    struct PromptClient {
+        prompt: std::sync::Mutex<Option<SystemPrompt>>,
+        filter: std::sync::Mutex<Option<ToolFilter>>,
+        attached: std::sync::Mutex<Vec<Attachment>>,
+    }
+
+    #[async_trait]
+    impl AgentClient for PromptClient {
+        async fn ask(&self, _prompt: &str) -> Result<Vec<Content>, AgentError> {
+            Ok(vec![])
+        }
+
+        async fn add_tool(&self, _tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        async fn add_system_prompt(
+            &self,
+            _prompt: &str,
+            _role: AgentRole,
+        ) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        async fn system_prompt(&self) -> Option<SystemPrompt> {
+            self.prompt.lock().unwrap().clone()
+        }
+
+        async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+            *self.prompt.lock().unwrap() = Some(prompt);
+            Ok(())
+        }
+
+        async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+            *self.filter.lock().unwrap() = Some(filter);
+            Ok(())
+        }
+
+        async fn attach(
+            &self,
+            name: &str,
+            mime: &str,
+            data: &[u8],
+        ) -> Result<Attachment, AgentError> {
+            let mut attached = self.attached.lock().unwrap();
+            let attachment = Attachment {
+                id: (attached.len() + 1).to_string(),
+                name: name.to_string(),
+                mime: mime.to_string(),
+                bytes: data.len() as u64,
+                tokens: 0,
+                uploaded: false,
+            };
+            attached.push(attachment.clone());
+            Ok(attachment)
+        }
+
+        async fn attachments(&self) -> Vec<Attachment> {
+            self.attached.lock().unwrap().clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mode_switch_keeps_tools_and_instructions() {
+        let client = PromptClient::default();
+        *client.prompt.lock().unwrap() = Some(SystemPrompt {
+            version: 1,
+            text: "You are in {{current_mode}} mode.".to_string(),
+        });
+        let agent = Agent::new(client).with_mode_config(
+            Mode::Ask,
+            ModeConfig {
+                system_prompt: Some("Answer in {{current_mode}} mode.".to_string()),
+                ..ModeConfig::default()
+            },
+        );
+        for name in ["read_file", "run_command"] {
+            agent
+                .add_tool(tool(name), ToolMetadata::default(), true)
+                .await
+                .unwrap();
+        }
+        agent.set_tool_enabled("run_command", false).await.unwrap();
+        agent
+            .set_instruction("language", Some("Always reply in Japanese."))
+            .await
+            .unwrap();
+
+        for mode in [Mode::Ask, Mode::Code] {
+            agent.set_mode(mode).await.unwrap();
+            let prompt = agent.client().system_prompt().await.unwrap().text;
+            assert!(prompt.contains(&format!("in {} mode.", mode)), "{}", prompt);
+            assert!(
+                prompt.ends_with("\n\nAlways reply in Japanese."),
+                "{}",
+                prompt
+            );
+            assert!(!agent.allows_tool("run_command").await);
+            assert!(agent.allows_tool("read_file").await);
+        }
+
+        agent.set_instruction("language", None).await.unwrap();
+        assert_eq!(
+            agent.client().system_prompt().await.unwrap().text,
+            "You are in Code mode."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replaced_client_keeps_attachments() {
+        let mut agent = Agent::new(PromptClient::default());
+        agent
+            .attach("notes.md", "text/markdown", b"# Notes".to_vec())
+            .await
+            .unwrap();
+        agent.replace_client(PromptClient::default()).await.unwrap();
+
+        let attachments = agent.client().attachments().await;
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(
+            (attachments[0].name.as_str(), attachments[0].bytes),
+            ("notes.md", 7)
+        );
+    }
+
+    #[test]
+    fn test_agent_role() {
+        for role in [
+            AgentRole::User,
+            AgentRole::Model,
+            AgentRole::System,
+            AgentRole::Tool,
+        ] {
+            assert_eq!(AgentRole::from(role.to_string().as_str()), role);
+        }
+        assert_eq!(AgentRole::from("assistant"), AgentRole::Model);
+        assert_eq!(AgentRole::from("unknown"), AgentRole::User);
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Content {
+    // A blocked or cut off candidate comes without parts.
+    #[serde(default)]
     pub parts: Vec<Part>,
+    #[serde(default)]
     pub role: String,
 }
 
@@ -195,18 +1043,78 @@ impl Content {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Part {
+    // Gemini takes a single kind of data per part, a null text next to a
+    // function response is rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub function_call: Option<FunctionCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_response: Option<FunctionResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought: Option<bool>,
+    // An image or other media attached to a message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<InlineData>,
+    // Media the model fetches itself, e.g. a YouTube video.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<FileData>,
 }
 
 impl Part {
     pub fn new(text: &str) -> Self {
         Self {
             text: Some(text.to_string()),
-            function_call: None,
+            ..Default::default()
+        }
+    }
+
+    pub fn thought(text: &str) -> Self {
+        Self {
+            text: Some(text.to_string()),
+            thought: Some(true),
+            ..Default::default()
+        }
+    }
+
+    pub fn function_response(name: &str, output: &ToolOutput) -> Self {
+        Self {
+            function_response: Some(FunctionResponse {
+                name: name.to_string(),
+                response: output.to_response(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn inline_data(mime: &str, data: &[u8]) -> Self {
+        Self {
+            inline_data: Some(InlineData {
+                mime_type: mime.to_string(),
+                data: STANDARD.encode(data),
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn file_data(mime: Option<&str>, uri: &str) -> Self {
+        Self {
+            file_data: Some(FileData {
+                mime_type: mime.map(str::to_string),
+                file_uri: uri.to_string(),
+            }),
+            ..Default::default()
         }
     }
+
+    pub fn is_thought(&self) -> bool {
+        self.thought.unwrap_or_default()
+    }
+
+    // Text meant for the user, excluding the model's reasoning.
+    pub fn answer(&self) -> Option<&String> {
+        self.text.as_ref().filter(|_| !self.is_thought())
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -215,3 +1123,26 @@ pub struct FunctionCall {
     pub name: String,
     pub args: Value,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionResponse {
+    pub name: String,
+    pub response: Value,
+}
+
+// Base64 encoded, as the Gemini API takes it.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineData {
+    pub mime_type: String,
+    pub data: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub file_uri: String,
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use super::agent::approximate_tokens;
+
+// Gemini bills an image of up to 384 pixels a side, and every 768 pixel tile
+// of a larger one, as 258 tokens. A PDF page counts as an image.
+const IMAGE_TOKENS: u64 = 258;
+// Per second of audio and of video with its sound track.
+const AUDIO_TOKENS: u64 = 32;
+const VIDEO_TOKENS: u64 = 263;
+// Bitrates assumed to guess the length of a recording from its size.
+const AUDIO_BYTES_PER_SECOND: u64 = 16_000;
+const VIDEO_BYTES_PER_SECOND: u64 = 250_000;
+
+// A file bound to the conversation with /attach. It goes with every request
+// until it is detached, either inline or as an upload the request refers to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub name: String,
+    pub mime: String,
+    pub bytes: u64,
+    // An estimate, see `estimate_tokens`.
+    pub tokens: u64,
+    pub uploaded: bool,
+}
+
+// Rough input tokens of a file. The length of images and recordings is
+// guessed from their size, which is good enough to tell a screenshot from a
+// movie.
+pub fn estimate_tokens(mime: &str, data: &[u8]) -> u64 {
+    let bytes = data.len() as u64;
+    match mime {
+        "application/pdf" => pdf_pages(data).max(1) * IMAGE_TOKENS,
+        mime if mime.starts_with("image/") => IMAGE_TOKENS,
+        mime if mime.starts_with("audio/") => bytes.div_ceil(AUDIO_BYTES_PER_SECOND) * AUDIO_TOKENS,
+        mime if mime.starts_with("video/") => bytes.div_ceil(VIDEO_BYTES_PER_SECOND) * VIDEO_TOKENS,
+        _ => approximate_tokens(&String::from_utf8_lossy(data)),
+    }
+}
+
+// The `/Type /Page` objects of a PDF, `/Type /Pages` are the page tree.
+fn pdf_pages(data: &[u8]) -> u64 {
+    let text = String::from_utf8_lossy(data).replace("/Type /", "/Type/");
+    text.match_indices("/Type/Page")
+        .filter(|(start, _)| !text[start + 10..].starts_with('s'))
+        .count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens("text/plain", b"fn main() {}"), 3);
+        assert_eq!(estimate_tokens("image/png", &[0; 4096]), 258);
+        assert_eq!(estimate_tokens("audio/mpeg", &[0; 160_000]), 320);
+
+        let pdf = b"<< /Type /Pages /Count 2 >> << /Type /Page >> << /Type/Page >>";
+        assert_eq!(estimate_tokens("application/pdf", pdf), 516);
+    }
+}
@@ -0,0 +1,509 @@
+use std::{
+    fmt::Debug,
+    fs::{File, OpenOptions, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    agent::{AgentError, Content},
+    jsonl,
+};
+
+// Keeps the conversation of an agent across restarts, one Content at a time.
+// A store refuses to append when another store wrote to the same
+// conversation since it last loaded, appended or cleared it.
+pub trait ConversationStore: Debug + Send + Sync {
+    fn load(&self) -> Result<Vec<Content>, AgentError>;
+    fn append(&self, content: &Content) -> Result<(), AgentError>;
+    fn clear(&self) -> Result<(), AgentError>;
+    // Replaces the whole conversation, leaving nothing of the old one behind,
+    // e.g. to take back a credential pasted by accident.
+    fn replace(&self, contents: &[Content]) -> Result<(), AgentError> {
+        self.clear()?;
+        contents.iter().try_for_each(|content| self.append(content))
+    }
+    // Keeps other frontends, e.g. `voo serve` while `voo chat` has the
+    // conversation open, from using it until the lock is dropped.
+    fn lock(&self, owner: &str) -> Result<ConversationLock, AgentError>;
+    // The version of the system prompt the conversation is continued with,
+    // None for one stored before versions were recorded.
+    fn prompt_version(&self) -> Result<Option<u32>, AgentError>;
+    fn set_prompt_version(&self, version: u32) -> Result<(), AgentError>;
+}
+
+// An advisory lock on a conversation. The OS releases it when the process
+// exits, so a crashed frontend does not leave it behind.
+#[derive(Debug, Default)]
+pub struct ConversationLock {
+    file: Option<File>,
+}
+
+impl ConversationLock {
+    pub fn acquire(path: &Path, owner: &str) -> Result<Self, AgentError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = std::fs::read_to_string(path)
+                    .ok()
+                    .filter(|holder| !holder.trim().is_empty())
+                    .unwrap_or_else(|| "another voo".to_string());
+                return Err(AgentError::UserInputError(Some(format!(
+                    "The conversation is in use by {}",
+                    holder.trim()
+                ))));
+            }
+            Err(TryLockError::Error(e)) => return Err(io_error(e)),
+        }
+
+        file.set_len(0).map_err(io_error)?;
+        write!(file, "{} (pid {})", owner, std::process::id()).map_err(io_error)?;
+
+        Ok(Self { file: Some(file) })
+    }
+}
+
+impl Drop for ConversationLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            _ = file.set_len(0);
+            _ = file.unlock();
+        }
+    }
+}
+
+// Fails when the conversation does not have the number of turns the store
+// last saw, i.e. another frontend wrote to it in between.
+pub(crate) fn check_turn(seen: Option<usize>, turns: usize) -> Result<(), AgentError> {
+    match seen {
+        Some(seen) if seen != turns => Err(AgentError::AgentError(Some(format!(
+            "The conversation was changed by another frontend (turn {} instead of {}), resume it to continue",
+            turns + 1,
+            seen + 1
+        )))),
+        _ => Ok(()),
+    }
+}
+
+// Compacts the log once this many events are superseded by a clear.
+const COMPACT_AFTER: usize = 256;
+
+// One line of the log. Logs written before the events had sequence numbers
+// hold a bare Content per line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum LogLine {
+    Event(LogEvent),
+    Legacy(Content),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LogEvent {
+    seq: usize,
+    #[serde(flatten)]
+    change: Change,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Change {
+    Append { content: Content },
+    Clear,
+}
+
+// What the store knows of the log file: the sequence number of the last
+// event, the length of the file after it, and how many events count and how
+// many a clear made obsolete.
+#[derive(Debug, Clone, Copy)]
+struct LogState {
+    seq: usize,
+    len: u64,
+    live: usize,
+    dead: usize,
+}
+
+#[derive(Debug)]
+struct Log {
+    live: Vec<LogEvent>,
+    state: LogState,
+}
+
+// An append-only log of conversation events, one JSON line each, so a turn
+// costs a single append however long the conversation is.
+#[derive(Debug)]
+pub struct JsonlConversationStore {
+    path: PathBuf,
+    // Serializes writes from concurrent tasks.
+    state: Mutex<Option<LogState>>,
+}
+
+impl JsonlConversationStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<Log, AgentError> {
+        let content = match std::fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(io_error(e)),
+        };
+        let parsed = jsonl::parse::<LogLine>(&content)
+            .map_err(|e| AgentError::AgentError(Some(format!("{}: {}", self.path.display(), e))))?;
+
+        let mut live = vec![];
+        let mut seq = 0;
+        let mut dead = 0;
+        for line in parsed.records {
+            let event = match line {
+                LogLine::Event(event) => event,
+                LogLine::Legacy(content) => LogEvent {
+                    seq: seq + 1,
+                    change: Change::Append { content },
+                },
+            };
+            seq = event.seq;
+            match event.change {
+                Change::Append { .. } => live.push(event),
+                Change::Clear => dead += live.drain(..).count() + 1,
+            }
+        }
+
+        Ok(Log {
+            state: LogState {
+                seq,
+                len: parsed.len,
+                live: live.len(),
+                dead,
+            },
+            live,
+        })
+    }
+
+    // Appends an event, unless another store wrote to the log since this one
+    // last saw it. A line left half written by a crash is cut off first.
+    fn write(&self, change: Change) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let file_len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let current = match *state {
+            Some(seen) if seen.len == file_len => seen,
+            seen => {
+                let log = self.read()?;
+                check_turn(seen.map(|seen| seen.seq), log.state.seq)?;
+                if log.state.len < file_len {
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&self.path)
+                        .and_then(|file| file.set_len(log.state.len))
+                        .map_err(io_error)?;
+                }
+                log.state
+            }
+        };
+
+        let event = LogEvent {
+            seq: current.seq + 1,
+            change,
+        };
+        let mut line = serde_json::to_string(&event)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        line.push('\n');
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(io_error)?;
+        file.write_all(line.as_bytes()).map_err(io_error)?;
+        file.sync_data().map_err(io_error)?;
+
+        let (live, dead) = match event.change {
+            Change::Append { .. } => (current.live + 1, current.dead),
+            Change::Clear => (0, current.dead + current.live + 1),
+        };
+        *state = Some(LogState {
+            seq: event.seq,
+            len: current.len + line.len() as u64,
+            live,
+            dead,
+        });
+        if dead >= COMPACT_AFTER {
+            *state = Some(self.compact()?);
+        }
+
+        Ok(())
+    }
+
+    // A file next to the log, e.g. `conversation.jsonl.lock`.
+    fn beside(&self, extension: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+
+    // Rewrites the log with only the events that still count, keeping their
+    // sequence numbers.
+    fn compact(&self) -> Result<LogState, AgentError> {
+        let log = self.read()?;
+        let mut events = log.live;
+        if events.is_empty() && log.state.seq > 0 {
+            events.push(LogEvent {
+                seq: log.state.seq,
+                change: Change::Clear,
+            });
+        }
+
+        self.rewrite(&events, log.state.seq)
+    }
+
+    // The new log replaces the old one in a single rename, so a crash leaves
+    // one or the other.
+    fn rewrite(&self, events: &[LogEvent], seq: usize) -> Result<LogState, AgentError> {
+        let mut content = String::new();
+        for event in events {
+            let line = serde_json::to_string(event)
+                .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp).map_err(io_error)?;
+        file.write_all(content.as_bytes()).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+        std::fs::rename(&tmp, &self.path).map_err(io_error)?;
+
+        Ok(LogState {
+            seq,
+            len: content.len() as u64,
+            live: events
+                .iter()
+                .filter(|event| matches!(event.change, Change::Append { .. }))
+                .count(),
+            dead: 0,
+        })
+    }
+}
+
+impl ConversationStore for JsonlConversationStore {
+    fn load(&self) -> Result<Vec<Content>, AgentError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let log = self.read()?;
+        *state = Some(match log.state.dead >= COMPACT_AFTER {
+            true => self.compact()?,
+            false => log.state,
+        });
+
+        Ok(log
+            .live
+            .into_iter()
+            .filter_map(|event| match event.change {
+                Change::Append { content } => Some(content),
+                Change::Clear => None,
+            })
+            .collect())
+    }
+
+    fn append(&self, content: &Content) -> Result<(), AgentError> {
+        self.write(Change::Append {
+            content: content.clone(),
+        })
+    }
+
+    fn clear(&self) -> Result<(), AgentError> {
+        self.write(Change::Clear)
+    }
+
+    // Written to a new log that replaces the old one, so nothing of the old
+    // conversation is left in the file, even after a crash halfway.
+    fn replace(&self, contents: &[Content]) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let log = self.read()?;
+        check_turn(state.map(|seen| seen.seq), log.state.seq)?;
+
+        let mut events = contents
+            .iter()
+            .zip(log.state.seq + 1..)
+            .map(|(content, seq)| LogEvent {
+                seq,
+                change: Change::Append {
+                    content: content.clone(),
+                },
+            })
+            .collect::<Vec<LogEvent>>();
+        if events.is_empty() {
+            events.push(LogEvent {
+                seq: log.state.seq + 1,
+                change: Change::Clear,
+            });
+        }
+
+        let seq = events.last().map_or(log.state.seq, |event| event.seq);
+        *state = Some(self.rewrite(&events, seq)?);
+        Ok(())
+    }
+
+    fn lock(&self, owner: &str) -> Result<ConversationLock, AgentError> {
+        ConversationLock::acquire(&self.beside("lock"), owner)
+    }
+
+    // Kept next to the log, which compaction rewrites.
+    fn prompt_version(&self) -> Result<Option<u32>, AgentError> {
+        match std::fs::read_to_string(self.beside("prompt")) {
+            Ok(version) => Ok(version.trim().parse().ok()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    fn set_prompt_version(&self, version: u32) -> Result<(), AgentError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        std::fs::write(self.beside("prompt"), version.to_string()).map_err(io_error)
+    }
+}
+
+fn io_error(e: std::io::Error) -> AgentError {
+    AgentError::AgentError(Some(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agent::{FunctionCall, Part};
+
+    #[test]
+    fn test_append_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("voo-conversation-{}", uuid::Uuid::new_v4()))
+            .join("conversation.jsonl");
+        let store = JsonlConversationStore::new(&path);
+        assert!(store.load().unwrap().is_empty());
+
+        let question = Content::new(vec![Part::new("what is in main.rs?")], "user");
+        let call = Content::new(
+            vec![Part {
+                function_call: Some(FunctionCall {
+                    name: "read_file".to_string(),
+                    args: serde_json::json!({ "path": "src/main.rs" }),
+                }),
+                ..Default::default()
+            }],
+            "model",
+        );
+        store.append(&question).unwrap();
+        store.append(&call).unwrap();
+        assert_eq!(store.load().unwrap(), [question, call]);
+
+        store.clear().unwrap();
+        assert!(store.load().unwrap().is_empty());
+
+        assert_eq!(store.prompt_version().unwrap(), None);
+        store.set_prompt_version(2).unwrap();
+        assert_eq!(
+            JsonlConversationStore::new(&path).prompt_version().unwrap(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_lock_and_conflicts() {
+        let path = std::env::temp_dir()
+            .join(format!("voo-conversation-{}", uuid::Uuid::new_v4()))
+            .join("conversation.jsonl");
+        let cli = JsonlConversationStore::new(&path);
+        let server = JsonlConversationStore::new(&path);
+
+        let lock = cli.lock("voo chat").unwrap();
+        let error = server.lock("voo serve").unwrap_err().to_string();
+        assert!(error.contains("in use by voo chat (pid"), "{}", error);
+        drop(lock);
+        let _lock = server.lock("voo serve").unwrap();
+
+        let message = |text: &str| Content::new(vec![Part::new(text)], "user");
+        cli.load().unwrap();
+        server.load().unwrap();
+        server.append(&message("from the server")).unwrap();
+        assert!(cli.append(&message("from the cli")).is_err());
+        assert_eq!(cli.load().unwrap(), [message("from the server")]);
+        cli.append(&message("from the cli")).unwrap();
+
+        _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_torn_lines_and_compaction() {
+        let path = std::env::temp_dir()
+            .join(format!("voo-conversation-{}", uuid::Uuid::new_v4()))
+            .join("conversation.jsonl");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let message = |text: &str| Content::new(vec![Part::new(text)], "user");
+
+        // A log from before the events, with a line a crash cut short.
+        let legacy = serde_json::to_string(&message("from an old voo")).unwrap();
+        std::fs::write(&path, format!("{}\n{{\"seq\":2,\"event\":\"app", legacy)).unwrap();
+        let store = JsonlConversationStore::new(&path);
+        assert_eq!(store.load().unwrap(), [message("from an old voo")]);
+        store.append(&message("after the crash")).unwrap();
+        assert_eq!(
+            JsonlConversationStore::new(&path).load().unwrap(),
+            [message("from an old voo"), message("after the crash")]
+        );
+
+        for _ in 0..COMPACT_AFTER {
+            store.clear().unwrap();
+        }
+        store.append(&message("kept")).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.lines().count() < 10, "{}", content);
+        assert_eq!(store.load().unwrap(), [message("kept")]);
+        assert!(content.contains(&format!("\"seq\":{}", COMPACT_AFTER + 3)));
+
+        // A replaced conversation is gone from the file as well, and other
+        // stores notice the change.
+        let other = JsonlConversationStore::new(&path);
+        other.load().unwrap();
+        store.replace(&[message("[redacted]")]).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("kept"), "{}", content);
+        assert_eq!(store.load().unwrap(), [message("[redacted]")]);
+        assert!(other.append(&message("stale")).is_err());
+
+        _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,35 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use super::agent::AgentError;
+
+pub type Embedding = Vec<f32>;
+
+#[async_trait]
+pub trait Embedder: Debug + Send + Sync {
+    // Identifies the embedding space. Vectors from different models are not
+    // comparable, so an index must be queried with the embedder it was built with.
+    fn model(&self) -> String;
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AgentError>;
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+}
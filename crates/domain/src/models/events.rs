@@ -0,0 +1,70 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::tools::ToolOutput;
+
+// What happens during a turn, for frontends to render. The agent itself
+// never prints.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    UserMessage { text: String },
+    // A piece of the answer as it streams in, or of the model's reasoning.
+    ModelChunk { text: String, thought: bool },
+    ToolCallStarted { name: String, args: Value },
+    ToolCallFinished { name: String, output: ToolOutput },
+    Error { message: String },
+    TurnComplete { answer: String },
+}
+
+pub trait AgentObserver: Send + Sync {
+    fn on_event(&self, event: &AgentEvent);
+}
+
+impl<F> AgentObserver for F
+where
+    F: Fn(&AgentEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &AgentEvent) {
+        self(event)
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Observers {
+    observers: Arc<Mutex<Vec<Arc<dyn AgentObserver>>>>,
+}
+
+impl Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Observers({})", self.list().len())
+    }
+}
+
+impl Observers {
+    pub fn add(&self, observer: Arc<dyn AgentObserver>) {
+        if let Ok(mut observers) = self.observers.lock() {
+            observers.push(observer);
+        }
+    }
+
+    // Observers are called in the order they were added, outside the lock so
+    // they can add further observers.
+    pub fn emit(&self, event: &AgentEvent) {
+        for observer in self.list() {
+            observer.on_event(event);
+        }
+    }
+
+    fn list(&self) -> Vec<Arc<dyn AgentObserver>> {
+        self.observers
+            .lock()
+            .map(|observers| observers.clone())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl Display for CircuitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+impl ProviderHealth {
+    pub fn healthy(provider: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            last_error: None,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.state != CircuitState::Open
+    }
+}
+
+// Requests the session sent in the current window of the provider's rate
+// limit, and how long an exhausted quota stays exhausted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitStatus {
+    pub requests: usize,
+    pub requests_per_minute: usize,
+    pub exhausted_secs: Option<u64>,
+}
+
+impl Display for RateLimitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} requests this minute",
+            self.requests, self.requests_per_minute
+        )?;
+
+        if let Some(secs) = self.exhausted_secs {
+            let minutes = secs.div_ceil(60);
+            write!(
+                f,
+                ", quota exhausted for another {}h {}m",
+                minutes / 60,
+                minutes % 60
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ProviderHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: circuit {} ({} consecutive failures)",
+            self.provider, self.state, self.consecutive_failures
+        )?;
+
+        if let Some(error) = &self.last_error {
+            write!(f, ", last error: {}", error)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use serde::de::DeserializeOwned;
+
+// The records of a JSON lines file and the length of the part they were read
+// from. A last line that doesn't parse was cut short by a crash while it was
+// written, so it is left out instead of failing the whole file; the caller
+// can truncate the file to `len` before appending to it.
+#[derive(Debug)]
+pub struct Records<T> {
+    pub records: Vec<T>,
+    pub len: u64,
+}
+
+pub fn parse<T: DeserializeOwned>(content: &[u8]) -> Result<Records<T>, String> {
+    let mut records = vec![];
+    let mut len = 0;
+
+    let mut lines = content.split_inclusive(|byte| *byte == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let text = String::from_utf8_lossy(line);
+        if !text.trim().is_empty() {
+            match serde_json::from_str::<T>(&text) {
+                Ok(record) => records.push(record),
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => return Err(format!("line {}: {}", records.len() + 1, e)),
+            }
+        }
+        len += line.len() as u64;
+    }
+
+    Ok(Records { records, len })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_torn_last_line() {
+        let content = b"{\"seq\":1}\n\n{\"seq\":2}\n{\"seq\":3,\"con";
+        let parsed = parse::<Value>(content).unwrap();
+        assert_eq!(parsed.records.len(), 2);
+        assert_eq!(parsed.len, 21);
+
+        assert!(parse::<Value>(b"{\"seq\":1}\n{\"seq\n{\"seq\":3}\n").is_err());
+    }
+}
@@ -1,2 +1,17 @@
 pub mod agent;
+pub mod attachment;
+pub mod conversation;
+pub mod embedding;
+pub mod events;
+pub mod health;
+pub mod jsonl;
+pub mod mode;
+pub mod project_context;
+pub mod tool_registry;
 pub mod tools;
+pub mod transcript;
+pub mod transcription;
+pub mod turn;
+pub mod usage;
+pub mod video;
+pub mod web_search;
@@ -0,0 +1,106 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use super::tools::ToolFilter;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    #[default]
+    Code,
+    Architect,
+    Ask,
+    Debug,
+    Orchestrator,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Code,
+        Mode::Architect,
+        Mode::Ask,
+        Mode::Debug,
+        Mode::Orchestrator,
+    ];
+
+    pub fn parse(name: &str) -> Option<Mode> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.to_string().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Code => write!(f, "Code"),
+            Mode::Architect => write!(f, "Architect"),
+            Mode::Ask => write!(f, "Ask"),
+            Mode::Debug => write!(f, "Debug"),
+            Mode::Orchestrator => write!(f, "Orchestrator"),
+        }
+    }
+}
+
+// The placeholder of the system prompt the name of the current mode fills.
+pub const CURRENT_MODE: &str = "{{current_mode}}";
+
+// How a mode sets up the agent. Unset fields keep what the agent was built
+// with: the system prompt of the client, the tools of the allowlists and the
+// provider's sampling settings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModeConfig {
+    pub system_prompt: Option<String>,
+    pub tools: Option<Vec<String>>,
+    pub temperature: Option<f32>,
+}
+
+pub fn render_prompt(template: &str, mode: Mode) -> String {
+    template.replace(CURRENT_MODE, &mode.to_string())
+}
+
+// The tools each mode may use. Modes without a list may use every tool.
+#[derive(Debug, Clone, Default)]
+pub struct ToolAllowlists {
+    lists: HashMap<Mode, Vec<String>>,
+}
+
+impl ToolAllowlists {
+    pub fn with_tools(mut self, mode: Mode, tools: &[&str]) -> Self {
+        self.lists
+            .insert(mode, tools.iter().map(|tool| tool.to_string()).collect());
+        self
+    }
+
+    pub fn filter(&self, mode: Mode) -> ToolFilter {
+        match self.lists.get(&mode) {
+            Some(tools) => ToolFilter::only(tools.clone()),
+            None => ToolFilter::all(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlists() {
+        let allowlists = ToolAllowlists::default().with_tools(Mode::Ask, &["read_file"]);
+
+        assert_eq!(Mode::parse(" ask"), Some(Mode::Ask));
+        assert!(allowlists.filter(Mode::Ask).allows("read_file"));
+        assert!(!allowlists.filter(Mode::Ask).allows("write_file"));
+        assert!(allowlists.filter(Mode::Code).allows("write_file"));
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        assert_eq!(
+            render_prompt("You are VOO in {{current_mode}} mode.", Mode::Debug),
+            "You are VOO in Debug mode."
+        );
+        assert_eq!(render_prompt("No placeholder", Mode::Ask), "No placeholder");
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use super::agent::SystemPrompt;
+
+// Instruction files looked for at the project root, injected in this order.
+pub const FILES: &[&str] = &["VOO.md", "AGENTS.md", ".voo/instructions.md"];
+// Keeps an oversized file from crowding out the conversation.
+const MAX_CHARS: usize = 32 * 1024;
+const START: &str = "<project_instructions>";
+const END: &str = "</project_instructions>";
+
+// The modification time and size of each file, None for missing ones.
+type Fingerprint = Vec<Option<(SystemTime, u64)>>;
+
+#[derive(Debug, Default)]
+struct State {
+    fingerprint: Option<Fingerprint>,
+    section: String,
+}
+
+// Project-specific instructions from the files at `root`, kept in a section
+// of the system prompt of their own that is replaced whenever one of the
+// files changes.
+#[derive(Debug)]
+pub struct ProjectContext {
+    root: PathBuf,
+    state: Mutex<State>,
+}
+
+impl ProjectContext {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // The instruction files that exist at the root.
+    pub fn files(&self) -> Vec<PathBuf> {
+        FILES
+            .iter()
+            .map(|file| self.root.join(file))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    // The section for the system prompt, empty without instruction files.
+    // The files are only read again once they changed.
+    pub fn section(&self) -> String {
+        let fingerprint = FILES
+            .iter()
+            .map(|file| {
+                let metadata = std::fs::metadata(self.root.join(file)).ok()?;
+                Some((metadata.modified().ok()?, metadata.len()))
+            })
+            .collect::<Fingerprint>();
+
+        let mut state = self.state.lock().unwrap();
+        if state.fingerprint.as_ref() != Some(&fingerprint) {
+            state.section = render(&self.root);
+            state.fingerprint = Some(fingerprint);
+        }
+
+        state.section.clone()
+    }
+
+    // `prompt` with its project section replaced by the current one, None when
+    // it is up to date.
+    pub fn apply(&self, prompt: &SystemPrompt) -> Option<SystemPrompt> {
+        let section = self.section();
+        let base = strip(&prompt.text);
+        let text = match section.is_empty() {
+            true => base,
+            false => format!("{}\n\n{}", base, section),
+        };
+
+        if text == prompt.text {
+            return None;
+        }
+        Some(SystemPrompt {
+            version: prompt.version,
+            text,
+        })
+    }
+}
+
+fn render(root: &Path) -> String {
+    let mut files = vec![];
+    for file in FILES {
+        let Ok(content) = std::fs::read_to_string(root.join(file)) else {
+            continue;
+        };
+        let content = content.trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        let content = match content.char_indices().nth(MAX_CHARS) {
+            Some((end, _)) => format!("{}\n[truncated, {} is too long]", &content[..end], file),
+            None => content.to_string(),
+        };
+        files.push(format!("# {}\n{}", file, content));
+    }
+
+    if files.is_empty() {
+        return String::new();
+    }
+    format!(
+        "{}\nThe project you work on comes with these instructions. Follow them unless the user says otherwise.\n\n{}\n{}",
+        START,
+        files.join("\n\n"),
+        END
+    )
+}
+
+// The prompt without its project section.
+fn strip(text: &str) -> String {
+    let Some(start) = text.find(START) else {
+        return text.to_string();
+    };
+    let end = text[start..]
+        .find(END)
+        .map(|end| start + end + END.len())
+        .unwrap_or(text.len());
+
+    format!("{}{}", text[..start].trim_end(), &text[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_context() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join(".voo")).unwrap();
+        let context = ProjectContext::new(dir);
+        let prompt = SystemPrompt {
+            version: 3,
+            text: "You are voo.".to_string(),
+        };
+        assert_eq!(context.apply(&prompt), None);
+
+        std::fs::write(dir.join("AGENTS.md"), "Run cargo test before committing.\n").unwrap();
+        std::fs::write(dir.join(".voo/instructions.md"), "Use tabs.").unwrap();
+        let applied = context.apply(&prompt).unwrap();
+        assert_eq!(applied.version, 3);
+        assert!(
+            applied
+                .text
+                .starts_with("You are voo.\n\n<project_instructions>\n")
+        );
+        assert!(applied.text.contains(
+            "# AGENTS.md\nRun cargo test before committing.\n\n# .voo/instructions.md\nUse tabs.\n</project_instructions>"
+        ));
+        assert_eq!(context.apply(&applied), None);
+        assert_eq!(context.files().len(), 2);
+
+        // A changed file replaces the section instead of adding another one.
+        std::fs::write(dir.join("AGENTS.md"), "Run just test instead.\n").unwrap();
+        let changed = context.apply(&applied).unwrap();
+        assert_eq!(changed.text.matches("<project_instructions>").count(), 1);
+        assert!(changed.text.contains("Run just test instead."));
+        assert!(!changed.text.contains("cargo test"));
+
+        std::fs::remove_file(dir.join("AGENTS.md")).unwrap();
+        std::fs::remove_dir_all(dir.join(".voo")).unwrap();
+        assert_eq!(context.apply(&changed).unwrap().text, "You are voo.");
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    agent::AgentError,
+    tools::{Tool, ToolFilter},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCategory {
+    #[default]
+    Read,
+    Search,
+    Edit,
+    Execute,
+    // Hands work to another agent.
+    Delegate,
+}
+
+impl Display for ToolCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolCategory::Read => write!(f, "read"),
+            ToolCategory::Search => write!(f, "search"),
+            ToolCategory::Edit => write!(f, "edit"),
+            ToolCategory::Execute => write!(f, "execute"),
+            ToolCategory::Delegate => write!(f, "delegate"),
+        }
+    }
+}
+
+// How much harm a call can do: none, workspace data sent to another host,
+// changes to files that a checkpoint or git can undo, or anything a shell
+// command can do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DangerLevel {
+    #[default]
+    Safe,
+    External,
+    Mutating,
+    Dangerous,
+}
+
+impl Display for DangerLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DangerLevel::Safe => write!(f, "safe"),
+            DangerLevel::External => write!(f, "external"),
+            DangerLevel::Mutating => write!(f, "mutating"),
+            DangerLevel::Dangerous => write!(f, "dangerous"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ToolMetadata {
+    pub category: ToolCategory,
+    pub danger: DangerLevel,
+}
+
+impl ToolMetadata {
+    pub fn new(category: ToolCategory, danger: DangerLevel) -> Self {
+        Self { category, danger }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisteredTool {
+    pub tool: Arc<dyn Tool>,
+    pub metadata: ToolMetadata,
+    pub enabled: bool,
+}
+
+// The tools of an agent by name. A disabled tool stays registered, it is only
+// left out of what the client offers the model until it is enabled again.
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, RegisteredTool>,
+}
+
+impl ToolRegistry {
+    pub fn register(&mut self, tool: Arc<dyn Tool>, metadata: ToolMetadata, enabled: bool) {
+        self.tools.insert(
+            tool.name().to_string(),
+            RegisteredTool {
+                tool,
+                metadata,
+                enabled,
+            },
+        );
+    }
+
+    // The tool, enabled or not.
+    pub fn get(&self, name: &str) -> Option<&RegisteredTool> {
+        self.tools.get(name)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|tool| tool.enabled)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), AgentError> {
+        match self.tools.get_mut(name) {
+            Some(tool) => {
+                tool.enabled = enabled;
+                Ok(())
+            }
+            None => Err(AgentError::UserInputError(Some(format!(
+                "Unknown tool: {}",
+                name
+            )))),
+        }
+    }
+
+    // Every registered tool, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredTool> {
+        self.tools.values()
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &RegisteredTool> {
+        self.iter().filter(|tool| tool.enabled)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    // Narrows the filter of the mode down to the enabled tools.
+    pub fn filter(&self, mode: &ToolFilter) -> ToolFilter {
+        if self.iter().all(|tool| tool.enabled) {
+            return mode.clone();
+        }
+
+        ToolFilter::only(
+            self.enabled()
+                .map(|tool| tool.tool.name().to_string())
+                .filter(|name| mode.allows(name))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use async_trait::async_trait;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::models::tools::{ToolDefinition, ToolError, ToolOutput};
+
+    #[derive(Debug)]
+    struct NamedTool(ToolDefinition);
+
+    impl Display for NamedTool {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0.name)
+        }
+    }
+
+    #[async_trait]
+    impl Tool for NamedTool {
+        async fn exec(&self, _input: Value) -> Result<ToolOutput, ToolError> {
+            Ok(ToolOutput::Text(String::new()))
+        }
+
+        fn name(&self) -> &str {
+            &self.0.name
+        }
+
+        fn description(&self) -> &str {
+            &self.0.description
+        }
+
+        fn tool_definition(&self) -> &ToolDefinition {
+            &self.0
+        }
+    }
+
+    pub(crate) fn tool(name: &str) -> Arc<dyn Tool> {
+        Arc::new(NamedTool(ToolDefinition {
+            name: name.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_enable_and_disable() {
+        let mut registry = ToolRegistry::default();
+        registry.register(tool("read_file"), ToolMetadata::default(), true);
+        registry.register(
+            tool("run_command"),
+            ToolMetadata::new(ToolCategory::Execute, DangerLevel::Dangerous),
+            true,
+        );
+        registry.register(tool("grep"), ToolMetadata::default(), true);
+
+        let mode = ToolFilter::only(vec!["read_file".to_string(), "run_command".to_string()]);
+        assert_eq!(registry.filter(&mode), mode);
+        assert_eq!(registry.filter(&ToolFilter::all()), ToolFilter::all());
+
+        registry.set_enabled("run_command", false).unwrap();
+        assert!(!registry.is_enabled("run_command"));
+        assert_eq!(
+            registry.filter(&mode),
+            ToolFilter::only(vec!["read_file".to_string()])
+        );
+        assert_eq!(
+            registry.filter(&ToolFilter::all()),
+            ToolFilter::only(vec!["grep".to_string(), "read_file".to_string()])
+        );
+        assert_eq!(
+            registry.get("run_command").unwrap().metadata.danger,
+            DangerLevel::Dangerous
+        );
+
+        registry.set_enabled("run_command", true).unwrap();
+        assert!(registry.is_enabled("run_command"));
+        assert!(registry.set_enabled("rm", false).is_err());
+    }
+}
@@ -1,22 +1,132 @@
-use std::fmt::{Debug, Display};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    sync::Arc,
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum ToolError {
+    #[error("File not found: {0}")]
     FileNotFound(String),
+    #[error("List file error: {0}")]
     ListFile(String),
+    #[error("Tool error: {0}")]
     ToolError(String),
+    #[error("Timed out after {}s", .0.as_secs())]
+    Timeout(Duration),
+    #[error("Cancelled by the user")]
+    Cancelled,
 }
 
-impl Display for ToolError {
+// What a tool hands back to the model. Errors the model should see and react
+// to, such as a declined edit, are outputs rather than a ToolError.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ToolOutput {
+    Text(String),
+    Json(Value),
+    Binary { mime: String, data: Vec<u8> },
+    Error(String),
+}
+
+impl ToolOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error(message.into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ToolOutput::Text(text) => text.is_empty(),
+            ToolOutput::Json(value) => value.is_null(),
+            ToolOutput::Binary { data, .. } => data.is_empty(),
+            ToolOutput::Error(_) => false,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, ToolOutput::Error(_))
+    }
+
+    // The output as JSON, text becomes a JSON string.
+    pub fn to_json(&self) -> Value {
+        match self {
+            ToolOutput::Text(text) => Value::from(text.as_str()),
+            ToolOutput::Json(value) => value.clone(),
+            ToolOutput::Binary { .. } => Value::from(self.to_string()),
+            ToolOutput::Error(message) => serde_json::json!({ "error": message }),
+        }
+    }
+
+    // The object a function response carries, providers expect an object
+    // rather than a bare value.
+    pub fn to_response(&self) -> Value {
+        match self {
+            ToolOutput::Json(value) if value.is_object() => value.clone(),
+            ToolOutput::Error(_) => self.to_json(),
+            _ => serde_json::json!({ "output": self.to_json() }),
+        }
+    }
+
+    // The output with escape sequences and control characters taken out of
+    // its text, also of the strings in Json, see `normalize`.
+    pub fn normalized(self) -> Self {
+        fn normalize_value(value: Value) -> Value {
+            match value {
+                Value::String(text) => Value::String(normalize(&text)),
+                Value::Array(values) => {
+                    Value::Array(values.into_iter().map(normalize_value).collect())
+                }
+                Value::Object(entries) => Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, normalize_value(value)))
+                        .collect(),
+                ),
+                value => value,
+            }
+        }
+
+        match self {
+            ToolOutput::Text(text) => ToolOutput::Text(normalize(&text)),
+            ToolOutput::Json(value) => ToolOutput::Json(normalize_value(value)),
+            ToolOutput::Error(message) => ToolOutput::Error(normalize(&message)),
+            binary @ ToolOutput::Binary { .. } => binary,
+        }
+    }
+
+    // Applies `f` to the textual outputs, Json is turned into text first.
+    pub fn map_text(self, f: impl FnOnce(String) -> String) -> Self {
+        match self {
+            ToolOutput::Text(text) => ToolOutput::Text(f(text)),
+            ToolOutput::Json(value) => ToolOutput::Text(f(value.to_string())),
+            ToolOutput::Error(message) => ToolOutput::Error(f(message)),
+            binary @ ToolOutput::Binary { .. } => binary,
+        }
+    }
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        ToolOutput::Text(text)
+    }
+}
+
+impl Display for ToolOutput {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ToolError::FileNotFound(path) => write!(f, "File not found: {}", path),
-            ToolError::ListFile(path) => write!(f, "List file error: {}", path),
-            ToolError::ToolError(msg) => write!(f, "Tool error: {}", msg),
+            ToolOutput::Text(text) => write!(f, "{}", text),
+            ToolOutput::Json(value) => write!(f, "{}", value),
+            ToolOutput::Binary { mime, data } => write!(f, "[{} bytes of {}]", data.len(), mime),
+            ToolOutput::Error(message) => write!(f, "Error: {}", message),
         }
     }
 }
@@ -26,14 +136,106 @@ pub struct ToolNameInput {
     pub name: String,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct ToolContext {
+    pub env: HashMap<String, String>,
+}
+
+// The tools a client advertises to the model, every registered tool unless
+// restricted to a list of names.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolFilter {
+    only: Option<Vec<String>>,
+}
+
+impl ToolFilter {
+    pub fn all() -> Self {
+        Self { only: None }
+    }
+
+    pub fn only(names: Vec<String>) -> Self {
+        Self { only: Some(names) }
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        match &self.only {
+            Some(names) => names.iter().any(|allowed| allowed == name),
+            None => true,
+        }
+    }
+
+    pub fn apply(&self, definitions: &[ToolDefinition]) -> Vec<ToolDefinition> {
+        definitions
+            .iter()
+            .filter(|definition| self.allows(&definition.name))
+            .cloned()
+            .collect()
+    }
+}
+
+// Adds workspace specific hints to a tool description each time the tools are
+// sent, without touching the tool itself.
+pub trait ToolDescriptionDecorator: Debug + Send + Sync {
+    fn name(&self) -> &str;
+    // Text appended to the description, None leaves the tool alone.
+    fn hint(&self, definition: &ToolDefinition) -> Option<String>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ToolDecorators {
+    decorators: Vec<Arc<dyn ToolDescriptionDecorator>>,
+}
+
+impl ToolDecorators {
+    pub fn new(decorators: Vec<Arc<dyn ToolDescriptionDecorator>>) -> Self {
+        Self { decorators }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decorators.is_empty()
+    }
+
+    pub fn apply(&self, definitions: Vec<ToolDefinition>) -> Vec<ToolDefinition> {
+        definitions
+            .into_iter()
+            .map(|mut definition| {
+                let hints = self
+                    .decorators
+                    .iter()
+                    .filter_map(|decorator| decorator.hint(&definition))
+                    .collect::<Vec<String>>();
+                if !hints.is_empty() {
+                    definition.description =
+                        format!("{}\n\n{}", definition.description, hints.join("\n"));
+                }
+                definition
+            })
+            .collect()
+    }
+}
+
 #[async_trait]
 pub trait Tool: Display + Debug + Send + Sync {
-    async fn exec(&self, input: Value) -> Result<String, ToolError>;
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError>;
+
+    async fn exec_with_context(
+        &self,
+        input: Value,
+        _context: &ToolContext,
+    ) -> Result<ToolOutput, ToolError> {
+        self.exec(input).await
+    }
+
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     fn tool_definition(&self) -> &ToolDefinition;
 }
 
+pub trait OutputProcessor: Debug + Send + Sync {
+    fn name(&self) -> &str;
+    fn process(&self, output: String) -> String;
+}
+
 pub type FunctionDeclaration = Vec<ToolDefinition>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -42,6 +244,47 @@ pub struct ToolDefinition {
     pub name: String,
     pub description: String,
     pub parameters: Parameters,
+    // No provider takes examples in its declarations, they reach the model
+    // through the description instead.
+    #[serde(default, skip_serializing)]
+    pub examples: Vec<ToolExample>,
+}
+
+impl ToolDefinition {
+    pub fn examples_text(&self) -> Option<String> {
+        if self.examples.is_empty() {
+            return None;
+        }
+
+        let examples = self
+            .examples
+            .iter()
+            .map(|example| {
+                format!(
+                    "Call: {}({})\nResult: {}",
+                    self.name, example.args, example.result
+                )
+            })
+            .collect::<Vec<String>>();
+        Some(format!("Examples:\n{}", examples.join("\n")))
+    }
+}
+
+// A sample call and the output it produces, showing the model how the
+// arguments of a tool fit together.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolExample {
+    pub args: Value,
+    pub result: String,
+}
+
+impl ToolExample {
+    pub fn new(args: Value, result: &str) -> Self {
+        Self {
+            args,
+            result: result.to_string(),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -74,3 +317,60 @@ pub struct Items {
     #[serde(rename = "type")]
     pub r#type: String,
 }
+
+// Strips escape sequences, unifies line endings and drops control characters
+// other than tabs and newlines.
+pub fn normalize(input: &str) -> String {
+    strip_ansi(input)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect()
+}
+
+pub fn strip_ansi(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: ESC [ parameters... final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ESC ] ... terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // nF escapes such as charset selection: ESC ( B
+            Some(c) if (' '..='/').contains(&c) => {
+                for c in chars.by_ref() {
+                    if !(' '..='/').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    output
+}
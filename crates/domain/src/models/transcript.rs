@@ -0,0 +1,269 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    agent::{AgentError, Content, SystemPrompt},
+    usage::TokenUsage,
+};
+use crate::storage::Storage;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    SessionStarted {
+        system_prompt: Option<SystemPrompt>,
+    },
+    UserMessage {
+        text: String,
+    },
+    ModelResponse {
+        content: Content,
+    },
+    ToolCall {
+        name: String,
+        args: Value,
+    },
+    ToolResult {
+        name: String,
+        output: String,
+    },
+    Error {
+        message: String,
+    },
+    Handoff {
+        provider: String,
+    },
+    // Closes the history copied from `parent`, the branch goes on from here.
+    Forked {
+        parent: String,
+        title: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub at: i64,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptSummary {
+    pub id: String,
+    pub started_at: i64,
+    pub turns: usize,
+    pub first_message: Option<String>,
+    pub prompt_version: Option<u32>,
+    // Set on sessions forked from another one.
+    pub parent: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Transcript {
+    id: String,
+    storage: Arc<dyn Storage>,
+}
+
+impl Transcript {
+    pub fn create(storage: Arc<dyn Storage>) -> Self {
+        let id = format!(
+            "{}-{}",
+            now(),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        Self { id, storage }
+    }
+
+    // A recorded session, to go on recording into.
+    pub fn open(storage: Arc<dyn Storage>, id: &str) -> Result<Self, AgentError> {
+        storage.load(id)?;
+        Ok(Self {
+            id: id.to_string(),
+            storage,
+        })
+    }
+
+    // Starts a new session with a copy of this one's history. This session
+    // stays as it was and becomes the parent of the returned one.
+    pub fn fork(&self, title: Option<String>) -> Result<Self, AgentError> {
+        let child = Self::create(self.storage.clone());
+        for entry in self.load()? {
+            self.storage.record(&child.id, &entry)?;
+        }
+        child.record(SessionEvent::Forked {
+            parent: self.id.clone(),
+            title,
+        })?;
+
+        Ok(child)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn record(&self, event: SessionEvent) -> Result<(), AgentError> {
+        let entry = TranscriptEntry { at: now(), event };
+        self.storage.record(&self.id, &entry)
+    }
+
+    pub fn load(&self) -> Result<Vec<TranscriptEntry>, AgentError> {
+        self.storage.load(&self.id)
+    }
+
+    pub fn rewrite(&self, entries: &[TranscriptEntry]) -> Result<(), AgentError> {
+        self.storage.rewrite(&self.id, entries)
+    }
+
+    pub fn record_usage(&self, usage: &TokenUsage) -> Result<(), AgentError> {
+        self.storage.record_usage(&self.id, usage)
+    }
+}
+
+pub fn summarize(id: &str, entries: &[TranscriptEntry]) -> TranscriptSummary {
+    let user_messages = entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            SessionEvent::UserMessage { text } => Some(text.clone()),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    let forked = entries.iter().rev().find_map(|entry| match &entry.event {
+        SessionEvent::Forked { parent, title } => Some((parent.clone(), title.clone())),
+        _ => None,
+    });
+    let (parent, title) = forked.unzip();
+
+    TranscriptSummary {
+        id: id.to_string(),
+        started_at: entries.first().map(|entry| entry.at).unwrap_or_default(),
+        turns: user_messages.len(),
+        first_message: user_messages.into_iter().next(),
+        prompt_version: system_prompt(entries).map(|prompt| prompt.version),
+        parent,
+        title: title.flatten(),
+    }
+}
+
+pub fn system_prompt(entries: &[TranscriptEntry]) -> Option<&SystemPrompt> {
+    entries.iter().find_map(|entry| match &entry.event {
+        SessionEvent::SessionStarted { system_prompt } => system_prompt.as_ref(),
+        _ => None,
+    })
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{files::FileStorage, sqlite::SqliteSessionStore};
+
+    fn check_storage(storage: Arc<dyn Storage>) {
+        let transcript = Transcript::create(storage.clone());
+
+        transcript
+            .record(SessionEvent::SessionStarted {
+                system_prompt: Some(SystemPrompt {
+                    version: 2,
+                    text: "be helpful".to_string(),
+                }),
+            })
+            .unwrap();
+        transcript
+            .record(SessionEvent::UserMessage {
+                text: "hello".to_string(),
+            })
+            .unwrap();
+        transcript
+            .record(SessionEvent::ToolCall {
+                name: "read_file".to_string(),
+                args: serde_json::json!({ "path": "src/main.rs" }),
+            })
+            .unwrap();
+
+        let entries = transcript.load().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[1].event,
+            SessionEvent::UserMessage {
+                text: "hello".to_string()
+            }
+        );
+
+        let sessions = storage.sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].turns, 1);
+        assert_eq!(sessions[0].first_message.as_deref(), Some("hello"));
+        assert_eq!(sessions[0].prompt_version, Some(2));
+        assert_eq!(sessions[0].parent, None);
+
+        assert!(storage.load("../etc/passwd").is_err());
+
+        let mut redacted = entries.clone();
+        redacted[1].event = SessionEvent::UserMessage {
+            text: "[redacted]".to_string(),
+        };
+        transcript.rewrite(&redacted).unwrap();
+        assert_eq!(transcript.load().unwrap(), redacted);
+        assert_eq!(
+            storage.sessions().unwrap()[0].first_message.as_deref(),
+            Some("[redacted]")
+        );
+
+        let usage = TokenUsage {
+            requests: 2,
+            prompt_tokens: 900,
+            candidate_tokens: 100,
+            total_tokens: 1000,
+        };
+        transcript.record_usage(&usage).unwrap();
+        transcript.record_usage(&usage).unwrap();
+        assert_eq!(storage.usage().unwrap().total_tokens, 2000);
+
+        let branch = transcript.fork(Some("try sqlite".to_string())).unwrap();
+        branch
+            .record(SessionEvent::UserMessage {
+                text: "use sqlite".to_string(),
+            })
+            .unwrap();
+        assert_eq!(transcript.load().unwrap(), redacted);
+        assert_eq!(branch.load().unwrap().len(), 5);
+        let sessions = storage.sessions().unwrap();
+        let forked = sessions
+            .iter()
+            .find(|session| session.id == branch.id())
+            .unwrap();
+        assert_eq!(forked.parent.as_deref(), Some(transcript.id()));
+        assert_eq!(forked.title.as_deref(), Some("try sqlite"));
+        assert_eq!(forked.turns, 2);
+        assert_eq!(forked.first_message.as_deref(), Some("[redacted]"));
+
+        let reopened = Transcript::open(storage.clone(), branch.id()).unwrap();
+        assert_eq!(reopened.load().unwrap().len(), 5);
+        assert!(Transcript::open(storage, "missing").is_err());
+    }
+
+    #[test]
+    fn test_record_and_load() {
+        let dir = std::env::temp_dir().join(format!("voo-transcript-{}", uuid::Uuid::new_v4()));
+        check_storage(Arc::new(FileStorage::new(
+            &dir.join("sessions"),
+            &dir.join("usage.jsonl"),
+        )));
+        check_storage(Arc::new(SqliteSessionStore::in_memory().unwrap()));
+
+        _ = std::fs::remove_dir_all(dir);
+    }
+}
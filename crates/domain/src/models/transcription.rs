@@ -0,0 +1,18 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use super::agent::AgentError;
+
+#[async_trait]
+pub trait Transcriber: Debug + Send + Sync {
+    fn model(&self) -> String;
+    // Turns the audio into text. `instructions` ask for more than a plain
+    // transcript, e.g. speaker labels or meeting notes.
+    async fn transcribe(
+        &self,
+        mime: &str,
+        audio: &[u8],
+        instructions: Option<&str>,
+    ) -> Result<String, AgentError>;
+}
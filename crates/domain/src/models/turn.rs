@@ -0,0 +1,552 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::{StreamExt, future::join_all};
+use tokio::sync::Semaphore;
+
+use super::{
+    agent::{Agent, AgentError, AgentRole, Content, FunctionCall, Part},
+    events::AgentEvent,
+    tool_registry::DangerLevel,
+    tools::{ToolContext, ToolError, ToolOutput},
+};
+
+// Requests one turn may send before it is given up, each round of tool calls
+// takes one.
+pub const MAX_STEPS: usize = 25;
+pub const DEFAULT_TOOL_CONCURRENCY: usize = 4;
+// Longer than run_command and ask_remote_agent wait themselves.
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(900);
+
+// How many calls of one response run at once, and how long one may take.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToolLimits {
+    pub concurrency: usize,
+    pub timeout: Duration,
+}
+
+impl Default for ToolLimits {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_TOOL_CONCURRENCY,
+            timeout: DEFAULT_TOOL_TIMEOUT,
+        }
+    }
+}
+
+// Lets a frontend follow the calls of `Agent::execute_tools` while they run,
+// e.g. to echo them, and cancel them.
+#[async_trait]
+pub trait ToolCallHooks: Send + Sync {
+    // The call got its turn and is about to run.
+    fn started(&self, _call: &FunctionCall) {}
+
+    fn finished(&self, _call: &FunctionCall, _output: &ToolOutput) {}
+
+    // Resolves when the calls in flight should be given up, never by default.
+    async fn cancelled(&self) {
+        std::future::pending::<()>().await
+    }
+}
+
+// What a turn produced: the final answer, every response on the way and the
+// results of the tool calls they made.
+#[derive(Debug, Default)]
+pub struct AgentTurn {
+    pub answer: String,
+    pub responses: Vec<Content>,
+    pub tool_results: Vec<(FunctionCall, ToolOutput)>,
+    // Set when the frontend stopped the turn before the model answered.
+    pub stopped: bool,
+}
+
+// How a frontend takes part in a turn. The defaults stream the response and
+// run the tool calls with `Agent::execute_tools`.
+#[async_trait]
+pub trait TurnHandler: Send + Sync {
+    // Sends one request, the input is empty when it only carries the results
+    // of the previous tool calls. None stops the turn, e.g. when the user
+    // cancelled it.
+    async fn ask(&self, agent: &Agent, input: &str) -> Result<Option<Vec<Content>>, AgentError> {
+        agent.stream(input).await.map(|content| Some(vec![content]))
+    }
+
+    // The outputs of the calls of one response, in the order of the calls.
+    async fn execute(
+        &self,
+        agent: &Agent,
+        calls: &[FunctionCall],
+    ) -> Result<Vec<ToolOutput>, AgentError> {
+        let context = agent.tool_context().await;
+        Ok(agent
+            .execute_tools(calls, &context, &DefaultTurnHandler)
+            .await)
+    }
+
+    async fn on_response(&self, _agent: &Agent, _response: &Content) {}
+
+    async fn on_tool_result(&self, _agent: &Agent, _call: &FunctionCall, _output: &ToolOutput) {}
+
+    // Checked after every round of tool calls, false stops the turn.
+    fn should_continue(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DefaultTurnHandler;
+
+impl TurnHandler for DefaultTurnHandler {}
+
+impl ToolCallHooks for DefaultTurnHandler {}
+
+impl Agent {
+    // Sends one request and collects the streamed response, emitting the text
+    // as ModelChunk events while it arrives.
+    pub async fn stream(&self, input: &str) -> Result<Content, AgentError> {
+        let mut stream = self.client().ask_stream(input).await?;
+        let mut parts = vec![];
+        let mut answer = String::new();
+
+        while let Some(part) = stream.next().await {
+            let part = part?;
+            match &part.text {
+                Some(text) if part.is_thought() => self.emit(AgentEvent::ModelChunk {
+                    text: text.clone(),
+                    thought: true,
+                }),
+                Some(text) if part.function_call.is_none() => {
+                    self.emit(AgentEvent::ModelChunk {
+                        text: text.clone(),
+                        thought: false,
+                    });
+                    answer.push_str(text);
+                    continue;
+                }
+                _ => {}
+            }
+
+            parts.push(part);
+        }
+
+        if !answer.is_empty() {
+            parts.push(Part::new(&answer));
+        }
+        Ok(Content::new(parts, "model"))
+    }
+
+    // Runs the calls of one response. Independent calls run concurrently, up
+    // to the concurrency of the tool limits, and their outputs keep the order
+    // of the calls. Every call gets a normalized output, a failed, timed out
+    // or cancelled tool reports its error to the model rather than ending the
+    // turn.
+    pub async fn execute_tools(
+        &self,
+        calls: &[FunctionCall],
+        context: &ToolContext,
+        hooks: &dyn ToolCallHooks,
+    ) -> Vec<ToolOutput> {
+        let permits = self.tool_limits().concurrency.max(1);
+        let semaphore = Semaphore::new(permits);
+
+        let calls = calls
+            .iter()
+            .map(|call| self.execute_tool(call, context, hooks, &semaphore, permits));
+        join_all(calls).await
+    }
+
+    // Safe tools share the permits. A tool that changes files or runs commands
+    // takes all of them, so it waits for the calls before it and never races a
+    // read or asks for approval while another call does. The semaphore is
+    // fair, calls start in the order the model made them.
+    async fn execute_tool(
+        &self,
+        call: &FunctionCall,
+        context: &ToolContext,
+        hooks: &dyn ToolCallHooks,
+        semaphore: &Semaphore,
+        permits: usize,
+    ) -> ToolOutput {
+        // The client only offers the tools of the current mode, but the model
+        // may still name another one.
+        if !self.allows_tool(&call.name).await {
+            return ToolOutput::error(format!(
+                "The {} tool is not available in {} mode",
+                call.name,
+                self.mode().await
+            ));
+        }
+        let Some((tool, metadata)) = self
+            .tools()
+            .lock()
+            .await
+            .get(&call.name)
+            .map(|registered| (registered.tool.clone(), registered.metadata))
+        else {
+            return ToolOutput::error(format!("Unknown tool: {}", call.name));
+        };
+
+        let needed = match metadata.danger {
+            DangerLevel::Safe => 1,
+            _ => permits,
+        };
+        // The semaphore is never closed.
+        let _permit = semaphore.acquire_many(needed as u32).await.ok();
+        hooks.started(call);
+
+        let timeout = self.tool_limits().timeout;
+        let result = tokio::select! {
+            result = tokio::time::timeout(timeout, tool.exec_with_context(call.args.clone(), context)) => {
+                result.unwrap_or(Err(ToolError::Timeout(timeout)))
+            }
+            _ = hooks.cancelled() => Err(ToolError::Cancelled),
+        };
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => ToolOutput::error(format!("Error executing tool: {}", e)),
+        }
+        .normalized();
+        hooks.finished(call, &output);
+
+        output
+    }
+
+    pub async fn run_turn(&self, input: &str) -> Result<AgentTurn, AgentError> {
+        self.run_turn_with(input, &DefaultTurnHandler).await
+    }
+
+    // Asks, runs the tool calls of the responses and feeds their results back
+    // until the model answers without calling a tool.
+    pub async fn run_turn_with(
+        &self,
+        input: &str,
+        handler: &dyn TurnHandler,
+    ) -> Result<AgentTurn, AgentError> {
+        if !input.is_empty() {
+            self.emit(AgentEvent::UserMessage {
+                text: input.to_string(),
+            });
+        }
+        let result = self.turn(input, handler).await;
+        match &result {
+            Ok(turn) if !turn.stopped => self.emit(AgentEvent::TurnComplete {
+                answer: turn.answer.clone(),
+            }),
+            Ok(_) => {}
+            Err(e) => self.emit(AgentEvent::Error {
+                message: e.to_string(),
+            }),
+        }
+
+        result
+    }
+
+    async fn turn(&self, input: &str, handler: &dyn TurnHandler) -> Result<AgentTurn, AgentError> {
+        self.refresh_project_context().await?;
+        let mut turn = AgentTurn::default();
+        let mut input = input.to_string();
+
+        for _ in 0..self.max_steps() {
+            let Some(responses) = handler.ask(self, &input).await? else {
+                turn.stopped = true;
+                return Ok(turn);
+            };
+            input.clear();
+
+            let mut texts = vec![];
+            let mut used_tools = false;
+            for response in responses {
+                handler.on_response(self, &response).await;
+
+                let calls = response
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.function_call.clone())
+                    .collect::<Vec<FunctionCall>>();
+                if calls.is_empty() {
+                    texts.extend(
+                        response
+                            .parts
+                            .iter()
+                            .filter_map(|part| part.answer().cloned()),
+                    );
+                } else {
+                    used_tools = true;
+                    for call in &calls {
+                        self.emit(AgentEvent::ToolCallStarted {
+                            name: call.name.clone(),
+                            args: call.args.clone(),
+                        });
+                    }
+                    // A failed round is reported to the model, which can
+                    // retry or answer without the tools.
+                    match handler.execute(self, &calls).await {
+                        Ok(outputs) => {
+                            for (call, output) in calls.into_iter().zip(outputs) {
+                                self.emit(AgentEvent::ToolCallFinished {
+                                    name: call.name.clone(),
+                                    output: output.clone(),
+                                });
+                                handler.on_tool_result(self, &call, &output).await;
+                                _ = self.client().add_tool_result(&call.name, &output).await;
+                                turn.tool_results.push((call, output));
+                            }
+                        }
+                        Err(e) => {
+                            let err = format!("Error performing function call: {}", e);
+                            self.emit(AgentEvent::Error {
+                                message: err.clone(),
+                            });
+                            _ = self.client().add_system_prompt(&err, AgentRole::Tool).await;
+                        }
+                    }
+                }
+                turn.responses.push(response);
+            }
+
+            if !used_tools {
+                turn.answer = texts.join("\n");
+                return Ok(turn);
+            }
+            if !handler.should_continue() {
+                turn.stopped = true;
+                return Ok(turn);
+            }
+        }
+
+        Err(AgentError::AgentError(Some(format!(
+            "The agent did not finish after {} steps",
+            self.max_steps()
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::Display,
+        sync::{Arc, Mutex},
+    };
+
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::models::{
+        agent::{AgentClient, Part},
+        tool_registry::{DangerLevel, ToolCategory, ToolMetadata},
+        tools::{Tool, ToolDefinition, ToolError},
+    };
+
+    // Answers with the scripted responses in order, then with "done".
+    #[derive(Debug, Default)]
+    struct ScriptedClient {
+        responses: Mutex<Vec<Content>>,
+        prompts: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl AgentClient for ScriptedClient {
+        async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            let mut responses = self.responses.lock().unwrap();
+            match responses.is_empty() {
+                true => Ok(vec![Content::new(vec![Part::new("done")], "model")]),
+                false => Ok(vec![responses.remove(0)]),
+            }
+        }
+
+        async fn add_tool(&self, _tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        async fn add_system_prompt(
+            &self,
+            prompt: &str,
+            _role: AgentRole,
+        ) -> Result<(), AgentError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct EchoTool(ToolDefinition);
+
+    impl Display for EchoTool {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0.name)
+        }
+    }
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+            if input["sleep"].as_bool() == Some(true) {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            match &input["json"] {
+                Value::Null => Ok(ToolOutput::text(input["text"].as_str().unwrap_or_default())),
+                value => Ok(ToolOutput::Json(value.clone())),
+            }
+        }
+
+        fn name(&self) -> &str {
+            &self.0.name
+        }
+
+        fn description(&self) -> &str {
+            &self.0.description
+        }
+
+        fn tool_definition(&self) -> &ToolDefinition {
+            &self.0
+        }
+    }
+
+    fn call(name: &str, text: &str) -> Content {
+        let part = Part {
+            function_call: Some(FunctionCall {
+                name: name.to_string(),
+                args: json!({ "text": text }),
+            }),
+            ..Default::default()
+        };
+        Content::new(vec![part], "model")
+    }
+
+    async fn agent(responses: Vec<Content>) -> (Agent, Arc<Mutex<Vec<String>>>) {
+        let prompts = Arc::new(Mutex::new(vec![]));
+        let client = ScriptedClient {
+            responses: Mutex::new(responses),
+            prompts: prompts.clone(),
+        };
+        let agent = Agent::new(client);
+        let tool = EchoTool(ToolDefinition {
+            name: "echo".to_string(),
+            ..Default::default()
+        });
+        agent
+            .add_tool(
+                Arc::new(tool),
+                ToolMetadata::new(ToolCategory::Read, DangerLevel::Safe),
+                true,
+            )
+            .await
+            .unwrap();
+
+        (agent, prompts)
+    }
+
+    #[tokio::test]
+    async fn test_run_turn() {
+        let (agent, prompts) = agent(vec![call("echo", "hello"), call("missing", "")]).await;
+
+        let turn = agent.run_turn("say hello").await.unwrap();
+        assert_eq!(turn.answer, "done");
+        assert!(!turn.stopped);
+        assert_eq!(turn.responses.len(), 3);
+        assert_eq!(turn.tool_results.len(), 2);
+        assert_eq!(turn.tool_results[0].1, ToolOutput::text("hello"));
+        assert_eq!(
+            turn.tool_results[1].1,
+            ToolOutput::error("The missing tool is not available in Code mode")
+        );
+        assert_eq!(
+            *prompts.lock().unwrap(),
+            vec![
+                "say hello",
+                "\"hello\"",
+                "",
+                "{\"error\":\"The missing tool is not available in Code mode\"}",
+                ""
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_turn_events() {
+        let (agent, _) = agent(vec![call("echo", "hello")]).await;
+        let events = Arc::new(Mutex::new(vec![]));
+        let observed = events.clone();
+        agent.observe(move |event: &AgentEvent| observed.lock().unwrap().push(event.clone()));
+
+        agent.run_turn("say hello").await.unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                AgentEvent::UserMessage {
+                    text: "say hello".to_string()
+                },
+                AgentEvent::ToolCallStarted {
+                    name: "echo".to_string(),
+                    args: json!({ "text": "hello" })
+                },
+                AgentEvent::ToolCallFinished {
+                    name: "echo".to_string(),
+                    output: ToolOutput::text("hello")
+                },
+                AgentEvent::ModelChunk {
+                    text: "done".to_string(),
+                    thought: false
+                },
+                AgentEvent::TurnComplete {
+                    answer: "done".to_string()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_tools() {
+        let (agent, _) = agent(vec![]).await;
+        let agent = agent.with_tool_limits(ToolLimits {
+            concurrency: 2,
+            timeout: Duration::from_millis(50),
+        });
+        let call = |args: Value| FunctionCall {
+            name: "echo".to_string(),
+            args,
+        };
+        let calls = [
+            call(json!({ "text": "\x1b[1mbold\x1b[0m\r\n" })),
+            call(json!({ "json": { "lines": ["\x1b[31mred\x1b[0m\r"] } })),
+            call(json!({ "sleep": true })),
+        ];
+
+        let context = agent.tool_context().await;
+        let outputs = agent
+            .execute_tools(&calls, &context, &DefaultTurnHandler)
+            .await;
+        assert_eq!(outputs[0], ToolOutput::text("bold\n"));
+        assert_eq!(outputs[1], ToolOutput::Json(json!({ "lines": ["red\n"] })));
+        assert!(
+            matches!(&outputs[2], ToolOutput::Error(message) if message.contains("Error executing tool"))
+        );
+        assert_eq!(
+            ToolOutput::error("\x1b[31mfailed\x1b[0m").normalized(),
+            ToolOutput::error("failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_turn_limits_steps() {
+        let (agent, _) = agent(vec![call("echo", "again"); 3]).await;
+        let agent = agent.with_max_steps(2);
+
+        let error = agent.run_turn("loop").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "AgentError: The agent did not finish after 2 steps"
+        );
+
+        struct StopAfterTools;
+        impl TurnHandler for StopAfterTools {
+            fn should_continue(&self) -> bool {
+                false
+            }
+        }
+        let turn = agent.run_turn_with("loop", &StopAfterTools).await.unwrap();
+        assert!(turn.stopped);
+        assert_eq!(turn.tool_results.len(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fmt::Display, ops::Add, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+// Tokens the provider reported for the requests of a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub candidate_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl Display for TokenUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} tokens ({} prompt, {} response) over {} requests",
+            self.total_tokens, self.prompt_tokens, self.candidate_tokens, self.requests
+        )
+    }
+}
+
+impl Add for TokenUsage {
+    type Output = TokenUsage;
+
+    fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            requests: self.requests + other.requests,
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            candidate_tokens: self.candidate_tokens + other.candidate_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    usage: Mutex<TokenUsage>,
+}
+
+impl UsageTracker {
+    // The total may be more than prompt and response together, e.g. with the
+    // thinking tokens of Gemini, so it is recorded as reported.
+    pub fn record(&self, prompt_tokens: u64, candidate_tokens: u64, total_tokens: u64) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.requests += 1;
+        usage.prompt_tokens += prompt_tokens;
+        usage.candidate_tokens += candidate_tokens;
+        usage.total_tokens += total_tokens.max(prompt_tokens + candidate_tokens);
+    }
+
+    pub fn usage(&self) -> TokenUsage {
+        *self.usage.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_tracker() {
+        let tracker = UsageTracker::default();
+        tracker.record(1200, 80, 1330);
+        tracker.record(1400, 20, 0);
+
+        assert_eq!(
+            tracker.usage(),
+            TokenUsage {
+                requests: 2,
+                prompt_tokens: 2600,
+                candidate_tokens: 100,
+                total_tokens: 2750,
+            }
+        );
+        assert_eq!(
+            tracker.usage().to_string(),
+            "2750 tokens (2600 prompt, 100 response) over 2 requests"
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use super::agent::AgentError;
+
+#[async_trait]
+pub trait VideoAnalyst: Debug + Send + Sync {
+    fn model(&self) -> String;
+    // Answers `question` about the video at `url`, which the model watches
+    // itself instead of the video being downloaded.
+    async fn ask(&self, url: &str, question: &str) -> Result<String, AgentError>;
+}
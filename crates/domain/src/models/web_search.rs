@@ -0,0 +1,20 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::agent::AgentError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+#[async_trait]
+pub trait SearchEngine: Debug + Send + Sync {
+    fn name(&self) -> String;
+    // Up to `limit` results for `query`, best first.
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, AgentError>;
+}
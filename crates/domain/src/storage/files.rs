@@ -0,0 +1,167 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Storage;
+use crate::models::{
+    agent::AgentError,
+    jsonl,
+    transcript::{self, TranscriptEntry, TranscriptSummary},
+    usage::TokenUsage,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UsageRecord {
+    session: String,
+    at: i64,
+    #[serde(flatten)]
+    usage: TokenUsage,
+}
+
+// A JSON lines file per session in `dir`, and the usage of all sessions in a
+// single JSON lines file. Listing the sessions reads every file.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+    usage: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(dir: &Path, usage: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            usage: usage.to_path_buf(),
+        }
+    }
+
+    fn path(&self, session: &str) -> Result<PathBuf, AgentError> {
+        let valid = !session.is_empty()
+            && session
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if !valid {
+            return Err(AgentError::UserInputError(Some(format!(
+                "Invalid session id: {}",
+                session
+            ))));
+        }
+
+        Ok(self.dir.join(format!("{}.jsonl", session)))
+    }
+}
+
+impl Storage for FileStorage {
+    fn record(&self, session: &str, entry: &TranscriptEntry) -> Result<(), AgentError> {
+        std::fs::create_dir_all(&self.dir).map_err(io_error)?;
+        append(&self.path(session)?, entry)
+    }
+
+    fn load(&self, session: &str) -> Result<Vec<TranscriptEntry>, AgentError> {
+        let content = std::fs::read(self.path(session)?).map_err(io_error)?;
+
+        jsonl::parse::<TranscriptEntry>(&content)
+            .map(|parsed| parsed.records)
+            .map_err(|e| AgentError::AgentError(Some(format!("{}: {}", session, e))))
+    }
+
+    // Replaced in a single rename, so a crash leaves the old or the new one.
+    fn rewrite(&self, session: &str, entries: &[TranscriptEntry]) -> Result<(), AgentError> {
+        let path = self.path(session)?;
+        let mut content = String::new();
+        for entry in entries {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        let tmp = path.with_extension("jsonl.tmp");
+        std::fs::create_dir_all(&self.dir).map_err(io_error)?;
+        std::fs::write(&tmp, content).map_err(io_error)?;
+        std::fs::rename(&tmp, &path).map_err(io_error)
+    }
+
+    fn sessions(&self) -> Result<Vec<TranscriptSummary>, AgentError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut summaries = vec![];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("jsonl") {
+                continue;
+            }
+
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            summaries.push(transcript::summarize(id, &self.load(id)?));
+        }
+
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.started_at));
+        Ok(summaries)
+    }
+
+    fn record_usage(&self, session: &str, usage: &TokenUsage) -> Result<(), AgentError> {
+        if let Some(parent) = self.usage.parent() {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+
+        let record = UsageRecord {
+            session: session.to_string(),
+            at: now(),
+            usage: *usage,
+        };
+        append(&self.usage, &record)
+    }
+
+    fn usage(&self) -> Result<TokenUsage, AgentError> {
+        let content = match std::fs::read(&self.usage) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(TokenUsage::default()),
+            Err(e) => return Err(io_error(e)),
+        };
+        let records = jsonl::parse::<UsageRecord>(&content).map_err(|e| {
+            AgentError::AgentError(Some(format!("{}: {}", self.usage.display(), e)))
+        })?;
+
+        Ok(records
+            .records
+            .iter()
+            .fold(TokenUsage::default(), |total, record| total + record.usage))
+    }
+}
+
+fn append(path: &Path, record: &impl Serialize) -> Result<(), AgentError> {
+    let mut line =
+        serde_json::to_string(record).map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(io_error)?;
+    file.write_all(line.as_bytes()).map_err(io_error)
+}
+
+fn io_error(e: std::io::Error) -> AgentError {
+    AgentError::AgentError(Some(e.to_string()))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
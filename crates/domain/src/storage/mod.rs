@@ -0,0 +1,36 @@
+use std::fmt::Debug;
+
+use crate::models::{
+    agent::AgentError,
+    transcript::{TranscriptEntry, TranscriptSummary},
+    usage::TokenUsage,
+};
+
+pub mod files;
+pub mod sqlite;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub name: String,
+    pub messages: usize,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+// Where the recorded sessions and their token usage are kept.
+pub trait Storage: Debug + Send + Sync + 'static {
+    fn record(&self, session: &str, entry: &TranscriptEntry) -> Result<(), AgentError>;
+
+    fn load(&self, session: &str) -> Result<Vec<TranscriptEntry>, AgentError>;
+
+    // Replaces the events of a recorded session, for redactions.
+    fn rewrite(&self, session: &str, entries: &[TranscriptEntry]) -> Result<(), AgentError>;
+
+    // The recorded sessions, the most recent first.
+    fn sessions(&self) -> Result<Vec<TranscriptSummary>, AgentError>;
+
+    fn record_usage(&self, session: &str, usage: &TokenUsage) -> Result<(), AgentError>;
+
+    // The usage of all sessions together.
+    fn usage(&self) -> Result<TokenUsage, AgentError>;
+}
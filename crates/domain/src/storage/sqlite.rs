@@ -0,0 +1,530 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::{SessionInfo, Storage};
+use crate::models::{
+    agent::{AgentError, Content},
+    conversation::{ConversationLock, ConversationStore, check_turn},
+    transcript::{self, SessionEvent, TranscriptEntry, TranscriptSummary},
+    usage::TokenUsage,
+};
+
+// Named sessions and their messages in a single SQLite database. As a
+// `Storage` it also keeps the recorded sessions and their token usage, with
+// the columns `voo sessions` lists kept up to date as events are recorded.
+#[derive(Debug, Clone)]
+pub struct SqliteSessionStore {
+    conn: Arc<Mutex<Connection>>,
+    // Where the lock files of the sessions go, None in memory.
+    locks: Option<PathBuf>,
+}
+
+impl SqliteSessionStore {
+    pub fn open(path: &Path) -> Result<Self, AgentError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        }
+
+        let locks = path.with_extension("locks");
+        Self::init(Connection::open(path).map_err(sql_error)?, Some(locks))
+    }
+
+    pub fn in_memory() -> Result<Self, AgentError> {
+        Self::init(Connection::open_in_memory().map_err(sql_error)?, None)
+    }
+
+    fn init(conn: Connection, locks: Option<PathBuf>) -> Result<Self, AgentError> {
+        conn.execute_batch(
+            r#"
+            PRAGMA foreign_keys = ON;
+            PRAGMA secure_delete = ON;
+            CREATE TABLE IF NOT EXISTS sessions (
+                name TEXT PRIMARY KEY,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session TEXT NOT NULL REFERENCES sessions (name) ON DELETE CASCADE,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS messages_session_idx ON messages (session, id);
+            CREATE TABLE IF NOT EXISTS transcripts (
+                id TEXT PRIMARY KEY,
+                started_at INTEGER NOT NULL,
+                turns INTEGER NOT NULL DEFAULT 0,
+                first_message TEXT,
+                prompt_version INTEGER,
+                parent TEXT,
+                title TEXT
+            );
+            CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transcript TEXT NOT NULL REFERENCES transcripts (id) ON DELETE CASCADE,
+                at INTEGER NOT NULL,
+                event TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS events_transcript_idx ON events (transcript, id);
+            CREATE TABLE IF NOT EXISTS usage (
+                transcript TEXT NOT NULL,
+                at INTEGER NOT NULL,
+                requests INTEGER NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                candidate_tokens INTEGER NOT NULL,
+                total_tokens INTEGER NOT NULL
+            );
+            "#,
+        )
+        .map_err(sql_error)?;
+        // Databases from before branching lack the fork columns.
+        for column in ["parent", "title"] {
+            add_column(&conn, "transcripts", column, "TEXT")?;
+        }
+        add_column(&conn, "sessions", "prompt_version", "INTEGER")?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            locks,
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Opens the named session, creating it when it doesn't exist yet.
+    pub fn session(&self, name: &str) -> Result<SqliteConversation, AgentError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(AgentError::UserInputError(Some(format!(
+                "Invalid session name: {} (use letters, digits, '-', '_' and '.')",
+                name
+            ))));
+        }
+
+        let now = now();
+        self.conn()
+            .execute(
+                "INSERT OR IGNORE INTO sessions (name, created_at, updated_at) VALUES (?1, ?2, ?2)",
+                params![name, now],
+            )
+            .map_err(sql_error)?;
+
+        Ok(SqliteConversation {
+            store: self.clone(),
+            name: name.to_string(),
+            turns: Mutex::new(None),
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<SessionInfo>, AgentError> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                r#"
+                SELECT s.name, COUNT(m.id), s.created_at, s.updated_at
+                FROM sessions s LEFT JOIN messages m ON m.session = s.name
+                GROUP BY s.name
+                ORDER BY s.updated_at DESC
+                "#,
+            )
+            .map_err(sql_error)?;
+
+        stmt.query_map([], |row| {
+            Ok(SessionInfo {
+                name: row.get(0)?,
+                messages: row.get::<_, i64>(1)? as usize,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })
+        .map_err(sql_error)?
+        .collect::<Result<Vec<SessionInfo>, _>>()
+        .map_err(sql_error)
+    }
+
+    // Returns false when there was no such session.
+    pub fn delete(&self, name: &str) -> Result<bool, AgentError> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM sessions WHERE name = ?1", params![name])
+            .map_err(sql_error)?;
+
+        Ok(deleted > 0)
+    }
+}
+
+impl Storage for SqliteSessionStore {
+    fn record(&self, session: &str, entry: &TranscriptEntry) -> Result<(), AgentError> {
+        let event = serde_json::to_string(entry)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(sql_error)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO transcripts (id, started_at) VALUES (?1, ?2)",
+            params![session, entry.at],
+        )
+        .map_err(sql_error)?;
+        match &entry.event {
+            SessionEvent::SessionStarted {
+                system_prompt: Some(prompt),
+            } => {
+                tx.execute(
+                    "UPDATE transcripts SET prompt_version = ?2 WHERE id = ?1",
+                    params![session, prompt.version],
+                )
+                .map_err(sql_error)?;
+            }
+            SessionEvent::UserMessage { text } => {
+                tx.execute(
+                    "UPDATE transcripts SET turns = turns + 1, first_message = COALESCE(first_message, ?2) WHERE id = ?1",
+                    params![session, text],
+                )
+                .map_err(sql_error)?;
+            }
+            SessionEvent::Forked { parent, title } => {
+                tx.execute(
+                    "UPDATE transcripts SET parent = ?2, title = ?3 WHERE id = ?1",
+                    params![session, parent, title],
+                )
+                .map_err(sql_error)?;
+            }
+            _ => {}
+        }
+        tx.execute(
+            "INSERT INTO events (transcript, at, event) VALUES (?1, ?2, ?3)",
+            params![session, entry.at, event],
+        )
+        .map_err(sql_error)?;
+
+        tx.commit().map_err(sql_error)
+    }
+
+    fn load(&self, session: &str) -> Result<Vec<TranscriptEntry>, AgentError> {
+        let conn = self.conn();
+        let exists = conn
+            .query_row(
+                "SELECT COUNT(*) FROM transcripts WHERE id = ?1",
+                params![session],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(sql_error)?;
+        if exists == 0 {
+            return Err(AgentError::UserInputError(Some(format!(
+                "No recorded session {}",
+                session
+            ))));
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT event FROM events WHERE transcript = ?1 ORDER BY id")
+            .map_err(sql_error)?;
+        stmt.query_map(params![session], |row| row.get::<_, String>(0))
+            .map_err(sql_error)?
+            .map(|event| {
+                let event = event.map_err(sql_error)?;
+                serde_json::from_str::<TranscriptEntry>(&event)
+                    .map_err(|e| AgentError::AgentError(Some(format!("{}: {}", session, e))))
+            })
+            .collect()
+    }
+
+    fn rewrite(&self, session: &str, entries: &[TranscriptEntry]) -> Result<(), AgentError> {
+        let summary = transcript::summarize(session, entries);
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(sql_error)?;
+        tx.execute("DELETE FROM events WHERE transcript = ?1", params![session])
+            .map_err(sql_error)?;
+        tx.execute(
+            "UPDATE transcripts SET turns = ?2, first_message = ?3, parent = ?4, title = ?5 WHERE id = ?1",
+            params![
+                session,
+                summary.turns as i64,
+                summary.first_message,
+                summary.parent,
+                summary.title
+            ],
+        )
+        .map_err(sql_error)?;
+        for entry in entries {
+            let event = serde_json::to_string(entry)
+                .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+            tx.execute(
+                "INSERT INTO events (transcript, at, event) VALUES (?1, ?2, ?3)",
+                params![session, entry.at, event],
+            )
+            .map_err(sql_error)?;
+        }
+
+        tx.commit().map_err(sql_error)
+    }
+
+    fn sessions(&self) -> Result<Vec<TranscriptSummary>, AgentError> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                r#"
+                SELECT id, started_at, turns, first_message, prompt_version, parent, title
+                FROM transcripts
+                ORDER BY started_at DESC
+                "#,
+            )
+            .map_err(sql_error)?;
+
+        stmt.query_map([], |row| {
+            Ok(TranscriptSummary {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                turns: row.get::<_, i64>(2)? as usize,
+                first_message: row.get(3)?,
+                prompt_version: row.get(4)?,
+                parent: row.get(5)?,
+                title: row.get(6)?,
+            })
+        })
+        .map_err(sql_error)?
+        .collect::<Result<Vec<TranscriptSummary>, _>>()
+        .map_err(sql_error)
+    }
+
+    fn record_usage(&self, session: &str, usage: &TokenUsage) -> Result<(), AgentError> {
+        self.conn()
+            .execute(
+                r#"
+                INSERT INTO usage (transcript, at, requests, prompt_tokens, candidate_tokens, total_tokens)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                "#,
+                params![
+                    session,
+                    now(),
+                    usage.requests as i64,
+                    usage.prompt_tokens as i64,
+                    usage.candidate_tokens as i64,
+                    usage.total_tokens as i64
+                ],
+            )
+            .map_err(sql_error)?;
+
+        Ok(())
+    }
+
+    fn usage(&self) -> Result<TokenUsage, AgentError> {
+        self.conn()
+            .query_row(
+                r#"
+                SELECT COALESCE(SUM(requests), 0), COALESCE(SUM(prompt_tokens), 0),
+                    COALESCE(SUM(candidate_tokens), 0), COALESCE(SUM(total_tokens), 0)
+                FROM usage
+                "#,
+                [],
+                |row| {
+                    Ok(TokenUsage {
+                        requests: row.get::<_, i64>(0)? as u64,
+                        prompt_tokens: row.get::<_, i64>(1)? as u64,
+                        candidate_tokens: row.get::<_, i64>(2)? as u64,
+                        total_tokens: row.get::<_, i64>(3)? as u64,
+                    })
+                },
+            )
+            .map_err(sql_error)
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteConversation {
+    store: SqliteSessionStore,
+    name: String,
+    // The number of messages this conversation knows of.
+    turns: Mutex<Option<usize>>,
+}
+
+impl SqliteConversation {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn turns(&self) -> MutexGuard<'_, Option<usize>> {
+        self.turns.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ConversationStore for SqliteConversation {
+    fn load(&self) -> Result<Vec<Content>, AgentError> {
+        let mut turns = self.turns();
+        let conn = self.store.conn();
+        let mut stmt = conn
+            .prepare("SELECT content FROM messages WHERE session = ?1 ORDER BY id")
+            .map_err(sql_error)?;
+
+        let contents = stmt
+            .query_map(params![self.name], |row| row.get::<_, String>(0))
+            .map_err(sql_error)?
+            .map(|content| {
+                let content = content.map_err(sql_error)?;
+                serde_json::from_str::<Content>(&content)
+                    .map_err(|e| AgentError::AgentError(Some(format!("{}: {}", self.name, e))))
+            })
+            .collect::<Result<Vec<Content>, AgentError>>()?;
+        *turns = Some(contents.len());
+
+        Ok(contents)
+    }
+
+    fn append(&self, content: &Content) -> Result<(), AgentError> {
+        let content = serde_json::to_string(content)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        let now = now();
+
+        let mut turns = self.turns();
+        let mut conn = self.store.conn();
+        let tx = conn.transaction().map_err(sql_error)?;
+        let current = tx
+            .query_row(
+                "SELECT COUNT(*) FROM messages WHERE session = ?1",
+                params![self.name],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(sql_error)? as usize;
+        check_turn(*turns, current)?;
+        tx.execute(
+            "INSERT INTO messages (session, content, created_at) VALUES (?1, ?2, ?3)",
+            params![self.name, content, now],
+        )
+        .map_err(sql_error)?;
+        tx.execute(
+            "UPDATE sessions SET updated_at = ?2 WHERE name = ?1",
+            params![self.name, now],
+        )
+        .map_err(sql_error)?;
+        tx.commit().map_err(sql_error)?;
+        *turns = Some(current + 1);
+
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), AgentError> {
+        let mut turns = self.turns();
+        self.store
+            .conn()
+            .execute(
+                "DELETE FROM messages WHERE session = ?1",
+                params![self.name],
+            )
+            .map_err(sql_error)?;
+        *turns = Some(0);
+
+        Ok(())
+    }
+
+    fn lock(&self, owner: &str) -> Result<ConversationLock, AgentError> {
+        match &self.store.locks {
+            Some(dir) => ConversationLock::acquire(&dir.join(format!("{}.lock", self.name)), owner),
+            None => Ok(ConversationLock::default()),
+        }
+    }
+
+    fn prompt_version(&self) -> Result<Option<u32>, AgentError> {
+        self.store
+            .conn()
+            .query_row(
+                "SELECT prompt_version FROM sessions WHERE name = ?1",
+                params![self.name],
+                |row| row.get::<_, Option<u32>>(0),
+            )
+            .optional()
+            .map(Option::flatten)
+            .map_err(sql_error)
+    }
+
+    fn set_prompt_version(&self, version: u32) -> Result<(), AgentError> {
+        self.store
+            .conn()
+            .execute(
+                "UPDATE sessions SET prompt_version = ?2 WHERE name = ?1",
+                params![self.name, version],
+            )
+            .map_err(sql_error)?;
+
+        Ok(())
+    }
+}
+
+fn add_column(conn: &Connection, table: &str, column: &str, kind: &str) -> Result<(), AgentError> {
+    let exists = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?1",
+                table
+            ),
+            params![column],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(sql_error)?;
+    if exists == 0 {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, kind),
+            [],
+        )
+        .map_err(sql_error)?;
+    }
+
+    Ok(())
+}
+
+fn sql_error(e: rusqlite::Error) -> AgentError {
+    AgentError::AgentError(Some(e.to_string()))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agent::Part;
+
+    #[test]
+    fn test_named_sessions() {
+        let store = SqliteSessionStore::in_memory().unwrap();
+        let auth = store.session("refactor-auth").unwrap();
+        let docs = store.session("docs").unwrap();
+
+        let message = Content::new(vec![Part::new("split the login handler")], "user");
+        auth.append(&message).unwrap();
+        assert_eq!(auth.load().unwrap(), [message]);
+        assert!(docs.load().unwrap().is_empty());
+
+        assert_eq!(auth.prompt_version().unwrap(), None);
+        auth.set_prompt_version(2).unwrap();
+        assert_eq!(auth.prompt_version().unwrap(), Some(2));
+        assert_eq!(docs.prompt_version().unwrap(), None);
+
+        let sessions = store.list().unwrap();
+        assert_eq!(sessions.len(), 2);
+        let auth_info = sessions
+            .iter()
+            .find(|session| session.name == "refactor-auth")
+            .unwrap();
+        assert_eq!(auth_info.messages, 1);
+
+        assert!(store.delete("refactor-auth").unwrap());
+        assert!(!store.delete("refactor-auth").unwrap());
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(store.session("../etc").is_err());
+    }
+}
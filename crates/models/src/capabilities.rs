@@ -0,0 +1,77 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    // Tokens of input the model accepts, history and tools included.
+    pub context_window: u64,
+}
+
+// Known limits of the models voo talks to, looked up by a part of the model
+// name so dated and region prefixed ids such as
+// us.anthropic.claude-3-5-sonnet-20241022-v2:0 match too. Later entries win,
+// so more specific names come after the general ones.
+#[derive(Debug, Clone)]
+pub struct CapabilityRegistry {
+    models: Vec<(String, ModelCapabilities)>,
+}
+
+impl CapabilityRegistry {
+    pub fn builtin() -> Self {
+        [
+            ("gemini-2.5", 1_048_576),
+            ("gemini-2.0", 1_048_576),
+            ("gemini-1.5", 1_048_576),
+            ("gemini-1.5-pro", 2_097_152),
+            ("gpt-4.1", 1_047_576),
+            ("gpt-4o", 128_000),
+            ("o4-mini", 200_000),
+            ("o3", 200_000),
+            ("claude", 200_000),
+            ("nova-pro", 300_000),
+            ("mistral-large", 128_000),
+            ("codestral", 256_000),
+            ("grok-3", 131_072),
+            ("deepseek", 64_000),
+            ("llama-3", 128_000),
+            ("qwen2.5-coder", 32_768),
+        ]
+        .into_iter()
+        .fold(Self { models: vec![] }, |registry, (model, tokens)| {
+            registry.with_context_window(model, tokens)
+        })
+    }
+
+    // Added models take precedence over the builtin ones.
+    pub fn with_context_window(mut self, model: &str, context_window: u64) -> Self {
+        self.models
+            .push((model.to_lowercase(), ModelCapabilities { context_window }));
+        self
+    }
+
+    pub fn get(&self, model: &str) -> Option<ModelCapabilities> {
+        let model = model.to_lowercase();
+        self.models
+            .iter()
+            .rev()
+            .find(|(name, _)| model.contains(name.as_str()))
+            .map(|(_, capabilities)| *capabilities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let registry = CapabilityRegistry::builtin().with_context_window("qwen3:8b", 40_960);
+
+        let window = |model: &str| registry.get(model).map(|c| c.context_window);
+        assert_eq!(window("gemini-1.5-pro-002"), Some(2_097_152));
+        assert_eq!(window("gemini-1.5-flash"), Some(1_048_576));
+        assert_eq!(
+            window("us.anthropic.claude-3-5-sonnet-20241022-v2:0"),
+            Some(200_000)
+        );
+        assert_eq!(window("qwen3:8b"), Some(40_960));
+        assert_eq!(window("my-finetune"), None);
+    }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use domain::models::{
+    agent::AgentError,
+    embedding::{Embedder, Embedding},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::models::endpoints::GeminiEndpoint;
+
+static MODEL: &str = "text-embedding-004";
+// batchEmbedContents accepts at most 100 texts per request.
+static BATCH_SIZE: usize = 100;
+
+#[derive(Debug)]
+pub struct GeminiEmbedder {
+    api_key: String,
+    model: String,
+    reqwest: reqwest::Client,
+}
+
+impl GeminiEmbedder {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            model: MODEL.to_string(),
+            reqwest: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Embedding>, AgentError> {
+        let request = BatchRequest {
+            requests: texts
+                .iter()
+                .map(|text| EmbedRequest {
+                    model: format!("models/{}", self.model),
+                    content: EmbedContent {
+                        parts: vec![EmbedPart { text: text.clone() }],
+                    },
+                })
+                .collect(),
+        };
+
+        let url = format!(
+            "{}{}:batchEmbedContents",
+            GeminiEndpoint::studio().base_url,
+            self.model
+        );
+        let response = self
+            .reqwest
+            .post(url)
+            .header("x-goog-api-key", &self.api_key)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+
+        match status.as_u16() {
+            200..=299 => {}
+            429 => {
+                return Err(AgentError::RateLimited {
+                    retry_after: None,
+                    message: text,
+                });
+            }
+            _ => {
+                return Err(AgentError::AgentError(Some(format!(
+                    "gemini embeddings {}: {}",
+                    status, text
+                ))));
+            }
+        }
+
+        let response = serde_json::from_str::<BatchResponse>(&text)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+
+        Ok(response
+            .embeddings
+            .into_iter()
+            .map(|embedding| embedding.values)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Embedder for GeminiEmbedder {
+    fn model(&self) -> String {
+        format!("gemini/{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AgentError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(BATCH_SIZE) {
+            embeddings.extend(self.embed_batch(batch).await?);
+        }
+
+        Ok(embeddings)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRequest {
+    requests: Vec<EmbedRequest>,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedRequest {
+    model: String,
+    content: EmbedContent,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedContent {
+    parts: Vec<EmbedPart>,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedPart {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    embeddings: Vec<EmbeddingValues>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingValues {
+    values: Embedding,
+}
@@ -0,0 +1,114 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use domain::models::{
+    agent::AgentError,
+    embedding::{Embedder, Embedding},
+};
+use tokenizers::Tokenizer;
+
+// Runs a BERT sentence embedding model such as all-MiniLM-L6-v2 in-process,
+// so indexes can be built without network access.
+pub struct LocalEmbedder {
+    model_dir: PathBuf,
+    model: Arc<BertModel>,
+    tokenizer: Arc<Tokenizer>,
+}
+
+impl std::fmt::Debug for LocalEmbedder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalEmbedder")
+            .field("model_dir", &self.model_dir)
+            .finish()
+    }
+}
+
+impl LocalEmbedder {
+    // `model_dir` holds the model's `config.json`, `tokenizer.json` and
+    // `model.safetensors`.
+    pub fn load(model_dir: PathBuf) -> Result<Self, AgentError> {
+        let config = std::fs::read_to_string(model_dir.join("config.json"))
+            .map_err(|e| embedder_error("reading config.json", e))?;
+        let config: Config =
+            serde_json::from_str(&config).map_err(|e| embedder_error("parsing config.json", e))?;
+        let tokenizer = Tokenizer::from_file(model_dir.join("tokenizer.json"))
+            .map_err(|e| embedder_error("loading tokenizer.json", e))?;
+
+        // Safety: the weights are memory mapped and must not be modified
+        // while voo is running.
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(
+                &[model_dir.join("model.safetensors")],
+                DType::F32,
+                &Device::Cpu,
+            )
+        }
+        .map_err(|e| embedder_error("loading weights", e))?;
+        let model =
+            BertModel::load(vb, &config).map_err(|e| embedder_error("building model", e))?;
+
+        Ok(Self {
+            model_dir,
+            model: Arc::new(model),
+            tokenizer: Arc::new(tokenizer),
+        })
+    }
+}
+
+#[async_trait]
+impl Embedder for LocalEmbedder {
+    fn model(&self) -> String {
+        let name = self
+            .model_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        format!("local/{}", name)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AgentError> {
+        let model = self.model.clone();
+        let tokenizer = self.tokenizer.clone();
+        let texts = texts.to_vec();
+
+        tokio::task::spawn_blocking(move || {
+            texts
+                .iter()
+                .map(|text| embed_text(&model, &tokenizer, text))
+                .collect()
+        })
+        .await
+        .map_err(|e| AgentError::AgentError(Some(e.to_string())))?
+    }
+}
+
+fn embed_text(
+    model: &BertModel,
+    tokenizer: &Tokenizer,
+    text: &str,
+) -> Result<Embedding, AgentError> {
+    let encoding = tokenizer
+        .encode(text, true)
+        .map_err(|e| embedder_error("tokenizing", e))?;
+
+    let embed = || -> candle_core::Result<Embedding> {
+        let ids = Tensor::new(encoding.get_ids(), &Device::Cpu)?.unsqueeze(0)?;
+        let type_ids = ids.zeros_like()?;
+        let hidden = model.forward(&ids, &type_ids, None)?;
+
+        // Mean pooling over the tokens, normalized to unit length.
+        let pooled = hidden.mean(1)?.squeeze(0)?;
+        let norm = pooled.sqr()?.sum_all()?.sqrt()?;
+        pooled.broadcast_div(&norm)?.to_vec1::<f32>()
+    };
+
+    embed().map_err(|e| embedder_error("embedding", e))
+}
+
+fn embedder_error(action: &str, e: impl std::fmt::Display) -> AgentError {
+    AgentError::AgentError(Some(format!("local embedder: error {}: {}", action, e)))
+}
@@ -0,0 +1,4 @@
+pub mod gemini;
+#[cfg(feature = "candle")]
+pub mod local;
+pub mod openai;
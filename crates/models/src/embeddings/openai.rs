@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use domain::models::{
+    agent::AgentError,
+    embedding::{Embedder, Embedding},
+};
+use serde::{Deserialize, Serialize};
+
+static URL: &str = "https://api.openai.com/v1/embeddings";
+static MODEL: &str = "text-embedding-3-small";
+static BATCH_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub struct OpenAiEmbedder {
+    api_key: String,
+    url: String,
+    model: String,
+    reqwest: reqwest::Client,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            url: URL.to_string(),
+            model: MODEL.to_string(),
+            reqwest: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    // Any server implementing the OpenAI embeddings API, e.g. a local one.
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Embedding>, AgentError> {
+        let request = EmbeddingRequest {
+            model: self.model.clone(),
+            input: texts.to_vec(),
+        };
+
+        let response = self
+            .reqwest
+            .post(&self.url)
+            .bearer_auth(&self.api_key)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+
+        match status.as_u16() {
+            200..=299 => {}
+            429 => {
+                return Err(AgentError::RateLimited {
+                    retry_after: None,
+                    message: text,
+                });
+            }
+            _ => {
+                return Err(AgentError::AgentError(Some(format!(
+                    "openai embeddings {}: {}",
+                    status, text
+                ))));
+            }
+        }
+
+        let mut response = serde_json::from_str::<EmbeddingResponse>(&text)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        response.data.sort_by_key(|data| data.index);
+
+        Ok(response
+            .data
+            .into_iter()
+            .map(|data| data.embedding)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    fn model(&self) -> String {
+        format!("openai/{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>, AgentError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(BATCH_SIZE) {
+            embeddings.extend(self.embed_batch(batch).await?);
+        }
+
+        Ok(embeddings)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Embedding,
+}
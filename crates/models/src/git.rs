@@ -0,0 +1,1165 @@
+use std::{
+    collections::HashMap,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git2::{
+    ApplyLocation, BlameOptions, BranchType, Commit, Delta, Diff, DiffFile, DiffOptions, ErrorCode,
+    Index, IndexAddOption, ObjectType, Patch, Repository, Sort, Status, StatusOptions, Tree,
+    build::CheckoutBuilder,
+};
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::{models::sigv4::civil_from_days, tools::read_policy::ReadPolicy};
+
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+const CHECKPOINT_REFS: &str = "refs/voo/checkpoints";
+// Older checkpoints are dropped so the refs do not pile up.
+const MAX_CHECKPOINTS: usize = 20;
+// Commits a path filtered log looks at before it gives up on older history.
+const MAX_LOG_WALK: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlameLine {
+    pub line: usize,
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub summary: String,
+    pub text: String,
+}
+
+impl BlameLine {
+    pub fn is_committed(&self) -> bool {
+        self.commit != UNCOMMITTED
+    }
+}
+
+// Blames the given 1-based inclusive line ranges of a file, or the whole file
+// when there are none. The repository is found from the file, so files of any
+// repository work, and lines changed since HEAD come back uncommitted.
+pub async fn blame(path: &Path, ranges: &[(usize, usize)]) -> Result<Vec<BlameLine>, String> {
+    let path = path.to_path_buf();
+    let ranges = ranges.to_vec();
+    blocking(move || blame_lines(&path, &ranges)).await
+}
+
+fn blame_lines(path: &Path, ranges: &[(usize, usize)]) -> Result<Vec<BlameLine>, String> {
+    let (repo, relative) = open_file(path)?;
+    let content = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // Line numbers only match the committed file while it is unchanged, so
+    // only then is the blame limited to the lines asked for.
+    let unchanged = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .and_then(|tree| tree.get_path(&relative))
+        .and_then(|entry| entry.to_object(&repo))
+        .is_ok_and(|object| {
+            object
+                .as_blob()
+                .is_some_and(|blob| blob.content() == content)
+        });
+    let mut options = BlameOptions::new();
+    if unchanged
+        && let Some(first) = ranges.iter().map(|(start, _)| *start).min()
+        && let Some(last) = ranges.iter().map(|(_, end)| *end).max()
+    {
+        options.min_line(first).max_line(last);
+    }
+    let committed = repo
+        .blame_file(&relative, Some(&mut options))
+        .map_err(|e| e.message().to_string())?;
+    let buffered = match unchanged {
+        true => None,
+        false => Some(
+            committed
+                .blame_buffer(&content)
+                .map_err(|e| e.message().to_string())?,
+        ),
+    };
+    let blame = buffered.as_ref().unwrap_or(&committed);
+
+    let mut commits = HashMap::new();
+    let mut lines = vec![];
+    for (index, text) in String::from_utf8_lossy(&content).lines().enumerate() {
+        let line = index + 1;
+        let wanted = ranges.is_empty()
+            || ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&line));
+        let Some(hunk) = blame.get_line(line).filter(|_| wanted) else {
+            continue;
+        };
+
+        let commit = hunk.final_commit_id();
+        if commit.is_zero() {
+            lines.push(BlameLine {
+                line,
+                commit: UNCOMMITTED.to_string(),
+                author: "Not Committed Yet".to_string(),
+                date: String::new(),
+                summary: String::new(),
+                text: text.to_string(),
+            });
+            continue;
+        }
+        // Hunks of a blamed buffer carry no signature, the commit has it.
+        let (author, date, summary) = commits
+            .entry(commit)
+            .or_insert_with(|| match repo.find_commit(commit) {
+                Ok(found) => (
+                    found.author().name().unwrap_or_default().to_string(),
+                    date(found.author().when().seconds()),
+                    found.summary().unwrap_or_default().to_string(),
+                ),
+                Err(_) => Default::default(),
+            })
+            .clone();
+        lines.push(BlameLine {
+            line,
+            commit: commit.to_string(),
+            author,
+            date,
+            summary,
+            text: text.to_string(),
+        });
+    }
+
+    Ok(lines)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusEntry {
+    pub path: String,
+    // How the file changed in the index, None when it is unchanged there.
+    pub staged: Option<&'static str>,
+    // How the working tree differs from the index.
+    pub unstaged: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepoStatus {
+    // None on a detached HEAD.
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub files: Vec<StatusEntry>,
+    // The number of changed files, `files` stops at the limit.
+    pub total: usize,
+}
+
+// The branch, how far it is from its upstream and up to `limit` changed
+// files, untracked ones included and ignored ones left out.
+pub async fn status(dir: &Path, limit: usize) -> Result<RepoStatus, String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        let head = repo.head().ok();
+        let branch = head
+            .as_ref()
+            .filter(|head| head.is_branch())
+            .and_then(|head| head.shorthand())
+            .map(str::to_string)
+            .or_else(|| {
+                // A repository without commits is on its unborn branch.
+                repo.find_reference("HEAD")
+                    .ok()
+                    .and_then(|head| head.symbolic_target().map(str::to_string))
+                    .map(|target| target.trim_start_matches("refs/heads/").to_string())
+            });
+        let head_commit = head.as_ref().and_then(|head| head.target());
+
+        let (mut upstream, mut ahead, mut behind) = (None, 0, 0);
+        if let Some(name) = head
+            .as_ref()
+            .filter(|head| head.is_branch())
+            .and_then(|head| head.shorthand())
+            && let Ok(local) = repo.find_branch(name, BranchType::Local)
+            && let Ok(remote) = local.upstream()
+        {
+            upstream = remote.name().ok().flatten().map(str::to_string);
+            if let (Some(local), Some(remote)) = (head_commit, remote.get().target()) {
+                (ahead, behind) = repo
+                    .graph_ahead_behind(local, remote)
+                    .map_err(|e| e.message().to_string())?;
+            }
+        }
+
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .renames_head_to_index(true);
+        let statuses = repo
+            .statuses(Some(&mut options))
+            .map_err(|e| e.message().to_string())?;
+        let files = statuses
+            .iter()
+            .filter(|entry| !entry.status().is_ignored())
+            .map(|entry| {
+                let status = entry.status();
+                StatusEntry {
+                    path: entry.path().unwrap_or_default().to_string(),
+                    staged: staged(status),
+                    unstaged: unstaged(status),
+                }
+            })
+            .collect::<Vec<StatusEntry>>();
+
+        Ok(RepoStatus {
+            branch,
+            head: head_commit.map(|commit| short(&commit.to_string())),
+            upstream,
+            ahead,
+            behind,
+            total: files.len(),
+            files: files.into_iter().take(limit).collect(),
+        })
+    })
+    .await
+}
+
+fn staged(status: Status) -> Option<&'static str> {
+    Some(match status {
+        status if status.is_conflicted() => "conflicted",
+        status if status.is_index_new() => "added",
+        status if status.is_index_modified() => "modified",
+        status if status.is_index_deleted() => "deleted",
+        status if status.is_index_renamed() => "renamed",
+        status if status.is_index_typechange() => "typechange",
+        _ => return None,
+    })
+}
+
+fn unstaged(status: Status) -> Option<&'static str> {
+    Some(match status {
+        status if status.is_conflicted() => "conflicted",
+        status if status.is_wt_new() => "untracked",
+        status if status.is_wt_modified() => "modified",
+        status if status.is_wt_deleted() => "deleted",
+        status if status.is_wt_renamed() => "renamed",
+        status if status.is_wt_typechange() => "typechange",
+        _ => return None,
+    })
+}
+
+// What `diff` compares.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffTarget {
+    // HEAD against the working tree, staged or not.
+    Uncommitted,
+    // HEAD against the index.
+    Staged,
+    // A commit against its first parent.
+    Commit(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    // The path before a rename.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub status: &'static str,
+    pub additions: usize,
+    pub deletions: usize,
+    #[serde(skip)]
+    pub patch: String,
+}
+
+// The changed files of `target`, limited to `pathspec` when given, with the
+// patch of each.
+pub async fn diff(
+    dir: &Path,
+    target: DiffTarget,
+    pathspec: Option<String>,
+) -> Result<Vec<FileDiff>, String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        let mut options = DiffOptions::new();
+        if let Some(pathspec) = &pathspec {
+            options.pathspec(pathspec);
+        }
+
+        let mut diff = match &target {
+            DiffTarget::Uncommitted => {
+                repo.diff_tree_to_workdir_with_index(head_tree(&repo)?.as_ref(), Some(&mut options))
+            }
+            DiffTarget::Staged => {
+                repo.diff_tree_to_index(head_tree(&repo)?.as_ref(), None, Some(&mut options))
+            }
+            DiffTarget::Commit(rev) => {
+                let commit = repo
+                    .revparse_single(rev)
+                    .and_then(|object| object.peel_to_commit())
+                    .map_err(|e| e.message().to_string())?;
+                let parent = commit
+                    .parent(0)
+                    .ok()
+                    .map(|parent| parent.tree())
+                    .transpose();
+                let parent = parent.map_err(|e| e.message().to_string())?;
+                let tree = commit.tree().map_err(|e| e.message().to_string())?;
+                repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), Some(&mut options))
+            }
+        }
+        .map_err(|e| e.message().to_string())?;
+        diff.find_similar(None)
+            .map_err(|e| e.message().to_string())?;
+
+        let mut files = vec![];
+        for (index, delta) in diff.deltas().enumerate() {
+            let path = |file: DiffFile| {
+                file.path()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            let new_path = path(delta.new_file());
+            let old_path = path(delta.old_file());
+            let status = match delta.status() {
+                Delta::Added | Delta::Untracked => "added",
+                Delta::Deleted => "deleted",
+                Delta::Renamed => "renamed",
+                Delta::Copied => "copied",
+                Delta::Typechange => "typechange",
+                Delta::Conflicted => "conflicted",
+                _ => "modified",
+            };
+
+            let mut patch = Patch::from_diff(&diff, index).map_err(|e| e.message().to_string())?;
+            let (additions, deletions, text) = match patch.as_mut() {
+                Some(patch) => {
+                    let (_, additions, deletions) =
+                        patch.line_stats().map_err(|e| e.message().to_string())?;
+                    let text = patch
+                        .to_buf()
+                        .map(|buf| String::from_utf8_lossy(&buf).to_string())
+                        .map_err(|e| e.message().to_string())?;
+                    (additions, deletions, text)
+                }
+                None => (0, 0, String::new()),
+            };
+
+            files.push(FileDiff {
+                old_path: (old_path != new_path && !old_path.is_empty()).then_some(old_path),
+                path: new_path,
+                status,
+                additions,
+                deletions,
+                patch: text,
+            });
+        }
+
+        Ok(files)
+    })
+    .await
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub summary: String,
+}
+
+// Up to `limit` commits reachable from `rev`, newest first, only those that
+// changed `path` among the newest MAX_LOG_WALK commits when one is given.
+pub async fn log(
+    dir: &Path,
+    rev: Option<String>,
+    path: Option<String>,
+    limit: usize,
+) -> Result<Vec<LogEntry>, String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        let start = repo
+            .revparse_single(rev.as_deref().unwrap_or("HEAD"))
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| e.message().to_string())?;
+        let mut walk = repo.revwalk().map_err(|e| e.message().to_string())?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+            .and_then(|_| walk.push(start.id()))
+            .map_err(|e| e.message().to_string())?;
+
+        let mut entries = vec![];
+        for id in walk.take(MAX_LOG_WALK) {
+            if entries.len() >= limit {
+                break;
+            }
+            let commit = id
+                .and_then(|id| repo.find_commit(id))
+                .map_err(|e| e.message().to_string())?;
+            if let Some(path) = &path
+                && !touches(&commit, Path::new(path))
+            {
+                continue;
+            }
+
+            entries.push(LogEntry {
+                commit: short(&commit.id().to_string()),
+                author: commit.author().name().unwrap_or_default().to_string(),
+                date: date(commit.author().when().seconds()),
+                summary: commit.summary().unwrap_or_default().to_string(),
+            });
+        }
+
+        Ok(entries)
+    })
+    .await
+}
+
+// Whether a commit changed the file or directory at `path` compared to its
+// first parent.
+fn touches(commit: &Commit, path: &Path) -> bool {
+    let entry = |commit: &Commit| {
+        commit
+            .tree()
+            .ok()
+            .and_then(|tree| tree.get_path(path).ok())
+            .map(|entry| entry.id())
+    };
+    let parent = commit.parent(0).ok();
+    entry(commit) != parent.as_ref().and_then(entry)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommitInfo {
+    pub commit: String,
+    // None on a detached HEAD.
+    pub branch: Option<String>,
+    pub summary: String,
+    pub files: Vec<String>,
+    // Files under the paths that the read policy kept out of the commit.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+// The files a commit would contain once `paths` are staged, new, changed and
+// deleted files under them alike, and the files the read policy keeps out.
+// The index on disk is not touched, so nothing is staged until `commit` runs.
+pub async fn staged_files(
+    dir: &Path,
+    paths: Vec<String>,
+    read_policy: ReadPolicy,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        check_commit_config(&repo)?;
+        let (index, skipped) = stage(&repo, &paths, &read_policy)?;
+        Ok((index_changes(&repo, &index)?, skipped))
+    })
+    .await
+}
+
+// Stages `paths` and commits everything staged on the current branch, with
+// the author and committer from the git config.
+pub async fn commit(
+    dir: &Path,
+    paths: Vec<String>,
+    message: String,
+    read_policy: ReadPolicy,
+) -> Result<CommitInfo, String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        check_commit_config(&repo)?;
+        let (mut index, skipped) = stage(&repo, &paths, &read_policy)?;
+        let files = index_changes(&repo, &index)?;
+        if files.is_empty() {
+            return Err("nothing to commit, stage files or pass their paths".to_string());
+        }
+
+        let signature = repo.signature().map_err(|e| {
+            format!(
+                "no commit identity, set user.name and user.email: {}",
+                e.message()
+            )
+        })?;
+        index.write().map_err(|e| e.message().to_string())?;
+        let tree = index
+            .write_tree()
+            .and_then(|tree| repo.find_tree(tree))
+            .map_err(|e| e.message().to_string())?;
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit().map_err(|e| e.message().to_string())?),
+            Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.message().to_string()),
+        };
+        let id = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &message,
+                &tree,
+                &parent.iter().collect::<Vec<&Commit>>(),
+            )
+            .map_err(|e| e.message().to_string())?;
+
+        let head = repo.head().map_err(|e| e.message().to_string())?;
+        Ok(CommitInfo {
+            commit: short(&id.to_string()),
+            branch: head
+                .is_branch()
+                .then(|| head.shorthand().map(str::to_string))
+                .flatten(),
+            summary: message.lines().next().unwrap_or_default().to_string(),
+            files,
+            skipped,
+        })
+    })
+    .await
+}
+
+// Switches to a local branch, creating it at HEAD first when `create` is set.
+// Like `git switch`, local changes the switch would overwrite make it fail
+// and leave everything as it was. Returns the branch it switched from.
+pub async fn checkout_branch(
+    dir: &Path,
+    name: String,
+    create: bool,
+) -> Result<Option<String>, String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        let previous = repo
+            .head()
+            .ok()
+            .filter(|head| head.is_branch())
+            .and_then(|head| head.shorthand().map(str::to_string));
+
+        let branch = match create {
+            true => {
+                let head = repo
+                    .head()
+                    .and_then(|head| head.peel_to_commit())
+                    .map_err(|e| format!("can't branch off HEAD: {}", e.message()))?;
+                repo.branch(&name, &head, false)
+            }
+            false => repo.find_branch(&name, BranchType::Local),
+        }
+        .map_err(|e| e.message().to_string())?;
+        let reference = branch
+            .get()
+            .name()
+            .ok_or_else(|| format!("{} is not a valid branch name", name))?
+            .to_string();
+        let target = branch
+            .get()
+            .peel(ObjectType::Commit)
+            .map_err(|e| e.message().to_string())?;
+
+        repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))
+            .map_err(|e| e.message().to_string())?;
+        repo.set_head(&reference)
+            .map_err(|e| e.message().to_string())?;
+
+        Ok(previous)
+    })
+    .await
+}
+
+// The files a patch touches, by their path in the patch and on disk. Renamed
+// files are listed under both names.
+pub async fn patch_files(dir: &Path, patch: String) -> Result<Vec<(String, PathBuf)>, String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| "the repository has no working tree".to_string())?;
+        let diff = parse_patch(&patch)?;
+        let mut files: Vec<(String, PathBuf)> = vec![];
+        for delta in diff.deltas() {
+            for path in [delta.old_file().path(), delta.new_file().path()]
+                .into_iter()
+                .flatten()
+            {
+                let name = path.to_string_lossy().to_string();
+                if !files.iter().any(|(known, _)| *known == name) {
+                    files.push((name, workdir.join(path)));
+                }
+            }
+        }
+        if files.is_empty() {
+            return Err("the patch changes no files".to_string());
+        }
+
+        Ok(files)
+    })
+    .await
+}
+
+// libgit2 works out every file before it writes one, so a patch that does
+// not apply cleanly changes nothing.
+pub async fn apply_patch(dir: &Path, patch: String) -> Result<(), String> {
+    let dir = dir.to_path_buf();
+    blocking(move || {
+        let repo = open(&dir)?;
+        let diff = parse_patch(&patch)?;
+        repo.apply(&diff, ApplyLocation::WorkDir, None)
+            .map_err(|e| format!("the patch does not apply: {}", e.message()))
+    })
+    .await
+}
+
+fn parse_patch(patch: &str) -> Result<Diff<'static>, String> {
+    Diff::from_buffer(with_git_headers(patch).as_bytes())
+        .map_err(|e| format!("not a valid patch: {}", e.message()))
+}
+
+// libgit2 only parses patches in the format of `git diff`. Plain unified
+// diffs get the `diff --git` line before each file they are missing.
+fn with_git_headers(patch: &str) -> String {
+    if patch.lines().any(|line| line.starts_with("diff --git ")) {
+        return patch.to_string();
+    }
+
+    let path = |line: &str, prefix: &str| {
+        let path = line[4..].split('\t').next().unwrap_or_default().trim();
+        (path != "/dev/null").then(|| path.strip_prefix(prefix).unwrap_or(path).to_string())
+    };
+    let lines = patch.lines().collect::<Vec<&str>>();
+    let mut out = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if line.starts_with("--- ")
+            && let Some(next) = lines.get(index + 1).filter(|next| next.starts_with("+++ "))
+            && let Some(file) = path(next, "b/").or_else(|| path(line, "a/"))
+        {
+            out.push_str(&format!("diff --git a/{} b/{}\n", file, file));
+            if path(line, "a/").is_none() {
+                out.push_str("new file mode 100644\n");
+            } else if path(next, "b/").is_none() {
+                out.push_str("deleted file mode 100644\n");
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    out
+}
+
+// The index with `paths` added, in memory, and the files under them the read
+// policy refused. `.` and globs match everything, secrets included.
+fn stage(
+    repo: &Repository,
+    paths: &[String],
+    read_policy: &ReadPolicy,
+) -> Result<(Index, Vec<String>), String> {
+    let mut index = repo.index().map_err(|e| e.message().to_string())?;
+    let mut skipped = vec![];
+    if !paths.is_empty() {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| "the repository has no working tree".to_string())?;
+        // The lock files of the edit tools stay next to the edited files.
+        let mut allowed = |path: &Path, _: &[u8]| match read_policy.check(&workdir.join(path)) {
+            _ if path.to_string_lossy().ends_with(".voo-lock") => 1,
+            Ok(()) => 0,
+            Err(_) => {
+                skipped.push(path.to_string_lossy().to_string());
+                1
+            }
+        };
+        index
+            .add_all(paths, IndexAddOption::DEFAULT, Some(&mut allowed))
+            .and_then(|_| index.update_all(paths, Some(&mut allowed)))
+            .map_err(|e| e.message().to_string())?;
+    }
+    skipped.sort();
+    skipped.dedup();
+
+    Ok((index, skipped))
+}
+
+// libgit2 runs no hooks and can't sign, so commits of repositories that rely
+// on them are left to git.
+fn check_commit_config(repo: &Repository) -> Result<(), String> {
+    let config = repo.config().map_err(|e| e.message().to_string())?;
+    if config.get_bool("commit.gpgsign").unwrap_or(false) {
+        return Err(
+            "commit.gpgsign is set and voo can't sign commits, commit with git".to_string(),
+        );
+    }
+
+    let hooks = match (config.get_path("core.hooksPath"), repo.workdir()) {
+        (Ok(path), Some(workdir)) => workdir.join(path),
+        _ => repo.path().join("hooks"),
+    };
+    for hook in ["pre-commit", "prepare-commit-msg", "commit-msg"] {
+        let executable = std::fs::metadata(hooks.join(hook))
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0);
+        if executable {
+            return Err(format!(
+                "the repository has a {} hook, which voo's commits would skip, commit with git",
+                hook
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// The files of `index` that differ from HEAD.
+fn index_changes(repo: &Repository, index: &Index) -> Result<Vec<String>, String> {
+    let diff = repo
+        .diff_tree_to_index(head_tree(repo)?.as_ref(), Some(index), None)
+        .map_err(|e| e.message().to_string())?;
+
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect())
+}
+
+// None before the first commit.
+fn head_tree(repo: &Repository) -> Result<Option<Tree<'_>>, String> {
+    match repo.head() {
+        Ok(head) => head
+            .peel_to_tree()
+            .map(Some)
+            .map_err(|e| e.message().to_string()),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => Ok(None),
+        Err(e) => Err(e.message().to_string()),
+    }
+}
+
+// The repository `dir` is in.
+fn open(dir: &Path) -> Result<Repository, String> {
+    Repository::discover(dir).map_err(|e| {
+        format!(
+            "{} is not in a git repository: {}",
+            dir.display(),
+            e.message()
+        )
+    })
+}
+
+// The repository of a file and the file's path in it.
+fn open_file(path: &Path) -> Result<(Repository, PathBuf), String> {
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let repo = open(dir)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "the repository has no working tree".to_string())?
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    let relative = path
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .strip_prefix(&workdir)
+        .map_err(|_| format!("{} is outside of {}", path.display(), workdir.display()))?
+        .to_path_buf();
+
+    Ok((repo, relative))
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn date(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn short(commit: &str) -> String {
+    commit[..commit.len().min(12)].to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub id: String,
+    pub commit: String,
+    pub message: String,
+}
+
+async fn git(dir: &Path, args: &[&str], envs: &[(&str, &str)]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .envs(envs.iter().copied())
+        .output()
+        .await
+        .map_err(|e| format!("Error running git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Snapshots the working tree, untracked files included, as a commit under
+// refs/voo/checkpoints. A copy of the index is used, so the index, HEAD and
+// the files stay as they are.
+pub async fn checkpoint(dir: &Path, message: &str) -> Result<Checkpoint, String> {
+    let index = dir.join(git(dir, &["rev-parse", "--git-path", "index"], &[]).await?);
+    let scratch = dir.join(
+        git(
+            dir,
+            &["rev-parse", "--git-path", "voo-checkpoint-index"],
+            &[],
+        )
+        .await?,
+    );
+    let head = git(dir, &["rev-parse", "--verify", "-q", "HEAD"], &[])
+        .await
+        .ok();
+
+    // Starting from the real index keeps its stat cache, so unchanged files
+    // are not hashed again.
+    if index.exists() {
+        std::fs::copy(&index, &scratch).map_err(|e| e.to_string())?;
+    }
+    let scratch_env = scratch.to_string_lossy().to_string();
+    let tree = async {
+        let env = [("GIT_INDEX_FILE", scratch_env.as_str())];
+        git(dir, &["add", "-A", "--", ":/"], &env).await?;
+        git(dir, &["write-tree"], &env).await
+    }
+    .await;
+    _ = std::fs::remove_file(&scratch);
+    let tree = tree?;
+
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+    if let Some(head) = &head {
+        args.extend(["-p", head.as_str()]);
+    }
+    let identity = [
+        ("GIT_AUTHOR_NAME", "voo"),
+        ("GIT_AUTHOR_EMAIL", "voo@localhost"),
+        ("GIT_COMMITTER_NAME", "voo"),
+        ("GIT_COMMITTER_EMAIL", "voo@localhost"),
+    ];
+    let commit = git(dir, &args, &identity).await?;
+
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+        .to_string();
+    let reference = format!("{}/{}", CHECKPOINT_REFS, id);
+    git(dir, &["update-ref", &reference, &commit], &[]).await?;
+
+    for old in checkpoints(dir).await?.iter().skip(MAX_CHECKPOINTS) {
+        let reference = format!("{}/{}", CHECKPOINT_REFS, old.id);
+        git(dir, &["update-ref", "-d", &reference], &[]).await?;
+    }
+
+    Ok(Checkpoint {
+        id,
+        commit,
+        message: message.to_string(),
+    })
+}
+
+// The checkpoints of the repository, newest first.
+pub async fn checkpoints(dir: &Path) -> Result<Vec<Checkpoint>, String> {
+    let output = git(
+        dir,
+        &[
+            "for-each-ref",
+            "--sort=-refname",
+            "--format=%(refname:lstrip=3)%09%(objectname)%09%(contents:subject)",
+            CHECKPOINT_REFS,
+        ],
+        &[],
+    )
+    .await?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            Some(Checkpoint {
+                id: fields.next()?.to_string(),
+                commit: fields.next()?.to_string(),
+                message: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect())
+}
+
+// Puts the files of a checkpoint back into the working tree. Files created
+// since the checkpoint are left alone, the index is not touched.
+pub async fn restore(dir: &Path, checkpoint: &Checkpoint) -> Result<(), String> {
+    let source = format!("--source={}", checkpoint.commit);
+    git(dir, &["restore", &source, "--worktree", "--", ":/"], &[])
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_history() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let author = [
+            ("GIT_AUTHOR_NAME", "Jane Doe"),
+            ("GIT_AUTHOR_EMAIL", "jane@example.com"),
+            ("GIT_AUTHOR_DATE", "1714521600 +0000"),
+            ("GIT_COMMITTER_NAME", "Jane Doe"),
+            ("GIT_COMMITTER_EMAIL", "jane@example.com"),
+        ];
+        git(dir, &["init", "-q", "-b", "main"], &[]).await.unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {\n}\n").unwrap();
+        std::fs::write(dir.join("README.md"), "# Demo\n").unwrap();
+        git(dir, &["add", "."], &[]).await.unwrap();
+        git(dir, &["commit", "-q", "-m", "Add main"], &author)
+            .await
+            .unwrap();
+        std::fs::write(dir.join("README.md"), "# Demo\n\nRun it.\n").unwrap();
+        git(dir, &["commit", "-q", "-am", "Document running"], &author)
+            .await
+            .unwrap();
+
+        std::fs::write(dir.join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "draft\n").unwrap();
+
+        let lines = blame(&dir.join("main.rs"), &[]).await.unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            (lines[0].author.as_str(), lines[0].date.as_str()),
+            ("Jane Doe", "2024-05-01")
+        );
+        assert_eq!(lines[0].summary, "Add main");
+        assert!(!lines[1].is_committed());
+        assert_eq!(
+            blame(&dir.join("main.rs"), &[(3, 3)]).await.unwrap()[0].text,
+            "}"
+        );
+
+        let repo_status = status(dir, 10).await.unwrap();
+        assert_eq!(repo_status.branch.as_deref(), Some("main"));
+        assert_eq!(
+            repo_status.files,
+            [
+                StatusEntry {
+                    path: "main.rs".to_string(),
+                    staged: None,
+                    unstaged: Some("modified"),
+                },
+                StatusEntry {
+                    path: "notes.txt".to_string(),
+                    staged: None,
+                    unstaged: Some("untracked"),
+                },
+            ]
+        );
+
+        let files = diff(dir, DiffTarget::Uncommitted, None).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0].additions, files[0].deletions), (1, 0));
+        assert!(files[0].patch.contains("+    run();"));
+        assert!(
+            diff(dir, DiffTarget::Staged, None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let files = diff(dir, DiffTarget::Commit("HEAD".to_string()), None)
+            .await
+            .unwrap();
+        assert_eq!(files[0].path, "README.md");
+
+        let commits = log(dir, None, None, 10).await.unwrap();
+        assert_eq!(
+            commits
+                .iter()
+                .map(|commit| commit.summary.as_str())
+                .collect::<Vec<&str>>(),
+            ["Document running", "Add main"]
+        );
+        let commits = log(dir, None, Some("main.rs".to_string()), 10)
+            .await
+            .unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].date, "2024-05-01");
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_and_restore() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        git(dir, &["init", "-q"], &[]).await.unwrap();
+        std::fs::write(dir.join("tracked.txt"), "one\n").unwrap();
+        std::fs::write(dir.join("untracked.txt"), "draft\n").unwrap();
+
+        let saved = checkpoint(dir, "before: rm *.txt").await.unwrap();
+        assert_eq!(
+            git(dir, &["status", "--short"], &[]).await.unwrap(),
+            "?? tracked.txt\n?? untracked.txt"
+        );
+        std::fs::remove_file(dir.join("tracked.txt")).unwrap();
+        std::fs::write(dir.join("untracked.txt"), "changed\n").unwrap();
+
+        assert_eq!(checkpoints(dir).await.unwrap(), vec![saved.clone()]);
+        restore(dir, &saved).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("tracked.txt")).unwrap(),
+            "one\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("untracked.txt")).unwrap(),
+            "draft\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit_checkout_and_apply() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        git(dir, &["init", "-q", "-b", "main"], &[]).await.unwrap();
+        git(dir, &["config", "user.name", "Jane Doe"], &[])
+            .await
+            .unwrap();
+        git(dir, &["config", "user.email", "jane@example.com"], &[])
+            .await
+            .unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "draft\n").unwrap();
+
+        std::fs::write(dir.join("deploy.pem"), "secret\n").unwrap();
+        std::fs::write(dir.join(".main.rs.voo-lock"), "").unwrap();
+        let policy = ReadPolicy::builtin();
+
+        let files = staged_files(dir, vec!["main.rs".to_string()], policy.clone())
+            .await
+            .unwrap();
+        assert_eq!(files, (vec!["main.rs".to_string()], vec![]));
+        // Nothing was staged by looking.
+        assert!(
+            staged_files(dir, vec![], policy.clone())
+                .await
+                .unwrap()
+                .0
+                .is_empty()
+        );
+        assert!(
+            commit(dir, vec![], "Empty".to_string(), policy.clone())
+                .await
+                .is_err()
+        );
+
+        // Hooks and signing would be skipped, so the commit is refused.
+        let hook = dir.join(".git/hooks/pre-commit");
+        std::fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let refused = commit(
+            dir,
+            vec![".".to_string()],
+            "Hook".to_string(),
+            policy.clone(),
+        );
+        assert!(refused.await.unwrap_err().contains("pre-commit hook"));
+        std::fs::remove_file(&hook).unwrap();
+        git(dir, &["config", "commit.gpgsign", "true"], &[])
+            .await
+            .unwrap();
+        let refused = commit(
+            dir,
+            vec![".".to_string()],
+            "Sign".to_string(),
+            policy.clone(),
+        );
+        assert!(refused.await.unwrap_err().contains("commit.gpgsign"));
+        git(dir, &["config", "commit.gpgsign", "false"], &[])
+            .await
+            .unwrap();
+
+        let info = commit(
+            dir,
+            vec![".".to_string()],
+            "Add main\n\nBody".to_string(),
+            policy.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(info.branch.as_deref(), Some("main"));
+        assert_eq!(info.summary, "Add main");
+        assert_eq!(info.files, ["main.rs", "notes.txt"]);
+        assert_eq!(info.skipped, ["deploy.pem"]);
+        let commits = log(dir, None, None, 10).await.unwrap();
+        assert_eq!(commits[0].commit, info.commit);
+        assert_eq!(commits[0].author, "Jane Doe");
+
+        let previous = checkout_branch(dir, "feature".to_string(), true)
+            .await
+            .unwrap();
+        assert_eq!(previous.as_deref(), Some("main"));
+        assert_eq!(
+            status(dir, 10).await.unwrap().branch.as_deref(),
+            Some("feature")
+        );
+        assert!(
+            checkout_branch(dir, "missing".to_string(), false)
+                .await
+                .is_err()
+        );
+
+        let patch = "--- a/main.rs\n+++ b/main.rs\n@@ -1 +1,3 @@\n-fn main() {}\n+fn main() {\n+    run();\n+}\n";
+        let files = patch_files(dir, patch.to_string()).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "main.rs");
+        assert!(files[0].1.ends_with("main.rs") && files[0].1.is_file());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        apply_patch(dir, patch.to_string()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("main.rs")).unwrap(),
+            "fn main() {\n    run();\n}\n"
+        );
+        assert!(apply_patch(dir, patch.to_string()).await.is_err());
+        let added = "--- /dev/null\n+++ b/lib.rs\n@@ -0,0 +1 @@\n+pub fn run() {}\n";
+        apply_patch(dir, added.to_string()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("lib.rs")).unwrap(),
+            "pub fn run() {}\n"
+        );
+        // The second file does not apply, so the first is left alone too.
+        let partial = "--- a/lib.rs\n+++ b/lib.rs\n@@ -1 +1 @@\n-pub fn run() {}\n+pub fn run() { todo!() }\n--- a/main.rs\n+++ b/main.rs\n@@ -1 +1 @@\n-fn missing() {}\n+fn main() {}\n";
+        assert!(apply_patch(dir, partial.to_string()).await.is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("lib.rs")).unwrap(),
+            "pub fn run() {}\n"
+        );
+
+        // Local changes the switch would overwrite keep it from happening.
+        commit(dir, vec!["main.rs".to_string()], "Run".to_string(), policy)
+            .await
+            .unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n// wip\n").unwrap();
+        assert!(
+            checkout_branch(dir, "main".to_string(), false)
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            status(dir, 10).await.unwrap().branch.as_deref(),
+            Some("feature")
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::HashMap, path::Path};
+
+use domain::models::tools::{ToolDefinition, ToolDescriptionDecorator};
+
+// Tells the tools that take a path where relative paths start. Read each time
+// the tools are sent, so it follows a changed working directory.
+#[derive(Debug)]
+pub struct WorkspaceRoot;
+
+impl ToolDescriptionDecorator for WorkspaceRoot {
+    fn name(&self) -> &str {
+        "workspace_root"
+    }
+
+    fn hint(&self, definition: &ToolDefinition) -> Option<String> {
+        definition.parameters.properties.get("path")?;
+        let root = std::env::current_dir().ok()?;
+
+        Some(format!(
+            "Relative paths are resolved against the project root {}.",
+            root.display()
+        ))
+    }
+}
+
+// Points the command tool at the test command of the project, so the model
+// does not have to guess it.
+#[derive(Debug)]
+pub struct TestCommand {
+    tool: String,
+}
+
+impl TestCommand {
+    pub fn new(tool: &str) -> Self {
+        Self {
+            tool: tool.to_string(),
+        }
+    }
+}
+
+impl ToolDescriptionDecorator for TestCommand {
+    fn name(&self) -> &str {
+        "test_command"
+    }
+
+    fn hint(&self, definition: &ToolDefinition) -> Option<String> {
+        if definition.name != self.tool {
+            return None;
+        }
+        let command = detect_test_command(&std::env::current_dir().ok()?)?;
+
+        Some(format!(
+            "The test command of this project is `{}`.",
+            command
+        ))
+    }
+}
+
+// Renders the examples of a tool into its description.
+#[derive(Debug)]
+pub struct ToolExamples;
+
+impl ToolDescriptionDecorator for ToolExamples {
+    fn name(&self) -> &str {
+        "examples"
+    }
+
+    fn hint(&self, definition: &ToolDefinition) -> Option<String> {
+        definition.examples_text()
+    }
+}
+
+// Fixed hints per tool, e.g. conventions of the project.
+#[derive(Debug, Default)]
+pub struct StaticHints {
+    hints: HashMap<String, Vec<String>>,
+}
+
+impl StaticHints {
+    pub fn with_hint(mut self, tool: &str, hint: &str) -> Self {
+        self.hints
+            .entry(tool.to_string())
+            .or_default()
+            .push(hint.trim().to_string());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+}
+
+impl ToolDescriptionDecorator for StaticHints {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    fn hint(&self, definition: &ToolDefinition) -> Option<String> {
+        self.hints
+            .get(&definition.name)
+            .map(|hints| hints.join("\n"))
+    }
+}
+
+// Task runners come first since their test recipe usually wraps the build
+// tool with the right flags.
+pub fn detect_test_command(root: &Path) -> Option<String> {
+    let read = |name: &str| std::fs::read_to_string(root.join(name)).ok();
+
+    let has_recipe = |content: &str, separators: &[char]| {
+        content.lines().any(|line| {
+            line.strip_prefix("test")
+                .and_then(|rest| rest.chars().next())
+                .is_some_and(|next| separators.contains(&next))
+        })
+    };
+    if let Some(justfile) = read("justfile").or_else(|| read("Justfile"))
+        && has_recipe(&justfile, &[':', ' '])
+    {
+        return Some("just test".to_string());
+    }
+    if let Some(makefile) = read("Makefile")
+        && has_recipe(&makefile, &[':'])
+    {
+        return Some("make test".to_string());
+    }
+
+    if let Some(manifest) = read("Cargo.toml") {
+        return Some(match manifest.contains("[workspace]") {
+            true => "cargo test --workspace".to_string(),
+            false => "cargo test".to_string(),
+        });
+    }
+    if let Some(package) = read("package.json") {
+        let package = serde_json::from_str::<serde_json::Value>(&package).ok()?;
+        package.get("scripts")?.get("test")?;
+        let runner = if root.join("pnpm-lock.yaml").exists() {
+            "pnpm"
+        } else if root.join("yarn.lock").exists() {
+            "yarn"
+        } else {
+            "npm"
+        };
+        return Some(format!("{} test", runner));
+    }
+    if root.join("go.mod").exists() {
+        return Some("go test ./...".to_string());
+    }
+    if ["pyproject.toml", "pytest.ini", "setup.cfg"]
+        .iter()
+        .any(|name| root.join(name).exists())
+    {
+        return Some("pytest".to_string());
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use domain::models::tools::{Parameters, ToolDecorators, ToolExample};
+
+    use super::*;
+
+    #[test]
+    fn test_detect_test_command() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        assert_eq!(detect_test_command(dir), None);
+
+        std::fs::write(dir.join("Cargo.toml"), "[workspace]\nmembers = []\n").unwrap();
+        assert_eq!(
+            detect_test_command(dir).as_deref(),
+            Some("cargo test --workspace")
+        );
+
+        std::fs::write(
+            dir.join("justfile"),
+            "build:\n\tcargo build\n\ntest *args:\n",
+        )
+        .unwrap();
+        assert_eq!(detect_test_command(dir).as_deref(), Some("just test"));
+    }
+
+    #[test]
+    fn test_static_hints() {
+        let definition = |name: &str| ToolDefinition {
+            name: name.to_string(),
+            description: "Run a shell command.".to_string(),
+            parameters: Parameters::default(),
+            examples: vec![],
+        };
+        let decorators = ToolDecorators::new(vec![
+            Arc::new(
+                StaticHints::default().with_hint("run_command", "Never run the deploy script."),
+            ),
+            Arc::new(ToolExamples),
+        ]);
+
+        let mut with_example = definition("grep");
+        with_example.examples = vec![ToolExample::new(
+            serde_json::json!({"pattern": "main"}),
+            "src/main.rs:1: fn main() {",
+        )];
+        let decorated = decorators.apply(vec![definition("run_command"), with_example]);
+        assert_eq!(
+            decorated[0].description,
+            "Run a shell command.\n\nNever run the deploy script."
+        );
+        assert_eq!(
+            decorated[1].description,
+            "Run a shell command.\n\nExamples:\nCall: grep({\"pattern\":\"main\"})\nResult: src/main.rs:1: fn main() {"
+        );
+    }
+}
@@ -1,2 +1,15 @@
+pub mod capabilities;
+pub mod embeddings;
+pub mod git;
+pub mod hints;
+pub mod lsp;
+pub mod media;
 pub mod models;
+pub mod processors;
+pub mod prompt;
+pub mod prune;
+pub mod relay;
 pub mod tools;
+pub mod transcription;
+pub mod video;
+pub mod web_search;
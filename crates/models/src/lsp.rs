@@ -0,0 +1,493 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use serde_json::{Value, json};
+use similar::{DiffTag, TextDiff};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+use url::Url;
+
+// Returned while the server is still loading the workspace.
+const CONTENT_MODIFIED: i64 = -32801;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum LspError {
+    Io(String),
+    Protocol(String),
+    Server { code: i64, message: String },
+    Timeout(String),
+}
+
+impl Display for LspError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LspError::Io(msg) => write!(f, "LSP io error: {}", msg),
+            LspError::Protocol(msg) => write!(f, "LSP protocol error: {}", msg),
+            LspError::Server { code, message } => write!(f, "LSP error {}: {}", code, message),
+            LspError::Timeout(method) => write!(f, "LSP request timed out: {}", method),
+        }
+    }
+}
+
+fn io_error(e: std::io::Error) -> LspError {
+    LspError::Io(e.to_string())
+}
+
+// Language server commands by file extension.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageServers {
+    commands: HashMap<String, Vec<String>>,
+}
+
+impl LanguageServers {
+    pub fn builtin() -> Self {
+        let typescript = ["ts", "tsx", "js", "jsx"];
+
+        let mut servers = Self::default()
+            .with_command("rs", &["rust-analyzer"])
+            .with_command("py", &["pylsp"])
+            .with_command("go", &["gopls"]);
+        for extension in typescript {
+            servers = servers.with_command(extension, &["typescript-language-server", "--stdio"]);
+        }
+        servers
+    }
+
+    pub fn with_command(mut self, extension: &str, command: &[&str]) -> Self {
+        self.commands.insert(
+            extension.to_lowercase(),
+            command.iter().map(|arg| arg.to_string()).collect(),
+        );
+        self
+    }
+
+    pub fn command(&self, path: &Path) -> Option<&[String]> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        self.commands
+            .get(&extension)
+            .map(Vec::as_slice)
+            .filter(|command| !command.is_empty())
+    }
+}
+
+// A minimal stdio client for one language server process.
+#[derive(Debug)]
+pub struct LspClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl LspClient {
+    pub async fn start(command: &[String], root: &Path) -> Result<Self, LspError> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| LspError::Protocol("empty server command".to_string()))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| LspError::Io(format!("{}: {}", program, e)))?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| LspError::Io("no stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| LspError::Io("no stdout".to_string()))?;
+        let mut client = Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            next_id: 0,
+        };
+
+        let root_uri = file_uri(root)?;
+        client
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "rootUri": root_uri,
+                    "workspaceFolders": [{ "uri": root_uri, "name": "workspace" }],
+                    "capabilities": {
+                        "general": { "positionEncodings": ["utf-16"] },
+                        "textDocument": { "rename": { "prepareSupport": false } },
+                        "workspace": { "workspaceEdit": { "documentChanges": true } }
+                    }
+                }),
+            )
+            .await?;
+        client.notify("initialized", json!({})).await?;
+
+        Ok(client)
+    }
+
+    pub async fn open(&mut self, path: &Path, language_id: &str) -> Result<(), LspError> {
+        let text = std::fs::read_to_string(path).map_err(io_error)?;
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": file_uri(path)?,
+                    "languageId": language_id,
+                    "version": 1,
+                    "text": text
+                }
+            }),
+        )
+        .await
+    }
+
+    // Sends a request, retrying while the server reports it is still loading
+    // the workspace.
+    pub async fn request_when_ready(
+        &mut self,
+        method: &str,
+        params: Value,
+        attempts: usize,
+    ) -> Result<Value, LspError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.request(method, params.clone()).await {
+                Err(LspError::Server { code, .. })
+                    if code == CONTENT_MODIFIED && attempt < attempts => {}
+                Ok(Value::Null) if attempt < attempts => {}
+                response => return response,
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value, LspError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+
+        tokio::time::timeout(REQUEST_TIMEOUT, self.response(id))
+            .await
+            .map_err(|_| LspError::Timeout(method.to_string()))?
+    }
+
+    pub async fn notify(&mut self, method: &str, params: Value) -> Result<(), LspError> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+
+    pub async fn shutdown(mut self) {
+        if self.request("shutdown", Value::Null).await.is_ok() {
+            _ = self.notify("exit", Value::Null).await;
+        }
+        _ = self.child.kill().await;
+    }
+
+    async fn response(&mut self, id: i64) -> Result<Value, LspError> {
+        loop {
+            let message = self.receive().await?;
+
+            // Requests from the server are answered with an empty result,
+            // e.g. workspace/configuration gets a null per requested item.
+            if let Some(method) = message.get("method").and_then(Value::as_str) {
+                if let Some(request_id) = message.get("id") {
+                    let result = match method {
+                        "workspace/configuration" => {
+                            let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                            Value::Array(vec![Value::Null; items])
+                        }
+                        _ => Value::Null,
+                    };
+                    self.send(&json!({ "jsonrpc": "2.0", "id": request_id, "result": result }))
+                        .await?;
+                }
+                continue;
+            }
+
+            if message.get("id").and_then(Value::as_i64) != Some(id) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(LspError::Server {
+                    code: error["code"].as_i64().unwrap_or_default(),
+                    message: error["message"].as_str().unwrap_or_default().to_string(),
+                });
+            }
+
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    async fn send(&mut self, message: &Value) -> Result<(), LspError> {
+        let body = serde_json::to_string(message).map_err(|e| LspError::Protocol(e.to_string()))?;
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+
+        self.stdin
+            .write_all(frame.as_bytes())
+            .await
+            .map_err(io_error)?;
+        self.stdin.flush().await.map_err(io_error)
+    }
+
+    async fn receive(&mut self) -> Result<Value, LspError> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).await.map_err(io_error)? == 0 {
+                return Err(LspError::Io("the language server exited".to_string()));
+            }
+
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+
+        let length =
+            length.ok_or_else(|| LspError::Protocol("missing Content-Length".to_string()))?;
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).await.map_err(io_error)?;
+
+        serde_json::from_slice(&body).map_err(|e| LspError::Protocol(e.to_string()))
+    }
+}
+
+pub fn file_uri(path: &Path) -> Result<String, LspError> {
+    let path = std::fs::canonicalize(path).map_err(io_error)?;
+    Url::from_file_path(&path)
+        .map(String::from)
+        .map_err(|_| LspError::Protocol(format!("{} is not an absolute path", path.display())))
+}
+
+pub fn uri_path(uri: &str) -> Result<PathBuf, LspError> {
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or_else(|| LspError::Protocol(format!("unsupported uri: {}", uri)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "range": {
+                "start": { "line": self.start.0, "character": self.start.1 },
+                "end": { "line": self.end.0, "character": self.end.1 },
+            },
+            "newText": self.new_text,
+        })
+    }
+}
+
+// The text edits of a WorkspaceEdit by file, from either `changes` or
+// `documentChanges`.
+pub fn workspace_edits(edit: &Value) -> Result<Vec<(PathBuf, Vec<TextEdit>)>, LspError> {
+    let mut files = vec![];
+
+    if let Some(changes) = edit.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            files.push((uri_path(uri)?, text_edits(edits)));
+        }
+    }
+    for change in edit
+        .get("documentChanges")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let Some(uri) = change["textDocument"]["uri"].as_str() else {
+            return Err(LspError::Protocol(
+                "file operations in rename results are not supported".to_string(),
+            ));
+        };
+        files.push((uri_path(uri)?, text_edits(&change["edits"])));
+    }
+
+    Ok(files)
+}
+
+fn text_edits(edits: &Value) -> Vec<TextEdit> {
+    let position = |value: &Value| {
+        (
+            value["line"].as_u64().unwrap_or_default() as usize,
+            value["character"].as_u64().unwrap_or_default() as usize,
+        )
+    };
+
+    edits
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|edit| TextEdit {
+            start: position(&edit["range"]["start"]),
+            end: position(&edit["range"]["end"]),
+            new_text: edit["newText"].as_str().unwrap_or_default().to_string(),
+        })
+        .collect()
+}
+
+// Byte offset of an LSP position, whose character is counted in UTF-16 code
+// units.
+fn offset(text: &str, (line, character): (usize, usize)) -> Option<usize> {
+    let mut start = 0;
+    for _ in 0..line {
+        start += text[start..].find('\n')? + 1;
+    }
+
+    let mut units = 0;
+    for (index, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(start + index);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> Result<String, LspError> {
+    let mut ranges = edits
+        .iter()
+        .map(|edit| {
+            let start = offset(text, edit.start);
+            let end = offset(text, edit.end);
+            match (start, end) {
+                (Some(start), Some(end)) if start <= end => Ok((start, end, edit)),
+                _ => Err(LspError::Protocol("edit out of range".to_string())),
+            }
+        })
+        .collect::<Result<Vec<(usize, usize, &TextEdit)>, LspError>>()?;
+    ranges.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+
+    let mut text = text.to_string();
+    for (start, end, edit) in ranges {
+        text.replace_range(start..end, &edit.new_text);
+    }
+    Ok(text)
+}
+
+// The edits that turn `old` into `new`, one for every run of changed lines.
+pub fn diff_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    // The end of a last line without a newline is on that line.
+    let position = |line: usize| match line.checked_sub(1).map(|last| old_lines[last]) {
+        Some(last) if line == old_lines.len() && !last.ends_with('\n') => {
+            (line - 1, last.encode_utf16().count())
+        }
+        _ => (line, 0),
+    };
+
+    diff.ops()
+        .iter()
+        .map(|op| op.as_tag_tuple())
+        .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+        .map(|(_, old_range, new_range)| TextEdit {
+            start: position(old_range.start),
+            end: position(old_range.end),
+            new_text: new_lines[new_range].concat(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_edits() {
+        let text = "fn old() {}\nlet s = \"é\"; old();\n";
+        let edits = vec![
+            TextEdit {
+                start: (0, 3),
+                end: (0, 6),
+                new_text: "new".to_string(),
+            },
+            TextEdit {
+                start: (1, 13),
+                end: (1, 16),
+                new_text: "new".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            apply_edits(text, &edits).unwrap(),
+            "fn new() {}\nlet s = \"é\"; new();\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_edits() {
+        let old = "fn main() {\n    println!(\"é\");\n}\n\nfn unused() {}";
+        for new in [
+            "fn main() {\n    println!(\"hello\");\n}\n\nfn unused() {}",
+            "fn main() {\n    println!(\"é\");\n}\n\nfn used() {}\n",
+            "use std::io;\n\nfn main() {\n}\n",
+            "",
+            old,
+        ] {
+            let edits = diff_edits(old, new);
+            assert_eq!(apply_edits(old, &edits).unwrap(), new);
+        }
+        assert!(diff_edits(old, old).is_empty());
+
+        let edits = diff_edits("a\nb\n", "a\nc\n");
+        assert_eq!(
+            edits,
+            vec![TextEdit {
+                start: (1, 0),
+                end: (2, 0),
+                new_text: "c\n".to_string(),
+            }]
+        );
+        assert_eq!(edits[0].to_json()["range"]["end"]["line"], 2);
+        assert_eq!(apply_edits("", &diff_edits("", "new\n")).unwrap(), "new\n");
+
+        // A buffer with \r\n line endings only has the changed line replaced.
+        let edits = diff_edits("a\r\nb\r\n", "a\r\nc\r\n");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "c\r\n");
+    }
+
+    #[test]
+    fn test_workspace_edits() {
+        let edit = json!({
+            "documentChanges": [{
+                "textDocument": { "uri": "file:///src/lib.rs", "version": 1 },
+                "edits": [{
+                    "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 7 } },
+                    "newText": "bar"
+                }]
+            }]
+        });
+
+        let files = workspace_edits(&edit).unwrap();
+        assert_eq!(files[0].0, PathBuf::from("/src/lib.rs"));
+        assert_eq!(files[0].1[0].start, (2, 4));
+        assert_eq!(files[0].1[0].new_text, "bar");
+    }
+}
@@ -0,0 +1,160 @@
+use std::{path::Path, time::Duration};
+
+use domain::models::agent::{AgentError, Content};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    attachments::AttachmentManager,
+    endpoints::{EndpointAuth, GeminiEndpoint},
+};
+
+// The type of an image, audio, video or PDF file as Gemini names it, by its
+// extension.
+pub fn media_mime(path: &Path) -> Option<&'static str> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mp3",
+        "aif" | "aiff" => "audio/aiff",
+        "aac" | "m4a" => "audio/aac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        _ => return None,
+    })
+}
+
+// Generous, a long recording or video takes a while to go through.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Sends the one-off generateContent requests of the tools that hand media to
+// Gemini whatever provider answers the chat, such as transcribe_audio and
+// ask_video. Media too large to go inline is uploaded to the Files API first.
+#[derive(Debug)]
+pub struct GeminiMedia {
+    api_key: String,
+    endpoint: GeminiEndpoint,
+    reqwest: reqwest::Client,
+    attachments: AttachmentManager,
+}
+
+impl GeminiMedia {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            endpoint: GeminiEndpoint::studio(),
+            reqwest: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            attachments: AttachmentManager::new(api_key),
+        }
+    }
+
+    // Where the requests go, for the organization policy to check.
+    pub fn endpoint(&self) -> &GeminiEndpoint {
+        &self.endpoint
+    }
+
+    // Media too large to go inline is refused instead of uploaded.
+    pub fn without_uploads(mut self) -> Self {
+        self.attachments = AttachmentManager::new(&self.api_key).without_uploads();
+        self
+    }
+
+    pub fn upload_url(&self) -> Option<String> {
+        self.attachments.upload_url()
+    }
+
+    pub(crate) fn attachments(&self) -> &AttachmentManager {
+        &self.attachments
+    }
+
+    // Sends a generateContent request and returns the response body, errors
+    // mapped like those of the chat client.
+    pub(crate) async fn generate(
+        &self,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<String, AgentError> {
+        let builder = self.reqwest.post(self.endpoint.url(model)).json(request);
+        let builder = match &self.endpoint.auth {
+            EndpointAuth::ApiKey => builder.header("x-goog-api-key", &self.api_key),
+            EndpointAuth::Bearer(token) => builder.bearer_auth(token),
+        };
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+
+        match status.as_u16() {
+            200..=299 => Ok(text),
+            429 => Err(AgentError::RateLimited {
+                retry_after: None,
+                message: text,
+            }),
+            _ => Err(AgentError::AgentError(Some(format!(
+                "gemini {}: {}",
+                status, text
+            )))),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct GenerateRequest {
+    pub contents: Vec<Content>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenerateResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Candidate {
+    #[serde(default)]
+    content: Content,
+}
+
+// The text of the first candidate, None when it is empty.
+pub(crate) fn answer(body: &str) -> Result<Option<String>, AgentError> {
+    let response = serde_json::from_str::<GenerateResponse>(body)
+        .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+    let text = response
+        .candidates
+        .into_iter()
+        .next()
+        .map(|candidate| {
+            candidate
+                .content
+                .parts
+                .iter()
+                .filter_map(|part| part.answer().cloned())
+                .collect::<Vec<String>>()
+                .join("")
+        })
+        .unwrap_or_default();
+
+    match text.trim().is_empty() {
+        true => Ok(None),
+        false => Ok(Some(text.trim().to_string())),
+    }
+}
@@ -0,0 +1,593 @@
+use std::time::{Duration, Instant};
+
+use domain::models::{
+    agent::{AgentError, Content, Part},
+    attachment::{Attachment, estimate_tokens},
+};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::endpoints::GeminiEndpoint;
+
+// A request may be 20 MB in total, base64 makes an attachment a third larger
+// and the rest of the conversation needs room too.
+const INLINE_LIMIT: usize = 8 * 1024 * 1024;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const PROCESSING_TIMEOUT: Duration = Duration::from_secs(300);
+const DELETE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FileState {
+    Processing,
+    Active,
+    Failed,
+    #[default]
+    #[serde(other)]
+    StateUnspecified,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedFile {
+    // `files/<id>`, what the file is polled and deleted by.
+    pub name: String,
+    // What parts refer to the file by.
+    pub uri: String,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub state: FileState,
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    file: UploadedFile,
+}
+
+// The files bound to the conversation, each with the part sent for it.
+#[derive(Debug, Default)]
+struct Bound {
+    next_id: usize,
+    files: Vec<(Attachment, Part)>,
+}
+
+// Attaches media to Gemini requests, inline when it is small enough and
+// through the Files API otherwise. Uploaded files are deleted by `cleanup`
+// once the session ends, or when the manager is dropped without it, Gemini
+// drops them after 48 hours anyway.
+#[derive(Debug)]
+pub struct AttachmentManager {
+    api_key: String,
+    // The Files API, None when the endpoints have none and large attachments
+    // are refused.
+    base_url: Option<String>,
+    inline_limit: usize,
+    poll_interval: Duration,
+    reqwest: reqwest::Client,
+    uploaded: Mutex<Vec<UploadedFile>>,
+    bound: Mutex<Bound>,
+}
+
+impl AttachmentManager {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            base_url: GeminiEndpoint::studio().files_url(),
+            inline_limit: INLINE_LIMIT,
+            poll_interval: POLL_INTERVAL,
+            reqwest: reqwest::Client::new(),
+            uploaded: Mutex::default(),
+            bound: Mutex::default(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(format!("{}/", base_url.trim_end_matches('/')));
+        self
+    }
+
+    pub fn without_uploads(mut self) -> Self {
+        self.base_url = None;
+        self
+    }
+
+    // Where uploads start, for checking against the endpoint policy.
+    pub fn upload_url(&self) -> Option<String> {
+        self.base_url
+            .as_ref()
+            .map(|base_url| format!("{}upload/v1beta/files", base_url))
+    }
+
+    fn files_url(&self) -> Result<&str, AgentError> {
+        self.base_url.as_deref().ok_or_else(|| {
+            AgentError::AgentError(Some(format!(
+                "attachments larger than {} MB need the Gemini Files API, which the configured endpoints do not offer",
+                self.inline_limit / (1024 * 1024)
+            )))
+        })
+    }
+
+    // Attachments larger than this many bytes are uploaded.
+    pub fn with_inline_limit(mut self, inline_limit: usize) -> Self {
+        self.inline_limit = inline_limit;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    // A part carrying `data`, uploaded first when it is too large to go inline.
+    pub async fn part(&self, mime: &str, data: &[u8]) -> Result<Part, AgentError> {
+        if data.len() <= self.inline_limit {
+            return Ok(Part::inline_data(mime, data));
+        }
+
+        let file = self.upload(mime, data).await?;
+        let mime = match file.mime_type.is_empty() {
+            true => mime,
+            false => &file.mime_type,
+        };
+        Ok(Part::file_data(Some(mime), &file.uri))
+    }
+
+    // Uploads `data` with a resumable upload and waits until Gemini processed
+    // it, which takes a while for videos.
+    pub async fn upload(&self, mime: &str, data: &[u8]) -> Result<UploadedFile, AgentError> {
+        let response = self
+            .reqwest
+            .post(format!("{}upload/v1beta/files", self.files_url()?))
+            .header("x-goog-api-key", &self.api_key)
+            .header("X-Goog-Upload-Protocol", "resumable")
+            .header("X-Goog-Upload-Command", "start")
+            .header("X-Goog-Upload-Header-Content-Length", data.len())
+            .header("X-Goog-Upload-Header-Content-Type", mime)
+            .json(&serde_json::json!({ "file": { "display_name": "voo attachment" } }))
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let response = checked(response).await?;
+        let upload_url = response
+            .headers()
+            .get("x-goog-upload-url")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                AgentError::AgentError(Some("Gemini returned no upload URL".to_string()))
+            })?
+            .to_string();
+
+        let response = self
+            .reqwest
+            .post(upload_url)
+            .header("X-Goog-Upload-Offset", "0")
+            .header("X-Goog-Upload-Command", "upload, finalize")
+            .body(data.to_vec())
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let file = checked(response)
+            .await?
+            .json::<UploadResponse>()
+            .await
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?
+            .file;
+        info!("[VOO] uploaded {} bytes as {}", data.len(), file.name);
+        self.uploaded.lock().await.push(file.clone());
+
+        self.wait_active(file).await
+    }
+
+    async fn wait_active(&self, mut file: UploadedFile) -> Result<UploadedFile, AgentError> {
+        let started = Instant::now();
+        loop {
+            match file.state {
+                FileState::Active => return Ok(file),
+                FileState::Failed => {
+                    return Err(AgentError::AgentError(Some(format!(
+                        "Gemini could not process {}",
+                        file.name
+                    ))));
+                }
+                _ if started.elapsed() > PROCESSING_TIMEOUT => {
+                    return Err(AgentError::AgentError(Some(format!(
+                        "{} was still processing after {} seconds",
+                        file.name,
+                        PROCESSING_TIMEOUT.as_secs()
+                    ))));
+                }
+                _ => tokio::time::sleep(self.poll_interval).await,
+            }
+
+            let response = self
+                .reqwest
+                .get(format!("{}v1beta/{}", self.files_url()?, file.name))
+                .header("x-goog-api-key", &self.api_key)
+                .send()
+                .await
+                .map_err(|e| AgentError::Network(e.to_string()))?;
+            file = checked(response)
+                .await?
+                .json::<UploadedFile>()
+                .await
+                .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        }
+    }
+
+    // Binds a file to the conversation. Text goes as text, images, audio,
+    // video and PDFs the way `part` sends them, other binary files are
+    // refused.
+    pub async fn bind(
+        &self,
+        name: &str,
+        mime: &str,
+        data: &[u8],
+    ) -> Result<Attachment, AgentError> {
+        let text = match std::str::from_utf8(data) {
+            Ok(text) if !data.contains(&0) => Some(text),
+            _ => None,
+        };
+        let media = ["image/", "audio/", "video/"]
+            .iter()
+            .any(|prefix| mime.starts_with(prefix))
+            || mime == "application/pdf";
+        let (part, uploaded) = match text {
+            Some(_) if !media && data.len() > self.inline_limit => {
+                return Err(AgentError::AgentError(Some(format!(
+                    "{} is {} bytes, text attachments are limited to {} bytes",
+                    name,
+                    data.len(),
+                    self.inline_limit
+                ))));
+            }
+            Some(text) if !media => (
+                Part::new(&format!("The contents of {}:\n{}", name, text)),
+                false,
+            ),
+            _ if media => {
+                let part = self.part(mime, data).await?;
+                let uploaded = part.file_data.is_some();
+                (part, uploaded)
+            }
+            _ => {
+                return Err(AgentError::AgentError(Some(format!(
+                    "{} is {}, only text, images, audio, video and PDFs can be attached",
+                    name, mime
+                ))));
+            }
+        };
+
+        let mut bound = self.bound.lock().await;
+        bound.next_id += 1;
+        let attachment = Attachment {
+            id: bound.next_id.to_string(),
+            name: name.to_string(),
+            mime: mime.to_string(),
+            bytes: data.len() as u64,
+            tokens: estimate_tokens(mime, data),
+            uploaded,
+        };
+        bound.files.push((attachment.clone(), part));
+        Ok(attachment)
+    }
+
+    pub async fn bound(&self) -> Vec<Attachment> {
+        let bound = self.bound.lock().await;
+        bound.files.iter().map(|(file, _)| file.clone()).collect()
+    }
+
+    // Unbinds the attachment with the id, or the name when no id matches,
+    // deleting its upload.
+    pub async fn unbind(&self, id: &str) -> Result<Attachment, AgentError> {
+        let (attachment, part) = {
+            let mut bound = self.bound.lock().await;
+            let index = bound
+                .files
+                .iter()
+                .position(|(file, _)| file.id == id)
+                .or_else(|| bound.files.iter().position(|(file, _)| file.name == id))
+                .ok_or_else(|| AgentError::AgentError(Some(format!("No attachment {}", id))))?;
+            bound.files.remove(index)
+        };
+        self.release(&part).await;
+        Ok(attachment)
+    }
+
+    // The content carrying the bound files, sent ahead of the conversation.
+    pub async fn bound_content(&self) -> Option<Content> {
+        let bound = self.bound.lock().await;
+        if bound.files.is_empty() {
+            return None;
+        }
+
+        let mut parts = vec![];
+        for (file, part) in &bound.files {
+            if part.text.is_none() {
+                parts.push(Part::new(&format!("The attached file {}:", file.name)));
+            }
+            parts.push(part.clone());
+        }
+        Some(Content::new(parts, "user"))
+    }
+
+    // Deletes the file `part` refers to, once the request it was made for is
+    // done. Inline parts have nothing to delete.
+    pub async fn release(&self, part: &Part) {
+        let Some(file_data) = &part.file_data else {
+            return;
+        };
+        let file = {
+            let mut uploaded = self.uploaded.lock().await;
+            match uploaded
+                .iter()
+                .position(|file| file.uri == file_data.file_uri)
+            {
+                Some(index) => uploaded.remove(index),
+                None => return,
+            }
+        };
+        self.delete(&file.name).await;
+    }
+
+    // Deletes the files uploaded so far. Failures are logged, the files
+    // expire on their own.
+    pub async fn cleanup(&self) {
+        self.bound.lock().await.files.clear();
+        let files = std::mem::take(&mut *self.uploaded.lock().await);
+        for file in files {
+            self.delete(&file.name).await;
+        }
+    }
+
+    async fn delete(&self, name: &str) {
+        if let Some(base_url) = &self.base_url {
+            delete(&self.reqwest, base_url, &self.api_key, name).await;
+        }
+    }
+}
+
+// A session that ended on an error never got to `cleanup`, so what is still
+// uploaded is deleted here, from a thread of its own since a runtime cannot
+// be blocked on from inside another.
+impl Drop for AttachmentManager {
+    fn drop(&mut self) {
+        let files = std::mem::take(self.uploaded.get_mut());
+        let Some(base_url) = self.base_url.clone() else {
+            return;
+        };
+        if files.is_empty() {
+            return;
+        }
+
+        let api_key = self.api_key.clone();
+        let cleanup = std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => return warn!("[VOO] could not delete uploaded files: {}", e),
+            };
+            let reqwest = reqwest::Client::builder()
+                .timeout(DELETE_TIMEOUT)
+                .build()
+                .unwrap_or_default();
+            runtime.block_on(async {
+                for file in files {
+                    delete(&reqwest, &base_url, &api_key, &file.name).await;
+                }
+            });
+        });
+        _ = cleanup.join();
+    }
+}
+
+async fn delete(reqwest: &reqwest::Client, base_url: &str, api_key: &str, name: &str) {
+    let response = reqwest
+        .delete(format!("{}v1beta/{}", base_url, name))
+        .header("x-goog-api-key", api_key)
+        .send()
+        .await
+        .map_err(|e| AgentError::Network(e.to_string()));
+    let deleted = match response {
+        Ok(response) => checked(response).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = deleted {
+        warn!("[VOO] could not delete {}: {}", name, e);
+    }
+}
+
+async fn checked(response: reqwest::Response) -> Result<reqwest::Response, AgentError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    Err(match status.as_u16() {
+        429 => AgentError::RateLimited {
+            retry_after: None,
+            message: text,
+        },
+        _ => AgentError::AgentError(Some(format!("gemini files {}: {}", status, text))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    // The request line and body of one request.
+    async fn read_request(socket: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut buffer = vec![];
+        let mut chunk = [0; 4096];
+        let headers_end = loop {
+            let read = socket.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break end + 4;
+            }
+        };
+        let headers = String::from_utf8_lossy(&buffer[..headers_end]).to_lowercase();
+        let length = headers
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .map(|length| length.trim().parse::<usize>().unwrap())
+            .unwrap_or_default();
+        while buffer.len() < headers_end + length {
+            let read = socket.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+
+        let line = headers.lines().next().unwrap_or_default().to_string();
+        (line, buffer[headers_end..].to_vec())
+    }
+
+    // Plays the Files API: the upload is still processing at first and active
+    // once polled.
+    async fn serve(requests: Arc<Mutex<Vec<String>>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let upload_url = format!("{}/upload-session", base_url);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (line, body) = read_request(&mut socket).await;
+                requests.lock().await.push(line.clone());
+                let file = |state: &str| {
+                    json!({
+                        "name": "files/abc",
+                        "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc",
+                        "mimeType": "video/mp4",
+                        "state": state
+                    })
+                };
+                let (headers, body) = match line.split_whitespace().take(2).collect::<Vec<&str>>()[..]
+                {
+                    ["post", "/upload/v1beta/files"] => {
+                        (format!("x-goog-upload-url: {}\r\n", upload_url), json!({}))
+                    }
+                    ["post", "/upload-session"] => {
+                        assert_eq!(body.len(), 16);
+                        (String::new(), json!({ "file": file("PROCESSING") }))
+                    }
+                    ["get", "/v1beta/files/abc"] => (String::new(), file("ACTIVE")),
+                    _ => (String::new(), json!({})),
+                };
+                let body = body.to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    headers,
+                    body.len(),
+                    body
+                );
+                _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        base_url
+    }
+
+    #[tokio::test]
+    async fn test_attachments() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let manager = AttachmentManager::new("key")
+            .with_base_url(&serve(requests.clone()).await)
+            .with_inline_limit(8)
+            .with_poll_interval(Duration::from_millis(1));
+
+        let part = manager.part("image/png", b"small").await.unwrap();
+        assert!(part.inline_data.is_some());
+
+        let part = manager.part("video/mp4", &[0; 16]).await.unwrap();
+        let file_data = part.file_data.clone().unwrap();
+        assert_eq!(file_data.mime_type.as_deref(), Some("video/mp4"));
+        assert_eq!(
+            file_data.file_uri,
+            "https://generativelanguage.googleapis.com/v1beta/files/abc"
+        );
+
+        manager.release(&part).await;
+        manager.cleanup().await;
+        assert_eq!(
+            *requests.lock().await,
+            [
+                "post /upload/v1beta/files http/1.1",
+                "post /upload-session http/1.1",
+                "get /v1beta/files/abc http/1.1",
+                "delete /v1beta/files/abc http/1.1",
+            ]
+        );
+
+        let notes = manager
+            .bind("notes.md", "text/markdown", b"# Notes")
+            .await
+            .unwrap();
+        assert_eq!((notes.id.as_str(), notes.uploaded), ("1", false));
+        let demo = manager
+            .bind("demo.mp4", "video/mp4", &[0; 16])
+            .await
+            .unwrap();
+        assert_eq!((demo.id.as_str(), demo.uploaded), ("2", true));
+        assert!(
+            manager
+                .bind("app.zip", "application/zip", &[0x50, 0x4b, 0, 0])
+                .await
+                .is_err()
+        );
+        assert!(
+            manager
+                .bind("big.log", "text/plain", b"more than eight bytes")
+                .await
+                .is_err()
+        );
+
+        let content = manager.bound_content().await.unwrap();
+        assert_eq!(
+            content.parts[0].text.as_deref(),
+            Some("The contents of notes.md:\n# Notes")
+        );
+        assert!(content.parts[2].file_data.is_some());
+
+        assert_eq!(manager.unbind("demo.mp4").await.unwrap().id, "2");
+        assert!(manager.unbind("2").await.is_err());
+        assert_eq!(manager.bound().await, [notes]);
+        assert_eq!(
+            requests.lock().await.last().map(String::as_str),
+            Some("delete /v1beta/files/abc http/1.1")
+        );
+    }
+
+    // The server has to keep answering while the drop blocks a worker.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dropped_manager_deletes_uploads() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let manager = AttachmentManager::new("key")
+            .with_base_url(&serve(requests.clone()).await)
+            .with_inline_limit(8)
+            .with_poll_interval(Duration::from_millis(1));
+        manager.part("video/mp4", &[0; 16]).await.unwrap();
+        drop(manager);
+        assert_eq!(
+            requests.lock().await.last().map(String::as_str),
+            Some("delete /v1beta/files/abc http/1.1")
+        );
+
+        let manager = AttachmentManager::new("key")
+            .without_uploads()
+            .with_inline_limit(8);
+        assert_eq!(manager.upload_url(), None);
+        assert!(manager.part("image/png", b"small").await.is_ok());
+        assert!(manager.part("video/mp4", &[0; 16]).await.is_err());
+    }
+}
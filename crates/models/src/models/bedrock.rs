@@ -0,0 +1,460 @@
+use std::{collections::VecDeque, sync::Arc, time::SystemTime};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{
+        AgentClient, AgentError, AgentRole, Content, FunctionCall, Part, SystemPrompt,
+        approximate_tokens,
+    },
+    tools::{Tool, ToolDecorators, ToolDefinition, ToolFilter},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::prompt::{SYSTEM_PROMPT, SYSTEM_PROMPT_VERSION};
+
+use super::sigv4::{self, AwsCredentials, SigningRequest};
+
+#[derive(Debug, Clone)]
+pub struct BedrockConfig {
+    pub region: String,
+    pub model_id: String,
+    pub credentials: AwsCredentials,
+}
+
+#[derive(Debug, Default)]
+struct Conversation {
+    messages: Vec<Message>,
+    // Tool uses from the last assistant message that still need a result.
+    pending_tool_uses: VecDeque<String>,
+}
+
+#[derive(Debug)]
+pub struct BedrockModel {
+    config: BedrockConfig,
+    reqwest: Arc<reqwest::Client>,
+    conversation: Arc<Mutex<Conversation>>,
+    tools: Arc<Mutex<Vec<ToolDefinition>>>,
+    tool_filter: Arc<Mutex<ToolFilter>>,
+    tool_decorators: Arc<Mutex<ToolDecorators>>,
+    system_prompt: Arc<Mutex<SystemPrompt>>,
+}
+
+impl BedrockModel {
+    pub fn new(config: BedrockConfig) -> Self {
+        Self {
+            config,
+            reqwest: Arc::new(reqwest::Client::new()),
+            conversation: Arc::new(Mutex::new(Conversation::default())),
+            tools: Arc::new(Mutex::new(vec![])),
+            tool_filter: Arc::new(Mutex::new(ToolFilter::all())),
+            tool_decorators: Arc::new(Mutex::new(ToolDecorators::default())),
+            system_prompt: Arc::new(Mutex::new(SystemPrompt {
+                version: SYSTEM_PROMPT_VERSION,
+                text: SYSTEM_PROMPT.to_string(),
+            })),
+        }
+    }
+
+    fn host(&self) -> String {
+        format!("bedrock-runtime.{}.amazonaws.com", self.config.region)
+    }
+
+    async fn advertised_tools(&self) -> Vec<ToolSpec> {
+        let tools = self
+            .tool_filter
+            .lock()
+            .await
+            .apply(&self.tools.lock().await);
+        self.tool_decorators
+            .lock()
+            .await
+            .apply(tools)
+            .into_iter()
+            .map(|definition| ToolSpec {
+                input_schema: InputSchema {
+                    json: serde_json::to_value(&definition.parameters).unwrap_or_default(),
+                },
+                name: definition.name,
+                description: definition.description,
+            })
+            .collect()
+    }
+
+    async fn converse(&self, request: &ConverseRequest) -> Result<ConverseResponse, AgentError> {
+        let host = self.host();
+        let model = sigv4::uri_encode(&self.config.model_id);
+        let body =
+            serde_json::to_vec(request).map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+
+        // SigV4 encodes path segments a second time for every service but S3.
+        let canonical_uri = format!("/model/{}/converse", sigv4::uri_encode(&model));
+        let signed = sigv4::sign(
+            &self.config.credentials,
+            &self.config.region,
+            "bedrock",
+            &SigningRequest {
+                method: "POST",
+                canonical_uri: &canonical_uri,
+                canonical_query: "",
+                headers: &[("content-type", "application/json"), ("host", &host)],
+                payload: &body,
+            },
+            SystemTime::now(),
+        );
+
+        let mut request = self
+            .reqwest
+            .post(format!("https://{}/model/{}/converse", host, model))
+            .header("content-type", "application/json")
+            .body(body);
+        for (name, value) in signed {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+
+        if !status.is_success() {
+            let message = serde_json::from_str::<BedrockError>(&text)
+                .map(|error| error.message)
+                .unwrap_or(text);
+
+            return Err(match status.as_u16() {
+                429 => AgentError::RateLimited {
+                    retry_after: None,
+                    message,
+                },
+                403 if message.contains("expired") => AgentError::InvalidApiKey(Some(message)),
+                500.. => AgentError::Network(format!("{}: {}", status, message)),
+                _ => AgentError::InvalidRequest(Some(format!("{}: {}", status, message))),
+            });
+        }
+
+        serde_json::from_str::<ConverseResponse>(&text).map_err(|e| AgentError::Deserialization {
+            body: text.clone(),
+            message: e.to_string(),
+        })
+    }
+}
+
+impl Conversation {
+    fn push(&mut self, role: &str, block: ContentBlock) {
+        match self.messages.last_mut() {
+            Some(message) if message.role == role => message.content.push(block),
+            _ => self.messages.push(Message {
+                role: role.to_string(),
+                content: vec![block],
+            }),
+        }
+    }
+
+    // Uses still waiting for a result are closed first, they have to be
+    // answered by the next user message.
+    fn push_user_text(&mut self, text: &str) {
+        self.close_pending_tool_uses();
+        if !text.trim().is_empty() {
+            self.push("user", ContentBlock::Text(text.to_string()))
+        }
+    }
+
+    // Tool results reach the client as text, in call order. Bedrock needs them
+    // as toolResult blocks answering the assistant's toolUse ids.
+    fn push_tool_text(&mut self, text: &str) {
+        match self.pending_tool_uses.pop_front() {
+            Some(tool_use_id) => self.push(
+                "user",
+                ContentBlock::ToolResult(ToolResult {
+                    tool_use_id,
+                    content: vec![ToolResultContent {
+                        text: text.to_string(),
+                    }],
+                    status: None,
+                }),
+            ),
+            None => self.push_user_text(text),
+        }
+    }
+
+    fn close_pending_tool_uses(&mut self) {
+        while let Some(tool_use_id) = self.pending_tool_uses.pop_front() {
+            self.push(
+                "user",
+                ContentBlock::ToolResult(ToolResult {
+                    tool_use_id,
+                    content: vec![ToolResultContent {
+                        text: "The tool was not executed".to_string(),
+                    }],
+                    status: Some("error".to_string()),
+                }),
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl AgentClient for BedrockModel {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        let messages = {
+            let mut conversation = self.conversation.lock().await;
+            conversation.push_user_text(prompt);
+            conversation.messages.clone()
+        };
+
+        let tools = self.advertised_tools().await;
+        let request = ConverseRequest {
+            messages,
+            system: vec![SystemBlock {
+                text: self.system_prompt.lock().await.text.clone(),
+            }],
+            tool_config: (!tools.is_empty()).then(|| ToolConfig {
+                tools: tools
+                    .into_iter()
+                    .map(|spec| ToolEntry { tool_spec: spec })
+                    .collect(),
+            }),
+        };
+
+        let response = self.converse(&request).await?;
+        let blocks = response
+            .output
+            .message
+            .content
+            .into_iter()
+            .filter_map(|block| serde_json::from_value::<ContentBlock>(block).ok())
+            .collect::<Vec<ContentBlock>>();
+
+        let mut parts = vec![];
+        {
+            let mut conversation = self.conversation.lock().await;
+            for block in blocks {
+                match &block {
+                    ContentBlock::Text(text) => parts.push(Part::new(text)),
+                    ContentBlock::ToolUse(tool_use) => {
+                        conversation
+                            .pending_tool_uses
+                            .push_back(tool_use.tool_use_id.clone());
+                        parts.push(Part {
+                            text: None,
+                            function_call: Some(FunctionCall {
+                                name: tool_use.name.clone(),
+                                args: tool_use.input.clone(),
+                            }),
+                            ..Default::default()
+                        });
+                    }
+                    ContentBlock::ToolResult(_) => continue,
+                }
+                conversation.push("assistant", block);
+            }
+        }
+
+        if parts.is_empty() {
+            return Err(AgentError::AgentError(Some(
+                "No response from Bedrock".to_string(),
+            )));
+        }
+
+        Ok(vec![Content::new(parts, "model")])
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.tools.lock().await.push(tool.tool_definition().clone());
+
+        Ok(())
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        *self.tool_filter.lock().await = filter;
+        Ok(())
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        *self.tool_decorators.lock().await = decorators;
+        Ok(())
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        let mut conversation = self.conversation.lock().await;
+        match role {
+            // Converse only takes a system prompt up front, later
+            // instructions go out as user text.
+            AgentRole::User | AgentRole::System => conversation.push_user_text(prompt),
+            AgentRole::Tool => conversation.push_tool_text(prompt),
+            AgentRole::Model if !prompt.trim().is_empty() => {
+                conversation.push("assistant", ContentBlock::Text(prompt.to_string()))
+            }
+            AgentRole::Model => {}
+        }
+
+        Ok(())
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        Some(self.system_prompt.lock().await.clone())
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        *self.system_prompt.lock().await = prompt;
+        Ok(())
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        let messages = serde_json::to_string(&self.conversation.lock().await.messages).ok()?;
+        let tools = serde_json::to_string(&self.advertised_tools().await).ok()?;
+        let system_prompt = self.system_prompt.lock().await.text.clone();
+
+        Some(
+            approximate_tokens(&messages)
+                + approximate_tokens(&tools)
+                + approximate_tokens(&system_prompt)
+                + approximate_tokens(prompt),
+        )
+    }
+
+    fn model(&self) -> Option<String> {
+        Some(self.config.model_id.clone())
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        Some(format!("https://{}", self.host()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConverseRequest {
+    messages: Vec<Message>,
+    system: Vec<SystemBlock>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<ToolConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ContentBlock {
+    Text(String),
+    ToolUse(ToolUse),
+    ToolResult(ToolResult),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolUse {
+    tool_use_id: String,
+    name: String,
+    input: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolResult {
+    tool_use_id: String,
+    content: Vec<ToolResultContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ToolResultContent {
+    text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SystemBlock {
+    text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ToolConfig {
+    tools: Vec<ToolEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolEntry {
+    tool_spec: ToolSpec,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolSpec {
+    name: String,
+    description: String,
+    input_schema: InputSchema,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct InputSchema {
+    json: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConverseResponse {
+    output: Output,
+}
+
+#[derive(Debug, Deserialize)]
+struct Output {
+    message: ResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    content: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BedrockError {
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_outputs_become_tool_results() {
+        let mut conversation = Conversation::default();
+        conversation.push_user_text("list src");
+        conversation.push(
+            "assistant",
+            ContentBlock::ToolUse(ToolUse {
+                tool_use_id: "t1".to_string(),
+                name: "list_files".to_string(),
+                input: serde_json::json!({ "path": "src" }),
+            }),
+        );
+        conversation.pending_tool_uses.push_back("t1".to_string());
+
+        conversation.push_tool_text("[\"main.rs\"]");
+        conversation.push_user_text("");
+
+        let messages = serde_json::to_value(&conversation.messages).unwrap();
+        assert_eq!(messages.as_array().unwrap().len(), 3);
+        assert_eq!(
+            messages[2],
+            serde_json::json!({
+                "role": "user",
+                "content": [{ "toolResult": {
+                    "toolUseId": "t1",
+                    "content": [{ "text": "[\"main.rs\"]" }]
+                }}]
+            })
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::{
+    generation::LogitsProcessor,
+    models::qwen2::{Config, ModelForCausalLM},
+};
+use domain::models::agent::AgentError;
+use tokenizers::Tokenizer;
+
+use super::local::{Generator, LocalMessage, chatml};
+
+#[derive(Debug, Clone)]
+pub struct CandleConfig {
+    // A directory with `config.json`, `tokenizer.json` and the
+    // `*.safetensors` weights of a Qwen2 architecture model.
+    pub model_dir: PathBuf,
+    pub context_size: usize,
+    pub max_tokens: usize,
+    pub temperature: f64,
+}
+
+impl CandleConfig {
+    pub fn new(model_dir: PathBuf) -> Self {
+        Self {
+            model_dir,
+            context_size: 8192,
+            max_tokens: 1024,
+            temperature: 0.2,
+        }
+    }
+}
+
+pub struct CandleGenerator {
+    config: CandleConfig,
+    model: Mutex<ModelForCausalLM>,
+    tokenizer: Tokenizer,
+    stop_tokens: Vec<u32>,
+    device: Device,
+}
+
+impl std::fmt::Debug for CandleGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CandleGenerator")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl CandleGenerator {
+    pub fn load(config: CandleConfig) -> Result<Self, AgentError> {
+        let device = Device::Cpu;
+
+        let model_config = std::fs::read_to_string(config.model_dir.join("config.json"))
+            .map_err(|e| candle_error("reading config.json", e))?;
+        let model_config: Config = serde_json::from_str(&model_config)
+            .map_err(|e| candle_error("parsing config.json", e))?;
+
+        let tokenizer = Tokenizer::from_file(config.model_dir.join("tokenizer.json"))
+            .map_err(|e| candle_error("loading tokenizer.json", e))?;
+        let stop_tokens = ["<|im_end|>", "<|endoftext|>"]
+            .iter()
+            .filter_map(|token| tokenizer.token_to_id(token))
+            .collect();
+
+        let mut weights = std::fs::read_dir(&config.model_dir)
+            .map_err(|e| candle_error("reading model directory", e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "safetensors"))
+            .collect::<Vec<PathBuf>>();
+        weights.sort();
+        if weights.is_empty() {
+            return Err(candle_error(
+                "loading weights",
+                format!("no .safetensors files in {}", config.model_dir.display()),
+            ));
+        }
+
+        // Safety: the weights are memory mapped and must not be modified
+        // while voo is running.
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&weights, DType::F32, &device) }
+            .map_err(|e| candle_error("loading weights", e))?;
+        let model = ModelForCausalLM::new(&model_config, vb)
+            .map_err(|e| candle_error("building model", e))?;
+
+        Ok(Self {
+            config,
+            model: Mutex::new(model),
+            tokenizer,
+            stop_tokens,
+            device,
+        })
+    }
+
+    fn next_logits(
+        &self,
+        model: &mut ModelForCausalLM,
+        tokens: &[u32],
+        offset: usize,
+    ) -> candle_core::Result<Tensor> {
+        let input = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+        model
+            .forward(&input, offset)?
+            .squeeze(0)?
+            .squeeze(0)?
+            .to_dtype(DType::F32)
+    }
+}
+
+impl Generator for CandleGenerator {
+    fn generate(&self, messages: &[LocalMessage]) -> Result<String, AgentError> {
+        let prompt = chatml(messages);
+        let tokens = self
+            .tokenizer
+            .encode(prompt, false)
+            .map_err(|e| candle_error("tokenizing prompt", e))?
+            .get_ids()
+            .to_vec();
+        if tokens.len() + self.config.max_tokens > self.config.context_size {
+            return Err(AgentError::AgentError(Some(format!(
+                "Prompt of {} tokens does not fit the {} token context",
+                tokens.len(),
+                self.config.context_size
+            ))));
+        }
+
+        let mut model = self
+            .model
+            .lock()
+            .map_err(|_| candle_error("locking model", "a previous generation panicked"))?;
+        model.clear_kv_cache();
+
+        let mut sampler = LogitsProcessor::new(rand_seed(), Some(self.config.temperature), None);
+        let mut logits = self
+            .next_logits(&mut model, &tokens, 0)
+            .map_err(|e| candle_error("evaluating prompt", e))?;
+
+        let mut output = vec![];
+        for position in tokens.len()..tokens.len() + self.config.max_tokens {
+            let token = sampler
+                .sample(&logits)
+                .map_err(|e| candle_error("sampling", e))?;
+            if self.stop_tokens.contains(&token) {
+                break;
+            }
+
+            output.push(token);
+            logits = self
+                .next_logits(&mut model, &[token], position)
+                .map_err(|e| candle_error("generating", e))?;
+        }
+
+        self.tokenizer
+            .decode(&output, true)
+            .map_err(|e| candle_error("decoding response", e))
+    }
+}
+
+fn rand_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn candle_error(action: &str, e: impl std::fmt::Display) -> AgentError {
+    AgentError::AgentError(Some(format!("candle: error {}: {}", action, e)))
+}
@@ -0,0 +1,763 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{
+        AgentClient, AgentError, AgentRole, Content, FunctionCall, Part, SystemPrompt,
+        approximate_tokens,
+    },
+    tools::{Tool, ToolDecorators, ToolDefinition, ToolFilter},
+    usage::{TokenUsage, UsageTracker},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::prompt::{SYSTEM_PROMPT, SYSTEM_PROMPT_VERSION};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatAuth {
+    Bearer(String),
+    Header(String, String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChatQuirks {
+    // Response headers telling when a rate limit resets, checked after
+    // `retry-after`.
+    pub rate_limit_reset_headers: Vec<String>,
+    // Tool messages must carry the name of the function they answer.
+    pub tool_result_name: bool,
+    // A user message may not directly follow tool results.
+    pub assistant_between_tool_and_user: bool,
+    // Retry once when the provider rejects a malformed tool call it generated.
+    pub retry_failed_tool_generation: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatCompletionsConfig {
+    pub provider: String,
+    pub url: String,
+    pub auth: ChatAuth,
+    // Sent as the request's `model`; None for deployment based endpoints.
+    pub model: Option<String>,
+    pub quirks: ChatQuirks,
+}
+
+impl ChatCompletionsConfig {
+    pub fn azure(endpoint: &str, deployment: &str, api_version: &str, auth: ChatAuth) -> Self {
+        Self {
+            provider: "azure".to_string(),
+            url: format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={}",
+                endpoint.trim_end_matches('/'),
+                deployment,
+                api_version
+            ),
+            auth,
+            model: None,
+            quirks: ChatQuirks::default(),
+        }
+    }
+
+    pub fn mistral(api_key: &str, model: &str) -> Self {
+        Self {
+            provider: "mistral".to_string(),
+            url: "https://api.mistral.ai/v1/chat/completions".to_string(),
+            auth: ChatAuth::Bearer(api_key.to_string()),
+            model: Some(model.to_string()),
+            quirks: ChatQuirks {
+                rate_limit_reset_headers: vec!["ratelimitbysize-reset".to_string()],
+                tool_result_name: true,
+                assistant_between_tool_and_user: true,
+                retry_failed_tool_generation: false,
+            },
+        }
+    }
+
+    pub fn xai(api_key: &str, model: &str) -> Self {
+        Self {
+            provider: "xai".to_string(),
+            url: "https://api.x.ai/v1/chat/completions".to_string(),
+            auth: ChatAuth::Bearer(api_key.to_string()),
+            model: Some(model.to_string()),
+            quirks: ChatQuirks::default(),
+        }
+    }
+
+    pub fn deepseek(api_key: &str, model: &str) -> Self {
+        Self {
+            provider: "deepseek".to_string(),
+            url: "https://api.deepseek.com/chat/completions".to_string(),
+            auth: ChatAuth::Bearer(api_key.to_string()),
+            model: Some(model.to_string()),
+            quirks: ChatQuirks::default(),
+        }
+    }
+
+    pub fn groq(api_key: &str, model: &str) -> Self {
+        Self {
+            provider: "groq".to_string(),
+            url: "https://api.groq.com/openai/v1/chat/completions".to_string(),
+            auth: ChatAuth::Bearer(api_key.to_string()),
+            model: Some(model.to_string()),
+            quirks: ChatQuirks {
+                rate_limit_reset_headers: vec![
+                    "x-ratelimit-reset-requests".to_string(),
+                    "x-ratelimit-reset-tokens".to_string(),
+                ],
+                tool_result_name: false,
+                assistant_between_tool_and_user: false,
+                retry_failed_tool_generation: true,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Conversation {
+    messages: Vec<Message>,
+    // Tool calls from the last assistant message that still need a result.
+    pending_tool_calls: VecDeque<ToolCall>,
+    quirks: ChatQuirks,
+}
+
+#[derive(Debug)]
+pub struct ChatCompletionsModel {
+    config: ChatCompletionsConfig,
+    reqwest: Arc<reqwest::Client>,
+    conversation: Arc<Mutex<Conversation>>,
+    tools: Arc<Mutex<Vec<ToolDefinition>>>,
+    tool_filter: Arc<Mutex<ToolFilter>>,
+    tool_decorators: Arc<Mutex<ToolDecorators>>,
+    system_prompt: Arc<Mutex<SystemPrompt>>,
+    usage: Arc<UsageTracker>,
+    // Set by the mode, None leaves the temperature to the provider.
+    temperature: Arc<Mutex<Option<f32>>>,
+}
+
+impl ChatCompletionsModel {
+    pub fn new(config: ChatCompletionsConfig) -> Self {
+        let conversation = Conversation {
+            quirks: config.quirks.clone(),
+            ..Default::default()
+        };
+
+        Self {
+            config,
+            reqwest: Arc::new(reqwest::Client::new()),
+            conversation: Arc::new(Mutex::new(conversation)),
+            tools: Arc::new(Mutex::new(vec![])),
+            tool_filter: Arc::new(Mutex::new(ToolFilter::all())),
+            tool_decorators: Arc::new(Mutex::new(ToolDecorators::default())),
+            usage: Arc::new(UsageTracker::default()),
+            temperature: Arc::new(Mutex::new(None)),
+            system_prompt: Arc::new(Mutex::new(SystemPrompt {
+                version: SYSTEM_PROMPT_VERSION,
+                text: SYSTEM_PROMPT.to_string(),
+            })),
+        }
+    }
+
+    async fn advertised_tools(&self) -> Vec<ChatTool> {
+        let tools = self
+            .tool_filter
+            .lock()
+            .await
+            .apply(&self.tools.lock().await);
+        self.tool_decorators
+            .lock()
+            .await
+            .apply(tools)
+            .into_iter()
+            .map(|definition| ChatTool {
+                type_field: "function".to_string(),
+                function: ChatFunction {
+                    parameters: serde_json::to_value(&definition.parameters).unwrap_or_default(),
+                    name: definition.name,
+                    description: definition.description,
+                },
+            })
+            .collect()
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse, AgentError> {
+        match self.send(request).await {
+            Err(ChatError::FailedToolGeneration(message))
+                if self.config.quirks.retry_failed_tool_generation =>
+            {
+                warn!(
+                    "[VOO] {} generated a malformed tool call, retrying: {}",
+                    self.config.provider, message
+                );
+                self.send(request).await.map_err(AgentError::from)
+            }
+            response => response.map_err(AgentError::from),
+        }
+    }
+
+    async fn send(&self, request: &ChatRequest) -> Result<ChatResponse, ChatError> {
+        let request = self.reqwest.post(&self.config.url).json(request);
+        let request = match &self.config.auth {
+            ChatAuth::Bearer(token) => request.bearer_auth(token),
+            ChatAuth::Header(name, value) => request.header(name, value),
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+        let status = response.status();
+        let retry_after = std::iter::once("retry-after")
+            .chain(
+                self.config
+                    .quirks
+                    .rate_limit_reset_headers
+                    .iter()
+                    .map(String::as_str),
+            )
+            .find_map(|name| response.headers().get(name)?.to_str().ok())
+            .map(str::to_string);
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::Network(e.to_string()))?;
+
+        if !status.is_success() {
+            let error = serde_json::from_str::<ErrorResponse>(&text)
+                .map(|error| error.error)
+                .unwrap_or(ErrorBody {
+                    message: text,
+                    code: None,
+                });
+
+            return Err(match (status.as_u16(), error.code.as_deref()) {
+                (429, _) => AgentError::RateLimited {
+                    retry_after: retry_after.as_deref().and_then(parse_retry_after),
+                    message: match retry_after {
+                        Some(retry_after) => {
+                            format!("{} (retry after {})", error.message, retry_after)
+                        }
+                        None => error.message,
+                    },
+                }
+                .into(),
+                (400, Some("tool_use_failed")) => ChatError::FailedToolGeneration(error.message),
+                (_, Some("context_length_exceeded")) => {
+                    AgentError::ContextTooLong(Some(error.message)).into()
+                }
+                (401, _) => AgentError::InvalidApiKey(Some(error.message)).into(),
+                (500.., _) => AgentError::Network(format!(
+                    "{} {}: {}",
+                    self.config.provider, status, error.message
+                ))
+                .into(),
+                _ => AgentError::InvalidRequest(Some(format!(
+                    "{} {}: {}",
+                    self.config.provider, status, error.message
+                )))
+                .into(),
+            });
+        }
+
+        serde_json::from_str::<ChatResponse>(&text).map_err(|e| {
+            AgentError::Deserialization {
+                body: text.clone(),
+                message: e.to_string(),
+            }
+            .into()
+        })
+    }
+}
+
+// Retry-After is a number of seconds. The reset headers some providers send
+// instead look like "1.5s" or "1m30s".
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut units = value.trim().chars().peekable();
+    while let Some(c) = units.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let value = std::mem::take(&mut number).parse::<f64>().ok()?;
+        total += match c {
+            'h' => value * 3600.0,
+            'm' if units.peek() == Some(&'s') => {
+                units.next();
+                value / 1000.0
+            }
+            'm' => value * 60.0,
+            's' => value,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() {
+        return None;
+    }
+
+    Duration::try_from_secs_f64(total).ok()
+}
+
+#[derive(Debug)]
+enum ChatError {
+    Agent(AgentError),
+    FailedToolGeneration(String),
+}
+
+impl From<AgentError> for ChatError {
+    fn from(error: AgentError) -> Self {
+        ChatError::Agent(error)
+    }
+}
+
+impl From<ChatError> for AgentError {
+    fn from(error: ChatError) -> Self {
+        match error {
+            ChatError::Agent(error) => error,
+            ChatError::FailedToolGeneration(message) => AgentError::AgentError(Some(message)),
+        }
+    }
+}
+
+impl Conversation {
+    // Tool results reach the client as plain user text, in call order. Chat
+    // completions APIs need them as `tool` messages answering each call id.
+    // Calls still waiting for a result are closed first, a user or system
+    // message may not come between a call and its result.
+    fn push_text(&mut self, role: &str, text: &str) {
+        self.close_pending_tool_calls();
+        if text.trim().is_empty() {
+            return;
+        }
+
+        let after_tool = self
+            .messages
+            .last()
+            .is_some_and(|message| message.role == "tool");
+        if after_tool && self.quirks.assistant_between_tool_and_user {
+            self.messages.push(Message::text("assistant", "Done."));
+        }
+        self.messages.push(Message::text(role, text));
+    }
+
+    fn push_user_text(&mut self, text: &str) {
+        self.push_text("user", text);
+    }
+
+    // Answers the oldest pending call, without one the text goes out as user
+    // text.
+    fn push_tool_text(&mut self, text: &str) {
+        match self.pending_tool_calls.pop_front() {
+            Some(tool_call) => self.push_tool_result(&tool_call, text),
+            None => self.push_user_text(text),
+        }
+    }
+
+    fn push_tool_result(&mut self, tool_call: &ToolCall, output: &str) {
+        let mut message = Message::tool(&tool_call.id, output);
+        if self.quirks.tool_result_name {
+            message.name = Some(tool_call.function.name.clone());
+        }
+        self.messages.push(message);
+    }
+
+    fn close_pending_tool_calls(&mut self) {
+        while let Some(tool_call) = self.pending_tool_calls.pop_front() {
+            self.push_tool_result(&tool_call, "The tool was not executed");
+        }
+    }
+}
+
+#[async_trait]
+impl AgentClient for ChatCompletionsModel {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        let mut messages = vec![Message::text(
+            "system",
+            &self.system_prompt.lock().await.text,
+        )];
+        {
+            let mut conversation = self.conversation.lock().await;
+            conversation.push_user_text(prompt);
+            messages.extend(conversation.messages.iter().cloned());
+        }
+
+        let tools = self.advertised_tools().await;
+        let request = ChatRequest {
+            model: self.config.model.clone(),
+            messages,
+            tools: (!tools.is_empty()).then_some(tools),
+            temperature: *self.temperature.lock().await,
+        };
+
+        let response = self.complete(&request).await?;
+        if let Some(usage) = &response.usage {
+            self.usage.record(
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.total_tokens,
+            );
+        }
+        let Some(message) = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+        else {
+            return Err(AgentError::AgentError(Some(format!(
+                "No response from {}",
+                self.config.provider
+            ))));
+        };
+
+        let mut parts = vec![];
+        if let Some(reasoning) = message
+            .reasoning_content
+            .as_deref()
+            .filter(|reasoning| !reasoning.is_empty())
+        {
+            parts.push(Part::thought(reasoning));
+        }
+        if let Some(text) = message.content.as_deref().filter(|text| !text.is_empty()) {
+            parts.push(Part::new(text));
+        }
+        for tool_call in message.tool_calls.iter().flatten() {
+            parts.push(Part {
+                text: None,
+                function_call: Some(FunctionCall {
+                    name: tool_call.function.name.clone(),
+                    args: tool_call.function.parsed_arguments(),
+                }),
+                ..Default::default()
+            });
+        }
+
+        if parts.is_empty() {
+            return Err(AgentError::AgentError(Some(format!(
+                "No response from {}",
+                self.config.provider
+            ))));
+        }
+
+        {
+            let mut conversation = self.conversation.lock().await;
+            conversation
+                .pending_tool_calls
+                .extend(message.tool_calls.iter().flatten().cloned());
+            conversation.messages.push(Message {
+                role: "assistant".to_string(),
+                content: message.content,
+                tool_calls: message.tool_calls,
+                ..Default::default()
+            });
+        }
+
+        Ok(vec![Content::new(parts, "model")])
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.tools.lock().await.push(tool.tool_definition().clone());
+
+        Ok(())
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        let mut conversation = self.conversation.lock().await;
+        match role {
+            AgentRole::User => conversation.push_user_text(prompt),
+            AgentRole::System => conversation.push_text("system", prompt),
+            AgentRole::Tool => conversation.push_tool_text(prompt),
+            AgentRole::Model if !prompt.trim().is_empty() => conversation
+                .messages
+                .push(Message::text("assistant", prompt)),
+            AgentRole::Model => {}
+        }
+
+        Ok(())
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        Some(self.system_prompt.lock().await.clone())
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        *self.system_prompt.lock().await = prompt;
+        Ok(())
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        *self.tool_filter.lock().await = filter;
+        Ok(())
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        *self.tool_decorators.lock().await = decorators;
+        Ok(())
+    }
+
+    async fn set_temperature(&self, temperature: Option<f32>) -> Result<(), AgentError> {
+        *self.temperature.lock().await = temperature;
+        Ok(())
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        let messages = serde_json::to_string(&self.conversation.lock().await.messages).ok()?;
+        let tools = serde_json::to_string(&self.advertised_tools().await).ok()?;
+        let system_prompt = self.system_prompt.lock().await.text.clone();
+
+        Some(
+            approximate_tokens(&messages)
+                + approximate_tokens(&tools)
+                + approximate_tokens(&system_prompt)
+                + approximate_tokens(prompt),
+        )
+    }
+
+    fn model(&self) -> Option<String> {
+        self.config.model.clone()
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        Some(self.config.url.clone())
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        Some(self.usage.usage())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<ChatTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    // Returned by reasoning models; DeepSeek rejects requests that send it back.
+    #[serde(default, skip_serializing)]
+    reasoning_content: Option<String>,
+}
+
+impl Message {
+    fn text(role: &str, text: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn tool(tool_call_id: &str, output: &str) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: Some(output.to_string()),
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ToolCall {
+    id: String,
+    #[serde(rename = "type", default = "function_type")]
+    type_field: String,
+    function: ToolCallFunction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ToolCallFunction {
+    name: String,
+    arguments: Value,
+}
+
+impl ToolCallFunction {
+    // Arguments are a JSON encoded string per the spec, but some providers
+    // return a plain object.
+    fn parsed_arguments(&self) -> Value {
+        match &self.arguments {
+            Value::String(arguments) => {
+                serde_json::from_str(arguments).unwrap_or(Value::Object(Default::default()))
+            }
+            arguments => arguments.clone(),
+        }
+    }
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatTool {
+    #[serde(rename = "type")]
+    type_field: String,
+    function: ChatFunction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatFunction {
+    name: String,
+    description: String,
+    parameters: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ChatUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    total_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    message: String,
+    #[serde(default)]
+    code: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_outputs_become_tool_messages() {
+        let response = serde_json::json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [
+                { "id": "a", "type": "function", "function": { "name": "read_file", "arguments": "{\"path\":\"a.rs\"}" } },
+                { "id": "b", "function": { "name": "read_file", "arguments": { "path": "b.rs" } } }
+            ]
+        });
+        let message = serde_json::from_value::<Message>(response).unwrap();
+        let calls = message.tool_calls.clone().unwrap();
+        assert_eq!(calls[0].function.parsed_arguments()["path"], "a.rs");
+        assert_eq!(calls[1].function.parsed_arguments()["path"], "b.rs");
+
+        let mut conversation = Conversation::default();
+        conversation
+            .pending_tool_calls
+            .extend(calls.iter().cloned());
+        conversation.messages.push(message);
+
+        conversation.push_tool_text("contents of a");
+        conversation.push_user_text("next");
+
+        assert_eq!(
+            conversation.messages[1],
+            Message::tool("a", "contents of a")
+        );
+        assert_eq!(
+            conversation.messages[2],
+            Message::tool("b", "The tool was not executed")
+        );
+        assert_eq!(conversation.messages[3], Message::text("user", "next"));
+    }
+
+    #[test]
+    fn test_mistral_tool_result_quirks() {
+        let mut conversation = Conversation {
+            quirks: ChatCompletionsConfig::mistral("key", "mistral-large-latest").quirks,
+            ..Default::default()
+        };
+        conversation.pending_tool_calls.push_back(ToolCall {
+            id: "abc123xyz".to_string(),
+            type_field: function_type(),
+            function: ToolCallFunction {
+                name: "list_files".to_string(),
+                arguments: Value::String("{}".to_string()),
+            },
+        });
+
+        conversation.push_tool_text("[]");
+        conversation.push_user_text("thanks");
+
+        let roles = conversation
+            .messages
+            .iter()
+            .map(|message| message.role.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(roles, ["tool", "assistant", "user"]);
+        assert_eq!(conversation.messages[0].name.as_deref(), Some("list_files"));
+    }
+
+    #[test]
+    fn test_reasoning_content_is_not_sent_back() {
+        let message = serde_json::from_value::<Message>(serde_json::json!({
+            "role": "assistant",
+            "content": "42",
+            "reasoning_content": "6 times 7"
+        }))
+        .unwrap();
+        assert_eq!(message.reasoning_content.as_deref(), Some("6 times 7"));
+
+        let sent = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            sent,
+            serde_json::json!({ "role": "assistant", "content": "42" })
+        );
+    }
+
+    #[test]
+    fn test_azure_url() {
+        let config = ChatCompletionsConfig::azure(
+            "https://contoso.openai.azure.com/",
+            "gpt-4o",
+            "2024-10-21",
+            ChatAuth::Header("api-key".to_string(), "key".to_string()),
+        );
+        assert_eq!(
+            config.url,
+            "https://contoso.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(config.model, None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_retry_after("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_retry_after("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
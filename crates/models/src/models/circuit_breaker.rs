@@ -0,0 +1,428 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{AgentClient, AgentError, AgentRole, Content, PartStream, SystemPrompt},
+    attachment::Attachment,
+    health::{CircuitState, ProviderHealth, RateLimitStatus},
+    tools::{Tool, ToolDecorators, ToolFilter, ToolOutput},
+    usage::TokenUsage,
+};
+use tracing::warn;
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+    last_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    provider: String,
+    inner: Arc<dyn AgentClient>,
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(provider: &str, inner: impl AgentClient + 'static) -> Self {
+        Self::with_config(provider, inner, CircuitBreakerConfig::default())
+    }
+
+    pub fn with_config(
+        provider: &str,
+        inner: impl AgentClient + 'static,
+        config: CircuitBreakerConfig,
+    ) -> Self {
+        Self {
+            provider: provider.to_string(),
+            inner: Arc::new(inner),
+            config,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+                last_error: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn acquire(&self) -> Result<Probe<'_>, AgentError> {
+        let mut breaker = self.lock();
+
+        match breaker.state {
+            CircuitState::Closed => Ok(Probe(None)),
+            CircuitState::Open => {
+                let elapsed = breaker
+                    .opened_at
+                    .map(|opened_at| opened_at.elapsed())
+                    .unwrap_or_default();
+
+                if elapsed < self.config.cooldown {
+                    let retry_in = self.config.cooldown - elapsed;
+                    return Err(AgentError::CircuitOpen {
+                        retry_after: Some(retry_in),
+                        message: format!(
+                            "{} is unavailable (circuit open), retry in {}s",
+                            self.provider,
+                            retry_in.as_secs().max(1)
+                        ),
+                    });
+                }
+
+                breaker.state = CircuitState::HalfOpen;
+                breaker.probe_in_flight = true;
+                Ok(Probe(Some(&self.state)))
+            }
+            CircuitState::HalfOpen => {
+                if breaker.probe_in_flight {
+                    return Err(AgentError::CircuitOpen {
+                        retry_after: None,
+                        message: format!(
+                            "{} is recovering (circuit half-open), probe already in flight",
+                            self.provider
+                        ),
+                    });
+                }
+
+                breaker.probe_in_flight = true;
+                Ok(Probe(Some(&self.state)))
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.lock();
+        breaker.state = CircuitState::Closed;
+        breaker.consecutive_failures = 0;
+        breaker.opened_at = None;
+        breaker.probe_in_flight = false;
+        breaker.last_error = None;
+    }
+
+    // Only failures of the provider itself count, a request it rejected, e.g.
+    // one too long for the context, says nothing about its health.
+    fn record_failure(&self, error: &AgentError) {
+        if !error.is_retryable() {
+            return;
+        }
+        let mut breaker = self.lock();
+        breaker.consecutive_failures += 1;
+        breaker.probe_in_flight = false;
+        breaker.last_error = Some(error.to_string());
+
+        let should_open = breaker.state == CircuitState::HalfOpen
+            || breaker.consecutive_failures >= self.config.failure_threshold;
+
+        if should_open {
+            if breaker.state != CircuitState::Open {
+                warn!(
+                    "[VOO] circuit opened for {} after {} consecutive failures",
+                    self.provider, breaker.consecutive_failures
+                );
+            }
+
+            breaker.state = CircuitState::Open;
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+// Held by the request that probes a half-open circuit. When the request is
+// dropped before it succeeded or failed, e.g. by a timeout or an interrupt,
+// the next request may probe instead.
+struct Probe<'a>(Option<&'a Mutex<BreakerState>>);
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.0 {
+            state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .probe_in_flight = false;
+        }
+    }
+}
+
+#[async_trait]
+impl AgentClient for CircuitBreaker {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        let _probe = self.acquire()?;
+
+        match self.inner.ask(prompt).await {
+            Ok(contents) => {
+                self.record_success();
+                Ok(contents)
+            }
+            Err(e) => {
+                self.record_failure(&e);
+                Err(e)
+            }
+        }
+    }
+
+    // Only opening the stream counts towards the circuit, errors in the
+    // middle of a response are left to the caller.
+    async fn ask_stream(&self, prompt: &str) -> Result<PartStream, AgentError> {
+        let _probe = self.acquire()?;
+
+        match self.inner.ask_stream(prompt).await {
+            Ok(stream) => {
+                self.record_success();
+                Ok(stream)
+            }
+            Err(e) => {
+                self.record_failure(&e);
+                Err(e)
+            }
+        }
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.inner.add_tool(tool).await
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        self.inner.add_system_prompt(prompt, role).await
+    }
+
+    async fn add_tool_result(&self, name: &str, output: &ToolOutput) -> Result<(), AgentError> {
+        self.inner.add_tool_result(name, output).await
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        self.inner.system_prompt().await
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        self.inner.set_system_prompt(prompt).await
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        self.inner.set_tool_filter(filter).await
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        self.inner.set_tool_decorators(decorators).await
+    }
+
+    async fn set_temperature(&self, temperature: Option<f32>) -> Result<(), AgentError> {
+        self.inner.set_temperature(temperature).await
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        self.inner.estimate_tokens(prompt).await
+    }
+
+    async fn attach(&self, name: &str, mime: &str, data: &[u8]) -> Result<Attachment, AgentError> {
+        self.inner.attach(name, mime, data).await
+    }
+
+    async fn attachments(&self) -> Vec<Attachment> {
+        self.inner.attachments().await
+    }
+
+    async fn detach(&self, id: &str) -> Result<Attachment, AgentError> {
+        self.inner.detach(id).await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    fn model(&self) -> Option<String> {
+        self.inner.model()
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    async fn rate_limit(&self) -> Option<RateLimitStatus> {
+        self.inner.rate_limit().await
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        self.inner.usage()
+    }
+
+    async fn health(&self) -> Option<ProviderHealth> {
+        let breaker = self.lock();
+
+        Some(ProviderHealth {
+            provider: self.provider.clone(),
+            state: breaker.state,
+            consecutive_failures: breaker.consecutive_failures,
+            last_error: breaker.last_error.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct FlakyClient {
+        failing: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl AgentClient for FlakyClient {
+        async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+            if prompt == "hang" {
+                std::future::pending::<()>().await;
+            }
+            if prompt == "too long" {
+                return Err(AgentError::ContextTooLong(None));
+            }
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(AgentError::Network("503: boom".to_string()));
+            }
+
+            Ok(vec![Content::default()])
+        }
+
+        async fn add_tool(&self, _tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        async fn add_system_prompt(
+            &self,
+            _prompt: &str,
+            _role: AgentRole,
+        ) -> Result<(), AgentError> {
+            Ok(())
+        }
+    }
+
+    fn breaker(cooldown: Duration) -> (Arc<AtomicBool>, CircuitBreaker) {
+        let failing = Arc::new(AtomicBool::new(false));
+        let client = FlakyClient {
+            failing: failing.clone(),
+        };
+        let breaker = CircuitBreaker::with_config(
+            "flaky",
+            client,
+            CircuitBreakerConfig {
+                failure_threshold: 2,
+                cooldown,
+            },
+        );
+
+        (failing, breaker)
+    }
+
+    #[tokio::test]
+    async fn test_opens_after_consecutive_failures() {
+        let (failing, breaker) = breaker(Duration::from_secs(60));
+        failing.store(true, Ordering::SeqCst);
+
+        assert!(breaker.ask("hi").await.is_err());
+        assert_eq!(breaker.health().await.unwrap().state, CircuitState::Closed);
+        assert!(breaker.ask("hi").await.is_err());
+
+        let health = breaker.health().await.unwrap();
+        assert_eq!(health.state, CircuitState::Open);
+        assert_eq!(health.consecutive_failures, 2);
+
+        failing.store(false, Ordering::SeqCst);
+        assert!(matches!(
+            breaker.ask("hi").await,
+            Err(AgentError::CircuitOpen { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests_keep_closed() {
+        let (_, breaker) = breaker(Duration::from_secs(60));
+
+        for _ in 0..3 {
+            assert!(matches!(
+                breaker.ask("too long").await,
+                Err(AgentError::ContextTooLong(_))
+            ));
+        }
+
+        let health = breaker.health().await.unwrap();
+        assert_eq!(health.state, CircuitState::Closed);
+        assert_eq!(health.consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_probe_closes_on_success() {
+        let (failing, breaker) = breaker(Duration::ZERO);
+        failing.store(true, Ordering::SeqCst);
+
+        _ = breaker.ask("hi").await;
+        _ = breaker.ask("hi").await;
+        assert_eq!(breaker.health().await.unwrap().state, CircuitState::Open);
+
+        failing.store(false, Ordering::SeqCst);
+        assert!(breaker.ask("hi").await.is_ok());
+
+        let health = breaker.health().await.unwrap();
+        assert_eq!(health.state, CircuitState::Closed);
+        assert_eq!(health.consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_reopens() {
+        let (failing, breaker) = breaker(Duration::ZERO);
+        failing.store(true, Ordering::SeqCst);
+
+        _ = breaker.ask("hi").await;
+        _ = breaker.ask("hi").await;
+        _ = breaker.ask("hi").await;
+
+        assert_eq!(breaker.health().await.unwrap().state, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_probe_allows_another() {
+        let (failing, breaker) = breaker(Duration::ZERO);
+        failing.store(true, Ordering::SeqCst);
+
+        _ = breaker.ask("hi").await;
+        _ = breaker.ask("hi").await;
+        failing.store(false, Ordering::SeqCst);
+
+        let probe = tokio::time::timeout(Duration::from_millis(10), breaker.ask("hang")).await;
+        assert!(probe.is_err());
+        assert_eq!(
+            breaker.health().await.unwrap().state,
+            CircuitState::HalfOpen
+        );
+
+        assert!(breaker.ask("hi").await.is_ok());
+        assert_eq!(breaker.health().await.unwrap().state, CircuitState::Closed);
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use domain::models::agent::{Content, Part};
+
+pub const SUMMARY_PROMPT: &str = "The conversation is getting too long for your context window. Summarize it so far for yourself: the task, the relevant facts learned from tool results, the decisions made, what has been done and what remains. Reply with the summary only.";
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
+#[derive(Debug, Clone)]
+pub struct ContextConfig {
+    pub context_window: u64,
+    // The share of the context window at which older turns get summarized.
+    pub threshold: f64,
+    // Messages at the end of the history that are always kept as they are.
+    pub keep_recent: usize,
+}
+
+impl ContextConfig {
+    pub fn new(context_window: u64) -> Self {
+        Self {
+            context_window,
+            threshold: 0.8,
+            keep_recent: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ContextManager {
+    config: ContextConfig,
+    summarizing: AtomicBool,
+}
+
+impl ContextManager {
+    pub fn new(config: ContextConfig) -> Self {
+        Self {
+            config,
+            summarizing: AtomicBool::new(false),
+        }
+    }
+
+    // The number of leading contents to summarize once `tokens` nears the
+    // context window. Only one summary is written at a time, `finish` allows
+    // the next one.
+    pub fn start(&self, tokens: u64, contents: &[Content]) -> Option<usize> {
+        if (tokens as f64) < self.config.context_window as f64 * self.config.threshold {
+            return None;
+        }
+        let split = split_point(contents, self.config.keep_recent)?;
+
+        self.summarizing
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+        Some(split)
+    }
+
+    pub fn finish(&self) {
+        self.summarizing.store(false, Ordering::SeqCst);
+    }
+}
+
+// Where the recent messages start. The first content is the system prompt and
+// is never summarized, and a function response stays with its call.
+pub fn split_point(contents: &[Content], keep_recent: usize) -> Option<usize> {
+    let mut split = contents.len().checked_sub(keep_recent)?;
+    while contents.get(split).is_some_and(|content| {
+        content
+            .parts
+            .iter()
+            .any(|part| part.function_response.is_some())
+    }) {
+        split += 1;
+    }
+
+    // Summarizing a single earlier message saves nothing.
+    (split > 2).then_some(split)
+}
+
+// Replaces the summarized contents after the system prompt with the summary.
+// The history may have been replaced while the summary was written, e.g. by
+// /clear, then it is left alone. Returns whether the summary was applied.
+pub fn apply_summary(contents: &mut Vec<Content>, summarized: &[Content], summary: &str) -> bool {
+    if summarized.len() < 2 || !contents.starts_with(summarized) {
+        return false;
+    }
+
+    let summary = Content::new(
+        vec![Part::new(&format!(
+            "{}\n{}",
+            SUMMARY_PREFIX,
+            summary.trim()
+        ))],
+        "user",
+    );
+    contents.splice(1..summarized.len(), [summary]);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::agent::FunctionResponse;
+    use serde_json::json;
+
+    use super::*;
+
+    fn message(text: &str) -> Content {
+        Content::new(vec![Part::new(text)], "user")
+    }
+
+    #[test]
+    fn test_summarize_older_turns() {
+        let mut contents = (0..8)
+            .map(|i| message(&i.to_string()))
+            .collect::<Vec<Content>>();
+        contents[5].parts[0].function_response = Some(FunctionResponse {
+            name: "read_file".to_string(),
+            response: json!({}),
+        });
+
+        let manager = ContextManager::new(ContextConfig {
+            context_window: 1000,
+            threshold: 0.8,
+            keep_recent: 3,
+        });
+        assert_eq!(manager.start(700, &contents), None);
+        assert_eq!(manager.start(800, &contents), Some(6));
+        assert_eq!(manager.start(900, &contents), None);
+        manager.finish();
+
+        let summarized = contents[..6].to_vec();
+        contents.push(message("8"));
+        assert!(apply_summary(&mut contents, &summarized, "counted to five"));
+        let texts = contents
+            .iter()
+            .map(|content| content.parts[0].text.clone().unwrap_or_default())
+            .collect::<Vec<String>>();
+        assert_eq!(texts[0], "0");
+        assert_eq!(texts[1], format!("{}\ncounted to five", SUMMARY_PREFIX));
+        assert_eq!(&texts[2..], ["6", "7", "8"]);
+
+        assert!(!apply_summary(&mut contents, &summarized, "again"));
+        assert_eq!(split_point(&contents[..3], 3), None);
+    }
+}
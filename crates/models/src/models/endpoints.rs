@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use domain::models::agent::AgentError;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+static STUDIO_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models/";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EndpointAuth {
+    ApiKey,
+    Bearer(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeminiEndpoint {
+    pub name: String,
+    pub base_url: String,
+    pub auth: EndpointAuth,
+}
+
+impl GeminiEndpoint {
+    pub fn studio() -> Self {
+        Self {
+            name: "studio".to_string(),
+            base_url: STUDIO_URL.to_string(),
+            auth: EndpointAuth::ApiKey,
+        }
+    }
+
+    pub fn vertex(project: &str, region: &str, access_token: &str) -> Self {
+        Self {
+            name: region.to_string(),
+            base_url: format!(
+                "https://{region}-aiplatform.googleapis.com/v1/projects/{project}/locations/{region}/publishers/google/models/"
+            ),
+            auth: EndpointAuth::Bearer(access_token.to_string()),
+        }
+    }
+
+    pub fn url(&self, model: &str) -> String {
+        format!("{}{}:generateContent", self.base_url, model)
+    }
+
+    pub fn stream_url(&self, model: &str) -> String {
+        format!("{}{}:streamGenerateContent?alt=sse", self.base_url, model)
+    }
+
+    // Where the Files API of the endpoint lives. Only AI Studio has one,
+    // Vertex takes attachments inline or from Cloud Storage.
+    pub fn files_url(&self) -> Option<String> {
+        match self.auth {
+            EndpointAuth::ApiKey => self
+                .base_url
+                .strip_suffix("v1beta/models/")
+                .map(str::to_string),
+            EndpointAuth::Bearer(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FailoverConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    unhealthy_since: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Vec<GeminiEndpoint>,
+    config: FailoverConfig,
+    states: Mutex<Vec<EndpointState>>,
+}
+
+impl EndpointPool {
+    pub fn new(endpoints: Vec<GeminiEndpoint>) -> Result<Self, AgentError> {
+        Self::with_config(endpoints, FailoverConfig::default())
+    }
+
+    pub fn with_config(
+        endpoints: Vec<GeminiEndpoint>,
+        config: FailoverConfig,
+    ) -> Result<Self, AgentError> {
+        if endpoints.is_empty() {
+            return Err(AgentError::UserInputError(Some(
+                "At least one Gemini endpoint is required".to_string(),
+            )));
+        }
+
+        let states = endpoints.iter().map(|_| EndpointState::default()).collect();
+
+        Ok(Self {
+            endpoints,
+            config,
+            states: Mutex::new(states),
+        })
+    }
+
+    pub fn endpoints(&self) -> &[GeminiEndpoint] {
+        &self.endpoints
+    }
+
+    // The Files API of the first endpoint that has one.
+    pub fn files_url(&self) -> Option<String> {
+        self.endpoints.iter().find_map(GeminiEndpoint::files_url)
+    }
+
+    // Healthy endpoints in configured order, followed by the unhealthy ones
+    // whose cooldown expires first, so a request is only refused when every
+    // endpoint has been tried.
+    pub async fn order(&self) -> Vec<usize> {
+        let states = self.states.lock().await;
+        let (mut healthy, mut unhealthy): (Vec<usize>, Vec<usize>) = (0..self.endpoints.len())
+            .partition(|index| {
+                states[*index]
+                    .unhealthy_since
+                    .is_none_or(|since| since.elapsed() >= self.config.cooldown)
+            });
+
+        unhealthy.sort_by_key(|index| states[*index].unhealthy_since);
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    pub async fn record_success(&self, index: usize) {
+        let mut states = self.states.lock().await;
+        states[index] = EndpointState::default();
+    }
+
+    pub async fn record_failure(&self, index: usize, error: &str) {
+        let mut states = self.states.lock().await;
+        let state = &mut states[index];
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.config.failure_threshold {
+            if state.unhealthy_since.is_none() {
+                warn!(
+                    "[VOO] gemini endpoint {} marked unhealthy after {} failures: {}",
+                    self.endpoints[index].name, state.consecutive_failures, error
+                );
+            }
+            state.unhealthy_since = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unhealthy_endpoints_are_tried_last() {
+        let pool = EndpointPool::new(vec![
+            GeminiEndpoint::vertex("p", "us-central1", "token"),
+            GeminiEndpoint::vertex("p", "europe-west4", "token"),
+            GeminiEndpoint::studio(),
+        ])
+        .unwrap();
+        assert_eq!(pool.order().await, vec![0, 1, 2]);
+
+        pool.record_failure(0, "503").await;
+        assert_eq!(pool.order().await, vec![0, 1, 2]);
+
+        pool.record_failure(0, "503").await;
+        pool.record_failure(1, "503").await;
+        pool.record_failure(1, "503").await;
+        assert_eq!(pool.order().await, vec![2, 0, 1]);
+
+        pool.record_success(0).await;
+        assert_eq!(pool.order().await, vec![0, 2, 1]);
+
+        assert_eq!(
+            pool.endpoints()[1].url("gemini-2.0-flash-001"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/p/locations/europe-west4/publishers/google/models/gemini-2.0-flash-001:generateContent"
+        );
+        assert_eq!(
+            pool.files_url().as_deref(),
+            Some("https://generativelanguage.googleapis.com/")
+        );
+        let vertex = EndpointPool::new(vec![GeminiEndpoint::vertex("p", "us-central1", "t")]);
+        assert_eq!(vertex.unwrap().files_url(), None);
+        assert!(EndpointPool::new(vec![]).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::{Duration, Instant};
+
+use domain::models::{
+    agent::{AgentError, Content, Part},
+    health::RateLimitStatus,
+};
+use tokio::sync::Mutex;
+
+use super::rate_limit::{RateLimitConfig, RateLimiter};
+
+#[derive(Debug, Clone)]
+pub struct FreeTierConfig {
+    pub requests_per_minute: usize,
+    pub max_history: usize,
+    pub intermediate_model: String,
+}
+
+impl Default for FreeTierConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 15,
+            max_history: 12,
+            intermediate_model: "gemini-2.0-flash-lite-001".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FreeTier {
+    config: FreeTierConfig,
+    limiter: RateLimiter,
+    exhausted_until: Mutex<Option<Instant>>,
+}
+
+impl FreeTier {
+    pub fn new(config: FreeTierConfig) -> Self {
+        Self {
+            // Requests of the last minute, say of an earlier run, already
+            // count against the quota, so they are spread out from the start
+            // instead of going out back to back.
+            limiter: RateLimiter::new(RateLimitConfig {
+                requests_per_minute: config.requests_per_minute,
+                burst: 1,
+            }),
+            config,
+            exhausted_until: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &FreeTierConfig {
+        &self.config
+    }
+
+    // Waits until another request fits in the per-minute budget, then claims it.
+    pub async fn throttle(&self) {
+        self.limiter.acquire().await;
+    }
+
+    // Fails fast while a previously reported daily quota is still exhausted, so
+    // the provider isn't hammered with requests that can only return 429.
+    pub async fn check_quota(&self) -> Result<(), AgentError> {
+        let mut exhausted_until = self.exhausted_until.lock().await;
+
+        match *exhausted_until {
+            Some(until) if Instant::now() < until => Err(AgentError::QuotaExhausted(Some(
+                daily_quota_message(until.saturating_duration_since(Instant::now())),
+            ))),
+            Some(_) => {
+                *exhausted_until = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub async fn mark_exhausted(&self, retry_after: Duration) {
+        *self.exhausted_until.lock().await = Some(Instant::now() + retry_after);
+    }
+
+    pub async fn status(&self) -> RateLimitStatus {
+        let exhausted_secs = self
+            .exhausted_until
+            .lock()
+            .await
+            .map(|until| until.saturating_duration_since(Instant::now()).as_secs())
+            .filter(|secs| *secs > 0);
+
+        RateLimitStatus {
+            exhausted_secs,
+            ..self.limiter.status().await
+        }
+    }
+
+    // Keeps the system prompt and the most recent messages only.
+    pub fn compact(&self, contents: Vec<Content>) -> Vec<Content> {
+        compact(contents, self.config.max_history)
+    }
+}
+
+pub fn compact(mut contents: Vec<Content>, max_history: usize) -> Vec<Content> {
+    if contents.len() <= max_history + 1 {
+        return contents;
+    }
+
+    let mut recent = contents.split_off(contents.len() - max_history);
+    // A function response must follow its call, so one whose call is dropped
+    // goes too.
+    let orphaned = recent
+        .iter()
+        .take_while(|content| {
+            content
+                .parts
+                .iter()
+                .any(|part| part.function_response.is_some())
+        })
+        .count();
+    let recent = recent.split_off(orphaned);
+    let dropped = contents.len() - 1 + orphaned;
+    contents.truncate(1);
+    contents.push(Content::new(
+        vec![Part::new(&format!(
+            "[{} earlier messages were dropped to stay within the free tier quota]",
+            dropped
+        ))],
+        "user",
+    ));
+    contents.extend(recent);
+
+    contents
+}
+
+pub fn daily_quota_message(retry_after: Duration) -> String {
+    let minutes = retry_after.as_secs().div_ceil(60);
+    format!(
+        "The daily Gemini free tier quota is exhausted. It resets in about {}h {}m (midnight Pacific time); use a paid API key to keep going.",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> Content {
+        Content::new(vec![Part::new(text)], "user")
+    }
+
+    #[test]
+    fn test_compact_keeps_system_prompt_and_recent() {
+        let contents = (0..10)
+            .map(|i| message(&i.to_string()))
+            .collect::<Vec<Content>>();
+
+        let compacted = compact(contents.clone(), 3);
+        let texts = compacted
+            .iter()
+            .map(|content| content.parts[0].text.clone().unwrap_or_default())
+            .collect::<Vec<String>>();
+
+        assert_eq!(texts.len(), 5);
+        assert_eq!(texts[0], "0");
+        assert!(texts[1].starts_with("[6 earlier messages"));
+        assert_eq!(&texts[2..], ["7", "8", "9"]);
+
+        assert_eq!(compact(contents.clone(), 9), contents);
+    }
+
+    #[tokio::test]
+    async fn test_quota_exhausted_fails_fast() {
+        let free_tier = FreeTier::new(FreeTierConfig::default());
+        assert!(free_tier.check_quota().await.is_ok());
+
+        free_tier.mark_exhausted(Duration::from_secs(3600)).await;
+        assert!(matches!(
+            free_tier.check_quota().await,
+            Err(AgentError::QuotaExhausted(_))
+        ));
+
+        free_tier.mark_exhausted(Duration::ZERO).await;
+        assert!(free_tier.check_quota().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_throttle_spreads_requests() {
+        let free_tier = FreeTier::new(FreeTierConfig {
+            requests_per_minute: 1200,
+            ..FreeTierConfig::default()
+        });
+
+        let started = Instant::now();
+        for _ in 0..3 {
+            free_tier.throttle().await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(90));
+    }
+}
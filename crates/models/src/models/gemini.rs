@@ -1,120 +1,702 @@
+use futures::{StreamExt, stream::BoxStream};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 
 use async_trait::async_trait;
 use domain::models::{
-    agent::{AgentClient, AgentError, AgentRole, Content, Part},
-    tools::{FunctionDeclaration, Tool},
+    agent::{
+        AgentClient, AgentError, AgentRole, Content, Part, PartStream, SystemPrompt,
+        approximate_tokens,
+    },
+    attachment::Attachment,
+    health::RateLimitStatus,
+    tools::{FunctionDeclaration, Tool, ToolDecorators, ToolFilter, ToolOutput},
+    usage::{TokenUsage, UsageTracker},
+};
+
+use crate::{
+    capabilities::CapabilityRegistry,
+    prompt::{SYSTEM_PROMPT, SYSTEM_PROMPT_VERSION},
 };
 
-static API_URL: &'static str = "https://generativelanguage.googleapis.com/v1beta/models/";
-static MODEL: &'static str = "gemini-2.0-flash-001";
+use super::attachments::AttachmentManager;
+use super::context::{ContextConfig, ContextManager, SUMMARY_PROMPT, apply_summary};
+use super::endpoints::{EndpointAuth, EndpointPool, GeminiEndpoint};
+use super::free_tier::{FreeTier, FreeTierConfig, daily_quota_message};
+use super::retry::RetryConfig;
+
+static DEFAULT_MODEL: &str = "gemini-2.0-flash-001";
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeminiModel {
     api_key: String,
+    model: String,
+    generation_config: Option<GenerationConfig>,
+    // Set by the mode, wins over the temperature of `generation_config`.
+    temperature: Arc<std::sync::Mutex<Option<f32>>>,
     reqwest: Arc<reqwest::Client>,
     conversation: Arc<Mutex<ConversationHistory>>,
     tools: Arc<Mutex<GeminiTool>>,
+    tool_filter: Arc<Mutex<ToolFilter>>,
+    tool_decorators: Arc<Mutex<ToolDecorators>>,
+    system_prompt: Arc<Mutex<SystemPrompt>>,
+    free_tier: Option<Arc<FreeTier>>,
+    endpoints: Arc<EndpointPool>,
+    usage: Arc<UsageTracker>,
+    context: Option<Arc<ContextManager>>,
+    retry: RetryConfig,
+    attachments: Arc<AttachmentManager>,
 }
 
 impl GeminiModel {
     pub fn new(api_key: String) -> Self {
-        let initial_prompt = Content::new(
-            vec![Part::new(
-                r#"
-You are VOO, an expert LLM Agent operating in {{current_mode}} mode. Strictly follow these rules:
-
-1. **TOOL USAGE**:
-   - Use tools step-by-step, one per message
-   - Always refer to the tool's response in your messages
-
-2. **RESPONSE FORMAT**:
-   - Format ALL code/language constructs as [`language.declaration()`](relative/path.ext:line)
-   - For filenames: [`filename.ext`](relative/path.ext)
-   - Use <thinking> tags for internal reasoning
-
-3. **MODES**:
-   - Code: Make code changes
-   - Architect: Plan system architecture
-   - Ask: Answer technical questions
-   - Debug: Diagnose and fix issues
-   - Orchestrator: Coordinate between modes
-
-4. **ERROR HANDLING**:
-   - On errors: diagnose, document in \memlog, and retry
-   - For credential issues: guide user through secure setup
-
-5. **SECURITY**:
-   - Never expose credentials
-   - Sanitize all inputs
-   - Validate file paths
-
-Always reference the project structure at f:/Dev/voo for context.
-Don't reply with empty messages.
-"#,
-            )],
-            "model",
-        );
+        Self::builder().api_key(&api_key).build()
+    }
+
+    pub fn builder() -> GeminiModelBuilder {
+        GeminiModelBuilder::default()
+    }
+
+    fn from_builder(builder: GeminiModelBuilder) -> Self {
+        let initial_prompt = Content::new(vec![Part::new(SYSTEM_PROMPT)], "model");
 
         let conversation_history = ConversationHistory::new(vec![initial_prompt]);
         let tools = Arc::new(Mutex::new(GeminiTool {
             function_declarations: vec![],
         }));
 
+        let model = builder.model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+        let context = CapabilityRegistry::builtin()
+            .get(&model)
+            .map(|capabilities| {
+                Arc::new(ContextManager::new(ContextConfig::new(
+                    capabilities.context_window,
+                )))
+            });
+
+        let attachments = Arc::new(AttachmentManager::new(&builder.api_key));
         Self {
-            api_key,
+            api_key: builder.api_key,
+            model,
+            generation_config: Some(builder.generation_config)
+                .filter(|config| *config != GenerationConfig::default()),
+            temperature: Arc::new(std::sync::Mutex::new(None)),
             conversation: Arc::new(Mutex::new(conversation_history)),
             reqwest: Arc::new(reqwest::Client::new()),
             tools,
+            tool_filter: Arc::new(Mutex::new(ToolFilter::all())),
+            tool_decorators: Arc::new(Mutex::new(ToolDecorators::default())),
+            system_prompt: Arc::new(Mutex::new(SystemPrompt {
+                version: SYSTEM_PROMPT_VERSION,
+                text: SYSTEM_PROMPT.to_string(),
+            })),
+            free_tier: None,
+            endpoints: Arc::new(
+                EndpointPool::new(vec![GeminiEndpoint::studio()])
+                    .expect("default endpoint list is not empty"),
+            ),
+            usage: Arc::new(UsageTracker::default()),
+            context,
+            retry: RetryConfig::default(),
+            attachments,
         }
     }
-}
 
-#[async_trait]
-impl AgentClient for GeminiModel {
-    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
-        let api_key = &self.api_key;
-        let url = format!("{}{}:generateContent?key={}", API_URL, MODEL, api_key);
+    // Also points uploads at the Files API of the endpoints, without one
+    // large attachments are refused.
+    pub fn with_endpoints(mut self, endpoints: EndpointPool) -> Self {
+        let attachments = AttachmentManager::new(&self.api_key);
+        self.attachments = Arc::new(match endpoints.files_url() {
+            Some(files_url) => attachments.with_base_url(&files_url),
+            None => attachments.without_uploads(),
+        });
+        self.endpoints = Arc::new(endpoints);
+        self
+    }
+
+    pub fn without_uploads(mut self) -> Self {
+        self.attachments = Arc::new(AttachmentManager::new(&self.api_key).without_uploads());
+        self
+    }
+
+    pub fn upload_url(&self) -> Option<String> {
+        self.attachments.upload_url()
+    }
+
+    pub fn with_free_tier(mut self, config: FreeTierConfig) -> Self {
+        self.free_tier = Some(Arc::new(FreeTier::new(config)));
+        self
+    }
+
+    pub fn with_retry(mut self, config: RetryConfig) -> Self {
+        self.retry = config;
+        self
+    }
 
+    // Replaces the context window looked up for the model.
+    pub fn with_context(mut self, config: ContextConfig) -> Self {
+        self.context = Some(Arc::new(ContextManager::new(config)));
+        self
+    }
+
+    // The declarations sent with a request, rebuilt each time from the
+    // registered tools so a changed filter or hint applies right away.
+    async fn declarations(&self) -> GeminiTool {
+        let filter = self.tool_filter.lock().await.clone();
+        let declarations = filter.apply(&self.tools.lock().await.function_declarations);
+        GeminiTool {
+            function_declarations: self.tool_decorators.lock().await.apply(declarations),
+        }
+    }
+
+    // An empty prompt continues the turn after tool results, there is nothing
+    // to add then.
+    async fn push_prompt(&self, prompt: &str) {
+        if prompt.is_empty() {
+            return;
+        }
         let content = Content::new(vec![Part::new(prompt)], "user");
-        {
-            self.conversation.lock().await.contents.push(content);
+        self.conversation.lock().await.contents.push(content);
+    }
+
+    // Summarizes the older turns in the background once the history nears the
+    // context window. The request that noticed it still sends all of them.
+    async fn summarize_history(&self) {
+        let Some(context) = self.context.clone() else {
+            return;
+        };
+        let Some(tokens) = self.estimate_tokens("").await else {
+            return;
+        };
+        let contents = self.conversation.lock().await.contents.clone();
+        let Some(split) = context.start(tokens, &contents) else {
+            return;
+        };
+
+        let model = self.clone();
+        tokio::spawn(async move {
+            let summarized = contents[..split].to_vec();
+            match model.summarize(&summarized).await {
+                Ok(summary) => {
+                    let mut conversation = model.conversation.lock().await;
+                    if apply_summary(&mut conversation.contents, &summarized, &summary) {
+                        info!(
+                            "[VOO] summarized {} earlier messages to stay within the context window",
+                            split - 1
+                        );
+                    }
+                }
+                Err(e) => error!("[VOO] could not summarize the conversation: {}", e),
+            }
+            context.finish();
+        });
+    }
+
+    async fn summarize(&self, contents: &[Content]) -> Result<String, AgentError> {
+        let mut contents = contents.to_vec();
+        contents.push(Content::new(vec![Part::new(SUMMARY_PROMPT)], "user"));
+        let prompt = self.prompt(contents, self.declarations().await);
+
+        if let Some(free_tier) = &self.free_tier {
+            free_tier.check_quota().await?;
+            free_tier.throttle().await;
+        }
+        let response = self.send(&self.model, &prompt).await?;
+        if let Some(usage_metadata) = &response.usage_metadata {
+            usage_metadata.record(&self.usage);
         }
 
-        let tools = self.tools.lock().await.clone();
-        let history = self.conversation.lock().await.clone();
-        let contents = history.contents;
+        let summary = response
+            .candidates
+            .iter()
+            .flatten()
+            .flat_map(|candidate| &candidate.content.parts)
+            .filter_map(|part| part.answer().cloned())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if summary.trim().is_empty() {
+            return Err(match response.error {
+                Some(error) => response_error(error),
+                None => no_response(response.block_reason()),
+            });
+        }
 
-        let prompt = Prompt::new(contents, tools);
+        Ok(summary)
+    }
 
-        let response = self
-            .reqwest
-            .post(url)
-            .json(&prompt)
-            .send()
-            .await
-            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+    // The files bound with /attach go right after the system prompt, so
+    // neither compaction nor summaries drop them.
+    async fn with_attachments(&self, mut contents: Vec<Content>) -> Vec<Content> {
+        if let Some(content) = self.attachments.bound_content().await {
+            contents.insert(contents.len().min(1), content);
+        }
+        contents
+    }
+
+    fn prompt(&self, contents: Vec<Content>, tools: GeminiTool) -> Prompt {
+        let mut generation_config = self.generation_config.clone();
+        if let Some(temperature) = *self.temperature.lock().unwrap_or_else(|e| e.into_inner()) {
+            generation_config
+                .get_or_insert_with(GenerationConfig::default)
+                .temperature = Some(temperature);
+        }
+
+        Prompt {
+            contents,
+            tools: vec![tools],
+            generation_config,
+        }
+    }
+
+    // Tries each endpoint in health order, moving on when one is unreachable
+    // or returns a server error. Client errors are returned as is since every
+    // region would reject the same request.
+    async fn send(&self, model: &str, prompt: &Prompt) -> Result<GeminiResponse, AgentError> {
+        let mut last_error = None;
+
+        for index in self.endpoints.order().await {
+            let endpoint = &self.endpoints.endpoints()[index];
+
+            match self.send_to(endpoint, model, prompt).await {
+                Ok(response) => {
+                    self.endpoints.record_success(index).await;
+                    return Ok(response);
+                }
+                Err(e) => {
+                    let error = format!("{}: {}", endpoint.name, e);
+                    self.endpoints.record_failure(index, &error).await;
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(AgentError::Network(last_error.unwrap_or_default()))
+    }
+
+    async fn send_to(
+        &self,
+        endpoint: &GeminiEndpoint,
+        model: &str,
+        prompt: &Prompt,
+    ) -> Result<GeminiResponse, String> {
+        let text = self.post(endpoint, model, prompt).await?;
+        serde_json::from_str::<GeminiResponse>(&text).map_err(|e| e.to_string())
+    }
+
+    async fn post(
+        &self,
+        endpoint: &GeminiEndpoint,
+        model: &str,
+        prompt: &Prompt,
+    ) -> Result<String, String> {
+        let request = self.reqwest.post(endpoint.url(model)).json(prompt);
+        let request = match &endpoint.auth {
+            EndpointAuth::ApiKey => request.header("x-goog-api-key", &self.api_key),
+            EndpointAuth::Bearer(token) => request.bearer_auth(token),
+        };
+
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| e.to_string())?;
+
+        if status.is_server_error() {
+            return Err(format!("{} {}", status, text.trim()));
+        }
 
-        let text = response
-            .text()
+        Ok(text)
+    }
+
+    // Sends a trivial prompt to the first endpoint and checks that the answer
+    // still parses the way voo expects. Returns the fields of the answer voo
+    // ignores, new ones there hint at upstream changes.
+    pub async fn schema_check(&self) -> Result<Vec<String>, AgentError> {
+        let endpoint = &self.endpoints.endpoints()[0];
+        let contents = vec![Content::new(vec![Part::new("Reply with OK.")], "user")];
+        let prompt = self.prompt(contents, GeminiTool::default());
+
+        let text = self
+            .post(endpoint, &self.model, &prompt)
             .await
-            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+            .map_err(|e| AgentError::Network(format!("{}: {}", endpoint.name, e)))?;
+        let response = serde_json::from_str::<GeminiResponse>(&text).map_err(|e| {
+            AgentError::Deserialization {
+                body: text.clone(),
+                message: format!("The response no longer parses: {}", e),
+            }
+        })?;
+        if let Some(error) = response.error {
+            return Err(response_error(error));
+        }
 
-        let response_json = serde_json::from_str::<GeminiResponse>(&text)
-            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        let answered = response
+            .candidates
+            .iter()
+            .flatten()
+            .flat_map(|candidate| &candidate.content.parts)
+            .any(|part| part.text.is_some());
+        if !answered {
+            return Err(AgentError::AgentError(Some(format!(
+                "The response has no text: {}",
+                text.trim()
+            ))));
+        }
 
-        if let Some(error) = response_json.error {
-            let error_msg = error.message;
+        let raw = serde_json::from_str::<Value>(&text).unwrap_or_default();
+        let parsed = serde_json::to_value(&response).unwrap_or_default();
+        Ok(ignored_fields(&raw, &parsed, ""))
+    }
+
+    // Opens a streamed response on the first endpoint that accepts the request,
+    // skipping unreachable endpoints and server errors like `send` does.
+    async fn open_stream(
+        &self,
+        model: &str,
+        prompt: &Prompt,
+    ) -> Result<reqwest::Response, AgentError> {
+        let mut last_error = None;
+
+        for index in self.endpoints.order().await {
+            let endpoint = &self.endpoints.endpoints()[index];
+            let request = self.reqwest.post(endpoint.stream_url(model)).json(prompt);
+            let request = match &endpoint.auth {
+                EndpointAuth::ApiKey => request.header("x-goog-api-key", &self.api_key),
+                EndpointAuth::Bearer(token) => request.bearer_auth(token),
+            };
+
+            let response = match request.send().await {
+                Ok(response) if response.status().is_server_error() => {
+                    Err(format!("{}: {}", endpoint.name, response.status()))
+                }
+                Ok(response) => Ok(response),
+                Err(e) => Err(format!("{}: {}", endpoint.name, e)),
+            };
+
+            match response {
+                Ok(response) if response.status().is_success() => {
+                    self.endpoints.record_success(index).await;
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    self.endpoints.record_success(index).await;
+                    let text = response
+                        .text()
+                        .await
+                        .map_err(|e| AgentError::Network(e.to_string()))?;
+
+                    return match serde_json::from_str::<GeminiResponse>(&text)
+                        .ok()
+                        .and_then(|response| response.error)
+                    {
+                        Some(error) => {
+                            if let Some(free_tier) = self.free_tier.as_ref()
+                                && error.is_daily_quota()
+                            {
+                                free_tier.mark_exhausted(until_quota_reset()).await;
+                            }
+                            Err(response_error(error))
+                        }
+                        None => Err(AgentError::AgentError(Some(text))),
+                    };
+                }
+                Err(error) => {
+                    self.endpoints.record_failure(index, &error).await;
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(AgentError::Network(last_error.unwrap_or_default()))
+    }
+
+    // Opens the stream like `open_stream`, retried like `generate_with_retry`.
+    // Only opening it is retried, parts that already arrived can't be taken
+    // back.
+    async fn open_stream_with_retry(
+        &self,
+        model: &str,
+        prompt: &Prompt,
+    ) -> Result<reqwest::Response, AgentError> {
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            if let Some(free_tier) = &self.free_tier {
+                free_tier.throttle().await;
+            }
+            let error = match self.open_stream(model, prompt).await {
+                Err(e) if e.is_retryable() && attempt < max_attempts => e,
+                response => return response,
+            };
+
+            let delay = self.retry.delay(attempt);
+            let delay = match &error {
+                AgentError::RateLimited {
+                    retry_after: Some(requested),
+                    ..
+                } if *requested > self.retry.max_delay => return Err(error),
+                AgentError::RateLimited {
+                    retry_after: Some(requested),
+                    ..
+                } => (*requested).max(delay),
+                _ => delay,
+            };
+            warn!(
+                "[VOO] gemini request failed, retrying in {}ms ({} of {}): {}",
+                delay.as_millis(),
+                attempt + 1,
+                max_attempts,
+                error
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // Sends the request again after network failures, server errors and
+    // per-minute rate limits, waiting longer after every attempt. Errors that
+    // would come back the same, like an expired key or a rejected request,
+    // are returned right away.
+    async fn generate_with_retry(&self, prompt: &str) -> Result<GeminiResponse, AgentError> {
+        let max_attempts = self.retry.max_attempts.max(1);
+        let mut attempt = 0;
 
-            if error_msg.contains("API key expired.") {
-                return Err(AgentError::ExpiredApiKey);
+        loop {
+            attempt += 1;
+            let response = self.generate(prompt).await;
+            let (error, requested_delay) = match &response {
+                Ok(body) => match body.error.as_ref().filter(|error| error.is_retryable()) {
+                    Some(error) => (error.message.clone(), error.retry_delay()),
+                    None => return response,
+                },
+                Err(e) if e.is_retryable() => (e.to_string(), None),
+                Err(_) => return response,
+            };
+            if attempt >= max_attempts {
+                return response;
             }
 
-            return Err(AgentError::AgentError(Some(error_msg)));
+            // A rate limit that resets later than we would wait is reported
+            // instead of sleeping through it.
+            let delay = self.retry.delay(attempt);
+            let delay = match requested_delay {
+                Some(requested) if requested > self.retry.max_delay => return response,
+                Some(requested) => requested.max(delay),
+                None => delay,
+            };
+            warn!(
+                "[VOO] gemini request failed, retrying in {}ms ({} of {}): {}",
+                delay.as_millis(),
+                attempt + 1,
+                max_attempts,
+                error
+            );
+            tokio::time::sleep(delay).await;
         }
+    }
+
+    async fn generate(&self, prompt: &str) -> Result<GeminiResponse, AgentError> {
+        let tools = self.declarations().await;
+        let contents = self.conversation.lock().await.contents.clone();
+
+        let Some(free_tier) = &self.free_tier else {
+            let contents = self.with_attachments(contents).await;
+            return self.send(&self.model, &self.prompt(contents, tools)).await;
+        };
+
+        free_tier.check_quota().await?;
+
+        // An empty prompt means the model is continuing after tool results,
+        // which the cheaper model handles well enough.
+        let model = if prompt.is_empty() {
+            free_tier.config().intermediate_model.as_str()
+        } else {
+            self.model.as_str()
+        };
+        let contents = self.with_attachments(free_tier.compact(contents)).await;
+        let prompt = self.prompt(contents, tools);
+
+        let mut retried = false;
+        loop {
+            free_tier.throttle().await;
+            let response = self.send(model, &prompt).await?;
+
+            let Some(error) = response.error.as_ref().filter(|error| error.is_quota()) else {
+                return Ok(response);
+            };
+
+            if error.is_daily_quota() {
+                free_tier.mark_exhausted(until_quota_reset()).await;
+                return Ok(response);
+            }
+
+            match error.retry_delay() {
+                Some(delay) if !retried && delay <= Duration::from_secs(60) => {
+                    info!(
+                        "[VOO] free tier: rate limited, retrying in {}s",
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    retried = true;
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+}
+
+struct StreamState {
+    bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+    pending: VecDeque<Part>,
+    // The streamed answer and function calls, added to the history once the
+    // response is complete.
+    answer: String,
+    calls: Vec<Part>,
+    received: bool,
+    done: bool,
+    conversation: Arc<Mutex<ConversationHistory>>,
+    // The counts of each chunk are running totals, so only the last one is
+    // recorded.
+    usage_metadata: Option<UsageMetadata>,
+    usage: Arc<UsageTracker>,
+}
+
+async fn next_part(mut state: StreamState) -> Option<(Result<Part, AgentError>, StreamState)> {
+    loop {
+        if let Some(part) = state.pending.pop_front() {
+            return Some((Ok(part), state));
+        }
+        if state.done {
+            return None;
+        }
+
+        match state.bytes.next().await {
+            Some(Ok(chunk)) => {
+                state.buffer.extend(chunk);
+                for event in take_events(&mut state.buffer) {
+                    let response = match serde_json::from_str::<GeminiResponse>(&event) {
+                        Ok(response) => response,
+                        Err(e) => {
+                            state.done = true;
+                            let error = AgentError::Deserialization {
+                                body: event,
+                                message: e.to_string(),
+                            };
+                            return Some((Err(error), state));
+                        }
+                    };
+                    if response.usage_metadata.is_some() {
+                        state.usage_metadata = response.usage_metadata.clone();
+                    }
+                    if let Some(error) = response.error {
+                        state.done = true;
+                        return Some((Err(response_error(error)), state));
+                    }
+                    if let Some(reason) = response.block_reason() {
+                        state.done = true;
+                        return Some((Err(no_response(Some(reason))), state));
+                    }
+
+                    for part in response
+                        .candidates
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flat_map(|candidate| candidate.content.parts)
+                    {
+                        if let Some(text) = part.answer() {
+                            state.answer.push_str(text);
+                        }
+                        if part.function_call.is_some() {
+                            state.calls.push(part.clone());
+                        }
+                        state.received = true;
+                        state.pending.push_back(part);
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                state.done = true;
+                return Some((Err(AgentError::Network(e.to_string())), state));
+            }
+            None => {
+                state.done = true;
+                if let Some(usage_metadata) = &state.usage_metadata {
+                    usage_metadata.record(&state.usage);
+                }
+                if !state.received {
+                    return Some((Err(no_response(None)), state));
+                }
+
+                let mut parts = vec![];
+                if !state.answer.is_empty() {
+                    parts.push(Part::new(&state.answer));
+                }
+                parts.append(&mut state.calls);
+                if !parts.is_empty() {
+                    let content = Content::new(parts, "model");
+                    state.conversation.lock().await.contents.push(content);
+                }
+            }
+        }
+    }
+}
+
+// Removes the complete server-sent events from the buffer and returns their
+// data.
+pub fn take_events(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut events = vec![];
+
+    loop {
+        let Some((end, separator)) = [&b"\r\n\r\n"[..], &b"\n\n"[..]]
+            .iter()
+            .filter_map(|separator| {
+                buffer
+                    .windows(separator.len())
+                    .position(|window| window == *separator)
+                    .map(|end| (end, separator.len()))
+            })
+            .min()
+        else {
+            return events;
+        };
+
+        let event = buffer.drain(..end + separator).collect::<Vec<u8>>();
+        let data = String::from_utf8_lossy(&event)
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(str::trim)
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        if !data.is_empty() {
+            events.push(data);
+        }
+    }
+}
+
+#[async_trait]
+impl AgentClient for GeminiModel {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        self.push_prompt(prompt).await;
+        self.summarize_history().await;
+
+        let response_json = self.generate_with_retry(prompt).await?;
+        if let Some(usage_metadata) = &response_json.usage_metadata {
+            usage_metadata.record(&self.usage);
+        }
+
+        if let Some(error) = response_json.error {
+            return Err(response_error(error));
+        }
+        let blocked = response_json.block_reason().map(str::to_string);
 
         let contents = response_json
             .candidates
@@ -125,33 +707,81 @@ impl AgentClient for GeminiModel {
 
         let parts = contents
             .iter()
-            .map(|content| content.parts.clone())
-            .flatten()
+            .flat_map(|content| content.parts.clone())
             .collect::<Vec<Part>>();
 
         let texts = parts
             .iter()
+            .filter(|part| !part.is_thought())
             .map(|part| part.text.clone().unwrap_or_default())
             .collect::<Vec<String>>();
 
         if texts.is_empty() {
-            return Err(AgentError::AgentError(Some(
-                "No response from Gemini".to_string(),
-            )));
+            return Err(no_response(blocked.as_deref()));
         }
 
-        for response in texts.iter() {
-            if response.is_empty() {
-                continue;
-            }
-
-            let response = format!(r##"{}"##, response);
-            _ = self.add_system_prompt(&response, AgentRole::Model).await;
+        // The calls stay in the history, their function responses answer them.
+        let remembered = parts
+            .into_iter()
+            .filter(|part| {
+                part.function_call.is_some() || part.answer().is_some_and(|text| !text.is_empty())
+            })
+            .collect::<Vec<Part>>();
+        if !remembered.is_empty() {
+            self.conversation
+                .lock()
+                .await
+                .contents
+                .push(Content::new(remembered, "model"));
         }
 
         Ok(contents)
     }
 
+    async fn ask_stream(&self, prompt: &str) -> Result<PartStream, AgentError> {
+        self.push_prompt(prompt).await;
+        self.summarize_history().await;
+
+        let tools = self.declarations().await;
+        let contents = self.conversation.lock().await.contents.clone();
+
+        let (model, contents) = match &self.free_tier {
+            Some(free_tier) => {
+                free_tier.check_quota().await?;
+
+                let model = if prompt.is_empty() {
+                    free_tier.config().intermediate_model.as_str()
+                } else {
+                    self.model.as_str()
+                };
+                (model, free_tier.compact(contents))
+            }
+            None => (self.model.as_str(), contents),
+        };
+        let contents = self.with_attachments(contents).await;
+
+        let response = self
+            .open_stream_with_retry(model, &self.prompt(contents, tools))
+            .await?;
+        let state = StreamState {
+            bytes: response
+                .bytes_stream()
+                .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+                .boxed(),
+            buffer: vec![],
+            pending: VecDeque::new(),
+            answer: String::new(),
+            calls: vec![],
+            received: false,
+            done: false,
+            conversation: self.conversation.clone(),
+            usage_metadata: None,
+            usage: self.usage.clone(),
+        };
+
+        Ok(Box::pin(futures::stream::unfold(state, next_part)))
+    }
+
     async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
         let tool_definition = tool.tool_definition();
         {
@@ -166,7 +796,13 @@ impl AgentClient for GeminiModel {
     }
 
     async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
-        let content = Content::new(vec![Part::new(prompt)], &role.to_string());
+        // Gemini only knows user and model turns, tool results without a
+        // function call to answer and late instructions come from the user.
+        let role = match role {
+            AgentRole::Model => "model",
+            AgentRole::User | AgentRole::System | AgentRole::Tool => "user",
+        };
+        let content = Content::new(vec![Part::new(prompt)], role);
         {
             self.conversation
                 .lock()
@@ -177,21 +813,188 @@ impl AgentClient for GeminiModel {
 
         Ok(())
     }
+
+    // The responses to the calls of one turn go together in a single content.
+    async fn add_tool_result(&self, name: &str, output: &ToolOutput) -> Result<(), AgentError> {
+        let mut parts = vec![Part::function_response(name, output)];
+        // Images and other media the model can see are attached next to the
+        // response, which itself only says how many bytes there were. Without
+        // the attachment the model still gets the response.
+        if let ToolOutput::Binary { mime, data } = output
+            && inline_mime(mime)
+        {
+            match self.attachments.part(mime, data).await {
+                Ok(part) => parts.push(part),
+                Err(e) => warn!(
+                    "[VOO] could not attach the {} output of {}: {}",
+                    mime, name, e
+                ),
+            }
+        }
+
+        let mut conversation = self.conversation.lock().await;
+        match conversation.contents.last_mut() {
+            Some(last)
+                if last.role == "user"
+                    && last.parts.iter().all(|part| {
+                        part.function_response.is_some()
+                            || part.inline_data.is_some()
+                            || part.file_data.is_some()
+                    }) =>
+            {
+                last.parts.extend(parts)
+            }
+            _ => conversation.contents.push(Content::new(parts, "user")),
+        }
+
+        Ok(())
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        Some(self.system_prompt.lock().await.clone())
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        let content = Content::new(vec![Part::new(&prompt.text)], "model");
+        {
+            let mut conversation = self.conversation.lock().await;
+            match conversation.contents.first_mut() {
+                Some(first) => *first = content,
+                None => conversation.contents.push(content),
+            }
+        }
+
+        *self.system_prompt.lock().await = prompt;
+        Ok(())
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        *self.tool_filter.lock().await = filter;
+        Ok(())
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        *self.tool_decorators.lock().await = decorators;
+        Ok(())
+    }
+
+    async fn set_temperature(&self, temperature: Option<f32>) -> Result<(), AgentError> {
+        *self.temperature.lock().unwrap_or_else(|e| e.into_inner()) = temperature;
+        Ok(())
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        let contents = self.conversation.lock().await.contents.clone();
+        let tools = self.declarations().await;
+        let request = serde_json::to_string(&self.prompt(contents, tools)).ok()?;
+        let attached = self
+            .attachments
+            .bound()
+            .await
+            .iter()
+            .map(|attachment| attachment.tokens)
+            .sum::<u64>();
+
+        Some(approximate_tokens(&request) + approximate_tokens(prompt) + attached)
+    }
+
+    async fn attach(&self, name: &str, mime: &str, data: &[u8]) -> Result<Attachment, AgentError> {
+        self.attachments.bind(name, mime, data).await
+    }
+
+    async fn attachments(&self) -> Vec<Attachment> {
+        self.attachments.bound().await
+    }
+
+    async fn detach(&self, id: &str) -> Result<Attachment, AgentError> {
+        self.attachments.unbind(id).await
+    }
+
+    async fn close(&self) {
+        self.attachments.cleanup().await
+    }
+
+    fn model(&self) -> Option<String> {
+        Some(self.model.clone())
+    }
+
+    // The first configured endpoint, the others are only tried when it fails.
+    fn endpoint(&self) -> Option<String> {
+        Some(self.endpoints.endpoints()[0].base_url.clone())
+    }
+
+    async fn rate_limit(&self) -> Option<RateLimitStatus> {
+        Some(self.free_tier.as_ref()?.status().await)
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        Some(self.usage.usage())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GeminiModelBuilder {
+    api_key: String,
+    model: Option<String>,
+    generation_config: GenerationConfig,
+}
+
+impl GeminiModelBuilder {
+    pub fn api_key(mut self, api_key: &str) -> Self {
+        self.api_key = api_key.to_string();
+        self
+    }
+
+    pub fn model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.generation_config.temperature = Some(temperature);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.generation_config.top_p = Some(top_p);
+        self
+    }
+
+    pub fn max_output_tokens(mut self, max_output_tokens: u32) -> Self {
+        self.generation_config.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+
+    pub fn stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.generation_config.stop_sequences = stop_sequences;
+        self
+    }
+
+    pub fn build(self) -> GeminiModel {
+        GeminiModel::from_builder(self)
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Prompt {
     contents: Vec<Content>,
     tools: Vec<GeminiTool>,
-}
-
-impl Prompt {
-    pub fn new(contents: Vec<Content>, tools: GeminiTool) -> Self {
-        Self {
-            contents,
-            tools: vec![tools],
-        }
-    }
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GenerationConfig>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -201,6 +1004,35 @@ pub struct GeminiResponse {
     pub usage_metadata: Option<UsageMetadata>,
     pub model_version: Option<String>,
     pub error: Option<GeminiError>,
+    pub prompt_feedback: Option<PromptFeedback>,
+}
+
+impl GeminiResponse {
+    // Why the prompt or the answer was blocked, if it was. A candidate that
+    // stopped for another reason than running out of tokens before giving
+    // any parts was blocked, e.g. for SAFETY or RECITATION.
+    pub fn block_reason(&self) -> Option<&str> {
+        if let Some(reason) = self
+            .prompt_feedback
+            .as_ref()
+            .and_then(|feedback| feedback.block_reason.as_deref())
+        {
+            return Some(reason);
+        }
+
+        self.candidates
+            .iter()
+            .flatten()
+            .filter(|candidate| candidate.content.parts.is_empty())
+            .map(|candidate| candidate.finish_reason.as_str())
+            .find(|reason| !matches!(*reason, "" | "STOP" | "MAX_TOKENS"))
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+    pub block_reason: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -209,9 +1041,43 @@ pub struct GeminiError {
     pub code: i64,
     pub message: String,
     pub status: String,
+    #[serde(default)]
     pub details: Vec<Detail>,
 }
 
+impl GeminiError {
+    pub fn is_quota(&self) -> bool {
+        self.code == 429 || self.status == "RESOURCE_EXHAUSTED"
+    }
+
+    pub fn is_daily_quota(&self) -> bool {
+        self.is_quota()
+            && self
+                .details
+                .iter()
+                .flat_map(|detail| detail.violations.iter().flatten())
+                .any(|violation| {
+                    violation
+                        .quota_id
+                        .as_deref()
+                        .is_some_and(|quota_id| quota_id.contains("PerDay"))
+                })
+    }
+
+    // Server errors and per-minute rate limits, which pass by themselves.
+    pub fn is_retryable(&self) -> bool {
+        (self.is_quota() && !self.is_daily_quota()) || self.code >= 500
+    }
+
+    pub fn retry_delay(&self) -> Option<Duration> {
+        self.details
+            .iter()
+            .filter_map(|detail| detail.retry_delay.as_deref())
+            .find_map(|delay| delay.trim_end_matches('s').parse::<f64>().ok())
+            .map(Duration::from_secs_f64)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Detail {
@@ -222,6 +1088,15 @@ pub struct Detail {
     pub metadata: Option<Metadata>,
     pub locale: Option<String>,
     pub message: Option<String>,
+    pub violations: Option<Vec<QuotaViolation>>,
+    pub retry_delay: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaViolation {
+    pub quota_metric: Option<String>,
+    pub quota_id: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -233,8 +1108,12 @@ pub struct Metadata {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Candidate {
+    #[serde(default)]
     pub content: Content,
+    // Only the last chunk of a streamed response carries these.
+    #[serde(default)]
     pub finish_reason: String,
+    #[serde(default)]
     pub avg_logprobs: f64,
 }
 
@@ -251,7 +1130,7 @@ impl ConversationHistory {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(default, rename_all = "camelCase")]
 pub struct UsageMetadata {
     pub prompt_token_count: i64,
     pub candidates_token_count: i64,
@@ -260,6 +1139,16 @@ pub struct UsageMetadata {
     pub candidates_tokens_details: Vec<CandidatesTokensDetail>,
 }
 
+impl UsageMetadata {
+    fn record(&self, tracker: &UsageTracker) {
+        tracker.record(
+            self.prompt_token_count.max(0) as u64,
+            self.candidates_token_count.max(0) as u64,
+            self.total_token_count.max(0) as u64,
+        );
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PromptTokensDetail {
@@ -289,3 +1178,430 @@ impl GeminiTool {
         }
     }
 }
+
+// The media Gemini takes inline, other binary output stays a byte count.
+fn inline_mime(mime: &str) -> bool {
+    ["image/", "audio/", "video/"]
+        .iter()
+        .any(|prefix| mime.starts_with(prefix))
+        || mime == "application/pdf"
+}
+
+fn response_error(error: GeminiError) -> AgentError {
+    if error.message.contains("API key expired.") || error.message.contains("API key not valid") {
+        return AgentError::InvalidApiKey(Some(error.message));
+    }
+
+    if error.is_daily_quota() {
+        return AgentError::QuotaExhausted(Some(daily_quota_message(until_quota_reset())));
+    }
+
+    if error.is_quota() {
+        return AgentError::RateLimited {
+            retry_after: error.retry_delay(),
+            message: error.message,
+        };
+    }
+
+    if error
+        .message
+        .contains("exceeds the maximum number of tokens")
+    {
+        return AgentError::ContextTooLong(Some(error.message));
+    }
+
+    match error.code {
+        500.. => AgentError::Network(error.message),
+        400..500 => AgentError::InvalidRequest(Some(error.message)),
+        _ => AgentError::AgentError(Some(error.message)),
+    }
+}
+
+fn no_response(block_reason: Option<&str>) -> AgentError {
+    match block_reason {
+        Some(reason) => {
+            AgentError::SafetyBlocked(Some(format!("Gemini blocked the response ({})", reason)))
+        }
+        None => AgentError::AgentError(Some("No response from Gemini".to_string())),
+    }
+}
+
+// The paths of the fields in `raw` that are lost when parsing it into
+// `parsed`, i.e. the fields voo does not know about.
+fn ignored_fields(raw: &Value, parsed: &Value, path: &str) -> Vec<String> {
+    match (raw, parsed) {
+        (Value::Object(raw), Value::Object(parsed)) => raw
+            .iter()
+            .flat_map(|(key, value)| {
+                let path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", path, key),
+                };
+                match parsed.get(key) {
+                    Some(parsed) => ignored_fields(value, parsed, &path),
+                    None => vec![path],
+                }
+            })
+            .collect(),
+        (Value::Array(raw), Value::Array(parsed)) => raw
+            .iter()
+            .zip(parsed)
+            .enumerate()
+            .flat_map(|(index, (raw, parsed))| {
+                ignored_fields(raw, parsed, &format!("{}[{}]", path, index))
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// Free tier daily quotas reset at midnight Pacific time. Using the daylight
+// saving offset errs on the side of retrying slightly early.
+fn until_quota_reset() -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default();
+    let pacific = now - 7 * 3600;
+
+    Duration::from_secs((86400 - pacific.rem_euclid(86400)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{read_file::ReadFileTool, write_file::WriteFileTool};
+
+    // Real responses of the API, parsed the way the client does so changes to
+    // the types that break the wire format show up here.
+    const RECORDED: [(&str, &str); 7] = [
+        ("success", include_str!("fixtures/gemini/success.json")),
+        ("tool_call", include_str!("fixtures/gemini/tool_call.json")),
+        (
+            "error_quota",
+            include_str!("fixtures/gemini/error_quota.json"),
+        ),
+        (
+            "error_api_key",
+            include_str!("fixtures/gemini/error_api_key.json"),
+        ),
+        (
+            "safety_block",
+            include_str!("fixtures/gemini/safety_block.json"),
+        ),
+        (
+            "prompt_blocked",
+            include_str!("fixtures/gemini/prompt_blocked.json"),
+        ),
+        (
+            "multi_candidate",
+            include_str!("fixtures/gemini/multi_candidate.json"),
+        ),
+    ];
+
+    fn recorded(name: &str) -> GeminiResponse {
+        let (_, json) = RECORDED
+            .iter()
+            .find(|(recorded, _)| *recorded == name)
+            .unwrap();
+        serde_json::from_str(json).unwrap_or_else(|e| panic!("{} no longer parses: {}", name, e))
+    }
+
+    #[test]
+    fn test_recorded_responses() {
+        let success = recorded("success");
+        let candidates = success.candidates.clone().unwrap();
+        assert_eq!(
+            candidates[0].content.parts[0].text.as_deref(),
+            Some("Hello! How can I help you today?\n")
+        );
+        assert_eq!(
+            success.usage_metadata.as_ref().unwrap().total_token_count,
+            14
+        );
+        assert_eq!(success.block_reason(), None);
+
+        let tool_call = recorded("tool_call").candidates.unwrap();
+        let call = tool_call[0].content.parts[0].function_call.clone().unwrap();
+        assert_eq!(call.name, "read_file");
+        assert_eq!(call.args, serde_json::json!({"path": "src/main.rs"}));
+
+        let quota = recorded("error_quota").error.unwrap();
+        assert!(quota.is_daily_quota());
+        assert_eq!(quota.retry_delay(), Some(Duration::from_secs(22)));
+        let api_key = recorded("error_api_key").error.unwrap();
+        assert_eq!(api_key.code, 400);
+        assert!(!api_key.is_quota());
+
+        assert_eq!(recorded("safety_block").block_reason(), Some("SAFETY"));
+        assert_eq!(
+            recorded("prompt_blocked").block_reason(),
+            Some("PROHIBITED_CONTENT")
+        );
+        assert_eq!(recorded("multi_candidate").candidates.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        assert!(!recorded("error_quota").error.unwrap().is_retryable());
+
+        let api_key = recorded("error_api_key").error.unwrap();
+        assert!(!api_key.is_retryable());
+        assert!(matches!(
+            response_error(api_key),
+            AgentError::InvalidApiKey(_)
+        ));
+
+        let unavailable = GeminiError {
+            code: 503,
+            message: "The model is overloaded. Please try again later.".to_string(),
+            status: "UNAVAILABLE".to_string(),
+            details: vec![],
+        };
+        assert!(unavailable.is_retryable());
+        assert!(response_error(unavailable).is_retryable());
+
+        let per_minute = GeminiError {
+            code: 429,
+            status: "RESOURCE_EXHAUSTED".to_string(),
+            ..Default::default()
+        };
+        assert!(per_minute.is_retryable());
+        assert_eq!(response_error(per_minute).code(), "rate_limited");
+
+        let too_long = GeminiError {
+            code: 400,
+            message: "The input token count (1200000) exceeds the maximum number of tokens allowed (1048576).".to_string(),
+            status: "INVALID_ARGUMENT".to_string(),
+            details: vec![],
+        };
+        assert!(matches!(
+            response_error(too_long),
+            AgentError::ContextTooLong(_)
+        ));
+        assert!(matches!(
+            no_response(Some("SAFETY")),
+            AgentError::SafetyBlocked(_)
+        ));
+    }
+
+    #[test]
+    fn test_ignored_fields() {
+        let ignored = |name: &str| {
+            let (_, json) = RECORDED
+                .iter()
+                .find(|(recorded, _)| *recorded == name)
+                .unwrap();
+            let raw = serde_json::from_str::<Value>(json).unwrap();
+            ignored_fields(&raw, &serde_json::to_value(recorded(name)).unwrap(), "")
+        };
+
+        assert_eq!(ignored("success"), vec!["responseId"]);
+        assert_eq!(
+            ignored("tool_call"),
+            vec![
+                "candidates[0].content.parts[0].thoughtSignature",
+                "candidates[0].index",
+                "responseId",
+                "usageMetadata.thoughtsTokenCount",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_filter() {
+        let model = GeminiModel::new("key".to_string());
+        model
+            .add_tool(Arc::new(ReadFileTool::new("read_file", "")))
+            .await
+            .unwrap();
+        model
+            .add_tool(Arc::new(WriteFileTool::new("write_file", "")))
+            .await
+            .unwrap();
+
+        model
+            .set_tool_filter(ToolFilter::only(vec!["read_file".to_string()]))
+            .await
+            .unwrap();
+        let names = model
+            .declarations()
+            .await
+            .function_declarations
+            .into_iter()
+            .map(|declaration| declaration.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["read_file"]);
+
+        model.set_tool_filter(ToolFilter::all()).await.unwrap();
+        assert_eq!(model.declarations().await.function_declarations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tool_results_are_function_responses() {
+        let model = GeminiModel::new("key".to_string());
+        model
+            .add_tool_result("list_files", &ToolOutput::Json(serde_json::json!(["a.rs"])))
+            .await
+            .unwrap();
+        model
+            .add_tool_result("write_file", &ToolOutput::error("The user declined"))
+            .await
+            .unwrap();
+        model
+            .add_tool_result(
+                "capture_screenshot",
+                &ToolOutput::Binary {
+                    mime: "image/png".to_string(),
+                    data: vec![0x89, b'P', b'N', b'G'],
+                },
+            )
+            .await
+            .unwrap();
+
+        let history = model.conversation.lock().await;
+        assert_eq!(history.contents.len(), 2);
+        let request = serde_json::to_value(&history.contents[1]).unwrap();
+        assert_eq!(
+            request,
+            serde_json::json!({
+                "role": "user",
+                "parts": [
+                    {"functionResponse": {"name": "list_files", "response": {"output": ["a.rs"]}}},
+                    {"functionResponse": {"name": "write_file", "response": {"error": "The user declined"}}},
+                    {"functionResponse": {"name": "capture_screenshot", "response": {"output": "[4 bytes of image/png]"}}},
+                    {"inlineData": {"mimeType": "image/png", "data": "iVBORw=="}},
+                ],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generation_config() {
+        let model = GeminiModel::builder()
+            .api_key("key")
+            .model("gemini-2.5-pro")
+            .temperature(0.5)
+            .max_output_tokens(2048)
+            .stop_sequences(vec!["END".to_string()])
+            .build();
+        assert_eq!(model.model, "gemini-2.5-pro");
+
+        let prompt = serde_json::to_value(model.prompt(vec![], GeminiTool::default())).unwrap();
+        assert_eq!(
+            prompt["generationConfig"],
+            serde_json::json!({"temperature": 0.5, "maxOutputTokens": 2048, "stopSequences": ["END"]})
+        );
+
+        model.set_temperature(Some(0.1)).await.unwrap();
+        let prompt = serde_json::to_value(model.prompt(vec![], GeminiTool::default())).unwrap();
+        assert_eq!(prompt["generationConfig"]["temperature"], 0.1_f32);
+        assert_eq!(prompt["generationConfig"]["maxOutputTokens"], 2048);
+
+        let prompt = GeminiModel::new("key".to_string()).prompt(vec![], GeminiTool::default());
+        assert!(
+            serde_json::to_value(prompt)
+                .unwrap()
+                .get("generationConfig")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_take_events() {
+        let mut buffer = b"data: {\"a\": 1}\n\ndata: {\"b\":\r\n\r\ndata: {\"c\"".to_vec();
+
+        assert_eq!(take_events(&mut buffer), vec!["{\"a\": 1}", "{\"b\":"]);
+        assert_eq!(buffer, b"data: {\"c\"".to_vec());
+
+        buffer.extend(b": 3}\n\n");
+        assert_eq!(take_events(&mut buffer), vec!["{\"c\": 3}"]);
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_parts() {
+        let chunks = [
+            "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"Hel\"}], \"role\": \"model\"}}], \"usageMetadata\": {\"promptTokenCount\": 12, \"candidatesTokenCount\": 1, \"totalTokenCount\": 13}}\n\n",
+            "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"lo\"}], \"role\": \"model\"}, \"finishReason\": \"STOP\"}], \"usageMetadata\": {\"promptTokenCount\": 12, \"candidatesTokenCount\": 2, \"totalTokenCount\": 14}}\n\n",
+        ];
+        let conversation = Arc::new(Mutex::new(ConversationHistory::new(vec![])));
+        let usage = Arc::new(UsageTracker::default());
+        let state = StreamState {
+            bytes: futures::stream::iter(chunks.map(|chunk| Ok(chunk.as_bytes().to_vec()))).boxed(),
+            buffer: vec![],
+            pending: VecDeque::new(),
+            answer: String::new(),
+            calls: vec![],
+            received: false,
+            done: false,
+            conversation: conversation.clone(),
+            usage_metadata: None,
+            usage: usage.clone(),
+        };
+
+        let texts = futures::stream::unfold(state, next_part)
+            .map(|part| part.unwrap().text.unwrap())
+            .collect::<Vec<String>>()
+            .await;
+        assert_eq!(texts, vec!["Hel", "lo"]);
+
+        let history = conversation.lock().await;
+        assert_eq!(history.contents[0].parts[0].text.as_deref(), Some("Hello"));
+        assert_eq!(usage.usage().requests, 1);
+        assert_eq!(usage.usage().total_tokens, 14);
+    }
+
+    #[tokio::test]
+    async fn test_stream_is_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = vec![0; 65536];
+                _ = socket.read(&mut request).await;
+                let response = match served.fetch_add(1, Ordering::SeqCst) {
+                    0 => "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                    _ => {
+                        let body = "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"Hello\"}], \"role\": \"model\"}, \"finishReason\": \"STOP\"}]}\n\n";
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                };
+                _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let endpoint = GeminiEndpoint {
+            name: "local".to_string(),
+            base_url,
+            auth: EndpointAuth::ApiKey,
+        };
+        let model = GeminiModel::new("key".to_string())
+            .with_endpoints(EndpointPool::new(vec![endpoint]).unwrap())
+            .with_retry(RetryConfig {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+            });
+
+        let texts = model
+            .ask_stream("hi")
+            .await
+            .unwrap()
+            .map(|part| part.unwrap().text.unwrap())
+            .collect::<Vec<String>>()
+            .await;
+        assert_eq!(texts, vec!["Hello"]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{num::NonZeroU32, path::PathBuf, sync::OnceLock};
+
+use domain::models::agent::AgentError;
+use llama_cpp_2::{
+    context::params::LlamaContextParams,
+    llama_backend::LlamaBackend,
+    llama_batch::LlamaBatch,
+    model::{LlamaChatMessage, LlamaChatTemplate, LlamaModel, params::LlamaModelParams},
+    sampling::LlamaSampler,
+};
+
+use super::local::{Generator, LocalMessage};
+
+static BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct LlamaCppConfig {
+    pub model_path: PathBuf,
+    // A template name known to llama.cpp (e.g. `chatml`, `llama3`) or a full
+    // template; defaults to the one embedded in the GGUF file.
+    pub template: Option<String>,
+    pub context_size: u32,
+    pub max_tokens: usize,
+    pub temperature: f32,
+}
+
+impl LlamaCppConfig {
+    pub fn new(model_path: PathBuf) -> Self {
+        Self {
+            model_path,
+            template: None,
+            context_size: 8192,
+            max_tokens: 1024,
+            temperature: 0.2,
+        }
+    }
+}
+
+pub struct LlamaCppGenerator {
+    config: LlamaCppConfig,
+    model: LlamaModel,
+    template: LlamaChatTemplate,
+}
+
+impl std::fmt::Debug for LlamaCppGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlamaCppGenerator")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl LlamaCppGenerator {
+    pub fn load(config: LlamaCppConfig) -> Result<Self, AgentError> {
+        let backend = backend()?;
+        let model =
+            LlamaModel::load_from_file(backend, &config.model_path, &LlamaModelParams::default())
+                .map_err(|e| llama_error("loading model", e))?;
+
+        let template = match &config.template {
+            Some(template) => {
+                LlamaChatTemplate::new(template).map_err(|e| llama_error("parsing template", e))?
+            }
+            None => model
+                .chat_template(None)
+                .map_err(|e| llama_error("reading the model's chat template", e))?,
+        };
+
+        Ok(Self {
+            config,
+            model,
+            template,
+        })
+    }
+}
+
+impl Generator for LlamaCppGenerator {
+    fn generate(&self, messages: &[LocalMessage]) -> Result<String, AgentError> {
+        let chat = messages
+            .iter()
+            .map(|message| LlamaChatMessage::new(message.role.clone(), message.content.clone()))
+            .collect::<Result<Vec<LlamaChatMessage>, _>>()
+            .map_err(|e| llama_error("building prompt", e))?;
+        let prompt = self
+            .model
+            .apply_chat_template(&self.template, &chat, true)
+            .map_err(|e| llama_error("applying chat template", e))?;
+
+        let vocab = self.model.vocab();
+        let tokens = vocab.tokenize(prompt.as_bytes(), false, true);
+        let context_size = self.config.context_size as usize;
+        if tokens.len() + self.config.max_tokens > context_size {
+            return Err(AgentError::AgentError(Some(format!(
+                "Prompt of {} tokens does not fit the {} token context",
+                tokens.len(),
+                context_size
+            ))));
+        }
+
+        let params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(self.config.context_size))
+            .with_n_batch(self.config.context_size);
+        let mut context = self
+            .model
+            .new_context(backend()?, params)
+            .map_err(|e| llama_error("creating context", e))?;
+
+        let mut batch = LlamaBatch::new(context_size, 1);
+        let last = tokens.len() as i32 - 1;
+        for (position, token) in (0i32..).zip(tokens.iter().copied()) {
+            batch
+                .add(token, position, &[0], position == last)
+                .map_err(|e| llama_error("batching prompt", e))?;
+        }
+        context
+            .decode(&mut batch)
+            .map_err(|e| llama_error("evaluating prompt", e))?;
+
+        let mut sampler = LlamaSampler::chain_simple([
+            LlamaSampler::temp(self.config.temperature),
+            LlamaSampler::dist(rand_seed()),
+        ]);
+
+        let mut output = vec![];
+        let mut position = tokens.len() as i32;
+        for _ in 0..self.config.max_tokens {
+            let token = sampler.sample(&context, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if vocab.is_eog(token) {
+                break;
+            }
+
+            output.extend(vocab.token_to_piece(token, false, None));
+
+            batch.clear();
+            batch
+                .add(token, position, &[0], true)
+                .map_err(|e| llama_error("batching token", e))?;
+            context
+                .decode(&mut batch)
+                .map_err(|e| llama_error("generating", e))?;
+            position += 1;
+        }
+
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+}
+
+fn backend() -> Result<&'static LlamaBackend, AgentError> {
+    if let Some(backend) = BACKEND.get() {
+        return Ok(backend);
+    }
+
+    let backend = LlamaBackend::init().map_err(|e| llama_error("initializing llama.cpp", e))?;
+    Ok(BACKEND.get_or_init(|| backend))
+}
+
+fn rand_seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or_default()
+}
+
+fn llama_error(action: &str, e: impl std::fmt::Display) -> AgentError {
+    AgentError::AgentError(Some(format!("llama.cpp: error {}: {}", action, e)))
+}
@@ -0,0 +1,337 @@
+use std::{collections::VecDeque, fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{AgentClient, AgentError, AgentRole, Content, FunctionCall, Part, SystemPrompt},
+    tools::{Tool, ToolDecorators, ToolDefinition, ToolFilter},
+};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::prompt::{SYSTEM_PROMPT, SYSTEM_PROMPT_VERSION};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl LocalMessage {
+    pub fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+// An in-process model that turns a chat into text. Generation is blocking
+// and runs on the blocking thread pool.
+pub trait Generator: Debug + Send + Sync + 'static {
+    fn generate(&self, messages: &[LocalMessage]) -> Result<String, AgentError>;
+}
+
+#[derive(Debug, Default)]
+struct Conversation {
+    messages: Vec<LocalMessage>,
+    // Names of emulated tool calls that still need a result.
+    pending_tool_calls: VecDeque<String>,
+}
+
+// Wraps a local generator with a conversation and emulated tool calling for
+// models without native function calling support.
+#[derive(Debug)]
+pub struct LocalModel<G: Generator> {
+    generator: Arc<G>,
+    conversation: Arc<Mutex<Conversation>>,
+    tools: Arc<Mutex<Vec<ToolDefinition>>>,
+    tool_filter: Arc<Mutex<ToolFilter>>,
+    tool_decorators: Arc<Mutex<ToolDecorators>>,
+    system_prompt: Arc<Mutex<SystemPrompt>>,
+}
+
+impl<G: Generator> LocalModel<G> {
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator: Arc::new(generator),
+            conversation: Arc::new(Mutex::new(Conversation::default())),
+            tools: Arc::new(Mutex::new(vec![])),
+            tool_filter: Arc::new(Mutex::new(ToolFilter::all())),
+            tool_decorators: Arc::new(Mutex::new(ToolDecorators::default())),
+            system_prompt: Arc::new(Mutex::new(SystemPrompt {
+                version: SYSTEM_PROMPT_VERSION,
+                text: SYSTEM_PROMPT.to_string(),
+            })),
+        }
+    }
+}
+
+impl Conversation {
+    fn push_user_text(&mut self, text: &str) {
+        self.pending_tool_calls.clear();
+        if !text.trim().is_empty() {
+            self.messages.push(LocalMessage::new("user", text));
+        }
+    }
+
+    // Emulated calls have no message of their own, their results are user
+    // text naming the tool.
+    fn push_tool_text(&mut self, text: &str) {
+        match self.pending_tool_calls.pop_front() {
+            Some(name) => self.messages.push(LocalMessage::new(
+                "user",
+                &format!("Result of the {} tool:\n{}", name, text),
+            )),
+            None => self.push_user_text(text),
+        }
+    }
+}
+
+#[async_trait]
+impl<G: Generator> AgentClient for LocalModel<G> {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        let tools = self
+            .tool_filter
+            .lock()
+            .await
+            .apply(&self.tools.lock().await);
+        let tools = self.tool_decorators.lock().await.apply(tools);
+        let mut system = self.system_prompt.lock().await.text.clone();
+        if !tools.is_empty() {
+            system.push_str("\n\n");
+            system.push_str(&tool_instructions(&tools));
+        }
+
+        let mut messages = vec![LocalMessage::new("system", &system)];
+        {
+            let mut conversation = self.conversation.lock().await;
+            conversation.push_user_text(prompt);
+            messages.extend(conversation.messages.iter().cloned());
+        }
+
+        let generator = self.generator.clone();
+        let text = tokio::task::spawn_blocking(move || generator.generate(&messages))
+            .await
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))??;
+        let text = text.trim();
+
+        if text.is_empty() {
+            return Err(AgentError::AgentError(Some(
+                "No response from local model".to_string(),
+            )));
+        }
+
+        let mut conversation = self.conversation.lock().await;
+        conversation
+            .messages
+            .push(LocalMessage::new("assistant", text));
+
+        let parts = match parse_tool_calls(text) {
+            Some(calls) => {
+                conversation
+                    .pending_tool_calls
+                    .extend(calls.iter().map(|call| call.name.clone()));
+                calls
+                    .into_iter()
+                    .map(|call| Part {
+                        function_call: Some(call),
+                        ..Default::default()
+                    })
+                    .collect()
+            }
+            None => vec![Part::new(text)],
+        };
+
+        Ok(vec![Content::new(parts, "model")])
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.tools.lock().await.push(tool.tool_definition().clone());
+        Ok(())
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        *self.tool_filter.lock().await = filter;
+        Ok(())
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        *self.tool_decorators.lock().await = decorators;
+        Ok(())
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        let mut conversation = self.conversation.lock().await;
+        match role {
+            // The system prompt leads the messages, later instructions go
+            // out as user text.
+            AgentRole::User | AgentRole::System => conversation.push_user_text(prompt),
+            AgentRole::Tool => conversation.push_tool_text(prompt),
+            AgentRole::Model if !prompt.trim().is_empty() => conversation
+                .messages
+                .push(LocalMessage::new("assistant", prompt)),
+            AgentRole::Model => {}
+        }
+
+        Ok(())
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        Some(self.system_prompt.lock().await.clone())
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        *self.system_prompt.lock().await = prompt;
+        Ok(())
+    }
+}
+
+pub fn tool_instructions(tools: &[ToolDefinition]) -> String {
+    let tools = tools
+        .iter()
+        .map(|tool| {
+            format!(
+                "- {}: {}\n  parameters: {}",
+                tool.name,
+                tool.description,
+                serde_json::to_string(&tool.parameters).unwrap_or_default()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!(
+        r#"You can use the following tools:
+{}
+
+To use a tool, reply with nothing but a JSON object such as {{"tool": "<name>", "args": {{...}}}}, or a JSON array of them to use several. The results will be sent back to you. Otherwise reply normally."#,
+        tools
+    )
+}
+
+// The ChatML prompt format used by Qwen and many other small models.
+pub fn chatml(messages: &[LocalMessage]) -> String {
+    let mut prompt = messages
+        .iter()
+        .map(|message| {
+            format!(
+                "<|im_start|>{}\n{}<|im_end|>\n",
+                message.role, message.content
+            )
+        })
+        .collect::<String>();
+    prompt.push_str("<|im_start|>assistant\n");
+    prompt
+}
+
+// Recognizes the emulated `{"tool", "args"}` format as well as the
+// `<tool_call>{"name", "arguments"}</tool_call>` format many local models are
+// trained on.
+pub fn parse_tool_calls(text: &str) -> Option<Vec<FunctionCall>> {
+    let text = text.trim();
+
+    if text.contains("<tool_call>") {
+        let calls = text
+            .split("<tool_call>")
+            .skip(1)
+            .filter_map(|rest| rest.split("</tool_call>").next())
+            .filter_map(|json| serde_json::from_str::<Value>(json.trim()).ok())
+            .filter_map(|value| function_call(&value))
+            .collect::<Vec<FunctionCall>>();
+        return (!calls.is_empty()).then_some(calls);
+    }
+
+    let json = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(text)
+        .trim();
+
+    let calls = match serde_json::from_str::<Value>(json).ok()? {
+        Value::Array(values) => values
+            .iter()
+            .map(function_call)
+            .collect::<Option<Vec<FunctionCall>>>()?,
+        value => vec![function_call(&value)?],
+    };
+
+    (!calls.is_empty()).then_some(calls)
+}
+
+fn function_call(value: &Value) -> Option<FunctionCall> {
+    let name = value.get("tool").or_else(|| value.get("name"))?.as_str()?;
+    let args = value
+        .get("args")
+        .or_else(|| value.get("arguments"))
+        .cloned()
+        .unwrap_or(Value::Object(Default::default()));
+
+    let args = match args {
+        Value::String(args) => serde_json::from_str(&args).ok()?,
+        args => args,
+    };
+
+    Some(FunctionCall {
+        name: name.to_string(),
+        args,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_calls() {
+        let calls = parse_tool_calls(
+            "```json\n{\"tool\": \"read_file\", \"args\": {\"path\": \"a.rs\"}}\n```",
+        )
+        .unwrap();
+        assert_eq!(calls[0].name, "read_file");
+        assert_eq!(calls[0].args["path"], "a.rs");
+
+        let calls = parse_tool_calls(
+            "<tool_call>\n{\"name\": \"list_files\", \"arguments\": \"{\\\"path\\\": \\\"src\\\"}\"}\n</tool_call>",
+        )
+        .unwrap();
+        assert_eq!(calls[0].name, "list_files");
+        assert_eq!(calls[0].args["path"], "src");
+
+        assert!(parse_tool_calls("The answer is {\"tool\": 1}").is_none());
+        assert!(parse_tool_calls("{\"status\": \"ok\"}").is_none());
+    }
+
+    #[derive(Debug)]
+    struct Echo;
+
+    impl Generator for Echo {
+        fn generate(&self, messages: &[LocalMessage]) -> Result<String, AgentError> {
+            match messages.last() {
+                Some(message) if message.content.starts_with("Result of") => {
+                    Ok(format!("done: {}", message.content))
+                }
+                _ => Ok(r#"{"tool": "list_files", "args": {"path": "."}}"#.to_string()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_emulated_tool_round_trip() {
+        let model = LocalModel::new(Echo);
+
+        let contents = model.ask("list files").await.unwrap();
+        let call = contents[0].parts[0].function_call.clone().unwrap();
+        assert_eq!(call.name, "list_files");
+
+        model
+            .add_system_prompt("[\"main.rs\"]", AgentRole::Tool)
+            .await
+            .unwrap();
+        let contents = model.ask("").await.unwrap();
+        assert_eq!(
+            contents[0].parts[0].text.as_deref(),
+            Some("done: Result of the list_files tool:\n[\"main.rs\"]")
+        );
+    }
+}
@@ -1 +1,19 @@
+pub mod attachments;
+pub mod bedrock;
+#[cfg(feature = "candle")]
+pub mod candle;
+pub mod chat_completions;
+pub mod circuit_breaker;
+pub mod context;
+pub mod endpoints;
+pub mod free_tier;
 pub mod gemini;
+#[cfg(feature = "llama-cpp")]
+pub mod llama_cpp;
+pub mod local;
+pub mod ollama;
+pub mod openai;
+pub mod rate_limit;
+pub mod retry;
+pub mod sigv4;
+pub mod watchdog;
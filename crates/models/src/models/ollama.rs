@@ -0,0 +1,436 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{
+        AgentClient, AgentError, AgentRole, Content, FunctionCall, Part, SystemPrompt,
+        approximate_tokens,
+    },
+    tools::{Tool, ToolDecorators, ToolDefinition, ToolFilter},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use super::local::{parse_tool_calls, tool_instructions};
+use crate::prompt::{SYSTEM_PROMPT, SYSTEM_PROMPT_VERSION};
+
+pub const OLLAMA_URL: &str = "http://localhost:11434";
+
+#[derive(Debug, Clone)]
+pub struct OllamaConfig {
+    pub url: String,
+    pub model: String,
+}
+
+impl OllamaConfig {
+    pub fn new(model: &str) -> Self {
+        Self {
+            url: OLLAMA_URL.to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Conversation {
+    messages: Vec<Message>,
+    // Names of tool calls from the last reply that still need a result.
+    pending_tool_calls: VecDeque<String>,
+    // None until the first request with tools tells whether the model
+    // supports native tool calling.
+    native_tools: Option<bool>,
+}
+
+// A model served by a local Ollama server. Tools are passed natively, and
+// emulated through the system prompt for models that don't support them.
+#[derive(Debug)]
+pub struct OllamaModel {
+    config: OllamaConfig,
+    reqwest: Arc<reqwest::Client>,
+    conversation: Arc<Mutex<Conversation>>,
+    tools: Arc<Mutex<Vec<ToolDefinition>>>,
+    tool_filter: Arc<Mutex<ToolFilter>>,
+    tool_decorators: Arc<Mutex<ToolDecorators>>,
+    system_prompt: Arc<Mutex<SystemPrompt>>,
+}
+
+impl OllamaModel {
+    pub fn new(config: OllamaConfig) -> Self {
+        Self {
+            config,
+            reqwest: Arc::new(reqwest::Client::new()),
+            conversation: Arc::new(Mutex::new(Conversation::default())),
+            tools: Arc::new(Mutex::new(vec![])),
+            tool_filter: Arc::new(Mutex::new(ToolFilter::all())),
+            tool_decorators: Arc::new(Mutex::new(ToolDecorators::default())),
+            system_prompt: Arc::new(Mutex::new(SystemPrompt {
+                version: SYSTEM_PROMPT_VERSION,
+                text: SYSTEM_PROMPT.to_string(),
+            })),
+        }
+    }
+
+    async fn request(&self, native_tools: bool) -> ChatRequest {
+        let tools = self
+            .tool_filter
+            .lock()
+            .await
+            .apply(&self.tools.lock().await);
+        let tools = self.tool_decorators.lock().await.apply(tools);
+        let mut system = self.system_prompt.lock().await.text.clone();
+        if !native_tools && !tools.is_empty() {
+            system.push_str("\n\n");
+            system.push_str(&tool_instructions(&tools));
+        }
+
+        let mut messages = vec![Message::text("system", &system)];
+        messages.extend(self.conversation.lock().await.messages.iter().cloned());
+
+        let tools = tools
+            .into_iter()
+            .map(|tool| ChatTool {
+                type_field: "function".to_string(),
+                function: tool,
+            })
+            .collect::<Vec<ChatTool>>();
+
+        ChatRequest {
+            model: self.config.model.clone(),
+            messages,
+            tools: (native_tools && !tools.is_empty()).then_some(tools),
+            stream: false,
+        }
+    }
+
+    async fn send(&self, request: &ChatRequest) -> Result<ChatResponse, OllamaError> {
+        let url = format!("{}/api/chat", self.config.url.trim_end_matches('/'));
+        let response = self
+            .reqwest
+            .post(&url)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| {
+                AgentError::AgentError(Some(format!(
+                    "Error connecting to Ollama at {}, is it running? {}",
+                    self.config.url, e
+                )))
+            })?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+
+        if !status.is_success() {
+            let message = serde_json::from_str::<ErrorResponse>(&text)
+                .map(|error| error.error)
+                .unwrap_or(text);
+            if message.contains("does not support tools") {
+                return Err(OllamaError::ToolsUnsupported);
+            }
+
+            return Err(
+                AgentError::AgentError(Some(format!("ollama {}: {}", status, message))).into(),
+            );
+        }
+
+        serde_json::from_str::<ChatResponse>(&text)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())).into())
+    }
+}
+
+#[derive(Debug)]
+enum OllamaError {
+    Agent(AgentError),
+    ToolsUnsupported,
+}
+
+impl From<AgentError> for OllamaError {
+    fn from(error: AgentError) -> Self {
+        OllamaError::Agent(error)
+    }
+}
+
+impl Conversation {
+    // Calls still waiting for a result are dropped, Ollama does not need every
+    // call answered.
+    fn push_text(&mut self, role: &str, text: &str) {
+        self.pending_tool_calls.clear();
+        if !text.trim().is_empty() {
+            self.messages.push(Message::text(role, text));
+        }
+    }
+
+    fn push_user_text(&mut self, text: &str) {
+        self.push_text("user", text);
+    }
+
+    // Answers the oldest pending call, without one the text goes out as user
+    // text.
+    fn push_tool_text(&mut self, text: &str) {
+        match self.pending_tool_calls.pop_front() {
+            Some(name) if self.native_tools == Some(false) => self.messages.push(Message::text(
+                "user",
+                &format!("Result of the {} tool:\n{}", name, text),
+            )),
+            Some(name) => self.messages.push(Message {
+                role: "tool".to_string(),
+                content: text.to_string(),
+                tool_name: Some(name),
+                ..Default::default()
+            }),
+            None => self.push_user_text(text),
+        }
+    }
+}
+
+#[async_trait]
+impl AgentClient for OllamaModel {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        let native_tools = {
+            let mut conversation = self.conversation.lock().await;
+            conversation.push_user_text(prompt);
+            conversation.native_tools != Some(false)
+        };
+
+        let request = self.request(native_tools).await;
+        let response = match self.send(&request).await {
+            Ok(response) => response,
+            Err(OllamaError::ToolsUnsupported) => {
+                info!(
+                    "[VOO] {} does not support tools, describing them in the prompt instead",
+                    self.config.model
+                );
+                self.conversation.lock().await.native_tools = Some(false);
+                let request = self.request(false).await;
+                match self.send(&request).await {
+                    Ok(response) => response,
+                    Err(OllamaError::Agent(e)) => return Err(e),
+                    Err(OllamaError::ToolsUnsupported) => {
+                        return Err(AgentError::AgentError(Some(
+                            "ollama rejected a request without tools".to_string(),
+                        )));
+                    }
+                }
+            }
+            Err(OllamaError::Agent(e)) => return Err(e),
+        };
+
+        let message = response.message;
+        let mut conversation = self.conversation.lock().await;
+        if request.tools.is_some() {
+            conversation.native_tools = Some(true);
+        }
+
+        let mut parts = vec![];
+        if let Some(thinking) = message.thinking.as_deref().filter(|text| !text.is_empty()) {
+            parts.push(Part::thought(thinking));
+        }
+
+        // Models that support tools still sometimes answer with the call as
+        // JSON text, so that is checked as well.
+        let calls = match &message.tool_calls {
+            Some(calls) if !calls.is_empty() => calls
+                .iter()
+                .map(|call| FunctionCall {
+                    name: call.function.name.clone(),
+                    args: call.function.arguments.clone(),
+                })
+                .collect(),
+            _ if !self.tools.lock().await.is_empty() => {
+                parse_tool_calls(&message.content).unwrap_or_default()
+            }
+            _ => vec![],
+        };
+
+        if calls.is_empty() {
+            let text = message.content.trim();
+            if text.is_empty() && parts.is_empty() {
+                return Err(AgentError::AgentError(Some(
+                    "No response from ollama".to_string(),
+                )));
+            }
+            if !text.is_empty() {
+                parts.push(Part::new(text));
+            }
+        } else {
+            conversation
+                .pending_tool_calls
+                .extend(calls.iter().map(|call| call.name.clone()));
+            parts.extend(calls.into_iter().map(|call| Part {
+                function_call: Some(call),
+                ..Default::default()
+            }));
+        }
+
+        conversation.messages.push(Message {
+            role: "assistant".to_string(),
+            content: message.content,
+            tool_calls: message.tool_calls,
+            ..Default::default()
+        });
+
+        Ok(vec![Content::new(parts, "model")])
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.tools.lock().await.push(tool.tool_definition().clone());
+        Ok(())
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        *self.tool_filter.lock().await = filter;
+        Ok(())
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        *self.tool_decorators.lock().await = decorators;
+        Ok(())
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        let mut conversation = self.conversation.lock().await;
+        match role {
+            AgentRole::User => conversation.push_user_text(prompt),
+            AgentRole::System => conversation.push_text("system", prompt),
+            AgentRole::Tool => conversation.push_tool_text(prompt),
+            AgentRole::Model if !prompt.trim().is_empty() => conversation
+                .messages
+                .push(Message::text("assistant", prompt)),
+            AgentRole::Model => {}
+        }
+
+        Ok(())
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        Some(self.system_prompt.lock().await.clone())
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        *self.system_prompt.lock().await = prompt;
+        Ok(())
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        let request = self.request(true).await;
+        let request = serde_json::to_string(&request).ok()?;
+
+        Some(approximate_tokens(&request) + approximate_tokens(prompt))
+    }
+
+    fn model(&self) -> Option<String> {
+        Some(self.config.model.clone())
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        Some(self.config.url.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<ChatTool>>,
+    stream: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    #[serde(default)]
+    content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_name: Option<String>,
+    // Returned by reasoning models, not sent back.
+    #[serde(default, skip_serializing)]
+    thinking: Option<String>,
+}
+
+impl Message {
+    fn text(role: &str, text: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: text.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ToolCall {
+    function: ToolCallFunction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ToolCallFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatTool {
+    #[serde(rename = "type")]
+    type_field: String,
+    function: ToolDefinition,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    message: Message,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_results() {
+        let response = serde_json::from_value::<ChatResponse>(serde_json::json!({
+            "model": "qwen2.5-coder",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{ "function": { "name": "read_file", "arguments": { "path": "a.rs" } } }]
+            },
+            "done": true
+        }))
+        .unwrap();
+        let calls = response.message.tool_calls.unwrap();
+        assert_eq!(calls[0].function.arguments["path"], "a.rs");
+
+        let mut conversation = Conversation::default();
+        conversation
+            .pending_tool_calls
+            .push_back("read_file".to_string());
+        conversation.push_tool_text("fn main() {}");
+        assert_eq!(conversation.messages[0].role, "tool");
+        assert_eq!(
+            conversation.messages[0].tool_name.as_deref(),
+            Some("read_file")
+        );
+
+        conversation.native_tools = Some(false);
+        conversation
+            .pending_tool_calls
+            .push_back("list_files".to_string());
+        conversation.push_tool_text("[]");
+        assert_eq!(
+            conversation.messages[1],
+            Message::text("user", "Result of the list_files tool:\n[]")
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{AgentClient, AgentError, AgentRole, Content, SystemPrompt},
+    tools::{Tool, ToolDecorators, ToolFilter, ToolOutput},
+    usage::TokenUsage,
+};
+
+use super::chat_completions::{ChatAuth, ChatCompletionsConfig, ChatCompletionsModel, ChatQuirks};
+
+pub const OPENAI_URL: &str = "https://api.openai.com/v1";
+
+// OpenAI's chat completions API. Tool calls are translated from and to the
+// domain FunctionCall by the shared chat completions client.
+#[derive(Debug)]
+pub struct OpenAiModel {
+    inner: ChatCompletionsModel,
+}
+
+impl OpenAiModel {
+    pub fn new(api_key: &str, model: &str) -> Self {
+        Self::compatible(api_key, model, OPENAI_URL)
+    }
+
+    // Any server implementing the OpenAI API, such as vLLM or LM Studio.
+    pub fn compatible(api_key: &str, model: &str, base_url: &str) -> Self {
+        Self {
+            inner: ChatCompletionsModel::new(openai_config(api_key, model, base_url)),
+        }
+    }
+}
+
+fn openai_config(api_key: &str, model: &str, base_url: &str) -> ChatCompletionsConfig {
+    ChatCompletionsConfig {
+        provider: "openai".to_string(),
+        url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+        auth: ChatAuth::Bearer(api_key.to_string()),
+        model: Some(model.to_string()),
+        quirks: ChatQuirks {
+            rate_limit_reset_headers: vec![
+                "x-ratelimit-reset-requests".to_string(),
+                "x-ratelimit-reset-tokens".to_string(),
+            ],
+            ..Default::default()
+        },
+    }
+}
+
+#[async_trait]
+impl AgentClient for OpenAiModel {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        self.inner.ask(prompt).await
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.inner.add_tool(tool).await
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        self.inner.add_system_prompt(prompt, role).await
+    }
+
+    async fn add_tool_result(&self, name: &str, output: &ToolOutput) -> Result<(), AgentError> {
+        self.inner.add_tool_result(name, output).await
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        self.inner.system_prompt().await
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        self.inner.set_system_prompt(prompt).await
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        self.inner.set_tool_filter(filter).await
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        self.inner.set_tool_decorators(decorators).await
+    }
+
+    async fn set_temperature(&self, temperature: Option<f32>) -> Result<(), AgentError> {
+        self.inner.set_temperature(temperature).await
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        self.inner.estimate_tokens(prompt).await
+    }
+
+    fn model(&self) -> Option<String> {
+        self.inner.model()
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        self.inner.usage()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_url() {
+        let config = openai_config("key", "gpt-4o-mini", OPENAI_URL);
+        assert_eq!(config.url, "https://api.openai.com/v1/chat/completions");
+
+        let config = openai_config("key", "qwen", "http://localhost:8000/v1/");
+        assert_eq!(config.url, "http://localhost:8000/v1/chat/completions");
+    }
+}
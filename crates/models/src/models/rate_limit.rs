@@ -0,0 +1,238 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{AgentClient, AgentError, AgentRole, Content, PartStream, SystemPrompt},
+    attachment::Attachment,
+    health::{ProviderHealth, RateLimitStatus},
+    tools::{Tool, ToolDecorators, ToolFilter, ToolOutput},
+    usage::TokenUsage,
+};
+use tokio::sync::Mutex;
+use tracing::info;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: usize,
+    // Requests that may go out back to back before the rate applies.
+    pub burst: usize,
+}
+
+impl RateLimitConfig {
+    pub fn per_minute(requests_per_minute: usize) -> Self {
+        Self {
+            requests_per_minute,
+            burst: requests_per_minute,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// A token bucket holding up to `burst` requests, refilled at the configured
+// rate. Requests wait for a token instead of failing, so a tool loop slows
+// down rather than running into the provider's limit.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                tokens: config.burst.max(1) as f64,
+                refilled_at: Instant::now(),
+            }),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    fn per_second(&self) -> f64 {
+        self.config.requests_per_minute.max(1) as f64 / MINUTE.as_secs_f64()
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let elapsed = bucket.refilled_at.elapsed().as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * self.per_second()).min(self.config.burst.max(1) as f64);
+        bucket.refilled_at = Instant::now();
+    }
+
+    // Waits until a token is available, then takes it.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                self.refill(&mut bucket);
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second())
+            };
+
+            info!(
+                "[VOO] rate limit: waiting {}s for the next request",
+                wait.as_secs() + 1
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    // The tokens taken from the bucket count as the requests of this minute.
+    pub async fn status(&self) -> RateLimitStatus {
+        let mut bucket = self.bucket.lock().await;
+        self.refill(&mut bucket);
+
+        RateLimitStatus {
+            requests: (self.config.burst.max(1) as f64 - bucket.tokens).ceil() as usize,
+            requests_per_minute: self.config.requests_per_minute,
+            exhausted_secs: None,
+        }
+    }
+}
+
+// Takes a token from a limiter before every request to the wrapped client.
+// The limiter can be shared by several clients of the same provider.
+#[derive(Debug)]
+pub struct RateLimited {
+    inner: Arc<dyn AgentClient>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimited {
+    pub fn new(inner: impl AgentClient + 'static, limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            limiter,
+        }
+    }
+}
+
+#[async_trait]
+impl AgentClient for RateLimited {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        self.limiter.acquire().await;
+        self.inner.ask(prompt).await
+    }
+
+    async fn ask_stream(&self, prompt: &str) -> Result<PartStream, AgentError> {
+        self.limiter.acquire().await;
+        self.inner.ask_stream(prompt).await
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.inner.add_tool(tool).await
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        self.inner.add_system_prompt(prompt, role).await
+    }
+
+    async fn add_tool_result(&self, name: &str, output: &ToolOutput) -> Result<(), AgentError> {
+        self.inner.add_tool_result(name, output).await
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        self.inner.system_prompt().await
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        self.inner.set_system_prompt(prompt).await
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        self.inner.set_tool_filter(filter).await
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        self.inner.set_tool_decorators(decorators).await
+    }
+
+    async fn set_temperature(&self, temperature: Option<f32>) -> Result<(), AgentError> {
+        self.inner.set_temperature(temperature).await
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        self.inner.estimate_tokens(prompt).await
+    }
+
+    async fn attach(&self, name: &str, mime: &str, data: &[u8]) -> Result<Attachment, AgentError> {
+        self.inner.attach(name, mime, data).await
+    }
+
+    async fn attachments(&self) -> Vec<Attachment> {
+        self.inner.attachments().await
+    }
+
+    async fn detach(&self, id: &str) -> Result<Attachment, AgentError> {
+        self.inner.detach(id).await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    fn model(&self) -> Option<String> {
+        self.inner.model()
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    // The client's own limits, e.g. the free tier quota, say more than the
+    // limiter does.
+    async fn rate_limit(&self) -> Option<RateLimitStatus> {
+        match self.inner.rate_limit().await {
+            Some(status) => Some(status),
+            None => Some(self.limiter.status().await),
+        }
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        self.inner.usage()
+    }
+
+    async fn health(&self) -> Option<ProviderHealth> {
+        self.inner.health().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waits_once_the_burst_is_used() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: 600,
+            burst: 2,
+        });
+
+        let started = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert_eq!(limiter.status().await.requests, 2);
+
+        limiter.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(80));
+    }
+}
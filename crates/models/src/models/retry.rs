@@ -0,0 +1,69 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    // Including the first attempt, 1 turns retrying off.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryConfig {
+    // The wait before the given retry, counted from 1. It doubles with every
+    // retry up to `max_delay`, and a random half of it is taken off so clients
+    // that failed together don't retry together.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_delay);
+
+        delay / 2 + delay.mul_f64(jitter() / 2.0)
+    }
+}
+
+// A number in [0, 1), random enough to spread out retries.
+fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let config = RetryConfig::default();
+
+        for retry in 1..=10 {
+            let full = config
+                .base_delay
+                .saturating_mul(2u32.pow(retry - 1))
+                .min(config.max_delay);
+            let delay = config.delay(retry);
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+        assert!(config.delay(40) <= config.max_delay);
+    }
+}
@@ -0,0 +1,467 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{
+        AgentClient, AgentError, AgentRole, Content, FunctionCall, Part, PartStream, SystemPrompt,
+    },
+    attachment::Attachment,
+    health::{ProviderHealth, RateLimitStatus},
+    tools::{Tool, ToolDecorators, ToolFilter, ToolOutput},
+    usage::TokenUsage,
+};
+use futures::StreamExt;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    // Nudges sent before giving up on a reply.
+    pub max_retries: u32,
+    // Identical consecutive tool calls tolerated within a turn.
+    pub max_repeated_calls: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            max_repeated_calls: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Verdict {
+    Ok,
+    Empty,
+    RepeatedAnswer,
+    RepeatedCall(String),
+}
+
+impl Verdict {
+    fn nudge(&self) -> String {
+        match self {
+            Verdict::Ok => String::new(),
+            Verdict::Empty => {
+                "Your last reply was empty. Reply with an answer or a tool call.".to_string()
+            }
+            Verdict::RepeatedAnswer => "You replied with exactly the same message as before. Don't repeat yourself, continue with the task or explain what is blocking you.".to_string(),
+            Verdict::RepeatedCall(name) => format!(
+                "You already called {} with these arguments and got its result. Use that result or try something different instead of calling it again.",
+                name
+            ),
+        }
+    }
+
+    fn error(&self) -> AgentError {
+        let message = match self {
+            Verdict::Ok => "The model reply was rejected".to_string(),
+            Verdict::Empty => "The model kept replying with empty messages".to_string(),
+            Verdict::RepeatedAnswer => "The model kept repeating the same reply".to_string(),
+            Verdict::RepeatedCall(name) => {
+                format!("The model kept calling {} with the same arguments", name)
+            }
+        };
+
+        AgentError::AgentError(Some(message))
+    }
+}
+
+// Tracks the replies of the current turn. A non-empty prompt starts a new
+// turn, an empty one continues it after tool results.
+#[derive(Debug, Default)]
+struct TurnState {
+    last_answer: Option<String>,
+    last_calls: Vec<FunctionCall>,
+    repeated_calls: u32,
+}
+
+impl TurnState {
+    fn judge(&mut self, config: &WatchdogConfig, answer: &str, calls: &[FunctionCall]) -> Verdict {
+        let answer = answer.trim();
+        if answer.is_empty() && calls.is_empty() {
+            return Verdict::Empty;
+        }
+
+        if !calls.is_empty() && calls == self.last_calls.as_slice() {
+            self.repeated_calls += 1;
+            if self.repeated_calls >= config.max_repeated_calls {
+                return Verdict::RepeatedCall(calls[0].name.clone());
+            }
+        } else {
+            self.repeated_calls = 1;
+            self.last_calls = calls.to_vec();
+        }
+
+        if calls.is_empty() && self.last_answer.as_deref() == Some(answer) {
+            return Verdict::RepeatedAnswer;
+        }
+        if !answer.is_empty() {
+            self.last_answer = Some(answer.to_string());
+        }
+
+        Verdict::Ok
+    }
+}
+
+// Re-prompts the model when it replies with nothing or repeats itself, and
+// only surfaces an error once the retries are used up.
+#[derive(Debug)]
+pub struct Watchdog {
+    inner: Arc<dyn AgentClient>,
+    config: WatchdogConfig,
+    turn: Arc<Mutex<TurnState>>,
+}
+
+impl Watchdog {
+    pub fn new(inner: impl AgentClient + 'static) -> Self {
+        Self::with_config(inner, WatchdogConfig::default())
+    }
+
+    pub fn with_config(inner: impl AgentClient + 'static, config: WatchdogConfig) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            config,
+            turn: Arc::new(Mutex::new(TurnState::default())),
+        }
+    }
+
+    async fn start(&self, prompt: &str) {
+        if !prompt.is_empty() {
+            *self.turn.lock().await = TurnState::default();
+        }
+    }
+}
+
+// Clients report an empty candidate list as an error.
+fn is_empty_reply(error: &AgentError) -> bool {
+    matches!(error, AgentError::AgentError(Some(message)) if message.starts_with("No response from"))
+}
+
+fn summarize(parts: &[Part]) -> (String, Vec<FunctionCall>) {
+    let answer = parts
+        .iter()
+        .filter_map(|part| part.answer().cloned())
+        .collect::<String>();
+    let calls = parts
+        .iter()
+        .filter_map(|part| part.function_call.clone())
+        .collect();
+
+    (answer, calls)
+}
+
+#[async_trait]
+impl AgentClient for Watchdog {
+    async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+        self.start(prompt).await;
+
+        let mut prompt = prompt.to_string();
+        let mut retries = 0;
+        loop {
+            let verdict = match self.inner.ask(&prompt).await {
+                Ok(contents) => {
+                    let parts = contents
+                        .iter()
+                        .flat_map(|content| content.parts.clone())
+                        .collect::<Vec<Part>>();
+                    let (answer, calls) = summarize(&parts);
+
+                    match self.turn.lock().await.judge(&self.config, &answer, &calls) {
+                        Verdict::Ok => return Ok(contents),
+                        verdict => verdict,
+                    }
+                }
+                Err(e) if is_empty_reply(&e) => Verdict::Empty,
+                Err(e) => return Err(e),
+            };
+
+            if retries >= self.config.max_retries {
+                return Err(verdict.error());
+            }
+
+            warn!("[VOO] watchdog: {:?} reply, nudging the model", verdict);
+            retries += 1;
+            prompt = verdict.nudge();
+        }
+    }
+
+    // Parts are held back while the reply could still turn out to be empty
+    // or a repeat of the previous one, and streamed as soon as it can't.
+    async fn ask_stream(&self, prompt: &str) -> Result<PartStream, AgentError> {
+        self.start(prompt).await;
+
+        let state = StreamState {
+            inner: self.inner.clone(),
+            config: self.config.clone(),
+            turn: self.turn.clone(),
+            stream: Some(self.inner.ask_stream(prompt).await?),
+            held: VecDeque::new(),
+            reply: vec![],
+            releasing: false,
+            retries: 0,
+        };
+
+        Ok(Box::pin(futures::stream::unfold(state, next_part)))
+    }
+
+    async fn add_tool(&self, tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+        self.inner.add_tool(tool).await
+    }
+
+    async fn add_system_prompt(&self, prompt: &str, role: AgentRole) -> Result<(), AgentError> {
+        self.inner.add_system_prompt(prompt, role).await
+    }
+
+    async fn add_tool_result(&self, name: &str, output: &ToolOutput) -> Result<(), AgentError> {
+        self.inner.add_tool_result(name, output).await
+    }
+
+    async fn system_prompt(&self) -> Option<SystemPrompt> {
+        self.inner.system_prompt().await
+    }
+
+    async fn set_system_prompt(&self, prompt: SystemPrompt) -> Result<(), AgentError> {
+        self.inner.set_system_prompt(prompt).await
+    }
+
+    async fn set_tool_filter(&self, filter: ToolFilter) -> Result<(), AgentError> {
+        self.inner.set_tool_filter(filter).await
+    }
+
+    async fn set_tool_decorators(&self, decorators: ToolDecorators) -> Result<(), AgentError> {
+        self.inner.set_tool_decorators(decorators).await
+    }
+
+    async fn set_temperature(&self, temperature: Option<f32>) -> Result<(), AgentError> {
+        self.inner.set_temperature(temperature).await
+    }
+
+    async fn estimate_tokens(&self, prompt: &str) -> Option<u64> {
+        self.inner.estimate_tokens(prompt).await
+    }
+
+    async fn attach(&self, name: &str, mime: &str, data: &[u8]) -> Result<Attachment, AgentError> {
+        self.inner.attach(name, mime, data).await
+    }
+
+    async fn attachments(&self) -> Vec<Attachment> {
+        self.inner.attachments().await
+    }
+
+    async fn detach(&self, id: &str) -> Result<Attachment, AgentError> {
+        self.inner.detach(id).await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    fn model(&self) -> Option<String> {
+        self.inner.model()
+    }
+
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    async fn rate_limit(&self) -> Option<RateLimitStatus> {
+        self.inner.rate_limit().await
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        self.inner.usage()
+    }
+
+    async fn health(&self) -> Option<ProviderHealth> {
+        self.inner.health().await
+    }
+}
+
+struct StreamState {
+    inner: Arc<dyn AgentClient>,
+    config: WatchdogConfig,
+    turn: Arc<Mutex<TurnState>>,
+    stream: Option<PartStream>,
+    held: VecDeque<Part>,
+    // Every part of the current attempt, released or not.
+    reply: Vec<Part>,
+    releasing: bool,
+    retries: u32,
+}
+
+// Whether the held parts can no longer turn into a rejected reply.
+async fn can_release(held: &VecDeque<Part>, turn: &Mutex<TurnState>) -> bool {
+    let parts = held.iter().cloned().collect::<Vec<Part>>();
+    let (answer, calls) = summarize(&parts);
+    if !calls.is_empty() || answer.trim().is_empty() {
+        return false;
+    }
+
+    match turn.lock().await.last_answer.as_deref() {
+        Some(last) => !last.starts_with(answer.trim()),
+        None => true,
+    }
+}
+
+async fn next_part(mut state: StreamState) -> Option<(Result<Part, AgentError>, StreamState)> {
+    loop {
+        if state.releasing
+            && let Some(part) = state.held.pop_front()
+        {
+            return Some((Ok(part), state));
+        }
+
+        let stream = state.stream.as_mut()?;
+        match stream.next().await {
+            Some(Ok(part)) => {
+                state.reply.push(part.clone());
+                state.held.push_back(part);
+                if !state.releasing && can_release(&state.held, &state.turn).await {
+                    state.releasing = true;
+                }
+                continue;
+            }
+            Some(Err(e)) if !is_empty_reply(&e) => {
+                state.stream = None;
+                return Some((Err(e), state));
+            }
+            Some(Err(_)) | None => {}
+        }
+
+        // The reply is complete, judge whatever was held back.
+        state.stream = None;
+        let (answer, calls) = summarize(&state.reply);
+        let verdict = state
+            .turn
+            .lock()
+            .await
+            .judge(&state.config, &answer, &calls);
+
+        if verdict == Verdict::Ok {
+            state.releasing = true;
+            continue;
+        }
+        if state.retries >= state.config.max_retries {
+            return Some((Err(verdict.error()), state));
+        }
+
+        warn!("[VOO] watchdog: {:?} reply, nudging the model", verdict);
+        state.retries += 1;
+        state.held.clear();
+        state.reply.clear();
+        state.releasing = false;
+        match state.inner.ask_stream(&verdict.nudge()).await {
+            Ok(stream) => state.stream = Some(stream),
+            Err(e) => return Some((Err(e), state)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct ScriptedClient {
+        replies: std::sync::Mutex<VecDeque<Vec<Part>>>,
+        prompts: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl ScriptedClient {
+        fn new(replies: Vec<Vec<Part>>) -> (Arc<std::sync::Mutex<Vec<String>>>, Self) {
+            let prompts = Arc::new(std::sync::Mutex::new(vec![]));
+            let client = Self {
+                replies: std::sync::Mutex::new(replies.into()),
+                prompts: prompts.clone(),
+            };
+
+            (prompts, client)
+        }
+    }
+
+    #[async_trait]
+    impl AgentClient for ScriptedClient {
+        async fn ask(&self, prompt: &str) -> Result<Vec<Content>, AgentError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            match self.replies.lock().unwrap().pop_front() {
+                Some(parts) if !parts.is_empty() => Ok(vec![Content::new(parts, "model")]),
+                _ => Err(AgentError::AgentError(Some(
+                    "No response from script".to_string(),
+                ))),
+            }
+        }
+
+        async fn add_tool(&self, _tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        async fn add_system_prompt(
+            &self,
+            _prompt: &str,
+            _role: AgentRole,
+        ) -> Result<(), AgentError> {
+            Ok(())
+        }
+    }
+
+    fn call() -> Part {
+        Part {
+            function_call: Some(FunctionCall {
+                name: "read_file".to_string(),
+                args: json!({"path": "main.rs"}),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nudges_empty_reply() {
+        let (prompts, client) = ScriptedClient::new(vec![vec![], vec![Part::new("done")]]);
+        let watchdog = Watchdog::new(client);
+
+        let contents = watchdog.ask("hi").await.unwrap();
+        assert_eq!(contents[0].parts[0].text.as_deref(), Some("done"));
+        assert_eq!(prompts.lock().unwrap()[1], Verdict::Empty.nudge());
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_looping_calls() {
+        let replies = (0..5).map(|_| vec![call()]).collect();
+        let (_, client) = ScriptedClient::new(replies);
+        let watchdog = Watchdog::new(client);
+
+        assert!(watchdog.ask("read main.rs").await.is_ok());
+        assert!(watchdog.ask("").await.is_ok());
+        let error = watchdog.ask("").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "AgentError: The model kept calling read_file with the same arguments"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_retries_repeated_answer() {
+        let replies = vec![
+            vec![Part::new("same")],
+            vec![Part::new("same")],
+            vec![Part::new("different")],
+        ];
+        let (prompts, client) = ScriptedClient::new(replies);
+        let watchdog = Watchdog::new(client);
+
+        assert!(watchdog.ask("hi").await.is_ok());
+        let texts = watchdog
+            .ask_stream("")
+            .await
+            .unwrap()
+            .map(|part| part.unwrap().text.unwrap())
+            .collect::<Vec<String>>()
+            .await;
+        assert_eq!(texts, vec!["different"]);
+        assert_eq!(prompts.lock().unwrap()[2], Verdict::RepeatedAnswer.nudge());
+    }
+}
@@ -0,0 +1,260 @@
+use std::{fmt::Display, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::tools::{
+    OutputProcessor, Tool, ToolContext, ToolDefinition, ToolError, ToolOutput,
+};
+pub use domain::models::tools::{normalize, strip_ansi};
+use serde_json::Value;
+
+#[derive(Debug)]
+pub struct StripAnsi;
+
+impl OutputProcessor for StripAnsi {
+    fn name(&self) -> &str {
+        "strip_ansi"
+    }
+
+    fn process(&self, output: String) -> String {
+        strip_ansi(&output)
+    }
+}
+
+#[derive(Debug)]
+pub struct Normalize;
+
+impl OutputProcessor for Normalize {
+    fn name(&self) -> &str {
+        "normalize"
+    }
+
+    fn process(&self, output: String) -> String {
+        normalize(&output)
+    }
+}
+
+#[derive(Debug)]
+pub struct HeadBytes {
+    pub limit: usize,
+}
+
+impl OutputProcessor for HeadBytes {
+    fn name(&self) -> &str {
+        "head"
+    }
+
+    fn process(&self, mut output: String) -> String {
+        if output.len() <= self.limit {
+            return output;
+        }
+
+        let mut end = self.limit;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let truncated = output.len() - end;
+        output.truncate(end);
+        format!("{}\n[truncated {} bytes]", output, truncated)
+    }
+}
+
+#[derive(Debug)]
+pub struct PrettyJson;
+
+impl OutputProcessor for PrettyJson {
+    fn name(&self) -> &str {
+        "pretty_json"
+    }
+
+    fn process(&self, output: String) -> String {
+        serde_json::from_str::<Value>(&output)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .unwrap_or(output)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcessorChain {
+    processors: Vec<Arc<dyn OutputProcessor>>,
+}
+
+impl ProcessorChain {
+    pub fn new(processors: Vec<Arc<dyn OutputProcessor>>) -> Self {
+        Self { processors }
+    }
+
+    // Parses a comma separated spec such as `strip_ansi,head:4000,pretty_json`.
+    pub fn parse(spec: &str) -> Result<Self, ToolError> {
+        let mut processors: Vec<Arc<dyn OutputProcessor>> = vec![];
+
+        for step in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|step| !step.is_empty())
+        {
+            let (name, arg) = step.split_once(':').unwrap_or((step, ""));
+
+            match name {
+                "strip_ansi" => processors.push(Arc::new(StripAnsi)),
+                "normalize" => processors.push(Arc::new(Normalize)),
+                "pretty_json" => processors.push(Arc::new(PrettyJson)),
+                "head" => {
+                    let limit = arg.parse::<usize>().map_err(|_| {
+                        ToolError::ToolError(format!("Invalid head limit: {:?}", arg))
+                    })?;
+                    processors.push(Arc::new(HeadBytes { limit }));
+                }
+                _ => {
+                    return Err(ToolError::ToolError(format!(
+                        "Unknown output processor: {}",
+                        name
+                    )));
+                }
+            }
+        }
+
+        Ok(Self { processors })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    pub fn apply(&self, output: String) -> String {
+        self.processors
+            .iter()
+            .fold(output, |output, processor| processor.process(output))
+    }
+
+    // Processors work on text, so Json only passes through untouched when
+    // there are none.
+    pub fn apply_output(&self, output: ToolOutput) -> ToolOutput {
+        if self.is_empty() {
+            return output;
+        }
+        output.map_text(|text| self.apply(text))
+    }
+}
+
+impl Display for ProcessorChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self
+            .processors
+            .iter()
+            .map(|processor| processor.name())
+            .collect::<Vec<&str>>();
+
+        write!(f, "{}", names.join(" | "))
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcessedTool {
+    inner: Arc<dyn Tool>,
+    chain: ProcessorChain,
+}
+
+impl ProcessedTool {
+    pub fn new(inner: Arc<dyn Tool>, chain: ProcessorChain) -> Self {
+        Self { inner, chain }
+    }
+}
+
+impl Display for ProcessedTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\nOutput processors: {}", self.inner, self.chain)
+    }
+}
+
+#[async_trait]
+impl Tool for ProcessedTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let output = self.inner.exec(input).await?;
+        Ok(self.chain.apply_output(output))
+    }
+
+    async fn exec_with_context(
+        &self,
+        input: Value,
+        context: &ToolContext,
+    ) -> Result<ToolOutput, ToolError> {
+        let output = self.inner.exec_with_context(input, context).await?;
+        Ok(self.chain.apply_output(output))
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        self.inner.tool_definition()
+    }
+}
+
+pub fn normalize_bytes(input: &[u8]) -> String {
+    normalize(&String::from_utf8_lossy(input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        let colored = "\x1b[1;31merror\x1b[0m: \x1b]0;title\x07failed\x1b(B";
+        assert_eq!(strip_ansi(colored), "error: failed");
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize_bytes(b"\x1b[32mok\x1b[0m\r\nnext\rline\x00\xff\tend"),
+            "ok\nnext\nline\u{fffd}\tend"
+        );
+    }
+
+    #[test]
+    fn test_head_respects_char_boundaries() {
+        let head = HeadBytes { limit: 4 };
+        assert_eq!(
+            head.process("héllo".to_string()),
+            "hél\n[truncated 2 bytes]"
+        );
+        assert_eq!(head.process("hi".to_string()), "hi");
+    }
+
+    #[test]
+    fn test_parse_chain() {
+        let chain = ProcessorChain::parse("strip_ansi, pretty_json,head:12").unwrap();
+        assert_eq!(chain.to_string(), "strip_ansi | pretty_json | head");
+        assert_eq!(
+            chain.apply("\x1b[32m{\"a\":1}\x1b[0m".to_string()),
+            "{\n  \"a\": 1\n}"
+        );
+
+        assert!(ProcessorChain::parse("head:lots").is_err());
+        assert!(ProcessorChain::parse("rot13").is_err());
+    }
+
+    #[test]
+    fn test_apply_output() {
+        let json = ToolOutput::Json(serde_json::json!({"a": 1}));
+        assert_eq!(ProcessorChain::default().apply_output(json.clone()), json);
+
+        let chain = ProcessorChain::parse("pretty_json").unwrap();
+        assert_eq!(
+            chain.apply_output(json),
+            ToolOutput::text("{\n  \"a\": 1\n}")
+        );
+        let binary = ToolOutput::Binary {
+            mime: "image/png".to_string(),
+            data: vec![0x89],
+        };
+        assert_eq!(chain.apply_output(binary.clone()), binary);
+    }
+}
@@ -0,0 +1,453 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneMode {
+    #[default]
+    None,
+    Comments,
+    Bodies,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    CLike,
+    Hash,
+    Python,
+    Unknown,
+}
+
+impl Syntax {
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        match extension {
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "go" | "java" | "kt" | "swift"
+            | "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "scala" | "dart" | "php" => {
+                Syntax::CLike
+            }
+            "py" | "pyi" => Syntax::Python,
+            "sh" | "bash" | "zsh" | "rb" | "toml" | "yaml" | "yml" | "pl" | "r" => Syntax::Hash,
+            _ => Syntax::Unknown,
+        }
+    }
+}
+
+pub fn prune(source: &str, path: &Path, mode: PruneMode, keep: &[String]) -> String {
+    let syntax = Syntax::from_path(path);
+
+    match mode {
+        PruneMode::None => source.to_string(),
+        PruneMode::Comments => drop_blank_lines(&strip_comments(source, syntax)),
+        PruneMode::Bodies => {
+            let stripped = strip_comments(source, syntax);
+            let elided = match syntax {
+                Syntax::CLike => elide_brace_bodies(&stripped, keep),
+                Syntax::Python => elide_python_bodies(&stripped, keep),
+                Syntax::Hash | Syntax::Unknown => stripped,
+            };
+
+            drop_blank_lines(&elided)
+        }
+    }
+}
+
+pub fn strip_comments(source: &str, syntax: Syntax) -> String {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut output = String::with_capacity(source.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        if let Some(end) = literal_end(&chars, i, syntax) {
+            output.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        match syntax {
+            Syntax::CLike if c == '/' && next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            Syntax::CLike if c == '/' && next == Some('*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i = (i + 2).min(chars.len());
+            }
+            Syntax::Hash | Syntax::Python
+                if c == '#' && (i == 0 || chars[i - 1].is_whitespace()) =>
+            {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            _ => {
+                output.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    output
+}
+
+pub fn drop_blank_lines(source: &str) -> String {
+    source
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+fn elide_brace_bodies(source: &str, keep: &[String]) -> String {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut output = String::with_capacity(source.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        if let Some(end) = literal_end(&chars, i, Syntax::CLike) {
+            output.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        let Some((name, body_start)) = function_at(&chars, i) else {
+            output.push(chars[i]);
+            i += 1;
+            continue;
+        };
+
+        let Some(body_end) = matching_brace(&chars, body_start) else {
+            output.extend(&chars[i..]);
+            break;
+        };
+
+        output.extend(&chars[i..body_start]);
+        if keep.contains(&name) {
+            output.extend(&chars[body_start..body_end]);
+        } else {
+            output.push_str("{ ... }");
+        }
+        i = body_end;
+    }
+
+    output
+}
+
+// Returns the function name and the index of its opening brace when a named
+// function definition with a body starts at `start`.
+fn function_at(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if start > 0 && is_ident(chars[start - 1]) {
+        return None;
+    }
+
+    let keyword = ["function", "func", "fn"].into_iter().find(|keyword| {
+        let end = start + keyword.len();
+        end < chars.len()
+            && chars[start..end].iter().copied().eq(keyword.chars())
+            && !is_ident(chars[end])
+    })?;
+
+    let mut i = skip_whitespace(chars, start + keyword.len());
+
+    if keyword == "func" && chars.get(i) == Some(&'(') {
+        i = skip_whitespace(chars, matching(chars, i, '(', ')')?);
+    }
+
+    let name_start = i;
+    while i < chars.len() && is_ident(chars[i]) {
+        i += 1;
+    }
+    if i == name_start {
+        return None;
+    }
+    let name = chars[name_start..i].iter().collect::<String>();
+
+    let mut depth = 0i32;
+    while i < chars.len() {
+        if let Some(end) = literal_end(chars, i, Syntax::CLike) {
+            i = end;
+            continue;
+        }
+
+        match chars[i] {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '{' if depth == 0 => return Some((name, i)),
+            ';' | '}' if depth == 0 => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+fn matching_brace(chars: &[char], open: usize) -> Option<usize> {
+    matching(chars, open, '{', '}')
+}
+
+// Returns the index just past the delimiter closing the one at `open`.
+fn matching(chars: &[char], open: usize, left: char, right: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+
+    while i < chars.len() {
+        if let Some(end) = literal_end(chars, i, Syntax::CLike) {
+            i = end;
+            continue;
+        }
+
+        if chars[i] == left {
+            depth += 1;
+        } else if chars[i] == right {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i + 1);
+            }
+        }
+        i += 1;
+    }
+
+    None
+}
+
+fn elide_python_bodies(source: &str, keep: &[String]) -> String {
+    let lines = source.lines().collect::<Vec<&str>>();
+    let mut output = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let signature = trimmed.strip_prefix("async ").unwrap_or(trimmed);
+
+        let Some(name) = signature.strip_prefix("def ").map(|rest| {
+            rest.trim_start()
+                .chars()
+                .take_while(|c| is_ident(*c))
+                .collect::<String>()
+        }) else {
+            output.push(line.to_string());
+            i += 1;
+            continue;
+        };
+
+        while i < lines.len() && !lines[i].trim_end().ends_with(':') {
+            output.push(lines[i].to_string());
+            i += 1;
+        }
+        if i < lines.len() {
+            output.push(lines[i].to_string());
+            i += 1;
+        }
+
+        let body_start = i;
+        while i < lines.len() {
+            let body_line = lines[i];
+            let body_indent = body_line.len() - body_line.trim_start().len();
+            if !body_line.trim().is_empty() && body_indent <= indent {
+                break;
+            }
+            i += 1;
+        }
+
+        if keep.contains(&name) {
+            output.extend(lines[body_start..i].iter().map(|line| line.to_string()));
+        } else if i > body_start {
+            output.push(format!("{}    ...", " ".repeat(indent)));
+        }
+    }
+
+    output.join("\n")
+}
+
+// Returns the index just past a string or character literal starting at `start`.
+fn literal_end(chars: &[char], start: usize, syntax: Syntax) -> Option<usize> {
+    let c = chars[start];
+    let at = |offset: usize| chars.get(start + offset).copied();
+
+    match (syntax, c) {
+        (Syntax::Python, '"' | '\'') if at(1) == Some(c) && at(2) == Some(c) => {
+            let mut i = start + 3;
+            while i + 2 < chars.len() {
+                if chars[i] == c && chars[i + 1] == c && chars[i + 2] == c {
+                    return Some(i + 3);
+                }
+                i += 1;
+            }
+            Some(chars.len())
+        }
+        (Syntax::CLike, 'r') if start == 0 || !is_ident(chars[start - 1]) => {
+            let hashes = chars[start + 1..].iter().take_while(|c| **c == '#').count();
+            if at(hashes + 1) != Some('"') {
+                return None;
+            }
+
+            let mut i = start + hashes + 2;
+            while i < chars.len() {
+                if chars[i] == '"'
+                    && chars[i + 1..]
+                        .iter()
+                        .take(hashes)
+                        .filter(|c| **c == '#')
+                        .count()
+                        == hashes
+                {
+                    return Some(i + 1 + hashes);
+                }
+                i += 1;
+            }
+            Some(chars.len())
+        }
+        (Syntax::CLike, '\'') => {
+            if at(1) == Some('\\') {
+                (start + 2..(start + 12).min(chars.len()))
+                    .find(|i| chars[*i] == '\'')
+                    .map(|i| i + 1)
+            } else if at(2) == Some('\'') {
+                Some(start + 3)
+            } else {
+                None
+            }
+        }
+        (_, '"') | (Syntax::CLike, '`') | (Syntax::Hash | Syntax::Python, '\'') => {
+            let mut i = start + 1;
+            while i < chars.len() {
+                if chars[i] == '\\' {
+                    i += 2;
+                    continue;
+                }
+                if chars[i] == c {
+                    return Some(i + 1);
+                }
+                i += 1;
+            }
+            Some(chars.len())
+        }
+        _ => None,
+    }
+}
+
+fn skip_whitespace(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_rust_comments() {
+        let source = r###"
+// leading comment
+fn main() {
+    let url = "http://example.com"; // trailing
+    let raw = r#"// not a comment"#;
+    /* block
+       comment */
+    let c = '/';
+}
+"###;
+
+        let pruned = prune(source, Path::new("main.rs"), PruneMode::Comments, &[]);
+        assert_eq!(
+            pruned,
+            r###"fn main() {
+    let url = "http://example.com";
+    let raw = r#"// not a comment"#;
+    let c = '/';
+}"###
+        );
+    }
+
+    #[test]
+    fn test_elide_rust_bodies() {
+        let source = r#"
+impl Foo {
+    pub fn keep_me<'a>(&'a self) -> &'a str {
+        "{"
+    }
+
+    fn drop_me(&self) -> Result<(), Error> where Self: Sized {
+        if true { return Ok(()); }
+        Ok(())
+    }
+}
+
+trait Bar {
+    fn declared(&self);
+}
+"#;
+
+        let pruned = prune(
+            source,
+            Path::new("lib.rs"),
+            PruneMode::Bodies,
+            &["keep_me".to_string()],
+        );
+        assert_eq!(
+            pruned,
+            r#"impl Foo {
+    pub fn keep_me<'a>(&'a self) -> &'a str {
+        "{"
+    }
+    fn drop_me(&self) -> Result<(), Error> where Self: Sized { ... }
+}
+trait Bar {
+    fn declared(&self);
+}"#
+        );
+    }
+
+    #[test]
+    fn test_elide_python_bodies() {
+        let source = r#"
+# module comment
+class Foo:
+    def keep(self):
+        return 1
+
+    async def drop(self, a,
+                   b):
+        """docstring # not a comment"""
+        return a + b
+"#;
+
+        let pruned = prune(
+            source,
+            Path::new("foo.py"),
+            PruneMode::Bodies,
+            &["keep".to_string()],
+        );
+        assert_eq!(
+            pruned,
+            r#"class Foo:
+    def keep(self):
+        return 1
+    async def drop(self, a,
+                   b):
+        ..."#
+        );
+    }
+}
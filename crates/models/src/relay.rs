@@ -0,0 +1,186 @@
+use std::time::{Duration, Instant};
+
+use domain::models::agent::AgentError;
+use serde::{Deserialize, Serialize};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const TIMEOUT: Duration = Duration::from_secs(600);
+// Each request on its own, the job may take up to TIMEOUT to finish.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Another voo running `voo serve`. A prompt becomes a job there, which is
+// polled until the remote agent answered it.
+#[derive(Debug, Clone)]
+pub struct RemoteAgent {
+    url: String,
+    token: Option<String>,
+    poll_interval: Duration,
+    timeout: Duration,
+    reqwest: reqwest::Client,
+}
+
+#[derive(Debug, Serialize)]
+struct JobRequest<'a> {
+    prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteJob {
+    id: String,
+    status: String,
+    result: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteError {
+    error: String,
+}
+
+impl RemoteAgent {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: None,
+            poll_interval: POLL_INTERVAL,
+            timeout: TIMEOUT,
+            reqwest: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    // Sent as the bearer token, for servers with users.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // Returns the answer of the remote agent. With a session the prompt
+    // continues that named session on the remote, so follow-up questions keep
+    // their context.
+    pub async fn ask(&self, prompt: &str, session: Option<&str>) -> Result<String, AgentError> {
+        let request = self
+            .reqwest
+            .post(format!("{}/jobs", self.url))
+            .json(&JobRequest { prompt, session });
+        let mut job = self.send(request).await?;
+
+        let started = Instant::now();
+        loop {
+            if let Some(outcome) = outcome(&job) {
+                return outcome;
+            }
+            if started.elapsed() > self.timeout {
+                return Err(AgentError::Network(format!(
+                    "{} did not answer job {} within {}s",
+                    self.url,
+                    job.id,
+                    self.timeout.as_secs()
+                )));
+            }
+
+            tokio::time::sleep(self.poll_interval).await;
+            job = self
+                .send(self.reqwest.get(format!("{}/jobs/{}", self.url, job.id)))
+                .await?;
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<RemoteJob, AgentError> {
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AgentError::Network(format!("{}: {}", self.url, e)))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| AgentError::Network(format!("{}: {}", self.url, e)))?;
+
+        if !status.is_success() {
+            let message = serde_json::from_str::<RemoteError>(&text)
+                .map(|error| error.error)
+                .unwrap_or(text);
+            let message = format!("{} {}: {}", self.url, status, message);
+            return Err(match status.as_u16() {
+                401 | 403 => AgentError::InvalidApiKey(Some(message)),
+                429 => AgentError::QuotaExhausted(Some(message)),
+                500.. => AgentError::Network(message),
+                _ => AgentError::InvalidRequest(Some(message)),
+            });
+        }
+
+        serde_json::from_str::<RemoteJob>(&text).map_err(|e| AgentError::Deserialization {
+            body: text.clone(),
+            message: e.to_string(),
+        })
+    }
+}
+
+// The answer of a finished job, none while it is queued or running.
+fn outcome(job: &RemoteJob) -> Option<Result<String, AgentError>> {
+    match job.status.as_str() {
+        "succeeded" => Some(Ok(job.result.clone().unwrap_or_default())),
+        "failed" => Some(Err(AgentError::AgentError(Some(format!(
+            "the remote agent failed job {}: {}",
+            job.id,
+            job.error.as_deref().unwrap_or("unknown error")
+        ))))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(status: &str, result: Option<&str>, error: Option<&str>) -> RemoteJob {
+        RemoteJob {
+            id: "42".to_string(),
+            status: status.to_string(),
+            result: result.map(str::to_string),
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert!(outcome(&job("queued", None, None)).is_none());
+        assert!(outcome(&job("running", None, None)).is_none());
+        assert_eq!(
+            outcome(&job("succeeded", Some("done"), None))
+                .unwrap()
+                .unwrap(),
+            "done"
+        );
+        assert_eq!(
+            outcome(&job("failed", None, Some("boom")))
+                .unwrap()
+                .unwrap_err()
+                .to_string(),
+            "AgentError: the remote agent failed job 42: boom"
+        );
+
+        assert_eq!(
+            RemoteAgent::new("http://gpu-box:8080/").url(),
+            "http://gpu-box:8080"
+        );
+    }
+}
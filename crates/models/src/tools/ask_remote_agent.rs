@@ -0,0 +1,144 @@
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::write_file::{ask, can_ask};
+use crate::relay::RemoteAgent;
+
+#[derive(Debug)]
+pub struct AskRemoteAgentTool {
+    name: String,
+    description: String,
+    input_schema: AskRemoteAgentInput,
+    tool_definition: ToolDefinition,
+    remote: RemoteAgent,
+    auto_approve: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AskRemoteAgentInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub prompt: String,
+    pub session: Option<String>,
+}
+
+impl Display for AskRemoteAgentTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl AskRemoteAgentTool {
+    pub fn new(name: &str, description: &str, remote: RemoteAgent) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: AskRemoteAgentInput {
+                input: Input {
+                    prompt: "".to_string(),
+                    session: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "prompt": {
+                                "type": "string",
+                                "description": "The complete request for the remote agent, including any file contents or context it needs"
+                            },
+                            "session": {
+                                "type": "string",
+                                "description": "A session name to continue on the remote agent, so follow-up requests keep the earlier context"
+                            }
+                        },
+                        "required": ["prompt"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            remote,
+            auto_approve: false,
+        }
+    }
+
+    // Sends prompts without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn input_schema(&self) -> &AskRemoteAgentInput {
+        &self.input_schema
+    }
+
+    // The prompt carries whatever code the model put into it to another host.
+    fn confirm(&self, prompt: &str) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        println!("{}", prompt);
+        ask(&format!("send this to {}?", self.remote.url()))
+    }
+}
+
+#[async_trait]
+impl Tool for AskRemoteAgentTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        if input.prompt.trim().is_empty() {
+            return Err(ToolError::ToolError("prompt must not be empty".to_string()));
+        }
+        if !self.confirm(&input.prompt)? {
+            return Ok(ToolOutput::error(format!(
+                "The user declined sending the prompt to {}, it was not sent",
+                self.remote.url()
+            )));
+        }
+
+        let answer = self
+            .remote
+            .ask(&input.prompt, input.session.as_deref())
+            .await
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        Ok(ToolOutput::text(answer))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
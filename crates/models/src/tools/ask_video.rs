@@ -0,0 +1,173 @@
+use std::{fmt::Display, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::{
+    tools::{Tool, ToolDefinition, ToolError, ToolOutput},
+    video::VideoAnalyst,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::video::youtube_url;
+
+#[derive(Debug)]
+pub struct AskVideoTool {
+    name: String,
+    description: String,
+    input_schema: AskVideoInput,
+    tool_definition: ToolDefinition,
+    analyst: Arc<dyn VideoAnalyst>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AskVideoInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub url: String,
+    pub question: String,
+}
+
+impl Display for AskVideoTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl AskVideoTool {
+    pub fn new(name: &str, description: &str, analyst: Arc<dyn VideoAnalyst>) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: AskVideoInput {
+                input: Input {
+                    url: "".to_string(),
+                    question: "".to_string(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "url": {
+                                "type": "string",
+                                "description": "The YouTube URL of the video, e.g. https://www.youtube.com/watch?v=9hE5-98ZeCg"
+                            },
+                            "question": {
+                                "type": "string",
+                                "description": "What to find out about the video, e.g. 'summarize the talk' or 'which command is run at 12:30?'"
+                            }
+                        },
+                        "required": ["url", "question"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            analyst,
+        }
+    }
+
+    pub fn input_schema(&self) -> &AskVideoInput {
+        &self.input_schema
+    }
+}
+
+#[async_trait]
+impl Tool for AskVideoTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        if input.question.trim().is_empty() {
+            return Err(ToolError::ToolError(
+                "question must not be empty".to_string(),
+            ));
+        }
+        if !youtube_url(&input.url) {
+            return Ok(ToolOutput::error(format!(
+                "{} is not a YouTube video, only YouTube URLs can be watched",
+                input.url
+            )));
+        }
+
+        match self.analyst.ask(&input.url, &input.question).await {
+            Ok(answer) => Ok(ToolOutput::Text(answer)),
+            Err(e) => Ok(ToolOutput::error(format!(
+                "{} could not watch {}: {}",
+                self.analyst.model(),
+                input.url,
+                e
+            ))),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::agent::AgentError;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct EchoAnalyst;
+
+    #[async_trait]
+    impl VideoAnalyst for EchoAnalyst {
+        fn model(&self) -> String {
+            "echo".to_string()
+        }
+
+        async fn ask(&self, url: &str, question: &str) -> Result<String, AgentError> {
+            Ok(format!("{} {}", url, question))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ask_video() {
+        let tool = AskVideoTool::new("ask_video", "", Arc::new(EchoAnalyst));
+
+        let output = tool
+            .exec(json!({ "url": "https://youtu.be/abc", "question": "summarize" }))
+            .await
+            .unwrap();
+        assert_eq!(output, ToolOutput::text("https://youtu.be/abc summarize"));
+
+        let output = tool
+            .exec(json!({ "url": "https://example.com/talk.mp4", "question": "summarize" }))
+            .await
+            .unwrap();
+        assert!(output.is_error());
+        assert!(
+            tool.exec(json!({ "url": "https://youtu.be/abc", "question": " " }))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolExample, ToolOutput};
+use globset::Glob;
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::{
+    formatter::Formatters,
+    locks::{EditLock, EditLocks, announce},
+    read_policy::ReadPolicy,
+    write_file::{ask, can_ask, colorize, unified_diff},
+};
+
+#[derive(Debug)]
+pub struct BulkReplaceTool {
+    name: String,
+    description: String,
+    input_schema: BulkReplaceInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+    formatters: Option<Formatters>,
+    locks: Option<EditLocks>,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkReplaceInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub pattern: String,
+    pub replacement: String,
+    pub glob: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+#[derive(Debug)]
+struct Change {
+    path: PathBuf,
+    display: String,
+    matches: usize,
+    new: String,
+    diff: String,
+}
+
+impl Display for BulkReplaceTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl BulkReplaceTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: BulkReplaceInput {
+                input: Input {
+                    pattern: "".to_string(),
+                    replacement: "".to_string(),
+                    glob: "".to_string(),
+                    path: default_path(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "The regular expression to find"
+                            },
+                            "replacement": {
+                                "type": "string",
+                                "description": "The replacement, $1 or ${name} refer to capture groups"
+                            },
+                            "glob": {
+                                "type": "string",
+                                "description": "Only files whose path matches this glob are changed, e.g. **/*.rs"
+                            },
+                            "path": {
+                                "type": "string",
+                                "description": "The directory to search, defaults to the current directory"
+                            }
+                        },
+                        "required": ["pattern", "replacement", "glob"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![ToolExample::new(
+                    json!({"pattern": r"log::(\w+)!", "replacement": "tracing::$1!", "glob": "src/**/*.rs"}),
+                    "Replaced matches in 2 files:\nsrc/main.rs (4 matches)\nsrc/server.rs (1 matches)",
+                )],
+            },
+            auto_approve: false,
+            formatters: None,
+            locks: None,
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    // Applies changes without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn with_formatters(mut self, formatters: Formatters) -> Self {
+        self.formatters = Some(formatters);
+        self
+    }
+
+    pub fn with_locks(mut self, locks: EditLocks) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    // Files the read rules deny are skipped, their matches would tell what
+    // they contain.
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &BulkReplaceInput {
+        &self.input_schema
+    }
+
+    fn confirm(&self, changes: &[Change], locks: &[EditLock]) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        announce(locks);
+        let diff = changes
+            .iter()
+            .map(|change| colorize(&change.diff))
+            .collect::<Vec<String>>()
+            .join("\n");
+        println!("{}", diff);
+
+        let matches = changes.iter().map(|change| change.matches).sum::<usize>();
+        ask(&format!(
+            "replace {} matches in {} files?",
+            matches,
+            changes.len()
+        ))
+    }
+}
+
+fn find_changes(input: &Input, read_policy: &ReadPolicy) -> Result<Vec<Change>, ToolError> {
+    let regex = Regex::new(&input.pattern)
+        .map_err(|e| ToolError::ToolError(format!("Invalid pattern: {}", e)))?;
+    let glob = Glob::new(&input.glob)
+        .map_err(|e| ToolError::ToolError(format!("Invalid glob: {}", e)))?
+        .compile_matcher();
+    let root = PathBuf::from(&input.path);
+
+    let mut changes = vec![];
+    for entry in WalkBuilder::new(&root).build().flatten() {
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+        if !glob.is_match(relative) || read_policy.check(entry.path()).is_err() {
+            continue;
+        }
+
+        // Binary and non UTF-8 files are skipped.
+        let Ok(old) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let matches = regex.find_iter(&old).count();
+        if matches == 0 {
+            continue;
+        }
+
+        let new = regex.replace_all(&old, input.replacement.as_str());
+        if new == old {
+            continue;
+        }
+
+        let display = entry.path().to_string_lossy().to_string();
+        changes.push(Change {
+            path: entry.path().to_path_buf(),
+            diff: unified_diff(&display, &old, &new),
+            display,
+            matches,
+            new: new.into_owned(),
+        });
+    }
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+#[async_trait]
+impl Tool for BulkReplaceTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let changes = find_changes(&input, &self.read_policy)?;
+        if changes.is_empty() {
+            return Ok(ToolOutput::text(format!(
+                "No matches for {} in files matching {}",
+                input.pattern, input.glob
+            )));
+        }
+
+        let locks = match &self.locks {
+            Some(locks) => locks.lock_all(changes.iter().map(|change| change.path.as_path()))?,
+            None => vec![],
+        };
+        if !self.confirm(&changes, &locks)? {
+            return Ok(ToolOutput::error(
+                "The user declined the replacement, no files were changed",
+            ));
+        }
+
+        let mut summary = vec![];
+        for change in &changes {
+            std::fs::write(&change.path, &change.new)
+                .map_err(|e| ToolError::ToolError(format!("{}: {}", change.display, e)))?;
+            summary.push(format!("{} ({} matches)", change.display, change.matches));
+            if let Some(formatters) = &self.formatters
+                && let Some(note) = formatters.format(&change.path).await
+            {
+                summary.push(note);
+            }
+        }
+
+        Ok(ToolOutput::text(format!(
+            "Replaced matches in {} files:\n{}",
+            changes.len(),
+            summary.join("\n")
+        )))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_auto_approved_replace() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/a.rs"), "let old_name = old_name(1);\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "old_name\n").unwrap();
+        let tool = BulkReplaceTool::new("bulk_replace", "").with_auto_approve(true);
+
+        let output = tool
+            .exec(json!({
+                "pattern": r"old_(\w+)",
+                "replacement": "new_$1",
+                "glob": "*.rs",
+                "path": dir.to_string_lossy(),
+            }))
+            .await
+            .unwrap();
+        assert!(
+            output
+                .to_string()
+                .starts_with("Replaced matches in 1 files:")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("src/a.rs")).unwrap(),
+            "let new_name = new_name(1);\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("notes.md")).unwrap(),
+            "old_name\n"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt::Display;
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::process::Command;
+
+use super::write_file::{ask, can_ask};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+// Replaced with the path of a temporary file, commands without it print the
+// image to stdout.
+const FILE: &str = "{file}";
+
+#[cfg(target_os = "macos")]
+const SCREEN: &[&[&str]] = &[&["screencapture", "-x", "-t", "png", FILE]];
+#[cfg(not(target_os = "macos"))]
+const SCREEN: &[&[&str]] = &[
+    &["grim", FILE],
+    &["gnome-screenshot", "-f", FILE],
+    &["spectacle", "-b", "-n", "-o", FILE],
+    &["scrot", "-o", FILE],
+    &["import", "-window", "root", FILE],
+];
+
+#[cfg(target_os = "macos")]
+const CLIPBOARD: &[&[&str]] = &[&["pngpaste", "-"]];
+#[cfg(not(target_os = "macos"))]
+const CLIPBOARD: &[&[&str]] = &[
+    &["wl-paste", "--type", "image/png"],
+    &["xclip", "-selection", "clipboard", "-t", "image/png", "-o"],
+];
+
+#[derive(Debug)]
+pub struct CaptureScreenshotTool {
+    name: String,
+    description: String,
+    input_schema: CaptureScreenshotInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaptureScreenshotInput {
+    input: Input,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    #[default]
+    Screen,
+    Clipboard,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    #[serde(default)]
+    pub source: Source,
+}
+
+impl Display for CaptureScreenshotTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl CaptureScreenshotTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: CaptureScreenshotInput {
+                input: Input {
+                    source: Source::Screen,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "source": {
+                                "type": "string",
+                                "enum": ["screen", "clipboard"],
+                                "description": "Capture the whole screen, or take the image the user copied to the clipboard. Defaults to screen"
+                            }
+                        },
+                        "required": []
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            auto_approve: false,
+        }
+    }
+
+    // Captures without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn input_schema(&self) -> &CaptureScreenshotInput {
+        &self.input_schema
+    }
+
+    // Whatever is on the screen goes to the model's provider.
+    fn confirm(&self, source: Source) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        ask(match source {
+            Source::Screen => "send a screenshot of your screen to the model?",
+            Source::Clipboard => "send the image on your clipboard to the model?",
+        })
+    }
+}
+
+// Tries each command in turn until one produces a PNG. Commands that are not
+// installed are skipped, the error of the last one that ran is kept.
+pub async fn capture(commands: &[&[&str]]) -> Result<Vec<u8>, String> {
+    let mut failure = None;
+    for command in commands {
+        let Some((program, args)) = command.split_first() else {
+            continue;
+        };
+        // The directory, and the image in it, are removed when it is dropped.
+        let dir = match args.contains(&FILE) {
+            true => Some(tempfile::tempdir().map_err(|e| e.to_string())?),
+            false => None,
+        };
+        let file = dir.as_ref().map(|dir| dir.path().join("screenshot.png"));
+        let args = args
+            .iter()
+            .map(|arg| match (&file, *arg == FILE) {
+                (Some(file), true) => file.to_string_lossy().to_string(),
+                _ => arg.to_string(),
+            })
+            .collect::<Vec<String>>();
+
+        let output = match Command::new(program).args(&args).output().await {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                failure = Some(format!("Error running {}: {}", program, e));
+                continue;
+            }
+        };
+        let data = match &file {
+            Some(file) => std::fs::read(file).unwrap_or_default(),
+            None => output.stdout,
+        };
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            failure = Some(format!("{} failed: {}", program, stderr));
+        } else if !data.starts_with(PNG_SIGNATURE) {
+            failure = Some(format!("{} did not produce a PNG image", program));
+        } else {
+            return Ok(data);
+        }
+    }
+
+    Err(failure.unwrap_or_else(|| {
+        let programs = commands
+            .iter()
+            .filter_map(|command| command.first())
+            .copied()
+            .collect::<Vec<&str>>();
+        format!("None of {} is installed", programs.join(", "))
+    }))
+}
+
+#[async_trait]
+impl Tool for CaptureScreenshotTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        if !self.confirm(input.source)? {
+            return Ok(ToolOutput::error(
+                "The user declined the capture, no image was taken",
+            ));
+        }
+
+        let commands = match input.source {
+            Source::Screen => SCREEN,
+            Source::Clipboard => CLIPBOARD,
+        };
+        match capture(commands).await {
+            Ok(data) => Ok(ToolOutput::Binary {
+                mime: "image/png".to_string(),
+                data,
+            }),
+            Err(reason) => Ok(ToolOutput::error(format!(
+                "No image was captured: {}",
+                reason
+            ))),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_capture() {
+        let png = r"printf '\211PNG\r\n\032\nimage'";
+        let to_file = format!("{} > \"$0\"", png);
+        let commands: &[&[&str]] = &[
+            &["voo-missing-screenshot-program", FILE],
+            &["sh", "-c", to_file.as_str(), FILE],
+        ];
+        let data = capture(commands).await.unwrap();
+        assert_eq!(data, b"\x89PNG\r\n\x1a\nimage");
+
+        let commands: &[&[&str]] = &[&["sh", "-c", png]];
+        assert!(capture(commands).await.unwrap().starts_with(PNG_SIGNATURE));
+
+        let commands: &[&[&str]] = &[&["sh", "-c", "echo no image >&2; exit 1"]];
+        assert_eq!(capture(commands).await.unwrap_err(), "sh failed: no image");
+        let commands: &[&[&str]] = &[&["voo-missing-screenshot-program"]];
+        assert_eq!(
+            capture(commands).await.unwrap_err(),
+            "None of voo-missing-screenshot-program is installed"
+        );
+    }
+}
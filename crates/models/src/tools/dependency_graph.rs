@@ -0,0 +1,684 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+    sync::LazyLock,
+};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::read_policy::ReadPolicy;
+
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+static RUST_USE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+([^;]+);").unwrap());
+static RUST_MOD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;").unwrap());
+static JS_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)['"]([^'"]+)['"]"#).unwrap()
+});
+static PY_FROM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*from\s+(\.*)([\w.]*)\s+import[ \t]+\(?([\w \t,*]+)").unwrap()
+});
+static PY_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*import\s+([\w., ]+)").unwrap());
+
+#[derive(Debug)]
+pub struct DependencyGraphTool {
+    name: String,
+    description: String,
+    input_schema: DependencyGraphInput,
+    tool_definition: ToolDefinition,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyGraphInput {
+    input: Input,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Dependents,
+    Dependencies,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default)]
+    pub transitive: bool,
+}
+
+impl Display for DependencyGraphTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl DependencyGraphTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: DependencyGraphInput {
+                input: Input {
+                    path: "".to_string(),
+                    direction: Direction::Dependents,
+                    transitive: false,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The relative path of the file"
+                            },
+                            "direction": {
+                                "type": "string",
+                                "enum": ["dependents", "dependencies"],
+                                "description": "'dependents' lists the files importing the file, 'dependencies' the files it imports. Defaults to 'dependents'."
+                            },
+                            "transitive": {
+                                "type": "boolean",
+                                "description": "Also follow the imports of the listed files. Defaults to false."
+                            }
+                        },
+                        "required": ["path"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &DependencyGraphInput {
+        &self.input_schema
+    }
+}
+
+// Which workspace files import which, from Rust `use`/`mod`, JS/TS imports
+// and Python imports. Imports of external packages are left out.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    edges: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+#[derive(Debug)]
+struct RustCrate {
+    name: String,
+    src: PathBuf,
+}
+
+impl DependencyGraph {
+    pub fn build(root: &Path, read_policy: &ReadPolicy) -> Self {
+        let mut sources = vec![];
+        for entry in WalkBuilder::new(root).build().flatten() {
+            if !entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+            {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(root) else {
+                continue;
+            };
+
+            let relevant = relative
+                .file_name()
+                .is_some_and(|name| name == "Cargo.toml")
+                || extension(relative).is_some_and(|extension| {
+                    extension == "rs" || extension == "py" || JS_EXTENSIONS.contains(&extension)
+                });
+            let content = match relevant && read_policy.check(entry.path()).is_ok() {
+                true => std::fs::read_to_string(entry.path()).unwrap_or_default(),
+                false => String::new(),
+            };
+            sources.push((relative.to_path_buf(), content));
+        }
+
+        Self::from_sources(&sources)
+    }
+
+    fn from_sources(sources: &[(PathBuf, String)]) -> Self {
+        let files = sources
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<HashSet<PathBuf>>();
+        let crates = sources
+            .iter()
+            .filter(|(path, _)| path.file_name().is_some_and(|name| name == "Cargo.toml"))
+            .filter_map(|(path, manifest)| {
+                Some(RustCrate {
+                    name: package_name(manifest)?.replace('-', "_"),
+                    src: path.parent()?.join("src"),
+                })
+            })
+            .collect::<Vec<RustCrate>>();
+
+        let mut graph = Self::default();
+        for (path, content) in sources {
+            let dependencies = match extension(path) {
+                Some("rs") => rust_dependencies(path, content, &crates, &files),
+                Some("py") => python_dependencies(path, content, &files),
+                Some(extension) if JS_EXTENSIONS.contains(&extension) => {
+                    js_dependencies(path, content, &files)
+                }
+                _ => continue,
+            };
+
+            let dependencies = dependencies
+                .into_iter()
+                .filter(|dependency| dependency != path)
+                .collect::<BTreeSet<PathBuf>>();
+            graph.edges.insert(path.clone(), dependencies);
+        }
+
+        graph
+    }
+
+    pub fn dependencies(&self, path: &Path, transitive: bool) -> BTreeSet<PathBuf> {
+        self.walk(path, transitive, |file| {
+            self.edges
+                .get(file)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect()
+        })
+    }
+
+    pub fn dependents(&self, path: &Path, transitive: bool) -> BTreeSet<PathBuf> {
+        self.walk(path, transitive, |file| {
+            self.edges
+                .iter()
+                .filter(|(_, dependencies)| dependencies.contains(file))
+                .map(|(dependent, _)| dependent.clone())
+                .collect()
+        })
+    }
+
+    fn walk(
+        &self,
+        path: &Path,
+        transitive: bool,
+        next: impl Fn(&Path) -> Vec<PathBuf>,
+    ) -> BTreeSet<PathBuf> {
+        let mut found = BTreeSet::new();
+        let mut queue = next(path);
+        while let Some(file) = queue.pop() {
+            if file == path || !found.insert(file.clone()) {
+                continue;
+            }
+            if transitive {
+                queue.extend(next(&file));
+            }
+        }
+        found
+    }
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
+
+fn package_name(manifest: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=')
+            && key.trim() == "name"
+        {
+            return Some(value.trim().trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+// Resolves `a/./b/../c` without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn rust_dependencies(
+    path: &Path,
+    content: &str,
+    crates: &[RustCrate],
+    files: &HashSet<PathBuf>,
+) -> Vec<PathBuf> {
+    let Some(krate) = crates
+        .iter()
+        .filter(|krate| path.starts_with(&krate.src))
+        .max_by_key(|krate| krate.src.components().count())
+    else {
+        return vec![];
+    };
+    let module = module_path(path, &krate.src);
+    let code = content
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let mut dependencies = vec![];
+    for captures in RUST_MOD.captures_iter(&code) {
+        let mut child = module.clone();
+        child.push(captures[1].to_string());
+        dependencies.extend(module_file(&krate.src, &child, files));
+    }
+    for captures in RUST_USE.captures_iter(&code) {
+        for segments in expand_use(&captures[1]) {
+            dependencies.extend(resolve_use(&segments, krate, &module, crates, files));
+        }
+    }
+
+    dependencies
+}
+
+// The module path of a file in a crate, e.g. src/models/mod.rs is [models].
+fn module_path(path: &Path, src: &Path) -> Vec<String> {
+    let Ok(relative) = path.strip_prefix(src) else {
+        return vec![];
+    };
+
+    let mut segments = relative
+        .with_extension("")
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    if segments.len() == 1 && matches!(segments[0].as_str(), "lib" | "main") {
+        return vec![];
+    }
+    if segments.last().is_some_and(|segment| segment == "mod") {
+        segments.pop();
+    }
+    segments
+}
+
+fn module_file(src: &Path, module: &[String], files: &HashSet<PathBuf>) -> Option<PathBuf> {
+    let candidates = match module {
+        [] => vec![src.join("lib.rs"), src.join("main.rs")],
+        module => {
+            let dir = module.iter().collect::<PathBuf>();
+            vec![
+                src.join(&dir).with_extension("rs"),
+                src.join(&dir).join("mod.rs"),
+            ]
+        }
+    };
+
+    candidates
+        .into_iter()
+        .find(|candidate| files.contains(candidate))
+}
+
+// Expands a use tree such as `crate::a::{b, c::{d, e}}` into its paths.
+fn expand_use(tree: &str) -> Vec<Vec<String>> {
+    let tree = tree.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let tree = tree.trim().trim_start_matches("::");
+
+    let Some(open) = tree.find('{') else {
+        let path = tree.split(" as ").next().unwrap_or_default();
+        let segments = path
+            .split("::")
+            .map(str::trim)
+            .filter(|segment| !segment.is_empty() && *segment != "*")
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        return vec![segments];
+    };
+
+    let prefix = tree[..open]
+        .split("::")
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    let close = tree
+        .rfind('}')
+        .filter(|close| *close > open)
+        .unwrap_or(tree.len());
+    let inner = &tree[open + 1..close];
+
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    items
+        .into_iter()
+        .filter(|item| !item.trim().is_empty())
+        .flat_map(expand_use)
+        .map(|segments| {
+            let mut path = prefix.clone();
+            path.extend(segments.into_iter().filter(|segment| segment != "self"));
+            path
+        })
+        .collect()
+}
+
+fn resolve_use(
+    segments: &[String],
+    krate: &RustCrate,
+    module: &[String],
+    crates: &[RustCrate],
+    files: &HashSet<PathBuf>,
+) -> Option<PathBuf> {
+    let (first, rest) = segments.split_first()?;
+
+    let (src, base, rest) = match first.as_str() {
+        "crate" => (&krate.src, vec![], rest),
+        "self" => (&krate.src, module.to_vec(), rest),
+        "super" => {
+            let mut base = module.to_vec();
+            base.pop();
+            let mut rest = rest;
+            while rest.first().is_some_and(|segment| segment == "super") {
+                base.pop();
+                rest = &rest[1..];
+            }
+            (&krate.src, base, rest)
+        }
+        name => match crates.iter().find(|other| other.name == name) {
+            Some(other) => (&other.src, vec![], rest),
+            // A child module of the current module, since 2018 paths may
+            // start with one.
+            None => {
+                let mut child = module.to_vec();
+                child.push(name.to_string());
+                module_file(&krate.src, &child, files)?;
+                (&krate.src, child, rest)
+            }
+        },
+    };
+
+    // The deepest module the path reaches; the rest are items inside it.
+    for depth in (0..=rest.len()).rev() {
+        let mut candidate = base.clone();
+        candidate.extend(rest[..depth].iter().cloned());
+        if let Some(file) = module_file(src, &candidate, files) {
+            return Some(file);
+        }
+    }
+    None
+}
+
+fn js_dependencies(path: &Path, content: &str, files: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    JS_IMPORT
+        .captures_iter(content)
+        .filter(|captures| captures[1].starts_with('.'))
+        .filter_map(|captures| {
+            let target = normalize(&dir.join(&captures[1]));
+            // TypeScript imports `./a.js` for a file named a.ts.
+            let stem = match extension(&target) {
+                Some(extension) if JS_EXTENSIONS.contains(&extension) => target.with_extension(""),
+                _ => target.clone(),
+            };
+
+            std::iter::once(target.clone())
+                .chain(
+                    JS_EXTENSIONS
+                        .iter()
+                        .map(|extension| stem.with_extension(extension)),
+                )
+                .chain(
+                    JS_EXTENSIONS
+                        .iter()
+                        .map(|extension| target.join("index").with_extension(extension)),
+                )
+                .find(|candidate| files.contains(candidate))
+        })
+        .collect()
+}
+
+fn python_dependencies(path: &Path, content: &str, files: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let module_file = |base: &Path, module: &str| {
+        let module = base.join(module.split('.').collect::<PathBuf>());
+        [module.with_extension("py"), module.join("__init__.py")]
+            .into_iter()
+            .find(|candidate| files.contains(candidate))
+    };
+    let absolute = |module: &str| {
+        module_file(Path::new(""), module).or_else(|| module_file(Path::new("src"), module))
+    };
+
+    let mut dependencies = vec![];
+    for captures in PY_FROM.captures_iter(content) {
+        let dots = captures[1].len();
+        let module = &captures[2];
+        let names = captures[3]
+            .split(',')
+            .map(|name| name.split_whitespace().next().unwrap_or_default())
+            .filter(|name| !name.is_empty() && *name != "*")
+            .collect::<Vec<&str>>();
+
+        if dots == 0 {
+            dependencies.extend(absolute(module));
+            continue;
+        }
+
+        let mut base = dir.to_path_buf();
+        for _ in 1..dots {
+            base.pop();
+        }
+        match module.is_empty() {
+            // `from . import a` imports sibling modules.
+            true => dependencies.extend(names.iter().filter_map(|name| module_file(&base, name))),
+            false => dependencies.extend(module_file(&base, module)),
+        }
+    }
+    for captures in PY_IMPORT.captures_iter(content) {
+        for module in captures[1].split(',') {
+            let module = module.split_whitespace().next().unwrap_or_default();
+            dependencies.extend(absolute(module));
+        }
+    }
+
+    dependencies
+}
+
+#[async_trait]
+impl Tool for DependencyGraphTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let path = normalize(Path::new(&input.path));
+
+        let root = std::env::current_dir().map_err(|e| ToolError::ToolError(e.to_string()))?;
+        if !root.join(&path).is_file() {
+            return Err(ToolError::FileNotFound(input.path));
+        }
+
+        let read_policy = self.read_policy.clone();
+        let graph =
+            tokio::task::spawn_blocking(move || DependencyGraph::build(&root, &read_policy))
+                .await
+                .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let (files, label) = match input.direction {
+            Direction::Dependents => (graph.dependents(&path, input.transitive), "depend on"),
+            Direction::Dependencies => (
+                graph.dependencies(&path, input.transitive),
+                "are imported by",
+            ),
+        };
+
+        if files.is_empty() {
+            return Ok(ToolOutput::text(format!(
+                "No workspace files {} {}",
+                label, input.path
+            )));
+        }
+
+        let files = files
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        Ok(ToolOutput::text(format!(
+            "{} files {} {}:\n{}",
+            files.len(),
+            label,
+            input.path,
+            files.join("\n")
+        )))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(sources: &[(&str, &str)]) -> DependencyGraph {
+        let sources = sources
+            .iter()
+            .map(|(path, content)| (PathBuf::from(path), content.to_string()))
+            .collect::<Vec<(PathBuf, String)>>();
+        DependencyGraph::from_sources(&sources)
+    }
+
+    fn paths(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_rust_dependencies() {
+        let graph = graph(&[
+            ("Cargo.toml", "[package]\nname = \"voo\"\n"),
+            (
+                "src/main.rs",
+                "mod cost;\nuse models::tools::{read_file::ReadFileTool, mod_a};\n",
+            ),
+            ("src/cost.rs", "use crate::main_helper;\nuse super::*;\n"),
+            (
+                "crates/models/Cargo.toml",
+                "[package]\nname = \"models\"\n[dependencies]\nname = \"x\"\n",
+            ),
+            ("crates/models/src/lib.rs", "pub mod tools;\n"),
+            ("crates/models/src/tools/mod.rs", "pub mod read_file;\n"),
+            (
+                "crates/models/src/tools/read_file.rs",
+                "use super::super::prune;\nuse std::fs;\n",
+            ),
+            ("crates/models/src/prune.rs", ""),
+        ]);
+
+        assert_eq!(
+            graph.dependencies(Path::new("src/main.rs"), false),
+            paths(&[
+                "src/cost.rs",
+                "crates/models/src/tools/read_file.rs",
+                "crates/models/src/tools/mod.rs"
+            ])
+        );
+        assert_eq!(
+            graph.dependents(Path::new("crates/models/src/prune.rs"), false),
+            paths(&["crates/models/src/tools/read_file.rs"])
+        );
+        assert!(
+            graph
+                .dependents(Path::new("crates/models/src/prune.rs"), true)
+                .contains(Path::new("src/main.rs"))
+        );
+    }
+
+    #[test]
+    fn test_js_and_python_dependencies() {
+        let graph = graph(&[
+            (
+                "web/app.ts",
+                "import { a } from './lib/a.js';\nimport React from 'react';\nconst b = require(\"../shared\");\n",
+            ),
+            ("web/lib/a.ts", ""),
+            ("shared/index.js", ""),
+            ("pkg/__init__.py", ""),
+            (
+                "pkg/core.py",
+                "from . import util\nfrom .models import User\nimport os, pkg.extra\n",
+            ),
+            ("pkg/util.py", ""),
+            ("pkg/models.py", ""),
+            ("pkg/extra.py", ""),
+        ]);
+
+        assert_eq!(
+            graph.dependencies(Path::new("web/app.ts"), false),
+            paths(&["web/lib/a.ts", "shared/index.js"])
+        );
+        assert_eq!(
+            graph.dependencies(Path::new("pkg/core.py"), false),
+            paths(&["pkg/util.py", "pkg/models.py", "pkg/extra.py"])
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolExample, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::{
+    formatter::Formatters,
+    locks::{EditLock, EditLocks, announce},
+    read_policy::ReadPolicy,
+    snapshots::{FileSnapshots, conflict},
+    write_file::{ask, can_ask, colorize, unified_diff},
+};
+
+#[derive(Debug)]
+pub struct EditFileTool {
+    name: String,
+    description: String,
+    input_schema: EditFileInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+    formatters: Option<Formatters>,
+    read_policy: ReadPolicy,
+    snapshots: Option<FileSnapshots>,
+    locks: Option<EditLocks>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditFileInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    pub old_string: String,
+    pub new_string: String,
+    #[serde(default)]
+    pub expected_replacements: Option<usize>,
+}
+
+impl Display for EditFileTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl EditFileTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: EditFileInput {
+                input: Input {
+                    path: "".to_string(),
+                    old_string: "".to_string(),
+                    new_string: "".to_string(),
+                    expected_replacements: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The path of the file to edit"
+                            },
+                            "old_string": {
+                                "type": "string",
+                                "description": "The exact text to replace, including enough surrounding lines to be unique"
+                            },
+                            "new_string": {
+                                "type": "string",
+                                "description": "The text to replace it with"
+                            },
+                            "expected_replacements": {
+                                "type": "integer",
+                                "description": "How many occurrences of old_string to replace, defaults to 1. The edit fails when the file contains a different number."
+                            }
+                        },
+                        "required": ["path", "old_string", "new_string"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![
+                    ToolExample::new(
+                        json!({"path": "src/config.rs", "old_string": "    timeout: 30,", "new_string": "    timeout: 60,"}),
+                        "Replaced 1 occurrences in src/config.rs",
+                    ),
+                    ToolExample::new(
+                        json!({"path": "src/lib.rs", "old_string": ".unwrap()", "new_string": "?", "expected_replacements": 3}),
+                        "Replaced 3 occurrences in src/lib.rs",
+                    ),
+                ],
+            },
+            auto_approve: false,
+            formatters: None,
+            read_policy: ReadPolicy::builtin(),
+            snapshots: None,
+            locks: None,
+        }
+    }
+
+    // Edits without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn with_formatters(mut self, formatters: Formatters) -> Self {
+        self.formatters = Some(formatters);
+        self
+    }
+
+    // Refuses edits of files that changed on disk since the agent read them.
+    pub fn with_snapshots(mut self, snapshots: FileSnapshots) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    // Keeps the content of denied files out of conflicts.
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn with_locks(mut self, locks: EditLocks) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    pub fn input_schema(&self) -> &EditFileInput {
+        &self.input_schema
+    }
+
+    fn confirm(&self, path: &str, diff: &str, locks: &[EditLock]) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        announce(locks);
+        println!("{}", colorize(diff));
+        ask(&format!("edit {}?", path))
+    }
+}
+
+// Replaces exactly `expected` occurrences of `old` and fails otherwise, so an
+// edit never lands in the wrong place. The model copies text with \n line
+// endings, so a file with only \r\n ones is matched without them and keeps
+// them afterwards.
+fn replace(content: &str, input: &Input) -> Result<String, ToolError> {
+    let lines = content.matches('\n').count();
+    if lines > 0 && content.matches("\r\n").count() == lines {
+        let unix = Input {
+            path: input.path.clone(),
+            old_string: input.old_string.replace("\r\n", "\n"),
+            new_string: input.new_string.replace("\r\n", "\n"),
+            expected_replacements: input.expected_replacements,
+        };
+        let replaced = replace_exactly(&content.replace("\r\n", "\n"), &unix)?;
+        return Ok(replaced.replace('\n', "\r\n"));
+    }
+
+    replace_exactly(content, input)
+}
+
+fn replace_exactly(content: &str, input: &Input) -> Result<String, ToolError> {
+    if input.old_string.is_empty() {
+        return Err(ToolError::ToolError(
+            "old_string is empty, use write_file to create a file".to_string(),
+        ));
+    }
+    if input.old_string == input.new_string {
+        return Err(ToolError::ToolError(
+            "old_string and new_string are the same".to_string(),
+        ));
+    }
+
+    let expected = input.expected_replacements.unwrap_or(1);
+    let found = content.matches(&input.old_string).count();
+    match found {
+        0 => Err(ToolError::ToolError(format!(
+            "old_string was not found in {}, read the file again and copy the text exactly",
+            input.path
+        ))),
+        found if found != expected && input.expected_replacements.is_none() => {
+            Err(ToolError::ToolError(format!(
+                "old_string appears {} times in {}, include more surrounding text to make it unique or set expected_replacements to {}",
+                found, input.path, found
+            )))
+        }
+        found if found != expected => Err(ToolError::ToolError(format!(
+            "Expected {} occurrences of old_string in {} but found {}",
+            expected, input.path, found
+        ))),
+        _ => Ok(content.replace(&input.old_string, &input.new_string)),
+    }
+}
+
+#[async_trait]
+impl Tool for EditFileTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let buf = PathBuf::from(&input.path);
+        let locks = match &self.locks {
+            Some(locks) => locks.lock_all([buf.as_path()])?,
+            None => vec![],
+        };
+
+        let old = std::fs::read(&buf).map_err(|e| ToolError::FileNotFound(e.to_string()))?;
+        if let Some(snapshots) = &self.snapshots
+            && snapshots.changed(&buf, &old)
+        {
+            return Ok(conflict(
+                Some(snapshots),
+                &self.read_policy,
+                &input.path,
+                &buf,
+                &old,
+            ));
+        }
+        let old = String::from_utf8(old)
+            .map_err(|_| ToolError::ToolError(format!("{} is not a text file", input.path)))?;
+        let new = replace(&old, &input)?;
+
+        let diff = unified_diff(&input.path, &old, &new);
+        if !self.confirm(&input.path, &diff, &locks)? {
+            return Ok(ToolOutput::error(format!(
+                "The user declined the edit to {}, it was not applied",
+                input.path
+            )));
+        }
+
+        // The user may have saved the file while the diff was shown.
+        let current = std::fs::read(&buf).map_err(|e| ToolError::FileNotFound(e.to_string()))?;
+        if current != old.as_bytes() {
+            return Ok(conflict(
+                self.snapshots.as_ref(),
+                &self.read_policy,
+                &input.path,
+                &buf,
+                &current,
+            ));
+        }
+
+        std::fs::write(&buf, &new).map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let replacements = input.expected_replacements.unwrap_or(1);
+        let mut output = format!("Replaced {} occurrences in {}", replacements, input.path);
+        if let Some(formatters) = &self.formatters
+            && let Some(note) = formatters.format(&buf).await
+        {
+            output.push('\n');
+            output.push_str(&note);
+        }
+        if let Some(snapshots) = &self.snapshots
+            && let Ok(written) = std::fs::read(&buf)
+        {
+            snapshots.record(&buf, &written);
+        }
+
+        Ok(ToolOutput::text(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(old_string: &str, expected_replacements: Option<usize>) -> Input {
+        Input {
+            path: "a.rs".to_string(),
+            old_string: old_string.to_string(),
+            new_string: "b".to_string(),
+            expected_replacements,
+        }
+    }
+
+    #[test]
+    fn test_replace() {
+        let content = "let a = 1;\nlet a = 2;\nlet c = 3;\n";
+
+        assert_eq!(
+            replace(content, &input("c", None)).unwrap(),
+            "let a = 1;\nlet a = 2;\nlet b = 3;\n"
+        );
+        assert_eq!(
+            replace(content, &input("a", Some(2))).unwrap(),
+            "let b = 1;\nlet b = 2;\nlet c = 3;\n"
+        );
+
+        let err = replace(content, &input("a", None)).unwrap_err().to_string();
+        assert!(err.contains("appears 2 times"));
+        let err = replace(content, &input("a", Some(3)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Expected 3 occurrences"));
+        assert!(replace(content, &input("z", None)).is_err());
+
+        let windows = Input {
+            old_string: "let a = 1;\nlet a = 2;".to_string(),
+            new_string: "let a = 1;\nlet b = 2;".to_string(),
+            ..input("", None)
+        };
+        assert_eq!(
+            replace("let a = 1;\r\nlet a = 2;\r\n", &windows).unwrap(),
+            "let a = 1;\r\nlet b = 2;\r\n"
+        );
+    }
+}
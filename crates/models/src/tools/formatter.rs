@@ -0,0 +1,107 @@
+use std::{collections::HashMap, path::Path};
+
+use tokio::process::Command;
+
+use super::write_file::unified_diff;
+
+// Formatter commands by file extension. The path of the file is appended to
+// the command, which formats it in place.
+#[derive(Debug, Clone, Default)]
+pub struct Formatters {
+    commands: HashMap<String, Vec<String>>,
+}
+
+impl Formatters {
+    pub fn builtin() -> Self {
+        let prettier = [
+            "js", "jsx", "ts", "tsx", "json", "css", "scss", "html", "md", "yaml", "yml",
+        ];
+
+        let mut formatters = Self::default()
+            .with_command("rs", &["rustfmt", "--edition", "2024"])
+            .with_command("py", &["black", "--quiet"]);
+        for extension in prettier {
+            formatters =
+                formatters.with_command(extension, &["prettier", "--write", "--log-level", "warn"]);
+        }
+        formatters
+    }
+
+    pub fn with_command(mut self, extension: &str, command: &[&str]) -> Self {
+        self.commands.insert(
+            extension.to_lowercase(),
+            command.iter().map(|arg| arg.to_string()).collect(),
+        );
+        self
+    }
+
+    pub fn command(&self, path: &Path) -> Option<&[String]> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        self.commands
+            .get(&extension)
+            .map(Vec::as_slice)
+            .filter(|command| !command.is_empty())
+    }
+
+    // Formats a file that was just written. Returns a note for the tool
+    // response when the formatter changed the file or could not run.
+    pub async fn format(&self, path: &Path) -> Option<String> {
+        let command = self.command(path)?;
+        let before = std::fs::read_to_string(path).ok()?;
+
+        let output = match Command::new(&command[0])
+            .args(&command[1..])
+            .arg(path)
+            .output()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                return Some(format!(
+                    "{} could not run, the file was not formatted: {}",
+                    command[0], e
+                ));
+            }
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Some(format!(
+                "{} failed, the file was not formatted:\n{}",
+                command[0],
+                stderr.trim()
+            ));
+        }
+
+        let after = std::fs::read_to_string(path).ok()?;
+        if after == before {
+            return None;
+        }
+
+        Some(format!(
+            "{} reformatted the file:\n{}",
+            command[0],
+            unified_diff(&path.to_string_lossy(), &before, &after)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_format() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "one  \ntwo\n").unwrap();
+
+        let formatters = Formatters::default().with_command("txt", &["sed", "-i", "s/ *$//"]);
+        let note = formatters.format(&path).await.unwrap();
+        assert!(note.starts_with("sed reformatted the file:"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+
+        assert_eq!(formatters.format(&path).await, None);
+        assert_eq!(formatters.format(&dir.join("a.rs")).await, None);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{fmt::Display, path::Path};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    locks::{EditLock, EditLocks, announce},
+    read_policy::ReadPolicy,
+    snapshots::{FileSnapshots, conflict},
+    write_file::{ask, can_ask, colorize},
+};
+use crate::git::{apply_patch, patch_files};
+
+#[derive(Debug)]
+pub struct GitApplyPatchTool {
+    name: String,
+    description: String,
+    input_schema: GitApplyPatchInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+    read_policy: ReadPolicy,
+    snapshots: Option<FileSnapshots>,
+    locks: Option<EditLocks>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitApplyPatchInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub patch: String,
+}
+
+impl Display for GitApplyPatchTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitApplyPatchTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitApplyPatchInput {
+                input: Input {
+                    patch: "".to_string(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "patch": {
+                                "type": "string",
+                                "description": "A unified diff with paths relative to the repository root, as git diff prints it"
+                            }
+                        },
+                        "required": ["patch"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            auto_approve: false,
+            read_policy: ReadPolicy::builtin(),
+            snapshots: None,
+            locks: None,
+        }
+    }
+
+    // Applies patches without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    // Patches touching files the policy refuses are not applied.
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    // Refuses patches of files that changed on disk since the agent read them.
+    pub fn with_snapshots(mut self, snapshots: FileSnapshots) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    pub fn with_locks(mut self, locks: EditLocks) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    pub fn input_schema(&self) -> &GitApplyPatchInput {
+        &self.input_schema
+    }
+
+    fn confirm(
+        &self,
+        patch: &str,
+        files: &[String],
+        locks: &[EditLock],
+    ) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        announce(locks);
+        println!("{}", colorize(patch));
+        ask(&format!("apply this patch to {} files?", files.len()))
+    }
+}
+
+#[async_trait]
+impl Tool for GitApplyPatchTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let files = patch_files(Path::new("."), input.patch.clone())
+            .await
+            .map_err(ToolError::ToolError)?;
+        for (_, path) in &files {
+            if let Err(reason) = self.read_policy.check(path) {
+                return Ok(ToolOutput::error(format!(
+                    "The patch was not applied: {}",
+                    reason
+                )));
+            }
+        }
+        let locks = match &self.locks {
+            Some(locks) => locks.lock_all(files.iter().map(|(_, path)| path.as_path()))?,
+            None => vec![],
+        };
+
+        // Files the patch creates are read as None.
+        let old = files
+            .iter()
+            .map(|(_, path)| std::fs::read(path).ok())
+            .collect::<Vec<Option<Vec<u8>>>>();
+        if let Some(snapshots) = &self.snapshots {
+            for ((name, path), content) in files.iter().zip(&old) {
+                if let Some(content) = content
+                    && snapshots.changed(path, content)
+                {
+                    return Ok(conflict(
+                        Some(snapshots),
+                        &self.read_policy,
+                        name,
+                        path,
+                        content,
+                    ));
+                }
+            }
+        }
+
+        let names = files
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>();
+        if !self.confirm(&input.patch, &names, &locks)? {
+            return Ok(ToolOutput::error(
+                "The user declined the patch, no files were changed",
+            ));
+        }
+
+        // The user may have saved a file while the patch was shown.
+        for ((name, path), content) in files.iter().zip(&old) {
+            let current = std::fs::read(path).ok();
+            if current != *content {
+                let current = current.unwrap_or_default();
+                return Ok(conflict(
+                    self.snapshots.as_ref(),
+                    &self.read_policy,
+                    name,
+                    path,
+                    &current,
+                ));
+            }
+        }
+
+        apply_patch(Path::new("."), input.patch)
+            .await
+            .map_err(ToolError::ToolError)?;
+        if let Some(snapshots) = &self.snapshots {
+            for (_, path) in &files {
+                if let Ok(written) = std::fs::read(path) {
+                    snapshots.record(path, &written);
+                }
+            }
+        }
+        Ok(ToolOutput::text(format!(
+            "Applied the patch to {} files:\n{}",
+            names.len(),
+            names.join("\n")
+        )))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::read_policy::ReadPolicy;
+use crate::git::{BlameLine, blame};
+
+// Blaming a whole large file is rarely useful and floods the conversation.
+const MAX_LINES: usize = 400;
+
+#[derive(Debug)]
+pub struct GitBlameTool {
+    name: String,
+    description: String,
+    input_schema: GitBlameInput,
+    tool_definition: ToolDefinition,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitBlameInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+}
+
+impl Display for GitBlameTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitBlameTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitBlameInput {
+                input: Input {
+                    path: "".to_string(),
+                    start_line: None,
+                    end_line: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The path of the file to blame"
+                            },
+                            "start_line": {
+                                "type": "integer",
+                                "description": "The first line to blame, 1-based. Defaults to the start of the file"
+                            },
+                            "end_line": {
+                                "type": "integer",
+                                "description": "The last line to blame, inclusive. Defaults to 400 lines after start_line"
+                            }
+                        },
+                        "required": ["path"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &GitBlameInput {
+        &self.input_schema
+    }
+}
+
+// One line per source line, then the summary of every commit once, so the
+// model can tell why a line changed without a second lookup.
+fn format_blame(lines: &[BlameLine]) -> String {
+    let mut output = lines
+        .iter()
+        .map(|line| match line.is_committed() {
+            true => format!(
+                "{} {} {} {} | {}",
+                line.line,
+                &line.commit[..line.commit.len().min(8)],
+                line.author,
+                line.date,
+                line.text
+            ),
+            false => format!("{} uncommitted | {}", line.line, line.text),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let mut commits = vec![];
+    for line in lines.iter().filter(|line| line.is_committed()) {
+        let summary = format!(
+            "{} {}",
+            &line.commit[..line.commit.len().min(8)],
+            line.summary
+        );
+        if !commits.contains(&summary) {
+            commits.push(summary);
+        }
+    }
+    if !commits.is_empty() {
+        output.push_str("\n\nCommits:\n");
+        output.push_str(&commits.join("\n"));
+    }
+
+    output
+}
+
+#[async_trait]
+impl Tool for GitBlameTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let path = PathBuf::from(&input.path);
+        if !path.is_file() {
+            return Err(ToolError::FileNotFound(input.path));
+        }
+        if let Err(reason) = self.read_policy.check(&path) {
+            return Ok(ToolOutput::error(format!(
+                "The file was not read: {}",
+                reason
+            )));
+        }
+
+        let start = input.start_line.unwrap_or(1).max(1);
+        let end = input.end_line.unwrap_or(start + MAX_LINES - 1);
+        if end < start {
+            return Err(ToolError::ToolError(format!(
+                "end_line {} is before start_line {}",
+                end, start
+            )));
+        }
+        // A range past the end of the file is a mistake worth reporting.
+        let length = std::fs::read_to_string(&path)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?
+            .lines()
+            .count();
+        if start > length {
+            return Err(ToolError::ToolError(format!(
+                "{} has only {} lines",
+                input.path, length
+            )));
+        }
+        let end = end.min(length).min(start + MAX_LINES - 1);
+
+        let lines = blame(&path, &[(start, end)])
+            .await
+            .map_err(ToolError::ToolError)?;
+
+        Ok(ToolOutput::text(format_blame(&lines)))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_blame() {
+        let line = |line: usize, commit: &str, summary: &str, text: &str| BlameLine {
+            line,
+            commit: commit.to_string(),
+            author: "Jane Doe".to_string(),
+            date: "2024-05-01".to_string(),
+            summary: summary.to_string(),
+            text: text.to_string(),
+        };
+        let lines = vec![
+            line(1, "3f1c2a9d8e7b", "Add main", "fn main() {"),
+            line(
+                2,
+                "0000000000000000000000000000000000000000",
+                "",
+                "    run();",
+            ),
+            line(3, "3f1c2a9d8e7b", "Add main", "}"),
+        ];
+
+        assert_eq!(
+            format_blame(&lines),
+            "1 3f1c2a9d Jane Doe 2024-05-01 | fn main() {\n2 uncommitted |     run();\n3 3f1c2a9d Jane Doe 2024-05-01 | }\n\nCommits:\n3f1c2a9d Add main"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::{fmt::Display, path::Path};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::write_file::{ask, can_ask};
+use crate::git::checkout_branch;
+
+#[derive(Debug)]
+pub struct GitCheckoutBranchTool {
+    name: String,
+    description: String,
+    input_schema: GitCheckoutBranchInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitCheckoutBranchInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub branch: String,
+    #[serde(default)]
+    pub create: bool,
+}
+
+impl Display for GitCheckoutBranchTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitCheckoutBranchTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitCheckoutBranchInput {
+                input: Input {
+                    branch: "".to_string(),
+                    create: false,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "branch": {
+                                "type": "string",
+                                "description": "The name of the local branch to switch to"
+                            },
+                            "create": {
+                                "type": "boolean",
+                                "description": "Create the branch at the current commit first"
+                            }
+                        },
+                        "required": ["branch"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            auto_approve: false,
+        }
+    }
+
+    // Switches without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn input_schema(&self) -> &GitCheckoutBranchInput {
+        &self.input_schema
+    }
+
+    fn confirm(&self, branch: &str, create: bool) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        match create {
+            true => ask(&format!("create and switch to branch {}?", branch)),
+            false => ask(&format!("switch to branch {}?", branch)),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for GitCheckoutBranchTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let branch = input.branch.trim().to_string();
+        if branch.is_empty() {
+            return Err(ToolError::ToolError(
+                "The branch name can't be empty".to_string(),
+            ));
+        }
+
+        if !self.confirm(&branch, input.create)? {
+            return Ok(ToolOutput::error(format!(
+                "The user declined switching to {}, the branch is unchanged",
+                branch
+            )));
+        }
+
+        let previous = checkout_branch(Path::new("."), branch.clone(), input.create)
+            .await
+            .map_err(ToolError::ToolError)?;
+
+        Ok(ToolOutput::text(match previous {
+            Some(previous) => format!("Switched from {} to {}", previous, branch),
+            None => format!("Switched to {}", branch),
+        }))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
@@ -0,0 +1,224 @@
+use std::{fmt::Display, path::Path};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    read_policy::ReadPolicy,
+    write_file::{Answer, ask_or_edit, can_ask, read_line},
+};
+use crate::git::{commit, staged_files};
+
+#[derive(Debug)]
+pub struct GitCommitTool {
+    name: String,
+    description: String,
+    input_schema: GitCommitInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitCommitInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub message: String,
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl Display for GitCommitTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitCommitTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitCommitInput {
+                input: Input {
+                    message: "".to_string(),
+                    paths: vec![],
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "message": {
+                                "type": "string",
+                                "description": "The commit message, a short summary line, optionally followed by a blank line and a body"
+                            },
+                            "paths": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Files or directories to stage before committing, relative to the repository root. New, changed and deleted files under them are staged. Leave out to commit only what is staged already"
+                            }
+                        },
+                        "required": ["message"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            auto_approve: false,
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    // Commits without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    // Files the policy refuses are not staged, whatever the paths match.
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &GitCommitInput {
+        &self.input_schema
+    }
+
+    // The message to commit with, the user can replace the summary line of
+    // the proposed one. None when the user declined.
+    fn confirm(
+        &self,
+        message: &str,
+        files: &[String],
+        skipped: &[String],
+    ) -> Result<Option<String>, ToolError> {
+        if self.auto_approve {
+            return Ok(Some(message.to_string()));
+        }
+        if !can_ask() {
+            return Ok(None);
+        }
+
+        let mut message = message.to_string();
+        for file in files {
+            println!("\x1b[33m  {}\x1b[0m", file);
+        }
+        if !skipped.is_empty() {
+            println!(
+                "\x1b[2mleft out by the read rules: {}\x1b[0m",
+                skipped.join(", ")
+            );
+        }
+        loop {
+            println!("\x1b[2m{}\x1b[0m", message.trim_end());
+            match ask_or_edit(&format!("commit {} files with this message?", files.len()))? {
+                Answer::Yes => return Ok(Some(message)),
+                Answer::No => return Ok(None),
+                Answer::Edit => {
+                    let summary = read_line("new summary line:")?;
+                    if !summary.is_empty() {
+                        message = replace_summary(&message, &summary);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn replace_summary(message: &str, summary: &str) -> String {
+    match message.split_once('\n') {
+        Some((_, body)) => format!("{}\n{}", summary, body),
+        None => summary.to_string(),
+    }
+}
+
+#[async_trait]
+impl Tool for GitCommitTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        if input.message.trim().is_empty() {
+            return Err(ToolError::ToolError(
+                "The commit message can't be empty".to_string(),
+            ));
+        }
+        let paths = input
+            .paths
+            .iter()
+            .map(|path| path.trim_start_matches("./").to_string())
+            .filter(|path| !path.is_empty())
+            .collect::<Vec<String>>();
+
+        let (files, skipped) =
+            staged_files(Path::new("."), paths.clone(), self.read_policy.clone())
+                .await
+                .map_err(ToolError::ToolError)?;
+
+        if files.is_empty() {
+            return Ok(ToolOutput::error(
+                "Nothing to commit, pass the paths of the files to stage",
+            ));
+        }
+
+        let Some(message) = self.confirm(&input.message, &files, &skipped)? else {
+            return Ok(ToolOutput::error(
+                "The user declined the commit, nothing was staged or committed",
+            ));
+        };
+
+        let info = commit(Path::new("."), paths, message, self.read_policy.clone())
+            .await
+            .map_err(ToolError::ToolError)?;
+        let output = serde_json::to_value(info).map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        Ok(ToolOutput::Json(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_summary() {
+        assert_eq!(
+            replace_summary("Fix parser\n\nIt dropped the last token.", "Fix tokenizer"),
+            "Fix tokenizer\n\nIt dropped the last token."
+        );
+        assert_eq!(
+            replace_summary("Fix parser", "Fix tokenizer"),
+            "Fix tokenizer"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::{fmt::Display, path::Path};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::read_policy::ReadPolicy;
+use crate::git::{DiffTarget, FileDiff, diff};
+
+// The patches together, the file list is always complete up to MAX_FILES.
+const MAX_PATCH_BYTES: usize = 32 * 1024;
+const MAX_FILES: usize = 200;
+
+#[derive(Debug)]
+pub struct GitDiffTool {
+    name: String,
+    description: String,
+    input_schema: GitDiffInput,
+    tool_definition: ToolDefinition,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitDiffInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    #[serde(default)]
+    pub staged: bool,
+    pub commit: Option<String>,
+    pub path: Option<String>,
+}
+
+impl Display for GitDiffTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitDiffTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitDiffInput {
+                input: Input {
+                    staged: false,
+                    commit: None,
+                    path: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "staged": {
+                                "type": "boolean",
+                                "description": "Only the changes staged for the next commit. By default all uncommitted changes to tracked files are shown"
+                            },
+                            "commit": {
+                                "type": "string",
+                                "description": "Show the changes of this commit instead, e.g. HEAD or a commit hash"
+                            },
+                            "path": {
+                                "type": "string",
+                                "description": "Only the changes of this file or directory, relative to the repository root"
+                            }
+                        }
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    // Patches of files the policy refuses are left out.
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &GitDiffInput {
+        &self.input_schema
+    }
+}
+
+// The file list and as many whole patches as fit in MAX_PATCH_BYTES. A patch
+// larger than that on its own is cut short in the space left, a file whose
+// patch did not fit, or may not be read, says so.
+fn bounded(files: Vec<FileDiff>, read_policy: &ReadPolicy) -> Value {
+    let total = files.len();
+    let mut patch = String::new();
+    let mut listed = vec![];
+    for file in files.into_iter().take(MAX_FILES) {
+        let mut entry = serde_json::to_value(&file).unwrap_or_default();
+        let refused = std::iter::once(&file.path)
+            .chain(&file.old_path)
+            .find_map(|path| read_policy.check_len(Path::new(path), 0).err());
+        let room = MAX_PATCH_BYTES - patch.len();
+        match refused {
+            Some(reason) => entry["patch"] = json!(format!("omitted: {}", reason)),
+            None if file.patch.len() <= room => patch.push_str(&file.patch),
+            None if file.patch.len() <= MAX_PATCH_BYTES => {
+                entry["patch"] = json!("omitted, the diff is too large, ask for this path alone")
+            }
+            None if room == 0 => {
+                entry["patch"] = json!(
+                    "omitted, the diff is too large, ask for this path alone to see its start"
+                )
+            }
+            None => {
+                let cut = truncate(&file.patch, room);
+                patch.push_str(cut);
+                entry["patch"] = json!(format!(
+                    "truncated, {} of {} bytes shown",
+                    cut.len(),
+                    file.patch.len()
+                ));
+            }
+        }
+        listed.push(entry);
+    }
+
+    json!({
+        "files": listed,
+        "total_files": total,
+        "patch": patch,
+    })
+}
+
+// The longest run of whole lines of `patch` within `max` bytes, or as much of
+// the first line as fits when not even that does.
+fn truncate(patch: &str, max: usize) -> &str {
+    let mut end = max.min(patch.len());
+    while !patch.is_char_boundary(end) {
+        end -= 1;
+    }
+    match patch[..end].rfind('\n') {
+        Some(newline) => &patch[..newline + 1],
+        None => &patch[..end],
+    }
+}
+
+#[async_trait]
+impl Tool for GitDiffTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let target = match (input.staged, input.commit) {
+            (true, Some(_)) => {
+                return Err(ToolError::ToolError(
+                    "staged and commit can't be combined".to_string(),
+                ));
+            }
+            (true, None) => DiffTarget::Staged,
+            (false, Some(commit)) => DiffTarget::Commit(commit),
+            (false, None) => DiffTarget::Uncommitted,
+        };
+        let path = input
+            .path
+            .map(|path| path.trim_start_matches("./").to_string())
+            .filter(|path| !path.is_empty() && path != ".");
+
+        let files = diff(Path::new("."), target, path)
+            .await
+            .map_err(ToolError::ToolError)?;
+
+        Ok(ToolOutput::Json(bounded(files, &self.read_policy)))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded() {
+        let file = |path: &str, patch: String| FileDiff {
+            path: path.to_string(),
+            old_path: None,
+            status: "modified",
+            additions: 1,
+            deletions: 0,
+            patch,
+        };
+        let files = vec![
+            file("src/main.rs", "+fn main() {}\n".to_string()),
+            file(".env", "+TOKEN=secret\n".to_string()),
+            file("Cargo.lock", "+".repeat(MAX_PATCH_BYTES)),
+            FileDiff {
+                old_path: Some(".env".to_string()),
+                ..file("env.txt", "+TOKEN=secret\n".to_string())
+            },
+        ];
+        let policy = ReadPolicy::builtin().with_denied(&[".env"]);
+
+        let output = bounded(files, &policy);
+        assert_eq!(output["patch"], "+fn main() {}\n");
+        assert_eq!(output["total_files"], 4);
+        assert_eq!(output["files"][0]["additions"], 1);
+        assert!(
+            output["files"][1]["patch"]
+                .as_str()
+                .unwrap()
+                .starts_with("omitted: .env is denied")
+        );
+        assert!(
+            output["files"][2]["patch"]
+                .as_str()
+                .unwrap()
+                .contains("too large")
+        );
+        assert!(
+            output["files"][3]["patch"]
+                .as_str()
+                .unwrap()
+                .starts_with("omitted: .env is denied")
+        );
+
+        let line = format!("+{}\n", "x".repeat(98));
+        let output = bounded(
+            vec![file("data.csv", line.repeat(MAX_PATCH_BYTES / 100 + 10))],
+            &policy,
+        );
+        let shown = output["patch"].as_str().unwrap();
+        assert_eq!(shown.len(), MAX_PATCH_BYTES / 100 * 100);
+        assert!(shown.ends_with('\n'));
+        assert!(
+            output["files"][0]["patch"]
+                .as_str()
+                .unwrap()
+                .starts_with("truncated")
+        );
+    }
+}
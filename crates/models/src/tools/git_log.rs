@@ -0,0 +1,125 @@
+use std::{fmt::Display, path::Path};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::git::log;
+
+const DEFAULT_MAX_COUNT: usize = 20;
+const MAX_COUNT: usize = 100;
+
+#[derive(Debug)]
+pub struct GitLogTool {
+    name: String,
+    description: String,
+    input_schema: GitLogInput,
+    tool_definition: ToolDefinition,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitLogInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: Option<String>,
+    pub rev: Option<String>,
+    pub max_count: Option<usize>,
+}
+
+impl Display for GitLogTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitLogTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitLogInput {
+                input: Input {
+                    path: None,
+                    rev: None,
+                    max_count: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "Only list the commits that changed this file or directory, relative to the repository root"
+                            },
+                            "rev": {
+                                "type": "string",
+                                "description": "The branch, tag or commit to start from. Defaults to HEAD"
+                            },
+                            "max_count": {
+                                "type": "integer",
+                                "description": "How many commits to list, 20 by default and at most 100"
+                            }
+                        }
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+        }
+    }
+
+    pub fn input_schema(&self) -> &GitLogInput {
+        &self.input_schema
+    }
+}
+
+#[async_trait]
+impl Tool for GitLogTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let max_count = input
+            .max_count
+            .unwrap_or(DEFAULT_MAX_COUNT)
+            .clamp(1, MAX_COUNT);
+        let path = input
+            .path
+            .map(|path| path.trim_start_matches("./").to_string())
+            .filter(|path| !path.is_empty() && path != ".");
+
+        let commits = log(Path::new("."), input.rev, path, max_count)
+            .await
+            .map_err(ToolError::ToolError)?;
+
+        Ok(ToolOutput::Json(json!({ "commits": commits })))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
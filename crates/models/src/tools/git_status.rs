@@ -0,0 +1,116 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::git::status;
+
+// A checkout full of build output would flood the conversation.
+const MAX_FILES: usize = 200;
+
+#[derive(Debug)]
+pub struct GitStatusTool {
+    name: String,
+    description: String,
+    input_schema: GitStatusInput,
+    tool_definition: ToolDefinition,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitStatusInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+impl Display for GitStatusTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GitStatusTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GitStatusInput {
+                input: Input {
+                    path: default_path(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "A directory inside the repository. Defaults to the workspace root"
+                            }
+                        }
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+        }
+    }
+
+    pub fn input_schema(&self) -> &GitStatusInput {
+        &self.input_schema
+    }
+}
+
+#[async_trait]
+impl Tool for GitStatusTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let path = PathBuf::from(&input.path);
+        if !path.is_dir() {
+            return Err(ToolError::FileNotFound(input.path));
+        }
+
+        let status = status(&path, MAX_FILES)
+            .await
+            .map_err(ToolError::ToolError)?;
+        let output =
+            serde_json::to_value(status).map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        Ok(ToolOutput::Json(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
@@ -0,0 +1,207 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Keeps a broad pattern such as **/* from flooding the conversation.
+const MAX_RESULTS: usize = 500;
+
+#[derive(Debug)]
+pub struct GlobTool {
+    name: String,
+    description: String,
+    input_schema: GlobInput,
+    tool_definition: ToolDefinition,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GlobInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub pattern: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+impl Display for GlobTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GlobTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GlobInput {
+                input: Input {
+                    pattern: "".to_string(),
+                    path: default_path(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "The glob to match, relative to path, e.g. src/**/*.rs or **/*.{ts,tsx}. * does not match /"
+                            },
+                            "path": {
+                                "type": "string",
+                                "description": "The directory to search in. Defaults to the workspace root"
+                            }
+                        },
+                        "required": ["pattern"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+        }
+    }
+
+    pub fn input_schema(&self) -> &GlobInput {
+        &self.input_schema
+    }
+}
+
+fn find_matches(input: &Input) -> Result<Vec<PathBuf>, ToolError> {
+    let glob = GlobBuilder::new(&input.pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| ToolError::ToolError(format!("Invalid glob: {}", e)))?
+        .compile_matcher();
+    let root = PathBuf::from(&input.path);
+    if !root.is_dir() {
+        return Err(ToolError::FileNotFound(input.path.clone()));
+    }
+
+    let mut matches = WalkBuilder::new(&root)
+        .build()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(&root).ok()?;
+            glob.is_match(relative).then(|| match input.path.as_str() {
+                "." | "./" => relative.to_path_buf(),
+                _ => entry.path().to_path_buf(),
+            })
+        })
+        .collect::<Vec<PathBuf>>();
+    matches.sort();
+
+    Ok(matches)
+}
+
+#[async_trait]
+impl Tool for GlobTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let matches = find_matches(&input)?;
+        if matches.is_empty() {
+            return Ok(ToolOutput::text(format!(
+                "No files match {}",
+                input.pattern
+            )));
+        }
+
+        let mut output = matches
+            .iter()
+            .take(MAX_RESULTS)
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if matches.len() > MAX_RESULTS {
+            output.push_str(&format!(
+                "\n[{} more files, use a narrower pattern]",
+                matches.len() - MAX_RESULTS
+            ));
+        }
+
+        Ok(ToolOutput::text(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_glob() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src/tools")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        for file in [
+            "src/main.rs",
+            "src/tools/glob.rs",
+            "src/notes.md",
+            "target/out.rs",
+        ] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        std::fs::write(dir.join(".ignore"), "target/\n").unwrap();
+
+        let path = dir.to_string_lossy().to_string();
+        let tool = GlobTool::new("glob", "");
+        let output = tool
+            .exec(json!({"pattern": "**/*.rs", "path": path}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::text(format!("{}/src/main.rs\n{}/src/tools/glob.rs", path, path))
+        );
+
+        let output = tool
+            .exec(json!({"pattern": "src/*.rs", "path": path}))
+            .await
+            .unwrap();
+        assert_eq!(output, ToolOutput::text(format!("{}/src/main.rs", path)));
+    }
+}
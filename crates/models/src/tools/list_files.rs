@@ -1,7 +1,7 @@
 use std::fmt::Display;
 
 use async_trait::async_trait;
-use domain::models::tools::{Tool, ToolDefinition, ToolError};
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -75,6 +75,7 @@ impl ListFileTool {
                     }"#,
                 )
                 .unwrap(),
+                examples: vec![],
             },
         }
     }
@@ -86,7 +87,7 @@ impl ListFileTool {
 
 #[async_trait]
 impl Tool for ListFileTool {
-    async fn exec(&self, input: Value) -> Result<String, ToolError> {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
         let input = serde_json::from_value::<ListFileInputInner>(input)
             .map_err(|e| ToolError::ToolError(format!("Invalid input: {}", e)))?;
 
@@ -107,7 +108,7 @@ impl Tool for ListFileTool {
 
             let full_path = {
                 if file_type.is_dir() {
-                    format!("{}/", entry.path().to_string_lossy().to_string())
+                    format!("{}/", entry.path().to_string_lossy())
                 } else {
                     entry.path().to_string_lossy().to_string()
                 }
@@ -115,9 +116,7 @@ impl Tool for ListFileTool {
             files.push(full_path);
         }
 
-        let files_str = files.join(", ");
-
-        Ok(files_str)
+        Ok(ToolOutput::Json(Value::from(files)))
     }
 
     fn name(&self) -> &str {
@@ -0,0 +1,166 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use domain::models::tools::ToolError;
+
+// Advisory locks on the files an edit is about to change, held from the diff
+// until the change is written and formatted, so formatters and watchers that
+// honour them do not race the patch. Each lock is an OS lock on a
+// `.<name>.voo-lock` file next to the edited one, which the OS releases even
+// when voo is killed.
+#[derive(Debug, Clone)]
+pub struct EditLocks {
+    owner: String,
+}
+
+#[derive(Debug)]
+pub struct EditLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl EditLocks {
+    pub fn new(owner: &str) -> Self {
+        Self {
+            owner: owner.to_string(),
+        }
+    }
+
+    // Fails without taking any lock when one of the files is locked already.
+    pub fn lock_all<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<Vec<EditLock>, ToolError> {
+        paths
+            .into_iter()
+            .filter_map(|path| self.lock(path).transpose())
+            .collect()
+    }
+
+    // Files in directories that do not exist yet have nothing to race with.
+    pub fn lock(&self, path: &Path) -> Result<Option<EditLock>, ToolError> {
+        let Some(lock_path) = lock_path(path) else {
+            return Ok(None);
+        };
+        if !lock_path.parent().is_some_and(Path::is_dir) {
+            return Ok(None);
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|e| ToolError::ToolError(format!("{}: {}", lock_path.display(), e)))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = std::fs::read_to_string(&lock_path)
+                    .ok()
+                    .filter(|holder| !holder.trim().is_empty())
+                    .unwrap_or_else(|| "another program".to_string());
+                return Err(ToolError::ToolError(format!(
+                    "{} is locked by {}, try again once it is done",
+                    path.display(),
+                    holder.trim()
+                )));
+            }
+            Err(TryLockError::Error(e)) => return Err(ToolError::ToolError(e.to_string())),
+        }
+
+        _ = file.set_len(0);
+        _ = write!(file, "{} (pid {})", self.owner, std::process::id());
+
+        Ok(Some(EditLock {
+            path: lock_path,
+            file,
+        }))
+    }
+}
+
+impl EditLock {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// The file stays, emptied. Removing it would let a program that opened it in
+// the meantime lock a file no longer on disk while the next one creates and
+// locks a new one.
+impl Drop for EditLock {
+    fn drop(&mut self) {
+        _ = self.file.set_len(0);
+        _ = self.file.unlock();
+    }
+}
+
+fn lock_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    Some(parent.join(format!(".{}.voo-lock", name)))
+}
+
+// Tells the user which files wait for their answer.
+pub(crate) fn announce(locks: &[EditLock]) {
+    if locks.is_empty() {
+        return;
+    }
+
+    let paths = locks
+        .iter()
+        .map(|lock| lock.path().display().to_string())
+        .collect::<Vec<String>>();
+    println!(
+        "\x1b[2mlocked until you answer: {}\x1b[0m",
+        paths.join(", ")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_locks() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let file = dir.join("a.rs");
+        let other = dir.join("b.rs");
+        let locks = EditLocks::new("voo");
+
+        let held = locks.lock(&file).unwrap().unwrap();
+        assert_eq!(held.path(), dir.join(".a.rs.voo-lock"));
+        assert!(
+            std::fs::read_to_string(held.path())
+                .unwrap()
+                .starts_with("voo (pid ")
+        );
+
+        let error = locks
+            .lock_all([other.as_path(), file.as_path()])
+            .unwrap_err();
+        assert!(error.to_string().contains("a.rs is locked by voo"));
+
+        drop(held);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(".a.rs.voo-lock")).unwrap(),
+            ""
+        );
+        assert_eq!(
+            locks
+                .lock_all([other.as_path(), file.as_path()])
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(locks.lock(&dir.join("missing/c.rs")).unwrap().is_none());
+    }
+}
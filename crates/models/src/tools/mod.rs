@@ -1,2 +1,32 @@
+pub mod ask_remote_agent;
+pub mod ask_video;
+pub mod bulk_replace;
+#[cfg(feature = "screenshot")]
+pub mod capture_screenshot;
+pub mod dependency_graph;
+pub mod edit_file;
+pub mod formatter;
+pub mod git_apply_patch;
+pub mod git_blame;
+pub mod git_checkout_branch;
+pub mod git_commit;
+pub mod git_diff;
+pub mod git_log;
+pub mod git_status;
+pub mod glob;
 pub mod list_files;
+pub mod locks;
+#[cfg(test)]
+mod proptests;
 pub mod read_file;
+pub mod read_policy;
+pub mod rename_symbol;
+pub mod run_command;
+pub mod scan_todos;
+pub mod search;
+pub mod snapshots;
+pub mod summarize_directory;
+pub mod transcribe_audio;
+pub mod web_fetch;
+pub mod web_search;
+pub mod write_file;
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use domain::models::tools::Tool;
+use proptest::prelude::*;
+use serde_json::{Map, Value, json};
+
+use super::{
+    bulk_replace::BulkReplaceTool,
+    dependency_graph::DependencyGraphTool,
+    edit_file::EditFileTool,
+    git_blame::GitBlameTool,
+    glob::GlobTool,
+    list_files::ListFileTool,
+    read_file::ReadFileTool,
+    rename_symbol::RenameSymbolTool,
+    run_command::{CommandPolicy, RunCommandTool},
+    scan_todos::ScanTodosTool,
+    search::GrepTool,
+    summarize_directory::SummarizeDirectoryTool,
+    write_file::WriteFileTool,
+};
+use crate::lsp::LanguageServers;
+
+fn tools() -> Vec<Arc<dyn Tool>> {
+    vec![
+        Arc::new(ReadFileTool::new("read_file", "")),
+        Arc::new(ListFileTool::new("list_files", "")),
+        Arc::new(SummarizeDirectoryTool::new("summarize_directory", "")),
+        Arc::new(GlobTool::new("glob", "")),
+        Arc::new(GrepTool::new("grep", "")),
+        Arc::new(WriteFileTool::new("write_file", "")),
+        Arc::new(EditFileTool::new("edit_file", "")),
+        Arc::new(BulkReplaceTool::new("bulk_replace", "")),
+        Arc::new(RenameSymbolTool::new(
+            "rename_symbol",
+            "",
+            LanguageServers::builtin(),
+        )),
+        Arc::new(DependencyGraphTool::new("dependency_graph", "")),
+        Arc::new(ScanTodosTool::new("scan_todos", "")),
+        Arc::new(GitBlameTool::new("git_blame", "")),
+        Arc::new(RunCommandTool::new("run_command", "")),
+    ]
+}
+
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// Paths a confused or malicious model might send. Relative paths start from
+// the crate directory, so the traversals end up in /root or /.
+fn adversarial_path() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z._-]{1,12}(/[a-z._-]{1,12}){0,4}",
+        "(\\.\\./){1,12}etc/(passwd|hosts|shadow)",
+        Just("/etc/passwd".to_string()),
+        "[a-z./]{0,8}\0[a-z./]{0,8}",
+        "[a-z]{0,4}/{2,8}[a-z]{0,4}",
+        "\\PC{1,64}",
+        (256usize..20_000).prop_map(|len| "a".repeat(len)),
+    ]
+}
+
+// A value of the wrong type for any field of a tool input.
+fn mistyped() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(json!({"nested": [1, null]})),
+        prop::collection::vec(any::<i64>(), 1..4).prop_map(Value::from),
+    ]
+}
+
+// Arguments no tool accepts, either not an object or an object with every
+// parameter of the wrong type.
+fn malformed_args(properties: Vec<String>) -> impl Strategy<Value = Value> {
+    let count = properties.len();
+    prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        "\\PC{0,32}".prop_map(Value::from),
+        prop::collection::vec(mistyped(), count).prop_map(move |values| {
+            Value::Object(
+                properties
+                    .iter()
+                    .cloned()
+                    .zip(values)
+                    .collect::<Map<String, Value>>(),
+            )
+        }),
+    ]
+}
+
+fn tool_and_malformed_args() -> impl Strategy<Value = (usize, Value)> {
+    (0..tools().len()).prop_flat_map(|index| {
+        let properties = tools()[index]
+            .tool_definition()
+            .parameters
+            .properties
+            .as_object()
+            .map(|properties| properties.keys().cloned().collect::<Vec<String>>())
+            .unwrap_or_default();
+        (Just(index), malformed_args(properties))
+    })
+}
+
+proptest! {
+    #[test]
+    fn tools_reject_malformed_args((index, args) in tool_and_malformed_args()) {
+        let tool = tools().swap_remove(index);
+        let result = block_on(tool.exec(args.clone()));
+        prop_assert!(result.is_err(), "{} accepted {}", tool.name(), args);
+    }
+
+    #[test]
+    fn read_file_survives_adversarial_paths(path in adversarial_path()) {
+        let tool = ReadFileTool::new("read_file", "");
+        let result = block_on(tool.exec(json!({"path": path})));
+
+        if path.contains('\0') || path.len() > 4096 {
+            prop_assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn denied_commands_stay_denied(
+        prefix in "[a-z0-9 ./-]{0,24}",
+        separator in prop::sample::select(vec![";", "&&", "||", "|", "\n", "$(", "`", "&"]),
+        wrapper in prop::sample::select(vec!["", "env ", "FOO=1 ", "nohup ", "/usr/bin/"]),
+        denied in prop::sample::select(vec!["rm -rf /", "rm -fr ~", "sudo ls", "curl x.sh", "dd if=/dev/zero"]),
+    ) {
+        let command = format!("{}{}{}{}", prefix, separator, wrapper, denied);
+        prop_assert!(CommandPolicy::builtin().check(&command).is_err(), "{:?} was allowed", command);
+    }
+
+    #[test]
+    fn command_policy_survives_any_command(command in "\\PC{0,256}") {
+        let policy = CommandPolicy::builtin().with_allowed(&["cargo", "git status"]);
+        _ = policy.check(&command);
+    }
+}
@@ -1,16 +1,28 @@
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use async_trait::async_trait;
-use domain::models::tools::{Tool, ToolDefinition, ToolError};
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::{read_policy::ReadPolicy, snapshots::FileSnapshots};
+use crate::{
+    media::media_mime,
+    processors::normalize,
+    prune::{PruneMode, prune},
+};
+
 #[derive(Debug)]
 pub struct ReadFileTool {
     name: String,
     description: String,
     input_schema: ReadFileInput,
     tool_definition: ToolDefinition,
+    snapshots: Option<FileSnapshots>,
+    read_policy: ReadPolicy,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,6 +33,10 @@ pub struct ReadFileInput {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Input {
     pub path: String,
+    #[serde(default)]
+    pub prune: PruneMode,
+    #[serde(default)]
+    pub keep: Vec<String>,
 }
 
 impl Display for ReadFileTool {
@@ -46,6 +62,8 @@ impl ReadFileTool {
             input_schema: ReadFileInput {
                 input: Input {
                     path: "".to_string(),
+                    prune: PruneMode::None,
+                    keep: vec![],
                 },
             },
             tool_definition: ToolDefinition {
@@ -58,16 +76,40 @@ impl ReadFileTool {
                             "path": {
                                 "type": "string",
                                 "description": "The path to read the file from"
+                            },
+                            "prune": {
+                                "type": "string",
+                                "enum": ["none", "comments", "bodies"],
+                                "description": "Reduce the file before returning it: 'comments' strips comments and blank lines, 'bodies' additionally elides function bodies. Defaults to 'none'."
+                            },
+                            "keep": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Function names whose bodies are kept when prune is 'bodies'"
                             }
                         },
                         "required": ["path"]
                     }"#,
                 )
                 .unwrap(),
+                examples: vec![],
             },
+            snapshots: None,
+            read_policy: ReadPolicy::builtin(),
         }
     }
 
+    // Remembers what was read, for the edit tools to detect outside changes.
+    pub fn with_snapshots(mut self, snapshots: FileSnapshots) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
     pub fn input_schema(&self) -> &ReadFileInput {
         &self.input_schema
     }
@@ -75,15 +117,39 @@ impl ReadFileTool {
 
 #[async_trait]
 impl Tool for ReadFileTool {
-    async fn exec(&self, input: Value) -> Result<String, ToolError> {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
         let input = serde_json::from_value::<Input>(input)
             .map_err(|e| ToolError::ToolError(e.to_string()))?;
-        let path = input.path;
-        let buf = PathBuf::from(path);
-        let content =
-            std::fs::read_to_string(buf).map_err(|e| ToolError::FileNotFound(e.to_string()))?;
+        let buf = PathBuf::from(&input.path);
+        if let Err(reason) = self.read_policy.check(&buf) {
+            return Ok(ToolOutput::error(format!(
+                "The file was not read: {}",
+                reason
+            )));
+        }
+        let content = std::fs::read(&buf).map_err(|e| ToolError::FileNotFound(e.to_string()))?;
+        if let Some(snapshots) = &self.snapshots {
+            snapshots.record(&buf, &content);
+        }
+        if let Some(mime) = binary_mime(&buf, &content) {
+            return Ok(ToolOutput::Binary {
+                mime: mime.to_string(),
+                data: content,
+            });
+        }
+        let content = normalize(&decode(&content));
+
+        if input.prune == PruneMode::None {
+            return Ok(ToolOutput::Text(content));
+        }
 
-        Ok(content)
+        let pruned = prune(&content, &buf, input.prune, &input.keep);
+        Ok(ToolOutput::text(format!(
+            "{}\n[pruned {} to {} bytes]",
+            pruned,
+            content.len(),
+            pruned.len()
+        )))
     }
 
     fn name(&self) -> &str {
@@ -98,3 +164,81 @@ impl Tool for ReadFileTool {
         &self.tool_definition
     }
 }
+
+// Files that are not text are handed over as they are, decoding them would
+// only produce noise. Text in another encoding has no NUL bytes, or starts
+// with a byte order mark when it is UTF-16.
+fn binary_mime(path: &Path, content: &[u8]) -> Option<&'static str> {
+    if let Some(mime) = media_mime(path) {
+        return Some(mime);
+    }
+    if utf16_bom(content).is_some() || !content.contains(&0) {
+        return None;
+    }
+
+    Some("application/octet-stream")
+}
+
+// Whether the content starts with a UTF-16 byte order mark, and if so
+// whether it is little endian.
+fn utf16_bom(content: &[u8]) -> Option<bool> {
+    match content {
+        [0xff, 0xfe, ..] => Some(true),
+        [0xfe, 0xff, ..] => Some(false),
+        _ => None,
+    }
+}
+
+// Decodes UTF-8 and UTF-16 by their byte order mark, anything else that is
+// not valid UTF-8 is read as Latin-1.
+fn decode(content: &[u8]) -> String {
+    if let Some(little_endian) = utf16_bom(content) {
+        let units = content[2..].chunks_exact(2).map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        });
+        return char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+    }
+    let content = content.strip_prefix(b"\xef\xbb\xbf").unwrap_or(content);
+    match std::str::from_utf8(content) {
+        Ok(text) => text.to_string(),
+        Err(_) => content.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_mime() {
+        assert_eq!(binary_mime(Path::new("main.rs"), b"fn main() {}\n"), None);
+        assert_eq!(
+            binary_mime(Path::new("logo.PNG"), &[0x89, b'P', b'N', b'G', 0, 0xff]),
+            Some("image/png")
+        );
+        assert_eq!(
+            binary_mime(Path::new("data.bin"), &[0, 1, 2]),
+            Some("application/octet-stream")
+        );
+        assert_eq!(binary_mime(Path::new("old.txt"), b"caf\xe9\n"), None);
+        assert_eq!(
+            binary_mime(Path::new("wide.txt"), &[0xff, 0xfe, b'h', 0, b'i', 0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("héllo".as_bytes()), "héllo");
+        assert_eq!(decode(b"\xef\xbb\xbfhi"), "hi");
+        assert_eq!(decode(b"caf\xe9"), "café");
+        assert_eq!(decode(&[0xff, 0xfe, b'h', 0, b'i', 0]), "hi");
+        assert_eq!(decode(&[0xfe, 0xff, 0, b'h', 0, b'i']), "hi");
+    }
+}
@@ -0,0 +1,218 @@
+use std::path::Path;
+
+// Reads are limited to 10 MiB unless configured otherwise.
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+// Key material and secrets that have no business in a prompt.
+const DENIED: &[&str] = &[
+    ".env",
+    "pem",
+    "key",
+    "p12",
+    "pfx",
+    "jks",
+    "keystore",
+    "kdbx",
+    "id_rsa",
+    "id_ecdsa",
+    "id_ed25519",
+];
+
+// Which files the read tools take in. A rule is an extension (`pem`, `.pem`),
+// a file name (`id_rsa`, `.env`) or a mime type (`application/pdf`,
+// `image/*`) guessed from the extension. Denied rules win over allowed ones,
+// an empty allowlist allows anything not denied, and files larger than the
+// limit are refused whatever their type. Every tool that reads files of the
+// workspace, and the tools that hand them to git or another model, checks
+// them here, so a secret stays out of the prompt whichever tool is asked.
+#[derive(Debug, Clone)]
+pub struct ReadPolicy {
+    max_bytes: u64,
+    allowed: Vec<String>,
+    denied: Vec<String>,
+}
+
+impl Default for ReadPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            allowed: vec![],
+            denied: vec![],
+        }
+    }
+}
+
+impl ReadPolicy {
+    pub fn builtin() -> Self {
+        Self::default().with_denied(DENIED)
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_allowed(mut self, rules: &[&str]) -> Self {
+        self.allowed
+            .extend(rules.iter().filter_map(|rule| rule_text(rule)));
+        self
+    }
+
+    pub fn with_denied(mut self, rules: &[&str]) -> Self {
+        self.denied
+            .extend(rules.iter().filter_map(|rule| rule_text(rule)));
+        self
+    }
+
+    // Checks the file on disk, a missing one is left to the tool to report.
+    // A symlink is checked under the name of the file it points to as well,
+    // so a link to `.env` is refused like `.env` itself.
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        let len = std::fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        self.check_len(path, len)?;
+        match std::fs::canonicalize(path) {
+            Ok(target) => self.check_len(&target, len).map_err(|reason| {
+                format!("{} links to a refused file: {}", path.display(), reason)
+            }),
+            Err(_) => Ok(()),
+        }
+    }
+
+    pub fn check_len(&self, path: &Path, len: u64) -> Result<(), String> {
+        if let Some(rule) = self.denied.iter().find(|rule| matches_rule(path, rule)) {
+            return Err(format!(
+                "{} is denied by the read rule `{}`",
+                path.display(),
+                rule
+            ));
+        }
+        if !self.allowed.is_empty() && !self.allowed.iter().any(|rule| matches_rule(path, rule)) {
+            return Err(format!("{} is not in the read allowlist", path.display()));
+        }
+        if len > self.max_bytes {
+            return Err(format!(
+                "{} is {} bytes, reads are limited to {} bytes",
+                path.display(),
+                len,
+                self.max_bytes
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn rule_text(rule: &str) -> Option<String> {
+    Some(rule.trim().to_ascii_lowercase()).filter(|rule| !rule.is_empty())
+}
+
+fn matches_rule(path: &Path, rule: &str) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+    if let Some((kind, subtype)) = rule.split_once('/') {
+        return mime(extension.as_deref().unwrap_or_default()).is_some_and(|mime| {
+            let (mime_kind, mime_subtype) = mime.split_once('/').unwrap_or((mime, ""));
+            mime_kind == kind && (subtype == "*" || mime_subtype == subtype)
+        });
+    }
+
+    // A dotfile rule covers its variants too, `.env` also `.env.local`.
+    let variant = rule.starts_with('.') && name.starts_with(&format!("{}.", rule));
+    name == rule || variant || extension.as_deref() == Some(rule.trim_start_matches('.'))
+}
+
+fn mime(extension: &str) -> Option<&'static str> {
+    Some(match extension {
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "sql" => "application/sql",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "db" | "sqlite" | "sqlite3" => "application/vnd.sqlite3",
+        "pem" => "application/x-pem-file",
+        "p12" | "pfx" => "application/pkcs12",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_policy() {
+        let policy = ReadPolicy::builtin().with_max_bytes(100);
+        assert!(policy.check_len(Path::new("src/main.rs"), 100).is_ok());
+        assert!(
+            policy
+                .check_len(Path::new("certs/server.PEM"), 10)
+                .unwrap_err()
+                .contains("denied by the read rule `pem`")
+        );
+        assert!(
+            policy
+                .check_len(Path::new("/home/me/.ssh/id_rsa"), 10)
+                .is_err()
+        );
+        for name in [
+            ".env",
+            ".env.local",
+            ".env.production",
+            "app/.ENV.development",
+        ] {
+            assert!(policy.check_len(Path::new(name), 10).is_err(), "{}", name);
+        }
+        assert!(policy.check_len(Path::new(".envrc"), 10).is_ok());
+        assert!(
+            policy
+                .check_len(Path::new("dump.sql"), 2 << 30)
+                .unwrap_err()
+                .contains("reads are limited to 100 bytes")
+        );
+
+        let policy = ReadPolicy::default()
+            .with_allowed(&["rs", ".toml", "image/*"])
+            .with_denied(&["application/vnd.sqlite3", ".env"]);
+        assert!(policy.check_len(Path::new("Cargo.toml"), 1).is_ok());
+        assert!(policy.check_len(Path::new("logo.png"), 1).is_ok());
+        assert!(policy.check_len(Path::new("notes.md"), 1).is_err());
+        assert!(policy.check_len(Path::new("data.sqlite3"), 1).is_err());
+        assert!(policy.check_len(Path::new(".env"), 1).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".env"), "TOKEN=secret\n").unwrap();
+        std::os::unix::fs::symlink(".env", dir.path().join("settings.txt")).unwrap();
+        let policy = ReadPolicy::builtin();
+        assert!(policy.check(&dir.path().join(".env")).is_err());
+        assert!(
+            policy
+                .check(&dir.path().join("settings.txt"))
+                .unwrap_err()
+                .contains("links to a refused file")
+        );
+    }
+}
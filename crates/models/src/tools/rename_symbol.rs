@@ -0,0 +1,321 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::{
+    locks::{EditLock, EditLocks, announce},
+    write_file::{ask, can_ask, colorize, unified_diff},
+};
+use crate::lsp::{LanguageServers, LspClient, LspError, apply_edits, file_uri, workspace_edits};
+
+// Covers the time a server like rust-analyzer needs to load the workspace.
+const READY_ATTEMPTS: usize = 60;
+
+#[derive(Debug)]
+pub struct RenameSymbolTool {
+    name: String,
+    description: String,
+    input_schema: RenameSymbolInput,
+    tool_definition: ToolDefinition,
+    servers: LanguageServers,
+    auto_approve: bool,
+    locks: Option<EditLocks>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameSymbolInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    pub line: usize,
+    pub symbol: String,
+    pub new_name: String,
+}
+
+#[derive(Debug)]
+struct Change {
+    path: PathBuf,
+    display: String,
+    edits: usize,
+    new: String,
+    diff: String,
+}
+
+impl Display for RenameSymbolTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl RenameSymbolTool {
+    pub fn new(name: &str, description: &str, servers: LanguageServers) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: RenameSymbolInput {
+                input: Input {
+                    path: "".to_string(),
+                    line: 1,
+                    symbol: "".to_string(),
+                    new_name: "".to_string(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "A file where the symbol is defined or used"
+                            },
+                            "line": {
+                                "type": "integer",
+                                "description": "The 1-based line of that file containing the symbol"
+                            },
+                            "symbol": {
+                                "type": "string",
+                                "description": "The current name of the symbol"
+                            },
+                            "new_name": {
+                                "type": "string",
+                                "description": "The new name of the symbol"
+                            }
+                        },
+                        "required": ["path", "line", "symbol", "new_name"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            servers,
+            auto_approve: false,
+            locks: None,
+        }
+    }
+
+    // Applies the rename without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn with_locks(mut self, locks: EditLocks) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    pub fn input_schema(&self) -> &RenameSymbolInput {
+        &self.input_schema
+    }
+
+    fn confirm(
+        &self,
+        input: &Input,
+        changes: &[Change],
+        locks: &[EditLock],
+    ) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        announce(locks);
+        let diff = changes
+            .iter()
+            .map(|change| colorize(&change.diff))
+            .collect::<Vec<String>>()
+            .join("\n");
+        println!("{}", diff);
+
+        ask(&format!(
+            "rename {} to {} in {} files?",
+            input.symbol,
+            input.new_name,
+            changes.len()
+        ))
+    }
+
+    async fn rename(&self, input: &Input) -> Result<Value, ToolError> {
+        let path = PathBuf::from(&input.path);
+        let command = self.servers.command(&path).ok_or_else(|| {
+            ToolError::ToolError(format!("No language server configured for {}", input.path))
+        })?;
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| ToolError::FileNotFound(format!("{}: {}", input.path, e)))?;
+        let character = symbol_column(&text, input.line, &input.symbol).ok_or_else(|| {
+            ToolError::ToolError(format!(
+                "{} was not found on line {} of {}",
+                input.symbol, input.line, input.path
+            ))
+        })?;
+
+        let root = std::env::current_dir().map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let mut client = LspClient::start(command, &root).await.map_err(lsp_error)?;
+        let result = async {
+            client.open(&path, language_id(&path)).await?;
+            client
+                .request_when_ready(
+                    "textDocument/rename",
+                    json!({
+                        "textDocument": { "uri": file_uri(&path)? },
+                        "position": { "line": input.line - 1, "character": character },
+                        "newName": input.new_name
+                    }),
+                    READY_ATTEMPTS,
+                )
+                .await
+        }
+        .await;
+        client.shutdown().await;
+
+        result.map_err(lsp_error)
+    }
+}
+
+// The UTF-16 column of the first whole-word occurrence of `symbol` on a
+// 1-based line.
+fn symbol_column(text: &str, line: usize, symbol: &str) -> Option<usize> {
+    let line = text.lines().nth(line.checked_sub(1)?)?;
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    line.match_indices(symbol)
+        .find(|(index, _)| {
+            let before = line[..*index].chars().next_back();
+            let after = line[index + symbol.len()..].chars().next();
+            !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+        })
+        .map(|(index, _)| line[..index].encode_utf16().count())
+}
+
+fn language_id(path: &std::path::Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("rs") => "rust",
+        Some("py") => "python",
+        Some("go") => "go",
+        Some("ts") => "typescript",
+        Some("tsx") => "typescriptreact",
+        Some("jsx") => "javascriptreact",
+        _ => "javascript",
+    }
+}
+
+fn lsp_error(e: LspError) -> ToolError {
+    ToolError::ToolError(e.to_string())
+}
+
+#[async_trait]
+impl Tool for RenameSymbolTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let edit = self.rename(&input).await?;
+        if edit.is_null() {
+            return Err(ToolError::ToolError(format!(
+                "The language server found nothing to rename at {} on line {} of {}",
+                input.symbol, input.line, input.path
+            )));
+        }
+
+        let root = std::env::current_dir().unwrap_or_default();
+        let mut changes = vec![];
+        for (path, edits) in workspace_edits(&edit).map_err(lsp_error)? {
+            let display = path
+                .strip_prefix(&root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let old = std::fs::read_to_string(&path)
+                .map_err(|e| ToolError::ToolError(format!("{}: {}", display, e)))?;
+            let new = apply_edits(&old, &edits).map_err(lsp_error)?;
+
+            changes.push(Change {
+                diff: unified_diff(&display, &old, &new),
+                path,
+                display,
+                edits: edits.len(),
+                new,
+            });
+        }
+        if changes.is_empty() {
+            return Ok(ToolOutput::text(format!(
+                "{} is not used anywhere, nothing was renamed",
+                input.symbol
+            )));
+        }
+
+        let locks = match &self.locks {
+            Some(locks) => locks.lock_all(changes.iter().map(|change| change.path.as_path()))?,
+            None => vec![],
+        };
+        if !self.confirm(&input, &changes, &locks)? {
+            return Ok(ToolOutput::error(
+                "The user declined the rename, no files were changed",
+            ));
+        }
+
+        let mut summary = vec![];
+        for change in &changes {
+            std::fs::write(&change.path, &change.new)
+                .map_err(|e| ToolError::ToolError(format!("{}: {}", change.display, e)))?;
+            summary.push(format!("{} ({} edits)", change.display, change.edits));
+        }
+
+        Ok(ToolOutput::text(format!(
+            "Renamed {} to {} with {} edits in {} files:\n{}",
+            input.symbol,
+            input.new_name,
+            changes.iter().map(|change| change.edits).sum::<usize>(),
+            changes.len(),
+            summary.join("\n")
+        )))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_column() {
+        let text = "use crate::parse;\nlet parsed = parse(\"é\", parse_all);\n";
+
+        assert_eq!(symbol_column(text, 1, "parse"), Some(11));
+        assert_eq!(symbol_column(text, 2, "parse"), Some(13));
+        assert_eq!(symbol_column(text, 2, "all"), None);
+        assert_eq!(symbol_column(text, 3, "parse"), None);
+        assert_eq!(symbol_column(text, 0, "parse"), None);
+    }
+}
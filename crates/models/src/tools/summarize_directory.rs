@@ -0,0 +1,439 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::read_policy::ReadPolicy;
+
+const DEFAULT_MAX_TOKENS: usize = 1000;
+// A rough estimate that is good enough to size a summary.
+const CHARS_PER_TOKEN: usize = 4;
+const MAX_EXTENSIONS: usize = 15;
+const MAX_SUBDIRECTORIES: usize = 20;
+const MAX_LARGEST: usize = 10;
+const README_LINES: usize = 20;
+// Files that say what a directory is about at a glance.
+const NOTABLE: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "requirements.txt",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "Gemfile",
+    "composer.json",
+    "Makefile",
+    "CMakeLists.txt",
+    "Dockerfile",
+    "docker-compose.yml",
+    "flake.nix",
+    "LICENSE",
+    "CONTRIBUTING.md",
+    "CHANGELOG.md",
+    ".github",
+    ".gitlab-ci.yml",
+];
+
+#[derive(Debug)]
+pub struct SummarizeDirectoryTool {
+    name: String,
+    description: String,
+    input_schema: SummarizeDirectoryInput,
+    tool_definition: ToolDefinition,
+    read_policy: ReadPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SummarizeDirectoryInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: usize,
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+fn default_max_tokens() -> usize {
+    DEFAULT_MAX_TOKENS
+}
+
+#[derive(Debug, Default)]
+struct Count {
+    files: usize,
+    bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    root: PathBuf,
+    files: usize,
+    directories: usize,
+    bytes: u64,
+    extensions: BTreeMap<String, Count>,
+    subdirectories: BTreeMap<String, Count>,
+    largest: Vec<(PathBuf, u64)>,
+    notable: Vec<String>,
+    readme: Option<(String, String)>,
+}
+
+impl Display for SummarizeDirectoryTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl SummarizeDirectoryTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: SummarizeDirectoryInput {
+                input: Input {
+                    path: default_path(),
+                    max_tokens: default_max_tokens(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The directory to summarize. Defaults to the workspace root"
+                            },
+                            "max_tokens": {
+                                "type": "integer",
+                                "description": "Roughly how many tokens the summary may take. Defaults to 1000"
+                            }
+                        },
+                        "required": []
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            read_policy: ReadPolicy::builtin(),
+        }
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &SummarizeDirectoryInput {
+        &self.input_schema
+    }
+}
+
+// Walks everything below `root` honouring .gitignore. Only the README is
+// read, the rest comes from the file metadata.
+pub fn summarize(root: &Path, read_policy: &ReadPolicy) -> Summary {
+    let mut summary = Summary {
+        root: root.to_path_buf(),
+        ..Default::default()
+    };
+
+    for entry in WalkBuilder::new(root)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .flatten()
+    {
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        let top_level = relative.components().count() == 1;
+        if top_level && NOTABLE.contains(&name.as_str()) {
+            summary.notable.push(name.clone());
+        }
+
+        if entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir())
+        {
+            summary.directories += 1;
+            continue;
+        }
+
+        let bytes = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        summary.files += 1;
+        summary.bytes += bytes;
+
+        let extension = relative
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_else(|| "(none)".to_string());
+        let count = summary.extensions.entry(extension).or_default();
+        count.files += 1;
+        count.bytes += bytes;
+
+        if let Some(directory) = relative
+            .components()
+            .next()
+            .filter(|_| !top_level)
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+        {
+            let count = summary.subdirectories.entry(directory).or_default();
+            count.files += 1;
+            count.bytes += bytes;
+        }
+
+        summary.largest.push((relative.to_path_buf(), bytes));
+
+        if top_level
+            && summary.readme.is_none()
+            && name.to_ascii_lowercase().starts_with("readme")
+            && read_policy.check_len(entry.path(), bytes).is_ok()
+            && let Ok(content) = std::fs::read_to_string(entry.path())
+        {
+            summary.readme = Some((name, excerpt(&content)));
+        }
+    }
+
+    summary
+        .largest
+        .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    summary.largest.truncate(MAX_LARGEST);
+    summary.notable.sort();
+
+    summary
+}
+
+// The first lines with text, skipping badges and blank lines.
+fn excerpt(content: &str) -> String {
+    content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with("[!["))
+        .take(README_LINES)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+impl Summary {
+    // Sections in the order they matter, each cut short once the output would
+    // go over the budget.
+    pub fn render(&self, max_tokens: usize) -> String {
+        let budget = max_tokens.max(1) * CHARS_PER_TOKEN;
+        let mut out = format!(
+            "{}: {} files in {} directories, {}\n",
+            self.root.display(),
+            self.files,
+            self.directories,
+            human_size(self.bytes)
+        );
+
+        let mut extensions = self.extensions.iter().collect::<Vec<(&String, &Count)>>();
+        extensions.sort_by(|a, b| b.1.files.cmp(&a.1.files).then_with(|| a.0.cmp(b.0)));
+        let extensions = extensions
+            .iter()
+            .map(|(extension, count)| {
+                format!(
+                    "  {}: {} files, {}",
+                    extension,
+                    count.files,
+                    human_size(count.bytes)
+                )
+            })
+            .collect::<Vec<String>>();
+        push_section(
+            &mut out,
+            "By extension",
+            &extensions,
+            MAX_EXTENSIONS,
+            budget,
+        );
+
+        let mut subdirectories = self
+            .subdirectories
+            .iter()
+            .collect::<Vec<(&String, &Count)>>();
+        subdirectories.sort_by(|a, b| b.1.files.cmp(&a.1.files).then_with(|| a.0.cmp(b.0)));
+        let subdirectories = subdirectories
+            .iter()
+            .map(|(directory, count)| {
+                format!(
+                    "  {}/: {} files, {}",
+                    directory,
+                    count.files,
+                    human_size(count.bytes)
+                )
+            })
+            .collect::<Vec<String>>();
+        push_section(
+            &mut out,
+            "Subdirectories",
+            &subdirectories,
+            MAX_SUBDIRECTORIES,
+            budget,
+        );
+
+        let largest = self
+            .largest
+            .iter()
+            .map(|(path, bytes)| format!("  {} ({})", path.display(), human_size(*bytes)))
+            .collect::<Vec<String>>();
+        push_section(&mut out, "Largest files", &largest, MAX_LARGEST, budget);
+
+        let notable = self
+            .notable
+            .iter()
+            .map(|name| format!("  {}", name))
+            .collect::<Vec<String>>();
+        push_section(&mut out, "Notable", &notable, NOTABLE.len(), budget);
+
+        if let Some((name, excerpt)) = &self.readme {
+            let lines = excerpt
+                .lines()
+                .map(|line| format!("  {}", line))
+                .collect::<Vec<String>>();
+            push_section(&mut out, name, &lines, README_LINES, budget);
+        }
+
+        out.trim_end().to_string()
+    }
+}
+
+fn push_section(out: &mut String, title: &str, lines: &[String], limit: usize, budget: usize) {
+    if lines.is_empty() || out.len() + title.len() + 2 > budget {
+        return;
+    }
+
+    out.push_str(&format!("{}:\n", title));
+    let mut shown = 0;
+    for line in lines.iter().take(limit) {
+        if out.len() + line.len() + 1 > budget {
+            break;
+        }
+        out.push_str(line);
+        out.push('\n');
+        shown += 1;
+    }
+    if shown < lines.len() {
+        out.push_str(&format!("  [{} more]\n", lines.len() - shown));
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[async_trait]
+impl Tool for SummarizeDirectoryTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let root = PathBuf::from(&input.path);
+        if !root.is_dir() {
+            return Err(ToolError::FileNotFound(input.path));
+        }
+
+        let read_policy = self.read_policy.clone();
+        let summary = tokio::task::spawn_blocking(move || summarize(&root, &read_policy))
+            .await
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        Ok(ToolOutput::text(summary.render(input.max_tokens)))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src/tools")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(
+            dir.join("README.md"),
+            "[![build](badge.svg)](ci)\n\n# demo\n\nA demo crate.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/main.rs"), "x".repeat(2048)).unwrap();
+        std::fs::write(dir.join("src/tools/a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.join("target/out.bin"), "ignored").unwrap();
+        std::fs::write(dir.join(".ignore"), "target/\n").unwrap();
+
+        let summary = summarize(dir, &ReadPolicy::builtin());
+        assert_eq!(summary.files, 5);
+        assert_eq!(summary.extensions["rs"].files, 2);
+        assert_eq!(summary.subdirectories["src"].files, 2);
+        assert_eq!(summary.largest[0].0, PathBuf::from("src/main.rs"));
+        assert_eq!(summary.notable, ["Cargo.toml"]);
+        assert_eq!(
+            summary.readme,
+            Some(("README.md".to_string(), "# demo\nA demo crate.".to_string()))
+        );
+
+        let output = summary.render(1000);
+        assert!(output.contains("By extension:\n  rs: 2 files, 2.0 KB\n"));
+        assert!(output.contains("README.md:\n  # demo\n  A demo crate."));
+        // A tight budget keeps the overview and cuts the rest short.
+        let output = summary.render(30);
+        assert!(output.len() <= 30 * CHARS_PER_TOKEN + 20);
+        assert!(output.contains("more]"));
+        assert!(!output.contains("A demo crate."));
+    }
+}
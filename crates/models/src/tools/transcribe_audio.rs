@@ -0,0 +1,252 @@
+use std::{fmt::Display, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::{
+    tools::{Tool, ToolDefinition, ToolError, ToolOutput},
+    transcription::Transcriber,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    read_policy::ReadPolicy,
+    write_file::{ask, can_ask},
+};
+use crate::media::media_mime;
+
+// Recordings too large to go inline are uploaded, this keeps them to a few
+// hours of audio.
+const MAX_BYTES: u64 = 200 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct TranscribeAudioTool {
+    name: String,
+    description: String,
+    input_schema: TranscribeAudioInput,
+    tool_definition: ToolDefinition,
+    transcriber: Arc<dyn Transcriber>,
+    read_policy: ReadPolicy,
+    auto_approve: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranscribeAudioInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    #[serde(default)]
+    pub instructions: Option<String>,
+}
+
+impl Display for TranscribeAudioTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl TranscribeAudioTool {
+    pub fn new(name: &str, description: &str, transcriber: Arc<dyn Transcriber>) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: TranscribeAudioInput {
+                input: Input {
+                    path: "".to_string(),
+                    instructions: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The audio file to transcribe, e.g. a .wav, .mp3, .m4a, .ogg or .flac recording"
+                            },
+                            "instructions": {
+                                "type": "string",
+                                "description": "What to do beyond a plain transcript, e.g. 'list the action items' or 'write meeting notes'"
+                            }
+                        },
+                        "required": ["path"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            transcriber,
+            read_policy: ReadPolicy::builtin(),
+            auto_approve: false,
+        }
+    }
+
+    // Sends recordings without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn input_schema(&self) -> &TranscribeAudioInput {
+        &self.input_schema
+    }
+
+    // The recording leaves the machine, whatever provider the chat uses.
+    fn confirm(&self, path: &str) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        ask(&format!(
+            "send {} to {} to transcribe it?",
+            path,
+            self.transcriber.model()
+        ))
+    }
+}
+
+fn audio_mime(path: &Path) -> Option<&'static str> {
+    media_mime(path).filter(|mime| mime.starts_with("audio/"))
+}
+
+#[async_trait]
+impl Tool for TranscribeAudioTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let path = Path::new(&input.path);
+
+        let Some(mime) = audio_mime(path) else {
+            return Ok(ToolOutput::error(format!(
+                "{} is not a supported audio file, use wav, mp3, aiff, aac, m4a, ogg or flac",
+                input.path
+            )));
+        };
+        let len = std::fs::metadata(path)
+            .map_err(|e| ToolError::FileNotFound(format!("{}: {}", input.path, e)))?
+            .len();
+        let read_policy = self.read_policy.clone().with_max_bytes(MAX_BYTES);
+        if let Err(reason) = read_policy.check_len(path, len) {
+            return Ok(ToolOutput::error(format!(
+                "The file was not transcribed: {}",
+                reason
+            )));
+        }
+
+        if !self.confirm(&input.path)? {
+            return Ok(ToolOutput::error(format!(
+                "The user declined to send {} to {}",
+                input.path,
+                self.transcriber.model()
+            )));
+        }
+
+        let audio = std::fs::read(path).map_err(|e| ToolError::FileNotFound(e.to_string()))?;
+        match self
+            .transcriber
+            .transcribe(mime, &audio, input.instructions.as_deref())
+            .await
+        {
+            Ok(transcript) => Ok(ToolOutput::Text(transcript)),
+            Err(e) => Ok(ToolOutput::error(format!(
+                "{} could not transcribe {}: {}",
+                self.transcriber.model(),
+                input.path,
+                e
+            ))),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::agent::AgentError;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct EchoTranscriber;
+
+    #[async_trait]
+    impl Transcriber for EchoTranscriber {
+        fn model(&self) -> String {
+            "echo".to_string()
+        }
+
+        async fn transcribe(
+            &self,
+            mime: &str,
+            audio: &[u8],
+            instructions: Option<&str>,
+        ) -> Result<String, AgentError> {
+            Ok(format!(
+                "{} {} {}",
+                mime,
+                String::from_utf8_lossy(audio),
+                instructions.unwrap_or_default()
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_audio() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let audio = dir.join("standup.M4A");
+        std::fs::write(&audio, "audio").unwrap();
+        let tool = TranscribeAudioTool::new("transcribe_audio", "", Arc::new(EchoTranscriber))
+            .with_auto_approve(true);
+
+        let output = tool
+            .exec(json!({ "path": audio, "instructions": "as notes" }))
+            .await
+            .unwrap();
+        assert_eq!(output, ToolOutput::text("audio/aac audio as notes"));
+
+        let output = tool
+            .exec(json!({ "path": dir.join("notes.txt") }))
+            .await
+            .unwrap();
+        assert!(output.is_error());
+        assert!(
+            tool.exec(json!({ "path": dir.join("missing.wav") }))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,495 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use futures::StreamExt;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use super::write_file::{ask, can_ask};
+
+const DEFAULT_MAX_BYTES: u64 = 2 * 1024 * 1024;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 10;
+// Lines of the converted page are wrapped at this width.
+const WIDTH: usize = 100;
+
+#[derive(Debug)]
+pub struct WebFetchTool {
+    name: String,
+    description: String,
+    input_schema: WebFetchInput,
+    tool_definition: ToolDefinition,
+    allowed_domains: Vec<String>,
+    max_bytes: u64,
+    timeout: Duration,
+    auto_approve: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebFetchInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub url: String,
+}
+
+impl Display for WebFetchTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl WebFetchTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: WebFetchInput {
+                input: Input {
+                    url: "".to_string(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "url": {
+                                "type": "string",
+                                "description": "The http or https URL of the page, e.g. https://docs.rs/serde/latest/serde/"
+                            }
+                        },
+                        "required": ["url"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            allowed_domains: vec![],
+            max_bytes: DEFAULT_MAX_BYTES,
+            timeout: DEFAULT_TIMEOUT,
+            auto_approve: false,
+        }
+    }
+
+    // Limits the tool to these domains and their subdomains, redirects
+    // included. Without any, every public host may be fetched once the user
+    // approves the URL, as a URL can carry workspace data anywhere.
+    pub fn with_allowed_domains(mut self, domains: &[&str]) -> Self {
+        self.allowed_domains.extend(
+            domains
+                .iter()
+                .map(|domain| {
+                    domain
+                        .trim()
+                        .trim_start_matches("*.")
+                        .trim_matches('.')
+                        .to_ascii_lowercase()
+                })
+                .filter(|domain| !domain.is_empty()),
+        );
+        self
+    }
+
+    // Bodies are cut off after this many bytes.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    // Whether the tool only fetches from allowed domains, without asking.
+    pub fn is_restricted(&self) -> bool {
+        !self.allowed_domains.is_empty()
+    }
+
+    pub fn input_schema(&self) -> &WebFetchInput {
+        &self.input_schema
+    }
+
+    fn confirm(&self, url: &Url) -> Result<bool, ToolError> {
+        if self.auto_approve || !self.allowed_domains.is_empty() {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        ask(&format!("fetch {}?", url))
+    }
+}
+
+// Why `url` may not be fetched, None when it may. Local addresses are only
+// fetched when the allowlist names them.
+fn refusal(url: &Url, allowed_domains: &[String]) -> Option<String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some(format!(
+            "only http and https URLs are fetched, not {}",
+            url.scheme()
+        ));
+    }
+    let Some(host) = url.host_str() else {
+        return Some("the URL has no host".to_string());
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    if allowed_domains.is_empty() {
+        return local(&host).then(|| format!("{} is a local address", host));
+    }
+    let allowed = allowed_domains
+        .iter()
+        .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+    match allowed {
+        true => None,
+        false => Some(format!(
+            "{} is not one of the allowed domains ({})",
+            host,
+            allowed_domains.join(", ")
+        )),
+    }
+}
+
+// Parses a URL that a request may be sent to without the user looking at it
+// first, e.g. a webhook: http or https to a public host.
+pub fn public_url(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("invalid URL {}: {}", url, e))?;
+    match refusal(&parsed, &[]) {
+        Some(reason) => Err(reason),
+        None => Ok(parsed),
+    }
+}
+
+fn local(host: &str) -> bool {
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+
+    host.trim_matches(['[', ']'])
+        .parse::<IpAddr>()
+        .is_ok_and(local_ip)
+}
+
+// Loopback, private, link-local (169.254.169.254 serves cloud credentials)
+// and shared addresses, also when written as IPv4-mapped IPv6.
+fn local_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let shared = ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64;
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.octets()[0] == 0
+                || shared
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return local_ip(IpAddr::V4(ip));
+            }
+            let unique_local = ip.segments()[0] & 0xfe00 == 0xfc00;
+            let link_local = ip.segments()[0] & 0xffc0 == 0xfe80;
+            ip.is_loopback() || ip.is_unspecified() || unique_local || link_local
+        }
+    }
+}
+
+// Resolves host names for every connection, redirects included, and refuses
+// names with a local address, so a public name can't point the request into
+// the local network. Addresses written in the URL are checked by `refusal`.
+#[derive(Debug)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .collect::<Vec<SocketAddr>>();
+            if let Some(addr) = addrs.iter().find(|addr| local_ip(addr.ip())) {
+                return Err(format!("{} resolves to the local address {}", host, addr.ip()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// reqwest hides why a request failed in the sources of its error.
+fn describe(e: &reqwest::Error) -> String {
+    let mut description = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        description.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    description
+}
+
+// The body as text for the model, HTML converted to markdown-like text. None
+// for binary content.
+fn readable(mime: &str, body: &[u8]) -> Option<String> {
+    match mime {
+        "text/html" | "application/xhtml+xml" => Some(
+            html2text::from_read(body, WIDTH)
+                .unwrap_or_else(|_| String::from_utf8_lossy(body).to_string()),
+        ),
+        "" | "application/json" | "application/xml" | "application/javascript" => {
+            Some(String::from_utf8_lossy(body).to_string())
+        }
+        mime if mime.starts_with("text/") || mime.ends_with("+json") || mime.ends_with("+xml") => {
+            Some(String::from_utf8_lossy(body).to_string())
+        }
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl Tool for WebFetchTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let url = Url::parse(input.url.trim())
+            .map_err(|e| ToolError::ToolError(format!("Invalid URL {}: {}", input.url, e)))?;
+        if let Some(reason) = refusal(&url, &self.allowed_domains) {
+            return Ok(ToolOutput::error(format!(
+                "{} was not fetched: {}",
+                url, reason
+            )));
+        }
+        if !self.confirm(&url)? {
+            return Ok(ToolOutput::error(format!(
+                "The user declined to fetch {}",
+                url
+            )));
+        }
+
+        let allowed_domains = self.allowed_domains.clone();
+        let mut client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .user_agent("voo")
+            // A proxy would resolve the host itself, past the resolver.
+            .no_proxy()
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    return attempt.error("too many redirects");
+                }
+                match refusal(attempt.url(), &allowed_domains) {
+                    Some(reason) => attempt.error(reason),
+                    None => attempt.follow(),
+                }
+            }));
+        // Hosts the user allowed may be local ones.
+        if self.allowed_domains.is_empty() {
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = client
+            .build()
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let response = match client.get(url.clone()).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                return Ok(ToolOutput::error(format!(
+                    "Error fetching {}: {}",
+                    url,
+                    describe(&e)
+                )));
+            }
+        };
+        let status = response.status();
+        if !status.is_success() {
+            return Ok(ToolOutput::error(format!("{} returned {}", url, status)));
+        }
+        let final_url = response.url().clone();
+        let mime = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        let mut body = vec![];
+        let mut truncated = false;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    return Ok(ToolOutput::error(format!("Error reading {}: {}", url, e)));
+                }
+            };
+            body.extend_from_slice(&chunk);
+            if body.len() as u64 >= self.max_bytes {
+                body.truncate(self.max_bytes as usize);
+                truncated = true;
+                break;
+            }
+        }
+
+        let Some(mut text) = readable(&mime, &body) else {
+            return Ok(ToolOutput::error(format!(
+                "{} is {}, not a page that can be read as text",
+                url, mime
+            )));
+        };
+        if final_url != url {
+            text = format!("Redirected to {}\n\n{}", final_url, text);
+        }
+        if truncated {
+            text.push_str(&format!(
+                "\n[truncated, the page is larger than {} bytes]",
+                self.max_bytes
+            ));
+        }
+
+        Ok(ToolOutput::Text(text))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    // Answers every connection with the same page.
+    async fn serve(content_type: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                );
+                _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        format!("http://{}/docs", address)
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch() {
+        let url = serve(
+            "text/html; charset=utf-8",
+            "<html><head><script>track()</script></head><body><h1>Guide</h1><p>Call <code>init</code> first.</p></body></html>",
+        )
+        .await;
+
+        let tool = WebFetchTool::new("web_fetch", "").with_auto_approve(true);
+        let output = tool.exec(json!({ "url": url })).await.unwrap();
+        assert!(output.is_error());
+        let local = url.replace("127.0.0.1", "localhost");
+        let output = tool.exec(json!({ "url": local })).await.unwrap();
+        assert!(output.is_error());
+
+        let tool = tool.with_allowed_domains(&["127.0.0.1"]);
+        let ToolOutput::Text(text) = tool.exec(json!({ "url": url })).await.unwrap() else {
+            panic!("expected text");
+        };
+        assert!(text.contains("# Guide"));
+        assert!(text.contains("Call `init` first."));
+        assert!(!text.contains("track()"));
+
+        let tool = tool.with_max_bytes(20);
+        let ToolOutput::Text(text) = tool.exec(json!({ "url": url })).await.unwrap() else {
+            panic!("expected text");
+        };
+        assert!(text.ends_with("[truncated, the page is larger than 20 bytes]"));
+
+        let url = serve("image/png", "\u{89}PNG").await;
+        let output = tool.exec(json!({ "url": url })).await.unwrap();
+        assert!(output.is_error());
+
+        let allowed = vec!["docs.rs".to_string()];
+        let refused = |url: &str| refusal(&Url::parse(url).unwrap(), &allowed);
+        assert_eq!(refused("https://docs.rs/serde"), None);
+        assert_eq!(refused("https://www.docs.rs/serde"), None);
+        assert!(refused("https://evildocs.rs/").is_some());
+        assert!(refused("file:///etc/passwd").is_some());
+        assert!(refusal(&Url::parse("http://[::1]:8080/").unwrap(), &[]).is_some());
+        assert!(refusal(&Url::parse("http://10.0.0.1/").unwrap(), &[]).is_some());
+        assert!(
+            refusal(
+                &Url::parse("http://[::ffff:169.254.169.254]/latest/meta-data/").unwrap(),
+                &[]
+            )
+            .is_some()
+        );
+        assert!(refusal(&Url::parse("http://100.100.100.200/").unwrap(), &[]).is_some());
+        assert_eq!(
+            refusal(&Url::parse("https://example.com/").unwrap(), &[]),
+            None
+        );
+
+        let resolved = PublicResolver
+            .resolve("localhost".parse().unwrap())
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            resolved
+                .to_string()
+                .contains("resolves to the local address")
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt::Display, sync::Arc};
+
+use async_trait::async_trait;
+use domain::models::{
+    tools::{Tool, ToolDefinition, ToolError, ToolOutput},
+    web_search::SearchEngine,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+const DEFAULT_MAX_RESULTS: usize = 5;
+const MAX_RESULTS: usize = 20;
+
+#[derive(Debug)]
+pub struct WebSearchTool {
+    name: String,
+    description: String,
+    input_schema: WebSearchInput,
+    tool_definition: ToolDefinition,
+    engine: Arc<dyn SearchEngine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebSearchInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub query: String,
+    #[serde(default)]
+    pub max_results: Option<usize>,
+}
+
+impl Display for WebSearchTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl WebSearchTool {
+    pub fn new(name: &str, description: &str, engine: Arc<dyn SearchEngine>) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: WebSearchInput {
+                input: Input {
+                    query: "".to_string(),
+                    max_results: None,
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "query": {
+                                "type": "string",
+                                "description": "What to search the web for, e.g. 'axum middleware from_fn example'"
+                            },
+                            "max_results": {
+                                "type": "integer",
+                                "description": "How many results to return, 5 by default and at most 20"
+                            }
+                        },
+                        "required": ["query"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            engine,
+        }
+    }
+
+    pub fn input_schema(&self) -> &WebSearchInput {
+        &self.input_schema
+    }
+}
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let query = input.query.trim();
+        if query.is_empty() {
+            return Err(ToolError::ToolError("query must not be empty".to_string()));
+        }
+        let limit = input
+            .max_results
+            .unwrap_or(DEFAULT_MAX_RESULTS)
+            .clamp(1, MAX_RESULTS);
+
+        match self.engine.search(query, limit).await {
+            Ok(results) => Ok(ToolOutput::Json(json!({
+                "engine": self.engine.name(),
+                "results": results.into_iter().take(limit).collect::<Vec<_>>(),
+            }))),
+            Err(e) => Ok(ToolOutput::error(format!(
+                "{} could not search for {}: {}",
+                self.engine.name(),
+                query,
+                e
+            ))),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::{agent::AgentError, web_search::SearchResult};
+
+    use super::*;
+
+    // Returns one result per requested slot, numbered.
+    #[derive(Debug)]
+    struct CountingEngine;
+
+    #[async_trait]
+    impl SearchEngine for CountingEngine {
+        fn name(&self) -> String {
+            "counting".to_string()
+        }
+
+        async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, AgentError> {
+            Ok((0..limit)
+                .map(|i| SearchResult {
+                    title: format!("{} {}", query, i),
+                    url: format!("https://example.com/{}", i),
+                    snippet: String::new(),
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_web_search() {
+        let tool = WebSearchTool::new("web_search", "", Arc::new(CountingEngine));
+
+        let ToolOutput::Json(output) = tool.exec(json!({ "query": "axum" })).await.unwrap() else {
+            panic!("expected json");
+        };
+        assert_eq!(output["engine"], "counting");
+        assert_eq!(output["results"].as_array().unwrap().len(), 5);
+        assert_eq!(
+            output["results"][0],
+            json!({ "title": "axum 0", "url": "https://example.com/0", "snippet": "" })
+        );
+
+        let ToolOutput::Json(output) = tool
+            .exec(json!({ "query": "axum", "max_results": 100 }))
+            .await
+            .unwrap()
+        else {
+            panic!("expected json");
+        };
+        assert_eq!(output["results"].as_array().unwrap().len(), 20);
+        assert!(tool.exec(json!({ "query": " " })).await.is_err());
+    }
+}
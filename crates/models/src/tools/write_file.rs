@@ -0,0 +1,371 @@
+use std::{
+    fmt::Display,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use async_trait::async_trait;
+use domain::models::tools::{Tool, ToolDefinition, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+
+use super::{
+    formatter::Formatters,
+    locks::{EditLock, EditLocks, announce},
+    read_policy::ReadPolicy,
+    snapshots::{FileSnapshots, conflict},
+};
+
+#[derive(Debug)]
+pub struct WriteFileTool {
+    name: String,
+    description: String,
+    input_schema: WriteFileInput,
+    tool_definition: ToolDefinition,
+    auto_approve: bool,
+    formatters: Option<Formatters>,
+    read_policy: ReadPolicy,
+    snapshots: Option<FileSnapshots>,
+    locks: Option<EditLocks>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WriteFileInput {
+    input: Input,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    pub content: String,
+}
+
+impl Display for WriteFileTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input_schema = serde_json::to_string(&self.input_schema).unwrap();
+        let name = self.name.clone();
+        let description = self.description.clone();
+
+        let about = format!(
+            "Name: {}\nDescription: {}\n:{}",
+            name, description, input_schema
+        );
+
+        write!(f, "{}", about)
+    }
+}
+
+impl WriteFileTool {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema: WriteFileInput {
+                input: Input {
+                    path: "".to_string(),
+                    content: "".to_string(),
+                },
+            },
+            tool_definition: ToolDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::from_str(
+                    r#"{
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "The path of the file to write"
+                            },
+                            "content": {
+                                "type": "string",
+                                "description": "The complete new contents of the file"
+                            }
+                        },
+                        "required": ["path", "content"]
+                    }"#,
+                )
+                .unwrap(),
+                examples: vec![],
+            },
+            auto_approve: false,
+            formatters: None,
+            read_policy: ReadPolicy::builtin(),
+            snapshots: None,
+            locks: None,
+        }
+    }
+
+    // Writes without asking, for non-interactive use.
+    pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    // Formats written files, the formatter's changes are part of the response.
+    pub fn with_formatters(mut self, formatters: Formatters) -> Self {
+        self.formatters = Some(formatters);
+        self
+    }
+
+    // Refuses to overwrite files that changed on disk since the agent read them.
+    pub fn with_snapshots(mut self, snapshots: FileSnapshots) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    // Keeps the content of denied files out of conflicts.
+    pub fn with_read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn with_locks(mut self, locks: EditLocks) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    pub fn input_schema(&self) -> &WriteFileInput {
+        &self.input_schema
+    }
+
+    fn confirm(&self, path: &str, diff: &str, locks: &[EditLock]) -> Result<bool, ToolError> {
+        if self.auto_approve {
+            return Ok(true);
+        }
+        if !can_ask() {
+            return Ok(false);
+        }
+
+        announce(locks);
+        println!("{}", colorize(diff));
+        ask(&format!("write {}?", path))
+    }
+}
+
+static PROMPTS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+// Makes the tools decline whatever they would ask about, for `voo serve`,
+// where the terminal belongs to whoever started the server and not to the
+// caller.
+pub fn disable_prompts() {
+    PROMPTS_DISABLED.store(true, Ordering::SeqCst);
+}
+
+// Whether there is someone on the terminal to ask.
+pub(crate) fn can_ask() -> bool {
+    !PROMPTS_DISABLED.load(Ordering::SeqCst) && std::io::stdin().is_terminal()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Answer {
+    Yes,
+    No,
+    Edit,
+}
+
+// Asks a yes/no question on the terminal. Declines when there is no terminal
+// to ask on.
+pub(crate) fn ask(question: &str) -> Result<bool, ToolError> {
+    Ok(choose(question, false)? == Answer::Yes)
+}
+
+// Like `ask`, with an edit option the caller handles before asking again.
+pub(crate) fn ask_or_edit(question: &str) -> Result<Answer, ToolError> {
+    choose(question, true)
+}
+
+fn choose(question: &str, edit: bool) -> Result<Answer, ToolError> {
+    if !can_ask() {
+        return Ok(Answer::No);
+    }
+
+    let options = if edit { "[y/n/e]" } else { "[y/n]" };
+    loop {
+        match read_line(&format!("{} {}", question, options))?.as_str() {
+            "y" | "yes" => return Ok(Answer::Yes),
+            "n" | "no" => return Ok(Answer::No),
+            "e" | "edit" if edit => return Ok(Answer::Edit),
+            _ => continue,
+        }
+    }
+}
+
+// Reads one answer from the terminal. A closed terminal is an error rather
+// than an empty answer, which would be asked again forever.
+pub(crate) fn read_line(question: &str) -> Result<String, ToolError> {
+    print!("\x1b[33m{}\x1b[0m ", question);
+    std::io::stdout()
+        .flush()
+        .map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+    let mut answer = String::new();
+    let read = std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| ToolError::ToolError(e.to_string()))?;
+    if read == 0 {
+        return Err(ToolError::ToolError(
+            "The terminal closed before the user answered".to_string(),
+        ));
+    }
+
+    Ok(answer.trim().to_string())
+}
+
+// A file that does not exist yet reads as empty.
+fn read_existing(path: &Path) -> Result<Vec<u8>, ToolError> {
+    match std::fs::read(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(ToolError::ToolError(e.to_string())),
+    }
+}
+
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+pub fn colorize(diff: &str) -> String {
+    diff.lines()
+        .map(|line| match line.chars().next() {
+            Some('+') if !line.starts_with("+++") => format!("\x1b[32m{}\x1b[0m", line),
+            Some('-') if !line.starts_with("---") => format!("\x1b[31m{}\x1b[0m", line),
+            Some('@') => format!("\x1b[36m{}\x1b[0m", line),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        let input = serde_json::from_value::<Input>(input)
+            .map_err(|e| ToolError::ToolError(e.to_string()))?;
+        let buf = PathBuf::from(&input.path);
+        let locks = match &self.locks {
+            Some(locks) => locks.lock_all([buf.as_path()])?,
+            None => vec![],
+        };
+
+        let old = read_existing(&buf)?;
+        if let Some(snapshots) = &self.snapshots
+            && snapshots.changed(&buf, &old)
+        {
+            return Ok(conflict(
+                Some(snapshots),
+                &self.read_policy,
+                &input.path,
+                &buf,
+                &old,
+            ));
+        }
+        let old = String::from_utf8(old)
+            .map_err(|_| ToolError::ToolError(format!("{} is not a text file", input.path)))?;
+        if old == input.content {
+            return Ok(ToolOutput::text(format!("{} is unchanged", input.path)));
+        }
+
+        let diff = unified_diff(&input.path, &old, &input.content);
+        if !self.confirm(&input.path, &diff, &locks)? {
+            return Ok(ToolOutput::error(format!(
+                "The user declined the change to {}, it was not written",
+                input.path
+            )));
+        }
+
+        // The user may have saved the file while the diff was shown.
+        let current = read_existing(&buf)?;
+        if current != old.as_bytes() {
+            return Ok(conflict(
+                self.snapshots.as_ref(),
+                &self.read_policy,
+                &input.path,
+                &buf,
+                &current,
+            ));
+        }
+
+        if let Some(parent) = buf.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| ToolError::ToolError(e.to_string()))?;
+        }
+        std::fs::write(&buf, &input.content).map_err(|e| ToolError::ToolError(e.to_string()))?;
+
+        let mut output = format!("Wrote {} bytes to {}", input.content.len(), input.path);
+        if let Some(formatters) = &self.formatters
+            && let Some(note) = formatters.format(&buf).await
+        {
+            output.push('\n');
+            output.push_str(&note);
+        }
+        if let Some(snapshots) = &self.snapshots
+            && let Ok(written) = std::fs::read(&buf)
+        {
+            snapshots.record(&buf, &written);
+        }
+
+        Ok(ToolOutput::text(output))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn tool_definition(&self) -> &ToolDefinition {
+        &self.tool_definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff("a.txt", "one\ntwo\n", "one\nthree\n");
+        assert_eq!(
+            diff,
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+three\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_approved_write() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("nested/file.txt");
+        let path_str = path.to_string_lossy().to_string();
+        let tool = WriteFileTool::new("write_file", "").with_auto_approve(true);
+
+        let output = tool
+            .exec(json!({"path": path_str, "content": "hello\n"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::text(format!("Wrote 6 bytes to {}", path_str))
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+
+        let output = tool
+            .exec(json!({"path": path_str, "content": "hello\n"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::text(format!("{} is unchanged", path_str))
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{AgentError, Content, Part},
+    transcription::Transcriber,
+};
+
+use crate::media::{GeminiMedia, GenerateRequest, answer};
+
+static MODEL: &str = "gemini-2.0-flash-001";
+static PROMPT: &str = "Transcribe this audio verbatim. Start a new paragraph when the speaker changes and label speakers when you can tell them apart. Reply with the transcript only.";
+
+// Sends the audio with a generateContent request, Gemini models take audio
+// parts like any other. Long recordings are uploaded to the Files API first.
+#[derive(Debug)]
+pub struct GeminiTranscriber {
+    media: Arc<GeminiMedia>,
+    model: String,
+}
+
+impl GeminiTranscriber {
+    pub fn new(media: Arc<GeminiMedia>) -> Self {
+        Self {
+            media,
+            model: MODEL.to_string(),
+        }
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl Transcriber for GeminiTranscriber {
+    fn model(&self) -> String {
+        format!("gemini/{}", self.model)
+    }
+
+    async fn transcribe(
+        &self,
+        mime: &str,
+        audio: &[u8],
+        instructions: Option<&str>,
+    ) -> Result<String, AgentError> {
+        let attachments = self.media.attachments();
+        let audio = attachments.part(mime, audio).await?;
+        let request = request(audio, instructions);
+        let text = self.media.generate(&self.model, &request).await;
+        // An upload is only needed for this one request.
+        attachments.release(&request.contents[0].parts[1]).await;
+        transcript(&text?)
+    }
+}
+
+fn request(audio: Part, instructions: Option<&str>) -> GenerateRequest {
+    let prompt = match instructions {
+        Some(instructions) => format!("{}\n\n{}", PROMPT, instructions),
+        None => PROMPT.to_string(),
+    };
+
+    GenerateRequest {
+        contents: vec![Content::new(vec![Part::new(&prompt), audio], "user")],
+    }
+}
+
+fn transcript(body: &str) -> Result<String, AgentError> {
+    answer(body)?
+        .ok_or_else(|| AgentError::AgentError(Some("The model returned no transcript".to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_transcription() {
+        let request = serde_json::to_value(request(
+            Part::inline_data("audio/wav", b"RIFF"),
+            Some("As notes."),
+        ))
+        .unwrap();
+        assert_eq!(
+            request["contents"][0]["parts"][1],
+            json!({ "inlineData": { "mimeType": "audio/wav", "data": "UklGRg==" } })
+        );
+        assert!(
+            request["contents"][0]["parts"][0]["text"]
+                .as_str()
+                .unwrap()
+                .ends_with("\n\nAs notes.")
+        );
+
+        let body = json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{ "text": "Alice: hello\n" }, { "text": "Bob: hi" }]
+                }
+            }]
+        });
+        assert_eq!(
+            transcript(&body.to_string()).unwrap(),
+            "Alice: hello\nBob: hi"
+        );
+        assert!(transcript(r#"{"candidates": []}"#).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{AgentError, Content, Part},
+    video::VideoAnalyst,
+};
+use url::Url;
+
+use crate::media::{GeminiMedia, GenerateRequest, answer};
+
+static MODEL: &str = "gemini-2.5-flash";
+static PROMPT: &str = "Answer the question about the attached video. Refer to moments in the video with MM:SS timestamps where that helps.";
+const YOUTUBE_HOSTS: &[&str] = &[
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "youtu.be",
+];
+
+// Hands the URL of the video to a Gemini model as a file part, Gemini fetches
+// and watches public YouTube videos itself.
+#[derive(Debug)]
+pub struct GeminiVideoAnalyst {
+    media: Arc<GeminiMedia>,
+    model: String,
+}
+
+impl GeminiVideoAnalyst {
+    pub fn new(media: Arc<GeminiMedia>) -> Self {
+        Self {
+            media,
+            model: MODEL.to_string(),
+        }
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+}
+
+// Whether `url` is a YouTube video, the only URLs Gemini watches.
+pub fn youtube_url(url: &str) -> bool {
+    let Ok(url) = Url::parse(url.trim()) else {
+        return false;
+    };
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    matches!(url.scheme(), "http" | "https") && YOUTUBE_HOSTS.contains(&host.as_str())
+}
+
+#[async_trait]
+impl VideoAnalyst for GeminiVideoAnalyst {
+    fn model(&self) -> String {
+        format!("gemini/{}", self.model)
+    }
+
+    async fn ask(&self, url: &str, question: &str) -> Result<String, AgentError> {
+        if !youtube_url(url) {
+            return Err(AgentError::AgentError(Some(format!(
+                "{} is not a YouTube video",
+                url
+            ))));
+        }
+
+        let text = self
+            .media
+            .generate(&self.model, &request(url, question))
+            .await?;
+        answer(&text)?
+            .ok_or_else(|| AgentError::AgentError(Some("The model returned no answer".to_string())))
+    }
+}
+
+fn request(url: &str, question: &str) -> GenerateRequest {
+    GenerateRequest {
+        contents: vec![Content::new(
+            vec![
+                Part::file_data(None, url.trim()),
+                Part::new(&format!("{}\n\n{}", PROMPT, question)),
+            ],
+            "user",
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_video() {
+        assert!(youtube_url("https://www.youtube.com/watch?v=9hE5-98ZeCg"));
+        assert!(youtube_url("https://youtu.be/9hE5-98ZeCg"));
+        assert!(!youtube_url("https://example.com/watch?v=9hE5-98ZeCg"));
+        assert!(!youtube_url("file:///tmp/talk.mp4"));
+
+        let request = serde_json::to_value(request(
+            "https://youtu.be/9hE5-98ZeCg ",
+            "What is it about?",
+        ))
+        .unwrap();
+        assert_eq!(
+            request["contents"][0]["parts"][0],
+            json!({ "fileData": { "fileUri": "https://youtu.be/9hE5-98ZeCg" } })
+        );
+        assert!(
+            request["contents"][0]["parts"][1]["text"]
+                .as_str()
+                .unwrap()
+                .ends_with("\n\nWhat is it about?")
+        );
+    }
+}
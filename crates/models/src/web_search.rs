@@ -0,0 +1,334 @@
+use std::{sync::LazyLock, time::Duration};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::AgentError,
+    web_search::{SearchEngine, SearchResult},
+};
+use regex::Regex;
+use serde::Deserialize;
+use url::Url;
+
+static BRAVE_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+static DUCKDUCKGO_URL: &str = "https://html.duckduckgo.com/html/";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+static DUCKDUCKGO_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)class="result__a"[^>]*href="([^"]*)"[^>]*>(.*?)</a>"#).unwrap()
+});
+static DUCKDUCKGO_SNIPPET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?s)class="result__snippet"[^>]*>(.*?)</a>"#).unwrap());
+
+// The Brave Search API, it needs a subscription token.
+#[derive(Debug)]
+pub struct BraveSearch {
+    api_key: String,
+    reqwest: reqwest::Client,
+}
+
+impl BraveSearch {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            reqwest: client(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BraveResponse {
+    #[serde(default)]
+    web: BraveResults,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BraveResults {
+    #[serde(default)]
+    results: Vec<BraveResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    description: String,
+}
+
+#[async_trait]
+impl SearchEngine for BraveSearch {
+    fn name(&self) -> String {
+        "brave".to_string()
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, AgentError> {
+        let request = self
+            .reqwest
+            .get(BRAVE_URL)
+            .query(&[("q", query), ("count", &limit.to_string())])
+            .header("Accept", "application/json")
+            .header("X-Subscription-Token", &self.api_key);
+        let body = send(request).await?;
+
+        Ok(brave_results(&body)?.into_iter().take(limit).collect())
+    }
+}
+
+fn brave_results(body: &str) -> Result<Vec<SearchResult>, AgentError> {
+    let response = serde_json::from_str::<BraveResponse>(body)
+        .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+    Ok(response
+        .web
+        .results
+        .into_iter()
+        .map(|result| SearchResult {
+            title: text(&result.title),
+            url: result.url,
+            snippet: text(&result.description),
+        })
+        .collect())
+}
+
+// A SearXNG instance, with the json format enabled in its settings.
+#[derive(Debug)]
+pub struct SearxngSearch {
+    base_url: String,
+    reqwest: reqwest::Client,
+}
+
+impl SearxngSearch {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            reqwest: client(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResponse {
+    #[serde(default)]
+    results: Vec<SearxngResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    content: String,
+}
+
+#[async_trait]
+impl SearchEngine for SearxngSearch {
+    fn name(&self) -> String {
+        format!("searxng ({})", self.base_url)
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, AgentError> {
+        let request = self
+            .reqwest
+            .get(format!("{}/search", self.base_url))
+            .query(&[("q", query), ("format", "json")]);
+        let body = send(request).await?;
+
+        let response = serde_json::from_str::<SearxngResponse>(&body)
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        Ok(response
+            .results
+            .into_iter()
+            .take(limit)
+            .map(|result| SearchResult {
+                title: text(&result.title),
+                url: result.url,
+                snippet: text(&result.content),
+            })
+            .collect())
+    }
+}
+
+// The HTML version of DuckDuckGo, which needs no key. Its markup may change,
+// the other engines are more reliable.
+#[derive(Debug)]
+pub struct DuckDuckGoSearch {
+    reqwest: reqwest::Client,
+}
+
+impl Default for DuckDuckGoSearch {
+    fn default() -> Self {
+        Self { reqwest: client() }
+    }
+}
+
+#[async_trait]
+impl SearchEngine for DuckDuckGoSearch {
+    fn name(&self) -> String {
+        "duckduckgo".to_string()
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, AgentError> {
+        let request = self
+            .reqwest
+            .get(DUCKDUCKGO_URL)
+            .query(&[("q", query)])
+            .header("User-Agent", "Mozilla/5.0 (compatible; voo)");
+        let body = send(request).await?;
+
+        Ok(duckduckgo_results(&body).into_iter().take(limit).collect())
+    }
+}
+
+// Each result is the block from its link up to the next one, so a result
+// without a snippet does not take the snippet of the one after it.
+fn duckduckgo_results(html: &str) -> Vec<SearchResult> {
+    let links = DUCKDUCKGO_LINK.captures_iter(html).collect::<Vec<_>>();
+    links
+        .iter()
+        .enumerate()
+        .filter_map(|(index, captures)| {
+            let start = captures.get(0)?.end();
+            let end = links
+                .get(index + 1)
+                .and_then(|next| next.get(0))
+                .map_or(html.len(), |next| next.start());
+            let snippet = DUCKDUCKGO_SNIPPET
+                .captures(&html[start..end])
+                .map(|snippet| text(&snippet[1]))
+                .unwrap_or_default();
+            Some(SearchResult {
+                title: text(&captures[2]),
+                url: duckduckgo_target(&entities(&captures[1]))?,
+                snippet,
+            })
+        })
+        .collect()
+}
+
+// The page a result link redirects to. Ads point back to DuckDuckGo and are
+// dropped.
+fn duckduckgo_target(href: &str) -> Option<String> {
+    let href = match href.starts_with("//") {
+        true => format!("https:{}", href),
+        false => href.to_string(),
+    };
+    let url = Url::parse(&href).ok()?;
+    let target = match url.host_str() {
+        Some("duckduckgo.com") if url.path() == "/l/" => url
+            .query_pairs()
+            .find(|(key, _)| key == "uddg")
+            .map(|(_, target)| target.to_string())?,
+        Some(host) if host.ends_with("duckduckgo.com") => return None,
+        _ => href,
+    };
+
+    Url::parse(&target)
+        .ok()
+        .filter(|url| {
+            !url.host_str()
+                .unwrap_or_default()
+                .ends_with("duckduckgo.com")
+        })
+        .map(|url| url.to_string())
+}
+
+// Plain text of an HTML fragment.
+fn text(html: &str) -> String {
+    let text = entities(&TAG.replace_all(html, ""));
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<String, AgentError> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| AgentError::Network(e.to_string()))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| AgentError::Network(e.to_string()))?;
+
+    match status.as_u16() {
+        200..=299 => Ok(text),
+        429 => Err(AgentError::RateLimited {
+            retry_after: None,
+            message: text,
+        }),
+        _ => Err(AgentError::AgentError(Some(format!(
+            "search {}: {}",
+            status, text
+        )))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_web_search() {
+        let body = json!({
+            "web": { "results": [{
+                "title": "serde - Rust",
+                "url": "https://docs.rs/serde",
+                "description": "A <strong>serialization</strong> framework &amp; more"
+            }]}
+        });
+        assert_eq!(
+            brave_results(&body.to_string()).unwrap(),
+            [SearchResult {
+                title: "serde - Rust".to_string(),
+                url: "https://docs.rs/serde".to_string(),
+                snippet: "A serialization framework & more".to_string(),
+            }]
+        );
+
+        let html = r#"
+<div class="result results_links results_links_deep result--ad">
+  <a rel="nofollow" class="result__a" href="https://duckduckgo.com/y.js?ad_domain=example.com">Ad</a>
+  <a class="result__snippet" href="https://duckduckgo.com/y.js">Buy now</a>
+</div>
+<div class="result results_links results_links_deep web-result">
+  <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdocs.rs%2Ftokio%2Flatest%2Ftokio%2F&amp;rut=abc"><b>Tokio</b> - Rust</a>
+  <a class="result__snippet" href="//duckduckgo.com/l/?uddg=x">A runtime for writing <b>reliable</b>
+  network applications</a>
+</div>
+<div class="result results_links results_links_deep web-result">
+  <a rel="nofollow" class="result__a" href="https://tokio.rs/">Tokio</a>
+</div>
+<div class="result results_links results_links_deep web-result">
+  <a rel="nofollow" class="result__a" href="https://docs.rs/mio">mio - Rust</a>
+  <a class="result__snippet" href="https://docs.rs/mio">Lightweight non-blocking I/O</a>
+</div>"#;
+        let results = duckduckgo_results(html);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].title, "Tokio - Rust");
+        assert_eq!(results[0].url, "https://docs.rs/tokio/latest/tokio/");
+        assert_eq!(
+            results[0].snippet,
+            "A runtime for writing reliable network applications"
+        );
+        assert_eq!(results[1].snippet, "");
+        assert_eq!(results[2].snippet, "Lightweight non-blocking I/O");
+    }
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use models::models::gemini::{GeminiResponse, take_events};
+
+// Feeds arbitrary bytes through the parsing of both a complete response and a
+// streamed one, which is split into server-sent events first.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(response) = serde_json::from_slice::<GeminiResponse>(data) {
+        _ = response.block_reason();
+    }
+
+    let mut buffer = data.to_vec();
+    for event in take_events(&mut buffer) {
+        if let Ok(response) = serde_json::from_str::<GeminiResponse>(&event) {
+            _ = response.block_reason();
+        }
+    }
+});
@@ -0,0 +1,53 @@
+use std::io::Read;
+
+use domain::models::agent::AgentClient;
+
+use crate::providers;
+
+// Answers a single question without tools and prints the answer. With a
+// video, Gemini watches it and answers about it whatever the provider.
+pub async fn run(prompt: &[String], video: Option<&str>) -> anyhow::Result<()> {
+    let prompt = match prompt.is_empty() {
+        true => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| anyhow::anyhow!("Error reading stdin: {}", e))?;
+            input
+        }
+        false => prompt.join(" "),
+    };
+
+    let answer = match video {
+        Some(url) => {
+            let question = match prompt.trim().is_empty() {
+                true => "Summarize the video.",
+                false => prompt.trim(),
+            };
+            let media = crate::gemini_media()?
+                .ok_or_else(|| anyhow::anyhow!("Watching videos needs GEMINI_API_KEY"))?;
+            let analyst = crate::video_analyst(media);
+            analyst
+                .ask(url, question)
+                .await
+                .map_err(|e| anyhow::anyhow!("Error asking about {}: {}", url, e))?
+        }
+        None => {
+            if prompt.trim().is_empty() {
+                return Err(anyhow::anyhow!("There is no question to ask"));
+            }
+            providers::registry()
+                .build(&providers::selected())?
+                .ask(&prompt)
+                .await
+                .map_err(|e| anyhow::anyhow!("Error asking the model: {}", e))?
+                .iter()
+                .flat_map(|response| &response.parts)
+                .filter_map(|part| part.answer().cloned())
+                .collect::<String>()
+        }
+    };
+    println!("{}", answer.trim_end());
+
+    Ok(())
+}
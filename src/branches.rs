@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use domain::models::{
+    agent::{Agent, Content, Part},
+    transcript::{Transcript, TranscriptSummary},
+};
+
+use crate::{handoff::portable_history, redact, sessions, terminal::styled_println};
+
+// `/fork [title]` goes on in a new session with a copy of the history, the
+// current one stays as it is to come back to with `/branches`.
+pub fn fork(transcript: &mut Transcript, args: &str) -> anyhow::Result<()> {
+    let title = Some(args.trim().to_string()).filter(|title| !title.is_empty());
+    let child = transcript
+        .fork(title)
+        .map_err(|e| anyhow::anyhow!("Error forking session: {}", e))?;
+
+    styled_println!(
+        "\x1b[32mfork>\x1b[0m {} forked from {}",
+        child.id(),
+        transcript.id()
+    );
+    *transcript = child;
+
+    Ok(())
+}
+
+// `/branches` shows the tree of forks the session belongs to, `/branches
+// <id|title>` switches to another branch of it.
+pub async fn run(agent: &mut Agent, args: &str, transcript: &mut Transcript) -> anyhow::Result<()> {
+    let storage = sessions::storage()?;
+    let summaries = storage
+        .sessions()
+        .map_err(|e| anyhow::anyhow!("Error listing sessions: {}", e))?;
+    let branches = tree(&summaries, transcript.id());
+
+    let target = args.trim();
+    if target.is_empty() {
+        for (depth, branch) in &branches {
+            let current = match branch.id == transcript.id() {
+                true => "* ",
+                false => "  ",
+            };
+            styled_println!(
+                "{}{}{}  {} turns  {}",
+                "  ".repeat(*depth),
+                current,
+                branch.id,
+                branch.turns,
+                label(branch)
+            );
+        }
+        if branches.len() == 1 {
+            styled_println!("\x1b[2mno forks yet, /fork [title] starts one\x1b[0m");
+        }
+        return Ok(());
+    }
+
+    let matches = branches
+        .iter()
+        .map(|(_, branch)| *branch)
+        .filter(|branch| branch.id.starts_with(target) || branch.title.as_deref() == Some(target))
+        .collect::<Vec<&TranscriptSummary>>();
+    let branch = match matches.as_slice() {
+        [branch] => *branch,
+        [] => return Err(anyhow::anyhow!("No branch {} in this session tree", target)),
+        _ => return Err(anyhow::anyhow!("{} matches several branches", target)),
+    };
+    if branch.id == transcript.id() {
+        return Err(anyhow::anyhow!("Already on {}", branch.id));
+    }
+
+    let next = Transcript::open(storage, &branch.id)
+        .map_err(|e| anyhow::anyhow!("Error opening branch {}: {}", branch.id, e))?;
+    let entries = next
+        .load()
+        .map_err(|e| anyhow::anyhow!("Error loading branch {}: {}", branch.id, e))?;
+    let contents = portable_history(&entries)
+        .into_iter()
+        .map(|(role, text)| Content::new(vec![Part::new(&text)], &role.to_string()))
+        .collect::<Vec<Content>>();
+    agent
+        .rewrite_history(&contents)
+        .map_err(|e| anyhow::anyhow!("Error rewriting conversation: {}", e))?;
+    redact::reload(agent, &entries).await?;
+
+    styled_println!(
+        "\x1b[32mbranches>\x1b[0m switched to {} ({} messages)",
+        branch.id,
+        contents.len()
+    );
+    *transcript = next;
+
+    Ok(())
+}
+
+// The sessions of the tree `current` is part of, from its root down, each
+// with its depth. Children are ordered by id, which starts with the time they
+// were forked.
+fn tree<'a>(
+    sessions: &'a [TranscriptSummary],
+    current: &str,
+) -> Vec<(usize, &'a TranscriptSummary)> {
+    let find = |id: &str| sessions.iter().find(|session| session.id == id);
+
+    let mut root = current.to_string();
+    let mut seen = HashSet::new();
+    while seen.insert(root.clone()) {
+        match find(&root).and_then(|session| session.parent.clone()) {
+            Some(parent) if find(&parent).is_some() => root = parent,
+            _ => break,
+        }
+    }
+
+    let mut branches = vec![];
+    let mut pending = find(&root)
+        .map(|session| (0, session))
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    while let Some((depth, session)) = pending.pop() {
+        if !visited.insert(&session.id) {
+            continue;
+        }
+        branches.push((depth, session));
+
+        let mut children = sessions
+            .iter()
+            .filter(|child| child.parent.as_deref() == Some(session.id.as_str()))
+            .collect::<Vec<&TranscriptSummary>>();
+        children.sort_by(|a, b| b.id.cmp(&a.id));
+        pending.extend(children.into_iter().map(|child| (depth + 1, child)));
+    }
+
+    branches
+}
+
+fn label(session: &TranscriptSummary) -> String {
+    match &session.title {
+        Some(title) => format!("\"{}\"", title),
+        None => {
+            let text = session.first_message.as_deref().unwrap_or_default();
+            let line = text.lines().next().unwrap_or_default();
+            match line.char_indices().nth(60) {
+                Some((end, _)) => format!("{}...", &line[..end]),
+                None => line.to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, parent: Option<&str>) -> TranscriptSummary {
+        TranscriptSummary {
+            id: id.to_string(),
+            started_at: 0,
+            turns: 1,
+            first_message: Some("hello".to_string()),
+            prompt_version: None,
+            parent: parent.map(str::to_string),
+            title: None,
+        }
+    }
+
+    #[test]
+    fn test_tree() {
+        let sessions = vec![
+            session("3-c", Some("1-a")),
+            session("2-b", Some("1-a")),
+            session("1-a", None),
+            session("4-d", Some("2-b")),
+            session("5-e", None),
+        ];
+
+        let branches = tree(&sessions, "4-d")
+            .into_iter()
+            .map(|(depth, session)| (depth, session.id.as_str()))
+            .collect::<Vec<(usize, &str)>>();
+        assert_eq!(
+            branches,
+            vec![(0, "1-a"), (1, "2-b"), (2, "4-d"), (1, "3-c")]
+        );
+
+        assert_eq!(tree(&sessions, "5-e").len(), 1);
+        // A parent that was deleted leaves the fork as the root.
+        let orphan = vec![session("2-b", Some("1-a"))];
+        assert_eq!(tree(&orphan, "2-b")[0].1.id, "2-b");
+    }
+}
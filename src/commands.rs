@@ -0,0 +1,420 @@
+use std::path::Path;
+
+use domain::models::{
+    agent::{Agent, AgentRole},
+    mode::Mode,
+};
+use models::{
+    capabilities::CapabilityRegistry,
+    git::{checkpoint, checkpoints, restore},
+    media::media_mime,
+};
+
+use crate::{config, providers, terminal::styled_println, tree};
+
+// Media is uploaded when it is too large to go inline, this keeps it to what
+// is reasonable to send with every request.
+const MAX_ATTACHMENT_BYTES: u64 = 200 * 1024 * 1024;
+
+pub async fn handle(agent: &Agent, input: &str) -> bool {
+    let input = input.trim();
+    if !input.starts_with('/') {
+        return false;
+    }
+
+    let (command, args) = input
+        .split_once(char::is_whitespace)
+        .map(|(command, args)| (command, args.trim()))
+        .unwrap_or((input, ""));
+
+    match command {
+        "/status" => print_status(agent).await,
+        "/usage" => print_usage(agent),
+        "/env" => env(agent, args).await,
+        "/tools" => tools(agent, args).await,
+        "/mode" => mode(agent, args).await,
+        "/tree" => show_tree(agent, args).await,
+        "/attach" => attach(agent, args).await,
+        "/attachments" => list_attachments(agent).await,
+        "/detach" => detach(agent, args).await,
+        "/checkpoints" => list_checkpoints().await,
+        "/restore-checkpoint" => restore_checkpoint(args).await,
+        _ => styled_println!("\x1b[41mvoo>\x1b[0m Unknown command: {}", command),
+    }
+
+    true
+}
+
+async fn print_status(agent: &Agent) {
+    let client = agent.client();
+    let health = client.health().await;
+    let provider = health
+        .as_ref()
+        .map(|health| health.provider.clone())
+        .unwrap_or_else(providers::selected);
+    let model = client.model();
+
+    let names = agent
+        .tools()
+        .lock()
+        .await
+        .iter()
+        .map(|registered| registered.tool.name().to_string())
+        .collect::<Vec<String>>();
+    let mut tools = vec![];
+    for name in names {
+        if agent.allows_tool(&name).await {
+            tools.push(name);
+        }
+    }
+
+    let context = match (client.estimate_tokens("").await, model.as_deref()) {
+        (Some(used), Some(model)) => match capabilities(model).get(model) {
+            Some(capabilities) => format!(
+                "~{} of {} tokens ({:.1}%)",
+                used,
+                capabilities.context_window,
+                used as f64 * 100.0 / capabilities.context_window as f64
+            ),
+            None => format!("~{} tokens, context window unknown", used),
+        },
+        (Some(used), None) => format!("~{} tokens, context window unknown", used),
+        (None, _) => "unknown".to_string(),
+    };
+    let rate_limit = match client.rate_limit().await {
+        Some(status) => status.to_string(),
+        None => "not limited by voo".to_string(),
+    };
+    let workspace = std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|e| format!("unknown ({})", e));
+
+    let rows = [
+        (
+            "model",
+            format!(
+                "{}/{}",
+                provider,
+                model.as_deref().unwrap_or("(set by the endpoint)")
+            ),
+        ),
+        ("mode", agent.mode().await.to_string()),
+        ("workspace", workspace),
+        ("tools", format!("{} ({})", tools.join(", "), tools.len())),
+        ("context", context),
+        ("rate limit", rate_limit),
+    ];
+    for (name, value) in rows {
+        styled_println!("\x1b[32mstatus>\x1b[0m {:<10} {}", name, value);
+    }
+
+    match health {
+        Some(health) if health.is_available() => {
+            styled_println!("\x1b[32mstatus>\x1b[0m {:<10} {}", "health", health);
+        }
+        Some(health) => {
+            styled_println!("\x1b[41mstatus>\x1b[0m {:<10} {}", "health", health);
+        }
+        None => styled_println!("\x1b[32mstatus>\x1b[0m {:<10} unknown", "health"),
+    }
+}
+
+fn print_usage(agent: &Agent) {
+    match agent.client().usage() {
+        Some(usage) => styled_println!("\x1b[32musage>\x1b[0m {}", usage),
+        None => styled_println!("\x1b[32musage>\x1b[0m the provider doesn't report token usage"),
+    }
+}
+
+// VOO_CONTEXT_WINDOW sets the context window of the current model, e.g. for a
+// local model voo doesn't know.
+fn capabilities(model: &str) -> CapabilityRegistry {
+    let registry = CapabilityRegistry::builtin();
+    match config::var("VOO_CONTEXT_WINDOW")
+        .ok()
+        .and_then(|tokens| tokens.trim().parse().ok())
+    {
+        Some(tokens) => registry.with_context_window(model, tokens),
+        None => registry,
+    }
+}
+
+async fn env(agent: &Agent, args: &str) {
+    let mut args = args.splitn(3, char::is_whitespace);
+
+    match (args.next(), args.next(), args.next()) {
+        (Some("set"), Some(key), Some(value)) => {
+            agent.set_env(key, value.trim()).await;
+            styled_println!("\x1b[32menv>\x1b[0m {} set for tool execution", key);
+        }
+        (Some("unset"), Some(key), None) => {
+            if agent.unset_env(key).await {
+                styled_println!("\x1b[32menv>\x1b[0m {} unset", key);
+            } else {
+                styled_println!("\x1b[41menv>\x1b[0m {} is not set", key);
+            }
+        }
+        (None | Some("list"), None, None) => {
+            let keys = agent.env_keys().await;
+            if keys.is_empty() {
+                styled_println!("\x1b[32menv>\x1b[0m no variables set");
+            }
+
+            for key in keys {
+                styled_println!("\x1b[32menv>\x1b[0m {}=********", key);
+            }
+        }
+        _ => styled_println!(
+            "\x1b[41menv>\x1b[0m usage: /env [list | set <KEY> <VALUE> | unset <KEY>]"
+        ),
+    }
+}
+
+async fn tools(agent: &Agent, args: &str) {
+    let mut args = args.split_whitespace();
+
+    match (args.next(), args.next(), args.next()) {
+        (Some(action @ ("enable" | "disable")), Some(name), None) => {
+            let enabled = action == "enable";
+            match agent.set_tool_enabled(name, enabled).await {
+                Ok(()) => styled_println!("\x1b[32mtools>\x1b[0m {} {}d", name, action),
+                Err(e) => styled_println!("\x1b[41mtools>\x1b[0m {}", e),
+            }
+        }
+        (None | Some("list"), None, None) => {
+            let tools = agent.tools();
+            let tools = tools.lock().await;
+            if tools.is_empty() {
+                styled_println!("\x1b[32mtools>\x1b[0m no tools registered");
+            }
+
+            for registered in tools.iter() {
+                styled_println!(
+                    "\x1b[32mtools>\x1b[0m {:<18} {:<8} {:<10} {}",
+                    registered.tool.name(),
+                    registered.metadata.category,
+                    registered.metadata.danger,
+                    if registered.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                );
+            }
+        }
+        _ => styled_println!(
+            "\x1b[41mtools>\x1b[0m usage: /tools [list | enable <NAME> | disable <NAME>]"
+        ),
+    }
+}
+
+async fn mode(agent: &Agent, args: &str) {
+    if args.is_empty() {
+        let modes = Mode::ALL
+            .iter()
+            .map(|mode| mode.to_string().to_lowercase())
+            .collect::<Vec<String>>()
+            .join(", ");
+        styled_println!(
+            "\x1b[32mmode>\x1b[0m {} mode (available: {})",
+            agent.mode().await,
+            modes
+        );
+        return;
+    }
+
+    let Some(mode) = Mode::parse(args) else {
+        styled_println!("\x1b[41mmode>\x1b[0m Unknown mode: {}", args);
+        return;
+    };
+    match agent.set_mode(mode).await {
+        Ok(()) => styled_println!("\x1b[32mmode>\x1b[0m switched to {} mode", mode),
+        Err(e) => styled_println!("\x1b[41mmode>\x1b[0m {}", e),
+    }
+}
+
+async fn list_checkpoints() {
+    match checkpoints(Path::new(".")).await {
+        Ok(checkpoints) if checkpoints.is_empty() => {
+            styled_println!("\x1b[32mcheckpoint>\x1b[0m no checkpoints yet")
+        }
+        Ok(checkpoints) => {
+            for checkpoint in checkpoints {
+                styled_println!(
+                    "\x1b[32mcheckpoint>\x1b[0m {} {}",
+                    checkpoint.id,
+                    checkpoint.message
+                );
+            }
+        }
+        Err(e) => styled_println!("\x1b[41mcheckpoint>\x1b[0m {}", e),
+    }
+}
+
+// Restores the newest checkpoint, or the one with the given id. The current
+// files are checkpointed first so the restore itself can be undone.
+async fn restore_checkpoint(args: &str) {
+    let dir = Path::new(".");
+    let found = match checkpoints(dir).await {
+        Ok(checkpoints) => checkpoints
+            .into_iter()
+            .find(|checkpoint| args.is_empty() || checkpoint.id == args),
+        Err(e) => {
+            styled_println!("\x1b[41mcheckpoint>\x1b[0m {}", e);
+            return;
+        }
+    };
+    let Some(found) = found else {
+        styled_println!("\x1b[41mcheckpoint>\x1b[0m no checkpoint to restore, see /checkpoints");
+        return;
+    };
+
+    let message = format!("before restoring {}", found.id);
+    let result = match checkpoint(dir, &message).await {
+        Ok(current) => restore(dir, &found).await.map(|_| current),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(current) => styled_println!(
+            "\x1b[32mcheckpoint>\x1b[0m restored {} ({}), /restore-checkpoint {} brings the previous files back",
+            found.id,
+            found.message,
+            current.id
+        ),
+        Err(e) => styled_println!("\x1b[41mcheckpoint>\x1b[0m {}", e),
+    }
+}
+
+async fn show_tree(agent: &Agent, args: &str) {
+    let attach = args.split_whitespace().any(|arg| arg == "--attach");
+    let args = args
+        .split_whitespace()
+        .filter(|arg| *arg != "--attach")
+        .collect::<Vec<&str>>();
+
+    let (path, depth) = match args.as_slice() {
+        [] => (".", Some(tree::DEFAULT_DEPTH)),
+        [depth] if depth.parse::<usize>().is_ok() => (".", depth.parse().ok()),
+        [path] => (*path, Some(tree::DEFAULT_DEPTH)),
+        [path, depth] => (*path, depth.parse().ok()),
+        _ => (".", None),
+    };
+    let Some(depth) = depth else {
+        styled_println!("\x1b[41mtree>\x1b[0m usage: /tree [path] [depth] [--attach]");
+        return;
+    };
+
+    match tree::render(Path::new(path), depth) {
+        Ok(tree) => {
+            print!("{}", tree);
+            if attach {
+                let message = format!("The file tree of {}:\n{}", path, tree);
+                match agent
+                    .client()
+                    .add_system_prompt(&message, AgentRole::User)
+                    .await
+                {
+                    Ok(()) => styled_println!("\x1b[32mtree>\x1b[0m attached to the conversation"),
+                    Err(e) => styled_println!("\x1b[41mtree>\x1b[0m {}", e),
+                }
+            }
+        }
+        Err(e) => styled_println!("\x1b[41mtree>\x1b[0m {}", e),
+    }
+}
+
+async fn attach(agent: &Agent, args: &str) {
+    if args.is_empty() {
+        styled_println!("\x1b[41mattach>\x1b[0m usage: /attach <path>");
+        return;
+    }
+
+    let path = Path::new(args);
+    let read_policy = match crate::read_policy() {
+        Ok(read_policy) => read_policy,
+        Err(e) => {
+            styled_println!("\x1b[41mattach>\x1b[0m {}", e);
+            return;
+        }
+    };
+    // Text goes with the read limit, media may be as large as an upload.
+    let read_policy = match media_mime(path) {
+        Some(_) => read_policy.with_max_bytes(MAX_ATTACHMENT_BYTES),
+        None => read_policy,
+    };
+    if let Err(reason) = read_policy.check(path) {
+        styled_println!("\x1b[41mattach>\x1b[0m {}", reason);
+        return;
+    }
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            styled_println!("\x1b[41mattach>\x1b[0m {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let mime = attachment_mime(path, &data);
+    match agent.attach(args, mime, data).await {
+        Ok(attachment) => styled_println!(
+            "\x1b[32mattach>\x1b[0m {} attached as {}, {} and ~{} tokens with every request",
+            attachment.name,
+            attachment.id,
+            tree::human_size(attachment.bytes),
+            attachment.tokens
+        ),
+        Err(e) => styled_println!("\x1b[41mattach>\x1b[0m {}", e),
+    }
+}
+
+async fn list_attachments(agent: &Agent) {
+    let attachments = agent.client().attachments().await;
+    if attachments.is_empty() {
+        styled_println!("\x1b[32mattach>\x1b[0m no files attached, see /attach <path>");
+        return;
+    }
+
+    for attachment in &attachments {
+        styled_println!(
+            "\x1b[32mattach>\x1b[0m {:<3} {:<30} {:<18} {:>9} ~{:<7} {}",
+            attachment.id,
+            attachment.name,
+            attachment.mime,
+            tree::human_size(attachment.bytes),
+            attachment.tokens,
+            if attachment.uploaded {
+                "uploaded"
+            } else {
+                "inline"
+            }
+        );
+    }
+    styled_println!(
+        "\x1b[32mattach>\x1b[0m ~{} tokens in total",
+        attachments
+            .iter()
+            .map(|attachment| attachment.tokens)
+            .sum::<u64>()
+    );
+}
+
+async fn detach(agent: &Agent, args: &str) {
+    if args.is_empty() {
+        styled_println!("\x1b[41mattach>\x1b[0m usage: /detach <id>");
+        return;
+    }
+
+    match agent.detach(args).await {
+        Ok(attachment) => styled_println!("\x1b[32mattach>\x1b[0m {} detached", attachment.name),
+        Err(e) => styled_println!("\x1b[41mattach>\x1b[0m {}", e),
+    }
+}
+
+// Text files are attached as text whatever their extension, other files by
+// the type their extension names.
+fn attachment_mime(path: &Path, data: &[u8]) -> &'static str {
+    match media_mime(path) {
+        Some(media) => media,
+        None if std::str::from_utf8(data).is_ok() && !data.contains(&0) => "text/plain",
+        None => "application/octet-stream",
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use domain::models::agent::SystemPrompt;
+use serde::{Deserialize, Serialize};
+
+use crate::{paths::data_dir, serve::Role};
+
+pub const PROJECT_CONFIG: &str = "voo.toml";
+
+// The environment variables the model and API key of each provider are read
+// from. Values from the config file fill in the ones that are not set.
+const MODEL_VARS: &[(&str, &str)] = &[
+    ("gemini", "VOO_GEMINI_MODEL"),
+    ("openai", "VOO_OPENAI_MODEL"),
+    ("bedrock", "VOO_BEDROCK_MODEL"),
+    ("azure", "AZURE_OPENAI_DEPLOYMENT"),
+    ("mistral", "VOO_MISTRAL_MODEL"),
+    ("groq", "VOO_GROQ_MODEL"),
+    ("xai", "VOO_XAI_MODEL"),
+    ("deepseek", "VOO_DEEPSEEK_MODEL"),
+    ("ollama", "VOO_OLLAMA_MODEL"),
+    ("llama-cpp", "VOO_LLAMA_MODEL_PATH"),
+    ("candle", "VOO_CANDLE_MODEL_DIR"),
+];
+const API_KEY_VARS: &[(&str, &str)] = &[
+    ("gemini", "GEMINI_API_KEY"),
+    ("openai", "OPENAI_API_KEY"),
+    ("azure", "AZURE_OPENAI_API_KEY"),
+    ("mistral", "MISTRAL_API_KEY"),
+    ("groq", "GROQ_API_KEY"),
+    ("xai", "XAI_API_KEY"),
+    ("deepseek", "DEEPSEEK_API_KEY"),
+    ("brave", "BRAVE_API_KEY"),
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    // Where sessions are recorded, "files" or "sqlite".
+    pub storage: Option<String>,
+    pub api_keys: HashMap<String, String>,
+    pub tools: ToolsConfig,
+    pub system_prompt: SystemPromptConfig,
+    pub logging: LoggingConfig,
+    pub sync: SyncConfig,
+    pub serve: ServeConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    // Every tool is enabled when there is no list.
+    pub enabled: Option<Vec<String>>,
+    // Command prefixes run_command is limited to, as VOO_RUN_ALLOW.
+    pub run_allow: Option<Vec<String>>,
+    // Command prefixes run_command refuses, as VOO_RUN_DENY.
+    pub run_deny: Option<Vec<String>>,
+    // The largest file the read tools take in, as VOO_READ_MAX_BYTES.
+    pub read_max_bytes: Option<u64>,
+    // File types the read tools are limited to, as VOO_READ_ALLOW.
+    pub read_allow: Option<Vec<String>>,
+    // File types the read tools refuse, as VOO_READ_DENY.
+    pub read_deny: Option<Vec<String>>,
+    // Domains web_fetch is limited to, as VOO_FETCH_ALLOW.
+    pub fetch_allow: Option<Vec<String>>,
+    // The most web_fetch reads of a page, as VOO_FETCH_MAX_BYTES.
+    pub fetch_max_bytes: Option<u64>,
+    // Seconds web_fetch waits for a page, as VOO_FETCH_TIMEOUT.
+    pub fetch_timeout: Option<u64>,
+    // brave, searxng or duckduckgo for web_search, as VOO_SEARCH_PROVIDER.
+    pub search_provider: Option<String>,
+    // The SearXNG instance web_search asks, as VOO_SEARXNG_URL.
+    pub searxng_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SystemPromptConfig {
+    // Replaces the built-in system prompt.
+    pub text: Option<String>,
+    // Added to the end of the system prompt, e.g. conventions of the project.
+    pub append: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    // The git repository `voo sync` shares the team config through.
+    pub repo: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServeConfig {
+    // Who may call `voo serve`, anyone when there are none.
+    pub users: Vec<ServeUser>,
+    // The quotas users share with the others of their team.
+    pub teams: HashMap<String, Quota>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServeUser {
+    pub name: String,
+    // Sent as `Authorization: Bearer <token>`.
+    pub token: String,
+    pub role: Role,
+    pub team: Option<String>,
+    #[serde(default)]
+    pub quota: Quota,
+}
+
+// Limits on what jobs may use per day, reset at midnight UTC.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    pub daily_tokens: Option<u64>,
+    // Estimated USD, priced as with VOO_COST_THRESHOLD.
+    pub daily_cost: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub level: Option<String>,
+    // Logs go to this file instead of the terminal.
+    pub file: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut config = toml::from_str::<Config>(content)?;
+
+        for (provider, api_key) in config.api_keys.iter_mut() {
+            if !API_KEY_VARS.iter().any(|(name, _)| name == provider) {
+                let names = API_KEY_VARS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<&str>>();
+                return Err(anyhow::anyhow!(
+                    "Unknown provider in api_keys: {} (expected one of {})",
+                    provider,
+                    names.join(", ")
+                ));
+            }
+            *api_key = interpolate(api_key);
+        }
+        for user in config.serve.users.iter_mut() {
+            user.token = interpolate(&user.token);
+        }
+        config.provider = config.provider.as_deref().map(interpolate);
+        config.model = config.model.as_deref().map(interpolate);
+
+        Ok(config)
+    }
+
+    // Values set in `other` take precedence.
+    pub fn merge(mut self, other: Config) -> Self {
+        self.api_keys.extend(other.api_keys);
+        Self {
+            provider: other.provider.or(self.provider),
+            model: other.model.or(self.model),
+            storage: other.storage.or(self.storage),
+            api_keys: self.api_keys,
+            tools: ToolsConfig {
+                enabled: other.tools.enabled.or(self.tools.enabled),
+                run_allow: other.tools.run_allow.or(self.tools.run_allow),
+                run_deny: other.tools.run_deny.or(self.tools.run_deny),
+                read_max_bytes: other.tools.read_max_bytes.or(self.tools.read_max_bytes),
+                read_allow: other.tools.read_allow.or(self.tools.read_allow),
+                read_deny: other.tools.read_deny.or(self.tools.read_deny),
+                fetch_allow: other.tools.fetch_allow.or(self.tools.fetch_allow),
+                fetch_max_bytes: other.tools.fetch_max_bytes.or(self.tools.fetch_max_bytes),
+                fetch_timeout: other.tools.fetch_timeout.or(self.tools.fetch_timeout),
+                search_provider: other.tools.search_provider.or(self.tools.search_provider),
+                searxng_url: other.tools.searxng_url.or(self.tools.searxng_url),
+            },
+            system_prompt: SystemPromptConfig {
+                text: other.system_prompt.text.or(self.system_prompt.text),
+                append: other.system_prompt.append.or(self.system_prompt.append),
+            },
+            logging: LoggingConfig {
+                level: other.logging.level.or(self.logging.level),
+                file: other.logging.file.or(self.logging.file),
+            },
+            sync: SyncConfig {
+                repo: other.sync.repo.or(self.sync.repo),
+            },
+            // The most specific list of users applies, they are not merged.
+            serve: match other.serve.users.is_empty() && other.serve.teams.is_empty() {
+                true => self.serve,
+                false => other.serve,
+            },
+        }
+    }
+
+    // The value the config gives an environment variable. The model belongs
+    // to the configured provider, so it is not used for another one picked
+    // with --provider or VOO_PROVIDER.
+    pub fn value(&self, key: &str) -> Option<String> {
+        if key == "VOO_PROVIDER" {
+            return self.provider.clone();
+        }
+        if key == "VOO_STORAGE" {
+            return self.storage.clone();
+        }
+        if key == "VOO_SYNC_REPO" {
+            return self.sync.repo.clone();
+        }
+        if key == "VOO_RUN_ALLOW" {
+            return self.tools.run_allow.as_ref().map(|rules| rules.join(","));
+        }
+        if key == "VOO_RUN_DENY" {
+            return self.tools.run_deny.as_ref().map(|rules| rules.join(","));
+        }
+        if key == "VOO_READ_MAX_BYTES" {
+            return self.tools.read_max_bytes.map(|bytes| bytes.to_string());
+        }
+        if key == "VOO_READ_ALLOW" {
+            return self.tools.read_allow.as_ref().map(|rules| rules.join(","));
+        }
+        if key == "VOO_READ_DENY" {
+            return self.tools.read_deny.as_ref().map(|rules| rules.join(","));
+        }
+        if key == "VOO_FETCH_ALLOW" {
+            return self
+                .tools
+                .fetch_allow
+                .as_ref()
+                .map(|domains| domains.join(","));
+        }
+        if key == "VOO_FETCH_MAX_BYTES" {
+            return self.tools.fetch_max_bytes.map(|bytes| bytes.to_string());
+        }
+        if key == "VOO_FETCH_TIMEOUT" {
+            return self.tools.fetch_timeout.map(|secs| secs.to_string());
+        }
+        if key == "VOO_SEARCH_PROVIDER" {
+            return self.tools.search_provider.clone();
+        }
+        if key == "VOO_SEARXNG_URL" {
+            return self.tools.searxng_url.clone();
+        }
+
+        let provider = self.provider.as_deref().unwrap_or("gemini");
+        if MODEL_VARS.contains(&(provider, key)) {
+            return self.model.clone();
+        }
+
+        API_KEY_VARS
+            .iter()
+            .find(|(_, var)| *var == key)
+            .and_then(|(name, _)| self.api_keys.get(*name))
+            .cloned()
+    }
+
+    pub fn tool_enabled(&self, name: &str) -> bool {
+        self.tools
+            .enabled
+            .as_ref()
+            .is_none_or(|enabled| enabled.iter().any(|tool| tool == name))
+    }
+
+    pub fn system_prompt(&self, base: SystemPrompt) -> Option<SystemPrompt> {
+        let prompt = &self.system_prompt;
+        if prompt.text.is_none() && prompt.append.is_none() {
+            return None;
+        }
+
+        let mut text = prompt.text.clone().unwrap_or(base.text);
+        if let Some(append) = &prompt.append {
+            text = format!("{}\n\n{}", text.trim_end(), append.trim());
+        }
+        Some(SystemPrompt {
+            version: base.version,
+            text,
+        })
+    }
+}
+
+// The team config pulled with `voo sync`, the user config on top of it, then
+// voo.toml of the working directory.
+pub fn load() -> anyhow::Result<Config> {
+    let mut config = Config::default();
+
+    // Keys and tokens stay personal, a shared file must not hand out its own.
+    if let Some(mut team) = read(&team_dir().join(PROJECT_CONFIG))? {
+        team.api_keys.clear();
+        team.serve = ServeConfig::default();
+        config = config.merge(team);
+    }
+
+    if let Some(path) = user_config_path()
+        && let Some(user) = read(&path)?
+    {
+        config = config.merge(user);
+    }
+
+    // A checked out repository must not decide who may call `voo serve`.
+    if let Some(mut project) = read(Path::new(PROJECT_CONFIG))? {
+        project.serve = ServeConfig::default();
+        config = config.merge(project);
+    }
+
+    Ok(config)
+}
+
+// The checkout of the team's config repository.
+pub fn team_dir() -> PathBuf {
+    data_dir().join("team")
+}
+
+fn read(path: &Path) -> anyhow::Result<Option<Config>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow::anyhow!("Error reading {}: {}", path.display(), e)),
+    };
+
+    Config::parse(&content)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", path.display(), e))
+}
+
+pub fn user_config_path() -> Option<PathBuf> {
+    let dir = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+
+    Some(dir.join("voo").join("config.toml"))
+}
+
+pub fn set(config: Config) {
+    _ = CONFIG.set(config);
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+// Reads an environment variable, falling back to the config file.
+pub fn var(key: &str) -> Result<String, VarError> {
+    std::env::var(key).or_else(|e| get().value(key).ok_or(e))
+}
+
+// Replaces ${NAME} with the environment variable NAME, or nothing when it is
+// not set, so keys can stay out of the file.
+fn interpolate(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        result.push_str(&std::env::var(&rest[start + 2..start + end]).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_merge() {
+        let user = Config::parse(
+            r#"
+provider = "openai"
+model = "gpt-4o"
+storage = "sqlite"
+
+[api_keys]
+openai = "${PATH}"
+gemini = "key-${VOO_TEST_UNSET_VARIABLE}"
+
+[logging]
+level = "debug"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            user.api_keys["openai"],
+            std::env::var("PATH").unwrap_or_default()
+        );
+        assert_eq!(user.api_keys["gemini"], "key-");
+        assert_eq!(user.value("VOO_OPENAI_MODEL").as_deref(), Some("gpt-4o"));
+        assert_eq!(user.value("VOO_GEMINI_MODEL"), None);
+
+        let project = Config::parse(
+            r#"
+[tools]
+enabled = ["read_file", "grep"]
+run_deny = ["git push", "docker"]
+read_max_bytes = 1048576
+read_deny = [".env", "application/vnd.sqlite3"]
+fetch_allow = ["docs.rs", "doc.rust-lang.org"]
+search_provider = "searxng"
+
+[system_prompt]
+append = "Use tabs."
+"#,
+        )
+        .unwrap();
+        let config = user.merge(project);
+        assert_eq!(config.provider.as_deref(), Some("openai"));
+        assert_eq!(config.value("VOO_STORAGE").as_deref(), Some("sqlite"));
+        assert_eq!(config.logging.level.as_deref(), Some("debug"));
+        assert!(config.tool_enabled("grep"));
+        assert!(!config.tool_enabled("run_command"));
+        assert_eq!(
+            config.value("VOO_RUN_DENY").as_deref(),
+            Some("git push,docker")
+        );
+        assert_eq!(config.value("VOO_RUN_ALLOW"), None);
+        assert_eq!(
+            config.value("VOO_READ_MAX_BYTES").as_deref(),
+            Some("1048576")
+        );
+        assert_eq!(
+            config.value("VOO_READ_DENY").as_deref(),
+            Some(".env,application/vnd.sqlite3")
+        );
+        assert_eq!(
+            config.value("VOO_FETCH_ALLOW").as_deref(),
+            Some("docs.rs,doc.rust-lang.org")
+        );
+        assert_eq!(
+            config.value("VOO_SEARCH_PROVIDER").as_deref(),
+            Some("searxng")
+        );
+
+        let base = SystemPrompt {
+            version: 2,
+            text: "You are voo.\n".to_string(),
+        };
+        assert_eq!(
+            config.system_prompt(base).unwrap().text,
+            "You are voo.\n\nUse tabs."
+        );
+
+        assert!(Config::parse("modle = \"gpt-4o\"").is_err());
+        assert!(Config::parse("[api_keys]\nbedrock = \"key\"").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use domain::models::agent::Agent;
+
+use crate::{
+    policy,
+    terminal::{prompt, styled_println},
+};
+
+const DEFAULT_THRESHOLD: f64 = 0.50;
+
+// Asks before sending a request whose estimated input cost exceeds the
+// threshold, e.g. after reading a huge file into the conversation. Requests
+// above the limit of the organization policy are refused without asking.
+#[derive(Debug)]
+pub struct CostGuard {
+    threshold: Option<f64>,
+    limit: Option<f64>,
+    always_allow: AtomicBool,
+}
+
+impl CostGuard {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let threshold =
+            match std::env::var("VOO_COST_THRESHOLD") {
+                Ok(value) if value.trim() == "off" => None,
+                Ok(value) => Some(value.trim().trim_start_matches('$').parse().map_err(|_| {
+                    anyhow::anyhow!("Error parsing VOO_COST_THRESHOLD: {:?}", value)
+                })?),
+                Err(_) => Some(DEFAULT_THRESHOLD),
+            };
+
+        Ok(Self {
+            threshold,
+            limit: policy::get().max_turn_cost,
+            always_allow: AtomicBool::new(false),
+        })
+    }
+
+    // Returns false when the user cancels the turn.
+    pub async fn check(&self, agent: &Agent, input: &str) -> anyhow::Result<bool> {
+        let asks = self.threshold.is_some()
+            && !self.always_allow.load(Ordering::SeqCst)
+            && std::io::stdin().is_terminal();
+        if !asks && self.limit.is_none() {
+            return Ok(true);
+        }
+
+        let Some((cost, tokens)) = estimate(agent, input).await? else {
+            // Without a price the policy limit can't be checked, so only an
+            // explicit VOO_INPUT_PRICE lets the turn through.
+            if let Some(limit) = self.limit {
+                styled_println!(
+                    "\x1b[41mvoo>\x1b[0m the cost of this turn is unknown and the organization policy allows at most ${:.2}, set VOO_INPUT_PRICE to the model's input price",
+                    limit
+                );
+                return Ok(false);
+            }
+            return Ok(true);
+        };
+
+        if let Some(limit) = self.limit
+            && cost > limit
+        {
+            styled_println!(
+                "\x1b[41mvoo>\x1b[0m this turn would cost ~${:.2}, more than the ${:.2} the organization policy allows",
+                cost,
+                limit
+            );
+            return Ok(false);
+        }
+        let Some(threshold) = self.threshold.filter(|_| asks) else {
+            return Ok(true);
+        };
+        if cost <= threshold {
+            return Ok(true);
+        }
+
+        loop {
+            match prompt(&format!(
+                "\x1b[33mthis turn will cost ~${:.2} (~{} input tokens), proceed? [y] yes, [a] always, [n] no:\x1b[0m ",
+                cost, tokens
+            ))?
+            .as_str()
+            {
+                "y" => return Ok(true),
+                "a" => {
+                    self.always_allow.store(true, Ordering::SeqCst);
+                    return Ok(true);
+                }
+                "n" | "q" => return Ok(false),
+                _ => continue,
+            }
+        }
+    }
+}
+
+// The estimated cost and input tokens of the turn, None when the provider or
+// its price is unknown.
+async fn estimate(agent: &Agent, input: &str) -> anyhow::Result<Option<(f64, u64)>> {
+    let Some(provider) = agent.client().health().await.map(|health| health.provider) else {
+        return Ok(None);
+    };
+    let Some(price) = input_price(&provider)? else {
+        return Ok(None);
+    };
+    let Some(tokens) = agent.client().estimate_tokens(input).await else {
+        return Ok(None);
+    };
+
+    Ok(Some((estimate_cost(tokens, price), tokens)))
+}
+
+// USD per million input tokens for the default model of each provider.
+// VOO_INPUT_PRICE overrides it when another model is configured.
+fn input_price(provider: &str) -> anyhow::Result<Option<f64>> {
+    if let Ok(price) = std::env::var("VOO_INPUT_PRICE") {
+        return price
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("Error parsing VOO_INPUT_PRICE: {:?}", price));
+    }
+
+    Ok(match provider {
+        "gemini" => Some(0.10),
+        "openai" => Some(0.15),
+        "bedrock" => Some(3.00),
+        "azure" => Some(2.50),
+        "mistral" => Some(2.00),
+        "groq" => Some(0.59),
+        "xai" => Some(3.00),
+        "deepseek" => Some(0.27),
+        _ => None,
+    })
+}
+
+// What the tokens of a finished job cost. Response tokens are priced as input
+// tokens too, so it errs on the low side.
+pub fn job_cost(provider: &str, tokens: u64) -> anyhow::Result<Option<f64>> {
+    Ok(input_price(provider)?.map(|price| estimate_cost(tokens, price)))
+}
+
+fn estimate_cost(tokens: u64, price_per_million: f64) -> f64 {
+    tokens as f64 * price_per_million / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        assert!((estimate_cost(283_334, 3.00) - 0.85).abs() < 0.001);
+        assert_eq!(estimate_cost(0, 3.00), 0.0);
+    }
+}
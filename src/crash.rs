@@ -0,0 +1,201 @@
+use std::{
+    backtrace::Backtrace,
+    collections::{BTreeMap, VecDeque},
+    panic::PanicHookInfo,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use domain::models::transcript::{SessionEvent, TranscriptEntry};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{config, paths::data_dir, terminal::styled_eprintln};
+
+const MAX_EVENTS: usize = 50;
+const REDACTED: &str = "[redacted]";
+const ISSUES_URL: &str = "https://github.com/mystique09/voo/issues/new";
+// Environment variables whose names contain these hold credentials.
+const SECRET_NAMES: &[&str] = &["KEY", "TOKEN", "SECRET", "PASSWORD", "CREDENTIAL"];
+
+static EVENTS: Mutex<VecDeque<TranscriptEntry>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Serialize)]
+struct CrashBundle {
+    version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    crashed_at: i64,
+    message: String,
+    location: Option<String>,
+    backtrace: String,
+    config: Value,
+    env: BTreeMap<String, String>,
+    events: Vec<TranscriptEntry>,
+}
+
+// Keeps the last events of the session for the crash bundle.
+pub fn remember(event: &SessionEvent) {
+    let mut events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if events.len() == MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(TranscriptEntry {
+        at: now(),
+        event: event.clone(),
+    });
+}
+
+// Rewrites the kept events, dropping those `keep` returns false for, so a
+// redacted message doesn't end up in a crash bundle either.
+pub fn retain(keep: impl FnMut(&mut TranscriptEntry) -> bool) {
+    EVENTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain_mut(keep);
+}
+
+// Writes a crash bundle on panic, after the default hook has printed the
+// panic.
+pub fn install() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+
+        match write_bundle(info) {
+            Ok(path) => styled_eprintln!(
+                "\n\x1b[41mvoo>\x1b[0m voo crashed, a crash report was written to {}\nPlease file a bug at {} and attach the report. Secrets are removed, but it holds the last messages of the session, so look through it before sharing.",
+                path.display(),
+                ISSUES_URL
+            ),
+            Err(e) => styled_eprintln!(
+                "\n\x1b[41mvoo>\x1b[0m voo crashed, and writing a crash report failed: {}",
+                e
+            ),
+        }
+    }));
+}
+
+fn write_bundle(info: &PanicHookInfo) -> std::io::Result<PathBuf> {
+    let secrets = secrets();
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+
+    // The panic may have happened while the events were locked.
+    let events = match EVENTS.try_lock() {
+        Ok(events) => events.iter().cloned().collect(),
+        Err(_) => vec![],
+    };
+
+    let bundle = CrashBundle {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        crashed_at: now(),
+        message,
+        location: info.location().map(|location| location.to_string()),
+        backtrace: Backtrace::force_capture().to_string(),
+        config: redacted_config(),
+        env: redacted_env(),
+        events,
+    };
+    let json = serde_json::to_string_pretty(&bundle).map_err(std::io::Error::other)?;
+
+    let dir = data_dir().join("crashes");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crash-{}.json", bundle.crashed_at));
+    std::fs::write(&path, redact(&json, &secrets))?;
+
+    Ok(path)
+}
+
+fn redacted_config() -> Value {
+    let mut config = serde_json::to_value(config::get()).unwrap_or_default();
+    if let Some(api_keys) = config.get_mut("api_keys").and_then(Value::as_object_mut) {
+        for value in api_keys.values_mut() {
+            *value = Value::from(REDACTED);
+        }
+    }
+    if let Some(users) = config
+        .pointer_mut("/serve/users")
+        .and_then(Value::as_array_mut)
+    {
+        for user in users {
+            user["token"] = Value::from(REDACTED);
+        }
+    }
+    config
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SECRET_NAMES.iter().any(|secret| name.contains(secret))
+}
+
+// The voo settings of the environment. Provider keys such as GEMINI_API_KEY
+// are left out entirely.
+fn redacted_env() -> BTreeMap<String, String> {
+    std::env::vars()
+        .filter(|(key, _)| key.starts_with("VOO_") || key == "RUST_LOG")
+        .map(|(key, value)| match is_secret_name(&key) {
+            true => (key, REDACTED.to_string()),
+            false => (key, value),
+        })
+        .collect()
+}
+
+// Credentials from the environment and the config file, which could show up
+// anywhere in the bundle, e.g. in a tool result that printed them.
+fn secrets() -> Vec<String> {
+    std::env::vars()
+        .filter(|(key, _)| is_secret_name(key))
+        .map(|(_, value)| value)
+        .chain(config::get().api_keys.values().cloned())
+        .chain(
+            config::get()
+                .serve
+                .users
+                .iter()
+                .map(|user| user.token.clone()),
+        )
+        .filter(|secret| secret.trim().len() >= 8)
+        .collect()
+}
+
+fn redact(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret, REDACTED)
+    })
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let secrets = vec!["sk-live-0123456789".to_string()];
+        assert_eq!(
+            redact(
+                "{\"output\": \"OPENAI_API_KEY=sk-live-0123456789\"}",
+                &secrets
+            ),
+            "{\"output\": \"OPENAI_API_KEY=[redacted]\"}"
+        );
+        assert!(is_secret_name("VOO_WEBHOOK_SECRET"));
+        assert!(is_secret_name("VOO_VERTEX_ACCESS_TOKEN"));
+        assert!(!is_secret_name("VOO_PROVIDER"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+
+use domain::models::{
+    agent::{Agent, AgentClient, AgentRole},
+    transcript::{SessionEvent, Transcript, TranscriptEntry},
+};
+
+use crate::{providers, record, terminal::styled_println};
+
+const SUMMARY_PROMPT: &str = "Another assistant is taking over this conversation. Summarize it for them: the task, the relevant facts learned from tool results, what has been done and what remains. Reply with the summary only.";
+
+// Converts a transcript into plain messages any provider accepts. Tool calls
+// can't be replayed with the ids of another provider, so each result is sent
+// as a tool message naming the call that produced it.
+pub fn portable_history(entries: &[TranscriptEntry]) -> Vec<(AgentRole, String)> {
+    let mut calls = VecDeque::new();
+    let mut history = vec![];
+
+    for entry in entries {
+        match &entry.event {
+            SessionEvent::UserMessage { text } => history.push((AgentRole::User, text.clone())),
+            SessionEvent::ModelResponse { content } => {
+                let text = content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.answer().cloned())
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                history.push((AgentRole::Model, text));
+            }
+            SessionEvent::ToolCall { name, args } => calls.push_back((name.clone(), args.clone())),
+            SessionEvent::ToolResult { name, output } => {
+                let text = match calls.pop_front() {
+                    Some((call, args)) if &call == name => {
+                        format!("Result of {}({}):\n{}", name, args, output)
+                    }
+                    _ => format!("Result of {}:\n{}", name, output),
+                };
+                history.push((AgentRole::Tool, text));
+            }
+            SessionEvent::SessionStarted { .. }
+            | SessionEvent::Error { .. }
+            | SessionEvent::Handoff { .. }
+            | SessionEvent::Forked { .. } => {}
+        }
+    }
+
+    history.retain(|(_, text)| !text.trim().is_empty());
+    history
+}
+
+pub async fn run(agent: &mut Agent, args: &str, transcript: &Transcript) -> anyhow::Result<()> {
+    let mut args = args.split_whitespace();
+    let (Some(provider), summarize) = (args.next(), args.next()) else {
+        return Err(anyhow::anyhow!("usage: /handoff <provider> [--summarize]"));
+    };
+    let summarize = match summarize {
+        None => false,
+        Some("--summarize") => true,
+        Some(arg) => return Err(anyhow::anyhow!("Unknown argument: {}", arg)),
+    };
+
+    let client = providers::registry().build(provider)?;
+
+    let entries = transcript
+        .load()
+        .map_err(|e| anyhow::anyhow!("Error loading session transcript: {}", e))?;
+    let history = match summarize {
+        true => match agent.client().ask(SUMMARY_PROMPT).await {
+            Ok(contents) => {
+                let summary = contents
+                    .iter()
+                    .flat_map(|content| &content.parts)
+                    .filter_map(|part| part.answer().cloned())
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                styled_println!("\x1b[2m{}\x1b[0m", summary.trim());
+                vec![(
+                    AgentRole::User,
+                    format!("Summary of the conversation so far:\n{}", summary),
+                )]
+            }
+            Err(e) => {
+                styled_println!(
+                    "\x1b[33mwarning: could not summarize ({}), handing off the full history\x1b[0m",
+                    e
+                );
+                portable_history(&entries)
+            }
+        },
+        false => portable_history(&entries),
+    };
+
+    if let Some(system_prompt) = agent.client().system_prompt().await {
+        // Providers that can't replace their prompt keep their own.
+        _ = client.set_system_prompt(system_prompt).await;
+    }
+    agent
+        .replace_client(client)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error adding tool: {}", e))?;
+
+    let messages = history.len();
+    for (role, text) in history {
+        _ = agent.client().add_system_prompt(&text, role).await;
+    }
+
+    record(
+        transcript,
+        SessionEvent::Handoff {
+            provider: provider.to_string(),
+        },
+    );
+    styled_println!(
+        "\x1b[32mhandoff>\x1b[0m continuing with {} ({} messages)",
+        provider,
+        messages
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::agent::{Content, FunctionCall, Part};
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry(event: SessionEvent) -> TranscriptEntry {
+        TranscriptEntry { at: 0, event }
+    }
+
+    #[test]
+    fn test_portable_history() {
+        let entries = vec![
+            entry(SessionEvent::SessionStarted {
+                system_prompt: None,
+            }),
+            entry(SessionEvent::UserMessage {
+                text: "what is in main.rs?".to_string(),
+            }),
+            entry(SessionEvent::ModelResponse {
+                content: Content::new(
+                    vec![Part {
+                        function_call: Some(FunctionCall {
+                            name: "read_file".to_string(),
+                            args: json!({"path": "main.rs"}),
+                        }),
+                        ..Default::default()
+                    }],
+                    "model",
+                ),
+            }),
+            entry(SessionEvent::ToolCall {
+                name: "read_file".to_string(),
+                args: json!({"path": "main.rs"}),
+            }),
+            entry(SessionEvent::ToolResult {
+                name: "read_file".to_string(),
+                output: "fn main() {}".to_string(),
+            }),
+            entry(SessionEvent::ModelResponse {
+                content: Content::new(
+                    vec![Part::thought("hmm"), Part::new("An empty main.")],
+                    "model",
+                ),
+            }),
+        ];
+
+        let history = portable_history(&entries);
+        assert_eq!(history.len(), 3);
+        assert_eq!(
+            history[1].1,
+            "Result of read_file({\"path\":\"main.rs\"}):\nfn main() {}"
+        );
+        assert_eq!(history[2].1, "An empty main.");
+    }
+}
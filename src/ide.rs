@@ -0,0 +1,453 @@
+use std::fmt::Display;
+
+use domain::models::{
+    agent::Agent,
+    mode::Mode,
+    tools::{Tool, ToolOutput},
+};
+use models::{
+    lsp::{TextEdit, diff_edits},
+    tools::{edit_file::EditFileTool, write_file::WriteFileTool},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tracing::error;
+
+use crate::{build_agent, formatters, policy, runner::run_prompt, transform};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+// Generous for a buffer and its instruction, larger messages are skipped.
+const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+// Header lines are short, a longer one is not the framing voo expects.
+const MAX_HEADER_BYTES: u64 = 1024;
+const METHODS: &[&str] = &[
+    "initialize",
+    "chat",
+    "applyEdit",
+    "diagnostics",
+    "editBuffer",
+    "shutdown",
+    "exit",
+];
+
+#[derive(Debug, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatParams {
+    prompt: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApplyEditParams {
+    path: String,
+    content: Option<String>,
+    old_string: Option<String>,
+    new_string: Option<String>,
+    expected_replacements: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagnosticsParams {
+    path: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EditBufferParams {
+    path: Option<String>,
+    content: String,
+    instruction: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Diagnostic {
+    line: usize,
+    column: Option<usize>,
+    severity: Option<String>,
+    source: Option<String>,
+    message: String,
+}
+
+// `voo ide` speaks JSON-RPC 2.0 on stdin and stdout with the Content-Length
+// framing of the language server protocol, so editor extensions can embed the
+// agent instead of scraping the terminal. Requests are answered in order, and
+// every chat continues the same conversation.
+pub async fn run() -> anyhow::Result<()> {
+    let agent = build_agent().await?;
+    let mut stdin = BufReader::new(tokio::io::stdin());
+    let mut stdout = tokio::io::stdout();
+    let mut shutting_down = false;
+
+    while let Some(body) = read_message(&mut stdin).await? {
+        let body = match body {
+            Ok(body) => body,
+            Err(error) => {
+                write_message(&mut stdout, &response(Value::Null, Err(error))).await?;
+                continue;
+            }
+        };
+        let message = match serde_json::from_slice::<Value>(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = RpcError::new(PARSE_ERROR, e);
+                write_message(&mut stdout, &response(Value::Null, Err(error))).await?;
+                continue;
+            }
+        };
+        let id = message.get("id").cloned();
+        let method = message
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match shutting_down {
+            true => Err(RpcError::new(INVALID_REQUEST, "voo is shutting down")),
+            false => dispatch(&agent, method, params).await,
+        };
+        shutting_down |= method == "shutdown";
+
+        // Notifications have no id and get no response.
+        match id {
+            Some(id) => write_message(&mut stdout, &response(id, result)).await?,
+            None => {
+                if let Err(e) = result {
+                    error!("Error handling {} notification: {}", method, e.message);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn dispatch(agent: &Agent, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "initialize" => Ok(json!({
+            "name": "voo",
+            "version": env!("CARGO_PKG_VERSION"),
+            "methods": METHODS,
+        })),
+        "chat" => {
+            let params = parse::<ChatParams>(params)?;
+            if params.prompt.trim().is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "prompt must not be empty"));
+            }
+            ask(agent, &params.prompt).await
+        }
+        "applyEdit" => apply_edit(parse(params)?).await,
+        "diagnostics" => {
+            let params = parse::<DiagnosticsParams>(params)?;
+            if params.diagnostics.is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "there are no diagnostics"));
+            }
+            ask(agent, &fix_prompt(&params)).await
+        }
+        "editBuffer" => {
+            let params = parse::<EditBufferParams>(params)?;
+            if params.instruction.trim().is_empty() {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "instruction must not be empty",
+                ));
+            }
+            edit_buffer(agent, &params).await
+        }
+        "shutdown" => Ok(Value::Null),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method: {}", method),
+        )),
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+async fn ask(agent: &Agent, prompt: &str) -> Result<Value, RpcError> {
+    let answer = run_prompt(agent, prompt)
+        .await
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+    Ok(json!({ "answer": answer }))
+}
+
+// The editor already showed the change to the user, so it is written without
+// asking. Tools denied by the policy are refused here too.
+async fn apply_edit(params: ApplyEditParams) -> Result<Value, RpcError> {
+    let (tool, input): (Box<dyn Tool>, Value) =
+        match (params.content, params.old_string, params.new_string) {
+            (Some(content), None, None) => {
+                let mut tool = WriteFileTool::new("write_file", "").with_auto_approve(true);
+                if let Some(formatters) = formatters() {
+                    tool = tool.with_formatters(formatters);
+                }
+                let input = json!({ "path": params.path, "content": content });
+                (Box::new(tool), input)
+            }
+            (None, Some(old_string), Some(new_string)) => {
+                let mut tool = EditFileTool::new("edit_file", "").with_auto_approve(true);
+                if let Some(formatters) = formatters() {
+                    tool = tool.with_formatters(formatters);
+                }
+                let input = json!({
+                    "path": params.path,
+                    "old_string": old_string,
+                    "new_string": new_string,
+                    "expected_replacements": params.expected_replacements,
+                });
+                (Box::new(tool), input)
+            }
+            _ => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "applyEdit takes either content or oldString and newString",
+                ));
+            }
+        };
+    if !policy::get().tool_allowed(tool.name()) {
+        return Err(RpcError::new(
+            INVALID_REQUEST,
+            format!("{} is denied by the policy", tool.name()),
+        ));
+    }
+
+    match tool.exec(input).await {
+        Ok(ToolOutput::Error(message)) => Err(RpcError::new(INTERNAL_ERROR, message)),
+        Ok(output) => Ok(json!({ "output": output.to_string() })),
+        Err(e) => Err(RpcError::new(INTERNAL_ERROR, e)),
+    }
+}
+
+// Buffers may have unsaved changes, so the agent works on the content the
+// editor sent and answers with edits for the editor to apply. It runs in ask
+// mode for the request, where it can read files but not change them.
+async fn edit_buffer(agent: &Agent, params: &EditBufferParams) -> Result<Value, RpcError> {
+    let mode = agent.mode().await;
+    agent
+        .set_mode(Mode::Ask)
+        .await
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+    let answer = run_prompt(
+        agent,
+        &transform::prompt(&params.instruction, params.path.as_deref(), &params.content),
+    )
+    .await;
+    agent
+        .set_mode(mode)
+        .await
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+    let answer = answer.map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+
+    let content = transform::rewritten(&params.content, &answer)
+        .ok_or_else(|| RpcError::new(INTERNAL_ERROR, "the answer contained no edited buffer"))?;
+
+    let edits = diff_edits(&params.content, &content);
+    Ok(json!({ "edits": edits.iter().map(TextEdit::to_json).collect::<Vec<Value>>() }))
+}
+
+fn fix_prompt(params: &DiagnosticsParams) -> String {
+    let mut prompt = format!(
+        "Fix the following diagnostics in {}, reading the file first:\n",
+        params.path
+    );
+    for diagnostic in &params.diagnostics {
+        let position = match diagnostic.column {
+            Some(column) => format!("{}:{}", diagnostic.line, column),
+            None => diagnostic.line.to_string(),
+        };
+        let severity = diagnostic.severity.as_deref().unwrap_or("error");
+        let source = match &diagnostic.source {
+            Some(source) => format!(" ({})", source),
+            None => String::new(),
+        };
+        prompt.push_str(&format!(
+            "- {}:{} {}{}: {}\n",
+            params.path, position, severity, source, diagnostic.message
+        ));
+    }
+
+    prompt
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+}
+
+// The body of the next message, none once the editor closed stdin. A body
+// larger than MAX_MESSAGE_BYTES is read past and answered with an error.
+async fn read_message(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> anyhow::Result<Option<Result<Vec<u8>, RpcError>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        let read = (&mut *reader)
+            .take(MAX_HEADER_BYTES)
+            .read_line(&mut line)
+            .await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with('\n') && read as u64 == MAX_HEADER_BYTES {
+            return Err(anyhow::anyhow!(
+                "header line longer than {} bytes",
+                MAX_HEADER_BYTES
+            ));
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| anyhow::anyhow!("missing Content-Length header"))?;
+    if length > MAX_MESSAGE_BYTES {
+        let skipped = tokio::io::copy(
+            &mut (&mut *reader).take(length as u64),
+            &mut tokio::io::sink(),
+        )
+        .await?;
+        if skipped < length as u64 {
+            return Ok(None);
+        }
+        return Ok(Some(Err(RpcError::new(
+            INVALID_REQUEST,
+            format!(
+                "the message is {} bytes, at most {} are accepted",
+                length, MAX_MESSAGE_BYTES
+            ),
+        ))));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(Ok(body)))
+}
+
+async fn write_message(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &Value,
+) -> anyhow::Result<()> {
+    let body = serde_json::to_string(message)?;
+    let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+
+    writer.write_all(frame.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_framing() {
+        let mut output = vec![];
+        write_message(&mut output, &json!({ "jsonrpc": "2.0", "method": "chat" }))
+            .await
+            .unwrap();
+        write_message(&mut output, &json!({ "id": 1 }))
+            .await
+            .unwrap();
+        assert!(output.starts_with(b"Content-Length: 33\r\n\r\n{"));
+
+        let mut reader = BufReader::new(output.as_slice());
+        let first = read_message(&mut reader).await.unwrap().unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&first).unwrap()["method"],
+            "chat"
+        );
+        let second = read_message(&mut reader).await.unwrap().unwrap().unwrap();
+        assert_eq!(second, br#"{"id":1}"#);
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+
+        let mut reader = BufReader::new(b"Content-Type: json\r\n\r\n{}".as_slice());
+        assert!(read_message(&mut reader).await.is_err());
+
+        // An oversized message is skipped, the next one is read as usual.
+        let mut input = format!("Content-Length: {}\r\n\r\n", MAX_MESSAGE_BYTES + 1).into_bytes();
+        input.resize(input.len() + MAX_MESSAGE_BYTES + 1, b' ');
+        input.extend_from_slice(b"Content-Length: 2\r\n\r\n{}");
+        let mut reader = BufReader::new(input.as_slice());
+        let error = read_message(&mut reader)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_REQUEST);
+        assert_eq!(
+            read_message(&mut reader).await.unwrap().unwrap().unwrap(),
+            b"{}"
+        );
+
+        let header = format!("X-Padding: {}\r\n\r\n", "x".repeat(2048));
+        let mut reader = BufReader::new(header.as_bytes());
+        assert!(read_message(&mut reader).await.is_err());
+    }
+
+    #[test]
+    fn test_fix_prompt() {
+        let params = serde_json::from_value::<DiagnosticsParams>(json!({
+            "path": "src/main.rs",
+            "diagnostics": [
+                { "line": 12, "column": 5, "severity": "warning", "source": "clippy", "message": "unused variable" },
+                { "line": 40, "message": "mismatched types" },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            fix_prompt(&params),
+            "Fix the following diagnostics in src/main.rs, reading the file first:\n\
+             - src/main.rs:12:5 warning (clippy): unused variable\n\
+             - src/main.rs:40 error: mismatched types\n"
+        );
+        assert_eq!(
+            response(
+                json!(3),
+                Err(RpcError::new(METHOD_NOT_FOUND, "unknown method: foo"))
+            ),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "error": { "code": -32601, "message": "unknown method: foo" },
+            })
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    future::Future,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use tokio::sync::broadcast;
+
+use crate::terminal::styled_eprintln;
+
+static INTERRUPTS: OnceLock<broadcast::Sender<()>> = OnceLock::new();
+// Requests and tool calls currently waiting in `cancellable`.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+struct InFlight;
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Ctrl+C cancels the request or tool calls in flight and hands the prompt
+// back. With nothing in flight, or when it is pressed again before they
+// stopped, e.g. at an approval prompt, it quits voo as before.
+pub fn install() {
+    let sender = INTERRUPTS.get_or_init(|| broadcast::channel(1).0).clone();
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if IN_FLIGHT.load(Ordering::SeqCst) == 0 || CANCELLED.swap(true, Ordering::SeqCst) {
+                eprintln!();
+                std::process::exit(130);
+            }
+            styled_eprintln!("\n\x1b[2mvoo> cancelling, press ctrl-c again to quit\x1b[0m");
+            _ = sender.send(());
+        }
+    });
+}
+
+// Runs `future` unless Ctrl+C is pressed first. Without `install`, e.g. in
+// `voo serve`, it always runs to the end.
+pub async fn cancellable<F: Future>(future: F) -> Result<F::Output, Cancelled> {
+    let Some(sender) = INTERRUPTS.get() else {
+        return Ok(future.await);
+    };
+    let mut interrupts = sender.subscribe();
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    let _in_flight = InFlight;
+
+    tokio::select! {
+        output = future => Ok(output),
+        _ = interrupts.recv() => Err(Cancelled),
+    }
+}
+
+// Whether anything was cancelled since the last call.
+pub fn take_cancelled() -> bool {
+    CANCELLED.swap(false, Ordering::SeqCst)
+}
@@ -0,0 +1,193 @@
+use domain::models::agent::Agent;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use whatlang::Lang;
+
+use crate::terminal::styled_println;
+
+// Shorter messages don't carry enough signal to switch languages.
+const MIN_DETECT_CHARS: usize = 12;
+
+const ISO_639_1: &[(&str, Lang)] = &[
+    ("ar", Lang::Ara),
+    ("bg", Lang::Bul),
+    ("bn", Lang::Ben),
+    ("cs", Lang::Ces),
+    ("da", Lang::Dan),
+    ("de", Lang::Deu),
+    ("el", Lang::Ell),
+    ("en", Lang::Eng),
+    ("es", Lang::Spa),
+    ("fa", Lang::Pes),
+    ("fi", Lang::Fin),
+    ("fr", Lang::Fra),
+    ("he", Lang::Heb),
+    ("hi", Lang::Hin),
+    ("hu", Lang::Hun),
+    ("id", Lang::Ind),
+    ("it", Lang::Ita),
+    ("ja", Lang::Jpn),
+    ("ko", Lang::Kor),
+    ("nl", Lang::Nld),
+    ("no", Lang::Nob),
+    ("pl", Lang::Pol),
+    ("pt", Lang::Por),
+    ("ro", Lang::Ron),
+    ("ru", Lang::Rus),
+    ("sv", Lang::Swe),
+    ("th", Lang::Tha),
+    ("tl", Lang::Tgl),
+    ("tr", Lang::Tur),
+    ("uk", Lang::Ukr),
+    ("vi", Lang::Vie),
+    ("zh", Lang::Cmn),
+];
+
+#[derive(Debug, Default)]
+struct PinState {
+    language: Option<Lang>,
+    // Set with /lang, detection no longer changes it.
+    manual: bool,
+}
+
+// Pins the response language to the user's by adding an instruction to the
+// system prompt, so replies don't drift back to English after tool results.
+#[derive(Debug, Default)]
+pub struct LanguagePin {
+    state: Mutex<PinState>,
+}
+
+impl LanguagePin {
+    pub async fn observe(&self, agent: &Agent, text: &str) {
+        let mut state = self.state.lock().await;
+        if state.manual {
+            return;
+        }
+
+        let Some(language) = detect(text) else {
+            return;
+        };
+        if state.language == Some(language) {
+            return;
+        }
+
+        match apply(agent, &mut state, Some(language)).await {
+            Ok(()) => info!("[VOO] responding in {}", name(language)),
+            Err(e) => warn!("[VOO] failed to pin the response language: {}", e),
+        }
+    }
+
+    pub async fn command(&self, agent: &Agent, args: &str) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+
+        match args.trim() {
+            "" => {
+                let mode = if state.manual { "pinned" } else { "detected" };
+                match state.language {
+                    Some(language) => {
+                        styled_println!("\x1b[32mlang>\x1b[0m {} ({})", name(language), mode)
+                    }
+                    None => styled_println!("\x1b[32mlang>\x1b[0m not pinned yet"),
+                }
+            }
+            "auto" => {
+                state.manual = false;
+                apply(agent, &mut state, None).await?;
+                styled_println!("\x1b[32mlang>\x1b[0m detecting the language of your messages");
+            }
+            code => {
+                let language = parse_language(code).ok_or_else(|| {
+                    anyhow::anyhow!("Unknown language: {} (try a code like `ja`)", code)
+                })?;
+                apply(agent, &mut state, Some(language)).await?;
+                state.manual = true;
+                styled_println!("\x1b[32mlang>\x1b[0m responding in {}", name(language));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn apply(agent: &Agent, state: &mut PinState, language: Option<Lang>) -> anyhow::Result<()> {
+    let instruction = language.map(instruction);
+    agent
+        .set_instruction("language", instruction.as_deref())
+        .await
+        .map_err(|e| anyhow::anyhow!("Error setting system prompt: {}", e))?;
+
+    state.language = language;
+    Ok(())
+}
+
+fn instruction(language: Lang) -> String {
+    format!(
+        "Always reply in {}, even when tool results, code or earlier messages are in another language. Keep code, identifiers and file paths as they are.",
+        name(language)
+    )
+}
+
+fn name(language: Lang) -> &'static str {
+    match language {
+        Lang::Cmn => "Chinese",
+        language => language.eng_name(),
+    }
+}
+
+// Detects the language of the prose in a message, ignoring code blocks.
+fn detect(text: &str) -> Option<Lang> {
+    let prose = text
+        .split("```")
+        .step_by(2)
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if prose.trim().chars().count() < MIN_DETECT_CHARS {
+        return None;
+    }
+
+    whatlang::detect(&prose)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang())
+}
+
+fn parse_language(code: &str) -> Option<Lang> {
+    let code = code.to_lowercase();
+
+    ISO_639_1
+        .iter()
+        .find(|(iso, _)| *iso == code)
+        .map(|(_, language)| *language)
+        .or_else(|| Lang::from_code(code.as_str()))
+        .or_else(|| {
+            Lang::all()
+                .iter()
+                .copied()
+                .find(|language| name(*language).to_lowercase() == code)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect("このファイルの中身を説明してください。"),
+            Some(Lang::Jpn)
+        );
+        assert_eq!(
+            detect("¿Puedes explicarme qué hace esta función?\n```rust\nfn main() {}\n```"),
+            Some(Lang::Spa)
+        );
+        assert_eq!(detect("ok"), None);
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(parse_language("ja"), Some(Lang::Jpn));
+        assert_eq!(parse_language("jpn"), Some(Lang::Jpn));
+        assert_eq!(parse_language("Chinese"), Some(Lang::Cmn));
+        assert_eq!(parse_language("xx"), None);
+    }
+}
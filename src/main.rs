@@ -1,209 +1,1403 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+mod ask;
+mod branches;
+mod commands;
+mod config;
+mod cost;
+mod crash;
+mod handoff;
+mod ide;
+mod interrupt;
+mod language;
+mod migrate;
+mod paths;
+mod plan;
+mod policy;
+mod providers;
+mod redact;
+mod relay;
+mod runnable;
+mod runner;
+mod scaffold;
+mod serve;
+mod sessions;
+mod state;
+mod sync;
+mod terminal;
+mod transform;
+mod tree;
+mod tui;
+mod webhooks;
 
+use std::{
+    collections::HashMap,
+    io::IsTerminal,
+    path::PathBuf,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use cost::CostGuard;
 use domain::models::{
-    agent::{Agent, AgentError, AgentRole, FunctionCall, Part},
-    tools::Tool,
+    agent::{Agent, AgentError, AgentRole, Content, FunctionCall, Part, PipeInputReader},
+    conversation::JsonlConversationStore,
+    events::AgentEvent,
+    mode::{Mode, ModeConfig, ToolAllowlists},
+    tool_registry::{DangerLevel, ToolCategory, ToolMetadata},
+    tools::{Tool, ToolDecorators, ToolDescriptionDecorator, ToolOutput},
+    transcript::{SessionEvent, Transcript},
+    transcription::Transcriber,
+    turn::TurnHandler,
+    video::VideoAnalyst,
+    web_search::SearchEngine,
 };
+use domain::storage::sqlite::SqliteSessionStore;
+use language::LanguagePin;
+#[cfg(feature = "screenshot")]
+use models::tools::capture_screenshot::CaptureScreenshotTool;
 use models::{
-    models::gemini::GeminiModel,
-    tools::{list_files::ListFileTool, read_file::ReadFileTool},
+    hints::{StaticHints, TestCommand, ToolExamples, WorkspaceRoot},
+    lsp::LanguageServers,
+    media::GeminiMedia,
+    processors::{ProcessedTool, ProcessorChain},
+    tools::{
+        ask_remote_agent::AskRemoteAgentTool,
+        ask_video::AskVideoTool,
+        bulk_replace::BulkReplaceTool,
+        dependency_graph::DependencyGraphTool,
+        edit_file::EditFileTool,
+        formatter::Formatters,
+        git_apply_patch::GitApplyPatchTool,
+        git_blame::GitBlameTool,
+        git_checkout_branch::GitCheckoutBranchTool,
+        git_commit::GitCommitTool,
+        git_diff::GitDiffTool,
+        git_log::GitLogTool,
+        git_status::GitStatusTool,
+        glob::GlobTool,
+        list_files::ListFileTool,
+        locks::EditLocks,
+        read_file::ReadFileTool,
+        read_policy::ReadPolicy,
+        rename_symbol::RenameSymbolTool,
+        run_command::{CommandPolicy, RunCommandTool},
+        scan_todos::ScanTodosTool,
+        search::GrepTool,
+        snapshots::FileSnapshots,
+        summarize_directory::SummarizeDirectoryTool,
+        transcribe_audio::TranscribeAudioTool,
+        web_fetch::WebFetchTool,
+        web_search::WebSearchTool,
+        write_file::WriteFileTool,
+    },
+    transcription::GeminiTranscriber,
+    video::GeminiVideoAnalyst,
+    web_search::{BraveSearch, DuckDuckGoSearch, SearxngSearch},
 };
-use tokio::sync::Mutex;
+use paths::{conversation_path, sessions_db};
+use runner::{perform_function_call, pre_request};
+use terminal::{AnswerPrinter, PlainStderr, ScreenLog, styled_eprintln, styled_println};
 use tracing::{error, info};
 use tracing_subscriber::{Layer, layer::SubscriberExt};
 
+const TOOL_ENV_PREFIX: &str = "VOO_TOOL_ENV_";
+const TOOL_POSTPROCESS_PREFIX: &str = "VOO_TOOL_POSTPROCESS_";
+const FORMATTER_PREFIX: &str = "VOO_FORMATTER_";
+const LSP_PREFIX: &str = "VOO_LSP_";
+const MODE_TOOLS_PREFIX: &str = "VOO_MODE_TOOLS_";
+const MODE_PROMPT_PREFIX: &str = "VOO_MODE_PROMPT_";
+const MODE_TEMPERATURE_PREFIX: &str = "VOO_MODE_TEMPERATURE_";
+const TOOL_HINT_PREFIX: &str = "VOO_TOOL_HINT_";
+// The only tools offered in modes that must not change the workspace.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "read_file",
+    "list_files",
+    "summarize_directory",
+    "glob",
+    "grep",
+    "dependency_graph",
+    "scan_todos",
+    "git_status",
+    "git_diff",
+    "git_log",
+    "git_blame",
+    "web_fetch",
+    "web_search",
+];
+
+static AUTO_APPROVE: AtomicBool = AtomicBool::new(false);
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "voo",
+    version,
+    about = "An AI agent that works in the terminal"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Ask for a tool-call plan and wait for approval before executing anything
+    #[arg(long)]
+    plan_first: bool,
+    /// Continue the previous conversation in this directory
+    #[arg(long)]
+    resume: bool,
+    /// Continue a named session, creating it on first use
+    #[arg(long)]
+    session: Option<String>,
+    /// Apply file changes without asking for confirmation
+    #[arg(long, global = true)]
+    auto_approve: bool,
+    /// Model provider to use, overrides VOO_PROVIDER
+    #[arg(long, global = true)]
+    provider: Option<String>,
+    /// Mode to work in: code, architect, ask, debug or orchestrator
+    #[arg(long, global = true)]
+    mode: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect and replay recorded sessions
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+    /// List the available model providers
+    Providers,
+    /// Check that the live Gemini API still answers in the format voo expects
+    SchemaCheck,
+    /// Expose the agent over HTTP
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Number of workers processing queued jobs
+        #[arg(long, default_value_t = 2)]
+        workers: usize,
+    },
+    /// Bundle the config, sessions and conversations into an archive, without api keys
+    ExportState { archive: PathBuf },
+    /// Restore the config, sessions and conversations from an archive
+    ImportState {
+        archive: PathBuf,
+        /// Replace files that already exist
+        #[arg(long)]
+        force: bool,
+    },
+    /// Share prompts and tool policies with the team through a git repository
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommand,
+    },
+    /// Ask a single question without tools and print the answer
+    Ask {
+        /// The question, read from stdin when left out
+        prompt: Vec<String>,
+        /// A YouTube video the question is about, watched by Gemini
+        #[arg(long)]
+        video: Option<String>,
+    },
+    /// Send a prompt to another voo running `voo serve` and print its answer
+    Relay {
+        /// The prompt, read from stdin when left out
+        prompt: Vec<String>,
+        /// Address of the remote voo, overrides VOO_RELAY_URL
+        #[arg(long)]
+        url: Option<String>,
+        /// Continue a named session on the remote voo
+        #[arg(long = "remote-session")]
+        session: Option<String>,
+    },
+    /// Speak JSON-RPC on stdin and stdout, for editor extensions
+    Ide,
+    /// Chat in a full-screen terminal interface
+    Tui,
+    /// Plan and generate a new project under a new directory, after a preview
+    Scaffold {
+        /// What to build, e.g. "a CLI todo app in Rust"
+        description: Vec<String>,
+        /// Directory to create, instead of the one the model names
+        #[arg(long)]
+        dir: Option<String>,
+    },
+    /// Migrate the workspace from one library or framework to another, in checked batches
+    Migrate {
+        /// What the code uses now, e.g. actix
+        #[arg(long)]
+        from: String,
+        /// What it should use, e.g. axum
+        #[arg(long)]
+        to: String,
+        /// Files migrated before every check
+        #[arg(long, default_value_t = 5)]
+        batch_size: usize,
+        /// Command that must pass after every batch
+        #[arg(long, default_value = "cargo check")]
+        check: String,
+        /// Inventory the call sites again instead of resuming
+        #[arg(long)]
+        restart: bool,
+    },
+    /// Rewrite stdin as instructed and print the result, without tools
+    Transform {
+        /// What to change, e.g. "convert to async"
+        #[arg(long, short)]
+        instruction: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SyncCommand {
+    /// Fetch the team config, cloning the repository on first use
+    Pull,
+    /// Commit and push changes to the team config
+    Push {
+        /// The commit message
+        #[arg(long, short)]
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SessionsCommand {
+    /// List recorded sessions
+    List,
+    /// Show the number of recorded sessions and the tokens they used
+    Stats,
+    /// List named sessions started with --session
+    Named,
+    /// Delete a named session
+    Delete { name: String },
+    /// Re-render a recorded session turn by turn
+    Replay {
+        id: String,
+        /// Pause after every turn and allow re-running it against the current provider
+        #[arg(long)]
+        step: bool,
+    },
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
-    setup_tracing();
+    let config = config::load()?;
+    let piped = !std::io::stdin().is_terminal();
+    terminal::set_plain(
+        piped || !std::io::stdout().is_terminal() || std::env::var_os("NO_COLOR").is_some(),
+    );
+    setup_tracing(&config.logging)?;
+    config::set(config);
+    policy::set(policy::load()?);
+    crash::install();
+
+    let cli = Cli::parse();
+    AUTO_APPROVE.store(cli.auto_approve, Ordering::SeqCst);
+    if let Some(provider) = cli.provider {
+        providers::select(provider);
+    }
+    if let Some(mode) = cli.mode {
+        let mode = Mode::parse(&mode).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown mode: {}, expected code, architect, ask, debug or orchestrator",
+                mode
+            )
+        })?;
+        _ = MODE.set(mode);
+    }
+
+    match cli.command {
+        Some(Command::Sessions { command }) => match command {
+            SessionsCommand::List => sessions::list(),
+            SessionsCommand::Stats => sessions::stats(),
+            SessionsCommand::Named => sessions::list_named(),
+            SessionsCommand::Delete { name } => sessions::delete_named(&name),
+            SessionsCommand::Replay { id, step } => sessions::replay(&id, step).await,
+        },
+        Some(Command::Providers) => {
+            providers::list();
+            Ok(())
+        }
+        Some(Command::SchemaCheck) => providers::schema_check().await,
+        Some(Command::Serve { addr, workers }) => serve::run(&addr, workers).await,
+        Some(Command::ExportState { archive }) => state::export(&archive),
+        Some(Command::ImportState { archive, force }) => state::import(&archive, force),
+        Some(Command::Sync { command }) => match command {
+            SyncCommand::Pull => sync::pull(),
+            SyncCommand::Push { message } => sync::push(message.as_deref()),
+        },
+        Some(Command::Ask { prompt, video }) => ask::run(&prompt, video.as_deref()).await,
+        Some(Command::Relay {
+            prompt,
+            url,
+            session,
+        }) => relay::run(&prompt, url.as_deref(), session.as_deref()).await,
+        Some(Command::Ide) => ide::run().await,
+        Some(Command::Tui) => tui::run().await,
+        Some(Command::Scaffold { description, dir }) => {
+            scaffold::run(&description.join(" "), dir.as_deref()).await
+        }
+        Some(Command::Migrate {
+            from,
+            to,
+            batch_size,
+            check,
+            restart,
+        }) => {
+            let options = migrate::MigrateOptions {
+                batch_size,
+                check,
+                restart,
+            };
+            migrate::run(&from, &to, &options).await
+        }
+        Some(Command::Transform { instruction }) => transform::run(&instruction).await,
+        None => chat(cli.plan_first, cli.resume, cli.session, piped).await,
+    }
+}
+
+pub async fn build_agent() -> anyhow::Result<Agent> {
+    build_agent_with(&providers::selected(), auto_approve()).await
+}
 
-    let api_key = std::env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY must be set");
+// `voo serve` builds the agent of every job with the provider it was submitted
+// for, and approves tool calls for the jobs of operators.
+pub async fn build_agent_with(provider: &str, auto_approve: bool) -> anyhow::Result<Agent> {
+    let client = providers::registry().build(provider)?;
 
-    let gemini = GeminiModel::new(api_key);
+    // Shared by the file tools, so edits of files the user changed since the
+    // agent read them are refused.
+    let snapshots = FileSnapshots::new();
+    let read_policy = read_policy()?;
     let read_file_tool = ReadFileTool::new(
         "read_file",
         "Read the contents of a given relative file path. Use this when you want to see what's inside a file. Do not use this with directory names.",
-    );
+    )
+    .with_snapshots(snapshots.clone())
+    .with_read_policy(read_policy.clone());
     let list_file_tool = ListFileTool::new(
         "list_files",
         "List the files of a given relative file path. Use this when you want to see what's inside a directory.",
     );
+    let summarize_directory_tool = SummarizeDirectoryTool::new(
+        "summarize_directory",
+        "Summarize a directory within a token budget: file counts by extension, the biggest subdirectories and files, notable files such as manifests and a README excerpt. Use it instead of list_files for large or unfamiliar directories.",
+    )
+    .with_read_policy(read_policy.clone());
+
+    let formatters = formatters();
+    let mut write_file_tool = WriteFileTool::new(
+        "write_file",
+        "Write the complete contents of a file at a given relative path, creating it if it does not exist. The user sees a diff and must approve the change.",
+    )
+    .with_auto_approve(auto_approve)
+    .with_snapshots(snapshots.clone())
+    .with_read_policy(read_policy.clone());
+    let mut edit_file_tool = EditFileTool::new(
+        "edit_file",
+        "Replace an exact string in a file with another, for small targeted edits instead of rewriting the whole file. old_string must match exactly once unless expected_replacements is set. The user sees a diff and must approve the change.",
+    )
+    .with_auto_approve(auto_approve)
+    .with_snapshots(snapshots.clone())
+    .with_read_policy(read_policy.clone());
+    let mut bulk_replace_tool = BulkReplaceTool::new(
+        "bulk_replace",
+        "Replace every match of a regular expression in the files matching a glob, e.g. to rename an identifier across the workspace. The user sees a diff of all changes and must approve them.",
+    )
+    .with_auto_approve(auto_approve)
+    .with_read_policy(read_policy.clone());
+
+    let mut rename_symbol_tool = RenameSymbolTool::new(
+        "rename_symbol",
+        "Rename a variable, function, type or other symbol everywhere it is used, through the language server. Safer than text replacement since it only touches real references. The user sees a diff and must approve the change.",
+        language_servers(),
+    )
+    .with_auto_approve(auto_approve);
+
+    let glob_tool = GlobTool::new(
+        "glob",
+        "Find files whose path matches a glob such as src/**/*.rs, skipping files ignored by .gitignore. Prefer it over listing directories one by one.",
+    );
+
+    let mut grep_tool = GrepTool::new(
+        "grep",
+        "Search file contents with a regular expression and get path:line: snippet results, skipping files ignored by .gitignore. Use it to locate definitions and usages before reading files.",
+    )
+    .with_read_policy(read_policy.clone());
+    if let Some(max_results) = std::env::var("VOO_GREP_MAX_RESULTS")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+    {
+        grep_tool = grep_tool.with_max_results(max_results);
+    }
+
+    let scan_todos_tool = ScanTodosTool::new(
+        "scan_todos",
+        "Find TODO, FIXME and HACK comments across the workspace with their author and date from git blame. Use it to answer what is left unfinished or to plan follow-up work.",
+    )
+    .with_read_policy(read_policy.clone());
+
+    let git_blame_tool = GitBlameTool::new(
+        "git_blame",
+        "Show who last changed each line of a file range, when, and the summary of each commit. Use it to find out why and by whom code was changed while debugging.",
+    )
+    .with_read_policy(read_policy.clone());
+
+    let git_status_tool = GitStatusTool::new(
+        "git_status",
+        "Show the current branch, how far it is ahead of or behind its upstream, and the staged, unstaged and untracked files. Use it to see what the user is working on before changing anything.",
+    );
+
+    let git_diff_tool = GitDiffTool::new(
+        "git_diff",
+        "Show the uncommitted changes, only the staged ones, or the changes of a commit, as a list of files with added and removed line counts and a size-limited patch. Use it to review or explain current work.",
+    )
+    .with_read_policy(read_policy.clone());
+
+    let git_log_tool = GitLogTool::new(
+        "git_log",
+        "List recent commits with their author, date and summary, optionally only those that changed a path. Use it to learn the history of a file or what changed lately.",
+    );
+
+    let git_commit_tool = GitCommitTool::new(
+        "git_commit",
+        "Commit changes to git, staging the given paths first. Use it when the user asks to commit your edits, with a summary line that says what the change does. The user sees the files and the message and must approve, and can reword the summary.",
+    )
+    .with_auto_approve(auto_approve)
+    .with_read_policy(read_policy.clone());
+
+    let git_checkout_branch_tool = GitCheckoutBranchTool::new(
+        "git_checkout_branch",
+        "Switch to a local git branch, or create one at the current commit and switch to it, e.g. before committing work that belongs on its own branch. Fails without changing anything when uncommitted changes would be overwritten. The user must approve.",
+    )
+    .with_auto_approve(auto_approve);
+
+    let mut git_apply_patch_tool = GitApplyPatchTool::new(
+        "git_apply_patch",
+        "Apply a unified diff to the working tree, e.g. to change several files at once. A patch that doesn't apply cleanly changes nothing. The user sees the patch and must approve it.",
+    )
+    .with_auto_approve(auto_approve)
+    .with_snapshots(snapshots)
+    .with_read_policy(read_policy.clone());
+
+    let dependency_graph_tool = DependencyGraphTool::new(
+        "dependency_graph",
+        "List the workspace files that import a given file (or, with direction 'dependencies', the files it imports), from Rust use/mod, JavaScript/TypeScript imports and Python imports. Use it to find what a change could affect.",
+    )
+    .with_read_policy(read_policy.clone());
+
+    let run_command_tool = RunCommandTool::new(
+        "run_command",
+        "Run a shell command with bash, e.g. to build, run tests or reproduce a bug, and get its exit code, stdout and stderr. The user must approve each command and dangerous or network commands are refused.",
+    )
+    .with_policy(command_policy())
+    .with_checkpoints(checkpoints_enabled())
+    .with_auto_approve(auto_approve);
+
+    if let Some(locks) = edit_locks() {
+        write_file_tool = write_file_tool.with_locks(locks.clone());
+        edit_file_tool = edit_file_tool.with_locks(locks.clone());
+        bulk_replace_tool = bulk_replace_tool.with_locks(locks.clone());
+        git_apply_patch_tool = git_apply_patch_tool.with_locks(locks.clone());
+        rename_symbol_tool = rename_symbol_tool.with_locks(locks);
+    }
+    if let Some(formatters) = formatters {
+        write_file_tool = write_file_tool.with_formatters(formatters.clone());
+        edit_file_tool = edit_file_tool.with_formatters(formatters.clone());
+        bulk_replace_tool = bulk_replace_tool.with_formatters(formatters);
+    }
+
+    let config = config::get();
+    let mut agent = Agent::new(client)
+        .with_allowlists(tool_allowlists())
+        .with_tool_limits(runner::tool_limits());
+    for (mode, mode_config) in mode_configs()? {
+        agent = agent.with_mode_config(mode, mode_config);
+    }
+    if let Ok(root) = std::env::current_dir() {
+        agent = agent.with_project_context(root);
+    }
+    let read = ToolMetadata::new(ToolCategory::Read, DangerLevel::Safe);
+    let search = ToolMetadata::new(ToolCategory::Search, DangerLevel::Safe);
+    let edit = ToolMetadata::new(ToolCategory::Edit, DangerLevel::Mutating);
+    // Tools that send workspace data to another host.
+    let external = ToolMetadata::new(ToolCategory::Read, DangerLevel::External);
+    add_tool(&agent, Arc::new(read_file_tool), read).await?;
+    add_tool(&agent, Arc::new(list_file_tool), read).await?;
+    add_tool(&agent, Arc::new(summarize_directory_tool), read).await?;
+    add_tool(&agent, Arc::new(glob_tool), search).await?;
+    add_tool(&agent, Arc::new(grep_tool), search).await?;
+    add_tool(&agent, Arc::new(write_file_tool), edit).await?;
+    add_tool(&agent, Arc::new(edit_file_tool), edit).await?;
+    add_tool(&agent, Arc::new(bulk_replace_tool), edit).await?;
+    add_tool(&agent, Arc::new(rename_symbol_tool), edit).await?;
+    add_tool(&agent, Arc::new(dependency_graph_tool), search).await?;
+    add_tool(&agent, Arc::new(scan_todos_tool), search).await?;
+    add_tool(&agent, Arc::new(git_status_tool), read).await?;
+    add_tool(&agent, Arc::new(git_diff_tool), read).await?;
+    add_tool(&agent, Arc::new(git_log_tool), read).await?;
+    add_tool(&agent, Arc::new(git_blame_tool), read).await?;
+    add_tool(&agent, Arc::new(git_apply_patch_tool), edit).await?;
+    add_tool(&agent, Arc::new(git_checkout_branch_tool), edit).await?;
+    add_tool(&agent, Arc::new(git_commit_tool), edit).await?;
+    let web_fetch_tool = web_fetch_tool()?.with_auto_approve(auto_approve);
+    let web_fetch = match web_fetch_tool.is_restricted() {
+        true => read,
+        false => external,
+    };
+    add_tool(&agent, Arc::new(web_fetch_tool), web_fetch).await?;
+    let web_search_tool = WebSearchTool::new(
+        "web_search",
+        "Search the web and get the titles, URLs and snippets of the top results, e.g. to find the documentation of an unfamiliar API or a fix for an error message. Read the promising results with web_fetch.",
+        search_engine()?,
+    );
+    add_tool(&agent, Arc::new(web_search_tool), external).await?;
+    #[cfg(feature = "screenshot")]
+    {
+        let capture_screenshot_tool = CaptureScreenshotTool::new(
+            "capture_screenshot",
+            "Take a screenshot of the user's screen, or the image they copied to the clipboard, and look at it. Use it when the user asks about something they see, e.g. why a UI looks wrong.",
+        )
+        .with_auto_approve(auto_approve);
+        add_tool(&agent, Arc::new(capture_screenshot_tool), external).await?;
+    }
+    add_tool(
+        &agent,
+        Arc::new(run_command_tool),
+        ToolMetadata::new(ToolCategory::Execute, DangerLevel::Dangerous),
+    )
+    .await?;
+    // Both tools are only offered with a Gemini API key, whichever provider
+    // answers the chat.
+    let media = gemini_media().unwrap_or_else(|e| {
+        info!("[VOO] transcribe_audio and ask_video are off: {}", e);
+        None
+    });
+    if let Some(media) = media {
+        let transcribe_audio_tool = TranscribeAudioTool::new(
+            "transcribe_audio",
+            "Transcribe an audio file such as a meeting recording or voice memo, optionally with instructions like 'write meeting notes' or 'list the action items'. Use it whenever the user refers to a recording.",
+            transcriber(media.clone()),
+        )
+        .with_read_policy(read_policy)
+        .with_auto_approve(auto_approve);
+        add_tool(&agent, Arc::new(transcribe_audio_tool), external).await?;
+        let ask_video_tool = AskVideoTool::new(
+            "ask_video",
+            "Answer a question about a YouTube video, e.g. summarize a talk or find the step of a tutorial the user is stuck on. The video is watched by a model, so ask for exactly what you need.",
+            video_analyst(media),
+        );
+        add_tool(&agent, Arc::new(ask_video_tool), read).await?;
+    }
+    if config::var("VOO_RELAY_URL").is_ok() {
+        let ask_remote_agent_tool = AskRemoteAgentTool::new(
+            "ask_remote_agent",
+            "Hand a self-contained task to a remote agent running a stronger model, e.g. a hard design question or a large review, and get its answer. It cannot see local files, so include the code and context it needs in the prompt.",
+            relay::remote(None)?,
+        )
+        .with_auto_approve(auto_approve);
+        add_tool(
+            &agent,
+            Arc::new(ask_remote_agent_tool),
+            ToolMetadata::new(ToolCategory::Delegate, DangerLevel::External),
+        )
+        .await?;
+    }
+
+    let tool_env = std::env::vars()
+        .filter_map(|(key, value)| Some((key.strip_prefix(TOOL_ENV_PREFIX)?.to_string(), value)))
+        .collect::<Vec<(String, String)>>();
+    for (key, value) in tool_env {
+        agent.set_env(&key, &value).await;
+    }
 
-    let read_file_tool: Arc<dyn Tool> = Arc::new(read_file_tool);
-    let list_file_tool: Arc<dyn Tool> = Arc::new(list_file_tool);
+    if let Some(prompt) = agent.client().system_prompt().await
+        && let Some(prompt) = config.system_prompt(prompt)
+    {
+        agent
+            .client()
+            .set_system_prompt(prompt)
+            .await
+            .map_err(|e| anyhow::anyhow!("Error setting system prompt: {}", e))?;
+    }
+    agent
+        .refresh_project_context()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error adding project instructions: {}", e))?;
 
-    let agent = Agent::new(gemini);
     agent
-        .add_tool(read_file_tool)
+        .set_tool_decorators(tool_decorators())
         .await
-        .map_err(|e| anyhow::anyhow!("Error adding tool: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Error adding tool hints: {}", e))?;
+
+    let mode = MODE.get().copied().unwrap_or_default();
     agent
-        .add_tool(list_file_tool)
+        .set_mode(mode)
         .await
-        .map_err(|e| anyhow::anyhow!("Error adding tool: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Error switching to {} mode: {}", mode, e))?;
+
+    Ok(agent)
+}
+
+// Format-on-write is opt-in with VOO_FORMAT_ON_WRITE, and VOO_FORMATTER_<EXT>
+// replaces the formatter of an extension, e.g. VOO_FORMATTER_JS="biome format --write".
+fn formatters() -> Option<Formatters> {
+    let enabled = std::env::var("VOO_FORMAT_ON_WRITE")
+        .is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"));
+    if !enabled {
+        return None;
+    }
+
+    let mut formatters = Formatters::builtin();
+    for (key, value) in std::env::vars() {
+        if let Some(extension) = key.strip_prefix(FORMATTER_PREFIX) {
+            let command = value.split_whitespace().collect::<Vec<&str>>();
+            formatters = formatters.with_command(extension, &command);
+        }
+    }
+
+    Some(formatters)
+}
+
+// VOO_LSP_<EXT> replaces the language server of an extension, e.g.
+// VOO_LSP_PY="pyright-langserver --stdio".
+fn language_servers() -> LanguageServers {
+    let mut servers = LanguageServers::builtin();
+    for (key, value) in std::env::vars() {
+        if let Some(extension) = key.strip_prefix(LSP_PREFIX) {
+            let command = value.split_whitespace().collect::<Vec<&str>>();
+            servers = servers.with_command(extension, &command);
+        }
+    }
+
+    servers
+}
+
+// Ask and Architect mode only offer read-only tools. VOO_MODE_TOOLS_<MODE>
+// replaces the tools of a mode, e.g. VOO_MODE_TOOLS_DEBUG="read_file,grep,run_command".
+fn tool_allowlists() -> ToolAllowlists {
+    let mut allowlists = ToolAllowlists::default()
+        .with_tools(Mode::Ask, READ_ONLY_TOOLS)
+        .with_tools(Mode::Architect, READ_ONLY_TOOLS);
+
+    for (key, value) in std::env::vars() {
+        let Some(mode) = key.strip_prefix(MODE_TOOLS_PREFIX).and_then(Mode::parse) else {
+            continue;
+        };
+        let tools = value
+            .split(',')
+            .map(str::trim)
+            .filter(|tool| !tool.is_empty())
+            .collect::<Vec<&str>>();
+        allowlists = allowlists.with_tools(mode, &tools);
+    }
+
+    allowlists
+}
+
+// VOO_MODE_PROMPT_<MODE> replaces the system prompt of a mode, with
+// {{current_mode}} filled in like in the default one, and
+// VOO_MODE_TEMPERATURE_<MODE> sets the sampling temperature of a mode.
+fn mode_configs() -> anyhow::Result<HashMap<Mode, ModeConfig>> {
+    let mut configs: HashMap<Mode, ModeConfig> = HashMap::new();
+
+    for mode in Mode::ALL {
+        let name = mode.to_string().to_uppercase();
+        if let Ok(prompt) = config::var(&format!("{}{}", MODE_PROMPT_PREFIX, name)) {
+            configs.entry(mode).or_default().system_prompt = Some(prompt);
+        }
+        let key = format!("{}{}", MODE_TEMPERATURE_PREFIX, name);
+        if let Ok(value) = config::var(&key) {
+            let temperature = value
+                .trim()
+                .parse::<f32>()
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", key, e))?;
+            configs.entry(mode).or_default().temperature = Some(temperature);
+        }
+    }
+
+    Ok(configs)
+}
+
+// VOO_RUN_ALLOW restricts run_command to the listed command prefixes and
+// VOO_RUN_DENY adds to the builtin denylist, both comma separated. The config
+// file sets them with `run_allow` and `run_deny` under `[tools]`.
+fn command_policy() -> CommandPolicy {
+    let rules = |key: &str| {
+        config::var(key)
+            .unwrap_or_default()
+            .split(',')
+            .map(|rule| rule.trim().to_string())
+            .filter(|rule| !rule.is_empty())
+            .collect::<Vec<String>>()
+    };
+    let allowed = rules("VOO_RUN_ALLOW");
+    let denied = rules("VOO_RUN_DENY");
+
+    CommandPolicy::builtin()
+        .with_allowed(&allowed.iter().map(String::as_str).collect::<Vec<&str>>())
+        .with_denied(&denied.iter().map(String::as_str).collect::<Vec<&str>>())
+}
+
+// The Gemini client of the tools that hand media to Gemini whatever the
+// provider, None without a Gemini API key. The organization policy must allow
+// Gemini and its endpoint, media would otherwise leave by a side door.
+fn gemini_media() -> anyhow::Result<Option<Arc<GeminiMedia>>> {
+    let Some(api_key) = config::var("GEMINI_API_KEY")
+        .ok()
+        .filter(|api_key| !api_key.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let mut media = GeminiMedia::new(&api_key);
+    policy::get().check_provider("gemini")?;
+    policy::get().check_endpoint("gemini", &media.endpoint().base_url)?;
+    if let Some(upload_url) = media.upload_url()
+        && let Err(e) = policy::get().check_endpoint("gemini", &upload_url)
+    {
+        info!("[VOO] large media is off: {}", e);
+        media = media.without_uploads();
+    }
+    Ok(Some(Arc::new(media)))
+}
+
+// Audio is transcribed with the model of VOO_TRANSCRIBE_MODEL.
+fn transcriber(media: Arc<GeminiMedia>) -> Arc<dyn Transcriber> {
+    let mut transcriber = GeminiTranscriber::new(media);
+    if let Ok(model) = config::var("VOO_TRANSCRIBE_MODEL") {
+        transcriber = transcriber.with_model(model.trim());
+    }
+    Arc::new(transcriber)
+}
 
-    let _crate_name = env!("CARGO_PKG_NAME").to_uppercase();
-    let _crate_version = env!("CARGO_PKG_VERSION");
+// Videos are watched with the model of VOO_VIDEO_MODEL.
+fn video_analyst(media: Arc<GeminiMedia>) -> Arc<dyn VideoAnalyst> {
+    let mut analyst = GeminiVideoAnalyst::new(media);
+    if let Ok(model) = config::var("VOO_VIDEO_MODEL") {
+        analyst = analyst.with_model(model.trim());
+    }
+    Arc::new(analyst)
+}
+
+// VOO_READ_MAX_BYTES caps the size of the files the read tools take in, and
+// VOO_READ_ALLOW and VOO_READ_DENY limit them by extension, file name or mime
+// type, comma separated, the denylist adding to the builtin one. The config
+// file sets them with `read_max_bytes`, `read_allow` and `read_deny` under
+// `[tools]`.
+fn read_policy() -> anyhow::Result<ReadPolicy> {
+    let rules = |key: &str| {
+        config::var(key)
+            .unwrap_or_default()
+            .split(',')
+            .map(|rule| rule.trim().to_string())
+            .filter(|rule| !rule.is_empty())
+            .collect::<Vec<String>>()
+    };
+    let allowed = rules("VOO_READ_ALLOW");
+    let denied = rules("VOO_READ_DENY");
+
+    let mut policy = ReadPolicy::builtin()
+        .with_allowed(&allowed.iter().map(String::as_str).collect::<Vec<&str>>())
+        .with_denied(&denied.iter().map(String::as_str).collect::<Vec<&str>>());
+    if let Ok(value) = config::var("VOO_READ_MAX_BYTES") {
+        let max_bytes = value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid VOO_READ_MAX_BYTES {}: {}", value, e))?;
+        policy = policy.with_max_bytes(max_bytes);
+    }
+
+    Ok(policy)
+}
 
-    println!("Chat with VOO (use 'ctrl-c' to quit)\n");
+// VOO_FETCH_ALLOW limits web_fetch to comma separated domains and their
+// subdomains, VOO_FETCH_MAX_BYTES and VOO_FETCH_TIMEOUT cap the size of a page
+// and the seconds spent on it. The config file sets them with `fetch_allow`,
+// `fetch_max_bytes` and `fetch_timeout` under `[tools]`.
+fn web_fetch_tool() -> anyhow::Result<WebFetchTool> {
+    let allowed = config::var("VOO_FETCH_ALLOW").unwrap_or_default();
+    let allowed = allowed
+        .split(',')
+        .map(str::trim)
+        .filter(|domain| !domain.is_empty())
+        .collect::<Vec<&str>>();
 
-    let mut should_read_input = true;
+    let mut tool = WebFetchTool::new(
+        "web_fetch",
+        "Download a web page and return it as readable text, e.g. documentation of a library or an error message the user linked. Use it for pages referenced during the task, not for searching the web.",
+    )
+    .with_allowed_domains(&allowed);
+    if let Ok(value) = config::var("VOO_FETCH_MAX_BYTES") {
+        let max_bytes = value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid VOO_FETCH_MAX_BYTES {}: {}", value, e))?;
+        tool = tool.with_max_bytes(max_bytes);
+    }
+    if let Ok(value) = config::var("VOO_FETCH_TIMEOUT") {
+        let secs = value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid VOO_FETCH_TIMEOUT {}: {}", value, e))?;
+        tool = tool.with_timeout(Duration::from_secs(secs));
+    }
+
+    Ok(tool)
+}
 
-    'main: loop {
-        let input = if should_read_input {
-            let input = agent
+// VOO_SEARCH_PROVIDER picks the engine of web_search: brave with
+// BRAVE_API_KEY, searxng with the instance at VOO_SEARXNG_URL or duckduckgo.
+// Without it Brave is used when there is a key, then SearXNG when there is
+// an instance, and DuckDuckGo otherwise.
+fn search_engine() -> anyhow::Result<Arc<dyn SearchEngine>> {
+    let var = |key: &str| {
+        config::var(key)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let brave_key = var("BRAVE_API_KEY");
+    let searxng_url = var("VOO_SEARXNG_URL");
+    let provider = match var("VOO_SEARCH_PROVIDER") {
+        Some(provider) => provider.to_ascii_lowercase(),
+        None if brave_key.is_some() => "brave".to_string(),
+        None if searxng_url.is_some() => "searxng".to_string(),
+        None => "duckduckgo".to_string(),
+    };
+
+    Ok(match provider.as_str() {
+        "brave" => {
+            let api_key = brave_key.ok_or_else(|| {
+                anyhow::anyhow!("VOO_SEARCH_PROVIDER is brave but BRAVE_API_KEY is not set")
+            })?;
+            Arc::new(BraveSearch::new(&api_key))
+        }
+        "searxng" => {
+            let base_url = searxng_url.ok_or_else(|| {
+                anyhow::anyhow!("VOO_SEARCH_PROVIDER is searxng but VOO_SEARXNG_URL is not set")
+            })?;
+            Arc::new(SearxngSearch::new(&base_url))
+        }
+        "duckduckgo" => Arc::new(DuckDuckGoSearch::default()),
+        provider => {
+            return Err(anyhow::anyhow!(
+                "Unknown VOO_SEARCH_PROVIDER {} (expected brave, searxng or duckduckgo)",
+                provider
+            ));
+        }
+    })
+}
+
+// Checkpoints before risky commands are on unless VOO_CHECKPOINTS turns them off.
+// The tool examples and workspace hints are on unless VOO_TOOL_EXAMPLES or
+// VOO_TOOL_HINTS is off, and VOO_TOOL_HINT_<TOOL> adds a fixed hint, e.g.
+// VOO_TOOL_HINT_RUN_COMMAND="Run tests with make check".
+fn tool_decorators() -> ToolDecorators {
+    let mut decorators: Vec<Arc<dyn ToolDescriptionDecorator>> = vec![];
+    let examples = !std::env::var("VOO_TOOL_EXAMPLES")
+        .is_ok_and(|value| matches!(value.trim(), "0" | "false" | "no"));
+    if examples {
+        decorators.push(Arc::new(ToolExamples));
+    }
+    let enabled = !std::env::var("VOO_TOOL_HINTS")
+        .is_ok_and(|value| matches!(value.trim(), "0" | "false" | "no"));
+    if enabled {
+        decorators.push(Arc::new(WorkspaceRoot));
+        decorators.push(Arc::new(TestCommand::new("run_command")));
+    }
+
+    let hints = std::env::vars()
+        .filter_map(|(key, value)| {
+            let tool = key.strip_prefix(TOOL_HINT_PREFIX)?.to_lowercase();
+            Some((tool, value))
+        })
+        .filter(|(_, hint)| !hint.trim().is_empty())
+        .fold(StaticHints::default(), |hints, (tool, hint)| {
+            hints.with_hint(&tool, &hint)
+        });
+    if !hints.is_empty() {
+        decorators.push(Arc::new(hints));
+    }
+
+    ToolDecorators::new(decorators)
+}
+
+// Opt-in, since only tools that look for the lock files benefit.
+fn edit_locks() -> Option<EditLocks> {
+    config::var("VOO_EDIT_LOCKS")
+        .is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+        .then(|| EditLocks::new("voo"))
+}
+
+fn checkpoints_enabled() -> bool {
+    !std::env::var("VOO_CHECKPOINTS")
+        .is_ok_and(|value| matches!(value.trim(), "0" | "false" | "no"))
+}
+
+pub fn auto_approve() -> bool {
+    AUTO_APPROVE.load(Ordering::SeqCst)
+        || std::env::var("VOO_AUTO_APPROVE")
+            .is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+}
+
+// Tools the policy disables are never registered, those the config leaves out
+// are registered disabled so `/tools enable` can bring them back.
+async fn add_tool(
+    agent: &Agent,
+    tool: Arc<dyn Tool>,
+    metadata: ToolMetadata,
+) -> anyhow::Result<()> {
+    if !policy::get().tool_allowed(tool.name()) {
+        return Ok(());
+    }
+    let enabled = config::get().tool_enabled(tool.name());
+    let key = format!("{}{}", TOOL_POSTPROCESS_PREFIX, tool.name().to_uppercase());
+
+    let tool = match std::env::var(&key) {
+        Ok(spec) => {
+            let chain = ProcessorChain::parse(&spec)
+                .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", key, e))?;
+            Arc::new(ProcessedTool::new(tool, chain))
+        }
+        Err(_) => tool,
+    };
+
+    agent
+        .add_tool(tool, metadata, enabled)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error adding tool: {}", e))
+}
+
+// With piped input, e.g. `echo "summarize src/main.rs" | voo`, the whole
+// input is a single prompt and the chat ends once it is answered.
+async fn chat(
+    plan_first: bool,
+    resume: bool,
+    session: Option<String>,
+    piped: bool,
+) -> anyhow::Result<()> {
+    let mut agent = build_agent().await?;
+    if piped {
+        agent = agent.with_reader(PipeInputReader::stdin());
+    }
+    agent.observe(AnswerPrinter::default());
+    // Named sessions exist to be continued, so they always resume.
+    let (mut agent, resume) = match &session {
+        Some(name) => {
+            let store = SqliteSessionStore::open(&sessions_db())
+                .map_err(|e| anyhow::anyhow!("Error opening session store: {}", e))?;
+            let conversation = store
+                .session(name)
+                .map_err(|e| anyhow::anyhow!("Error opening session {}: {}", name, e))?;
+            (agent.with_store(conversation), true)
+        }
+        None => (
+            agent.with_store(JsonlConversationStore::new(conversation_path())),
+            resume,
+        ),
+    };
+    // Held for the whole chat, so `voo serve` or a second terminal can't
+    // write to the same conversation in between.
+    let _lock = agent.lock_conversation("voo chat").map_err(|e| {
+        anyhow::anyhow!(
+            "Error opening conversation: {}, use --session <name> to start a separate one",
+            e
+        )
+    })?;
+    let cost_guard = CostGuard::from_env()?;
+    let language = LanguagePin::default();
+    let mut transcript = Transcript::create(sessions::storage()?);
+
+    if resume {
+        let recorded = agent
+            .stored_prompt_version()
+            .map_err(|e| anyhow::anyhow!("Error resuming conversation: {}", e))?;
+        let restored = agent
+            .resume()
+            .await
+            .map_err(|e| anyhow::anyhow!("Error resuming conversation: {}", e))?;
+        info!("[VOO] resumed {} messages", restored);
+        if restored > 0 {
+            sessions::warn_if_stale(recorded);
+        }
+    } else {
+        agent
+            .forget()
+            .await
+            .map_err(|e| anyhow::anyhow!("Error clearing previous conversation: {}", e))?;
+    }
+
+    if !piped {
+        interrupt::install();
+        println!("Chat with VOO (use 'ctrl-c' to quit)\n");
+    }
+    info!("[VOO] session {}", transcript.id());
+    record(
+        &transcript,
+        SessionEvent::SessionStarted {
+            system_prompt: agent.client().system_prompt().await,
+        },
+    );
+
+    // Set after runnable blocks of an answer ran, their output is in the
+    // conversation already and the model continues without new input.
+    let mut follow_up = false;
+
+    loop {
+        let input = match follow_up {
+            true => String::new(),
+            false => agent
                 .reader()
                 .read()
-                .map_err(|e| anyhow::anyhow!("Error reading input: {}", e))?;
-
-            input
-        } else {
-            "".to_string()
+                .map_err(|e| anyhow::anyhow!("Error reading input: {}", e))?,
         };
 
+        // The end of the input, from the pipe or ctrl-d.
+        if !follow_up && input.is_empty() {
+            break;
+        }
+        // A new turn, whatever an earlier command cancelled is done with.
+        interrupt::take_cancelled();
+
         if input.starts_with("exit") {
             info!("Bye!");
             break;
         }
 
-        if !should_read_input {
-            tokio::time::sleep(Duration::from_secs(1)).await;
-        }
-
-        let response = agent.client().ask(&input).await;
-        let agent_tools = agent.tools();
-
-        match response {
-            Ok(responses) => {
-                for response in responses {
-                    let function_calls = response
-                        .parts
-                        .iter()
-                        .map(|part| part.function_call.clone())
-                        .collect::<Vec<Option<FunctionCall>>>();
-                    let has_function_call = function_calls.iter().any(|call| call.is_some());
-
-                    if has_function_call {
-                        let tool_use = loop {
-                            let outputs =
-                                perform_function_call(agent_tools.clone(), &function_calls).await;
-
-                            match outputs {
-                                Ok(outputs) => {
-                                    break outputs;
-                                }
-                                Err(e) => {
-                                    error!("\x1b[41mvoo>\x1b[0m {}", e);
-                                    let err = format!("Error performing function call: {}", e);
-                                    _ = agent
-                                        .client()
-                                        .add_system_prompt(&err, AgentRole::User)
-                                        .await;
-                                    should_read_input = false;
-                                    continue 'main;
-                                }
-                            }
-                        };
-
-                        for output in &tool_use {
-                            if output.is_empty() {
-                                continue;
-                            }
-
-                            _ = agent
-                                .client()
-                                .add_system_prompt(&output, AgentRole::User)
-                                .await;
-                        }
-
-                        if !tool_use.is_empty() {
-                            should_read_input = false;
-                            continue 'main;
-                        }
-                    } else {
-                        print_response(&agent, &response.parts).await;
-                        should_read_input = true;
+        let plan_task = match input.trim().strip_prefix("/plan") {
+            Some(task) if task.is_empty() || task.starts_with(char::is_whitespace) => {
+                Some(task.trim())
+            }
+            _ if plan_first && !follow_up && !input.trim().starts_with('/') => Some(input.trim()),
+            _ => None,
+        };
+        if let Some(task) = plan_task {
+            match task {
+                "" => styled_println!("\x1b[41mplan>\x1b[0m usage: /plan <task>"),
+                task => {
+                    if let Err(e) = plan::run(&agent, task, &transcript).await {
+                        error!("\x1b[41mvoo>\x1b[0m {}", e);
                     }
                 }
             }
-            Err(AgentError::ExpiredApiKey) => {
-                error!(
-                    "\x1b[41mvoo>\x1b[0m API key expired. Please update the API key in the .env file."
-                );
+            continue;
+        }
+
+        if let Some(args) = input
+            .trim()
+            .strip_prefix("/handoff")
+            .filter(|args| args.is_empty() || args.starts_with(char::is_whitespace))
+        {
+            if let Err(e) = handoff::run(&mut agent, args, &transcript).await {
+                styled_println!("\x1b[41mhandoff>\x1b[0m {}", e);
+            }
+            continue;
+        }
+
+        if let Some(args) = input
+            .trim()
+            .strip_prefix("/fork")
+            .filter(|args| args.is_empty() || args.starts_with(char::is_whitespace))
+        {
+            if let Err(e) = branches::fork(&mut transcript, args) {
+                styled_println!("\x1b[41mfork>\x1b[0m {}", e);
+            }
+            continue;
+        }
+
+        if let Some(args) = input
+            .trim()
+            .strip_prefix("/branches")
+            .filter(|args| args.is_empty() || args.starts_with(char::is_whitespace))
+        {
+            if let Err(e) = branches::run(&mut agent, args, &mut transcript).await {
+                styled_println!("\x1b[41mbranches>\x1b[0m {}", e);
+            }
+            continue;
+        }
+
+        if let Some(args) = input
+            .trim()
+            .strip_prefix("/redact")
+            .filter(|args| args.is_empty() || args.starts_with(char::is_whitespace))
+        {
+            if let Err(e) = redact::run(&mut agent, args, &transcript).await {
+                styled_println!("\x1b[41mredact>\x1b[0m {}", e);
             }
+            continue;
+        }
+
+        if let Some(args) = input
+            .trim()
+            .strip_prefix("/lang")
+            .filter(|args| args.is_empty() || args.starts_with(char::is_whitespace))
+        {
+            if let Err(e) = language.command(&agent, args).await {
+                styled_println!("\x1b[41mlang>\x1b[0m {}", e);
+            }
+            continue;
+        }
+
+        if commands::handle(&agent, &input).await {
+            continue;
+        }
+
+        if !follow_up {
+            language.observe(&agent, &input).await;
+        }
+
+        let handler = ChatTurn {
+            transcript: &transcript,
+            cost_guard: &cost_guard,
+        };
+        follow_up = match agent.run_turn_with(&input, &handler).await {
+            Ok(turn) if turn.stopped => false,
+            Ok(turn) => match runnable::offer(&agent, &turn.answer, &transcript).await {
+                Ok(ran) => ran,
+                Err(e) => {
+                    error!("\x1b[41mvoo>\x1b[0m {}", e);
+                    false
+                }
+            },
             Err(e) => {
-                error!("\x1b[41mvoo>\x1b[0m {}", e);
-                _ = agent
-                    .client()
-                    .add_system_prompt(&e.to_string(), AgentRole::User)
-                    .await;
+                report_error(&agent, &transcript, e).await;
+                false
             }
+        };
+    }
+
+    agent.client().close().await;
+    // On stderr, so it doesn't end up in the piped answer.
+    if let Some(usage) = agent.client().usage().filter(|usage| usage.requests > 0) {
+        styled_eprintln!("\x1b[2mvoo> used {}\x1b[0m", usage);
+        if let Err(e) = transcript.record_usage(&usage) {
+            error!("[VOO] failed to record token usage: {}", e);
         }
     }
 
     Ok(())
 }
 
-async fn print_response(agent: &Agent, parts: &[Part]) {
-    for part in parts {
-        let text = part.text.as_ref();
-        if text.is_none() {
-            continue;
+// The chat's part in a turn: streams the answer, asks before expensive
+// requests, echoes the tool calls and records everything in the transcript and
+// the conversation.
+struct ChatTurn<'a> {
+    transcript: &'a Transcript,
+    cost_guard: &'a CostGuard,
+}
+
+#[async_trait]
+impl TurnHandler for ChatTurn<'_> {
+    async fn ask(&self, agent: &Agent, input: &str) -> Result<Option<Vec<Content>>, AgentError> {
+        let send = pre_request(agent, self.cost_guard, input)
+            .await
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?;
+        if !send {
+            styled_println!("\x1b[41mvoo>\x1b[0m turn cancelled");
+            return Ok(None);
         }
 
-        if let Some(text) = text {
-            println!("\x1b[32mvoo>\x1b[0m {}", text);
-            _ = agent
-                .client()
-                .add_system_prompt(&text, AgentRole::User)
-                .await;
+        match input.is_empty() {
+            true => tokio::time::sleep(Duration::from_secs(1)).await,
+            false => {
+                record(
+                    self.transcript,
+                    SessionEvent::UserMessage {
+                        text: input.trim_end().to_string(),
+                    },
+                );
+                remember(
+                    agent,
+                    &Content::new(vec![Part::new(input.trim_end())], "user"),
+                );
+            }
+        }
+
+        match interrupt::cancellable(agent.stream(input)).await {
+            Ok(response) => response.map(|content| Some(vec![content])),
+            Err(_) => {
+                interrupt::take_cancelled();
+                agent.emit(AgentEvent::Error {
+                    message: "request cancelled".to_string(),
+                });
+                record(
+                    self.transcript,
+                    SessionEvent::Error {
+                        message: "request cancelled".to_string(),
+                    },
+                );
+                styled_println!("\x1b[41mvoo>\x1b[0m request cancelled");
+                Ok(None)
+            }
         }
     }
-}
 
-async fn perform_function_call(
-    agent_tools: Arc<Mutex<HashMap<String, Arc<dyn Tool>>>>,
-    function_calls: &[Option<FunctionCall>],
-) -> anyhow::Result<Vec<String>> {
-    let mut tool_outputs = vec![];
-    for (_index, function_call) in function_calls.iter().enumerate() {
-        if let Some(function_call) = function_call {
-            let tool_name = function_call.name.clone();
-            let tool_input = function_call.args.clone();
-            let tool_input_str = serde_json::to_string(&tool_input).unwrap();
-            let tool = agent_tools.lock().await.get(&tool_name).unwrap().clone();
+    async fn execute(
+        &self,
+        agent: &Agent,
+        calls: &[FunctionCall],
+    ) -> Result<Vec<ToolOutput>, AgentError> {
+        let context = agent.tool_context().await;
+        perform_function_call(agent, calls, &context, true)
+            .await
+            .map_err(|e| {
+                error!("\x1b[41mvoo>\x1b[0m {}", e);
+                record(
+                    self.transcript,
+                    SessionEvent::Error {
+                        message: format!("Error performing function call: {}", e),
+                    },
+                );
+                AgentError::AgentError(Some(e.to_string()))
+            })
+    }
 
-            println!("\x1b[33m{}> {}\x1b[0m", tool_name, tool_input_str);
+    async fn on_response(&self, agent: &Agent, response: &Content) {
+        record(
+            self.transcript,
+            SessionEvent::ModelResponse {
+                content: response.clone(),
+            },
+        );
+        remember(agent, response);
 
-            let tool_output = tool.exec(tool_input).await;
+        let calls = response
+            .parts
+            .iter()
+            .filter_map(|part| part.function_call.as_ref())
+            .collect::<Vec<&FunctionCall>>();
+        for call in calls {
+            record(
+                self.transcript,
+                SessionEvent::ToolCall {
+                    name: call.name.clone(),
+                    args: call.args.clone(),
+                },
+            );
+        }
+    }
 
-            if let Err(e) = tool_output {
-                return Err(anyhow::anyhow!("Error executing tool: {}", e));
-            }
+    async fn on_tool_result(&self, agent: &Agent, call: &FunctionCall, output: &ToolOutput) {
+        let text = output.to_json().to_string();
+        record(
+            self.transcript,
+            SessionEvent::ToolResult {
+                name: call.name.clone(),
+                output: text.clone(),
+            },
+        );
+        let result = format!("Result of {}:\n{}", call.name, text);
+        remember(agent, &Content::new(vec![Part::new(&result)], "tool"));
+    }
 
-            let tool_output = tool_output.unwrap();
-            let tool_output_str = serde_json::to_string(&tool_output).unwrap();
+    // The results of cancelled calls go out with the next prompt instead.
+    fn should_continue(&self) -> bool {
+        !interrupt::take_cancelled()
+    }
+}
 
-            tool_outputs.push(tool_output_str);
+async fn report_error(agent: &Agent, transcript: &Transcript, e: AgentError) {
+    let message = match &e {
+        AgentError::ContextTooLong(message) => message
+            .clone()
+            .unwrap_or_else(|| "The prompt is too long".to_string()),
+        AgentError::RateLimited { message, .. } | AgentError::CircuitOpen { message, .. } => {
+            message.clone()
+        }
+        AgentError::QuotaExhausted(message) => message
+            .clone()
+            .unwrap_or_else(|| "Gemini quota exhausted".to_string()),
+        e => e.to_string(),
+    };
+    record(
+        transcript,
+        SessionEvent::Error {
+            message: message.clone(),
+        },
+    );
+
+    match e {
+        AgentError::InvalidApiKey(_) => error!(
+            "\x1b[41mvoo>\x1b[0m The API key is invalid or expired. Please update the API key in the .env file."
+        ),
+        AgentError::ContextTooLong(_) => error!(
+            "\x1b[41mvoo>\x1b[0m {}. Shorten the prompt or start a new session.",
+            message.trim_end_matches('.')
+        ),
+        AgentError::RateLimited { .. }
+        | AgentError::QuotaExhausted(_)
+        | AgentError::CircuitOpen { .. } => {
+            error!("\x1b[41mvoo>\x1b[0m {}", message)
+        }
+        _ => {
+            error!("\x1b[41mvoo>\x1b[0m {}", message);
+            _ = agent
+                .client()
+                .add_system_prompt(&message, AgentRole::User)
+                .await;
         }
     }
+}
 
-    Ok(tool_outputs)
+fn remember(agent: &Agent, content: &Content) {
+    if let Err(e) = agent.remember(content) {
+        error!("[VOO] failed to store conversation: {}", e);
+    }
+}
+
+fn record(transcript: &Transcript, event: SessionEvent) {
+    crash::remember(&event);
+    policy::audit(transcript.id(), &event);
+    if let Err(e) = transcript.record(event) {
+        error!("[VOO] failed to record session event: {}", e);
+    }
 }
 
-pub fn setup_tracing() {
+// RUST_LOG takes precedence over the level of the config file.
+pub fn setup_tracing(logging: &config::LoggingConfig) -> anyhow::Result<()> {
     let crate_name = env!("CARGO_CRATE_NAME");
     let crate_version = env!("CARGO_PKG_VERSION");
+    let level = logging.level.as_deref().unwrap_or("info");
 
     let filter_layer = tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-        format!("RUST_LOG=info,{}=info,domain=info,models=info,tokio=trace,runtime=trace,actix_web=info", crate_name).into()
+        format!(
+            "RUST_LOG={0},{1}={0},domain={0},models={0},tokio=trace,runtime=trace,actix_web=info",
+            level, crate_name
+        )
+        .into()
     });
 
-    let fmt_layer = tracing_subscriber::fmt::layer().with_filter(filter_layer);
-    let subscriber = tracing_subscriber::registry().with(fmt_layer);
+    let fmt_layer = match &logging.file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| anyhow::anyhow!("Error opening log file {}: {}", path.display(), e))?;
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(file))
+                .boxed()
+        }
+        // Keeps the output clean when it is piped.
+        None if terminal::plain() => tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(|| PlainStderr)
+            .boxed(),
+        None => tracing_subscriber::fmt::layer()
+            .with_writer(|| ScreenLog)
+            .boxed(),
+    };
+    let subscriber = tracing_subscriber::registry().with(fmt_layer.with_filter(filter_layer));
 
     tracing::subscriber::set_global_default(subscriber)
         .expect("Failed to set global default subscriber");
 
     info!("[VOO] {} v{}\n", crate_name, crate_version);
+    Ok(())
 }
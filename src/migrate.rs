@@ -0,0 +1,318 @@
+use std::{collections::BTreeMap, io::IsTerminal};
+
+use domain::models::{agent::Agent, mode::Mode};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::process::Command;
+
+use crate::{
+    auto_approve, build_agent,
+    paths::migration_path,
+    plan::{Plan, PlanProgress, PlanStep},
+    policy,
+    runner::run_prompt,
+    terminal::styled_println,
+};
+
+// Rounds of fixes a batch gets before the migration stops for the user.
+const MAX_ATTEMPTS: usize = 3;
+// The tail of the check output the model sees, where the errors end up.
+const MAX_CHECK_OUTPUT: usize = 8_000;
+
+#[derive(Debug)]
+pub struct MigrateOptions {
+    pub batch_size: usize,
+    pub check: String,
+    pub restart: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Inventory {
+    sites: Vec<Site>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Site {
+    path: String,
+    #[serde(default)]
+    line: Option<u64>,
+    #[serde(default)]
+    code: String,
+}
+
+// Inventories the call sites of `from` with the read-only tools, then migrates
+// them a batch of files at a time, running the check after every batch. The
+// inventory and the finished batches are saved, so an interrupted migration
+// resumes where it stopped.
+pub async fn run(from: &str, to: &str, options: &MigrateOptions) -> anyhow::Result<()> {
+    if !policy::get().tool_allowed("edit_file") {
+        return Err(anyhow::anyhow!("The policy does not allow editing files"));
+    }
+    if !auto_approve() && !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "voo migrate asks before changing files, run it in a terminal or pass --auto-approve"
+        ));
+    }
+
+    let path = migration_path(from, to);
+    if options.restart {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+
+    let agent = build_agent().await?;
+    let mut progress = match PlanProgress::load(&path)? {
+        Some(progress) => {
+            styled_println!(
+                "\x1b[32mmigrate>\x1b[0m resuming, {} of {} files done, pass --restart to inventory again",
+                progress.done,
+                progress.plan.steps.len()
+            );
+            progress
+        }
+        None => {
+            let Some(plan) = inventory(&agent, from, to).await? else {
+                styled_println!("\x1b[32mmigrate>\x1b[0m found nothing to migrate");
+                return Ok(());
+            };
+            let progress = PlanProgress::new(plan);
+            progress.save(&path)?;
+            progress
+        }
+    };
+    styled_println!("\x1b[32mmigrate>\x1b[0m {}", progress.plan);
+
+    agent
+        .set_mode(Mode::Code)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error switching to code mode: {}", e))?;
+
+    let total = progress.plan.steps.len();
+    while !progress.remaining().is_empty() {
+        let batch = progress
+            .remaining()
+            .iter()
+            .take(options.batch_size.max(1))
+            .cloned()
+            .collect::<Vec<PlanStep>>();
+        styled_println!(
+            "\x1b[32mmigrate>\x1b[0m [{}-{}/{}] {}",
+            progress.done + 1,
+            progress.done + batch.len(),
+            total,
+            batch
+                .iter()
+                .map(step_path)
+                .collect::<Vec<&str>>()
+                .join(", ")
+        );
+
+        run_prompt(&agent, &batch_prompt(from, to, &batch)).await?;
+        validate(&agent, &options.check).await?;
+
+        progress.finish(batch.len());
+        progress.save(&path)?;
+    }
+
+    _ = std::fs::remove_file(&path);
+    styled_println!(
+        "\x1b[32mmigrate>\x1b[0m migrated {} files from {} to {}",
+        total,
+        from,
+        to
+    );
+
+    Ok(())
+}
+
+async fn inventory(agent: &Agent, from: &str, to: &str) -> anyhow::Result<Option<Plan>> {
+    // Nothing changes until the inventory is saved.
+    agent
+        .set_mode(Mode::Ask)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error switching to ask mode: {}", e))?;
+
+    styled_println!("\x1b[32mmigrate>\x1b[0m looking for code using {}", from);
+    let answer = run_prompt(agent, &inventory_prompt(from, to)).await?;
+    let plan = parse_inventory(&answer, from, to)?;
+
+    Ok(Some(plan).filter(|plan| !plan.steps.is_empty()))
+}
+
+fn inventory_prompt(from: &str, to: &str) -> String {
+    format!(
+        r#"Do not change any files. The project is being migrated from {} to {}. Use the search tools (grep, glob) to find every call site, import and type in the workspace that uses {} and has to change.
+
+Reply with JSON only, in this shape:
+{{"sites": [{{"path": "relative/path", "line": 12, "code": "the line"}}]}}"#,
+        from, to, from
+    )
+}
+
+// One step per file, in the order the files were first reported.
+fn parse_inventory(text: &str, from: &str, to: &str) -> anyhow::Result<Plan> {
+    let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) else {
+        return Err(anyhow::anyhow!(
+            "The model did not return an inventory: {}",
+            text
+        ));
+    };
+
+    let inventory = serde_json::from_str::<Inventory>(&text[start..=end])
+        .map_err(|e| anyhow::anyhow!("Error parsing the inventory: {}", e))?;
+
+    let mut order = vec![];
+    let mut files = BTreeMap::<String, Vec<String>>::new();
+    for site in inventory.sites {
+        let sites = files.entry(site.path.clone()).or_insert_with(|| {
+            order.push(site.path.clone());
+            vec![]
+        });
+        let code = site.code.trim();
+        sites.push(match site.line {
+            Some(line) => format!("{}: {}", line, code),
+            None => code.to_string(),
+        });
+    }
+
+    let steps = order
+        .into_iter()
+        .map(|path| {
+            let sites = files.remove(&path).unwrap_or_default();
+            PlanStep {
+                tool: "edit_file".to_string(),
+                reason: Some(match sites.len() {
+                    1 => "1 call site".to_string(),
+                    count => format!("{} call sites", count),
+                }),
+                args: json!({ "path": path, "sites": sites }),
+            }
+        })
+        .collect();
+
+    Ok(Plan {
+        summary: format!("Migrate from {} to {}", from, to),
+        steps,
+    })
+}
+
+fn step_path(step: &PlanStep) -> &str {
+    step.args["path"].as_str().unwrap_or_default()
+}
+
+fn batch_prompt(from: &str, to: &str, batch: &[PlanStep]) -> String {
+    let files = batch
+        .iter()
+        .map(|step| {
+            let sites = step.args["sites"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|site| site.as_str())
+                .map(|site| format!("  {}", site))
+                .collect::<Vec<String>>();
+            format!("- {}\n{}", step_path(step), sites.join("\n"))
+        })
+        .collect::<Vec<String>>();
+
+    format!(
+        "Migrate these files from {} to {}, changing only what the migration needs. The call sites found earlier are listed under each file, the line numbers may have shifted.\n{}\n\nFiles of later batches still use {}, keep them compiling where the two can coexist. Answer with a short summary once the files are changed.",
+        from,
+        to,
+        files.join("\n"),
+        from
+    )
+}
+
+// Runs the check and lets the model fix what it reports, until it passes or
+// the attempts are used up.
+async fn validate(agent: &Agent, check: &str) -> anyhow::Result<()> {
+    for attempt in 1..=MAX_ATTEMPTS {
+        styled_println!("\x1b[33mcheck> {}\x1b[0m", check);
+        let Err(output) = run_check(check).await? else {
+            return Ok(());
+        };
+        if attempt == MAX_ATTEMPTS {
+            break;
+        }
+
+        styled_println!(
+            "\x1b[41mcheck>\x1b[0m failed, asking for a fix ({}/{})",
+            attempt,
+            MAX_ATTEMPTS - 1
+        );
+        run_prompt(
+            agent,
+            &format!(
+                "`{}` fails after the migration of this batch. Fix the errors:\n{}",
+                check, output
+            ),
+        )
+        .await?;
+    }
+
+    Err(anyhow::anyhow!(
+        "`{}` still fails, fix the errors and run voo migrate again to resume with this batch",
+        check
+    ))
+}
+
+// The output of a failed check, stdout and stderr combined.
+async fn run_check(check: &str) -> anyhow::Result<Result<(), String>> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(check)
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error running {}: {}", check, e))?;
+    if output.status.success() {
+        return Ok(Ok(()));
+    }
+
+    let output = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut start = output.len().saturating_sub(MAX_CHECK_OUTPUT);
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+
+    Ok(Err(output[start..].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inventory() {
+        let plan = parse_inventory(
+            r#"Found these:
+{"sites": [
+  {"path": "src/main.rs", "line": 3, "code": "use actix_web::App;"},
+  {"path": "src/routes.rs", "line": 10, "code": " web::Json(body) "},
+  {"path": "src/main.rs", "code": "HttpServer::new"}
+]}"#,
+            "actix",
+            "axum",
+        )
+        .unwrap();
+
+        assert_eq!(plan.summary, "Migrate from actix to axum");
+        assert_eq!(
+            plan.steps.iter().map(step_path).collect::<Vec<&str>>(),
+            vec!["src/main.rs", "src/routes.rs"]
+        );
+        assert_eq!(
+            plan.steps[0].args["sites"],
+            json!(["3: use actix_web::App;", "HttpServer::new"])
+        );
+        assert_eq!(plan.steps[1].reason.as_deref(), Some("1 call site"));
+
+        assert!(parse_inventory("nothing found", "actix", "axum").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+pub fn data_dir() -> PathBuf {
+    std::env::var("VOO_HOME")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| dirs::home_dir().map(|home| home.join(".voo")))
+        .unwrap_or_else(|| PathBuf::from(".voo"))
+}
+
+pub fn sessions_dir() -> PathBuf {
+    data_dir().join("sessions")
+}
+
+pub fn sessions_db() -> PathBuf {
+    data_dir().join("sessions.db")
+}
+
+pub fn usage_path() -> PathBuf {
+    data_dir().join("usage.jsonl")
+}
+
+// The conversation of the current working directory, kept for --resume.
+pub fn conversation_path() -> PathBuf {
+    data_dir()
+        .join("conversations")
+        .join(format!("{}.jsonl", workspace_key("")))
+}
+
+// The progress of `voo migrate` in the current working directory.
+pub fn migration_path(from: &str, to: &str) -> PathBuf {
+    data_dir().join("migrations").join(format!(
+        "{}.json",
+        workspace_key(&format!("{}\n{}", from, to))
+    ))
+}
+
+fn workspace_key(suffix: &str) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let key = hex::encode(Sha256::digest(
+        format!("{}{}", cwd.to_string_lossy(), suffix).as_bytes(),
+    ));
+
+    key[..16].to_string()
+}
@@ -0,0 +1,415 @@
+use std::{fmt::Display, path::Path};
+
+use domain::models::{
+    agent::{Agent, AgentRole, Content, FunctionCall},
+    transcript::{SessionEvent, Transcript},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    record,
+    runner::perform_function_call,
+    terminal::{prompt, styled_println},
+};
+
+const MAX_CANDIDATES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub tool: String,
+    #[serde(default)]
+    pub args: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    #[serde(default)]
+    pub summary: String,
+    pub steps: Vec<PlanStep>,
+}
+
+// A plan worked through over several runs, e.g. by `voo migrate`. It is saved
+// after every finished step, so a rerun resumes with the first step not done.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanProgress {
+    pub plan: Plan,
+    #[serde(default)]
+    pub done: usize,
+}
+
+impl PlanProgress {
+    pub fn new(plan: Plan) -> Self {
+        Self { plan, done: 0 }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow::anyhow!("Error reading {}: {}", path.display(), e)),
+        };
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Invalid plan progress in {}: {}", path.display(), e))
+    }
+
+    // Written next to the target first, so an interrupted save keeps the
+    // previous progress.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&partial, path)?;
+
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> &[PlanStep] {
+        &self.plan.steps[self.done.min(self.plan.steps.len())..]
+    }
+
+    pub fn finish(&mut self, steps: usize) {
+        self.done = (self.done + steps).min(self.plan.steps.len());
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PlanCandidates {
+    candidates: Vec<Plan>,
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.summary)?;
+        for (index, step) in self.steps.iter().enumerate() {
+            write!(
+                f,
+                "  {}. \x1b[33m{}> {}\x1b[0m",
+                index + 1,
+                step.tool,
+                step.args
+            )?;
+            if let Some(reason) = &step.reason {
+                write!(f, "  \x1b[2m# {}\x1b[0m", reason)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Asks the model for candidate tool-call plans, lets the user pick and edit
+// one, and only then executes it.
+pub async fn run(agent: &Agent, task: &str, transcript: &Transcript) -> anyhow::Result<()> {
+    record(
+        transcript,
+        SessionEvent::UserMessage {
+            text: task.to_string(),
+        },
+    );
+
+    let responses = agent
+        .client()
+        .ask(&planning_prompt(agent, task).await)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error requesting plan: {}", e))?;
+
+    let candidates = parse_candidates(&responses)?;
+    if candidates.is_empty() {
+        styled_println!("\x1b[32mplan>\x1b[0m no tool calls are needed");
+        return Ok(());
+    }
+
+    let Some(mut plan) = select(candidates)? else {
+        return cancel(agent).await;
+    };
+
+    loop {
+        styled_println!("\x1b[32mplan>\x1b[0m {}", plan);
+        let answer = prompt("[a] approve, [d <n>] drop step, [e] edit, [q] cancel: ")?;
+
+        match answer.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["a"] => break,
+            ["q"] => return cancel(agent).await,
+            ["e"] => match edit(&plan) {
+                Ok(edited) => plan = edited,
+                Err(e) => styled_println!("\x1b[41mplan>\x1b[0m {}", e),
+            },
+            ["d", step] => match step.parse::<usize>() {
+                Ok(step) if (1..=plan.steps.len()).contains(&step) => {
+                    plan.steps.remove(step - 1);
+                }
+                _ => styled_println!("\x1b[41mplan>\x1b[0m no step {}", step),
+            },
+            _ => continue,
+        }
+    }
+
+    execute(agent, &plan, transcript).await
+}
+
+async fn planning_prompt(agent: &Agent, task: &str) -> String {
+    let tools = agent.tools();
+    let tools = tools
+        .lock()
+        .await
+        .enabled()
+        .map(|registered| {
+            format!(
+                "- {}: {}",
+                registered.tool.name(),
+                registered.tool.description()
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!(
+        r#"Do not call any tools yet. Propose up to {} alternative plans for the task below as tool calls, using only these tools:
+{}
+
+Reply with JSON only, in this shape:
+{{"candidates": [{{"summary": "...", "steps": [{{"tool": "...", "args": {{}}, "reason": "..."}}]}}]}}
+
+Task: {}"#,
+        MAX_CANDIDATES,
+        tools.join("\n"),
+        task
+    )
+}
+
+fn parse_candidates(responses: &[Content]) -> anyhow::Result<Vec<Plan>> {
+    let parts = responses.iter().flat_map(|content| &content.parts);
+
+    // Some models answer with function calls despite being asked for JSON;
+    // treat those as a single candidate.
+    let calls = parts
+        .clone()
+        .filter_map(|part| part.function_call.as_ref())
+        .map(|call| PlanStep {
+            tool: call.name.clone(),
+            args: call.args.clone(),
+            reason: None,
+        })
+        .collect::<Vec<PlanStep>>();
+    if !calls.is_empty() {
+        return Ok(vec![Plan {
+            summary: "Proposed tool calls".to_string(),
+            steps: calls,
+        }]);
+    }
+
+    let text = parts
+        .filter_map(|part| part.answer().map(String::as_str))
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    parse_plan_json(&text)
+}
+
+fn parse_plan_json(text: &str) -> anyhow::Result<Vec<Plan>> {
+    let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) else {
+        return Err(anyhow::anyhow!("Model did not return a plan: {}", text));
+    };
+
+    let mut candidates = serde_json::from_str::<PlanCandidates>(&text[start..=end])
+        .map_err(|e| anyhow::anyhow!("Error parsing plan: {}", e))?
+        .candidates;
+    candidates.retain(|plan| !plan.steps.is_empty());
+    candidates.truncate(MAX_CANDIDATES);
+
+    Ok(candidates)
+}
+
+fn select(mut candidates: Vec<Plan>) -> anyhow::Result<Option<Plan>> {
+    if candidates.len() == 1 {
+        return Ok(candidates.pop());
+    }
+
+    for (index, plan) in candidates.iter().enumerate() {
+        styled_println!("\x1b[32mplan {}>\x1b[0m {}", index + 1, plan);
+    }
+
+    loop {
+        let answer = prompt(&format!(
+            "select a plan [1-{}], [q] cancel: ",
+            candidates.len()
+        ))?;
+        if answer == "q" {
+            return Ok(None);
+        }
+
+        match answer.parse::<usize>() {
+            Ok(index) if (1..=candidates.len()).contains(&index) => {
+                return Ok(Some(candidates.swap_remove(index - 1)));
+            }
+            _ => continue,
+        }
+    }
+}
+
+fn edit(plan: &Plan) -> anyhow::Result<Plan> {
+    let path = std::env::temp_dir().join(format!("voo-plan-{}.json", uuid::Uuid::new_v4()));
+    std::fs::write(&path, serde_json::to_string_pretty(plan)?)?;
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let status = std::process::Command::new(&editor)
+        .arg(&path)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Error starting {}: {}", editor, e));
+    let edited = std::fs::read_to_string(&path);
+    _ = std::fs::remove_file(&path);
+
+    if !status?.success() {
+        return Err(anyhow::anyhow!("{} exited with an error", editor));
+    }
+
+    serde_json::from_str::<Plan>(&edited?).map_err(|e| anyhow::anyhow!("Invalid plan: {}", e))
+}
+
+async fn cancel(agent: &Agent) -> anyhow::Result<()> {
+    styled_println!("\x1b[32mplan>\x1b[0m cancelled");
+    _ = agent
+        .client()
+        .add_system_prompt("The user rejected the proposed plan.", AgentRole::User)
+        .await;
+
+    Ok(())
+}
+
+async fn execute(agent: &Agent, plan: &Plan, transcript: &Transcript) -> anyhow::Result<()> {
+    let function_calls = plan
+        .steps
+        .iter()
+        .map(|step| FunctionCall {
+            name: step.tool.clone(),
+            args: step.args.clone(),
+        })
+        .collect::<Vec<FunctionCall>>();
+
+    for call in &function_calls {
+        record(
+            transcript,
+            SessionEvent::ToolCall {
+                name: call.name.clone(),
+                args: call.args.clone(),
+            },
+        );
+    }
+
+    let context = agent.tool_context().await;
+    let report = match perform_function_call(agent, &function_calls, &context, true).await {
+        Ok(outputs) => {
+            for (call, output) in function_calls.iter().zip(&outputs) {
+                record(
+                    transcript,
+                    SessionEvent::ToolResult {
+                        name: call.name.clone(),
+                        output: output.to_json().to_string(),
+                    },
+                );
+                _ = agent.client().add_tool_result(&call.name, output).await;
+            }
+
+            "The approved plan has been executed and the tool results are above. Summarize the outcome. Do not call any more tools.".to_string()
+        }
+        Err(e) => {
+            let err = format!("Error executing the approved plan: {}", e);
+            styled_println!("\x1b[41mplan>\x1b[0m {}", err);
+            record(
+                transcript,
+                SessionEvent::Error {
+                    message: err.clone(),
+                },
+            );
+            err
+        }
+    };
+
+    let responses = agent
+        .client()
+        .ask(&report)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error summarizing plan: {}", e))?;
+
+    for response in responses {
+        record(
+            transcript,
+            SessionEvent::ModelResponse {
+                content: response.clone(),
+            },
+        );
+
+        for part in &response.parts {
+            if let Some(text) = part.answer().filter(|text| !text.is_empty()) {
+                styled_println!("\x1b[32mvoo>\x1b[0m {}", text);
+            }
+            if let Some(call) = &part.function_call {
+                styled_println!(
+                    "\x1b[33m{}> {} (not executed, use /plan to continue)\x1b[0m",
+                    call.name,
+                    call.args
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plan_json() {
+        let text = r#"Here is the plan:
+```json
+{"candidates": [
+  {"summary": "Inspect", "steps": [{"tool": "list_files", "args": {"path": "src"}, "reason": "find entry"}]},
+  {"summary": "Nothing", "steps": []}
+]}
+```"#;
+
+        let candidates = parse_plan_json(text).unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].steps[0].tool, "list_files");
+        assert_eq!(candidates[0].steps[0].args["path"], "src");
+
+        assert!(parse_plan_json("no plan here").is_err());
+    }
+
+    #[test]
+    fn test_plan_progress() {
+        let path = std::env::temp_dir()
+            .join(format!("voo-progress-{}", uuid::Uuid::new_v4()))
+            .join("progress.json");
+        assert_eq!(PlanProgress::load(&path).unwrap(), None);
+
+        let step = |path: &str| PlanStep {
+            tool: "edit_file".to_string(),
+            args: serde_json::json!({ "path": path }),
+            reason: None,
+        };
+        let mut progress = PlanProgress::new(Plan {
+            summary: "Migrate".to_string(),
+            steps: vec![step("a.rs"), step("b.rs"), step("c.rs")],
+        });
+        progress.finish(2);
+        progress.save(&path).unwrap();
+
+        let mut loaded = PlanProgress::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, progress);
+        assert_eq!(loaded.remaining(), &[step("c.rs")]);
+        loaded.finish(5);
+        assert!(loaded.remaining().is_empty());
+
+        _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
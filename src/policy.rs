@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use domain::models::transcript::SessionEvent;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+// Installed by an administrator. Unlike the config files, neither the user nor
+// the environment can point voo elsewhere or override what it says.
+pub const POLICY_PATH: &str = "/etc/voo/policy.toml";
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    // Never offered to the model, whatever the config enables.
+    pub disabled_tools: Vec<String>,
+    // The providers that may be used, all of them when empty.
+    pub providers: Vec<String>,
+    // URL prefixes the endpoint of a provider must start with.
+    pub endpoints: HashMap<String, Vec<String>>,
+    // Turns estimated to cost more, in USD, are refused.
+    pub max_turn_cost: Option<f64>,
+    // Every session event is appended to this file.
+    pub audit_log: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct AuditRecord<'a> {
+    at: i64,
+    user: String,
+    cwd: String,
+    session: &'a str,
+    #[serde(flatten)]
+    event: &'a SessionEvent,
+}
+
+// A decision on what a `voo serve` user asked to do.
+#[derive(Debug, Serialize)]
+struct AccessRecord<'a> {
+    at: i64,
+    user: &'a str,
+    role: &'a str,
+    action: &'a str,
+    allowed: bool,
+}
+
+impl Policy {
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str::<Policy>(content)?)
+    }
+
+    pub fn tool_allowed(&self, name: &str) -> bool {
+        !self.disabled_tools.iter().any(|tool| tool == name)
+    }
+
+    pub fn check_provider(&self, name: &str) -> anyhow::Result<()> {
+        if self.providers.is_empty() || self.providers.iter().any(|provider| provider == name) {
+            return Ok(());
+        }
+
+        Err(anyhow::anyhow!(
+            "The organization policy does not allow provider {} (allowed: {})",
+            name,
+            self.providers.join(", ")
+        ))
+    }
+
+    pub fn check_endpoint(&self, provider: &str, url: &str) -> anyhow::Result<()> {
+        let Some(approved) = self.endpoints.get(provider) else {
+            return Ok(());
+        };
+        if approved
+            .iter()
+            .any(|prefix| url.starts_with(prefix.as_str()))
+        {
+            return Ok(());
+        }
+
+        Err(anyhow::anyhow!(
+            "The organization policy does not allow {} endpoint {} (allowed: {})",
+            provider,
+            url,
+            approved.join(", ")
+        ))
+    }
+}
+
+// No policy file means no restrictions. One that doesn't parse, or an audit
+// log that can't be written, stops voo instead of running unguarded.
+pub fn load() -> anyhow::Result<Policy> {
+    let policy = match read(Path::new(POLICY_PATH))? {
+        Some(policy) => policy,
+        None => return Ok(Policy::default()),
+    };
+
+    if let Some(path) = &policy.audit_log {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "The organization policy requires an audit log, {} can't be written: {}",
+                    path.display(),
+                    e
+                )
+            })?;
+    }
+
+    Ok(policy)
+}
+
+fn read(path: &Path) -> anyhow::Result<Option<Policy>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow::anyhow!("Error reading {}: {}", path.display(), e)),
+    };
+
+    Policy::parse(&content)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", path.display(), e))
+}
+
+pub fn set(policy: Policy) {
+    _ = POLICY.set(policy);
+}
+
+pub fn get() -> &'static Policy {
+    POLICY.get_or_init(Policy::default)
+}
+
+// Appends the event to the audit log when the policy asks for one.
+pub fn audit(session: &str, event: &SessionEvent) {
+    audit_as(None, session, event)
+}
+
+// As `audit`, for an event on behalf of a `voo serve` user rather than the
+// user running voo.
+pub fn audit_as(user: Option<&str>, session: &str, event: &SessionEvent) {
+    let record = AuditRecord {
+        at: now(),
+        user: match user {
+            Some(user) => user.to_string(),
+            None => std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default(),
+        },
+        cwd: std::env::current_dir()
+            .map(|cwd| cwd.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        session,
+        event,
+    };
+    write(&record);
+}
+
+pub fn audit_access(user: &str, role: &str, action: &str, allowed: bool) {
+    write(&AccessRecord {
+        at: now(),
+        user,
+        role,
+        action,
+        allowed,
+    });
+}
+
+fn write(record: &impl Serialize) {
+    let Some(path) = &get().audit_log else {
+        return;
+    };
+
+    if let Err(e) = append(path, record) {
+        error!("[VOO] failed to write audit log {}: {}", path.display(), e);
+    }
+}
+
+fn append(path: &Path, record: &impl Serialize) -> anyhow::Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let policy = Policy::parse(
+            r#"
+disabled_tools = ["run_command"]
+providers = ["openai", "ollama"]
+max_turn_cost = 0.25
+
+[endpoints]
+openai = ["https://llm-proxy.example.com/"]
+"#,
+        )
+        .unwrap();
+
+        assert!(!policy.tool_allowed("run_command"));
+        assert!(policy.tool_allowed("read_file"));
+        assert!(policy.check_provider("openai").is_ok());
+        assert!(policy.check_provider("gemini").is_err());
+        assert!(
+            policy
+                .check_endpoint("openai", "https://llm-proxy.example.com/v1")
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check_endpoint("openai", "https://api.openai.com/v1")
+                .is_err()
+        );
+        assert!(
+            policy
+                .check_endpoint("ollama", "http://localhost:11434")
+                .is_ok()
+        );
+
+        assert!(Policy::default().check_provider("gemini").is_ok());
+        assert!(Policy::parse("disabled_tool = [\"grep\"]").is_err());
+    }
+}
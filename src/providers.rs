@@ -0,0 +1,502 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use domain::models::agent::AgentClient;
+#[cfg(feature = "candle")]
+use models::models::candle::{CandleConfig, CandleGenerator};
+#[cfg(feature = "llama-cpp")]
+use models::models::llama_cpp::{LlamaCppConfig, LlamaCppGenerator};
+#[cfg(any(feature = "candle", feature = "llama-cpp"))]
+use models::models::local::LocalModel;
+use models::models::{
+    bedrock::{BedrockConfig, BedrockModel},
+    chat_completions::{ChatAuth, ChatCompletionsConfig, ChatCompletionsModel},
+    circuit_breaker::CircuitBreaker,
+    context::ContextConfig,
+    endpoints::{EndpointPool, GeminiEndpoint},
+    free_tier::FreeTierConfig,
+    gemini::GeminiModel,
+    ollama::{OllamaConfig, OllamaModel},
+    openai::{OPENAI_URL, OpenAiModel},
+    rate_limit::{RateLimitConfig, RateLimited, RateLimiter},
+    retry::RetryConfig,
+    sigv4::AwsCredentials,
+    watchdog::Watchdog,
+};
+use tracing::info;
+
+use crate::{config, policy};
+
+// Set by the --provider flag, takes precedence over VOO_PROVIDER.
+static SELECTED: OnceLock<String> = OnceLock::new();
+// One limiter per provider for the whole process, so every client built for
+// it, e.g. the workers of `voo serve`, draws from the same budget.
+static RATE_LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy)]
+pub struct Provider {
+    pub name: &'static str,
+    pub description: &'static str,
+    build: fn() -> anyhow::Result<CircuitBreaker>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProviderRegistry {
+    providers: Vec<Provider>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self { providers: vec![] }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        build: fn() -> anyhow::Result<CircuitBreaker>,
+    ) {
+        self.providers.retain(|provider| provider.name != name);
+        self.providers.push(Provider {
+            name,
+            description,
+            build,
+        });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    pub fn providers(&self) -> &[Provider] {
+        &self.providers
+    }
+
+    // Builds the named provider wrapped in its circuit breaker.
+    pub fn build(&self, name: &str) -> anyhow::Result<CircuitBreaker> {
+        let provider = self.get(name).ok_or_else(|| {
+            let names = self
+                .providers
+                .iter()
+                .map(|provider| provider.name)
+                .collect::<Vec<&str>>();
+            anyhow::anyhow!(
+                "Unknown provider: {} (available: {})",
+                name,
+                names.join(", ")
+            )
+        })?;
+        policy::get().check_provider(name)?;
+
+        (provider.build)()
+    }
+}
+
+pub fn select(name: String) {
+    _ = SELECTED.set(name);
+}
+
+pub fn selected() -> String {
+    if let Some(name) = SELECTED.get() {
+        return name.clone();
+    }
+
+    config::var("VOO_PROVIDER").unwrap_or_else(|_| "gemini".to_string())
+}
+
+pub fn list() {
+    let selected = selected();
+
+    for provider in registry().providers() {
+        let marker = if provider.name == selected { "*" } else { " " };
+        println!("{} {:<10} {}", marker, provider.name, provider.description);
+    }
+}
+
+// Checks the live Gemini API against the response types, so upstream format
+// changes show up before they break a chat.
+pub async fn schema_check() -> anyhow::Result<()> {
+    policy::get().check_provider("gemini")?;
+    let ignored = gemini_model()?
+        .schema_check()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error checking the Gemini response format: {}", e))?;
+
+    println!("The Gemini response format matches.");
+    if !ignored.is_empty() {
+        println!("Fields voo ignores:");
+        for field in ignored {
+            println!("  {}", field);
+        }
+    }
+    Ok(())
+}
+
+pub fn registry() -> ProviderRegistry {
+    let mut registry = ProviderRegistry::new();
+    registry.register("gemini", "Google Gemini via AI Studio or Vertex AI", || {
+        guarded("gemini", gemini_model()?)
+    });
+    registry.register("openai", "OpenAI or an OpenAI compatible server", || {
+        guarded("openai", openai_model()?)
+    });
+    registry.register("bedrock", "AWS Bedrock Converse API", || {
+        guarded("bedrock", bedrock_model()?)
+    });
+    registry.register("azure", "Azure OpenAI deployments", || {
+        guarded("azure", azure_model()?)
+    });
+    registry.register("mistral", "Mistral AI chat API", || {
+        guarded("mistral", mistral_model()?)
+    });
+    registry.register("groq", "Groq chat API", || guarded("groq", groq_model()?));
+    registry.register("xai", "xAI Grok chat API", || guarded("xai", xai_model()?));
+    registry.register("deepseek", "DeepSeek chat API", || {
+        guarded("deepseek", deepseek_model()?)
+    });
+    registry.register("ollama", "Local model served by Ollama", || {
+        guarded("ollama", ollama_model()?)
+    });
+    #[cfg(feature = "candle")]
+    registry.register("candle", "Local Qwen2 model via candle", || {
+        guarded("candle", candle_model()?)
+    });
+    #[cfg(feature = "llama-cpp")]
+    registry.register("llama-cpp", "Local GGUF model via llama.cpp", || {
+        guarded("llama-cpp", llama_cpp_model()?)
+    });
+
+    registry
+}
+
+// Every provider is watched for empty or looping replies, and the circuit
+// breaker only sees the outcome after the watchdog's retries.
+fn guarded(name: &str, client: impl AgentClient + 'static) -> anyhow::Result<CircuitBreaker> {
+    Ok(match rate_limiter(name)? {
+        Some(limiter) => {
+            CircuitBreaker::new(name, Watchdog::new(RateLimited::new(client, limiter)))
+        }
+        None => CircuitBreaker::new(name, Watchdog::new(client)),
+    })
+}
+
+// VOO_RATE_LIMIT_<PROVIDER>, e.g. VOO_RATE_LIMIT_GROQ=30, caps the requests
+// per minute sent to a provider.
+fn rate_limiter(name: &str) -> anyhow::Result<Option<Arc<RateLimiter>>> {
+    let key = format!("VOO_RATE_LIMIT_{}", name.to_uppercase().replace('-', "_"));
+    let Some(requests_per_minute) = parsed_var::<usize>(&key)? else {
+        return Ok(None);
+    };
+
+    let mut limiters = RATE_LIMITERS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let limiter = limiters
+        .entry(name.to_string())
+        .or_insert_with(|| {
+            Arc::new(RateLimiter::new(RateLimitConfig::per_minute(
+                requests_per_minute,
+            )))
+        })
+        .clone();
+
+    Ok(Some(limiter))
+}
+
+fn gemini_model() -> anyhow::Result<GeminiModel> {
+    let api_key = config::var("GEMINI_API_KEY")
+        .ok()
+        .filter(|api_key| !api_key.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("GEMINI_API_KEY must be set"))?;
+
+    let mut builder = GeminiModel::builder().api_key(&api_key);
+    if let Some(model) = optional_var("VOO_GEMINI_MODEL") {
+        builder = builder.model(&model);
+    }
+    if let Some(temperature) = parsed_var("VOO_GEMINI_TEMPERATURE")? {
+        builder = builder.temperature(temperature);
+    }
+    if let Some(top_p) = parsed_var("VOO_GEMINI_TOP_P")? {
+        builder = builder.top_p(top_p);
+    }
+    if let Some(max_output_tokens) = parsed_var("VOO_GEMINI_MAX_OUTPUT_TOKENS")? {
+        builder = builder.max_output_tokens(max_output_tokens);
+    }
+    if let Some(stop) = optional_var("VOO_GEMINI_STOP") {
+        builder = builder.stop_sequences(
+            stop.split(',')
+                .map(|stop| stop.trim().to_string())
+                .collect(),
+        );
+    }
+
+    let mut gemini = builder.build().with_endpoints(gemini_endpoints()?);
+    if let Some(upload_url) = gemini.upload_url()
+        && let Err(e) = policy::get().check_endpoint("gemini", &upload_url)
+    {
+        info!("[VOO] large attachments are off: {}", e);
+        gemini = gemini.without_uploads();
+    }
+    if let Some(config) = free_tier_config()? {
+        info!(
+            "[VOO] free tier mode: {} requests per minute",
+            config.requests_per_minute
+        );
+        gemini = gemini.with_free_tier(config);
+    }
+    if let Some(context_window) = parsed_var("VOO_CONTEXT_WINDOW")? {
+        gemini = gemini.with_context(ContextConfig::new(context_window));
+    }
+    if let Some(max_attempts) = parsed_var("VOO_GEMINI_MAX_ATTEMPTS")? {
+        gemini = gemini.with_retry(RetryConfig {
+            max_attempts,
+            ..RetryConfig::default()
+        });
+    }
+
+    Ok(gemini)
+}
+
+fn openai_model() -> anyhow::Result<OpenAiModel> {
+    let api_key = required_var("OPENAI_API_KEY", "OpenAI")?;
+    let model = config::var("VOO_OPENAI_MODEL").unwrap_or_else(|_| "gpt-4o-mini".to_string());
+    let base_url = config::var("OPENAI_BASE_URL").unwrap_or_else(|_| OPENAI_URL.to_string());
+    policy::get().check_endpoint("openai", &base_url)?;
+
+    Ok(OpenAiModel::compatible(&api_key, &model, &base_url))
+}
+
+fn bedrock_model() -> anyhow::Result<BedrockModel> {
+    let region = config::var("AWS_REGION")
+        .or_else(|_| config::var("AWS_DEFAULT_REGION"))
+        .map_err(|_| anyhow::anyhow!("AWS_REGION must be set to use Bedrock"))?;
+    let credentials = AwsCredentials::from_env().ok_or_else(|| {
+        anyhow::anyhow!("AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set to use Bedrock")
+    })?;
+    let model_id = config::var("VOO_BEDROCK_MODEL")
+        .unwrap_or_else(|_| "anthropic.claude-3-5-sonnet-20240620-v1:0".to_string());
+
+    Ok(BedrockModel::new(BedrockConfig {
+        region,
+        model_id,
+        credentials,
+    }))
+}
+
+fn azure_model() -> anyhow::Result<ChatCompletionsModel> {
+    let var = |key: &str| {
+        config::var(key).map_err(|_| anyhow::anyhow!("{} must be set to use Azure OpenAI", key))
+    };
+
+    let auth = match (
+        config::var("AZURE_OPENAI_API_KEY"),
+        config::var("AZURE_OPENAI_AD_TOKEN"),
+    ) {
+        (Ok(api_key), _) => ChatAuth::Header("api-key".to_string(), api_key),
+        (_, Ok(token)) => ChatAuth::Bearer(token),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "AZURE_OPENAI_API_KEY or AZURE_OPENAI_AD_TOKEN must be set to use Azure OpenAI"
+            ));
+        }
+    };
+    let api_version =
+        config::var("AZURE_OPENAI_API_VERSION").unwrap_or_else(|_| "2024-10-21".to_string());
+    let endpoint = var("AZURE_OPENAI_ENDPOINT")?;
+    policy::get().check_endpoint("azure", &endpoint)?;
+
+    Ok(ChatCompletionsModel::new(ChatCompletionsConfig::azure(
+        &endpoint,
+        &var("AZURE_OPENAI_DEPLOYMENT")?,
+        &api_version,
+        auth,
+    )))
+}
+
+// Parses VOO_GEMINI_REGIONS, e.g. `us-central1,europe-west4,studio`, where
+// `studio` is the global AI Studio endpoint and anything else a Vertex AI region.
+fn gemini_endpoints() -> anyhow::Result<EndpointPool> {
+    let regions = config::var("VOO_GEMINI_REGIONS").unwrap_or_else(|_| "studio".to_string());
+
+    let mut endpoints = vec![];
+    for region in regions
+        .split(',')
+        .map(str::trim)
+        .filter(|region| !region.is_empty())
+    {
+        if region == "studio" {
+            endpoints.push(GeminiEndpoint::studio());
+            continue;
+        }
+
+        let project = config::var("VOO_VERTEX_PROJECT").map_err(|_| {
+            anyhow::anyhow!("VOO_VERTEX_PROJECT must be set to use region {}", region)
+        })?;
+        let access_token = config::var("VOO_VERTEX_ACCESS_TOKEN").map_err(|_| {
+            anyhow::anyhow!(
+                "VOO_VERTEX_ACCESS_TOKEN must be set to use region {}",
+                region
+            )
+        })?;
+        endpoints.push(GeminiEndpoint::vertex(&project, region, &access_token));
+    }
+
+    for endpoint in &endpoints {
+        policy::get().check_endpoint("gemini", &endpoint.base_url)?;
+    }
+
+    EndpointPool::new(endpoints).map_err(|e| anyhow::anyhow!("Error configuring regions: {}", e))
+}
+
+fn free_tier_config() -> anyhow::Result<Option<FreeTierConfig>> {
+    let enabled = config::var("VOO_FREE_TIER")
+        .is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"));
+    if !enabled {
+        return Ok(None);
+    }
+
+    let mut config = FreeTierConfig::default();
+    if let Ok(rpm) = config::var("VOO_FREE_TIER_RPM") {
+        config.requests_per_minute = rpm
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Error parsing VOO_FREE_TIER_RPM: {:?}", rpm))?;
+    }
+
+    Ok(Some(config))
+}
+
+fn mistral_model() -> anyhow::Result<ChatCompletionsModel> {
+    let api_key = required_var("MISTRAL_API_KEY", "Mistral")?;
+    let model =
+        config::var("VOO_MISTRAL_MODEL").unwrap_or_else(|_| "mistral-large-latest".to_string());
+
+    Ok(ChatCompletionsModel::new(ChatCompletionsConfig::mistral(
+        &api_key, &model,
+    )))
+}
+
+fn groq_model() -> anyhow::Result<ChatCompletionsModel> {
+    let api_key = required_var("GROQ_API_KEY", "Groq")?;
+    let model =
+        config::var("VOO_GROQ_MODEL").unwrap_or_else(|_| "llama-3.3-70b-versatile".to_string());
+
+    Ok(ChatCompletionsModel::new(ChatCompletionsConfig::groq(
+        &api_key, &model,
+    )))
+}
+
+fn xai_model() -> anyhow::Result<ChatCompletionsModel> {
+    let api_key = required_var("XAI_API_KEY", "xAI")?;
+    let model = config::var("VOO_XAI_MODEL").unwrap_or_else(|_| "grok-3".to_string());
+
+    Ok(ChatCompletionsModel::new(ChatCompletionsConfig::xai(
+        &api_key, &model,
+    )))
+}
+
+fn deepseek_model() -> anyhow::Result<ChatCompletionsModel> {
+    let api_key = required_var("DEEPSEEK_API_KEY", "DeepSeek")?;
+    let model = config::var("VOO_DEEPSEEK_MODEL").unwrap_or_else(|_| "deepseek-chat".to_string());
+
+    Ok(ChatCompletionsModel::new(ChatCompletionsConfig::deepseek(
+        &api_key, &model,
+    )))
+}
+
+fn ollama_model() -> anyhow::Result<OllamaModel> {
+    let model = config::var("VOO_OLLAMA_MODEL").unwrap_or_else(|_| "qwen2.5-coder".to_string());
+
+    let mut config = OllamaConfig::new(&model);
+    // OLLAMA_HOST is shared with the ollama CLI, which allows leaving out the scheme.
+    if let Ok(host) = config::var("OLLAMA_HOST") {
+        let host = host.trim();
+        config.url = match host.contains("://") {
+            true => host.to_string(),
+            false => format!("http://{}", host),
+        };
+    }
+    policy::get().check_endpoint("ollama", &config.url)?;
+
+    Ok(OllamaModel::new(config))
+}
+
+#[cfg(feature = "candle")]
+fn candle_model() -> anyhow::Result<LocalModel<CandleGenerator>> {
+    let model_dir = required_var("VOO_CANDLE_MODEL_DIR", "candle")?;
+
+    let mut config = CandleConfig::new(model_dir.into());
+    if let Ok(context_size) = config::var("VOO_CANDLE_CONTEXT") {
+        config.context_size = context_size
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Error parsing VOO_CANDLE_CONTEXT: {:?}", context_size))?;
+    }
+    if let Ok(max_tokens) = config::var("VOO_CANDLE_MAX_TOKENS") {
+        config.max_tokens = max_tokens.trim().parse().map_err(|_| {
+            anyhow::anyhow!("Error parsing VOO_CANDLE_MAX_TOKENS: {:?}", max_tokens)
+        })?;
+    }
+
+    info!("[VOO] loading {}", config.model_dir.display());
+    let generator = CandleGenerator::load(config)
+        .map_err(|e| anyhow::anyhow!("Error loading local model: {}", e))?;
+
+    Ok(LocalModel::new(generator))
+}
+
+#[cfg(feature = "llama-cpp")]
+fn llama_cpp_model() -> anyhow::Result<LocalModel<LlamaCppGenerator>> {
+    let model_path = required_var("VOO_LLAMA_MODEL_PATH", "llama.cpp")?;
+
+    let mut config = LlamaCppConfig::new(model_path.into());
+    config.template = config::var("VOO_LLAMA_TEMPLATE")
+        .ok()
+        .filter(|template| !template.trim().is_empty());
+    if let Ok(context_size) = config::var("VOO_LLAMA_CONTEXT") {
+        config.context_size = context_size
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Error parsing VOO_LLAMA_CONTEXT: {:?}", context_size))?;
+    }
+    if let Ok(max_tokens) = config::var("VOO_LLAMA_MAX_TOKENS") {
+        config.max_tokens = max_tokens
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Error parsing VOO_LLAMA_MAX_TOKENS: {:?}", max_tokens))?;
+    }
+
+    info!("[VOO] loading {}", config.model_path.display());
+    let generator = LlamaCppGenerator::load(config)
+        .map_err(|e| anyhow::anyhow!("Error loading local model: {}", e))?;
+
+    Ok(LocalModel::new(generator))
+}
+
+fn required_var(key: &str, provider: &str) -> anyhow::Result<String> {
+    config::var(key)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("{} must be set to use {}", key, provider))
+}
+
+fn optional_var(key: &str) -> Option<String> {
+    config::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn parsed_var<T: FromStr>(key: &str) -> anyhow::Result<Option<T>> {
+    optional_var(key)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Error parsing {}: {:?}", key, value))
+        })
+        .transpose()
+}
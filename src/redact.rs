@@ -0,0 +1,430 @@
+use domain::models::{
+    agent::{Agent, AgentClient, Content},
+    transcript::{SessionEvent, Transcript, TranscriptEntry},
+};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{crash, policy, providers, terminal::styled_println};
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug)]
+enum Target {
+    // Masked wherever it appears.
+    Pattern(Regex),
+    // The n-th message of this session, dropped with everything it led to.
+    Turn(usize),
+}
+
+// `/redact <pattern|turn>` takes content back out of the conversation, e.g. a
+// credential pasted by accident. It is masked or dropped in the session
+// transcript and the stored conversation, and the model client is rebuilt
+// from the redacted conversation so no later request carries it.
+pub async fn run(agent: &mut Agent, args: &str, transcript: &Transcript) -> anyhow::Result<()> {
+    let mut entries = transcript
+        .load()
+        .map_err(|e| anyhow::anyhow!("Error loading session transcript: {}", e))?;
+    let turns = user_messages(&entries);
+
+    let target = match parse(args.trim())? {
+        Some(target) => target,
+        None => {
+            styled_println!(
+                "\x1b[32mredact>\x1b[0m usage: /redact <pattern|turn>, a /regex/ or the number of a message:"
+            );
+            for (turn, text) in turns.iter().enumerate() {
+                styled_println!("  {}: {}", turn + 1, preview(text));
+            }
+            return Ok(());
+        }
+    };
+
+    let mut contents = agent
+        .history()
+        .map_err(|e| anyhow::anyhow!("Error loading conversation: {}", e))?;
+    let removed = match &target {
+        Target::Pattern(pattern) => {
+            let masked = entries
+                .iter_mut()
+                .map(|entry| mask_entry(entry, pattern))
+                .sum::<usize>()
+                + contents
+                    .iter_mut()
+                    .map(|content| mask_content(content, pattern))
+                    .sum::<usize>();
+            if masked == 0 {
+                return Err(anyhow::anyhow!(
+                    "{} does not appear in the conversation",
+                    args.trim()
+                ));
+            }
+            crash::retain(|entry| {
+                mask_entry(entry, pattern);
+                true
+            });
+            format!("masked {} occurrence(s)", masked)
+        }
+        Target::Turn(turn) => {
+            let Some(text) = turns.get(turn - 1) else {
+                return Err(anyhow::anyhow!(
+                    "There is no message {}, this session has {}",
+                    turn,
+                    turns.len()
+                ));
+            };
+            let text = text.clone();
+            if !drop_stored_turn(&mut contents, &turns, *turn) {
+                return Err(anyhow::anyhow!(
+                    "Message {} is no longer in the stored conversation",
+                    turn
+                ));
+            }
+            let dropped = drop_turn(&mut entries, *turn);
+            crash::retain(|entry| !dropped.contains(&entry.event));
+            format!("removed message {} ({})", turn, preview(&text))
+        }
+    };
+
+    transcript
+        .rewrite(&entries)
+        .map_err(|e| anyhow::anyhow!("Error rewriting session transcript: {}", e))?;
+    agent
+        .rewrite_history(&contents)
+        .map_err(|e| anyhow::anyhow!("Error rewriting conversation: {}", e))?;
+    reload(agent, &entries).await?;
+
+    styled_println!("\x1b[32mredact>\x1b[0m {}", removed);
+    if let Some(path) = &policy::get().audit_log {
+        styled_println!(
+            "\x1b[33mredact>\x1b[0m the audit log {} is append-only and was left alone",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+// None asks for the usage. A number is a message, text between slashes a
+// regex and anything else is masked as written.
+fn parse(args: &str) -> anyhow::Result<Option<Target>> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(turn) = args.parse::<usize>() {
+        return match turn {
+            0 => Err(anyhow::anyhow!("Messages are numbered from 1")),
+            turn => Ok(Some(Target::Turn(turn))),
+        };
+    }
+
+    let pattern = match args
+        .strip_prefix('/')
+        .and_then(|args| args.strip_suffix('/'))
+        .filter(|pattern| !pattern.is_empty())
+    {
+        Some(pattern) => {
+            Regex::new(pattern).map_err(|e| anyhow::anyhow!("Invalid pattern: {}", e))?
+        }
+        None => Regex::new(&regex::escape(args))?,
+    };
+
+    Ok(Some(Target::Pattern(pattern)))
+}
+
+fn user_messages(entries: &[TranscriptEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            SessionEvent::UserMessage { text } => Some(text.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(60) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+fn mask_entry(entry: &mut TranscriptEntry, pattern: &Regex) -> usize {
+    match &mut entry.event {
+        SessionEvent::UserMessage { text }
+        | SessionEvent::ToolResult { output: text, .. }
+        | SessionEvent::Error { message: text }
+        | SessionEvent::Forked {
+            title: Some(text), ..
+        } => mask_text(text, pattern),
+        SessionEvent::ModelResponse { content } => mask_content(content, pattern),
+        SessionEvent::ToolCall { args, .. } => mask_value(args, pattern),
+        SessionEvent::SessionStarted { .. }
+        | SessionEvent::Handoff { .. }
+        | SessionEvent::Forked { .. } => 0,
+    }
+}
+
+fn mask_content(content: &mut Content, pattern: &Regex) -> usize {
+    content
+        .parts
+        .iter_mut()
+        .map(|part| {
+            part.text
+                .as_mut()
+                .map_or(0, |text| mask_text(text, pattern))
+                + part
+                    .function_call
+                    .as_mut()
+                    .map_or(0, |call| mask_value(&mut call.args, pattern))
+                + part
+                    .function_response
+                    .as_mut()
+                    .map_or(0, |response| mask_value(&mut response.response, pattern))
+        })
+        .sum()
+}
+
+// Returns the number of matches.
+fn mask_text(text: &mut String, pattern: &Regex) -> usize {
+    let count = pattern.find_iter(text).count();
+    if count > 0 {
+        *text = pattern.replace_all(text, REDACTED).to_string();
+    }
+
+    count
+}
+
+// The strings of tool arguments and results, keys are left as they are.
+fn mask_value(value: &mut Value, pattern: &Regex) -> usize {
+    match value {
+        Value::String(text) => mask_text(text, pattern),
+        Value::Array(values) => values
+            .iter_mut()
+            .map(|value| mask_value(value, pattern))
+            .sum(),
+        Value::Object(map) => map
+            .values_mut()
+            .map(|value| mask_value(value, pattern))
+            .sum(),
+        _ => 0,
+    }
+}
+
+// Drops the turn's message and everything up to the next one, returning the
+// dropped events.
+fn drop_turn(entries: &mut Vec<TranscriptEntry>, turn: usize) -> Vec<SessionEvent> {
+    let mut current = 0;
+    let mut dropped = vec![];
+    entries.retain(|entry| {
+        if matches!(entry.event, SessionEvent::UserMessage { .. }) {
+            current += 1;
+        }
+        if current != turn {
+            return true;
+        }
+        dropped.push(entry.event.clone());
+        false
+    });
+
+    dropped
+}
+
+// Drops the turn's message and everything up to the next user message. The
+// stored conversation may go back further than this session, so the session's
+// messages are its last ones. False when the message at that position is not
+// the one of the transcript, e.g. after the history was summarized.
+fn drop_stored_turn(contents: &mut Vec<Content>, turns: &[String], turn: usize) -> bool {
+    let messages = contents
+        .iter()
+        .enumerate()
+        .filter(|(_, content)| is_user_message(content))
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+    let Some(start) = (messages.len() + turn)
+        .checked_sub(turns.len() + 1)
+        .and_then(|position| messages.get(position))
+        .copied()
+    else {
+        return false;
+    };
+    if text(&contents[start]) != turns[turn - 1] {
+        return false;
+    }
+
+    let end = messages
+        .iter()
+        .find(|index| **index > start)
+        .copied()
+        .unwrap_or(contents.len());
+    contents.drain(start..end);
+    true
+}
+
+// A message the user typed, not a tool result sent back in the user's role.
+fn is_user_message(content: &Content) -> bool {
+    content.role == "user"
+        && content
+            .parts
+            .iter()
+            .all(|part| part.function_response.is_none())
+}
+
+fn text(content: &Content) -> String {
+    content
+        .parts
+        .iter()
+        .filter_map(|part| part.answer().cloned())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// A client that never saw the redacted content, with the provider the session
+// uses now, the stored conversation replayed into it. `/branches` swaps the
+// conversation the same way.
+pub async fn reload(agent: &mut Agent, entries: &[TranscriptEntry]) -> anyhow::Result<()> {
+    let provider = entries
+        .iter()
+        .rev()
+        .find_map(|entry| match &entry.event {
+            SessionEvent::Handoff { provider } => Some(provider.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(providers::selected);
+    let client = providers::registry().build(&provider)?;
+
+    if let Some(system_prompt) = agent.client().system_prompt().await {
+        _ = client.set_system_prompt(system_prompt).await;
+    }
+    agent
+        .replace_client(client)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error adding tool: {}", e))?;
+    agent
+        .resume()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error restoring conversation: {}", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::agent::{FunctionCall, Part};
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry(event: SessionEvent) -> TranscriptEntry {
+        TranscriptEntry { at: 0, event }
+    }
+
+    fn message(text: &str, role: &str) -> Content {
+        Content::new(vec![Part::new(text)], role)
+    }
+
+    #[test]
+    fn test_redact() {
+        let Some(Target::Pattern(pattern)) = parse("sk-live-0123").unwrap() else {
+            panic!("expected a pattern");
+        };
+        let mut call = entry(SessionEvent::ToolCall {
+            name: "bash".to_string(),
+            args: json!({ "command": "curl -H 'key: sk-live-0123' example.com" }),
+        });
+        assert_eq!(mask_entry(&mut call, &pattern), 1);
+        assert_eq!(
+            call.event,
+            SessionEvent::ToolCall {
+                name: "bash".to_string(),
+                args: json!({ "command": "curl -H 'key: [redacted]' example.com" }),
+            }
+        );
+        let mut content = Content::new(
+            vec![
+                Part::new("use sk-live-0123 twice: sk-live-0123"),
+                Part {
+                    function_call: Some(FunctionCall {
+                        name: "user".to_string(),
+                        args: json!({ "user": "user" }),
+                    }),
+                    ..Default::default()
+                },
+            ],
+            "user",
+        );
+        assert_eq!(mask_content(&mut content, &pattern), 2);
+        let Some(Target::Pattern(user)) = parse("/us.r/").unwrap() else {
+            panic!("expected a regex");
+        };
+        // Only values are masked, never roles, names or keys.
+        assert_eq!(mask_content(&mut content, &user), 1);
+        assert_eq!(content.role, "user");
+        assert_eq!(
+            content.parts[1].function_call.as_ref().unwrap().args,
+            json!({ "user": "[redacted]" })
+        );
+
+        assert!(matches!(parse("2").unwrap(), Some(Target::Turn(2))));
+        assert!(parse("0").is_err());
+        assert!(parse("").unwrap().is_none());
+
+        // Message 2 of this session, in a conversation resumed from an
+        // earlier one.
+        let mut entries = vec![
+            entry(SessionEvent::UserMessage {
+                text: "hi".to_string(),
+            }),
+            entry(SessionEvent::UserMessage {
+                text: "my key is sk-live-0123".to_string(),
+            }),
+            entry(SessionEvent::ToolResult {
+                name: "read_file".to_string(),
+                output: "{}".to_string(),
+            }),
+            entry(SessionEvent::UserMessage {
+                text: "thanks".to_string(),
+            }),
+        ];
+        let turns = user_messages(&entries);
+        let mut contents = vec![
+            message("my key is sk-live-0123", "user"),
+            message("earlier answer", "model"),
+            message("hi", "user"),
+            message("hello", "model"),
+            message("my key is sk-live-0123", "user"),
+            message("Result of read_file:\n{}", "tool"),
+            message("stored", "model"),
+            message("thanks", "user"),
+        ];
+        assert_eq!(drop_turn(&mut entries, 2).len(), 2);
+        assert_eq!(user_messages(&entries), ["hi", "thanks"]);
+        assert!(drop_stored_turn(&mut contents, &turns, 2));
+        assert_eq!(
+            contents.iter().map(text).collect::<Vec<String>>(),
+            [
+                "my key is sk-live-0123",
+                "earlier answer",
+                "hi",
+                "hello",
+                "thanks"
+            ]
+        );
+
+        // The same text twice, the first of the two is dropped.
+        let turns = ["retry".to_string(), "retry".to_string()];
+        let mut contents = vec![
+            message("retry", "user"),
+            message("first", "model"),
+            message("retry", "user"),
+            message("second", "model"),
+        ];
+        assert!(drop_stored_turn(&mut contents, &turns, 1));
+        assert_eq!(
+            contents.iter().map(text).collect::<Vec<String>>(),
+            ["retry", "second"]
+        );
+        assert!(!drop_stored_turn(&mut contents, &turns, 1));
+    }
+}
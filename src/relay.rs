@@ -0,0 +1,64 @@
+use std::{io::Read, time::Duration};
+
+use models::relay::RemoteAgent;
+
+use crate::{config, policy};
+
+// The voo named by VOO_RELAY_URL, or by `url` when given. VOO_RELAY_TOKEN is
+// its bearer token and VOO_RELAY_TIMEOUT how many seconds to wait for an
+// answer.
+pub fn remote(url: Option<&str>) -> anyhow::Result<RemoteAgent> {
+    let url = match url {
+        Some(url) => url.to_string(),
+        None => config::var("VOO_RELAY_URL").map_err(|_| {
+            anyhow::anyhow!(
+                "--url or VOO_RELAY_URL must name the remote voo, e.g. http://gpu-box:8080"
+            )
+        })?,
+    };
+
+    policy::get().check_endpoint("relay", &url)?;
+    let mut remote = RemoteAgent::new(&url);
+    if let Ok(token) = config::var("VOO_RELAY_TOKEN") {
+        remote = remote.with_token(&token);
+    }
+    if let Ok(timeout) = config::var("VOO_RELAY_TIMEOUT") {
+        let secs = timeout
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Error parsing VOO_RELAY_TIMEOUT: {:?}", timeout))?;
+        remote = remote.with_timeout(Duration::from_secs(secs));
+    }
+
+    Ok(remote)
+}
+
+// Sends a prompt, or stdin when there is none, and prints the answer.
+pub async fn run(
+    prompt: &[String],
+    url: Option<&str>,
+    session: Option<&str>,
+) -> anyhow::Result<()> {
+    let prompt = match prompt.is_empty() {
+        true => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| anyhow::anyhow!("Error reading stdin: {}", e))?;
+            input
+        }
+        false => prompt.join(" "),
+    };
+    if prompt.trim().is_empty() {
+        return Err(anyhow::anyhow!("There is no prompt to relay"));
+    }
+
+    let remote = remote(url)?;
+    let answer = remote
+        .ask(&prompt, session)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error asking {}: {}", remote.url(), e))?;
+    println!("{}", answer);
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::io::IsTerminal;
+
+use std::path::Path;
+
+use domain::models::{
+    agent::{Agent, AgentRole, Content, Part},
+    transcript::{SessionEvent, Transcript},
+};
+use models::{git::checkpoint, tools::run_command::is_risky};
+use tokio::process::Command;
+use tracing::warn;
+
+use crate::{
+    checkpoints_enabled, record, remember,
+    terminal::{prompt, styled_println},
+};
+
+const RUN_TAGS: &[&str] = &["bash:run", "sh:run"];
+// Keeps a chatty command from flooding the conversation.
+const MAX_OUTPUT_BYTES: usize = 20_000;
+
+// Code fences the model marked as runnable, e.g. ```bash:run.
+pub fn runnable_blocks(text: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut current: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        let fence = line.trim_start();
+        match current.as_mut() {
+            Some(lines) if fence.starts_with("```") => {
+                blocks.push(lines.join("\n"));
+                current = None;
+            }
+            Some(lines) => lines.push(line),
+            None => {
+                if let Some(tag) = fence.strip_prefix("```")
+                    && RUN_TAGS.contains(&tag.trim())
+                {
+                    current = Some(vec![]);
+                }
+            }
+        }
+    }
+
+    blocks
+        .into_iter()
+        .filter(|block| !block.trim().is_empty())
+        .collect()
+}
+
+// Offers to run every runnable block of an answer. Returns true when any
+// output was added to the conversation and the model should continue.
+pub async fn offer(agent: &Agent, answer: &str, transcript: &Transcript) -> anyhow::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    let mut ran = false;
+    for block in runnable_blocks(answer) {
+        let answer = prompt("\x1b[33mrun it?\x1b[0m [y/N] ")?;
+        if !matches!(answer.as_str(), "y" | "yes") {
+            continue;
+        }
+
+        styled_println!(
+            "\x1b[33mbash> {}\x1b[0m",
+            block.lines().next().unwrap_or_default()
+        );
+        record(
+            transcript,
+            SessionEvent::ToolCall {
+                name: "bash".to_string(),
+                args: serde_json::json!({ "command": block }),
+            },
+        );
+
+        if checkpoints_enabled() && is_risky(&block) {
+            let message = format!("before: {}", block.lines().next().unwrap_or_default());
+            match checkpoint(Path::new("."), &message).await {
+                Ok(checkpoint) => styled_println!(
+                    "\x1b[2msaved checkpoint {}, /restore-checkpoint undoes this block\x1b[0m",
+                    checkpoint.id
+                ),
+                Err(e) => warn!("[VOO] no checkpoint before running the block: {}", e),
+            }
+        }
+
+        let output = run(agent, &block).await?;
+        println!("{}", output);
+        record(
+            transcript,
+            SessionEvent::ToolResult {
+                name: "bash".to_string(),
+                output: output.clone(),
+            },
+        );
+
+        let message = format!(
+            "I ran this block:\n```bash\n{}\n```\n{}",
+            block.trim_end(),
+            output
+        );
+        _ = agent
+            .client()
+            .add_system_prompt(&message, AgentRole::User)
+            .await;
+        remember(agent, &Content::new(vec![Part::new(&message)], "user"));
+        ran = true;
+    }
+
+    Ok(ran)
+}
+
+async fn run(agent: &Agent, block: &str) -> anyhow::Result<String> {
+    let context = agent.tool_context().await;
+    let output = Command::new("bash")
+        .arg("-c")
+        .arg(block)
+        .envs(&context.env)
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error running block: {}", e))?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if text.len() > MAX_OUTPUT_BYTES {
+        let end = text.floor_char_boundary(MAX_OUTPUT_BYTES);
+        text.truncate(end);
+        text.push_str("\n[output truncated]");
+    }
+
+    Ok(format!("{}\n{}", text.trim_end(), output.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runnable_blocks() {
+        let answer = "Try this:\n```bash:run\ncargo test\n```\nnot this:\n```bash\nrm -rf target\n```\n  ```sh:run\nls\n```";
+        assert_eq!(runnable_blocks(answer), ["cargo test", "ls"]);
+        assert!(runnable_blocks("```bash:run\n\n```").is_empty());
+    }
+}
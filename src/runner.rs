@@ -0,0 +1,273 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{Agent, AgentError, FunctionCall},
+    tools::{ToolContext, ToolOutput},
+    turn::{ToolCallHooks, ToolLimits, TurnHandler},
+};
+
+use crate::{config, cost::CostGuard, interrupt, terminal::styled_println};
+
+const PREVIEW_LINES: usize = 8;
+
+// Runs the tool calls of a turn with `perform_function_call`, echoing them
+// when asked to.
+pub struct ToolRunner {
+    pub echo: bool,
+}
+
+#[async_trait]
+impl TurnHandler for ToolRunner {
+    async fn execute(
+        &self,
+        agent: &Agent,
+        calls: &[FunctionCall],
+    ) -> Result<Vec<ToolOutput>, AgentError> {
+        let context = agent.tool_context().await;
+        perform_function_call(agent, calls, &context, self.echo)
+            .await
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))
+    }
+}
+
+#[async_trait]
+impl ToolCallHooks for ToolRunner {
+    fn started(&self, call: &FunctionCall) {
+        if self.echo {
+            styled_println!("\x1b[33m{}> {}\x1b[0m", call.name, call.args);
+        }
+    }
+
+    fn finished(&self, call: &FunctionCall, output: &ToolOutput) {
+        if self.echo {
+            print_preview(&call.name, output);
+        }
+    }
+
+    // Ctrl+C gives up the calls in flight.
+    async fn cancelled(&self) {
+        _ = interrupt::cancellable(std::future::pending::<()>()).await;
+    }
+}
+
+// Runs the calls of one response with `Agent::execute_tools`, see there.
+pub async fn perform_function_call(
+    agent: &Agent,
+    function_calls: &[FunctionCall],
+    context: &ToolContext,
+    echo: bool,
+) -> anyhow::Result<Vec<ToolOutput>> {
+    Ok(agent
+        .execute_tools(function_calls, context, &ToolRunner { echo })
+        .await)
+}
+
+// VOO_TOOL_CONCURRENCY caps the calls running at once, 1 runs them one after
+// another. VOO_TOOL_TIMEOUT is how many seconds a tool call may take, 0 would
+// time out every call and is ignored like an invalid value.
+pub fn tool_limits() -> ToolLimits {
+    let var = |name: &str| {
+        config::var(name)
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|&value| value > 0)
+    };
+    let defaults = ToolLimits::default();
+
+    ToolLimits {
+        concurrency: var("VOO_TOOL_CONCURRENCY").unwrap_or(defaults.concurrency),
+        timeout: var("VOO_TOOL_TIMEOUT")
+            .map(|secs| Duration::from_secs(secs as u64))
+            .unwrap_or(defaults.timeout),
+    }
+}
+
+// Shows the start of an output, long file contents would bury the
+// conversation.
+fn print_preview(tool_name: &str, output: &ToolOutput) {
+    let text = match output {
+        ToolOutput::Error(message) => {
+            styled_println!("\x1b[41m{}>\x1b[0m {}", tool_name, message);
+            return;
+        }
+        ToolOutput::Json(value) => {
+            serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+        }
+        output => output.to_string(),
+    };
+
+    styled_println!("\x1b[2m{}\x1b[0m", preview(&text, PREVIEW_LINES));
+}
+
+fn preview(text: &str, max_lines: usize) -> String {
+    let lines = text.lines().collect::<Vec<&str>>();
+    if lines.len() <= max_lines {
+        return lines.join("\n");
+    }
+
+    format!(
+        "{}\n... {} more lines",
+        lines[..max_lines].join("\n"),
+        lines.len() - max_lines
+    )
+}
+
+// Runs before every request of an interactive turn. Returns false when the
+// turn should not be sent.
+pub async fn pre_request(
+    agent: &Agent,
+    cost_guard: &CostGuard,
+    input: &str,
+) -> anyhow::Result<bool> {
+    cost_guard.check(agent, input).await
+}
+
+pub async fn run_prompt(agent: &Agent, prompt: &str) -> anyhow::Result<String> {
+    agent
+        .run_turn_with(prompt, &ToolRunner { echo: false })
+        .await
+        .map(|turn| turn.answer)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::Display,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    use domain::models::{
+        agent::{AgentClient, AgentRole, Content},
+        tool_registry::{DangerLevel, ToolCategory, ToolMetadata},
+        tools::{Tool, ToolDefinition, ToolError},
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct NoClient;
+
+    #[async_trait]
+    impl AgentClient for NoClient {
+        async fn ask(&self, _prompt: &str) -> Result<Vec<Content>, AgentError> {
+            Ok(vec![])
+        }
+
+        async fn add_tool(&self, _tool: Arc<dyn Tool>) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        async fn add_system_prompt(
+            &self,
+            _prompt: &str,
+            _role: AgentRole,
+        ) -> Result<(), AgentError> {
+            Ok(())
+        }
+    }
+
+    // Sleeps and answers with its input, counting the calls running at once.
+    #[derive(Debug)]
+    struct SlowTool {
+        definition: ToolDefinition,
+        running: Arc<AtomicUsize>,
+        most: Arc<AtomicUsize>,
+    }
+
+    impl Display for SlowTool {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.definition.name)
+        }
+    }
+
+    #[async_trait]
+    impl Tool for SlowTool {
+        async fn exec(&self, input: Value) -> Result<ToolOutput, ToolError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(ToolOutput::text(input["n"].to_string()))
+        }
+
+        fn name(&self) -> &str {
+            &self.definition.name
+        }
+
+        fn description(&self) -> &str {
+            ""
+        }
+
+        fn tool_definition(&self) -> &ToolDefinition {
+            &self.definition
+        }
+    }
+
+    fn call(name: &str, n: usize) -> FunctionCall {
+        FunctionCall {
+            name: name.to_string(),
+            args: json!({ "n": n }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calls_run_concurrently_in_order() {
+        let agent = Agent::new(NoClient);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        for (name, danger) in [("read", DangerLevel::Safe), ("edit", DangerLevel::Mutating)] {
+            let tool = SlowTool {
+                definition: ToolDefinition {
+                    name: name.to_string(),
+                    ..Default::default()
+                },
+                running: running.clone(),
+                most: most.clone(),
+            };
+            agent
+                .add_tool(
+                    Arc::new(tool),
+                    ToolMetadata::new(ToolCategory::Read, danger),
+                    true,
+                )
+                .await
+                .unwrap();
+        }
+        let context = agent.tool_context().await;
+
+        let calls = (0..4)
+            .map(|n| call("read", n))
+            .collect::<Vec<FunctionCall>>();
+        let outputs = perform_function_call(&agent, &calls, &context, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            outputs,
+            (0..4)
+                .map(|n| ToolOutput::text(n.to_string()))
+                .collect::<Vec<ToolOutput>>()
+        );
+        assert!(most.load(Ordering::SeqCst) > 1);
+
+        // A call that changes files runs alone.
+        most.store(0, Ordering::SeqCst);
+        let calls = [call("read", 0), call("edit", 1), call("read", 2)];
+        let outputs = perform_function_call(&agent, &calls, &context, false)
+            .await
+            .unwrap();
+        assert_eq!(outputs[1], ToolOutput::text("1"));
+        assert_eq!(most.load(Ordering::SeqCst), 1);
+
+        let outputs = perform_function_call(&agent, &[call("missing", 0)], &context, false)
+            .await
+            .unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert!(outputs[0].is_error());
+    }
+}
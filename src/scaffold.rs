@@ -0,0 +1,261 @@
+use std::{
+    io::IsTerminal,
+    path::{Component, Path, PathBuf},
+};
+
+use domain::models::{
+    agent::Agent,
+    mode::Mode,
+    tools::{Tool, ToolOutput},
+};
+use models::tools::write_file::{WriteFileTool, colorize, unified_diff};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    auto_approve, build_agent, formatters, policy,
+    runner::run_prompt,
+    terminal::{prompt, styled_println},
+    transform,
+};
+
+const MAX_FILES: usize = 40;
+
+#[derive(Debug, Deserialize)]
+struct Layout {
+    directory: String,
+    files: Vec<PlannedFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlannedFile {
+    path: String,
+    #[serde(default)]
+    purpose: String,
+}
+
+// Plans the files of a new project, generates them one after another in the
+// same conversation so they fit together, and writes them under a new
+// directory once the user approved the combined preview.
+pub async fn run(description: &str, directory: Option<&str>) -> anyhow::Result<()> {
+    if description.trim().is_empty() {
+        return Err(anyhow::anyhow!("Describe what to scaffold"));
+    }
+    if !policy::get().tool_allowed("write_file") {
+        return Err(anyhow::anyhow!("The policy does not allow writing files"));
+    }
+    if !auto_approve() && !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "voo scaffold asks before writing, run it in a terminal or pass --auto-approve"
+        ));
+    }
+
+    let agent = build_agent().await?;
+    // The agent may read the workspace for conventions, only the approved
+    // preview gets written.
+    agent
+        .set_mode(Mode::Ask)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error switching to ask mode: {}", e))?;
+
+    styled_println!("\x1b[32mscaffold>\x1b[0m planning the files");
+    let answer = run_prompt(&agent, &layout_prompt(description)).await?;
+    let mut layout = parse_layout(&answer)?;
+    if let Some(directory) = directory {
+        layout.directory = directory.to_string();
+    }
+    check_path(&layout.directory)?;
+    for file in &layout.files {
+        check_path(&file.path)?;
+    }
+    let root = PathBuf::from(&layout.directory);
+    if root.exists() {
+        return Err(anyhow::anyhow!(
+            "{} already exists, pass --dir to scaffold into a new directory",
+            root.display()
+        ));
+    }
+
+    if !auto_approve() && !approve_layout(&mut layout)? {
+        styled_println!("\x1b[32mscaffold>\x1b[0m cancelled");
+        return Ok(());
+    }
+
+    let files = generate(&agent, description, &layout).await?;
+    for (path, content) in &files {
+        let diff = unified_diff(&path.display().to_string(), "", content);
+        styled_println!("{}", colorize(&diff));
+    }
+    if !auto_approve() {
+        let answer = prompt(&format!(
+            "write {} files under {}? [y/n] ",
+            files.len(),
+            root.display()
+        ))?;
+        if !matches!(answer.as_str(), "y" | "yes") {
+            styled_println!("\x1b[32mscaffold>\x1b[0m cancelled, nothing was written");
+            return Ok(());
+        }
+    }
+
+    let mut write_file_tool = WriteFileTool::new("write_file", "").with_auto_approve(true);
+    if let Some(formatters) = formatters() {
+        write_file_tool = write_file_tool.with_formatters(formatters);
+    }
+    for (path, content) in &files {
+        let input = json!({ "path": path, "content": content });
+        match write_file_tool.exec(input).await {
+            Ok(ToolOutput::Error(message)) => return Err(anyhow::anyhow!("{}", message)),
+            Ok(output) => styled_println!("\x1b[2m{}\x1b[0m", output),
+            Err(e) => return Err(anyhow::anyhow!("Error writing {}: {}", path.display(), e)),
+        }
+    }
+    styled_println!(
+        "\x1b[32mscaffold>\x1b[0m wrote {} files under {}",
+        files.len(),
+        root.display()
+    );
+
+    Ok(())
+}
+
+fn layout_prompt(description: &str) -> String {
+    format!(
+        r#"Do not write any files. Plan the files of a new project for the description below, only the ones needed to build, run and test it, at most {}.
+
+Reply with JSON only, in this shape:
+{{"directory": "short-kebab-case-name", "files": [{{"path": "relative/path", "purpose": "..."}}]}}
+
+Description: {}"#,
+        MAX_FILES, description
+    )
+}
+
+fn file_prompt(description: &str, layout: &Layout, file: &PlannedFile) -> String {
+    let files = layout
+        .files
+        .iter()
+        .map(|file| format!("- {}: {}", file.path, file.purpose))
+        .collect::<Vec<String>>();
+
+    format!(
+        "Do not write any files. Write the complete contents of {} ({}) for the project \"{}\" with these files:\n{}\n\nKeep it consistent with the files written so far. Answer with the file in a single code block and nothing else.",
+        file.path,
+        file.purpose,
+        description,
+        files.join("\n")
+    )
+}
+
+fn parse_layout(text: &str) -> anyhow::Result<Layout> {
+    let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) else {
+        return Err(anyhow::anyhow!(
+            "The model did not return a file tree: {}",
+            text
+        ));
+    };
+
+    let mut layout = serde_json::from_str::<Layout>(&text[start..=end])
+        .map_err(|e| anyhow::anyhow!("Error parsing the file tree: {}", e))?;
+    layout.files.truncate(MAX_FILES);
+    if layout.files.is_empty() {
+        return Err(anyhow::anyhow!("The model planned no files"));
+    }
+
+    Ok(layout)
+}
+
+// Paths stay inside the new directory.
+fn check_path(path: &str) -> anyhow::Result<()> {
+    let relative = !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    match relative {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("{:?} is not a relative path", path)),
+    }
+}
+
+fn approve_layout(layout: &mut Layout) -> anyhow::Result<bool> {
+    loop {
+        styled_println!("\x1b[32mscaffold>\x1b[0m {}/", layout.directory);
+        for (index, file) in layout.files.iter().enumerate() {
+            styled_println!(
+                "  {}. {}  \x1b[2m# {}\x1b[0m",
+                index + 1,
+                file.path,
+                file.purpose
+            );
+        }
+
+        let answer = prompt("[a] approve, [d <n>] drop file, [q] cancel: ")?;
+        match answer.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["a"] => return Ok(true),
+            ["q"] => return Ok(false),
+            ["d", file] => match file.parse::<usize>() {
+                Ok(file) if (1..=layout.files.len()).contains(&file) => {
+                    layout.files.remove(file - 1);
+                    if layout.files.is_empty() {
+                        return Ok(false);
+                    }
+                }
+                _ => styled_println!("\x1b[41mscaffold>\x1b[0m no file {}", file),
+            },
+            _ => continue,
+        }
+    }
+}
+
+async fn generate(
+    agent: &Agent,
+    description: &str,
+    layout: &Layout,
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let root = PathBuf::from(&layout.directory);
+    let mut files = vec![];
+
+    for (index, file) in layout.files.iter().enumerate() {
+        styled_println!(
+            "\x1b[32mscaffold>\x1b[0m [{}/{}] {}",
+            index + 1,
+            layout.files.len(),
+            file.path
+        );
+        let answer = run_prompt(agent, &file_prompt(description, layout, file)).await?;
+        let mut content = transform::code_block(&answer).ok_or_else(|| {
+            anyhow::anyhow!("The answer for {} contained no code block", file.path)
+        })?;
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        files.push((root.join(&file.path), content));
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_layout() {
+        let layout = parse_layout(
+            r#"Here is the plan:
+{"directory": "todo-cli", "files": [{"path": "Cargo.toml", "purpose": "manifest"}, {"path": "src/main.rs"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(layout.directory, "todo-cli");
+        assert_eq!(layout.files[1].path, "src/main.rs");
+        assert_eq!(layout.files[1].purpose, "");
+
+        assert!(parse_layout(r#"{"directory": "empty", "files": []}"#).is_err());
+        assert!(parse_layout("no plan").is_err());
+
+        assert!(check_path("src/main.rs").is_ok());
+        assert!(check_path("../escape.rs").is_err());
+        assert!(check_path("/etc/passwd").is_err());
+        assert!(check_path("").is_err());
+    }
+}
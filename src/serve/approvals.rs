@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{Agent, AgentError, FunctionCall},
+    tool_registry::DangerLevel,
+    tools::ToolOutput,
+    turn::TurnHandler,
+};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::runner::perform_function_call;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub name: String,
+    pub args: Value,
+    pub danger: DangerLevel,
+}
+
+// Tool calls waiting for a remote user to approve them. The requests go out
+// on `requests`, and a call whose request is never answered, e.g. because the
+// client went away, is declined.
+#[derive(Debug, Clone)]
+pub struct Approvals {
+    requests: mpsc::UnboundedSender<ApprovalRequest>,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
+}
+
+impl Approvals {
+    pub fn new(requests: mpsc::UnboundedSender<ApprovalRequest>) -> Self {
+        Self {
+            requests,
+            pending: Arc::default(),
+        }
+    }
+
+    async fn ask(&self, call: &FunctionCall, danger: DangerLevel) -> bool {
+        let id = uuid::Uuid::new_v4().to_string();
+        let (answer, answered) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.clone(), answer);
+
+        let request = ApprovalRequest {
+            id: id.clone(),
+            name: call.name.clone(),
+            args: call.args.clone(),
+            danger,
+        };
+        if self.requests.send(request).is_err() {
+            self.pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&id);
+            return false;
+        }
+
+        answered.await.unwrap_or(false)
+    }
+
+    // Declines the calls waiting for an answer, e.g. once the client is gone.
+    pub fn decline_all(&self) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    // False when nothing waits for the answer, e.g. an unknown or already
+    // answered request.
+    pub fn answer(&self, id: &str, approved: bool) -> bool {
+        match self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id)
+        {
+            Some(answer) => answer.send(approved).is_ok(),
+            None => false,
+        }
+    }
+}
+
+// Asks for every call that isn't safe, e.g. one that changes files, runs
+// commands or sends data to another host, before running the approved ones.
+// The agent is built with approval, the tools don't ask again.
+pub struct ApprovalRunner {
+    pub approvals: Approvals,
+}
+
+#[async_trait]
+impl TurnHandler for ApprovalRunner {
+    async fn execute(
+        &self,
+        agent: &Agent,
+        calls: &[FunctionCall],
+    ) -> Result<Vec<ToolOutput>, AgentError> {
+        let dangers = {
+            let tools = agent.tools();
+            let tools = tools.lock().await;
+            calls
+                .iter()
+                .map(|call| {
+                    tools
+                        .get(&call.name)
+                        .map(|registered| registered.metadata.danger)
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<DangerLevel>>()
+        };
+
+        let mut approved = vec![];
+        for (call, danger) in calls.iter().zip(dangers) {
+            approved.push(danger == DangerLevel::Safe || self.approvals.ask(call, danger).await);
+        }
+
+        let runnable = calls
+            .iter()
+            .zip(&approved)
+            .filter(|(_, approved)| **approved)
+            .map(|(call, _)| call.clone())
+            .collect::<Vec<FunctionCall>>();
+        let context = agent.tool_context().await;
+        let mut outputs = perform_function_call(agent, &runnable, &context, false)
+            .await
+            .map_err(|e| AgentError::AgentError(Some(e.to_string())))?
+            .into_iter();
+
+        Ok(calls
+            .iter()
+            .zip(approved)
+            .map(|(call, approved)| match approved {
+                true => outputs.next().unwrap_or_else(|| {
+                    ToolOutput::error(format!("The {} call produced no output", call.name))
+                }),
+                false => ToolOutput::error(format!(
+                    "The user declined the {} call, it was not run",
+                    call.name
+                )),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_approvals() {
+        let (requests, mut received) = mpsc::unbounded_channel();
+        let approvals = Approvals::new(requests);
+        let call = FunctionCall {
+            name: "run_command".to_string(),
+            args: json!({ "command": "ls" }),
+        };
+
+        let asking = approvals.clone();
+        let asked = {
+            let call = call.clone();
+            tokio::spawn(async move { asking.ask(&call, DangerLevel::Dangerous).await })
+        };
+        let request = received.recv().await.unwrap();
+        assert_eq!(request.name, "run_command");
+        assert!(!approvals.answer("unknown", true));
+        assert!(approvals.answer(&request.id, true));
+        assert!(asked.await.unwrap());
+        assert!(!approvals.answer(&request.id, true));
+
+        // Calls still waiting when the client goes away are declined.
+        let asking = approvals.clone();
+        let asked = tokio::spawn(async move { asking.ask(&call, DangerLevel::Mutating).await });
+        received.recv().await.unwrap();
+        approvals.decline_all();
+        assert!(!asked.await.unwrap());
+    }
+}
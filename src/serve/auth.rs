@@ -0,0 +1,192 @@
+use std::fmt::Display;
+
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{StatusCode, header::AUTHORIZATION, request::Parts},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ServeUser, policy};
+
+use super::{
+    AppState,
+    jobs::{JobError, job_error},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Admin,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+// What a job may do beyond answering a prompt of its user. Every role can
+// submit prompts and read its own jobs, but without approval the tools that
+// change files or run commands are declined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ApproveTools,
+    ChangeModel,
+    OtherUsers,
+    InspectUsage,
+}
+
+impl Permission {
+    fn role(self) -> Role {
+        match self {
+            Permission::ApproveTools => Role::Operator,
+            Permission::ChangeModel | Permission::OtherUsers | Permission::InspectUsage => {
+                Role::Admin
+            }
+        }
+    }
+}
+
+// The user a request was made by. Without configured users the server is
+// open and callers are anonymous viewers, who share their jobs and sessions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller {
+    pub user: Option<String>,
+    pub role: Role,
+}
+
+impl Caller {
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.role >= permission.role()
+    }
+
+    pub fn owns(&self, owner: Option<&str>) -> bool {
+        self.user.as_deref() == owner
+    }
+
+    // Refuses the request unless the role grants the permission. Decisions on
+    // the requests of configured users end up in the audit log.
+    pub fn authorize(
+        &self,
+        permission: Permission,
+        action: &str,
+    ) -> Result<(), (StatusCode, Json<JobError>)> {
+        let allowed = self.allows(permission);
+        if let Some(user) = &self.user {
+            policy::audit_access(user, &self.role.to_string(), action, allowed);
+        }
+
+        if allowed {
+            return Ok(());
+        }
+        Err(job_error(
+            StatusCode::FORBIDDEN,
+            format!(
+                "{} may not {}, that takes the {} role",
+                self.role,
+                action,
+                permission.role()
+            ),
+        ))
+    }
+}
+
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = (StatusCode, Json<JobError>);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if state.serve.users.is_empty() {
+            return Ok(Caller {
+                user: None,
+                role: Role::Viewer,
+            });
+        }
+
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token.and_then(|token| find(&state.serve.users, token)) {
+            Some(user) => Ok(Caller {
+                user: Some(user.name.clone()),
+                role: user.role,
+            }),
+            None => Err(job_error(
+                StatusCode::UNAUTHORIZED,
+                "a valid bearer token is required",
+            )),
+        }
+    }
+}
+
+fn find<'a>(users: &'a [ServeUser], token: &str) -> Option<&'a ServeUser> {
+    users
+        .iter()
+        .find(|user| !user.token.is_empty() && constant_time_eq(&user.token, token))
+}
+
+// Compares tokens without returning early, so the time taken doesn't tell
+// how much of a guess was right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles() {
+        let viewer = Caller {
+            user: Some("ana".to_string()),
+            role: Role::Viewer,
+        };
+        let operator = Caller {
+            user: Some("ben".to_string()),
+            role: Role::Operator,
+        };
+        let admin = Caller {
+            user: Some("cy".to_string()),
+            role: Role::Admin,
+        };
+
+        assert!(!viewer.allows(Permission::ApproveTools));
+        assert!(operator.allows(Permission::ApproveTools));
+        assert!(!operator.allows(Permission::ChangeModel));
+        assert!(!operator.allows(Permission::OtherUsers));
+        assert!(admin.allows(Permission::OtherUsers));
+
+        let error = viewer
+            .authorize(Permission::ApproveTools, "approve tool calls")
+            .unwrap_err();
+        assert_eq!(error.0, StatusCode::FORBIDDEN);
+        assert!(viewer.owns(Some("ana")));
+        assert!(!viewer.owns(None));
+
+        let users = vec![ServeUser {
+            name: "ben".to_string(),
+            token: "s3cret-token".to_string(),
+            role: Role::Operator,
+            team: None,
+            quota: Default::default(),
+        }];
+        assert_eq!(find(&users, "s3cret-token").unwrap().name, "ben");
+        assert!(find(&users, "s3cret-tokem").is_none());
+        assert!(find(&users, "s3cret").is_none());
+    }
+}
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use axum::{Json, extract::State, http::StatusCode};
+use domain::models::health::ProviderHealth;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use super::AppState;
+
+// Load balancers poll /readyz every few seconds, so whether the provider can
+// be reached is only probed again once the last result is this old.
+const PROBE_TTL: Duration = Duration::from_secs(30);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+    version: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    status: &'static str,
+    checks: ReadinessChecks,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessChecks {
+    config: Check,
+    provider: Check,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<ProviderHealth>,
+}
+
+// The last result of probing the provider's endpoint.
+#[derive(Debug, Default)]
+pub struct ReachabilityProbe {
+    last: Mutex<Option<(Instant, Result<(), String>)>>,
+}
+
+impl ReachabilityProbe {
+    // Concurrent checks wait for the probe in flight instead of sending their
+    // own.
+    pub async fn check(&self, url: &str) -> Result<(), String> {
+        let mut last = self.last.lock().await;
+        if let Some((probed_at, result)) = last.as_ref()
+            && probed_at.elapsed() < PROBE_TTL
+        {
+            return result.clone();
+        }
+
+        let result = reach(url).await;
+        *last = Some((Instant::now(), result.clone()));
+        result
+    }
+}
+
+// Any response counts, an error status for a request without credentials
+// still shows the provider is up.
+async fn reach(url: &str) -> Result<(), String> {
+    match reqwest::Client::new()
+        .head(url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) if e.is_timeout() => Err(format!(
+            "provider did not answer within {}s",
+            PROBE_TIMEOUT.as_secs()
+        )),
+        Err(e) => Err(format!("provider is unreachable: {}", e)),
+    }
+}
+
+pub async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+    })
+}
+
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let config = Check {
+        ok: state.config_error.is_none(),
+        message: state.config_error.clone(),
+        health: None,
+    };
+
+    let provider = match &state.agent {
+        Some(agent) => {
+            let health = agent.client().health().await;
+            let reachable = match agent.client().endpoint() {
+                Some(url) => state.probe.check(&url).await,
+                None => Ok(()),
+            };
+            Check {
+                ok: health.as_ref().is_none_or(|health| health.is_available()) && reachable.is_ok(),
+                message: reachable.err(),
+                health,
+            }
+        }
+        None => Check {
+            ok: false,
+            message: Some("provider is not configured".to_string()),
+            health: None,
+        },
+    };
+
+    let ready = config.ok && provider.ok;
+    let status_code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status: if ready { "ready" } else { "not_ready" },
+            checks: ReadinessChecks { config, provider },
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_probe_caches_result() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let probe = ReachabilityProbe::default();
+        assert_eq!(probe.check(&url).await, Ok(()));
+        server.await.unwrap();
+        // The server is gone, the cached result is still used.
+        assert_eq!(probe.check(&url).await, Ok(()));
+
+        let unreachable = ReachabilityProbe::default();
+        assert!(unreachable.check("http://127.0.0.1:1").await.is_err());
+    }
+}
@@ -0,0 +1,777 @@
+use std::{
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    Json,
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+};
+use domain::{
+    models::{
+        agent::{Agent, Content, Part},
+        events::AgentEvent,
+        transcript::SessionEvent,
+    },
+    storage::sqlite::SqliteSessionStore,
+};
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, broadcast};
+use tracing::{error, info, warn};
+
+use crate::{
+    auto_approve, build_agent_with, cost,
+    paths::sessions_db,
+    policy, providers,
+    runner::run_prompt,
+    sessions,
+    webhooks::{self, RunCompleted, RunUsage, WebhookNotifier},
+};
+
+use super::{
+    AppState,
+    approvals::{ApprovalRunner, Approvals},
+    auth::{Caller, Permission},
+    quotas,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Queued => write!(f, "queued"),
+            JobStatus::Running => write!(f, "running"),
+            JobStatus::Succeeded => write!(f, "succeeded"),
+            JobStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "succeeded" => Ok(JobStatus::Succeeded),
+            "failed" => Ok(JobStatus::Failed),
+            _ => Err(anyhow::anyhow!("Unknown job status: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub prompt: String,
+    pub status: JobStatus,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub callback_url: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    // The named session the prompt continues, as with `voo --session`.
+    pub session: Option<String>,
+    // The `voo serve` user who submitted the job, none when the server has no
+    // users.
+    pub user: Option<String>,
+    // Runs the job with this provider instead of the server's.
+    pub provider: Option<String>,
+    // Tool calls that change files or run commands are approved.
+    pub approved: bool,
+    // What the job used, counted against the quotas of its user.
+    pub tokens: i64,
+    pub cost: f64,
+}
+
+impl Job {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let status: String = row.get(2)?;
+
+        Ok(Self {
+            id: row.get(0)?,
+            prompt: row.get(1)?,
+            status: status.parse().map_err(|e: anyhow::Error| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    e.into_boxed_dyn_error(),
+                )
+            })?,
+            result: row.get(3)?,
+            error: row.get(4)?,
+            callback_url: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+            session: row.get(8)?,
+            user: row.get(9)?,
+            provider: row.get(10)?,
+            approved: row.get(11)?,
+            tokens: row.get(12)?,
+            cost: row.get(13)?,
+        })
+    }
+}
+
+const JOB_COLUMNS: &str = "id, prompt, status, result, error, callback_url, created_at, updated_at, session, user, provider, approved, tokens, cost";
+
+// Columns added since the first version of the table.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("session", "TEXT"),
+    ("user", "TEXT"),
+    ("provider", "TEXT"),
+    ("approved", "INTEGER NOT NULL DEFAULT 0"),
+    ("tokens", "INTEGER NOT NULL DEFAULT 0"),
+    ("cost", "REAL NOT NULL DEFAULT 0"),
+];
+
+#[derive(Debug)]
+pub struct JobStore {
+    conn: Mutex<Connection>,
+}
+
+impl JobStore {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                prompt TEXT NOT NULL,
+                status TEXT NOT NULL,
+                result TEXT,
+                error TEXT,
+                callback_url TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS jobs_status_idx ON jobs (status, created_at);
+            "#,
+        )?;
+        // Databases from older versions lack the columns added since.
+        for (column, definition) in ADDED_COLUMNS {
+            if conn
+                .prepare(&format!("SELECT {} FROM jobs LIMIT 0", column))
+                .is_err()
+            {
+                conn.execute(
+                    &format!("ALTER TABLE jobs ADD COLUMN {} {}", column, definition),
+                    [],
+                )?;
+            }
+        }
+
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
+        let requeued = store.requeue_running()?;
+        if requeued > 0 {
+            info!("[VOO] requeued {} interrupted jobs", requeued);
+        }
+
+        Ok(store)
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn enqueue(&self, request: CreateJobRequest, user: Option<String>) -> anyhow::Result<Job> {
+        self.insert(request, user, JobStatus::Queued)
+    }
+
+    // A job that runs right away instead of waiting for a worker, e.g. a
+    // message to a session. A restart requeues it like any running job.
+    pub fn start(&self, request: CreateJobRequest, user: Option<String>) -> anyhow::Result<Job> {
+        self.insert(request, user, JobStatus::Running)
+    }
+
+    fn insert(
+        &self,
+        request: CreateJobRequest,
+        user: Option<String>,
+        status: JobStatus,
+    ) -> anyhow::Result<Job> {
+        let now = now();
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            prompt: request.prompt,
+            status,
+            result: None,
+            error: None,
+            callback_url: request.callback_url,
+            created_at: now,
+            updated_at: now,
+            session: request.session,
+            user,
+            provider: request.provider,
+            approved: request.approve,
+            tokens: 0,
+            cost: 0.0,
+        };
+
+        self.conn().execute(
+            "INSERT INTO jobs (id, prompt, status, callback_url, created_at, updated_at, session, user, provider, approved) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                job.id,
+                job.prompt,
+                job.status.to_string(),
+                job.callback_url,
+                job.created_at,
+                job.updated_at,
+                job.session,
+                job.user,
+                job.provider,
+                job.approved
+            ],
+        )?;
+
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> anyhow::Result<Option<Job>> {
+        let job = self
+            .conn()
+            .query_row(
+                &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+                params![id],
+                Job::from_row,
+            )
+            .optional()?;
+
+        Ok(job)
+    }
+
+    pub fn list(&self, limit: usize) -> anyhow::Result<Vec<Job>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM jobs ORDER BY created_at DESC, rowid DESC LIMIT ?1",
+            JOB_COLUMNS
+        ))?;
+        let jobs = stmt
+            .query_map(params![limit as i64], Job::from_row)?
+            .collect::<Result<Vec<Job>, _>>()?;
+
+        Ok(jobs)
+    }
+
+    // The jobs of one user, or those submitted without one.
+    pub fn list_for(&self, user: Option<&str>, limit: usize) -> anyhow::Result<Vec<Job>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM jobs WHERE user IS ?1 ORDER BY created_at DESC, rowid DESC LIMIT ?2",
+            JOB_COLUMNS
+        ))?;
+        let jobs = stmt
+            .query_map(params![user, limit as i64], Job::from_row)?
+            .collect::<Result<Vec<Job>, _>>()?;
+
+        Ok(jobs)
+    }
+
+    // A session belongs to the user of the first job that continued it.
+    pub fn session_owner(&self, session: &str) -> anyhow::Result<Option<Option<String>>> {
+        let owner = self
+            .conn()
+            .query_row(
+                "SELECT user FROM jobs WHERE session = ?1 ORDER BY created_at, rowid LIMIT 1",
+                params![session],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(owner)
+    }
+
+    pub fn claim_next(&self) -> anyhow::Result<Option<Job>> {
+        let job = self
+            .conn()
+            .query_row(
+                &format!(
+                    "UPDATE jobs SET status = 'running', updated_at = ?1
+                     WHERE id = (SELECT id FROM jobs WHERE status = 'queued' ORDER BY created_at, rowid LIMIT 1)
+                     RETURNING {}",
+                    JOB_COLUMNS
+                ),
+                params![now()],
+                Job::from_row,
+            )
+            .optional()?;
+
+        Ok(job)
+    }
+
+    pub fn finish(&self, id: &str, outcome: Result<String, String>) -> anyhow::Result<Option<Job>> {
+        let (status, result, error) = match outcome {
+            Ok(result) => (JobStatus::Succeeded, Some(result), None),
+            Err(error) => (JobStatus::Failed, None, Some(error)),
+        };
+
+        self.conn().execute(
+            "UPDATE jobs SET status = ?1, result = ?2, error = ?3, updated_at = ?4 WHERE id = ?5",
+            params![status.to_string(), result, error, now(), id],
+        )?;
+
+        self.get(id)
+    }
+
+    pub fn record_usage(&self, id: &str, tokens: i64, cost: f64) -> anyhow::Result<()> {
+        self.conn().execute(
+            "UPDATE jobs SET tokens = ?1, cost = ?2 WHERE id = ?3",
+            params![tokens, cost, id],
+        )?;
+
+        Ok(())
+    }
+
+    // What the jobs of a user submitted since `since` used.
+    pub fn usage(&self, user: &str, since: i64) -> anyhow::Result<(i64, f64)> {
+        let usage = self.conn().query_row(
+            "SELECT COALESCE(SUM(tokens), 0), COALESCE(SUM(cost), 0) FROM jobs WHERE user = ?1 AND created_at >= ?2",
+            params![user, since],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        Ok(usage)
+    }
+
+    fn requeue_running(&self) -> anyhow::Result<usize> {
+        let updated = self.conn().execute(
+            "UPDATE jobs SET status = 'queued', updated_at = ?1 WHERE status = 'running'",
+            params![now()],
+        )?;
+
+        Ok(updated)
+    }
+}
+
+#[derive(Debug)]
+pub struct JobQueue {
+    pub(super) store: JobStore,
+    notify: Notify,
+    webhooks: WebhookNotifier,
+}
+
+impl JobQueue {
+    pub fn new(store: JobStore, webhooks: WebhookNotifier) -> Self {
+        Self {
+            store,
+            notify: Notify::new(),
+            webhooks,
+        }
+    }
+
+    pub fn spawn_workers(self: &Arc<Self>, workers: usize) {
+        for worker in 0..workers.max(1) {
+            let queue = self.clone();
+            tokio::spawn(async move { queue.work(worker).await });
+        }
+    }
+
+    async fn work(&self, worker: usize) {
+        loop {
+            let job = match self.store.claim_next() {
+                Ok(Some(job)) => job,
+                Ok(None) => {
+                    self.notify.notified().await;
+                    continue;
+                }
+                Err(e) => {
+                    error!("[VOO] job worker {} failed to claim a job: {}", worker, e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            info!("[VOO] job worker {} running job {}", worker, job.id);
+            self.run(&job, None, None).await;
+        }
+    }
+
+    // Runs a claimed or started job and records its outcome. The events of
+    // the agent go to `events` when given, ending with TurnComplete or Error.
+    // With `approvals`, a job that wasn't approved asks for its tool calls
+    // there instead of declining them.
+    pub(super) async fn run(
+        &self,
+        job: &Job,
+        events: Option<broadcast::Sender<AgentEvent>>,
+        approvals: Option<Approvals>,
+    ) {
+        policy::audit_as(
+            job.user.as_deref(),
+            &job.id,
+            &SessionEvent::UserMessage {
+                text: job.prompt.clone(),
+            },
+        );
+
+        let ended = Arc::new(AtomicBool::new(false));
+        let provider = job.provider.clone().unwrap_or_else(providers::selected);
+        let approvals = approvals.filter(|_| !auto_approve() && !job.approved);
+        let outcome = match build_agent_with(
+            &provider,
+            auto_approve() || job.approved || approvals.is_some(),
+        )
+        .await
+        {
+            Ok(agent) => {
+                if let Some(events) = &events {
+                    let events = events.clone();
+                    let ended = ended.clone();
+                    agent.observe(move |event: &AgentEvent| {
+                        if matches!(
+                            event,
+                            AgentEvent::TurnComplete { .. } | AgentEvent::Error { .. }
+                        ) {
+                            ended.store(true, Ordering::SeqCst);
+                        }
+                        _ = events.send(event.clone());
+                    });
+                }
+                let client = agent.client().clone();
+                let outcome = run_job(agent, job, approvals)
+                    .await
+                    .map_err(|e| e.to_string());
+                if let Some(usage) = client.usage() {
+                    self.record_usage(job, &provider, usage.total_tokens);
+                }
+                client.close().await;
+                outcome
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        // Failures before the turn started, e.g. a session locked by a chat.
+        if let (Some(events), Err(message)) = (&events, &outcome)
+            && !ended.load(Ordering::SeqCst)
+        {
+            _ = events.send(AgentEvent::Error {
+                message: message.clone(),
+            });
+        }
+        policy::audit_as(
+            job.user.as_deref(),
+            &job.id,
+            &match &outcome {
+                Ok(answer) => SessionEvent::ModelResponse {
+                    content: Content::new(vec![Part::new(answer)], "model"),
+                },
+                Err(e) => SessionEvent::Error { message: e.clone() },
+            },
+        );
+
+        match self.store.finish(&job.id, outcome) {
+            Ok(Some(job)) => self.send_callback(&job).await,
+            Ok(None) => {}
+            Err(e) => error!("[VOO] failed to record result of job {}: {}", job.id, e),
+        }
+    }
+
+    fn record_usage(&self, job: &Job, provider: &str, tokens: u64) {
+        let cost = match cost::job_cost(provider, tokens) {
+            Ok(cost) => cost.unwrap_or_default(),
+            Err(e) => {
+                error!("[VOO] failed to price job {}: {}", job.id, e);
+                0.0
+            }
+        };
+
+        if let Err(e) = self.store.record_usage(&job.id, tokens as i64, cost) {
+            error!("[VOO] failed to record usage of job {}: {}", job.id, e);
+        }
+    }
+
+    async fn send_callback(&self, job: &Job) {
+        let outcome = match (&job.result, &job.error) {
+            (_, Some(error)) => Err(error.as_str()),
+            (result, None) => Ok(result.as_deref().unwrap_or_default()),
+        };
+        let usage = RunUsage {
+            tokens: job.tokens,
+            cost: job.cost,
+        };
+        let event = RunCompleted::new(
+            &job.id,
+            job.session.as_deref(),
+            outcome,
+            usage,
+            job.updated_at,
+        );
+
+        self.webhooks
+            .notify(&event, job.callback_url.as_deref())
+            .await;
+    }
+}
+
+// A job in a session holds the lock of the session while it runs, so it fails
+// instead of interleaving with a `voo --session` chat that has it open.
+async fn run_job(agent: Agent, job: &Job, approvals: Option<Approvals>) -> anyhow::Result<String> {
+    let Some(name) = &job.session else {
+        return answer(&agent, &job.prompt, approvals).await;
+    };
+
+    let conversation = SqliteSessionStore::open(&sessions_db())
+        .and_then(|store| store.session(name))
+        .map_err(|e| anyhow::anyhow!("Error opening session {}: {}", name, e))?;
+    let agent = agent.with_store(conversation);
+    let _lock = agent
+        .lock_conversation("voo serve")
+        .map_err(|e| anyhow::anyhow!("Error opening session {}: {}", name, e))?;
+    let recorded = agent
+        .stored_prompt_version()
+        .map_err(|e| anyhow::anyhow!("Error resuming session {}: {}", name, e))?;
+    let restored = agent
+        .resume()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error resuming session {}: {}", name, e))?;
+    if restored > 0
+        && let Some(warning) = sessions::stale_prompt(recorded)
+    {
+        warn!("[VOO] session {}: {}", name, warning);
+    }
+
+    agent
+        .remember(&Content::new(vec![Part::new(&job.prompt)], "user"))
+        .map_err(|e| anyhow::anyhow!("Error storing session {}: {}", name, e))?;
+    let answer = answer(&agent, &job.prompt, approvals).await?;
+    agent
+        .remember(&Content::new(vec![Part::new(&answer)], "model"))
+        .map_err(|e| anyhow::anyhow!("Error storing session {}: {}", name, e))?;
+
+    Ok(answer)
+}
+
+async fn answer(
+    agent: &Agent,
+    prompt: &str,
+    approvals: Option<Approvals>,
+) -> anyhow::Result<String> {
+    let Some(approvals) = approvals else {
+        return run_prompt(agent, prompt).await;
+    };
+
+    agent
+        .run_turn_with(prompt, &ApprovalRunner { approvals })
+        .await
+        .map(|turn| turn.answer)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateJobRequest {
+    pub(super) prompt: String,
+    pub(super) session: Option<String>,
+    pub(super) callback_url: Option<String>,
+    pub(super) provider: Option<String>,
+    #[serde(default)]
+    pub(super) approve: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobError {
+    pub(super) error: String,
+}
+
+pub(super) type JobResponse<T> = Result<(StatusCode, Json<T>), (StatusCode, Json<JobError>)>;
+
+pub(super) fn job_error(status: StatusCode, error: impl Display) -> (StatusCode, Json<JobError>) {
+    (
+        status,
+        Json(JobError {
+            error: error.to_string(),
+        }),
+    )
+}
+
+pub(super) fn queue(state: &AppState) -> Result<&Arc<JobQueue>, (StatusCode, Json<JobError>)> {
+    state.jobs.as_ref().ok_or_else(|| {
+        job_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "job queue is not available, check the server configuration",
+        )
+    })
+}
+
+pub async fn create_job(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(request): Json<CreateJobRequest>,
+) -> JobResponse<Job> {
+    let queue = queue(&state)?;
+
+    if request.prompt.trim().is_empty() {
+        return Err(job_error(
+            StatusCode::BAD_REQUEST,
+            "prompt must not be empty",
+        ));
+    }
+    if let Some(url) = &request.callback_url
+        && let Err(e) = webhooks::check_callback(url)
+    {
+        return Err(job_error(StatusCode::BAD_REQUEST, e));
+    }
+    if request.approve {
+        caller.authorize(Permission::ApproveTools, "approve tool calls")?;
+    }
+    if let Some(provider) = &request.provider {
+        caller.authorize(Permission::ChangeModel, "change the model")?;
+        if providers::registry().get(provider).is_none() {
+            return Err(job_error(
+                StatusCode::BAD_REQUEST,
+                format!("unknown provider: {}", provider),
+            ));
+        }
+    }
+    quotas::check(&state, queue, &caller)?;
+    if let Some(session) = &request.session {
+        let owner = queue
+            .store
+            .session_owner(session)
+            .map_err(|e| job_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        if let Some(owner) = owner.filter(|owner| !caller.owns(owner.as_deref())) {
+            caller.authorize(
+                Permission::OtherUsers,
+                &format!(
+                    "continue session {} of {}",
+                    session,
+                    owner.as_deref().unwrap_or("anonymous")
+                ),
+            )?;
+        }
+    }
+
+    let job = queue
+        .store
+        .enqueue(request, caller.user)
+        .map_err(|e| job_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    queue.notify.notify_one();
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    caller: Caller,
+    UrlPath(id): UrlPath<String>,
+) -> JobResponse<Job> {
+    let queue = queue(&state)?;
+
+    match queue.store.get(&id) {
+        Ok(Some(job)) => {
+            if !caller.owns(job.user.as_deref()) {
+                caller.authorize(Permission::OtherUsers, &format!("read job {}", job.id))?;
+            }
+            Ok((StatusCode::OK, Json(job)))
+        }
+        Ok(None) => Err(job_error(
+            StatusCode::NOT_FOUND,
+            format!("job {} not found", id),
+        )),
+        Err(e) => Err(job_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+// Admins see the jobs of every user, the others only their own.
+pub async fn list_jobs(State(state): State<AppState>, caller: Caller) -> JobResponse<Vec<Job>> {
+    let queue = queue(&state)?;
+
+    let jobs = match caller.allows(Permission::OtherUsers) {
+        true => queue.store.list(50),
+        false => queue.store.list_for(caller.user.as_deref(), 50),
+    };
+    jobs.map(|jobs| (StatusCode::OK, Json(jobs)))
+        .map_err(|e| job_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim_jobs_in_order() {
+        let dir = std::env::temp_dir().join(format!("voo-jobs-{}", uuid::Uuid::new_v4()));
+        let store = JobStore::open(&dir.join("jobs.db")).unwrap();
+
+        let first = store.enqueue(request("first", None), None).unwrap();
+        let second = store
+            .enqueue(request("second", Some("docs")), Some("ana".to_string()))
+            .unwrap();
+        assert_eq!(
+            store.session_owner("docs").unwrap(),
+            Some(Some("ana".to_string()))
+        );
+        assert_eq!(store.session_owner("notes").unwrap(), None);
+        assert_eq!(store.list_for(Some("ana"), 10).unwrap().len(), 1);
+        assert_eq!(store.list_for(None, 10).unwrap()[0].id, first.id);
+
+        store.record_usage(&second.id, 1200, 0.5).unwrap();
+        assert_eq!(store.usage("ana", 0).unwrap(), (1200, 0.5));
+        assert_eq!(store.usage("ana", now() + 60).unwrap(), (0, 0.0));
+        assert_eq!(store.usage("ben", 0).unwrap(), (0, 0.0));
+
+        let claimed = store.claim_next().unwrap().unwrap();
+        assert_eq!(claimed.id, first.id);
+        assert_eq!(claimed.status, JobStatus::Running);
+
+        let finished = store
+            .finish(&first.id, Ok("done".to_string()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(finished.status, JobStatus::Succeeded);
+        assert_eq!(finished.result.as_deref(), Some("done"));
+
+        let claimed = store.claim_next().unwrap().unwrap();
+        assert_eq!(claimed.id, second.id);
+        assert_eq!(claimed.session.as_deref(), Some("docs"));
+        assert!(store.claim_next().unwrap().is_none());
+
+        drop(store);
+        let reopened = JobStore::open(&dir.join("jobs.db")).unwrap();
+        assert_eq!(reopened.claim_next().unwrap().unwrap().id, second.id);
+
+        let started = reopened.start(request("now", Some("docs")), None).unwrap();
+        assert_eq!(started.status, JobStatus::Running);
+        assert!(reopened.claim_next().unwrap().is_none());
+
+        _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn request(prompt: &str, session: Option<&str>) -> CreateJobRequest {
+        CreateJobRequest {
+            prompt: prompt.to_string(),
+            session: session.map(str::to_string),
+            callback_url: None,
+            provider: None,
+            approve: false,
+        }
+    }
+}
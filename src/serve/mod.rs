@@ -0,0 +1,99 @@
+mod approvals;
+mod auth;
+mod health;
+mod jobs;
+mod quotas;
+mod sessions;
+mod ws;
+
+use std::sync::Arc;
+
+pub use auth::Role;
+use axum::{
+    Router,
+    routing::{get, post},
+};
+use domain::models::agent::Agent;
+use health::ReachabilityProbe;
+use jobs::{JobQueue, JobStore};
+use models::tools::write_file::disable_prompts;
+use sessions::LiveSessions;
+use tracing::{error, info};
+
+use crate::{
+    build_agent,
+    config::{self, ServeConfig},
+    paths::data_dir,
+    webhooks::WebhookNotifier,
+};
+
+#[derive(Debug, Clone)]
+pub struct AppState {
+    agent: Option<Arc<Agent>>,
+    jobs: Option<Arc<JobQueue>>,
+    sessions: Arc<LiveSessions>,
+    config_error: Option<String>,
+    serve: Arc<ServeConfig>,
+    probe: Arc<ReachabilityProbe>,
+}
+
+pub async fn run(addr: &str, workers: usize) -> anyhow::Result<()> {
+    // Tool calls of jobs are approved through the API or not at all, never on
+    // the server's terminal.
+    disable_prompts();
+    let serve = Arc::new(config::get().serve.clone());
+    let state = match build_agent().await {
+        Ok(agent) => {
+            let store = JobStore::open(&data_dir().join("jobs.db"))?;
+            let jobs = Arc::new(JobQueue::new(store, WebhookNotifier::from_env()));
+            jobs.spawn_workers(workers);
+
+            AppState {
+                agent: Some(Arc::new(agent)),
+                jobs: Some(jobs),
+                sessions: Arc::default(),
+                config_error: None,
+                serve,
+                probe: Arc::default(),
+            }
+        }
+        Err(e) => {
+            error!("[VOO] {}, serving as not ready", e);
+            AppState {
+                agent: None,
+                jobs: None,
+                sessions: Arc::default(),
+                config_error: Some(e.to_string()),
+                serve,
+                probe: Arc::default(),
+            }
+        }
+    };
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error binding to {}: {}", addr, e))?;
+
+    info!("[VOO] serving on http://{}", addr);
+    if state.serve.users.is_empty() {
+        info!("[VOO] no users are configured, the API is open to anyone who can reach it");
+    }
+
+    axum::serve(listener, router(state))
+        .await
+        .map_err(|e| anyhow::anyhow!("Error serving: {}", e))
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/jobs", post(jobs::create_job).get(jobs::list_jobs))
+        .route("/jobs/{id}", get(jobs::get_job))
+        .route("/sessions", post(sessions::create_session))
+        .route("/sessions/{id}/messages", post(sessions::send_message))
+        .route("/sessions/{id}/events", get(sessions::events))
+        .route("/usage", get(quotas::usage))
+        .route("/ws", get(ws::chat))
+        .with_state(state)
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+
+use crate::{
+    config::{Quota, ServeConfig},
+    policy,
+};
+
+use super::{
+    AppState,
+    auth::{Caller, Permission},
+    jobs::{JobError, JobQueue, JobResponse, job_error, queue},
+};
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Consumption {
+    tokens: i64,
+    cost: f64,
+}
+
+impl Consumption {
+    fn add(&mut self, other: &Consumption) {
+        self.tokens += other.tokens;
+        self.cost += other.cost;
+    }
+
+    // Why the quota allows no further job, if it doesn't.
+    fn exceeds(&self, quota: &Quota) -> Option<String> {
+        if let Some(limit) = quota.daily_tokens
+            && self.tokens >= limit as i64
+        {
+            return Some(format!("{} of {} tokens", self.tokens, limit));
+        }
+        if let Some(limit) = quota.daily_cost
+            && self.cost >= limit
+        {
+            return Some(format!("${:.2} of ${:.2}", self.cost, limit));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    since: i64,
+    resets_at: i64,
+    users: BTreeMap<String, Usage>,
+    teams: BTreeMap<String, Usage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    #[serde(flatten)]
+    used: Consumption,
+    quota: Quota,
+}
+
+// Refuses a job with 429 once the caller or their team used up today's quota.
+// Jobs already queued still run, so a quota can be overrun by those.
+pub fn check(
+    state: &AppState,
+    queue: &JobQueue,
+    caller: &Caller,
+) -> Result<(), (StatusCode, Json<JobError>)> {
+    let Some(name) = &caller.user else {
+        return Ok(());
+    };
+    let Some(user) = state.serve.users.iter().find(|user| &user.name == name) else {
+        return Ok(());
+    };
+    let since = today();
+
+    let used = consumption(queue, &[name.as_str()], since)?;
+    let mut exceeded = used
+        .exceeds(&user.quota)
+        .map(|usage| format!("{} used {} today", name, usage));
+    if exceeded.is_none()
+        && let Some(team) = &user.team
+        && let Some(quota) = state.serve.teams.get(team)
+    {
+        let used = consumption(queue, &members(&state.serve, team), since)?;
+        exceeded = used
+            .exceeds(quota)
+            .map(|usage| format!("team {} used {} today", team, usage));
+    }
+
+    let Some(exceeded) = exceeded else {
+        return Ok(());
+    };
+    policy::audit_access(
+        name,
+        &caller.role.to_string(),
+        "submit a job over the daily quota",
+        false,
+    );
+    Err(job_error(
+        StatusCode::TOO_MANY_REQUESTS,
+        format!(
+            "daily quota exceeded: {}, it resets in {}h",
+            exceeded,
+            (since + DAY - now() + 60 * 60 - 1) / (60 * 60)
+        ),
+    ))
+}
+
+// What every user and team used today, next to their quotas.
+pub async fn usage(State(state): State<AppState>, caller: Caller) -> JobResponse<UsageReport> {
+    caller.authorize(Permission::InspectUsage, "inspect usage")?;
+    let queue = queue(&state)?;
+    let since = today();
+
+    let mut users = BTreeMap::new();
+    for user in &state.serve.users {
+        let used = consumption(queue, &[user.name.as_str()], since)?;
+        users.insert(
+            user.name.clone(),
+            Usage {
+                used,
+                quota: user.quota.clone(),
+            },
+        );
+    }
+
+    let mut teams = BTreeMap::new();
+    for (team, quota) in &state.serve.teams {
+        let mut used = Consumption::default();
+        for member in members(&state.serve, team) {
+            if let Some(usage) = users.get(member) {
+                used.add(&usage.used);
+            }
+        }
+        teams.insert(
+            team.clone(),
+            Usage {
+                used,
+                quota: quota.clone(),
+            },
+        );
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(UsageReport {
+            since,
+            resets_at: since + DAY,
+            users,
+            teams,
+        }),
+    ))
+}
+
+fn consumption(
+    queue: &JobQueue,
+    users: &[&str],
+    since: i64,
+) -> Result<Consumption, (StatusCode, Json<JobError>)> {
+    let mut total = Consumption::default();
+    for user in users {
+        let (tokens, cost) = queue
+            .store
+            .usage(user, since)
+            .map_err(|e| job_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        total.add(&Consumption { tokens, cost });
+    }
+
+    Ok(total)
+}
+
+fn members<'a>(serve: &'a ServeConfig, team: &str) -> Vec<&'a str> {
+    serve
+        .users
+        .iter()
+        .filter(|user| user.team.as_deref() == Some(team))
+        .map(|user| user.name.as_str())
+        .collect()
+}
+
+// Midnight UTC, when the quotas reset.
+fn today() -> i64 {
+    let now = now();
+    now - now.rem_euclid(DAY)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeds() {
+        let quota = Quota {
+            daily_tokens: Some(1000),
+            daily_cost: Some(0.5),
+        };
+
+        let used = Consumption {
+            tokens: 999,
+            cost: 0.25,
+        };
+        assert_eq!(used.exceeds(&quota), None);
+        assert_eq!(used.exceeds(&Quota::default()), None);
+
+        let used = Consumption {
+            tokens: 1000,
+            cost: 0.25,
+        };
+        assert_eq!(used.exceeds(&quota).as_deref(), Some("1000 of 1000 tokens"));
+
+        let used = Consumption {
+            tokens: 10,
+            cost: 0.75,
+        };
+        assert_eq!(used.exceeds(&quota).as_deref(), Some("$0.75 of $0.50"));
+        assert_eq!(today() % DAY, 0);
+    }
+}
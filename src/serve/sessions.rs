@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use axum::{
+    Json,
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use domain::{models::events::AgentEvent, storage::sqlite::SqliteSessionStore};
+use futures::{Stream, stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::{paths::sessions_db, providers};
+
+use super::{
+    AppState,
+    auth::{Caller, Permission},
+    jobs::{CreateJobRequest, Job, JobError, JobQueue, JobResponse, job_error, queue},
+    quotas,
+};
+
+// Events a slow stream may fall behind by before it misses some.
+const EVENT_BUFFER: usize = 256;
+
+// A conversation driven over HTTP. Its history is the named session of the
+// same name, so `voo --session <id>` can continue it, and every message runs
+// as a job.
+#[derive(Debug)]
+pub struct LiveSession {
+    id: String,
+    user: Option<String>,
+    provider: Option<String>,
+    approved: bool,
+    events: broadcast::Sender<AgentEvent>,
+    busy: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+pub struct LiveSessions {
+    sessions: Mutex<HashMap<String, Arc<LiveSession>>>,
+}
+
+impl LiveSessions {
+    fn get(&self, id: &str) -> Option<Arc<LiveSession>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    // Continues the named session, a new one is created without.
+    name: Option<String>,
+    provider: Option<String>,
+    #[serde(default)]
+    approve: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    id: String,
+    user: Option<String>,
+    provider: Option<String>,
+    approved: bool,
+}
+
+impl SessionInfo {
+    fn new(session: &LiveSession) -> Self {
+        Self {
+            id: session.id.clone(),
+            user: session.user.clone(),
+            provider: session.provider.clone(),
+            approved: session.approved,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageRequest {
+    text: String,
+}
+
+pub async fn create_session(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(request): Json<CreateSessionRequest>,
+) -> JobResponse<SessionInfo> {
+    let queue = queue(&state)?;
+
+    if request.approve {
+        caller.authorize(Permission::ApproveTools, "approve tool calls")?;
+    }
+    if let Some(provider) = &request.provider {
+        caller.authorize(Permission::ChangeModel, "change the model")?;
+        if providers::registry().get(provider).is_none() {
+            return Err(job_error(
+                StatusCode::BAD_REQUEST,
+                format!("unknown provider: {}", provider),
+            ));
+        }
+    }
+
+    let id = request
+        .name
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if let Some(session) = state.sessions.get(&id) {
+        authorize(&caller, &session)?;
+        return Ok((StatusCode::OK, Json(SessionInfo::new(&session))));
+    }
+    continue_session(queue, &caller, &id)?;
+
+    let session = Arc::new(LiveSession {
+        id: id.clone(),
+        user: caller.user,
+        provider: request.provider,
+        approved: request.approve,
+        events: broadcast::channel(EVENT_BUFFER).0,
+        busy: AtomicBool::new(false),
+    });
+    let info = SessionInfo::new(&session);
+    state
+        .sessions
+        .sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(id, session);
+
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+// Starts a turn and returns its job right away, the answer arrives on the
+// event stream and in the job.
+pub async fn send_message(
+    State(state): State<AppState>,
+    caller: Caller,
+    UrlPath(id): UrlPath<String>,
+    Json(request): Json<MessageRequest>,
+) -> JobResponse<Job> {
+    let queue = queue(&state)?.clone();
+    let session = session(&state, &caller, &id)?;
+
+    if request.text.trim().is_empty() {
+        return Err(job_error(StatusCode::BAD_REQUEST, "text must not be empty"));
+    }
+    quotas::check(&state, &queue, &caller)?;
+    if session.busy.swap(true, Ordering::SeqCst) {
+        return Err(job_error(
+            StatusCode::CONFLICT,
+            format!("session {} is still answering the previous message", id),
+        ));
+    }
+
+    let request = CreateJobRequest {
+        prompt: request.text,
+        session: Some(session.id.clone()),
+        callback_url: None,
+        provider: session.provider.clone(),
+        approve: session.approved,
+    };
+    let job = match queue.store.start(request, caller.user) {
+        Ok(job) => job,
+        Err(e) => {
+            session.busy.store(false, Ordering::SeqCst);
+            return Err(job_error(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+
+    let started = job.clone();
+    tokio::spawn(async move {
+        queue
+            .run(&started, Some(session.events.clone()), None)
+            .await;
+        session.busy.store(false, Ordering::SeqCst);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+// Server-sent events of the session's turns from now on, one per agent event
+// and named after its type.
+pub async fn events(
+    State(state): State<AppState>,
+    caller: Caller,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<JobError>)> {
+    let session = session(&state, &caller, &id)?;
+
+    let events = stream::unfold(session.events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((Ok(sse_event(&event)), receiver)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+// Checks the name of a named session and that the caller may continue it,
+// sessions belong to the user of their first job.
+pub(super) fn continue_session(
+    queue: &JobQueue,
+    caller: &Caller,
+    id: &str,
+) -> Result<(), (StatusCode, Json<JobError>)> {
+    SqliteSessionStore::open(&sessions_db())
+        .and_then(|store| store.session(id))
+        .map_err(|e| job_error(StatusCode::BAD_REQUEST, e))?;
+
+    let owner = queue
+        .store
+        .session_owner(id)
+        .map_err(|e| job_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    match owner.filter(|owner| !caller.owns(owner.as_deref())) {
+        Some(owner) => caller.authorize(
+            Permission::OtherUsers,
+            &format!(
+                "continue session {} of {}",
+                id,
+                owner.as_deref().unwrap_or("anonymous")
+            ),
+        ),
+        None => Ok(()),
+    }
+}
+
+fn session(
+    state: &AppState,
+    caller: &Caller,
+    id: &str,
+) -> Result<Arc<LiveSession>, (StatusCode, Json<JobError>)> {
+    let session = state.sessions.get(id).ok_or_else(|| {
+        job_error(
+            StatusCode::NOT_FOUND,
+            format!("session {} not found, open it with POST /sessions", id),
+        )
+    })?;
+    authorize(caller, &session)?;
+
+    Ok(session)
+}
+
+fn authorize(caller: &Caller, session: &LiveSession) -> Result<(), (StatusCode, Json<JobError>)> {
+    if caller.owns(session.user.as_deref()) {
+        return Ok(());
+    }
+
+    caller.authorize(
+        Permission::OtherUsers,
+        &format!(
+            "use session {} of {}",
+            session.id,
+            session.user.as_deref().unwrap_or("anonymous")
+        ),
+    )
+}
+
+fn sse_event(event: &AgentEvent) -> Event {
+    let data = serde_json::to_value(event).unwrap_or_default();
+
+    Event::default()
+        .event(data["type"].as_str().unwrap_or("event"))
+        .data(data.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::tools::ToolOutput;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = AgentEvent::ToolCallFinished {
+            name: "read_file".to_string(),
+            output: ToolOutput::text("fn main() {}"),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "type": "tool_call_finished",
+                "name": "read_file",
+                "output": { "type": "text", "value": "fn main() {}" }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(AgentEvent::TurnComplete {
+                answer: "done".to_string()
+            })
+            .unwrap(),
+            json!({ "type": "turn_complete", "answer": "done" })
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use axum::{
+    Json,
+    extract::{
+        Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::StatusCode,
+    response::Response,
+};
+use domain::models::events::AgentEvent;
+use futures::{SinkExt, StreamExt, stream::SplitSink};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::providers;
+
+use super::{
+    AppState,
+    approvals::{ApprovalRequest, Approvals},
+    auth::{Caller, Permission},
+    jobs::{CreateJobRequest, Job, JobError, JobQueue, job_error, queue},
+    quotas,
+    sessions::continue_session,
+};
+
+// Events a slow client may fall behind by before it misses some.
+const EVENT_BUFFER: usize = 256;
+
+#[derive(Debug, Deserialize)]
+pub struct ChatParams {
+    // Continues the named session, a new one is created without.
+    session: Option<String>,
+    provider: Option<String>,
+    #[serde(default)]
+    approve: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    UserMessage { text: String },
+    ApprovalResponse { id: String, approved: bool },
+}
+
+// The agent events are sent as they are, next to the messages of the
+// connection itself.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Ready {
+        session: String,
+        approvals: bool,
+    },
+    JobStarted {
+        job: Job,
+    },
+    ApprovalRequest(ApprovalRequest),
+    // A client message that was not acted on.
+    Rejected {
+        message: String,
+    },
+    #[serde(untagged)]
+    Event(AgentEvent),
+}
+
+struct Connection {
+    state: AppState,
+    queue: Arc<JobQueue>,
+    caller: Caller,
+    params: ChatParams,
+    session: String,
+}
+
+// A chat over a WebSocket: the client sends messages and answers approval
+// requests, the server sends the agent events of every turn. Each message
+// runs as a job of the named session.
+pub async fn chat(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(params): Query<ChatParams>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, Json<JobError>)> {
+    let queue = queue(&state)?.clone();
+
+    if params.approve {
+        caller.authorize(Permission::ApproveTools, "approve tool calls")?;
+    }
+    if let Some(provider) = &params.provider {
+        caller.authorize(Permission::ChangeModel, "change the model")?;
+        if providers::registry().get(provider).is_none() {
+            return Err(job_error(
+                StatusCode::BAD_REQUEST,
+                format!("unknown provider: {}", provider),
+            ));
+        }
+    }
+
+    let session = params
+        .session
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    continue_session(&queue, &caller, &session)?;
+
+    let connection = Connection {
+        state,
+        queue,
+        caller,
+        params,
+        session,
+    };
+    Ok(upgrade.on_upgrade(move |socket| connection.run(socket)))
+}
+
+impl Connection {
+    async fn run(self, socket: WebSocket) {
+        let (sink, mut stream) = socket.split();
+        let (outgoing, outbox) = mpsc::unbounded_channel();
+        let (requests, approval_requests) = mpsc::unbounded_channel();
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let approvals = Approvals::new(requests);
+        // Only those who may approve tool calls are asked, the calls of the
+        // others are declined as in their jobs.
+        let asks = !self.params.approve && self.caller.allows(Permission::ApproveTools);
+
+        let writer = tokio::spawn(write(sink, outbox, approval_requests, events.subscribe()));
+        _ = outgoing.send(ServerMessage::Ready {
+            session: self.session.clone(),
+            approvals: asks,
+        });
+
+        let busy = Arc::new(AtomicBool::new(false));
+        while let Some(Ok(message)) = stream.next().await {
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(ClientMessage::UserMessage { text }) => {
+                    let approvals = asks.then(|| approvals.clone());
+                    self.start(text, &busy, &events, approvals)
+                        .map(|job| ServerMessage::JobStarted { job })
+                        .unwrap_or_else(|message| ServerMessage::Rejected { message })
+                }
+                Ok(ClientMessage::ApprovalResponse { id, approved }) => {
+                    if approvals.answer(&id, approved) {
+                        continue;
+                    }
+                    ServerMessage::Rejected {
+                        message: format!("no tool call is waiting for approval {}", id),
+                    }
+                }
+                Err(e) => ServerMessage::Rejected {
+                    message: format!("invalid message: {}", e),
+                },
+            };
+            _ = outgoing.send(reply);
+        }
+
+        // A running turn finishes as a job, without the calls waiting here.
+        approvals.decline_all();
+        writer.abort();
+    }
+
+    // Starts a turn as a job, or says why it didn't.
+    fn start(
+        &self,
+        text: String,
+        busy: &Arc<AtomicBool>,
+        events: &broadcast::Sender<AgentEvent>,
+        approvals: Option<Approvals>,
+    ) -> Result<Job, String> {
+        if text.trim().is_empty() {
+            return Err("text must not be empty".to_string());
+        }
+        if let Err((_, Json(error))) = quotas::check(&self.state, &self.queue, &self.caller) {
+            return Err(error.error);
+        }
+        if busy.swap(true, Ordering::SeqCst) {
+            return Err("still answering the previous message".to_string());
+        }
+
+        let request = CreateJobRequest {
+            prompt: text,
+            session: Some(self.session.clone()),
+            callback_url: None,
+            provider: self.params.provider.clone(),
+            approve: self.params.approve,
+        };
+        let job = match self.queue.store.start(request, self.caller.user.clone()) {
+            Ok(job) => job,
+            Err(e) => {
+                busy.store(false, Ordering::SeqCst);
+                return Err(e.to_string());
+            }
+        };
+
+        let queue = self.queue.clone();
+        let busy = busy.clone();
+        let events = events.clone();
+        let started = job.clone();
+        tokio::spawn(async move {
+            queue.run(&started, Some(events), approvals).await;
+            busy.store(false, Ordering::SeqCst);
+        });
+
+        Ok(job)
+    }
+}
+
+async fn write(
+    mut sink: SplitSink<WebSocket, Message>,
+    mut outbox: mpsc::UnboundedReceiver<ServerMessage>,
+    mut approval_requests: mpsc::UnboundedReceiver<ApprovalRequest>,
+    mut events: broadcast::Receiver<AgentEvent>,
+) {
+    loop {
+        let message = tokio::select! {
+            Some(message) = outbox.recv() => message,
+            Some(request) = approval_requests.recv() => ServerMessage::ApprovalRequest(request),
+            event = events.recv() => match event {
+                Ok(event) => ServerMessage::Event(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+
+        let Ok(json) = serde_json::to_string(&message) else {
+            continue;
+        };
+        if sink.send(Message::Text(json.into())).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::models::tool_registry::DangerLevel;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let message = serde_json::from_value::<ClientMessage>(json!({
+            "type": "approval_response",
+            "id": "1",
+            "approved": true
+        }))
+        .unwrap();
+        assert!(matches!(
+            message,
+            ClientMessage::ApprovalResponse { id, approved: true } if id == "1"
+        ));
+        assert!(
+            serde_json::from_value::<ClientMessage>(json!({ "type": "user_message" })).is_err()
+        );
+
+        let request = ServerMessage::ApprovalRequest(ApprovalRequest {
+            id: "1".to_string(),
+            name: "run_command".to_string(),
+            args: json!({ "command": "ls" }),
+            danger: DangerLevel::Dangerous,
+        });
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "type": "approval_request",
+                "id": "1",
+                "name": "run_command",
+                "args": { "command": "ls" },
+                "danger": "dangerous"
+            })
+        );
+        let event = ServerMessage::Event(AgentEvent::TurnComplete {
+            answer: "done".to_string(),
+        });
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({ "type": "turn_complete", "answer": "done" })
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::Arc;
+
+use domain::models::{
+    agent::{Content, SystemPrompt},
+    transcript::{self, SessionEvent, TranscriptEntry},
+};
+use domain::storage::{Storage, files::FileStorage, sqlite::SqliteSessionStore};
+use models::prompt::SYSTEM_PROMPT_VERSION;
+
+use crate::{
+    build_agent, config,
+    handoff::portable_history,
+    paths::{sessions_db, sessions_dir, usage_path},
+    terminal::{prompt, styled_println},
+};
+
+const OUTPUT_PREVIEW: usize = 300;
+
+// VOO_STORAGE, or `storage` in the config file, picks where sessions are
+// recorded. The sqlite backend keeps them in the database of the named
+// sessions, which lists thousands of sessions without reading each.
+pub fn storage() -> anyhow::Result<Arc<dyn Storage>> {
+    match config::var("VOO_STORAGE").ok().as_deref() {
+        None | Some("files") => Ok(Arc::new(FileStorage::new(&sessions_dir(), &usage_path()))),
+        Some("sqlite") => Ok(Arc::new(named_store()?)),
+        Some(storage) => Err(anyhow::anyhow!(
+            "Unknown storage: {} (expected files or sqlite)",
+            storage
+        )),
+    }
+}
+
+pub fn list() -> anyhow::Result<()> {
+    let sessions = storage()?
+        .sessions()
+        .map_err(|e| anyhow::anyhow!("Error listing sessions: {}", e))?;
+
+    if sessions.is_empty() {
+        println!("No recorded sessions");
+    }
+
+    for session in sessions {
+        let stale = match session.prompt_version {
+            Some(version) if version == SYSTEM_PROMPT_VERSION => "",
+            Some(_) => "  (stale prompt)",
+            None => "  (unknown prompt)",
+        };
+
+        println!(
+            "{}  {} turns  {}{}",
+            session.id,
+            session.turns,
+            preview(session.first_message.as_deref().unwrap_or_default(), 60),
+            stale
+        );
+    }
+
+    Ok(())
+}
+
+pub fn stats() -> anyhow::Result<()> {
+    let storage = storage()?;
+    let sessions = storage
+        .sessions()
+        .map_err(|e| anyhow::anyhow!("Error listing sessions: {}", e))?;
+    let usage = storage
+        .usage()
+        .map_err(|e| anyhow::anyhow!("Error reading token usage: {}", e))?;
+
+    println!(
+        "{} sessions, {} turns",
+        sessions.len(),
+        sessions.iter().map(|session| session.turns).sum::<usize>()
+    );
+    println!("{}", usage);
+
+    Ok(())
+}
+
+pub fn list_named() -> anyhow::Result<()> {
+    let sessions = named_store()?
+        .list()
+        .map_err(|e| anyhow::anyhow!("Error listing sessions: {}", e))?;
+
+    if sessions.is_empty() {
+        println!("No named sessions");
+    }
+
+    for session in sessions {
+        println!("{}  {} messages", session.name, session.messages);
+    }
+
+    Ok(())
+}
+
+pub fn delete_named(name: &str) -> anyhow::Result<()> {
+    let deleted = named_store()?
+        .delete(name)
+        .map_err(|e| anyhow::anyhow!("Error deleting session {}: {}", name, e))?;
+
+    match deleted {
+        true => println!("Deleted session {}", name),
+        false => println!("No session named {}", name),
+    }
+
+    Ok(())
+}
+
+fn named_store() -> anyhow::Result<SqliteSessionStore> {
+    SqliteSessionStore::open(&sessions_db())
+        .map_err(|e| anyhow::anyhow!("Error opening session store: {}", e))
+}
+
+pub async fn replay(id: &str, step: bool) -> anyhow::Result<()> {
+    let entries = storage()?
+        .load(id)
+        .map_err(|e| anyhow::anyhow!("Error loading session {}: {}", id, e))?;
+    let recorded_prompt = transcript::system_prompt(&entries).cloned();
+    let turns = split_turns(entries);
+
+    println!("Session {} ({} turns)", id, turns.len());
+    warn_if_stale(recorded_prompt.as_ref().map(|prompt| prompt.version));
+    println!();
+
+    let mut migrate = None;
+
+    for (index, turn) in turns.iter().enumerate() {
+        styled_println!("\x1b[2m--- turn {} ---\x1b[0m", index + 1);
+        for entry in turn {
+            render(&entry.event);
+        }
+        println!();
+
+        if !step {
+            continue;
+        }
+
+        loop {
+            match prompt("[enter] next, [r] re-run turn, [q] quit: ")?.as_str() {
+                "" => break,
+                "q" => return Ok(()),
+                "r" => {
+                    if migrate.is_none() {
+                        migrate = Some(should_migrate(recorded_prompt.as_ref())?);
+                    }
+                    let prompt = recorded_prompt.as_ref().filter(|_| migrate == Some(false));
+                    rerun(&turns[..index], turn, prompt).await?
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn split_turns(entries: Vec<TranscriptEntry>) -> Vec<Vec<TranscriptEntry>> {
+    let mut turns: Vec<Vec<TranscriptEntry>> = vec![];
+
+    for entry in entries {
+        if matches!(entry.event, SessionEvent::SessionStarted { .. }) {
+            continue;
+        }
+
+        let starts_turn = matches!(entry.event, SessionEvent::UserMessage { .. });
+        match turns.last_mut() {
+            Some(turn) if !starts_turn => turn.push(entry),
+            _ => turns.push(vec![entry]),
+        }
+    }
+
+    turns
+}
+
+pub fn warn_if_stale(recorded: Option<u32>) {
+    if let Some(warning) = stale_prompt(recorded) {
+        styled_println!("\x1b[33mwarning: {}\x1b[0m", warning);
+    }
+}
+
+// Why a session recorded with the system prompt of version `recorded` may
+// behave differently now, None when it was recorded with the current one.
+pub fn stale_prompt(recorded: Option<u32>) -> Option<String> {
+    match recorded {
+        Some(version) if version == SYSTEM_PROMPT_VERSION => None,
+        Some(version) => Some(format!(
+            "recorded with system prompt v{}, current is v{}",
+            version, SYSTEM_PROMPT_VERSION
+        )),
+        None => Some(format!(
+            "session has no recorded system prompt version, current is v{}",
+            SYSTEM_PROMPT_VERSION
+        )),
+    }
+}
+
+// Asks whether re-runs should use the current system prompt instead of the
+// one the session was recorded with. Sessions without a recorded prompt can
+// only run with the current one.
+fn should_migrate(recorded: Option<&SystemPrompt>) -> anyhow::Result<bool> {
+    let Some(recorded) = recorded else {
+        return Ok(true);
+    };
+    if recorded.version == SYSTEM_PROMPT_VERSION {
+        return Ok(false);
+    }
+
+    loop {
+        match prompt(&format!(
+            "[m] migrate to prompt v{}, [k] keep v{}: ",
+            SYSTEM_PROMPT_VERSION, recorded.version
+        ))?
+        .as_str()
+        {
+            "m" => return Ok(true),
+            "k" | "q" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+fn render(event: &SessionEvent) {
+    match event {
+        SessionEvent::SessionStarted { .. } => {}
+        SessionEvent::UserMessage { text } => styled_println!("\x1b[38;5;5mYOU:\x1b[0m {}", text),
+        SessionEvent::ModelResponse { content } => render_content(content),
+        SessionEvent::ToolCall { name, args } => {
+            styled_println!("\x1b[33m{}> {}\x1b[0m", name, args)
+        }
+        SessionEvent::ToolResult { name, output } => {
+            styled_println!(
+                "\x1b[2m{}< {}\x1b[0m",
+                name,
+                preview(output, OUTPUT_PREVIEW)
+            )
+        }
+        SessionEvent::Error { message } => styled_println!("\x1b[41mvoo>\x1b[0m {}", message),
+        SessionEvent::Handoff { provider } => {
+            styled_println!("\x1b[2m--- handed off to {} ---\x1b[0m", provider)
+        }
+        SessionEvent::Forked { parent, title } => match title {
+            Some(title) => styled_println!(
+                "\x1b[2m--- forked from {} as \"{}\" ---\x1b[0m",
+                parent,
+                title
+            ),
+            None => styled_println!("\x1b[2m--- forked from {} ---\x1b[0m", parent),
+        },
+    }
+}
+
+fn render_content(content: &Content) {
+    for part in &content.parts {
+        match &part.text {
+            Some(text) if part.is_thought() => {
+                styled_println!("\x1b[2mthinking> {}\x1b[0m", text.trim())
+            }
+            Some(text) if !text.is_empty() => styled_println!("\x1b[32mvoo>\x1b[0m {}", text),
+            _ => {}
+        }
+    }
+}
+
+async fn rerun(
+    history: &[Vec<TranscriptEntry>],
+    turn: &[TranscriptEntry],
+    system_prompt: Option<&SystemPrompt>,
+) -> anyhow::Result<()> {
+    let Some(SessionEvent::UserMessage { text: input }) = turn.first().map(|entry| &entry.event)
+    else {
+        styled_println!("\x1b[41mvoo>\x1b[0m this turn has no user message to re-run");
+        return Ok(());
+    };
+
+    let agent = build_agent().await?;
+
+    if let Some(system_prompt) = system_prompt {
+        agent
+            .client()
+            .set_system_prompt(system_prompt.clone())
+            .await
+            .map_err(|e| anyhow::anyhow!("Error restoring system prompt: {}", e))?;
+    }
+
+    let entries = history
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<TranscriptEntry>>();
+    for (role, text) in portable_history(&entries) {
+        _ = agent.client().add_system_prompt(&text, role).await;
+    }
+
+    styled_println!("\x1b[2m--- re-run against current provider ---\x1b[0m");
+    match agent.client().ask(input).await {
+        Ok(contents) => {
+            for content in &contents {
+                render_content(content);
+                for call in content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.function_call.as_ref())
+                {
+                    styled_println!("\x1b[33m{}> {} (not executed)\x1b[0m", call.name, call.args);
+                }
+            }
+        }
+        Err(e) => styled_println!("\x1b[41mvoo>\x1b[0m {}", e),
+    }
+    println!();
+
+    Ok(())
+}
+
+fn preview(text: &str, limit: usize) -> String {
+    let text = text.replace('\n', " ");
+    if text.chars().count() <= limit {
+        return text;
+    }
+
+    format!("{}...", text.chars().take(limit).collect::<String>())
+}
@@ -0,0 +1,293 @@
+use std::path::{Component, Path, PathBuf};
+
+use domain::models::jsonl;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, paths::data_dir, terminal::styled_println};
+
+const ARCHIVE_VERSION: u32 = 1;
+const CONFIG_ENTRY: &str = "config.toml";
+const DATA_PREFIX: &str = "data/";
+// Crash reports belong to the machine they happened on.
+const SKIPPED_DIRS: &[&str] = &["crashes"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    voo_state: u32,
+}
+
+// One file of the archive, its path relative to the data directory under
+// `data/`, or the user config.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    path: String,
+    data: String,
+}
+
+pub fn export(archive: &Path) -> anyhow::Result<()> {
+    let count = export_to(config::user_config_path().as_deref(), &data_dir(), archive)?;
+    styled_println!(
+        "\x1b[32mvoo>\x1b[0m exported {} files to {}, api keys are left out",
+        count,
+        archive.display()
+    );
+
+    Ok(())
+}
+
+pub fn import(archive: &Path, force: bool) -> anyhow::Result<()> {
+    let imported = import_from(
+        archive,
+        config::user_config_path().as_deref(),
+        &data_dir(),
+        force,
+    )?;
+    styled_println!(
+        "\x1b[32mvoo>\x1b[0m imported {} files from {}",
+        imported.written,
+        archive.display()
+    );
+    if !imported.skipped.is_empty() {
+        styled_println!(
+            "\x1b[33mvoo>\x1b[0m kept {} existing files, use --force to replace them: {}",
+            imported.skipped.len(),
+            imported.skipped.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct Imported {
+    written: usize,
+    skipped: Vec<String>,
+}
+
+// The user config without its api keys, and every file of the data directory.
+fn export_to(config: Option<&Path>, data: &Path, archive: &Path) -> anyhow::Result<usize> {
+    let mut entries = vec![];
+
+    if let Some(config) = config
+        && let Some(content) = read_optional(config)?
+    {
+        entries.push(Entry {
+            path: CONFIG_ENTRY.to_string(),
+            data: hex::encode(without_secrets(config, &content)?),
+        });
+    }
+
+    for file in files(data)? {
+        let relative = file
+            .strip_prefix(data)
+            .map_err(|e| anyhow::anyhow!("Error exporting {}: {}", file.display(), e))?;
+        let content = std::fs::read(&file)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", file.display(), e))?;
+        entries.push(Entry {
+            path: format!(
+                "{}{}",
+                DATA_PREFIX,
+                relative.to_string_lossy().replace('\\', "/")
+            ),
+            data: hex::encode(content),
+        });
+    }
+
+    let mut lines = vec![serde_json::to_string(&Header {
+        voo_state: ARCHIVE_VERSION,
+    })?];
+    for entry in &entries {
+        lines.push(serde_json::to_string(entry)?);
+    }
+    std::fs::write(archive, lines.join("\n") + "\n")
+        .map_err(|e| anyhow::anyhow!("Error writing {}: {}", archive.display(), e))?;
+
+    Ok(entries.len())
+}
+
+fn import_from(
+    archive: &Path,
+    config: Option<&Path>,
+    data: &Path,
+    force: bool,
+) -> anyhow::Result<Imported> {
+    let content = std::fs::read(archive)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", archive.display(), e))?;
+    let (header, rest) = match content.iter().position(|byte| *byte == b'\n') {
+        Some(end) => (&content[..end], &content[end + 1..]),
+        None => (&content[..], &[][..]),
+    };
+    let header = serde_json::from_slice::<Header>(header)
+        .map_err(|_| anyhow::anyhow!("{} is not a voo state archive", archive.display()))?;
+    if header.voo_state != ARCHIVE_VERSION {
+        return Err(anyhow::anyhow!(
+            "{} has archive version {}, this voo reads version {}",
+            archive.display(),
+            header.voo_state,
+            ARCHIVE_VERSION
+        ));
+    }
+    let entries = jsonl::parse::<Entry>(rest)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", archive.display(), e))?
+        .records;
+
+    // Every path is checked before anything is written.
+    let mut targets = vec![];
+    for entry in entries {
+        let target = match entry.path.strip_prefix(DATA_PREFIX) {
+            Some(relative) if is_relative(relative) => data.join(relative),
+            None if entry.path == CONFIG_ENTRY => config
+                .ok_or_else(|| anyhow::anyhow!("No config directory to import the config to"))?
+                .to_path_buf(),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid path in {}: {}",
+                    archive.display(),
+                    entry.path
+                ));
+            }
+        };
+        let content = hex::decode(&entry.data)
+            .map_err(|e| anyhow::anyhow!("Invalid content of {}: {}", entry.path, e))?;
+        targets.push((entry.path, target, content));
+    }
+
+    let mut imported = Imported::default();
+    for (path, target, content) in targets {
+        if target.exists() && !force {
+            imported.skipped.push(path);
+            continue;
+        }
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| anyhow::anyhow!("Error creating {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&target, content)
+            .map_err(|e| anyhow::anyhow!("Error writing {}: {}", target.display(), e))?;
+        imported.written += 1;
+    }
+
+    Ok(imported)
+}
+
+fn read_optional(path: &Path) -> anyhow::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Error reading {}: {}", path.display(), e)),
+    }
+}
+
+// The config as written, not as loaded, so ${NAME} references are kept and
+// the values of environment variables don't end up in the archive.
+fn without_secrets(path: &Path, content: &str) -> anyhow::Result<String> {
+    let mut table = content
+        .parse::<toml::Table>()
+        .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", path.display(), e))?;
+    table.remove("api_keys");
+    let users = table
+        .get_mut("serve")
+        .and_then(|serve| serve.get_mut("users"))
+        .and_then(toml::Value::as_array_mut);
+    for user in users.into_iter().flatten() {
+        if let Some(user) = user.as_table_mut() {
+            user.remove("token");
+        }
+    }
+
+    Ok(toml::to_string(&table)?)
+}
+
+// The files below `dir`, leaving out lock files, which only mean something
+// to the processes holding them.
+fn files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(anyhow::anyhow!("Error reading {}: {}", dir.display(), e)),
+    };
+
+    let mut found = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                found.extend(files(&path)?);
+            }
+        } else if !name.ends_with(".lock") && !name.ends_with("-journal") {
+            found.push(path);
+        }
+    }
+    found.sort();
+
+    Ok(found)
+}
+
+fn is_relative(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_import() {
+        let dir = std::env::temp_dir().join(format!("voo-state-{}", uuid::Uuid::new_v4()));
+        let config = dir.join("config.toml");
+        let data = dir.join("data");
+        std::fs::create_dir_all(data.join("sessions")).unwrap();
+        std::fs::create_dir_all(data.join("crashes")).unwrap();
+        std::fs::write(
+            &config,
+            "provider = \"openai\"\n\n[api_keys]\nopenai = \"sk-live-0123456789\"\n",
+        )
+        .unwrap();
+        std::fs::write(data.join("sessions").join("1-abc.jsonl"), "{}\n").unwrap();
+        std::fs::write(data.join("sessions.db"), [0u8, 1, 2]).unwrap();
+        std::fs::write(data.join("crashes").join("crash-1.json"), "{}").unwrap();
+        std::fs::write(data.join("conversation.jsonl.lock"), "voo").unwrap();
+
+        let archive = dir.join("state.jsonl");
+        assert_eq!(export_to(Some(&config), &data, &archive).unwrap(), 3);
+        assert!(
+            !std::fs::read_to_string(&archive)
+                .unwrap()
+                .contains(&hex::encode("sk-live"))
+        );
+
+        let target = dir.join("target");
+        let target_config = target.join("config.toml");
+        let imported =
+            import_from(&archive, Some(&target_config), &target.join("data"), false).unwrap();
+        assert_eq!(imported.written, 3);
+        assert_eq!(
+            std::fs::read_to_string(&target_config).unwrap(),
+            "provider = \"openai\"\n"
+        );
+        assert_eq!(
+            std::fs::read(target.join("data").join("sessions.db")).unwrap(),
+            vec![0u8, 1, 2]
+        );
+        assert!(!target.join("data").join("crashes").exists());
+
+        let imported =
+            import_from(&archive, Some(&target_config), &target.join("data"), false).unwrap();
+        assert_eq!(imported.written, 0);
+        assert_eq!(imported.skipped.len(), 3);
+
+        std::fs::write(
+            &archive,
+            "{\"voo_state\":1}\n{\"path\":\"data/../escape\",\"data\":\"\"}\n",
+        )
+        .unwrap();
+        assert!(import_from(&archive, None, &target.join("data"), true).is_err());
+        assert!(!target.join("escape").exists());
+
+        _ = std::fs::remove_dir_all(dir);
+    }
+}
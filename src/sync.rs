@@ -0,0 +1,98 @@
+use std::{path::Path, process::Command};
+
+use crate::{
+    config::{self, Config, PROJECT_CONFIG, team_dir},
+    terminal::styled_println,
+};
+
+const DEFAULT_MESSAGE: &str = "Update the shared voo config";
+
+// Clones the team repository on first use, later fast-forwards the checkout.
+// The team's voo.toml applies from the next start of voo.
+pub fn pull() -> anyhow::Result<()> {
+    let dir = team_dir();
+
+    if dir.join(".git").exists() {
+        git(&dir, &["pull", "--ff-only"])?;
+    } else {
+        let repo = config::var("VOO_SYNC_REPO").map_err(|_| {
+            anyhow::anyhow!("VOO_SYNC_REPO or `repo` under [sync] must name the team repository")
+        })?;
+        if let Some(parent) = dir.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| anyhow::anyhow!("Error creating {}: {}", parent.display(), e))?;
+        }
+        // A repository starting with `-` would be read as an option.
+        git(
+            Path::new("."),
+            &["clone", "--", &repo, &dir.to_string_lossy()],
+        )?;
+    }
+
+    check(&dir)?;
+    styled_println!(
+        "\x1b[32mvoo>\x1b[0m the team config in {} is up to date",
+        dir.display()
+    );
+
+    Ok(())
+}
+
+// Commits the changes made in the checkout and pushes them to the team.
+pub fn push(message: Option<&str>) -> anyhow::Result<()> {
+    let dir = team_dir();
+    if !dir.join(".git").exists() {
+        return Err(anyhow::anyhow!(
+            "There is no team config yet, run `voo sync pull` first"
+        ));
+    }
+
+    // A broken file would break voo for everyone who pulls it.
+    check(&dir)?;
+
+    git(&dir, &["add", "-A"])?;
+    let changed = !Command::new("git")
+        .current_dir(&dir)
+        .args(["diff", "--cached", "--quiet"])
+        .status()
+        .map_err(|e| anyhow::anyhow!("Error running git: {}", e))?
+        .success();
+    if changed {
+        git(&dir, &["commit", "-m", message.unwrap_or(DEFAULT_MESSAGE)])?;
+    }
+    git(&dir, &["push"])?;
+
+    styled_println!(
+        "\x1b[32mvoo>\x1b[0m pushed the team config from {}",
+        dir.display()
+    );
+
+    Ok(())
+}
+
+fn check(dir: &Path) -> anyhow::Result<()> {
+    let path = dir.join(PROJECT_CONFIG);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(anyhow::anyhow!("Error reading {}: {}", path.display(), e)),
+    };
+
+    Config::parse(&content)
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", path.display(), e))
+}
+
+// Runs git with the terminal attached, so it can ask for credentials.
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let status = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Error running git: {}", e))?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("git {} failed: {}", args[0], status));
+    }
+    Ok(())
+}
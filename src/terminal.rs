@@ -0,0 +1,142 @@
+use std::{
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use domain::models::events::{AgentEvent, AgentObserver};
+use models::processors::strip_ansi;
+
+// Set when stdin or stdout is not a terminal, or NO_COLOR is set, so piped
+// output carries no colour codes.
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::SeqCst);
+}
+
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::SeqCst)
+}
+
+// Set while the TUI owns the screen, logs written to the terminal then would
+// draw over it and are dropped.
+static FULL_SCREEN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_full_screen(full_screen: bool) {
+    FULL_SCREEN.store(full_screen, Ordering::SeqCst);
+}
+
+pub fn full_screen() -> bool {
+    FULL_SCREEN.load(Ordering::SeqCst)
+}
+
+pub fn style(text: &str) -> String {
+    match plain() {
+        true => strip_ansi(text),
+        false => text.to_string(),
+    }
+}
+
+// println!, print! and eprintln! that drop the colour codes in plain mode.
+macro_rules! styled_println {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::terminal::style(&format!($($arg)*)))
+    };
+}
+
+macro_rules! styled_print {
+    ($($arg:tt)*) => {
+        print!("{}", $crate::terminal::style(&format!($($arg)*)))
+    };
+}
+
+macro_rules! styled_eprintln {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::terminal::style(&format!($($arg)*)))
+    };
+}
+
+pub(crate) use {styled_eprintln, styled_println};
+
+// Writes the logs to stderr without colour codes in plain mode, including the
+// ones in the messages themselves.
+pub struct PlainStderr;
+
+impl Write for PlainStderr {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if full_screen() {
+            return Ok(buf.len());
+        }
+        let text = strip_ansi(&String::from_utf8_lossy(buf));
+        std::io::stderr().write_all(text.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+// Writes the logs to stdout as they are.
+pub struct ScreenLog;
+
+impl Write for ScreenLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match full_screen() {
+            true => Ok(buf.len()),
+            false => std::io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+// Renders the streamed answer of a chat: the reasoning dimmed and the answer
+// after a voo> prompt, ending its line once the response is over.
+#[derive(Debug, Default)]
+pub struct AnswerPrinter {
+    answering: AtomicBool,
+}
+
+impl AgentObserver for AnswerPrinter {
+    fn on_event(&self, event: &AgentEvent) {
+        match event {
+            AgentEvent::ModelChunk {
+                text,
+                thought: true,
+            } => styled_println!("\x1b[2mthinking> {}\x1b[0m", text.trim()),
+            AgentEvent::ModelChunk {
+                text,
+                thought: false,
+            } => {
+                if !self.answering.swap(true, Ordering::SeqCst) {
+                    styled_print!("\x1b[32mvoo>\x1b[0m ");
+                }
+                print!("{}", text);
+                _ = std::io::stdout().flush();
+            }
+            AgentEvent::ToolCallStarted { .. }
+            | AgentEvent::Error { .. }
+            | AgentEvent::TurnComplete { .. }
+                if self.answering.swap(false, Ordering::SeqCst) =>
+            {
+                println!();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn prompt(message: &str) -> anyhow::Result<String> {
+    print!("{}", style(message));
+    std::io::stdout().flush()?;
+
+    let mut input = String::new();
+    if std::io::stdin().read_line(&mut input)? == 0 {
+        return Ok("q".to_string());
+    }
+
+    Ok(input.trim().to_lowercase())
+}
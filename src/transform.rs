@@ -0,0 +1,121 @@
+use std::io::{Read, Write};
+
+use domain::models::agent::AgentClient;
+
+use crate::providers;
+
+// Rewrites stdin as instructed in a single request without tools and prints
+// the result, so editors can use it as a filter command, e.g.
+// `:%!voo transform --instruction "convert to async"` in Vim.
+pub async fn run(instruction: &str) -> anyhow::Result<()> {
+    if instruction.trim().is_empty() {
+        return Err(anyhow::anyhow!("The instruction must not be empty"));
+    }
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .map_err(|e| anyhow::anyhow!("Error reading stdin: {}", e))?;
+
+    let client = providers::registry().build(&providers::selected())?;
+    let answer = client
+        .ask(&prompt(instruction, None, &content))
+        .await
+        .map_err(|e| anyhow::anyhow!("Error transforming the input: {}", e))?
+        .iter()
+        .flat_map(|response| &response.parts)
+        .filter_map(|part| part.answer().cloned())
+        .collect::<String>();
+    let output = rewritten(&content, &answer)
+        .ok_or_else(|| anyhow::anyhow!("The answer contained no transformed text"))?;
+
+    let mut stdout = std::io::stdout();
+    stdout.write_all(output.as_bytes())?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+// Asks for the whole edited text in a code block. With a path, the text is an
+// editor buffer that can differ from the file on disk.
+pub fn prompt(instruction: &str, path: Option<&str>, content: &str) -> String {
+    let text = match path {
+        Some(path) => format!(
+            "the buffer of {} below as instructed. It may have unsaved changes, so work on this text rather than the file on disk.",
+            path
+        ),
+        None => "the text below as instructed.".to_string(),
+    };
+    format!(
+        "Edit {} Answer with the complete edited text in a single code block and nothing else.\n\nInstruction: {}\n\n```\n{}\n```",
+        text,
+        instruction,
+        content.trim_end_matches(['\r', '\n'])
+    )
+}
+
+// The edited text of an answer to `prompt`, ending in a newline when the
+// original did. Text with \r\n line endings keeps them, however the model
+// wrote its answer.
+pub fn rewritten(content: &str, answer: &str) -> Option<String> {
+    let mut text = code_block(answer)?;
+    if content.ends_with('\n') && !text.is_empty() {
+        text.push('\n');
+    }
+    if content.contains("\r\n") {
+        text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+    Some(text)
+}
+
+// The text between the first and the last fence of an answer, so fences
+// inside the text, e.g. of a markdown file, survive.
+pub fn code_block(answer: &str) -> Option<String> {
+    // Split on \n alone, a \r belongs to the text.
+    let lines = answer.split('\n').collect::<Vec<&str>>();
+    let is_fence = |line: &&str| line.trim_start().starts_with("```");
+    let first = lines.iter().position(is_fence)?;
+    let last = lines.iter().rposition(is_fence)?;
+    if first == last {
+        return None;
+    }
+
+    let text = lines[first + 1..last].join("\n");
+    Some(text.strip_suffix('\r').map(str::to_string).unwrap_or(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_block() {
+        assert_eq!(
+            code_block("Here it is:\n```rust\nfn main() {}\n```\n").as_deref(),
+            Some("fn main() {}")
+        );
+        assert_eq!(
+            code_block("```markdown\n# Usage\n```bash\nvoo\n```\n```").as_deref(),
+            Some("# Usage\n```bash\nvoo\n```")
+        );
+        assert_eq!(code_block("```\n```").as_deref(), Some(""));
+        assert_eq!(code_block("no code here"), None);
+        assert_eq!(code_block("```rust\nfn main() {}"), None);
+
+        assert_eq!(
+            rewritten("fn main() {}\n", "```rust\nasync fn main() {}\n```").as_deref(),
+            Some("async fn main() {}\n")
+        );
+        assert_eq!(
+            rewritten("fn main() {}", "```rust\nasync fn main() {}\n```").as_deref(),
+            Some("async fn main() {}")
+        );
+        assert_eq!(
+            code_block("```\r\nlet a = 1;\r\nlet b = 2;\r\n```\r\n").as_deref(),
+            Some("let a = 1;\r\nlet b = 2;")
+        );
+        assert_eq!(
+            rewritten("let a = 1;\r\n", "```\nlet a = 2;\nlet b = 3;\n```").as_deref(),
+            Some("let a = 2;\r\nlet b = 3;\r\n")
+        );
+    }
+}
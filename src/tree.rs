@@ -0,0 +1,143 @@
+use std::{collections::BTreeMap, path::Path};
+
+use ignore::WalkBuilder;
+
+pub const DEFAULT_DEPTH: usize = 3;
+
+#[derive(Debug, Default)]
+struct Node {
+    size: u64,
+    dir: bool,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn insert(&mut self, components: &[String], size: u64, dir: bool) {
+        self.size += size;
+
+        let Some((name, rest)) = components.split_first() else {
+            return;
+        };
+        let child = self.children.entry(name.clone()).or_default();
+        if rest.is_empty() {
+            child.dir = dir;
+            child.size += size;
+        } else {
+            child.dir = true;
+            child.insert(rest, size, dir);
+        }
+    }
+}
+
+// Renders the files under `root` honouring .gitignore, with directory sizes
+// covering everything below them, not only the rendered levels.
+pub fn render(root: &Path, depth: usize) -> anyhow::Result<String> {
+    if !root.is_dir() {
+        return Err(anyhow::anyhow!("{} is not a directory", root.display()));
+    }
+
+    let mut tree = Node {
+        dir: true,
+        ..Default::default()
+    };
+    for entry in WalkBuilder::new(root)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+    {
+        let entry =
+            entry.map_err(|e| anyhow::anyhow!("Error walking {}: {}", root.display(), e))?;
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+
+        let dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+        let size = match dir {
+            true => 0,
+            false => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+        };
+        let components = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<String>>();
+        tree.insert(&components, size, dir);
+    }
+
+    let mut out = format!("{} ({})\n", root.display(), human_size(tree.size));
+    render_children(&tree, "", depth, &mut out);
+    Ok(out)
+}
+
+fn render_children(node: &Node, prefix: &str, depth: usize, out: &mut String) {
+    if depth == 0 {
+        return;
+    }
+
+    let mut children = node.children.iter().collect::<Vec<(&String, &Node)>>();
+    children.sort_by_key(|(_, child)| !child.dir);
+
+    for (i, (name, child)) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let branch = if last { "└── " } else { "├── " };
+        let slash = if child.dir { "/" } else { "" };
+        out.push_str(&format!(
+            "{}{}{}{} ({})\n",
+            prefix,
+            branch,
+            name,
+            slash,
+            human_size(child.size)
+        ));
+
+        if child.dir {
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            render_children(child, &prefix, depth - 1, out);
+        }
+    }
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_children() {
+        let mut tree = Node::default();
+        tree.insert(&["src".to_string(), "main.rs".to_string()], 2048, false);
+        tree.insert(
+            &["src".to_string(), "deep".to_string(), "a.rs".to_string()],
+            10,
+            false,
+        );
+        tree.insert(&["Cargo.toml".to_string()], 300, false);
+
+        let mut out = String::new();
+        render_children(&tree, "", 2, &mut out);
+        assert_eq!(
+            out,
+            "├── src/ (2.0 KB)\n│   ├── deep/ (10 B)\n│   └── main.rs (2.0 KB)\n└── Cargo.toml (300 B)\n"
+        );
+        assert_eq!(tree.size, 2358);
+    }
+}
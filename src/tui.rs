@@ -0,0 +1,639 @@
+use std::{
+    io::{IsTerminal, Stdout},
+    sync::{Arc, Once},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use domain::models::{
+    agent::{Agent, AgentError, FunctionCall},
+    events::AgentEvent,
+    mode::Mode,
+    tool_registry::DangerLevel,
+    tools::ToolOutput,
+    turn::TurnHandler,
+};
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    crossterm::{
+        cursor::Show,
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    },
+    layout::{Constraint, Layout, Position},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{auto_approve, build_agent, providers, runner::perform_function_call, terminal};
+
+const MAX_INPUT_LINES: usize = 6;
+const SCROLL_LINES: usize = 10;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type Screen = Terminal<CrosstermBackend<Stdout>>;
+
+// What the turns running in the background send to the screen.
+enum Message {
+    Event(AgentEvent),
+    // Hands the terminal to the approval prompts of a tool, the screen comes
+    // back on Resume.
+    Suspend(oneshot::Sender<()>),
+    Resume,
+    Done,
+}
+
+// Runs the tool calls of a turn. Calls that may ask for approval leave the
+// screen for the time they run, so their prompts read from a normal terminal.
+struct TuiTurn {
+    messages: mpsc::UnboundedSender<Message>,
+}
+
+#[async_trait]
+impl TurnHandler for TuiTurn {
+    async fn execute(
+        &self,
+        agent: &Agent,
+        calls: &[FunctionCall],
+    ) -> Result<Vec<ToolOutput>, AgentError> {
+        let context = agent.tool_context().await;
+        let asks = !auto_approve() && {
+            let tools = agent.tools();
+            let tools = tools.lock().await;
+            calls.iter().any(|call| {
+                tools
+                    .get(&call.name)
+                    .is_some_and(|registered| registered.metadata.danger != DangerLevel::Safe)
+            })
+        };
+        if !asks {
+            return perform_function_call(agent, calls, &context, false)
+                .await
+                .map_err(|e| AgentError::AgentError(Some(e.to_string())));
+        }
+
+        let (ready, suspended) = oneshot::channel();
+        _ = self.messages.send(Message::Suspend(ready));
+        _ = suspended.await;
+        let outputs = perform_function_call(agent, calls, &context, true).await;
+        _ = self.messages.send(Message::Resume);
+
+        outputs.map_err(|e| AgentError::AgentError(Some(e.to_string())))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    User,
+    Assistant,
+    Thought,
+    Tool,
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+    role: Role,
+    text: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct Activity {
+    name: String,
+    args: String,
+    // None while the call runs, then whether it succeeded.
+    succeeded: Option<bool>,
+}
+
+// A multi-line text box, the cursor is a byte offset into the text.
+#[derive(Debug, Default)]
+struct Input {
+    text: String,
+    cursor: usize,
+}
+
+impl Input {
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    fn backspace(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.text.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    fn left(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+        }
+    }
+
+    fn right(&mut self) {
+        if let Some(c) = self.text[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    fn home(&mut self) {
+        self.cursor = self.text[..self.cursor]
+            .rfind('\n')
+            .map(|newline| newline + 1)
+            .unwrap_or(0);
+    }
+
+    fn end(&mut self) {
+        self.cursor += self.text[self.cursor..]
+            .find('\n')
+            .unwrap_or(self.text.len() - self.cursor);
+    }
+
+    // Moves to the same column of another line, or its end when shorter.
+    fn vertical(&mut self, down: bool) {
+        let (row, column) = self.position();
+        let row = match down {
+            true => row + 1,
+            false if row == 0 => return,
+            false => row - 1,
+        };
+        let Some(line) = self.text.split('\n').nth(row) else {
+            return;
+        };
+
+        let start = self
+            .text
+            .split('\n')
+            .take(row)
+            .map(|line| line.len() + 1)
+            .sum::<usize>();
+        let offset = line
+            .char_indices()
+            .nth(column)
+            .map(|(offset, _)| offset)
+            .unwrap_or(line.len());
+        self.cursor = start + offset;
+    }
+
+    // Row and column of the cursor, in characters.
+    fn position(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let row = before.matches('\n').count();
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count();
+
+        (row, column)
+    }
+
+    fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+}
+
+enum Action {
+    None,
+    Quit,
+    Submit(String),
+    SwitchMode(Mode),
+}
+
+struct App {
+    entries: Vec<Entry>,
+    activity: Vec<Activity>,
+    input: Input,
+    mode: Mode,
+    provider: String,
+    busy: bool,
+    // Lines scrolled up from the end of the conversation, 0 follows it.
+    scroll: usize,
+}
+
+impl App {
+    fn new(mode: Mode, provider: String) -> Self {
+        Self {
+            entries: vec![],
+            activity: vec![],
+            input: Input::default(),
+            mode,
+            provider,
+            busy: false,
+            scroll: 0,
+        }
+    }
+
+    fn apply(&mut self, event: AgentEvent) {
+        match event {
+            AgentEvent::UserMessage { text } => self.push(Role::User, text),
+            AgentEvent::ModelChunk { text, thought } => {
+                let role = match thought {
+                    true => Role::Thought,
+                    false => Role::Assistant,
+                };
+                match self.entries.last_mut() {
+                    Some(entry) if entry.role == role => entry.text.push_str(&text),
+                    _ => self.push(role, text),
+                }
+            }
+            AgentEvent::ToolCallStarted { name, args } => {
+                self.push(Role::Tool, format!("{} {}", name, args));
+                self.activity.push(Activity {
+                    name,
+                    args: args.to_string(),
+                    succeeded: None,
+                });
+            }
+            AgentEvent::ToolCallFinished { name, output } => {
+                if let Some(activity) = self
+                    .activity
+                    .iter_mut()
+                    .rev()
+                    .find(|activity| activity.name == name && activity.succeeded.is_none())
+                {
+                    activity.succeeded = Some(!matches!(output, ToolOutput::Error(_)));
+                }
+            }
+            AgentEvent::Error { message } => self.push(Role::Error, message),
+            AgentEvent::TurnComplete { .. } => {}
+        }
+    }
+
+    fn push(&mut self, role: Role, text: String) {
+        self.entries.push(Entry { role, text });
+    }
+
+    fn key(&mut self, key: KeyEvent) -> Action {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+        match key.code {
+            KeyCode::Char('c' | 'd') if control => return Action::Quit,
+            KeyCode::Char('j') if control => self.input.insert('\n'),
+            KeyCode::Enter if alt || shift => self.input.insert('\n'),
+            KeyCode::Enter if !self.busy && !self.input.text.trim().is_empty() => {
+                self.scroll = 0;
+                return Action::Submit(self.input.take());
+            }
+            KeyCode::Tab | KeyCode::BackTab if !self.busy => {
+                let index = Mode::ALL
+                    .iter()
+                    .position(|mode| *mode == self.mode)
+                    .unwrap_or_default();
+                let next = match key.code {
+                    KeyCode::BackTab => index + Mode::ALL.len() - 1,
+                    _ => index + 1,
+                };
+                return Action::SwitchMode(Mode::ALL[next % Mode::ALL.len()]);
+            }
+            KeyCode::PageUp => self.scroll += SCROLL_LINES,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(SCROLL_LINES),
+            KeyCode::Char(c) if !control => self.input.insert(c),
+            KeyCode::Backspace => self.input.backspace(),
+            KeyCode::Delete => self.input.delete(),
+            KeyCode::Left => self.input.left(),
+            KeyCode::Right => self.input.right(),
+            KeyCode::Up => self.input.vertical(false),
+            KeyCode::Down => self.input.vertical(true),
+            KeyCode::Home => self.input.home(),
+            KeyCode::End => self.input.end(),
+            _ => {}
+        }
+
+        Action::None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let input_lines = self.input.text.split('\n').count().min(MAX_INPUT_LINES);
+        let [main, input, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(input_lines as u16 + 2),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [conversation, sidebar] =
+            Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .areas(main);
+
+        let lines = self
+            .entries
+            .iter()
+            .flat_map(|entry| {
+                let (prefix, style) = match entry.role {
+                    Role::User => ("you> ", Style::new().fg(Color::Cyan)),
+                    Role::Assistant => ("voo> ", Style::new().fg(Color::Green)),
+                    Role::Thought => ("thinking> ", Style::new().add_modifier(Modifier::DIM)),
+                    Role::Tool => ("tool> ", Style::new().fg(Color::Yellow)),
+                    Role::Error => ("error> ", Style::new().fg(Color::Red)),
+                };
+                let mut lines = entry
+                    .text
+                    .trim_end()
+                    .split('\n')
+                    .enumerate()
+                    .map(|(index, text)| {
+                        let text = Span::raw(text.to_string());
+                        match (index, entry.role) {
+                            (0, _) => Line::from(vec![Span::styled(prefix, style), text]),
+                            (_, Role::Thought) => Line::from(text).style(style),
+                            _ => Line::from(text),
+                        }
+                    })
+                    .collect::<Vec<Line>>();
+                lines.push(Line::default());
+                lines
+            })
+            .collect::<Vec<Line>>();
+
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::new().borders(Borders::ALL).title(" conversation "));
+        let height = conversation.height.saturating_sub(2) as usize;
+        let hidden = paragraph
+            .line_count(conversation.width.saturating_sub(2))
+            .saturating_sub(height);
+        self.scroll = self.scroll.min(hidden);
+        let offset = (hidden - self.scroll) as u16;
+        frame.render_widget(paragraph.scroll((offset, 0)), conversation);
+
+        let visible = sidebar.height.saturating_sub(2) as usize;
+        let items = self
+            .activity
+            .iter()
+            .skip(self.activity.len().saturating_sub(visible))
+            .map(|activity| {
+                let (mark, color) = match activity.succeeded {
+                    None => ("… ", Color::Yellow),
+                    Some(true) => ("✓ ", Color::Green),
+                    Some(false) => ("✗ ", Color::Red),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(mark, Style::new().fg(color)),
+                    Span::raw(activity.name.clone()),
+                    Span::styled(
+                        format!(" {}", activity.args),
+                        Style::new().add_modifier(Modifier::DIM),
+                    ),
+                ]))
+            })
+            .collect::<Vec<ListItem>>();
+        frame.render_widget(
+            List::new(items).block(Block::new().borders(Borders::ALL).title(" tools ")),
+            sidebar,
+        );
+
+        let title = match self.busy {
+            true => " waiting for the model ",
+            false => " message, enter sends, alt+enter adds a line ",
+        };
+        let (row, column) = self.input.position();
+        let first = row.saturating_sub(MAX_INPUT_LINES - 1);
+        frame.render_widget(
+            Paragraph::new(self.input.text.as_str())
+                .scroll((first as u16, 0))
+                .block(Block::new().borders(Borders::ALL).title(title)),
+            input,
+        );
+        frame.set_cursor_position(Position::new(
+            input.x + 1 + column as u16,
+            input.y + 1 + (row - first) as u16,
+        ));
+
+        frame.render_widget(
+            Paragraph::new(format!(
+                " {} mode · {} · tab switches mode · pgup/pgdn scroll · ctrl-c quits",
+                self.mode, self.provider
+            ))
+            .style(Style::new().add_modifier(Modifier::DIM)),
+            status,
+        );
+    }
+}
+
+// A full-screen chat that renders the events of the agent: the conversation,
+// the tool calls in a sidebar and a multi-line input box.
+pub async fn run() -> anyhow::Result<()> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!("voo tui needs a terminal"));
+    }
+
+    let agent = Arc::new(build_agent().await?);
+    let (sender, mut messages) = mpsc::unbounded_channel();
+    let observer = sender.clone();
+    agent.observe(move |event: &AgentEvent| {
+        _ = observer.send(Message::Event(event.clone()));
+    });
+
+    let mut app = App::new(agent.mode().await, providers::selected());
+    let mut screen = enter()?;
+    let result = event_loop(&mut screen, &agent, &mut app, &sender, &mut messages).await;
+    leave(&mut screen)?;
+
+    result
+}
+
+async fn event_loop(
+    screen: &mut Screen,
+    agent: &Arc<Agent>,
+    app: &mut App,
+    sender: &mpsc::UnboundedSender<Message>,
+    messages: &mut mpsc::UnboundedReceiver<Message>,
+) -> anyhow::Result<()> {
+    loop {
+        while let Ok(message) = messages.try_recv() {
+            match message {
+                Message::Event(event) => app.apply(event),
+                Message::Suspend(ready) => {
+                    leave(screen)?;
+                    _ = ready.send(());
+                    while let Some(message) = messages.recv().await {
+                        match message {
+                            Message::Event(event) => app.apply(event),
+                            Message::Done => app.busy = false,
+                            Message::Resume => break,
+                            Message::Suspend(_) => {}
+                        }
+                    }
+                    *screen = enter()?;
+                }
+                Message::Resume => {}
+                Message::Done => app.busy = false,
+            }
+        }
+        screen.draw(|frame| app.draw(frame))?;
+
+        if !tokio::task::block_in_place(|| event::poll(POLL_INTERVAL))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match app.key(key) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Submit(input) => {
+                app.busy = true;
+                let agent = agent.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let handler = TuiTurn {
+                        messages: sender.clone(),
+                    };
+                    // Errors reach the screen as events.
+                    _ = agent.run_turn_with(&input, &handler).await;
+                    _ = sender.send(Message::Done);
+                });
+            }
+            Action::SwitchMode(mode) => match agent.set_mode(mode).await {
+                Ok(()) => app.mode = mode,
+                Err(e) => app.push(
+                    Role::Error,
+                    format!("Error switching to {} mode: {}", mode, e),
+                ),
+            },
+        }
+    }
+}
+
+fn enter() -> anyhow::Result<Screen> {
+    restore_on_panic();
+    terminal::set_full_screen(true);
+    enable_raw_mode()?;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+
+    Ok(Terminal::new(CrosstermBackend::new(std::io::stdout()))?)
+}
+
+fn leave(screen: &mut Screen) -> anyhow::Result<()> {
+    disable_raw_mode()?;
+    execute!(screen.backend_mut(), LeaveAlternateScreen)?;
+    screen.show_cursor()?;
+    terminal::set_full_screen(false);
+
+    Ok(())
+}
+
+// Hands the terminal back before the panic is printed, which would otherwise
+// land on the alternate screen of a terminal left in raw mode.
+fn restore_on_panic() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if terminal::full_screen() {
+                terminal::set_full_screen(false);
+                _ = disable_raw_mode();
+                _ = execute!(std::io::stdout(), LeaveAlternateScreen, Show);
+            }
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_input() {
+        let mut input = Input::default();
+        for c in "héllo\nwo".chars() {
+            input.insert(c);
+        }
+        assert_eq!(input.position(), (1, 2));
+
+        input.vertical(false);
+        assert_eq!(input.position(), (0, 2));
+        input.backspace();
+        assert_eq!(input.text, "hllo\nwo");
+        input.end();
+        input.insert('!');
+        input.vertical(true);
+        assert_eq!(input.position(), (1, 2));
+        input.home();
+        input.delete();
+        assert_eq!(input.take(), "hllo!\no");
+        assert_eq!(input.position(), (0, 0));
+    }
+
+    #[test]
+    fn test_apply_events() {
+        let mut app = App::new(Mode::Code, "gemini".to_string());
+        app.apply(AgentEvent::UserMessage {
+            text: "hi".to_string(),
+        });
+        for text in ["Hel", "lo"] {
+            app.apply(AgentEvent::ModelChunk {
+                text: text.to_string(),
+                thought: false,
+            });
+        }
+        app.apply(AgentEvent::ToolCallStarted {
+            name: "read_file".to_string(),
+            args: json!({ "path": "a.rs" }),
+        });
+        app.apply(AgentEvent::ToolCallFinished {
+            name: "read_file".to_string(),
+            output: ToolOutput::error("missing"),
+        });
+        app.apply(AgentEvent::ModelChunk {
+            text: "Done".to_string(),
+            thought: false,
+        });
+
+        let roles = app
+            .entries
+            .iter()
+            .map(|entry| entry.role)
+            .collect::<Vec<Role>>();
+        assert_eq!(
+            roles,
+            vec![Role::User, Role::Assistant, Role::Tool, Role::Assistant]
+        );
+        assert_eq!(app.entries[1].text, "Hello");
+        assert_eq!(
+            app.activity,
+            vec![Activity {
+                name: "read_file".to_string(),
+                args: "{\"path\":\"a.rs\"}".to_string(),
+                succeeded: Some(false),
+            }]
+        );
+
+        let mut screen = Terminal::new(TestBackend::new(60, 12)).unwrap();
+        screen.draw(|frame| app.draw(frame)).unwrap();
+        let rendered = screen
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+        // The conversation follows its end.
+        assert!(rendered.contains("voo> Done"));
+        assert!(!rendered.contains("you> hi"));
+        assert!(rendered.contains("✗ read_file"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::{sync::Arc, time::Duration};
+
+use hmac::{Hmac, Mac};
+use models::tools::web_fetch::{PublicResolver, public_url};
+use reqwest::redirect;
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::error;
+
+use crate::policy;
+
+const SUMMARY_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webhook {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunCompleted {
+    pub event: &'static str,
+    pub job_id: String,
+    // The named session the run continued, none for a one-off prompt.
+    pub session_id: Option<String>,
+    pub status: String,
+    pub summary: Option<String>,
+    pub error: Option<String>,
+    pub usage: RunUsage,
+    pub completed_at: i64,
+}
+
+// What the run used in total, the cost in USD.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RunUsage {
+    pub tokens: i64,
+    pub cost: f64,
+}
+
+impl RunCompleted {
+    pub fn new(
+        job_id: &str,
+        session_id: Option<&str>,
+        outcome: Result<&str, &str>,
+        usage: RunUsage,
+        completed_at: i64,
+    ) -> Self {
+        let (status, summary, error) = match outcome {
+            Ok(result) => ("succeeded", Some(summarize(result)), None),
+            Err(error) => ("failed", None, Some(error.to_string())),
+        };
+
+        Self {
+            event: "run.completed",
+            job_id: job_id.to_string(),
+            session_id: session_id.map(str::to_string),
+            status: status.to_string(),
+            summary,
+            error,
+            usage,
+            completed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WebhookNotifier {
+    webhooks: Vec<Webhook>,
+    default_secret: Option<String>,
+    http: reqwest::Client,
+    // For callback URLs, which come from API clients: only public addresses,
+    // no proxies and no redirects.
+    callback_http: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn from_env() -> Self {
+        let default_secret = std::env::var("VOO_WEBHOOK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let webhooks = std::env::var("VOO_WEBHOOK_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| Webhook {
+                url: url.to_string(),
+                secret: default_secret.clone(),
+            })
+            .collect();
+
+        Self {
+            webhooks,
+            default_secret,
+            http: reqwest::Client::new(),
+            callback_http: reqwest::Client::builder()
+                .no_proxy()
+                .redirect(redirect::Policy::none())
+                .dns_resolver(Arc::new(PublicResolver))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub async fn notify(&self, event: &RunCompleted, callback_url: Option<&str>) {
+        let callback = callback_url
+            .filter(|url| match check_callback(url) {
+                Ok(()) => true,
+                Err(e) => {
+                    error!("[VOO] webhook for {} not sent: {}", event.job_id, e);
+                    false
+                }
+            })
+            .map(|url| Webhook {
+                url: url.to_string(),
+                secret: self.default_secret.clone(),
+            });
+
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("[VOO] failed to serialize webhook payload: {}", e);
+                return;
+            }
+        };
+
+        let webhooks = self.webhooks.iter().map(|webhook| (&self.http, webhook));
+        let callback = callback
+            .iter()
+            .map(|webhook| (&self.callback_http, webhook));
+        for (http, webhook) in webhooks.chain(callback) {
+            let mut request = http
+                .post(&webhook.url)
+                .header("content-type", "application/json")
+                .header("x-voo-event", event.event)
+                .timeout(Duration::from_secs(10))
+                .body(body.clone());
+
+            if let Some(secret) = &webhook.secret {
+                request = request.header("x-voo-signature", sign(secret, &body));
+            }
+
+            let response = request
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            if let Err(e) = response {
+                error!(
+                    "[VOO] webhook for {} to {} failed: {}",
+                    event.job_id, webhook.url, e
+                );
+            }
+        }
+    }
+}
+
+// A callback URL of a job must be a public http(s) address, so the server
+// can't be made to post into its own network, and one of the `webhook`
+// endpoints of the policy.
+pub fn check_callback(url: &str) -> anyhow::Result<()> {
+    public_url(url).map_err(|e| anyhow::anyhow!("callback_url {} is refused: {}", url, e))?;
+    policy::get().check_endpoint("webhook", url)
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn summarize(result: &str) -> String {
+    if result.chars().count() <= SUMMARY_LIMIT {
+        return result.to_string();
+    }
+
+    let summary = result.chars().take(SUMMARY_LIMIT).collect::<String>();
+    format!("{}...", summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("key", b"The quick brown fox jumps over the lazy dog");
+        assert_eq!(
+            signature,
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_run_completed_payload() {
+        let usage = RunUsage {
+            tokens: 1200,
+            cost: 0.5,
+        };
+        let event = RunCompleted::new("job-1", Some("review"), Ok("done"), usage, 42);
+        let payload = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(payload["job_id"], "job-1");
+        assert_eq!(payload["session_id"], "review");
+        assert_eq!(payload["status"], "succeeded");
+        assert_eq!(payload["summary"], "done");
+        assert_eq!(payload["usage"]["tokens"], 1200);
+        assert_eq!(payload["usage"]["cost"], 0.5);
+
+        let event = RunCompleted::new("job-2", None, Err("boom"), RunUsage::default(), 42);
+        let payload = serde_json::to_value(&event).unwrap();
+        assert!(payload["session_id"].is_null());
+        assert_eq!(payload["error"], "boom");
+    }
+
+    #[test]
+    fn test_check_callback() {
+        assert!(check_callback("https://ci.example.com/hooks/voo").is_ok());
+        for url in [
+            "http://localhost:8080/hook",
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::ffff:10.0.0.1]/hook",
+            "file:///etc/passwd",
+            "not a url",
+        ] {
+            assert!(check_callback(url).is_err(), "{}", url);
+        }
+    }
+}